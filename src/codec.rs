@@ -18,6 +18,7 @@ use crate::{
 };
 use std::fmt::{Debug, Display};
 use tremor_script::Value;
+pub(crate) mod avro;
 pub(crate) mod binary;
 pub(crate) mod binflux;
 pub(crate) mod csv;
@@ -116,7 +117,8 @@ pub fn resolve(config: &config::Codec) -> Result<Box<dyn Codec>> {
         "yaml" => Ok(Box::new(yaml::Yaml {})),
         "binary" => Ok(Box::new(binary::Binary {})),
         "syslog" => Ok(Box::new(syslog::Syslog::utcnow())),
-        "csv" => Ok(Box::new(csv::Csv {})),
+        "csv" => Ok(Box::new(csv::Csv::from_config(config.config.as_ref())?)),
+        "avro" => Ok(Box::new(avro::Avro::from_config(config.config.as_ref())?)),
         s => Err(ErrorKind::CodecNotFound(s.into()).into()),
     }
 }
@@ -0,0 +1,880 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! # Avro codec
+//!
+//! Decodes and encodes single Avro datums in the binary encoding, given a static schema.
+//!
+//! * records and maps are decoded into objects
+//! * arrays are decoded into arrays
+//! * enums are decoded into their symbol
+//! * bytes and fixed are decoded into bytes
+//! * unions are decoded into the value of the selected branch, when encoding the first
+//!   branch matching the value is picked
+//!
+//! Logical types are decoded as their underlying type.
+//!
+//! ## Configuration
+//!
+//! * `schema` - the Avro schema, inline
+//! * `schema_file` - path to a file containing the Avro schema as JSON, instead of `schema`
+//! * `confluent_schema_id` - if set, datums are framed in the Confluent wire format:
+//!   a zero magic byte followed by the schema id as 4 byte big endian integer.
+//!   When decoding, the schema id must match.
+//!
+//! Decoding fails for datums with more than 1048576 array and map items in total, or nested
+//! deeper than 128 schemas.
+
+use crate::codec::prelude::*;
+use beef::Cow;
+use std::collections::HashMap;
+
+const CONFLUENT_MAGIC_BYTE: u8 = 0;
+const CONFIG_KEYS: [&str; 3] = ["schema", "schema_file", "confluent_schema_id"];
+/// upper bound of the array and map items of a single datum
+const MAX_ITEMS: usize = 1 << 20;
+/// upper bound of the nesting of schemas while decoding a single datum, recursive named types
+/// would otherwise recurse without end
+const MAX_DEPTH: usize = 128;
+
+fn invalid_config(msg: impl Into<String>) -> Error {
+    ErrorKind::InvalidConfiguration("avro codec".to_string(), msg.into()).into()
+}
+
+fn invalid(msg: impl Into<String>) -> Error {
+    ErrorKind::InvalidAvro(msg.into()).into()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Field {
+    name: String,
+    schema: Schema,
+    default: Option<Value<'static>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Schema {
+    Null,
+    Boolean,
+    Int,
+    Long,
+    Float,
+    Double,
+    Bytes,
+    String,
+    Record {
+        fields: Vec<Field>,
+    },
+    Enum {
+        symbols: Vec<String>,
+    },
+    Array(Box<Schema>),
+    Map(Box<Schema>),
+    Union(Vec<Schema>),
+    Fixed {
+        size: usize,
+    },
+    /// reference to a named type by its full name
+    Named(String),
+}
+
+/// A parsed schema together with all the named types it defines
+#[derive(Debug, Clone, PartialEq)]
+struct AvroSchema {
+    root: Schema,
+    names: HashMap<String, Schema>,
+}
+
+fn fullname(name: &str, namespace: Option<&str>) -> String {
+    match namespace {
+        Some(ns) if !ns.is_empty() && !name.contains('.') => format!("{ns}.{name}"),
+        _ => name.to_string(),
+    }
+}
+
+impl AvroSchema {
+    fn parse(schema: &Value) -> Result<Self> {
+        let mut names = HashMap::new();
+        let root = Self::parse_schema(schema, None, &mut names)?;
+        Ok(Self { root, names })
+    }
+
+    fn parse_schema(
+        schema: &Value,
+        namespace: Option<&str>,
+        names: &mut HashMap<String, Schema>,
+    ) -> Result<Schema> {
+        if let Some(name) = schema.as_str() {
+            return Self::parse_type_name(name, namespace, names);
+        }
+        if let Some(branches) = schema.as_array() {
+            return branches
+                .iter()
+                .map(|s| Self::parse_schema(s, namespace, names))
+                .collect::<Result<_>>()
+                .map(Schema::Union);
+        }
+        let t = schema
+            .get_str("type")
+            .ok_or_else(|| invalid_config(format!("Invalid schema: {schema}")))?;
+        match t {
+            "record" | "error" | "enum" | "fixed" => {
+                let name = schema
+                    .get_str("name")
+                    .ok_or_else(|| invalid_config(format!("Missing name in {t} schema")))?;
+                let namespace = schema.get_str("namespace").or(namespace);
+                let full = fullname(name, namespace);
+                let namespace = full.rsplit_once('.').map(|(ns, _)| ns.to_string());
+                let namespace = namespace.as_deref();
+                if names.contains_key(&full) {
+                    return Err(invalid_config(format!("Duplicate type {full}")));
+                }
+                // register the name first, so records can refer to themselves
+                names.insert(full.clone(), Schema::Null);
+                let named = match t {
+                    "enum" => Schema::Enum {
+                        symbols: schema
+                            .get_array("symbols")
+                            .ok_or_else(|| invalid_config("Missing symbols in enum schema"))?
+                            .iter()
+                            .map(|s| {
+                                s.as_str()
+                                    .map(ToString::to_string)
+                                    .ok_or_else(|| invalid_config("Invalid enum symbol"))
+                            })
+                            .collect::<Result<_>>()?,
+                    },
+                    "fixed" => Schema::Fixed {
+                        size: schema
+                            .get_u64("size")
+                            .and_then(|size| usize::try_from(size).ok())
+                            .ok_or_else(|| invalid_config("Missing size in fixed schema"))?,
+                    },
+                    _ => Schema::Record {
+                        fields: schema
+                            .get_array("fields")
+                            .ok_or_else(|| invalid_config("Missing fields in record schema"))?
+                            .iter()
+                            .map(|f| Self::parse_field(f, namespace, names))
+                            .collect::<Result<_>>()?,
+                    },
+                };
+                names.insert(full.clone(), named);
+                Ok(Schema::Named(full))
+            }
+            "array" => Ok(Schema::Array(Box::new(Self::parse_schema(
+                schema
+                    .get("items")
+                    .ok_or_else(|| invalid_config("Missing items in array schema"))?,
+                namespace,
+                names,
+            )?))),
+            "map" => Ok(Schema::Map(Box::new(Self::parse_schema(
+                schema
+                    .get("values")
+                    .ok_or_else(|| invalid_config("Missing values in map schema"))?,
+                namespace,
+                names,
+            )?))),
+            other => Self::parse_type_name(other, namespace, names),
+        }
+    }
+
+    fn parse_field(
+        field: &Value,
+        namespace: Option<&str>,
+        names: &mut HashMap<String, Schema>,
+    ) -> Result<Field> {
+        let name = field
+            .get_str("name")
+            .ok_or_else(|| invalid_config("Missing name in record field"))?;
+        let schema = field
+            .get("type")
+            .ok_or_else(|| invalid_config(format!("Missing type in record field {name}")))?;
+        Ok(Field {
+            name: name.to_string(),
+            schema: Self::parse_schema(schema, namespace, names)?,
+            default: field.get("default").map(Value::clone_static),
+        })
+    }
+
+    fn parse_type_name(
+        name: &str,
+        namespace: Option<&str>,
+        names: &HashMap<String, Schema>,
+    ) -> Result<Schema> {
+        Ok(match name {
+            "null" => Schema::Null,
+            "boolean" => Schema::Boolean,
+            "int" => Schema::Int,
+            "long" => Schema::Long,
+            "float" => Schema::Float,
+            "double" => Schema::Double,
+            "bytes" => Schema::Bytes,
+            "string" => Schema::String,
+            other => {
+                let full = fullname(other, namespace);
+                if names.contains_key(&full) {
+                    Schema::Named(full)
+                } else if names.contains_key(other) {
+                    Schema::Named(other.to_string())
+                } else {
+                    return Err(invalid_config(format!("Unknown type {other}")));
+                }
+            }
+        })
+    }
+
+    fn resolve<'schema>(&'schema self, schema: &'schema Schema) -> Result<&'schema Schema> {
+        if let Schema::Named(name) = schema {
+            self.names
+                .get(name)
+                .ok_or_else(|| invalid(format!("Unknown type {name}")))
+        } else {
+            Ok(schema)
+        }
+    }
+
+    /// if values of `schema` are encoded in zero bytes, like `null`
+    fn is_zero_sized(&self, schema: &Schema, visited: &mut Vec<String>) -> bool {
+        if let Schema::Named(name) = schema {
+            // a record containing itself can't be zero sized
+            if visited.contains(name) {
+                return false;
+            }
+            visited.push(name.clone());
+        }
+        match self.resolve(schema) {
+            Ok(Schema::Null) => true,
+            Ok(Schema::Fixed { size }) => *size == 0,
+            Ok(Schema::Record { fields }) => fields
+                .iter()
+                .all(|field| self.is_zero_sized(&field.schema, visited)),
+            _ => false,
+        }
+    }
+}
+
+struct Reader<'data> {
+    data: &'data [u8],
+    pos: usize,
+    /// array and map items read so far
+    items: usize,
+    /// schemas currently being decoded
+    depth: usize,
+}
+
+impl<'data> Reader<'data> {
+    fn read(&mut self, len: usize) -> Result<&'data [u8]> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .ok_or_else(|| invalid("Length overflow"))?;
+        let bytes = self
+            .data
+            .get(self.pos..end)
+            .ok_or_else(|| invalid("Unexpected end of data"))?;
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn read_byte(&mut self) -> Result<u8> {
+        Ok(self.read(1)?[0])
+    }
+
+    #[allow(clippy::cast_possible_wrap)]
+    fn read_long(&mut self) -> Result<i64> {
+        let mut value: u64 = 0;
+        let mut shift = 0;
+        loop {
+            let byte = self.read_byte()?;
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                break;
+            }
+            shift += 7;
+            if shift > 63 {
+                return Err(invalid("Variable length integer too long"));
+            }
+        }
+        // zig-zag decoding
+        Ok((value >> 1) as i64 ^ -((value & 1) as i64))
+    }
+
+    fn read_len(&mut self) -> Result<usize> {
+        let len = self.read_long()?;
+        usize::try_from(len).map_err(|_| invalid(format!("Invalid length {len}")))
+    }
+
+    /// reads the number of items of the next array or map block, 0 marks the end
+    ///
+    /// Items that aren't zero sized take at least a byte each, so there can't be more of them
+    /// than bytes left.
+    fn read_block_len(&mut self, zero_sized_items: bool) -> Result<usize> {
+        let len = self.read_long()?;
+        if len < 0 {
+            // negative counts are followed by the size of the block in bytes
+            self.read_long()?;
+        }
+        let len = usize::try_from(len.unsigned_abs())
+            .map_err(|_| invalid(format!("Invalid length {len}")))?;
+        let remaining = self.data.len() - self.pos;
+        if !zero_sized_items && len > remaining {
+            return Err(invalid(format!(
+                "Block of {len} items exceeds the remaining {remaining} bytes"
+            )));
+        }
+        self.items = self.items.saturating_add(len);
+        if self.items > MAX_ITEMS {
+            return Err(invalid(format!(
+                "More than {MAX_ITEMS} array and map items"
+            )));
+        }
+        Ok(len)
+    }
+
+    fn read_string(&mut self) -> Result<String> {
+        let len = self.read_len()?;
+        Ok(std::str::from_utf8(self.read(len)?)?.to_string())
+    }
+}
+
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn write_long(out: &mut Vec<u8>, value: i64) {
+    // zig-zag encoding
+    let mut n = ((value << 1) ^ (value >> 63)) as u64;
+    while n & !0x7f != 0 {
+        out.push((n & 0x7f | 0x80) as u8);
+        n >>= 7;
+    }
+    out.push(n as u8);
+}
+
+fn write_len(out: &mut Vec<u8>, len: usize) -> Result<()> {
+    write_long(
+        out,
+        i64::try_from(len).map_err(|_| invalid(format!("Invalid length {len}")))?,
+    );
+    Ok(())
+}
+
+impl AvroSchema {
+    fn decode(&self, schema: &Schema, reader: &mut Reader) -> Result<Value<'static>> {
+        if reader.depth >= MAX_DEPTH {
+            return Err(invalid(format!("Datum nested deeper than {MAX_DEPTH}")));
+        }
+        reader.depth += 1;
+        let value = self.decode_schema(schema, reader);
+        reader.depth -= 1;
+        value
+    }
+
+    fn decode_schema(&self, schema: &Schema, reader: &mut Reader) -> Result<Value<'static>> {
+        Ok(match self.resolve(schema)? {
+            Schema::Null => Value::const_null(),
+            Schema::Boolean => match reader.read_byte()? {
+                0 => Value::from(false),
+                1 => Value::from(true),
+                b => return Err(invalid(format!("Invalid boolean {b}"))),
+            },
+            Schema::Int | Schema::Long => Value::from(reader.read_long()?),
+            Schema::Float => {
+                let mut bytes = [0; 4];
+                bytes.copy_from_slice(reader.read(4)?);
+                Value::from(f64::from(f32::from_le_bytes(bytes)))
+            }
+            Schema::Double => {
+                let mut bytes = [0; 8];
+                bytes.copy_from_slice(reader.read(8)?);
+                Value::from(f64::from_le_bytes(bytes))
+            }
+            Schema::Bytes => {
+                let len = reader.read_len()?;
+                Value::Bytes(Cow::owned(reader.read(len)?.to_vec()))
+            }
+            Schema::String => Value::from(reader.read_string()?),
+            Schema::Record { fields } => {
+                let mut record = Object::with_capacity(fields.len());
+                for field in fields {
+                    let value = self.decode(&field.schema, reader)?;
+                    record.insert(field.name.clone().into(), value);
+                }
+                Value::from(record)
+            }
+            Schema::Enum { symbols } => {
+                let idx = reader.read_len()?;
+                let symbol = symbols
+                    .get(idx)
+                    .ok_or_else(|| invalid(format!("Invalid enum index {idx}")))?;
+                Value::from(symbol.clone())
+            }
+            Schema::Array(items) => {
+                let zero_sized_items = self.is_zero_sized(items, &mut Vec::new());
+                let mut array = Vec::new();
+                loop {
+                    let len = reader.read_block_len(zero_sized_items)?;
+                    if len == 0 {
+                        break;
+                    }
+                    for _ in 0..len {
+                        array.push(self.decode(items, reader)?);
+                    }
+                }
+                Value::from(array)
+            }
+            Schema::Map(values) => {
+                let mut map = Object::new();
+                loop {
+                    // keys take at least a byte
+                    let len = reader.read_block_len(false)?;
+                    if len == 0 {
+                        break;
+                    }
+                    for _ in 0..len {
+                        let key = reader.read_string()?;
+                        let value = self.decode(values, reader)?;
+                        map.insert(key.into(), value);
+                    }
+                }
+                Value::from(map)
+            }
+            Schema::Union(branches) => {
+                let idx = reader.read_len()?;
+                let branch = branches
+                    .get(idx)
+                    .ok_or_else(|| invalid(format!("Invalid union index {idx}")))?;
+                self.decode(branch, reader)?
+            }
+            Schema::Fixed { size } => Value::Bytes(Cow::owned(reader.read(*size)?.to_vec())),
+            Schema::Named(name) => return Err(invalid(format!("Unresolved type {name}"))),
+        })
+    }
+
+    /// if `value` can be encoded with `schema`, used for selecting a union branch
+    fn matches(&self, schema: &Schema, value: &Value) -> bool {
+        match self.resolve(schema) {
+            Ok(Schema::Null) => value.is_null(),
+            Ok(Schema::Boolean) => value.is_bool(),
+            Ok(Schema::Int) => value.as_i64().map_or(false, |i| i32::try_from(i).is_ok()),
+            Ok(Schema::Long) => value.as_i64().is_some(),
+            Ok(Schema::Float | Schema::Double) => value.cast_f64().is_some(),
+            Ok(Schema::Bytes) => matches!(value, Value::Bytes(_)),
+            Ok(Schema::String) => value.is_str(),
+            Ok(Schema::Record { fields }) => value.as_object().map_or(false, |o| {
+                fields
+                    .iter()
+                    .all(|f| f.default.is_some() || o.contains_key(f.name.as_str()))
+            }),
+            Ok(Schema::Enum { symbols }) => value
+                .as_str()
+                .map_or(false, |s| symbols.iter().any(|sym| sym == s)),
+            Ok(Schema::Array(_)) => value.is_array(),
+            Ok(Schema::Map(_)) => value.is_object(),
+            Ok(Schema::Fixed { size }) => {
+                matches!(value, Value::Bytes(b) if b.len() == *size)
+            }
+            // unions can't contain unions
+            Ok(Schema::Union(_) | Schema::Named(_)) | Err(_) => false,
+        }
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    fn encode(&self, schema: &Schema, value: &Value, out: &mut Vec<u8>) -> Result<()> {
+        let mismatch = || invalid(format!("Value {value} does not match schema {schema:?}"));
+        match self.resolve(schema)? {
+            Schema::Null => {
+                if !value.is_null() {
+                    return Err(mismatch());
+                }
+            }
+            Schema::Boolean => out.push(u8::from(value.as_bool().ok_or_else(mismatch)?)),
+            Schema::Int => {
+                let i = value.as_i64().ok_or_else(mismatch)?;
+                i32::try_from(i).map_err(|_| mismatch())?;
+                write_long(out, i);
+            }
+            Schema::Long => write_long(out, value.as_i64().ok_or_else(mismatch)?),
+            Schema::Float => {
+                let f = value.cast_f64().ok_or_else(mismatch)? as f32;
+                out.extend_from_slice(&f.to_le_bytes());
+            }
+            Schema::Double => {
+                let f = value.cast_f64().ok_or_else(mismatch)?;
+                out.extend_from_slice(&f.to_le_bytes());
+            }
+            Schema::Bytes => {
+                let bytes = value.as_bytes().ok_or_else(mismatch)?;
+                write_len(out, bytes.len())?;
+                out.extend_from_slice(bytes);
+            }
+            Schema::String => {
+                let s = value.as_str().ok_or_else(mismatch)?;
+                write_len(out, s.len())?;
+                out.extend_from_slice(s.as_bytes());
+            }
+            Schema::Record { fields } => {
+                if !value.is_object() {
+                    return Err(mismatch());
+                }
+                for field in fields {
+                    let field_value = value
+                        .get(field.name.as_str())
+                        .or(field.default.as_ref())
+                        .ok_or_else(|| invalid(format!("Missing record field {}", field.name)))?;
+                    self.encode(&field.schema, field_value, out)?;
+                }
+            }
+            Schema::Enum { symbols } => {
+                let symbol = value.as_str().ok_or_else(mismatch)?;
+                let idx = symbols
+                    .iter()
+                    .position(|s| s == symbol)
+                    .ok_or_else(mismatch)?;
+                write_len(out, idx)?;
+            }
+            Schema::Array(items) => {
+                let array = value.as_array().ok_or_else(mismatch)?;
+                if !array.is_empty() {
+                    write_len(out, array.len())?;
+                    for item in array {
+                        self.encode(items, item, out)?;
+                    }
+                }
+                out.push(0);
+            }
+            Schema::Map(values) => {
+                let map = value.as_object().ok_or_else(mismatch)?;
+                if !map.is_empty() {
+                    write_len(out, map.len())?;
+                    for (k, v) in map.iter() {
+                        write_len(out, k.len())?;
+                        out.extend_from_slice(k.as_bytes());
+                        self.encode(values, v, out)?;
+                    }
+                }
+                out.push(0);
+            }
+            Schema::Union(branches) => {
+                let idx = branches
+                    .iter()
+                    .position(|b| self.matches(b, value))
+                    .ok_or_else(mismatch)?;
+                write_len(out, idx)?;
+                self.encode(&branches[idx], value, out)?;
+            }
+            Schema::Fixed { size } => match value {
+                Value::Bytes(b) if b.len() == *size => out.extend_from_slice(b),
+                _ => return Err(mismatch()),
+            },
+            Schema::Named(_) => return Err(mismatch()),
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone)]
+pub struct Avro {
+    schema: AvroSchema,
+    confluent_schema_id: Option<u32>,
+}
+
+impl Avro {
+    pub(crate) fn from_config(config: Option<&Value>) -> Result<Self> {
+        let config =
+            config.ok_or_else(|| invalid_config("Either `schema` or `schema_file` is required"))?;
+        if let Some(unknown) = config
+            .as_object()
+            .and_then(|o| o.keys().find(|k| !CONFIG_KEYS.contains(&k.as_ref())))
+        {
+            return Err(invalid_config(format!("Unknown option `{unknown}`")));
+        }
+        let schema = match (config.get("schema"), config.get_str("schema_file")) {
+            (Some(schema), None) => AvroSchema::parse(schema)?,
+            (None, Some(path)) => {
+                let mut data = std::fs::read(path)?;
+                AvroSchema::parse(&tremor_value::parse_to_value(&mut data)?)?
+            }
+            _ => {
+                return Err(invalid_config(
+                    "Exactly one of `schema` and `schema_file` is required",
+                ))
+            }
+        };
+        let confluent_schema_id = config
+            .get("confluent_schema_id")
+            .map(|id| {
+                id.as_u32()
+                    .ok_or_else(|| invalid_config("Invalid `confluent_schema_id`"))
+            })
+            .transpose()?;
+        Ok(Self {
+            schema,
+            confluent_schema_id,
+        })
+    }
+}
+
+impl Codec for Avro {
+    fn name(&self) -> &str {
+        "avro"
+    }
+
+    fn mime_types(&self) -> Vec<&'static str> {
+        vec!["application/avro"]
+    }
+
+    fn decode<'input>(
+        &mut self,
+        data: &'input mut [u8],
+        _ingest_ns: u64,
+    ) -> Result<Option<Value<'input>>> {
+        let mut reader = Reader {
+            data: &*data,
+            pos: 0,
+            items: 0,
+            depth: 0,
+        };
+        if let Some(expected) = self.confluent_schema_id {
+            if reader.read_byte()? != CONFLUENT_MAGIC_BYTE {
+                return Err(invalid("Missing magic byte"));
+            }
+            let mut id = [0; 4];
+            id.copy_from_slice(reader.read(4)?);
+            let id = u32::from_be_bytes(id);
+            if id != expected {
+                return Err(invalid(format!(
+                    "Unexpected schema id {id}, expected {expected}"
+                )));
+            }
+        }
+        let value = self.schema.decode(&self.schema.root, &mut reader)?;
+        if reader.pos != reader.data.len() {
+            return Err(invalid("Trailing data after datum"));
+        }
+        Ok(Some(value))
+    }
+
+    fn encode(&self, data: &Value) -> Result<Vec<u8>> {
+        let mut out = Vec::new();
+        if let Some(id) = self.confluent_schema_id {
+            out.push(CONFLUENT_MAGIC_BYTE);
+            out.extend_from_slice(&id.to_be_bytes());
+        }
+        self.schema.encode(&self.schema.root, data, &mut out)?;
+        Ok(out)
+    }
+
+    fn boxed_clone(&self) -> Box<dyn Codec> {
+        Box::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn order_schema() -> Value<'static> {
+        literal!({
+            "type": "record",
+            "name": "Order",
+            "namespace": "com.example",
+            "fields": [
+                {"name": "id", "type": "long"},
+                {"name": "customer", "type": {
+                    "type": "record",
+                    "name": "Customer",
+                    "fields": [
+                        {"name": "name", "type": "string"},
+                        {"name": "email", "type": ["null", "string"], "default": null}
+                    ]
+                }},
+                {"name": "items", "type": {"type": "array", "items": {
+                    "type": "record",
+                    "name": "Item",
+                    "fields": [
+                        {"name": "sku", "type": "string"},
+                        {"name": "qty", "type": "int"},
+                        {"name": "price", "type": "double"}
+                    ]
+                }}},
+                {"name": "status", "type": {"type": "enum", "name": "Status", "symbols": ["NEW", "SHIPPED"]}},
+                {"name": "attributes", "type": {"type": "map", "values": "string"}},
+                {"name": "checksum", "type": {"type": "fixed", "name": "Checksum", "size": 4}},
+                {"name": "previous", "type": ["null", "Order"], "default": null}
+            ]
+        })
+    }
+
+    fn order(previous: Value<'static>) -> Value<'static> {
+        let mut order = literal!({
+            "id": 42,
+            "customer": {"name": "snot", "email": "snot@example.com"},
+            "items": [
+                {"sku": "badger", "qty": 2, "price": 1.5},
+                {"sku": "cake", "qty": 1, "price": -0.25}
+            ],
+            "status": "SHIPPED",
+            "attributes": {"gift": "yes"},
+            "previous": previous
+        });
+        order
+            .insert("checksum", Value::Bytes(Cow::owned(vec![1, 2, 3, 4])))
+            .expect("order is an object");
+        order
+    }
+
+    #[test]
+    fn nested_record_round_trip() -> Result<()> {
+        let config = literal!({ "schema": order_schema() });
+        let mut codec = Avro::from_config(Some(&config))?;
+
+        let mut previous = order(Value::const_null());
+        previous.insert("customer", literal!({"name": "badger", "email": null}))?;
+        let value = order(previous);
+
+        let mut encoded = codec.encode(&value)?;
+        let decoded = codec.decode(&mut encoded, 0)?;
+        assert_eq!(Some(value), decoded);
+        Ok(())
+    }
+
+    #[test]
+    fn binary_encoding() -> Result<()> {
+        let config = literal!({
+            "schema": {
+                "type": "record",
+                "name": "R",
+                "fields": [
+                    {"name": "a", "type": "long"},
+                    {"name": "b", "type": "string"},
+                    {"name": "c", "type": ["null", "int"], "default": null}
+                ]
+            }
+        });
+        let mut codec = Avro::from_config(Some(&config))?;
+        // missing fields are encoded with their default
+        let encoded = codec.encode(&literal!({"a": -1, "b": "hi"}))?;
+        assert_eq!(vec![0x01, 0x04, b'h', b'i', 0x00], encoded);
+        let encoded = codec.encode(&literal!({"a": 64, "b": "", "c": 3}))?;
+        assert_eq!(vec![0x80, 0x01, 0x00, 0x02, 0x06], encoded);
+
+        let mut data = vec![0x80, 0x01, 0x00, 0x02, 0x06];
+        assert_eq!(
+            Some(literal!({"a": 64, "b": "", "c": 3})),
+            codec.decode(&mut data, 0)?
+        );
+        let mut truncated = vec![0x80, 0x01, 0x02];
+        assert!(codec.decode(&mut truncated, 0).is_err());
+        let mut trailing = vec![0x80, 0x01, 0x00, 0x00, 0x00];
+        assert!(codec.decode(&mut trailing, 0).is_err());
+
+        assert!(codec.encode(&literal!({"b": "hi"})).is_err());
+        assert!(codec.encode(&literal!({"a": "snot", "b": "hi"})).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn block_lengths() -> Result<()> {
+        let config = literal!({"schema": {"type": "array", "items": "long"}});
+        let mut codec = Avro::from_config(Some(&config))?;
+        let mut encoded = codec.encode(&literal!([1, 2, 3]))?;
+        assert_eq!(Some(literal!([1, 2, 3])), codec.decode(&mut encoded, 0)?);
+        // a block of 2^62 longs in a handful of bytes
+        let mut huge = vec![
+            0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x01, 0x02,
+        ];
+        assert!(codec.decode(&mut huge, 0).is_err());
+        // negative counts with a block size
+        let mut huge = vec![0xff, 0xff, 0xff, 0xff, 0x0f, 0x02, 0x02];
+        assert!(codec.decode(&mut huge, 0).is_err());
+
+        let config = literal!({"schema": {"type": "map", "values": "null"}});
+        let mut codec = Avro::from_config(Some(&config))?;
+        let mut huge = vec![0x80, 0x80, 0x80, 0x80, 0x10, 0x02, b'a', 0x00];
+        assert!(codec.decode(&mut huge, 0).is_err());
+
+        // nulls take no space, only the total is bounded
+        let config = literal!({"schema": {"type": "array", "items": "null"}});
+        let mut codec = Avro::from_config(Some(&config))?;
+        let mut nulls = vec![0x08, 0x00];
+        assert_eq!(
+            Some(literal!([null, null, null, null])),
+            codec.decode(&mut nulls, 0)?
+        );
+        let mut too_many = vec![0x82, 0x80, 0x80, 0x01, 0x00];
+        assert!(codec.decode(&mut too_many, 0).is_err());
+        let mut too_many = vec![0x80, 0x80, 0x40, 0x80, 0x80, 0x40, 0x02, 0x00];
+        assert!(codec.decode(&mut too_many, 0).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn recursive_schemas() -> Result<()> {
+        // a record containing itself, decoded without consuming any data
+        let config = literal!({
+            "schema": {
+                "type": "record",
+                "name": "Loop",
+                "fields": [{"name": "next", "type": "Loop"}]
+            }
+        });
+        let mut codec = Avro::from_config(Some(&config))?;
+        let mut empty: Vec<u8> = Vec::new();
+        assert!(codec.decode(&mut empty, 0).is_err());
+
+        // a linked list, one byte per element
+        let config = literal!({
+            "schema": {
+                "type": "record",
+                "name": "List",
+                "fields": [{"name": "next", "type": ["null", "List"]}]
+            }
+        });
+        let mut codec = Avro::from_config(Some(&config))?;
+        let mut short = vec![0x02, 0x02, 0x00];
+        assert_eq!(
+            Some(literal!({"next": {"next": {"next": null}}})),
+            codec.decode(&mut short, 0)?
+        );
+        let mut deep = vec![0x02; MAX_DEPTH];
+        deep.push(0x00);
+        assert!(codec.decode(&mut deep, 0).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn confluent_framing() -> Result<()> {
+        let config = literal!({"schema": "string", "confluent_schema_id": 7});
+        let mut codec = Avro::from_config(Some(&config))?;
+        let mut encoded = codec.encode(&literal!("snot"))?;
+        assert_eq!(vec![0, 0, 0, 0, 7, 0x08, b's', b'n', b'o', b't'], encoded);
+        assert_eq!(Some(literal!("snot")), codec.decode(&mut encoded, 0)?);
+
+        let mut wrong_id = vec![0, 0, 0, 0, 8, 0x08, b's', b'n', b'o', b't'];
+        assert!(codec.decode(&mut wrong_id, 0).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn config() {
+        assert!(Avro::from_config(None).is_err());
+        assert!(Avro::from_config(Some(&literal!({}))).is_err());
+        assert!(Avro::from_config(Some(&literal!({"schema": "snot"}))).is_err());
+        assert!(Avro::from_config(Some(&literal!({"schema": "long", "snot": 1}))).is_err());
+        assert!(Avro::from_config(Some(
+            &literal!({"schema": "long", "schema_file": "snot.avsc"})
+        ))
+        .is_err());
+    }
+}
@@ -11,11 +11,147 @@
 // WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
 // See the License for the specific language governing permissions and
 // limitations under the License.
+
+//! # CSV codec
+//!
+//! Decodes a single CSV record per event into an array of strings.
+//!
+//! ## Configuration
+//!
+//! * `delimiter` - the field delimiter, a single ASCII character, defaults to `,`
+//! * `quote` - the quote character, a single ASCII character, defaults to `"`
+//! * `has_header` - if `true`, the first record is treated as the header row.
+//!   It is not emitted as an event, all following records are decoded into objects keyed by the header.
+//!   When encoding, a header row is written before the first object. Defaults to `false`.
+//!
+//! As the header is tracked per codec instance, `has_header` should only be used with connectors
+//! that receive or send a single CSV stream.
+
 use crate::codec::prelude::*;
 use beef::Cow;
+use serde::Deserialize;
+use std::sync::Mutex;
+use tremor_pipeline::ConfigImpl;
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+struct Config {
+    #[serde(default = "default_delimiter")]
+    delimiter: char,
+    #[serde(default = "default_quote")]
+    quote: char,
+    #[serde(default)]
+    has_header: bool,
+}
+
+impl ConfigImpl for Config {}
+
+fn default_delimiter() -> char {
+    ','
+}
+
+fn default_quote() -> char {
+    '"'
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            delimiter: default_delimiter(),
+            quote: default_quote(),
+            has_header: false,
+        }
+    }
+}
+
+pub struct Csv {
+    delimiter: u8,
+    quote: u8,
+    has_header: bool,
+    // header row received when decoding
+    decode_header: Option<Vec<String>>,
+    // header row written when encoding
+    encode_header: Mutex<Option<Vec<String>>>,
+}
+
+impl Default for Csv {
+    fn default() -> Self {
+        Self::new(b',', b'"', false)
+    }
+}
+
+fn ascii(name: &str, c: char) -> Result<u8> {
+    u8::try_from(c).ok().filter(u8::is_ascii).ok_or_else(|| {
+        ErrorKind::InvalidConfiguration(
+            "csv codec".to_string(),
+            format!("Invalid '{name}': '{c}', must be a single ASCII character."),
+        )
+        .into()
+    })
+}
+
+impl Csv {
+    fn new(delimiter: u8, quote: u8, has_header: bool) -> Self {
+        Self {
+            delimiter,
+            quote,
+            has_header,
+            decode_header: None,
+            encode_header: Mutex::new(None),
+        }
+    }
+
+    pub(crate) fn from_config(config: Option<&Value>) -> Result<Self> {
+        let config = config.map_or_else(|| Ok(Config::default()), Config::new)?;
+        Ok(Self::new(
+            ascii("delimiter", config.delimiter)?,
+            ascii("quote", config.quote)?,
+            config.has_header,
+        ))
+    }
+
+    fn write_records(&self, records: &[Vec<String>]) -> Result<Vec<u8>> {
+        let mut result = vec![];
+        let mut writer = csv::WriterBuilder::new()
+            .delimiter(self.delimiter)
+            .quote(self.quote)
+            .from_writer(&mut result);
+        for record in records {
+            writer.write_record(record)?;
+        }
+        writer.flush()?;
+        drop(writer);
 
-#[derive(Clone)]
-pub struct Csv {}
+        while result.last() == Some(&b'\n') || result.last() == Some(&b'\r') {
+            result.pop();
+        }
+        Ok(result)
+    }
+
+    /// the records for an object, preceded by the header row for the first object
+    fn object_records(&self, data: &Value, values: &Object) -> Vec<Vec<String>> {
+        let mut encode_header = self
+            .encode_header
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        let mut records = Vec::with_capacity(2);
+        let header = encode_header.get_or_insert_with(|| {
+            let header: Vec<String> = values.keys().map(ToString::to_string).collect();
+            records.push(header.clone());
+            header
+        });
+        let record = header
+            .iter()
+            .map(|k| {
+                data.get(k.as_str())
+                    .map(ToString::to_string)
+                    .unwrap_or_default()
+            })
+            .collect();
+        records.push(record);
+        records
+    }
+}
 
 impl Codec for Csv {
     fn name(&self) -> &str {
@@ -33,6 +169,8 @@ impl Codec for Csv {
     ) -> Result<Option<Value<'input>>> {
         let mut reader = csv::ReaderBuilder::new()
             .has_headers(false)
+            .delimiter(self.delimiter)
+            .quote(self.quote)
             .from_reader(&*data); // the reborrow here is needed because std::io::Read is implemented only for &[u8], not &mut [u8]
 
         let record = match reader.records().next() {
@@ -41,29 +179,48 @@ impl Codec for Csv {
             None => return Ok(None),
         }?;
 
-        let mut fields = vec![];
-        for field in record.iter() {
-            fields.push(Value::String(Cow::from(field.to_string())));
+        if !self.has_header {
+            let mut fields = vec![];
+            for field in record.iter() {
+                fields.push(Value::String(Cow::from(field.to_string())));
+            }
+            return Ok(Some(Value::Array(fields)));
         }
 
-        Ok(Some(Value::Array(fields)))
+        if let Some(header) = self.decode_header.as_ref() {
+            if header.len() != record.len() {
+                return Err(format!(
+                    "CSV record has {} fields, but the header has {}",
+                    record.len(),
+                    header.len()
+                )
+                .into());
+            }
+            let mut fields = Object::with_capacity(header.len());
+            for (key, field) in header.iter().zip(record.iter()) {
+                fields.insert(
+                    Cow::from(key.clone()),
+                    Value::String(Cow::from(field.to_string())),
+                );
+            }
+            Ok(Some(Value::from(fields)))
+        } else {
+            self.decode_header = Some(record.iter().map(ToString::to_string).collect());
+            Ok(None)
+        }
     }
 
     fn encode(&self, data: &Value) -> Result<Vec<u8>> {
         if let Some(values) = data.as_array() {
             let fields: Vec<String> = values.iter().map(ToString::to_string).collect();
-
-            let mut result = vec![];
-            let mut writer = csv::Writer::from_writer(&mut result);
-            writer.write_record(&fields)?;
-            writer.flush()?;
-            drop(writer);
-
-            while result.last() == Some(&b'\n') || result.last() == Some(&b'\r') {
-                result.pop();
+            return self.write_records(&[fields]);
+        }
+        if let Some(values) = data.as_object() {
+            if self.has_header {
+                return self.write_records(&self.object_records(data, values));
             }
-
-            return Ok(result);
+            let fields: Vec<String> = values.values().map(ToString::to_string).collect();
+            return self.write_records(&[fields]);
         }
 
         Err(
@@ -73,7 +230,7 @@ impl Codec for Csv {
     }
 
     fn boxed_clone(&self) -> Box<dyn Codec> {
-        Box::new(self.clone())
+        Box::new(Self::new(self.delimiter, self.quote, self.has_header))
     }
 }
 
@@ -83,7 +240,7 @@ mod tests {
 
     #[test]
     fn test_can_decode_csv() {
-        let mut codec = Csv {};
+        let mut codec = Csv::default();
         let mut data = b"a,b,c,123".to_vec();
         let result = codec.decode(&mut data, 0);
 
@@ -92,11 +249,63 @@ mod tests {
 
     #[test]
     fn test_can_encode_csv() {
-        let codec = Csv {};
+        let codec = Csv::default();
         let data = literal!(["a", "b", "c", 123]);
 
         let result = codec.encode(&data).unwrap();
 
         assert_eq!(b"a,b,c,123".to_vec(), result);
     }
+
+    #[test]
+    fn test_quoted_fields() -> Result<()> {
+        let mut codec = Csv::default();
+        let mut data = br#"a,"b,c","say ""snot""",d"#.to_vec();
+        let decoded = codec.decode(&mut data, 0)?;
+        assert_eq!(Some(literal!(["a", "b,c", "say \"snot\"", "d"])), decoded);
+
+        let encoded = codec.encode(&literal!(["a", "b,c", "say \"snot\"", "d"]))?;
+        assert_eq!(br#"a,"b,c","say ""snot""",d"#.to_vec(), encoded);
+        Ok(())
+    }
+
+    #[test]
+    fn test_delimiter_and_quote() -> Result<()> {
+        let config = literal!({"delimiter": ";", "quote": "'"});
+        let mut codec = Csv::from_config(Some(&config))?;
+        let mut data = b"a;'b;c';'it''s'".to_vec();
+        let decoded = codec.decode(&mut data, 0)?;
+        assert_eq!(Some(literal!(["a", "b;c", "it's"])), decoded);
+
+        let encoded = codec.encode(&literal!(["a", "b;c", "it's"]))?;
+        assert_eq!(b"a;'b;c';'it''s'".to_vec(), encoded);
+
+        assert!(Csv::from_config(Some(&literal!({"delimiter": "ä"}))).is_err());
+        assert!(Csv::from_config(Some(&literal!({"delimiter": ";;"}))).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_header() -> Result<()> {
+        let config = literal!({"has_header": true});
+        let mut decoder = Csv::from_config(Some(&config))?;
+        let mut header = b"name,comment".to_vec();
+        assert_eq!(None, decoder.decode(&mut header, 0)?);
+        let mut data = br#"snot,"badger, the""#.to_vec();
+        let decoded = decoder.decode(&mut data, 0)?;
+        assert_eq!(
+            Some(literal!({"name": "snot", "comment": "badger, the"})),
+            decoded
+        );
+        let mut data = b"too,many,fields".to_vec();
+        assert!(decoder.decode(&mut data, 0).is_err());
+
+        // round trip, the header is only written once
+        let encoder = Csv::from_config(Some(&config))?;
+        let encoded = encoder.encode(&literal!({"name": "snot", "comment": "badger, the"}))?;
+        assert_eq!(b"name,comment\nsnot,\"badger, the\"".to_vec(), encoded);
+        let encoded = encoder.encode(&literal!({"comment": "ok", "name": "badger"}))?;
+        assert_eq!(b"badger,ok".to_vec(), encoded);
+        Ok(())
+    }
 }
@@ -132,6 +132,28 @@ mod tests {
         assert_eq!(decoded, &e)
     }
 
+    #[test]
+    pub fn decode_malformed() {
+        let lines = [
+            "weather",
+            "weather,location temperature=82",
+            "weather temperature",
+            "weather temperature=",
+            "weather temperature=82x",
+            r#"weather temperature="unterminated"#,
+            r#"weather temperature=1\"#,
+            "weather temperature=82 notatimestamp",
+        ];
+        let mut codec = Influx {};
+        for line in lines {
+            let mut data = line.as_bytes().to_vec();
+            assert!(
+                codec.decode(data.as_mut_slice(), 0).is_err(),
+                "{line} should not decode"
+            );
+        }
+    }
+
     #[allow(clippy::too_many_lines)]
     fn get_data_for_tests() -> [(Vec<u8>, Value<'static>, &'static str); 13] {
         [
@@ -70,6 +70,19 @@ fn encode(value: &Value) -> Result<Vec<u8>> {
         }
     }
 
+    if let Some(tags) = value.get("tags") {
+        let tags = tags.as_array().ok_or(ErrorKind::InvalidStatsD)?;
+        if !tags.is_empty() {
+            r.push_str("|#");
+            for (i, tag) in tags.iter().enumerate() {
+                if i > 0 {
+                    r.push(',');
+                }
+                r.push_str(tag.as_str().ok_or(ErrorKind::InvalidStatsD)?);
+            }
+        }
+    }
+
     Ok(r.as_bytes().to_vec())
 }
 
@@ -120,11 +133,15 @@ fn decode(data: &[u8], _ingest_ns: u64) -> Result<Value> {
             None => return Err(invalid()),
         }
     }
-    match d.next() {
-        Some((i, b'c' | b'h' | b's')) => m.insert("type".into(), substr(data, i..=i)?.into()),
+    let type_end = match d.next() {
+        Some((i, b'c' | b'h' | b's')) => {
+            m.insert("type".into(), substr(data, i..=i)?.into());
+            i
+        }
         Some((i, b'm')) => {
             if let Some((j, b's')) = d.next() {
-                m.insert("type".into(), substr(data, i..=j)?.into())
+                m.insert("type".into(), substr(data, i..=j)?.into());
+                j
             } else {
                 return Err(invalid());
             }
@@ -147,23 +164,40 @@ fn decode(data: &[u8], _ingest_ns: u64) -> Result<Value> {
                 }
                 Sign::None => (),
             };
-            m.insert("type".into(), substr(data, i..=i)?.into())
+            m.insert("type".into(), substr(data, i..=i)?.into());
+            i
         }
         _ => return Err(invalid()),
     };
-    match d.next() {
-        Some((_, b'|')) => {
-            if let Some((sample_start, b'@')) = d.next() {
-                let s = substr(data, sample_start + 1..)?;
-                let v: f64 = s.parse()?;
-                m.insert("sample_rate".into(), Value::from(v));
+    // optional `|@<sample rate>` and `|#<tag>,<tag>` extensions
+    let rest = substr(data, type_end + 1..)?;
+    if !rest.is_empty() {
+        let rest = rest.strip_prefix('|').ok_or_else(invalid)?;
+        for section in rest.split('|') {
+            if let Some(rate) = section.strip_prefix('@') {
+                let v: f64 = rate.parse()?;
+                if m.insert("sample_rate".into(), Value::from(v)).is_some() {
+                    return Err(invalid());
+                }
+            } else if let Some(tags) = section.strip_prefix('#') {
+                let tags = tags
+                    .split(',')
+                    .map(|tag| {
+                        if tag.is_empty() {
+                            Err(invalid())
+                        } else {
+                            Ok(Value::from(tag))
+                        }
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                if m.insert("tags".into(), Value::from(tags)).is_some() {
+                    return Err(invalid());
+                }
             } else {
                 return Err(invalid());
             }
         }
-        Some(_) => return Err(invalid()),
-        None => (),
-    };
+    }
     m.insert("value".into(), value);
     Ok(Value::from(m))
 }
@@ -324,6 +358,74 @@ mod test {
         assert_eq!(encoded, data);
     }
 
+    #[test]
+    fn tags() {
+        let data = b"tagged:7|c|@0.5|#env:prod,canary";
+        let parsed = decode(data, 0).expect("failed to decode");
+        let expected = literal!({
+            "type": "c",
+            "metric": "tagged",
+            "value": 7,
+            "sample_rate": 0.5,
+            "tags": ["env:prod", "canary"]
+        });
+        assert_eq!(parsed, expected);
+        let encoded = encode(&parsed).expect("failed to encode");
+        assert_eq!(encoded, data);
+
+        let data = b"timed:12.5|ms|#host:snot";
+        let parsed = decode(data, 0).expect("failed to decode");
+        let expected = literal!({
+            "type": "ms",
+            "metric": "timed",
+            "value": 12.5,
+            "tags": ["host:snot"]
+        });
+        assert_eq!(parsed, expected);
+        let encoded = encode(&parsed).expect("failed to encode");
+        assert_eq!(encoded, data);
+
+        // tags may come before the sample rate
+        let data = b"gauged:-3|g|#host:badger|@0.1";
+        let parsed = decode(data, 0).expect("failed to decode");
+        let expected = literal!({
+            "type": "g",
+            "metric": "gauged",
+            "action": "sub",
+            "value": 3,
+            "sample_rate": 0.1,
+            "tags": ["host:badger"]
+        });
+        assert_eq!(parsed, expected);
+        let encoded = encode(&parsed).expect("failed to encode");
+        assert_eq!(encoded, b"gauged:-3|g|@0.1|#host:badger");
+    }
+
+    #[test]
+    fn malformed() {
+        let lines: [&[u8]; 8] = [
+            b"nocolon",
+            b"notype:1",
+            b"badtype:1|x",
+            b"badrate:1|c|@snot",
+            b"twice:1|c|@0.1|@0.2",
+            b"emptytag:1|c|#a,,b",
+            b"section:1|c|snot",
+            b"trailing:1|c|",
+        ];
+        for line in lines {
+            assert!(
+                decode(line, 0).is_err(),
+                "{} should not decode",
+                String::from_utf8_lossy(line)
+            );
+        }
+        assert!(
+            encode(&literal!({"metric": "snot", "type": "c", "value": 1, "tags": "badger"}))
+                .is_err()
+        );
+    }
+
     #[test]
     fn bench() {
         let data = b"foo:1620649445.3351967|h";
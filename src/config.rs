@@ -83,6 +83,15 @@ pub struct NameWithConfig {
     pub(crate) config: Option<Value<'static>>,
 }
 
+impl NameWithConfig {
+    fn to_value(&self) -> Value<'static> {
+        literal!({
+            "name": self.name.clone(),
+            "config": self.config.clone(),
+        })
+    }
+}
+
 impl<'v> TryFrom<&Value<'v>> for NameWithConfig {
     type Error = crate::errors::Error;
 
@@ -149,6 +158,17 @@ pub(crate) struct Connector {
 
     //pub(crate) on_pause: PauseBehaviour,
     pub(crate) metrics_interval_s: Option<u64>,
+
+    /// maximum number of events of a transactional source that are neither acked nor failed,
+    /// the source isn't read from while this many events are in flight
+    pub(crate) max_in_flight: Option<usize>,
+
+    /// events in flight for this long are no longer waited for, without it they are waited for
+    /// until the connection is lost
+    pub(crate) in_flight_timeout_ms: Option<u64>,
+
+    /// host used in the origin URIs of events of this connector, instead of the global instance host
+    pub(crate) instance_host: Option<String>,
 }
 
 impl Connector {
@@ -221,6 +241,12 @@ impl Connector {
             ValueType::Array,
             connector_alias,
         )?;
+        validate_type(
+            connector_config,
+            ConnectorDefinition::INSTANCE_HOST,
+            ValueType::String,
+            connector_alias,
+        )?;
         validate_type(
             connector_config,
             ConnectorDefinition::METRICS_INTERVAL_S,
@@ -235,6 +261,40 @@ impl Connector {
                 connector_alias,
             )
         })?;
+        let max_in_flight = connector_config
+            .get(ConnectorDefinition::MAX_IN_FLIGHT)
+            .map(|max_in_flight| {
+                max_in_flight
+                    .as_usize()
+                    .filter(|max_in_flight| *max_in_flight > 0)
+                    .ok_or_else(|| {
+                        ErrorKind::InvalidConnectorDefinition(
+                            connector_alias.to_string(),
+                            format!(
+                                "Expected a positive integer for key {} but got {max_in_flight}",
+                                ConnectorDefinition::MAX_IN_FLIGHT
+                            ),
+                        )
+                    })
+            })
+            .transpose()?;
+        let in_flight_timeout_ms = connector_config
+            .get(ConnectorDefinition::IN_FLIGHT_TIMEOUT_MS)
+            .map(|timeout_ms| {
+                timeout_ms
+                    .as_u64()
+                    .filter(|timeout_ms| *timeout_ms > 0)
+                    .ok_or_else(|| {
+                        ErrorKind::InvalidConnectorDefinition(
+                            connector_alias.to_string(),
+                            format!(
+                                "Expected a positive integer for key {} but got {timeout_ms}",
+                                ConnectorDefinition::IN_FLIGHT_TIMEOUT_MS
+                            ),
+                        )
+                    })
+            })
+            .transpose()?;
 
         Ok(Self {
             connector_type,
@@ -254,12 +314,52 @@ impl Connector {
                 .transpose()?
                 .unwrap_or_default(),
             metrics_interval_s: connector_config.get_u64(ConnectorDefinition::METRICS_INTERVAL_S),
+            max_in_flight,
+            in_flight_timeout_ms,
+            instance_host: connector_config
+                .get_str(ConnectorDefinition::INSTANCE_HOST)
+                .map(ToString::to_string),
             codec: connector_config
                 .get(ConnectorDefinition::CODEC)
                 .map(Codec::try_from)
                 .transpose()?,
         })
     }
+
+    /// The effective configuration, with the defaults of the settings common to all connectors applied
+    ///
+    /// The connector specific `config` is included as given: its defaults are only known to the
+    /// connector, which applies them when it is created, so they are omitted here.
+    pub(crate) fn to_value(&self) -> Result<Value<'static>> {
+        let processors = |processors: &Option<Vec<NameWithConfig>>| {
+            processors.as_ref().map(|processors| {
+                processors
+                    .iter()
+                    .map(NameWithConfig::to_value)
+                    .collect::<Vec<_>>()
+            })
+        };
+        Ok(literal!({
+            "type": self.connector_type.to_string(),
+            "codec": self.codec.as_ref().map(NameWithConfig::to_value),
+            "config": self.config.clone(),
+            "preprocessors": processors(&self.preprocessors),
+            "postprocessors": processors(&self.postprocessors),
+            "reconnect": tremor_value::to_value(&self.reconnect)?,
+            "metrics_interval_s": self.metrics_interval_s,
+            "max_in_flight": self.max_in_flight,
+            "in_flight_timeout_ms": self.in_flight_timeout_ms,
+            "instance_host": self.instance_host.clone(),
+        }))
+    }
+
+    /// The host used in the origin URIs of events of this connector,
+    /// the configured `instance_host` or the global instance host
+    pub(crate) fn instance_host(&self) -> String {
+        self.instance_host
+            .clone()
+            .unwrap_or_else(crate::utils::instance_host)
+    }
 }
 
 /// Configuration for a Binding
@@ -339,4 +439,50 @@ mod tests {
         assert_eq!(String::from("Invalid Definition for connector \"flow::my_id\": Expected type I64 for key metrics_interval_s but got String"), res.err().unwrap().to_string());
         Ok(())
     }
+
+    #[test]
+    fn test_connector_config_instance_host() -> Result<()> {
+        let id = Alias::new(flow::Alias::new("flow"), "my_id");
+        let config = Connector::from_config(
+            &id,
+            "metronome".into(),
+            &literal!({"instance_host": "snot.badger"}),
+        )?;
+        assert_eq!("snot.badger", config.instance_host());
+        let config = Connector::from_config(&id, "metronome".into(), &literal!({}))?;
+        assert_eq!(None, config.instance_host);
+        assert!(
+            Connector::from_config(&id, "metronome".into(), &literal!({"instance_host": 42}))
+                .is_err()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_connector_config_max_in_flight() -> Result<()> {
+        let id = Alias::new(flow::Alias::new("flow"), "my_id");
+        let config = Connector::from_config(&id, "wal".into(), &literal!({"max_in_flight": 10}))?;
+        assert_eq!(Some(10), config.max_in_flight);
+        let config = Connector::from_config(&id, "wal".into(), &literal!({}))?;
+        assert_eq!(None, config.max_in_flight);
+        assert!(
+            Connector::from_config(&id, "wal".into(), &literal!({"max_in_flight": 0})).is_err()
+        );
+        assert!(
+            Connector::from_config(&id, "wal".into(), &literal!({"max_in_flight": "snot"}))
+                .is_err()
+        );
+
+        let config = Connector::from_config(
+            &id,
+            "wal".into(),
+            &literal!({"max_in_flight": 10, "in_flight_timeout_ms": 1000}),
+        )?;
+        assert_eq!(Some(1000), config.in_flight_timeout_ms);
+        assert!(
+            Connector::from_config(&id, "wal".into(), &literal!({"in_flight_timeout_ms": 0}))
+                .is_err()
+        );
+        Ok(())
+    }
 }
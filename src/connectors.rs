@@ -50,7 +50,7 @@ use async_std::{
 use beef::Cow;
 use futures::Future;
 use halfbrown::HashMap;
-use std::{fmt::Display, sync::atomic::Ordering, time::Duration};
+use std::{fmt::Display, sync::atomic::Ordering};
 use tremor_common::ids::{ConnectorId, ConnectorIdGen, SourceId};
 use tremor_common::ports::{ERR, IN, OUT};
 use tremor_pipeline::METRICS_CHANNEL;
@@ -62,8 +62,13 @@ use value_trait::{Builder, Mutable, ValueAccess};
 /// quiescence stuff
 pub(crate) use utils::{metrics, reconnect};
 
-/// Accept timeout
-pub(crate) const ACCEPT_TIMEOUT: Duration = Duration::from_millis(100);
+/// Accept timeout in milliseconds
+const DEFAULT_ACCEPT_TIMEOUT_MS: u64 = 100;
+
+/// default accept timeout in milliseconds for connectors accepting connections
+pub(crate) fn default_accept_timeout_ms() -> u64 {
+    DEFAULT_ACCEPT_TIMEOUT_MS
+}
 
 /// connector address
 #[derive(Clone, Debug)]
@@ -157,6 +162,13 @@ impl Addr {
     pub async fn resume(&self) -> Result<()> {
         self.send(Msg::Resume).await
     }
+    /// restarts the connector after it failed to reconnect
+    ///
+    /// # Errors
+    ///   * if sending failed
+    pub async fn restart(&self) -> Result<()> {
+        self.send(Msg::Restart).await
+    }
 
     /// report status of the connector instance
     ///
@@ -206,6 +218,9 @@ pub(crate) enum Msg {
     Pause,
     /// resume the connector after a pause
     Resume,
+    /// restart the connector after it gave up reconnecting,
+    /// starting over with the configured reconnect strategy
+    Restart,
     /// Drain events from this connector
     ///
     /// - stop reading events from external connections
@@ -674,7 +689,9 @@ async fn connector_task(
                     if connector_state == State::Running {
                         // ensure we don't reconnect in a hot loop
                         // ensure we adhere to the reconnect strategy, waiting and possibly not reconnecting at all
-                        reconnect.enqueue_retry(&ctx).await;
+                        if !reconnect.enqueue_retry(&ctx).await {
+                            connector_state = State::Failed;
+                        }
                     }
                 }
                 Msg::Reconnect => {
@@ -795,6 +812,15 @@ async fn connector_task(
                 Msg::Resume => {
                     info!("{ctx} Ignoring Resume Msg. Current state: {connector_state}",);
                 }
+                Msg::Restart if connector_state == State::Failed && reconnect.is_failed() => {
+                    info!("{ctx} Restarting...");
+                    reconnect.restart();
+                    connector_state = State::Running;
+                    connector_addr.send(Msg::Reconnect).await?;
+                }
+                Msg::Restart => {
+                    info!("{ctx} Ignoring Restart Msg. Current state: {connector_state}",);
+                }
                 Msg::Drain(_) if connector_state == State::Draining => {
                     info!("{ctx} Ignoring Drain Msg. Current state: {connector_state}",);
                 }
@@ -1241,6 +1267,8 @@ pub(crate) fn builtin_connector_types() -> Vec<Box<dyn ConnectorBuilder + 'stati
         Box::new(impls::udp::server::Builder::default()),
         Box::new(impls::kv::Builder::default()),
         Box::new(impls::metronome::Builder::default()),
+        Box::new(impls::metrics_aggregator::Builder::default()),
+        Box::new(impls::replay::Builder::default()),
         Box::new(impls::wal::Builder::default()),
         Box::new(impls::dns::client::Builder::default()),
         Box::new(impls::discord::Builder::default()),
@@ -1276,6 +1304,7 @@ pub(crate) fn debug_connector_types() -> Vec<Box<dyn ConnectorBuilder + 'static>
     vec![
         Box::new(impls::cb::Builder::default()),
         Box::new(impls::bench::Builder::default()),
+        Box::new(impls::blackhole::Builder::default()),
         Box::new(impls::null::Builder::default()),
         Box::new(impls::exit::Builder::default()),
     ]
@@ -14,6 +14,8 @@
 
 /// benchmarking connector
 pub(crate) mod bench;
+/// Discard all events, for benchmarking
+pub(crate) mod blackhole;
 /// connector for checking guaranteed delivery and circuit breaker logic
 pub(crate) mod cb;
 /// Clickhouse connector
@@ -43,12 +45,16 @@ pub(crate) mod kafka;
 pub(crate) mod kv;
 /// Home of the famous metrics collector
 pub(crate) mod metrics;
+/// Aggregates event counts and values by key
+pub(crate) mod metrics_aggregator;
 /// Metronome
 pub(crate) mod metronome;
 /// Never send any events and swallow all events it receives into the void.
 pub(crate) mod null;
 /// `OpenTelemetry`
 pub(crate) mod otel;
+/// Replays recorded events with their original timing
+pub(crate) mod replay;
 /// AWS S3 connectors
 pub(crate) mod s3;
 /// std streams connector (stdout, stderr, stdin)
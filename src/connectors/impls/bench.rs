@@ -85,7 +85,7 @@ impl ConnectorBuilder for Builder {
     async fn build_cfg(
         &self,
         id: &Alias,
-        _: &ConnectorConfig,
+        connector_config: &ConnectorConfig,
         config: &Value,
         kill_switch: &KillSwitch,
     ) -> Result<Box<dyn Connector>> {
@@ -100,7 +100,7 @@ impl ConnectorBuilder for Builder {
         };
         let origin_uri = EventOriginUri {
             scheme: "tremor-blaster".to_string(),
-            host: hostname(),
+            host: connector_config.instance_host(),
             port: None,
             path: vec![config.path.clone()],
         };
@@ -0,0 +1,238 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A sink that accepts all events and discards them, optionally acking them.
+//!
+//! This connector is used for benchmarking sources and pipelines in isolation.
+
+use crate::connectors::prelude::*;
+use tremor_common::time::nanotime;
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Config {
+    /// if `true` all events are acked, otherwise no acks or fails are sent at all
+    #[serde(default = "default_true")]
+    auto_ack: bool,
+    /// log a throughput line every `log_every` events
+    #[serde(default)]
+    log_every: Option<usize>,
+}
+
+impl ConfigImpl for Config {}
+
+#[derive(Debug, Default)]
+pub(crate) struct Builder {}
+
+impl Builder {
+    const INVALID_LOG_EVERY: &'static str = "`log_every` must be greater than 0.";
+}
+
+#[async_trait::async_trait]
+impl ConnectorBuilder for Builder {
+    fn connector_type(&self) -> ConnectorType {
+        "blackhole".into()
+    }
+
+    async fn build_cfg(
+        &self,
+        alias: &Alias,
+        _: &ConnectorConfig,
+        raw: &Value,
+        _kill_switch: &KillSwitch,
+    ) -> Result<Box<dyn Connector>> {
+        let config = Config::new(raw)?;
+        if config.log_every == Some(0) {
+            return Err(err_connector_def(alias, Builder::INVALID_LOG_EVERY));
+        }
+        Ok(Box::new(Blackhole { config }))
+    }
+}
+
+struct Blackhole {
+    config: Config,
+}
+
+#[async_trait::async_trait]
+impl Connector for Blackhole {
+    async fn create_sink(
+        &mut self,
+        sink_context: SinkContext,
+        builder: SinkManagerBuilder,
+    ) -> Result<Option<SinkAddr>> {
+        let sink = BlackholeSink {
+            auto_ack: self.config.auto_ack,
+            throughput: Throughput::new(self.config.log_every, nanotime()),
+        };
+        builder.spawn(sink, sink_context).map(Some)
+    }
+
+    fn codec_requirements(&self) -> CodecReq {
+        CodecReq::Optional("json")
+    }
+}
+
+/// Totals of all events and bytes seen
+#[derive(Debug)]
+struct Throughput {
+    log_every: Option<usize>,
+    events: u64,
+    bytes: u64,
+    // start, events and bytes of the current logging window
+    window_start: u64,
+    window_events: usize,
+    window_bytes: u64,
+}
+
+impl Throughput {
+    fn new(log_every: Option<usize>, now: u64) -> Self {
+        Self {
+            log_every,
+            events: 0,
+            bytes: 0,
+            window_start: now,
+            window_events: 0,
+            window_bytes: 0,
+        }
+    }
+
+    /// Records a single event of `bytes` size, returning a throughput line
+    /// each time `log_every` events have been recorded.
+    #[allow(clippy::cast_precision_loss)]
+    fn record(&mut self, bytes: u64, now: u64) -> Option<String> {
+        self.events += 1;
+        self.bytes += bytes;
+        self.window_events += 1;
+        self.window_bytes += bytes;
+        let log_every = self.log_every?;
+        if self.window_events < log_every {
+            return None;
+        }
+        let secs = (now.saturating_sub(self.window_start) as f64 / 1_000_000_000.0).max(1e-9);
+        let line = format!(
+            "{} events/s {} bytes/s (total: {} events {} bytes)",
+            (self.window_events as f64 / secs).round(),
+            (self.window_bytes as f64 / secs).round(),
+            self.events,
+            self.bytes
+        );
+        self.window_start = now;
+        self.window_events = 0;
+        self.window_bytes = 0;
+        Some(line)
+    }
+}
+
+struct BlackholeSink {
+    auto_ack: bool,
+    throughput: Throughput,
+}
+
+#[async_trait::async_trait]
+impl Sink for BlackholeSink {
+    async fn on_event(
+        &mut self,
+        _input: &str,
+        event: Event,
+        ctx: &SinkContext,
+        serializer: &mut EventSerializer,
+        _start: u64,
+    ) -> Result<SinkReply> {
+        for value in event.value_iter() {
+            let bytes: usize = serializer
+                .serialize(value, event.ingest_ns)?
+                .iter()
+                .map(Vec::len)
+                .sum();
+            if let Some(line) = self.throughput.record(bytes as u64, nanotime()) {
+                info!("{ctx} {line}");
+            }
+        }
+        Ok(SinkReply::NONE)
+    }
+
+    fn auto_ack(&self) -> bool {
+        self.auto_ack
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Reconnect;
+
+    #[async_std::test]
+    async fn ack_behaviour() -> Result<()> {
+        let alias = Alias::new("flow", "blackhole");
+        let connector_config = ConnectorConfig {
+            connector_type: "blackhole".into(),
+            codec: None,
+            config: None,
+            preprocessors: None,
+            postprocessors: None,
+            reconnect: Reconnect::None,
+            metrics_interval_s: None,
+            max_in_flight: None,
+            in_flight_timeout_ms: None,
+            instance_host: None,
+        };
+        let builder = Builder::default();
+        let kill_switch = KillSwitch::dummy();
+
+        let config = Config::new(&literal!({}))?;
+        assert!(config.auto_ack);
+        let config = Config::new(&literal!({"auto_ack": false}))?;
+        assert!(!config.auto_ack);
+
+        let sink = BlackholeSink {
+            auto_ack: false,
+            throughput: Throughput::new(None, 0),
+        };
+        assert!(!sink.auto_ack());
+
+        let res = builder
+            .build_cfg(
+                &alias,
+                &connector_config,
+                &literal!({"log_every": 0}),
+                &kill_switch,
+            )
+            .await;
+        assert!(res.is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn log_every() {
+        let mut throughput = Throughput::new(Some(3), 0);
+        assert_eq!(None, throughput.record(10, 500_000_000));
+        assert_eq!(None, throughput.record(10, 1_000_000_000));
+        assert_eq!(
+            Some("2 events/s 20 bytes/s (total: 3 events 30 bytes)".to_string()),
+            throughput.record(10, 1_500_000_000)
+        );
+        assert_eq!(None, throughput.record(5, 2_000_000_000));
+        assert_eq!(None, throughput.record(5, 2_500_000_000));
+        assert_eq!(
+            Some("3 events/s 15 bytes/s (total: 6 events 45 bytes)".to_string()),
+            throughput.record(5, 2_500_000_000)
+        );
+
+        let mut throughput = Throughput::new(None, 0);
+        for _ in 0..10 {
+            assert_eq!(None, throughput.record(1, 0));
+        }
+        assert_eq!(10, throughput.events);
+    }
+}
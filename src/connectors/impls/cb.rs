@@ -14,14 +14,21 @@
 
 // #![cfg_attr(coverage, no_coverage)] // This is for benchmarking and testing
 
-use std::{path::PathBuf, time::Duration};
+use std::{
+    collections::BTreeSet,
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
 use crate::system::{KillSwitch, ShutdownMode};
 use crate::{connectors::prelude::*, errors::err_connector_def};
 use async_std::io::prelude::BufReadExt;
 use async_std::stream::StreamExt;
 use async_std::{fs::File, io};
+use rand::rngs::SmallRng;
+use rand::{Rng, SeedableRng};
 use tremor_common::asy::file::open;
+use tremor_common::time::nanotime;
 
 #[derive(Deserialize, Debug, Clone)]
 #[serde(deny_unknown_fields)]
@@ -34,6 +41,27 @@ pub(crate) struct Config {
     // only expect the latest event to be acked, the earliest to be failed
     #[serde(default = "default_false")]
     expect_batched: bool,
+    // ratio of events the sink acks, regardless of their payload
+    #[serde(default)]
+    ack_ratio: Option<f64>,
+    // ratio of events the sink fails, regardless of their payload
+    #[serde(default)]
+    fail_ratio: Option<f64>,
+    // seed for sampling acks and fails, for reproducible runs
+    #[serde(default)]
+    seed: Option<u64>,
+    // how often to read the file, a number or "forever"
+    #[serde(default)]
+    repeat: Repeat,
+    // target number of events per second to emit, unpaced if not set
+    #[serde(default)]
+    pace_per_sec: Option<u64>,
+    // ids of exactly the events expected to be acked, not checked if not set
+    #[serde(default)]
+    expected_acks: Option<Vec<u64>>,
+    // ids of exactly the events expected to be failed, not checked if not set
+    #[serde(default)]
+    expected_fails: Option<Vec<u64>>,
 }
 
 /// 10 seconds
@@ -41,11 +69,52 @@ fn default_timeout() -> u64 {
     10_000_000_000
 }
 
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum Forever {
+    Forever,
+}
+
+/// How often the source reads its input file
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(untagged)]
+enum Repeat {
+    Times(usize),
+    Forever(Forever),
+}
+
+impl Default for Repeat {
+    fn default() -> Self {
+        Self::Times(1)
+    }
+}
+
+impl Repeat {
+    fn is_forever(self) -> bool {
+        matches!(self, Self::Forever(_))
+    }
+
+    /// if the file should be read again after it has been read `reads` times
+    fn again(self, reads: usize) -> bool {
+        match self {
+            Self::Times(times) => reads < times,
+            Self::Forever(_) => true,
+        }
+    }
+}
+
 impl ConfigImpl for Config {}
 
 #[derive(Debug, Default)]
 pub(crate) struct Builder {}
 
+impl Builder {
+    const INVALID_RATIOS: &'static str =
+        "`ack_ratio` and `fail_ratio` must be between 0 and 1 and sum up to at most 1.";
+    const INVALID_REPEAT: &'static str = "`repeat` must be greater than 0.";
+    const INVALID_PACE: &'static str = "`pace_per_sec` must be greater than 0.";
+}
+
 #[async_trait::async_trait()]
 impl ConnectorBuilder for Builder {
     fn connector_type(&self) -> ConnectorType {
@@ -54,14 +123,27 @@ impl ConnectorBuilder for Builder {
 
     async fn build_cfg(
         &self,
-        _: &Alias,
-        _: &ConnectorConfig,
+        alias: &Alias,
+        connector_config: &ConnectorConfig,
         raw: &Value,
         kill_switch: &KillSwitch,
     ) -> Result<Box<dyn Connector>> {
         let config = Config::new(raw)?;
+        if config.repeat == Repeat::Times(0) {
+            return Err(err_connector_def(alias, Self::INVALID_REPEAT));
+        }
+        if config.pace_per_sec == Some(0) {
+            return Err(err_connector_def(alias, Self::INVALID_PACE));
+        }
+        let ratios = [config.ack_ratio, config.fail_ratio];
+        let valid = ratios.iter().flatten().all(|r| (0.0..=1.0).contains(r))
+            && ratios.iter().flatten().sum::<f64>() <= 1.0;
+        if !valid {
+            return Err(err_connector_def(alias, Self::INVALID_RATIOS));
+        }
         Ok(Box::new(Cb {
             config,
+            instance_host: connector_config.instance_host(),
             kill_switch: kill_switch.clone(),
         }))
     }
@@ -70,9 +152,15 @@ impl ConnectorBuilder for Builder {
 /// Testing connector for verifying correct CB Ack/Fail behaviour of the whole downstream pipeline/connectors
 /// and for triggering custom cb (circuit breaker open/close) or gd (guaranteed delivery ack/fail) contraflow events.
 ///
-/// Source: takes events from a file and expects at least one (or exactly one) ack or fail for each event.
+/// Source: takes events from a file, one per line decoded with the configured codec (`json` by default),
+///         and expects at least one (or exactly one) ack or fail for each event.
+///         Lines are read as bytes, so binary codecs work as long as the records don't contain newlines.
+///         The file is read `repeat` times, if `repeat` is "forever" it never finishes.
+///         With `expected_acks` or `expected_fails` exactly the listed ids must be acked or failed,
+///         missing and unexpected ids are reported when finishing.
 /// Sink: expects a `"cb"` array or string in the event payload or metadata and reacts with the given event
 ///       (possible values: "ack", "fail", "open", "close", "trigger", "restore")
+///       If `ack_ratio` or `fail_ratio` are configured, it instead acks or fails a sampled fraction of all events.
 ///
 /// ### Notes:
 ///
@@ -80,6 +168,8 @@ impl ConnectorBuilder for Builder {
 /// * In case the pipeline branches off, it copies the event and it reaches two offramps, we might receive more than 1 ack or fail for an event with the current runtime.
 pub(crate) struct Cb {
     config: Config,
+    /// host of the origin URI of the emitted events
+    instance_host: String,
     kill_switch: KillSwitch,
 }
 
@@ -97,6 +187,7 @@ impl Connector for Cb {
         let source = CbSource::new(
             &self.config,
             source_context.alias(),
+            self.instance_host.clone(),
             self.kill_switch.clone(),
         )
         .await?;
@@ -109,13 +200,92 @@ impl Connector for Cb {
         sink_context: SinkContext,
         builder: SinkManagerBuilder,
     ) -> Result<Option<SinkAddr>> {
-        let sink = CbSink {};
+        let sink = CbSink {
+            sampler: Sampler::new(&self.config),
+        };
         let sink_addr = builder.spawn(sink, sink_context)?;
         Ok(Some(sink_addr))
     }
 }
 
-struct CbSink {}
+/// Randomly acks or fails events according to the configured ratios
+struct Sampler {
+    ack_ratio: f64,
+    fail_ratio: f64,
+    rng: SmallRng,
+}
+
+impl Sampler {
+    fn new(config: &Config) -> Option<Self> {
+        let (ack_ratio, fail_ratio) = match (config.ack_ratio, config.fail_ratio) {
+            (None, None) => return None,
+            (Some(ack), None) => (ack, 1.0 - ack),
+            (None, Some(fail)) => (1.0 - fail, fail),
+            (Some(ack), Some(fail)) => (ack, fail),
+        };
+        let rng = config
+            .seed
+            .map_or_else(SmallRng::from_entropy, SmallRng::seed_from_u64);
+        Some(Self {
+            ack_ratio,
+            fail_ratio,
+            rng,
+        })
+    }
+
+    fn sample(&mut self) -> SinkAck {
+        let r: f64 = self.rng.gen();
+        if r < self.ack_ratio {
+            SinkAck::Ack
+        } else if r < self.ack_ratio + self.fail_ratio {
+            SinkAck::Fail
+        } else {
+            SinkAck::None
+        }
+    }
+}
+
+struct CbSink {
+    sampler: Option<Sampler>,
+}
+
+impl CbSink {
+    /// the reply for the commands in a `"cb"` array or string
+    fn reply(cb: &Value) -> SinkReply {
+        let cb_cmds = if let Some(array) = cb.as_array() {
+            array
+                .iter()
+                .filter_map(|v| v.as_str().map(ToString::to_string))
+                .collect()
+        } else if let Some(str) = cb.as_str() {
+            vec![str.to_string()]
+        } else {
+            vec![]
+        };
+
+        // Acknowledgement tracking
+        let ack = if cb_cmds.contains(&"ack".to_string()) {
+            SinkAck::Ack
+        } else if cb_cmds.contains(&"fail".to_string()) {
+            SinkAck::Fail
+        } else {
+            SinkAck::None
+        };
+
+        // Circuit breaker tracking
+        let cb = if cb_cmds.contains(&"close".to_string())
+            || cb_cmds.contains(&"trigger".to_string())
+        {
+            CbAction::Trigger
+        } else if cb_cmds.contains(&"open".to_string()) || cb_cmds.contains(&"restore".to_string())
+        {
+            CbAction::Restore
+        } else {
+            CbAction::None
+        };
+        SinkReply { ack, cb }
+    }
+}
 
 #[async_trait::async_trait()]
 impl Sink for CbSink {
@@ -127,41 +297,16 @@ impl Sink for CbSink {
         _serializer: &mut EventSerializer,
         _start: u64,
     ) -> Result<SinkReply> {
+        if let Some(sampler) = self.sampler.as_mut() {
+            return Ok(SinkReply {
+                ack: sampler.sample(),
+                cb: CbAction::None,
+            });
+        }
+        // the values are already decoded, whatever the codec of the source was
         for (value, meta) in event.value_meta_iter() {
             if let Some(cb) = ctx.extract_meta(meta).or_else(|| ctx.extract_meta(value)) {
-                let cb_cmds = if let Some(array) = cb.as_array() {
-                    array
-                        .iter()
-                        .filter_map(|v| v.as_str().map(ToString::to_string))
-                        .collect()
-                } else if let Some(str) = cb.as_str() {
-                    vec![str.to_string()]
-                } else {
-                    vec![]
-                };
-
-                // Acknowledgement tracking
-                let ack = if cb_cmds.contains(&"ack".to_string()) {
-                    SinkAck::Ack
-                } else if cb_cmds.contains(&"fail".to_string()) {
-                    SinkAck::Fail
-                } else {
-                    SinkAck::None
-                };
-
-                // Circuit breaker tracking
-                let cb = if cb_cmds.contains(&"close".to_string())
-                    || cb_cmds.contains(&"trigger".to_string())
-                {
-                    CbAction::Trigger
-                } else if cb_cmds.contains(&"open".to_string())
-                    || cb_cmds.contains(&"restore".to_string())
-                {
-                    CbAction::Restore
-                } else {
-                    CbAction::None
-                };
-                return Ok(SinkReply { ack, cb });
+                return Ok(Self::reply(cb));
             }
         }
         Ok(SinkReply::NONE)
@@ -194,9 +339,66 @@ impl ReceivedCbs {
     }
 }
 
+/// Difference between the expected and the received ids of acks or fails
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Discrepancy {
+    missing: BTreeSet<u64>,
+    unexpected: BTreeSet<u64>,
+}
+
+impl Discrepancy {
+    fn between(expected: &[u64], received: &[u64]) -> Self {
+        let expected: BTreeSet<u64> = expected.iter().copied().collect();
+        let received: BTreeSet<u64> = received.iter().copied().collect();
+        Self {
+            missing: expected.difference(&received).copied().collect(),
+            unexpected: received.difference(&expected).copied().collect(),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.missing.is_empty() && self.unexpected.is_empty()
+    }
+}
+
+/// Spaces out emitted events to approximate a target rate
+#[derive(Debug)]
+struct Pacer {
+    interval_ns: u64,
+    next: Option<u64>,
+}
+
+impl Pacer {
+    fn new(per_sec: u64) -> Self {
+        Self {
+            interval_ns: 1_000_000_000 / per_sec.max(1),
+            next: None,
+        }
+    }
+
+    /// waits until the next event is due
+    ///
+    /// This is cancellation safe, the source manager drops `pull_data` futures
+    /// when receiving control plane messages, so quiescence and shutdown don't wait for us.
+    async fn pace(&mut self) {
+        if let Some(next) = self.next {
+            let now = nanotime();
+            if now < next {
+                async_std::task::sleep(Duration::from_nanos(next - now)).await;
+            }
+        }
+        // don't burst to catch up after pauses
+        self.next = Some(nanotime() + self.interval_ns);
+    }
+}
+
 #[derive(Debug)]
 struct CbSource {
-    file: io::Lines<io::BufReader<File>>,
+    path: PathBuf,
+    file: io::Split<io::BufReader<File>>,
+    // number of times the file has been read completely
+    reads: usize,
+    pacer: Option<Pacer>,
     num_sent: usize,
     last_sent: u64,
     received_cbs: ReceivedCbs,
@@ -208,6 +410,9 @@ struct CbSource {
 
 impl CbSource {
     fn did_receive_all(&self) -> bool {
+        if self.config.repeat.is_forever() {
+            return false;
+        }
         let all_received = if self.config.expect_batched {
             self.received_cbs
                 .max()
@@ -216,13 +421,62 @@ impl CbSource {
         } else {
             self.received_cbs.count() == self.num_sent
         };
-        self.finished && all_received
+        self.finished && all_received && self.discrepancies().is_empty()
+    }
+
+    /// the configured `expected_acks` and `expected_fails` not matching the received ones
+    fn discrepancies(&self) -> Vec<(&'static str, Discrepancy)> {
+        [
+            ("acks", &self.config.expected_acks, &self.received_cbs.ack),
+            (
+                "fails",
+                &self.config.expected_fails,
+                &self.received_cbs.fail,
+            ),
+        ]
+        .into_iter()
+        .filter_map(|(kind, expected, received)| {
+            let discrepancy = Discrepancy::between(expected.as_ref()?, received);
+            if discrepancy.is_empty() {
+                None
+            } else {
+                Some((kind, discrepancy))
+            }
+        })
+        .collect()
+    }
+
+    /// the lines reported on stderr once finished
+    fn report(&self) -> Vec<String> {
+        let mut lines = if self.did_receive_all() {
+            vec!["All required CB events received.".to_string()]
+        } else {
+            vec![format!("Expected CB events up to id {}.", self.last_sent)]
+        };
+        lines.push(format!("Got acks: {:?}", self.received_cbs.ack));
+        lines.push(format!("Got fails: {:?}", self.received_cbs.fail));
+        for (kind, discrepancy) in self.discrepancies() {
+            if !discrepancy.missing.is_empty() {
+                lines.push(format!("Missing {kind}: {:?}", discrepancy.missing));
+            }
+            if !discrepancy.unexpected.is_empty() {
+                lines.push(format!("Unexpected {kind}: {:?}", discrepancy.unexpected));
+            }
+        }
+        lines
     }
-    async fn new(config: &Config, alias: &Alias, kill_switch: KillSwitch) -> Result<Self> {
+    async fn new(
+        config: &Config,
+        alias: &Alias,
+        host: String,
+        kill_switch: KillSwitch,
+    ) -> Result<Self> {
         if let Some(path) = config.path.as_ref() {
-            let file = open(path).await?;
             Ok(Self {
-                file: io::BufReader::new(file).lines(),
+                path: path.clone(),
+                file: Self::open_split(path).await?,
+                reads: 0,
+                pacer: config.pace_per_sec.map(Pacer::new),
                 num_sent: 0,
                 last_sent: 0,
                 received_cbs: ReceivedCbs::default(),
@@ -230,7 +484,7 @@ impl CbSource {
                 config: config.clone(),
                 origin_uri: EventOriginUri {
                     scheme: String::from("tremor-cb"),
-                    host: hostname(),
+                    host,
                     ..EventOriginUri::default()
                 },
                 kill_switch,
@@ -239,17 +493,41 @@ impl CbSource {
             Err(err_connector_def(alias, "Missing path key."))
         }
     }
+
+    /// splits the file into lines of bytes, leaving the decoding to the configured codec
+    async fn open_split(path: &Path) -> Result<io::Split<io::BufReader<File>>> {
+        let file = open(path).await?;
+        Ok(io::BufReader::new(file).split(b'\n'))
+    }
+
+    /// reads the next line, starting over at the beginning of the file as often as configured
+    async fn next_line(&mut self) -> Result<Option<Vec<u8>>> {
+        loop {
+            if let Some(line) = self.file.next().await {
+                return Ok(Some(line?));
+            }
+            self.reads += 1;
+            // don't spin on empty files
+            if self.num_sent == 0 || !self.config.repeat.again(self.reads) {
+                return Ok(None);
+            }
+            self.file = Self::open_split(&self.path).await?;
+        }
+    }
 }
 
 #[async_trait::async_trait()]
 impl Source for CbSource {
     async fn pull_data(&mut self, pull_id: &mut u64, _ctx: &SourceContext) -> Result<SourceReply> {
-        if let Some(line) = self.file.next().await {
+        if let Some(pacer) = self.pacer.as_mut() {
+            pacer.pace().await;
+        }
+        if let Some(line) = self.next_line().await? {
             self.num_sent += 1;
             self.last_sent = self.last_sent.max(*pull_id);
 
             Ok(SourceReply::Data {
-                data: line?.into_bytes(),
+                data: line,
                 meta: None,
                 stream: Some(DEFAULT_STREAM_ID),
                 port: None,
@@ -263,15 +541,9 @@ impl Source for CbSource {
                 async_std::task::sleep(Duration::from_nanos(self.config.timeout)).await;
             }
 
-            if self.did_receive_all() {
-                eprintln!("All required CB events received.");
-                eprintln!("Got acks: {:?}", self.received_cbs.ack);
-                eprintln!("Got fails: {:?}", self.received_cbs.fail);
-            } else {
-                // report failures to stderr and exit with 1
-                eprintln!("Expected CB events up to id {}.", self.last_sent);
-                eprintln!("Got acks: {:?}", self.received_cbs.ack);
-                eprintln!("Got fails: {:?}", self.received_cbs.fail);
+            // report to stderr, failures are reported with the expected and the mismatching ids
+            for line in self.report() {
+                eprintln!("{line}");
             }
             async_std::task::spawn::<_, Result<()>>(async move {
                 kill_switch.stop(ShutdownMode::Graceful).await?;
@@ -316,3 +588,198 @@ impl Source for CbSource {
         false
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(config: &Value, n: usize) -> Result<(usize, usize, usize)> {
+        let config = Config::new(config)?;
+        let mut sampler = Sampler::new(&config).expect("No sampler configured");
+        let (mut acks, mut fails, mut none) = (0, 0, 0);
+        for _ in 0..n {
+            match sampler.sample() {
+                SinkAck::Ack => acks += 1,
+                SinkAck::Fail => fails += 1,
+                SinkAck::None => none += 1,
+            }
+        }
+        Ok((acks, fails, none))
+    }
+
+    #[test]
+    fn sampled_acks() -> Result<()> {
+        assert!(Sampler::new(&Config::new(&literal!({}))?).is_none());
+
+        let (acks, fails, none) = sample(&literal!({"ack_ratio": 0.8, "seed": 42}), 10_000)?;
+        assert!((7_800..=8_200).contains(&acks), "{acks} acks");
+        assert_eq!(10_000 - acks, fails);
+        assert_eq!(0, none);
+
+        let (acks, fails, none) = sample(
+            &literal!({"ack_ratio": 0.5, "fail_ratio": 0.25, "seed": 42}),
+            10_000,
+        )?;
+        assert!((4_800..=5_200).contains(&acks), "{acks} acks");
+        assert!((2_300..=2_700).contains(&fails), "{fails} fails");
+        assert_eq!(10_000 - acks - fails, none);
+
+        // the same seed yields the same distribution
+        assert_eq!(
+            sample(&literal!({"fail_ratio": 0.1, "seed": 23}), 1_000)?,
+            sample(&literal!({"fail_ratio": 0.1, "seed": 23}), 1_000)?
+        );
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn repeat() -> Result<()> {
+        let mut file = tempfile::NamedTempFile::new()?;
+        std::io::Write::write_all(&mut file, b"{\"snot\": 1}\n{\"snot\": 2}\n{\"snot\": 3}\n")?;
+        let path = file.path().display().to_string();
+        let alias = Alias::new("flow", "cb");
+
+        let config = Config::new(&literal!({ "path": path.clone() }))?;
+        assert_eq!(Repeat::Times(1), config.repeat);
+        let config = Config::new(&literal!({ "path": path.clone(), "repeat": "forever" }))?;
+        assert!(config.repeat.is_forever());
+
+        let config = Config::new(&literal!({ "path": path, "repeat": 2 }))?;
+        let mut source = CbSource::new(&config, &alias, hostname(), KillSwitch::dummy()).await?;
+        let mut lines = Vec::new();
+        while let Some(line) = source.next_line().await? {
+            // simulate pull_data accounting
+            source.num_sent += 1;
+            lines.push(line);
+        }
+        assert_eq!(6, lines.len());
+        assert_eq!(lines[0..3], lines[3..6]);
+        assert_eq!(2, source.reads);
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn msgpack_lines() -> Result<()> {
+        let mut codec = crate::codec::resolve(&"msgpack".into())?;
+        let mut file = tempfile::NamedTempFile::new()?;
+        for cb in ["ack", "fail"] {
+            let mut line = codec.encode(&literal!({ "cb": cb }))?;
+            line.push(b'\n');
+            std::io::Write::write_all(&mut file, &line)?;
+        }
+        let path = file.path().display().to_string();
+        let config = Config::new(&literal!({ "path": path }))?;
+        let mut source = CbSource::new(
+            &config,
+            &Alias::new("flow", "cb"),
+            hostname(),
+            KillSwitch::dummy(),
+        )
+        .await?;
+
+        let mut replies = Vec::new();
+        while let Some(mut line) = source.next_line().await? {
+            source.num_sent += 1;
+            let value = codec.decode(&mut line, 0)?.ok_or("nothing decoded")?;
+            replies.push(CbSink::reply(value.get("cb").ok_or("missing cb")?).ack);
+        }
+        assert_eq!(vec![SinkAck::Ack, SinkAck::Fail], replies);
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn expected_ids() -> Result<()> {
+        let mut file = tempfile::NamedTempFile::new()?;
+        std::io::Write::write_all(&mut file, b"{\"cb\": \"ack\"}\n")?;
+        let path = file.path().display().to_string();
+        let config = Config::new(&literal!({
+            "path": path,
+            "expected_acks": [0, 1, 2],
+            "expected_fails": [3],
+        }))?;
+        let mut source = CbSource::new(
+            &config,
+            &Alias::new("flow", "cb"),
+            hostname(),
+            KillSwitch::dummy(),
+        )
+        .await?;
+        source.finished = true;
+        source.num_sent = 4;
+        source.last_sent = 3;
+        source.received_cbs.ack = vec![0, 2, 3];
+        source.received_cbs.fail = vec![1];
+
+        // all events were acked or failed, but not the expected ones
+        assert!(!source.did_receive_all());
+        assert_eq!(
+            vec![
+                (
+                    "acks",
+                    Discrepancy {
+                        missing: BTreeSet::from([1]),
+                        unexpected: BTreeSet::from([3]),
+                    }
+                ),
+                (
+                    "fails",
+                    Discrepancy {
+                        missing: BTreeSet::from([3]),
+                        unexpected: BTreeSet::from([1]),
+                    }
+                ),
+            ],
+            source.discrepancies()
+        );
+        let report = source.report();
+        assert_eq!("Expected CB events up to id 3.", report[0]);
+        assert!(report.contains(&"Missing acks: {1}".to_string()));
+        assert!(report.contains(&"Unexpected acks: {3}".to_string()));
+        assert!(report.contains(&"Missing fails: {3}".to_string()));
+        assert!(report.contains(&"Unexpected fails: {1}".to_string()));
+
+        source.received_cbs.ack = vec![2, 1, 0];
+        source.received_cbs.fail = vec![3];
+        assert!(source.did_receive_all());
+        assert_eq!("All required CB events received.", source.report()[0]);
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn pace() -> Result<()> {
+        let mut pacer = Pacer::new(100);
+        let start = std::time::Instant::now();
+        for _ in 0..20 {
+            pacer.pace().await;
+        }
+        // the first event is emitted right away
+        let elapsed = start.elapsed();
+        assert!(
+            elapsed >= Duration::from_millis(190) && elapsed < Duration::from_millis(500),
+            "{elapsed:?} elapsed"
+        );
+
+        let alias = Alias::new("flow", "cb");
+        let res = Builder::default()
+            .build_cfg(
+                &alias,
+                &ConnectorConfig {
+                    connector_type: "cb".into(),
+                    codec: None,
+                    config: None,
+                    preprocessors: None,
+                    postprocessors: None,
+                    reconnect: crate::config::Reconnect::None,
+                    metrics_interval_s: None,
+                    max_in_flight: None,
+                    in_flight_timeout_ms: None,
+                    instance_host: None,
+                },
+                &literal!({"path": "snot", "pace_per_sec": 0}),
+                &KillSwitch::dummy(),
+            )
+            .await;
+        assert!(res.is_err());
+        Ok(())
+    }
+}
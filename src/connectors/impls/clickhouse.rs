@@ -15,14 +15,20 @@
 mod conversion;
 
 use std::fmt::{self, Display, Formatter};
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
 
 use crate::connectors::prelude::*;
+use crate::connectors::sink::retry::{DeadLetter, RetryConfig, RetrySink};
+use crate::connectors::utils::diagnostics::{ConnectDiagnostic, ConnectStep};
 
+use async_std::channel::{bounded, Receiver, Sender};
 use clickhouse_rs::{
     errors::Error as CError,
     types::{DateTimeType, SqlType},
     Block, ClientHandle, Pool,
 };
+use regex::Regex;
 
 #[derive(Default, Debug)]
 pub(crate) struct Builder {}
@@ -41,17 +47,38 @@ impl ConnectorBuilder for Builder {
         _kill_switch: &KillSwitch,
     ) -> Result<Box<dyn Connector>> {
         let config = ClickhouseConfig::new(connector_config)?;
-
-        Ok(Box::new(Clickhouse { config }))
+        let (error_tx, error_rx) = bounded(crate::QSIZE.load(Ordering::Relaxed));
+
+        Ok(Box::new(Clickhouse {
+            config,
+            error_tx,
+            error_rx,
+            source_is_connected: Arc::new(AtomicBool::new(false)),
+        }))
     }
 }
 
 pub(crate) struct Clickhouse {
     config: ClickhouseConfig,
+    error_tx: Sender<SourceReply>,
+    error_rx: Receiver<SourceReply>,
+    source_is_connected: Arc<AtomicBool>,
 }
 
 #[async_trait::async_trait]
 impl Connector for Clickhouse {
+    async fn create_source(
+        &mut self,
+        source_context: SourceContext,
+        builder: SourceManagerBuilder,
+    ) -> Result<Option<SourceAddr>> {
+        let source = ClickhouseSource {
+            source_is_connected: self.source_is_connected.clone(),
+            error_rx: self.error_rx.clone(),
+        };
+        builder.spawn(source, source_context).map(Some)
+    }
+
     async fn create_sink(
         &mut self,
         sink_context: SinkContext,
@@ -65,14 +92,42 @@ impl Connector for Clickhouse {
             .map(|Column { name, type_ }| (name.clone(), type_.clone()))
             .collect();
         let table = self.config.table.clone();
+        let origin_uri = EventOriginUri {
+            scheme: "tremor-clickhouse".to_string(),
+            host: self.config.url.host_str().unwrap_or_default().to_string(),
+            port: self.config.url.port(),
+            path: vec![table.clone()],
+        };
 
+        let endpoint = format!(
+            "{}:{}",
+            origin_uri.host,
+            self.config.url.port_or_known_default().unwrap_or(9000)
+        );
         let sink = ClickhouseSink {
             db_url,
+            endpoint,
             handle: None,
             table,
             columns,
+            dry_run: self.config.dry_run,
+            origin_uri: origin_uri.clone(),
+            error_tx: self.error_tx.clone(),
+            source_is_connected: self.source_is_connected.clone(),
+            // with retries, the whole event ends up in the dead letter instead
+            reject_rows: self.config.retry.is_none(),
         };
-        builder.spawn(sink, sink_context).map(Some)
+        if let Some(retry) = self.config.retry.clone() {
+            let dead_letter = DeadLetter {
+                tx: self.error_tx.clone(),
+                origin_uri,
+                source_is_connected: self.source_is_connected.clone(),
+            };
+            let sink = RetrySink::new(retry, Some(dead_letter), builder.reply_tx(), |_| sink);
+            builder.spawn(sink, sink_context).map(Some)
+        } else {
+            builder.spawn(sink, sink_context).map(Some)
+        }
     }
 
     fn codec_requirements(&self) -> CodecReq {
@@ -100,6 +155,12 @@ struct ClickhouseConfig {
     database: Option<String>,
     table: String,
     columns: Vec<Column>,
+    /// convert events into rows, but log them instead of inserting them
+    #[serde(default)]
+    dry_run: bool,
+    /// retry failed inserts, events failing all retries are emitted on the `err` port
+    #[serde(default)]
+    retry: Option<RetryConfig>,
 }
 
 pub(crate) struct ClickHouseDefaults;
@@ -141,11 +202,148 @@ struct Column {
     type_: DummySqlType,
 }
 
+/// Emits the rows rejected by ClickHouse on the `err` port
+struct ClickhouseSource {
+    source_is_connected: Arc<AtomicBool>,
+    error_rx: Receiver<SourceReply>,
+}
+
+#[async_trait::async_trait]
+impl Source for ClickhouseSource {
+    async fn pull_data(&mut self, _pull_id: &mut u64, _ctx: &SourceContext) -> Result<SourceReply> {
+        Ok(self.error_rx.recv().await?)
+    }
+
+    async fn on_cb_open(&mut self, _ctx: &SourceContext) -> Result<()> {
+        // rejected rows are only sent once we know some pipeline is connected to us
+        self.source_is_connected.store(true, Ordering::Release);
+        Ok(())
+    }
+
+    fn is_transactional(&self) -> bool {
+        false
+    }
+
+    fn asynchronous(&self) -> bool {
+        true
+    }
+}
+
+lazy_static! {
+    // ALLOW: we know these regexes are valid
+    static ref CODE_RE: Regex = Regex::new(r"Code: (\d+)").expect("Invalid Regex");
+    // ALLOW: we know these regexes are valid
+    static ref NAME_RE: Regex = Regex::new(r"\(([A-Z][A-Z0-9_]+)\)\s*$").expect("Invalid Regex");
+    // ALLOW: we know these regexes are valid
+    static ref ROW_RE: Regex = Regex::new(r"(?i)\brow (\d+)").expect("Invalid Regex");
+    // ALLOW: we know these regexes are valid
+    static ref COLUMN_RE: Regex =
+        Regex::new(r"(?:name: |[Cc]olumn `?)([A-Za-z_][A-Za-z0-9_.]*)").expect("Invalid Regex");
+}
+
+/// An error reported by ClickHouse, with the offending row and column if the message names them
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ClickhouseError {
+    code: Option<u32>,
+    name: Option<String>,
+    message: String,
+    /// index of the offending row in the inserted block
+    row: Option<usize>,
+    column: Option<String>,
+}
+
+impl ClickhouseError {
+    /// parses an error message, like `Code: 60. DB::Exception: Table default.snot doesn't exist. (UNKNOWN_TABLE)`
+    fn parse(error: &str) -> Self {
+        let capture = |re: &Regex, s: &str| {
+            re.captures(s)
+                .and_then(|c| c.get(1))
+                .map(|c| c.as_str().to_string())
+        };
+        // the first line carries the message, following lines the details
+        let first_line = error.lines().next().unwrap_or_default();
+        let mut message = first_line;
+        if let Some(code) = CODE_RE.find(message) {
+            message = message[code.end()..].trim_start_matches(['.', ' ']);
+        }
+        message = message.strip_prefix("DB::Exception: ").unwrap_or(message);
+        if let Some(name) = NAME_RE.find(message) {
+            message = &message[..name.start()];
+        }
+        Self {
+            code: capture(&CODE_RE, error).and_then(|code| code.parse().ok()),
+            name: capture(&NAME_RE, first_line),
+            message: message.trim_end_matches(['.', ' ']).to_string(),
+            // ClickHouse counts rows from 1
+            row: capture(&ROW_RE, error)
+                .and_then(|row| row.parse::<usize>().ok())
+                .and_then(|row| row.checked_sub(1)),
+            column: capture(&COLUMN_RE, error),
+        }
+    }
+
+    fn to_value(&self) -> Value<'static> {
+        literal!({
+            "code": self.code,
+            "name": self.name.clone(),
+            "message": self.message.clone(),
+            "row": self.row,
+            "column": self.column.clone(),
+        })
+    }
+}
+
+impl From<&CError> for ClickhouseError {
+    fn from(e: &CError) -> Self {
+        match e {
+            // the message of server errors doesn't repeat the code
+            CError::Server(e) => Self {
+                code: Some(e.code),
+                ..Self::parse(&e.message)
+            },
+            e => Self::parse(&e.to_string()),
+        }
+    }
+}
+
+/// The step of connecting a server error with `code` was raised at
+fn connect_step_of(code: u32) -> ConnectStep {
+    match code {
+        // UNKNOWN_USER, WRONG_PASSWORD, REQUIRED_PASSWORD, IP_ADDRESS_NOT_ALLOWED,
+        // AUTHENTICATION_FAILED
+        192 | 193 | 194 | 195 | 516 => ConnectStep::Auth,
+        // UNKNOWN_DATABASE
+        81 => ConnectStep::Schema,
+        _ => ConnectStep::Tcp,
+    }
+}
+
+impl Display for ClickhouseError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        if let Some(code) = self.code {
+            write!(f, "Code {code}: ")?;
+        }
+        write!(f, "{}", self.message)?;
+        if let Some(name) = self.name.as_ref() {
+            write!(f, " ({name})")?;
+        }
+        Ok(())
+    }
+}
+
 pub(crate) struct ClickhouseSink {
     db_url: String,
+    /// `host:port` of the server, without the credentials in `db_url`
+    endpoint: String,
     handle: Option<ClientHandle>,
     table: String,
     columns: Vec<(String, DummySqlType)>,
+    dry_run: bool,
+    origin_uri: EventOriginUri,
+    error_tx: Sender<SourceReply>,
+    source_is_connected: Arc<AtomicBool>,
+    /// emit the row named by an insert error on the `err` port
+    reject_rows: bool,
 }
 
 #[async_trait::async_trait]
@@ -157,9 +355,18 @@ impl Sink for ClickhouseSink {
             Err(e) => {
                 return match e {
                     CError::Driver(_) | CError::Io(_) | CError::Connection(_) => {
+                        let diagnostic =
+                            ConnectDiagnostic::new(&self.endpoint, ConnectStep::Tcp, &e);
+                        warn!("{ctx} {diagnostic}");
                         ctx.notifier.connection_lost().await?;
                         Ok(false)
                     }
+                    CError::Server(ref server_error) => Err(ConnectDiagnostic::new(
+                        &self.endpoint,
+                        connect_step_of(server_error.code),
+                        &ClickhouseError::from(&e),
+                    )
+                    .into()),
                     _ => Err(Error::from(e)),
                 }
             }
@@ -174,15 +381,10 @@ impl Sink for ClickhouseSink {
         &mut self,
         _input: &str,
         event: Event,
-        _ctx: &SinkContext,
+        ctx: &SinkContext,
         _serializer: &mut EventSerializer,
         _start: u64,
     ) -> Result<SinkReply> {
-        let handle = self
-            .handle
-            .as_mut()
-            .ok_or_else(|| Error::from(ErrorKind::NoClickHouseClientAvailable))?;
-
         let mut block = Block::with_capacity(event.len());
 
         for value in event.value_iter() {
@@ -190,8 +392,39 @@ impl Sink for ClickhouseSink {
             block.push(row)?;
         }
 
+        if self.dry_run {
+            info!(
+                "{ctx} Dry run, not inserting {} rows into {}",
+                block.row_count(),
+                self.table
+            );
+            debug!("{ctx} Dry run block:{:#?}", block);
+            return Ok(SinkReply::NONE);
+        }
+
+        let handle = self
+            .handle
+            .as_mut()
+            .ok_or_else(|| Error::from(ErrorKind::NoClickHouseClientAvailable))?;
+
         debug!("Inserting block:{:#?}", block);
-        handle.insert(&self.table, block).await?;
+        if let Err(e) = handle.insert(&self.table, block).await {
+            if matches!(e, CError::Driver(_) | CError::Io(_) | CError::Connection(_)) {
+                return Err(e.into());
+            }
+            // inserts over the native protocol are atomic, so there is no partial success
+            // and the whole batch is failed, but the row ClickHouse complained about is rejected
+            let error = ClickhouseError::from(&e);
+            error!("{ctx} Error inserting into {}: {error}", self.table);
+            if let Some(value) = error
+                .row
+                .filter(|_| self.reject_rows)
+                .and_then(|row| event.value_iter().nth(row))
+            {
+                self.reject(value, &event, &error).await?;
+            }
+            return Ok(SinkReply::FAIL);
+        }
 
         Ok(SinkReply::NONE)
     }
@@ -202,6 +435,35 @@ impl Sink for ClickhouseSink {
 }
 
 impl ClickhouseSink {
+    /// sends a row rejected by ClickHouse to the `err` port, with the parsed error in its metadata
+    async fn reject(
+        &self,
+        value: &Value<'_>,
+        event: &Event,
+        error: &ClickhouseError,
+    ) -> Result<()> {
+        if !self.source_is_connected.load(Ordering::Acquire) {
+            return Ok(());
+        }
+        let mut meta = literal!({
+            "clickhouse": {
+                "table": self.table.clone(),
+                "error": error.to_value()
+            }
+        });
+        if let Some(correlation) = event.correlation_meta() {
+            meta.try_insert("correlation", correlation);
+        }
+        let reply = SourceReply::Structured {
+            origin_uri: self.origin_uri.clone(),
+            payload: (value.clone_static(), meta).into(),
+            stream: DEFAULT_STREAM_ID,
+            port: Some(ERR),
+        };
+        self.error_tx.send(reply).await?;
+        Ok(())
+    }
+
     fn clickhouse_row_of(
         columns: &[(String, DummySqlType)],
         input: &tremor_value::Value,
@@ -489,4 +751,156 @@ mod tests {
             datetime64_nanos :: DateTime64Nanos  => &DateTime(DateTimeType::DateTime64(9, UTC)),
         }
     }
+
+    mod dry_run {
+        use super::*;
+        use crate::connectors::reconnect::ConnectionLostNotifier;
+
+        async fn insert(sink: &mut ClickhouseSink, value: Value<'static>) -> Result<SinkReply> {
+            let (rx, _tx) = async_std::channel::unbounded();
+            let alias = Alias::new("flow", "connector");
+            let ctx = SinkContext {
+                uid: Default::default(),
+                alias: alias.clone(),
+                connector_type: "clickhouse".into(),
+                quiescence_beacon: Default::default(),
+                notifier: ConnectionLostNotifier::new(rx),
+            };
+            let mut serializer = EventSerializer::new(
+                None,
+                CodecReq::Structured,
+                vec![],
+                &"clickhouse".into(),
+                &alias,
+            )?;
+            let event = Event {
+                data: (value, Value::object()).into(),
+                ..Event::default()
+            };
+            sink.on_event("", event, &ctx, &mut serializer, 0).await
+        }
+
+        fn clickhouse_sink(dry_run: bool) -> ClickhouseSink {
+            let (error_tx, _error_rx) = async_std::channel::unbounded();
+            ClickhouseSink {
+                db_url: String::new(),
+                endpoint: "localhost:9000".to_string(),
+                handle: None,
+                table: "people".to_string(),
+                columns: vec![
+                    ("name".to_string(), DummySqlType::String),
+                    ("age".to_string(), DummySqlType::UInt8),
+                ],
+                dry_run,
+                origin_uri: EventOriginUri::default(),
+                error_tx,
+                source_is_connected: Arc::new(AtomicBool::new(false)),
+                reject_rows: true,
+            }
+        }
+
+        #[async_std::test]
+        async fn does_not_insert() -> Result<()> {
+            // without a client, an insert would fail
+            let mut sink = clickhouse_sink(true);
+            let reply = insert(&mut sink, literal!({"name": "snot", "age": 42})).await?;
+            assert_eq!(SinkReply::NONE, reply);
+
+            let mut sink = clickhouse_sink(false);
+            assert!(insert(&mut sink, literal!({"name": "snot", "age": 42}))
+                .await
+                .is_err());
+            Ok(())
+        }
+
+        #[async_std::test]
+        async fn surfaces_mapping_errors() {
+            let mut sink = clickhouse_sink(true);
+            assert!(
+                insert(&mut sink, literal!({"name": "snot", "age": "badger"}))
+                    .await
+                    .is_err()
+            );
+            assert!(insert(&mut sink, literal!("snot")).await.is_err());
+        }
+    }
+
+    mod error_parsing {
+        use super::*;
+
+        #[test]
+        fn connect_steps() {
+            assert_eq!(ConnectStep::Auth, connect_step_of(516));
+            assert_eq!(ConnectStep::Auth, connect_step_of(192));
+            assert_eq!(ConnectStep::Schema, connect_step_of(81));
+            assert_eq!(ConnectStep::Tcp, connect_step_of(210));
+        }
+
+        #[test]
+        fn unknown_table() {
+            let error = ClickhouseError::parse(
+                "Code: 60. DB::Exception: Table default.people doesn't exist. (UNKNOWN_TABLE)",
+            );
+            assert_eq!(
+                ClickhouseError {
+                    code: Some(60),
+                    name: Some("UNKNOWN_TABLE".to_string()),
+                    message: "Table default.people doesn't exist".to_string(),
+                    row: None,
+                    column: None,
+                },
+                error
+            );
+            assert_eq!(
+                "Code 60: Table default.people doesn't exist (UNKNOWN_TABLE)",
+                error.to_string()
+            );
+        }
+
+        #[test]
+        fn offending_row() {
+            let error = ClickhouseError::parse(
+                "Code: 27. DB::Exception: Cannot parse input: expected '\\t' before: 'snot': (at row 3)\n\
+                 Row 3:\n\
+                 Column 0,   name: age,  type: UInt8,  ERROR: text \"snot\" is not like UInt8\n\
+                 . (CANNOT_PARSE_INPUT_ASSERTION_FAILED)",
+            );
+            assert_eq!(Some(27), error.code);
+            // the name is only taken from the first line
+            assert_eq!(None, error.name);
+            assert_eq!(Some(2), error.row);
+            assert_eq!(Some("age".to_string()), error.column);
+            assert_eq!(
+                "Cannot parse input: expected '\\t' before: 'snot': (at row 3)",
+                error.message
+            );
+        }
+
+        #[test]
+        fn missing_column() {
+            let error = ClickhouseError::parse(
+                "DB::Exception: No such column snot in table default.people (NO_SUCH_COLUMN_IN_TABLE)",
+            );
+            assert_eq!(None, error.code);
+            assert_eq!(Some("NO_SUCH_COLUMN_IN_TABLE".to_string()), error.name);
+            assert_eq!("No such column snot in table default.people", error.message);
+            assert_eq!(None, error.row);
+            assert_eq!(Some("snot".to_string()), error.column);
+        }
+
+        #[test]
+        fn unstructured() {
+            let error = ClickhouseError::parse("Connection reset by peer");
+            assert_eq!(
+                ClickhouseError {
+                    code: None,
+                    name: None,
+                    message: "Connection reset by peer".to_string(),
+                    row: None,
+                    column: None,
+                },
+                error
+            );
+        }
+    }
 }
@@ -39,7 +39,7 @@ impl ConnectorBuilder for Builder {
     async fn build_cfg(
         &self,
         id: &Alias,
-        _: &ConnectorConfig,
+        connector_config: &ConnectorConfig,
         raw: &Value,
         _kill_switch: &KillSwitch,
     ) -> Result<Box<dyn Connector>> {
@@ -62,13 +62,18 @@ impl ConnectorBuilder for Builder {
             return Err(err_connector_def(id, "missing `entries` array"));
         };
 
-        Ok(Box::new(Crononome { entries }))
+        Ok(Box::new(Crononome {
+            entries,
+            instance_host: connector_config.instance_host(),
+        }))
     }
 }
 
 #[derive(Clone, Debug)]
 pub(crate) struct Crononome {
     entries: Vec<CronEntryInt>,
+    /// host of the origin URI of the emitted events
+    instance_host: String,
 }
 
 #[async_trait::async_trait()]
@@ -78,7 +83,7 @@ impl Connector for Crononome {
         source_context: SourceContext,
         builder: SourceManagerBuilder,
     ) -> Result<Option<SourceAddr>> {
-        let source = CrononomeSource::new(self.entries.clone());
+        let source = CrononomeSource::new(self.entries.clone(), self.instance_host.clone());
         builder.spawn(source, source_context).map(Some)
     }
 
@@ -93,13 +98,13 @@ struct CrononomeSource {
 }
 
 impl CrononomeSource {
-    fn new(entries: Vec<CronEntryInt>) -> Self {
+    fn new(entries: Vec<CronEntryInt>, host: String) -> Self {
         Self {
             entries,
             cq: ChronomicQueue::default(),
             origin_uri: EventOriginUri {
                 scheme: "tremor-crononome".to_string(),
-                host: hostname(),
+                host,
                 port: None,
                 path: vec![],
             },
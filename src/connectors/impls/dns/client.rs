@@ -36,17 +36,23 @@ impl ConnectorBuilder for Builder {
     async fn build(
         &self,
         _id: &Alias,
-        _raw_config: &ConnectorConfig,
+        raw_config: &ConnectorConfig,
         _kill_switch: &KillSwitch,
     ) -> Result<Box<dyn Connector>> {
         let (tx, rx) = bounded(128);
-        Ok(Box::new(Client { tx, rx }))
+        Ok(Box::new(Client {
+            tx,
+            rx,
+            instance_host: raw_config.instance_host(),
+        }))
     }
 }
 
 pub(crate) struct Client {
     tx: Sender<SourceReply>,
     rx: Receiver<SourceReply>,
+    /// host of the origin URI of the responses
+    instance_host: String,
 }
 
 #[async_trait::async_trait()]
@@ -71,7 +77,7 @@ impl Connector for Client {
         builder: SinkManagerBuilder,
     ) -> Result<Option<SinkAddr>> {
         // issues DNS queries and forwards the responses to the source
-        let s = DnsSink::new(self.tx.clone());
+        let s = DnsSink::new(self.tx.clone(), self.instance_host.clone());
         builder.spawn(s, sink_context).map(Some)
     }
 }
@@ -84,10 +90,10 @@ struct DnsSink {
 }
 
 impl DnsSink {
-    fn new(tx: Sender<SourceReply>) -> Self {
+    fn new(tx: Sender<SourceReply>, host: String) -> Self {
         let origin_uri = EventOriginUri {
             scheme: "tremor-dns".to_string(),
-            host: hostname(),
+            host,
             port: None,
             path: Vec::new(),
         };
@@ -12,7 +12,12 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::{ffi::OsStr, path::PathBuf};
+use std::{
+    collections::{HashMap, HashSet},
+    ffi::OsStr,
+    fmt::Write,
+    path::{Path, PathBuf},
+};
 
 use crate::connectors::prelude::*;
 use async_compression::futures::bufread::XzDecoder;
@@ -20,8 +25,13 @@ use async_std::{
     fs::{File as FSFile, OpenOptions},
     io::BufReader,
 };
+use chrono::{
+    format::{Item, StrftimeItems},
+    TimeZone, Utc,
+};
 use futures::{AsyncRead, AsyncReadExt, AsyncWriteExt};
-use tremor_common::asy::file;
+use tremor_common::{asy::file, time::nanotime};
+use tremor_pipeline::SignalKind;
 
 const URL_SCHEME: &str = "tremor-file";
 
@@ -66,27 +76,137 @@ impl Mode {
 #[derive(Deserialize, Debug, Clone)]
 #[serde(deny_unknown_fields)]
 pub(crate) struct Config {
-    /// path to the file
+    /// path to the file, when writing it can contain placeholders resolved for each event:
+    /// `{ingest:<format>}` for the ingest time formatted with the given strftime format
+    /// and `{<field>}` for the value of the given (dotted) field of the event
     pub(crate) path: PathBuf,
     /// how to interface with the file
     pub(crate) mode: Mode, // whether we read or write (in various forms)
     /// chunk_size to read from the file
     #[serde(default = "default_buf_size")]
     pub(crate) chunk_size: usize,
+    /// move a file to `<path>.<n>` once it reaches this size in bytes and continue in a new file
+    #[serde(default)]
+    pub(crate) rotate_size: Option<u64>,
+    /// move a file to `<path>.<n>` once it has been open for this many seconds and continue in a new file
+    #[serde(default)]
+    pub(crate) rotate_interval_s: Option<u64>,
 }
 
 impl ConfigImpl for Config {}
 
+/// A part of a file path
+#[derive(Debug, Clone, PartialEq)]
+enum PathSegment {
+    /// used as it is
+    Literal(String),
+    /// the ingest time of the event, formatted with the given strftime format
+    Ingest(String),
+    /// the value of the given field of the event
+    Field(Vec<String>),
+}
+
+/// A file path with placeholders, resolved for each event
+#[derive(Debug, Clone, PartialEq)]
+struct PathTemplate {
+    segments: Vec<PathSegment>,
+}
+
+impl PathTemplate {
+    fn parse(path: &str) -> Result<Self> {
+        let mut segments = Vec::new();
+        let mut rest = path;
+        while let Some(start) = rest.find('{') {
+            if start > 0 {
+                segments.push(PathSegment::Literal(rest[..start].to_string()));
+            }
+            let end = rest[start..]
+                .find('}')
+                .ok_or_else(|| Error::from(format!("Unterminated placeholder in path `{path}`")))?
+                + start;
+            let placeholder = &rest[start + 1..end];
+            if let Some(format) = placeholder.strip_prefix("ingest:") {
+                if StrftimeItems::new(format).any(|item| item == Item::Error) {
+                    return Err(format!("Invalid time format `{format}` in path `{path}`").into());
+                }
+                segments.push(PathSegment::Ingest(format.to_string()));
+            } else if placeholder.is_empty() {
+                return Err(format!("Empty placeholder in path `{path}`").into());
+            } else {
+                segments.push(PathSegment::Field(
+                    placeholder.split('.').map(ToString::to_string).collect(),
+                ));
+            }
+            rest = &rest[end + 1..];
+        }
+        if !rest.is_empty() {
+            segments.push(PathSegment::Literal(rest.to_string()));
+        }
+        Ok(Self { segments })
+    }
+
+    /// true if the path doesn't contain any placeholders
+    fn is_static(&self) -> bool {
+        self.segments
+            .iter()
+            .all(|segment| matches!(segment, PathSegment::Literal(_)))
+    }
+
+    fn render(&self, value: &Value, ingest_ns: u64) -> Result<PathBuf> {
+        let mut path = String::new();
+        for segment in &self.segments {
+            match segment {
+                PathSegment::Literal(literal) => path.push_str(literal),
+                PathSegment::Ingest(format) => {
+                    let ingest = Utc.timestamp_nanos(i64::try_from(ingest_ns).unwrap_or(i64::MAX));
+                    write!(path, "{}", ingest.format(format))
+                        .map_err(|_| Error::from(format!("Invalid time format `{format}`")))?;
+                }
+                PathSegment::Field(keys) => {
+                    let field = keys
+                        .iter()
+                        .try_fold(value, |v, key| v.get(key.as_str()))
+                        .ok_or_else(|| {
+                            Error::from(format!("Missing field `{}` for the path", keys.join(".")))
+                        })?;
+                    // field values must not leave the directory they are used in
+                    let field = field
+                        .as_str()
+                        .map_or_else(|| field.to_string(), ToString::to_string)
+                        .replace(['/', '\\'], "_");
+                    if field.is_empty() || field == "." || field == ".." {
+                        return Err(format!(
+                            "Invalid value `{field}` of field `{}` for the path",
+                            keys.join(".")
+                        )
+                        .into());
+                    }
+                    path.push_str(&field);
+                }
+            }
+        }
+        Ok(PathBuf::from(path))
+    }
+}
+
 /// file connector
 pub(crate) struct File {
     config: Config,
+    path: PathTemplate,
+    /// host of the origin URI of the events read
+    instance_host: String,
 }
 
 /// builder for file connector
 #[derive(Default, Debug)]
 pub(crate) struct Builder {}
 
-impl Builder {}
+impl Builder {
+    const READ_ROTATION: &'static str =
+        "`rotate_size` and `rotate_interval_s` require writing to the file.";
+    const NO_ROTATE_SIZE: &'static str = "`rotate_size` needs to be at least 1.";
+    const NO_ROTATE_INTERVAL: &'static str = "`rotate_interval_s` needs to be at least 1.";
+}
 
 #[async_trait::async_trait]
 impl ConnectorBuilder for Builder {
@@ -96,13 +216,38 @@ impl ConnectorBuilder for Builder {
 
     async fn build_cfg(
         &self,
-        _: &Alias,
-        _: &ConnectorConfig,
+        alias: &Alias,
+        connector_config: &ConnectorConfig,
         config: &Value,
         _kill_switch: &KillSwitch,
     ) -> Result<Box<dyn Connector>> {
         let config = Config::new(config)?;
-        Ok(Box::new(File { config }))
+        let rotation = config.rotate_size.is_some() || config.rotate_interval_s.is_some();
+        if config.mode == Mode::Read && rotation {
+            return Err(err_connector_def(alias, Self::READ_ROTATION));
+        }
+        if config.rotate_size == Some(0) {
+            return Err(err_connector_def(alias, Self::NO_ROTATE_SIZE));
+        }
+        if config.rotate_interval_s == Some(0) {
+            return Err(err_connector_def(alias, Self::NO_ROTATE_INTERVAL));
+        }
+        // placeholders are only resolved when writing
+        let path = if config.mode == Mode::Read {
+            PathTemplate {
+                segments: vec![PathSegment::Literal(
+                    config.path.to_string_lossy().into_owned(),
+                )],
+            }
+        } else {
+            PathTemplate::parse(&config.path.to_string_lossy())
+                .map_err(|e| err_connector_def(alias, &e))?
+        };
+        Ok(Box::new(File {
+            config,
+            path,
+            instance_host: connector_config.instance_host(),
+        }))
     }
 }
 
@@ -116,7 +261,7 @@ impl Connector for File {
         if self.config.mode == Mode::Read {
             Ok(None)
         } else {
-            let sink = FileSink::new(self.config.clone());
+            let sink = FileSink::new(self.config.clone(), self.path.clone());
             builder.spawn(sink, sink_context).map(Some)
         }
     }
@@ -127,7 +272,7 @@ impl Connector for File {
         builder: SourceManagerBuilder,
     ) -> Result<Option<SourceAddr>> {
         if self.config.mode == Mode::Read {
-            let source = FileSource::new(self.config.clone());
+            let source = FileSource::new(self.config.clone(), self.instance_host.clone());
             builder.spawn(source, source_context).map(Some)
         } else {
             Ok(None)
@@ -150,11 +295,11 @@ struct FileSource {
 }
 
 impl FileSource {
-    fn new(config: Config) -> Self {
+    fn new(config: Config, host: String) -> Self {
         let buf = vec![0; config.chunk_size];
         let origin_uri = EventOriginUri {
             scheme: URL_SCHEME.to_string(),
-            host: hostname(),
+            host,
             port: None,
             path: vec![config.path.display().to_string()],
         };
@@ -243,36 +388,106 @@ impl Source for FileSource {
     }
 }
 
+/// A file the sink writes to
+struct OpenFile {
+    file: FSFile,
+    /// size in bytes
+    size: u64,
+    /// when the file was opened, in nanoseconds
+    opened_at: u64,
+    /// if the file has been written to since the last flush
+    dirty: bool,
+}
+
 struct FileSink {
     config: Config,
-    file: Option<FSFile>,
+    path: PathTemplate,
+    files: HashMap<PathBuf, OpenFile>,
+    /// files opened before, they are only appended to when opened again
+    opened: HashSet<PathBuf>,
 }
 
 impl FileSink {
-    fn new(config: Config) -> Self {
-        Self { config, file: None }
+    fn new(config: Config, path: PathTemplate) -> Self {
+        Self {
+            config,
+            path,
+            files: HashMap::new(),
+            opened: HashSet::new(),
+        }
+    }
+
+    /// the open file at `path`, opening it if necessary
+    async fn file(&mut self, path: &Path) -> Result<&mut OpenFile> {
+        if !self.files.contains_key(path) {
+            let mode = if self.opened.contains(path) {
+                // if we have already opened the file successfully once
+                // we should not truncate it again or overwrite, but indeed append
+                // otherwise the reconnect logic will lead to unwanted effects
+                // e.g. if a simple write failed temporarily
+                &Mode::Append
+            } else {
+                &self.config.mode
+            };
+            debug!(
+                "[Sink::file] opening file {} with options {:?}",
+                path.to_string_lossy(),
+                mode
+            );
+            if !self.path.is_static() {
+                if let Some(parent) = path.parent() {
+                    async_std::fs::create_dir_all(parent).await?;
+                }
+            }
+            let file = file::open_with(path, &mut mode.as_open_options()).await?;
+            let size = file.metadata().await?.len();
+            self.opened.insert(path.to_path_buf());
+            self.files.insert(
+                path.to_path_buf(),
+                OpenFile {
+                    file,
+                    size,
+                    opened_at: nanotime(),
+                    dirty: false,
+                },
+            );
+        }
+        self.files
+            .get_mut(path)
+            .ok_or_else(|| Error::from("No file available."))
+    }
+
+    /// closes the file at `path` and moves it to the first free `<path>.<n>`
+    async fn rotate(&mut self, path: &Path) -> Result<()> {
+        if let Some(mut open) = self.files.remove(path) {
+            open.file.flush().await?;
+            open.file.sync_all().await?;
+        }
+        let mut n = 1_u64;
+        loop {
+            let target = PathBuf::from(format!("{}.{n}", path.display()));
+            if async_std::fs::metadata(&target).await.is_err() {
+                debug!(
+                    "[Sink::file] rotating file {} to {}",
+                    path.to_string_lossy(),
+                    target.to_string_lossy()
+                );
+                async_std::fs::rename(path, &target).await?;
+                return Ok(());
+            }
+            n += 1;
+        }
     }
 }
 
 #[async_trait::async_trait]
 impl Sink for FileSink {
-    async fn connect(&mut self, _ctx: &SinkContext, attempt: &Attempt) -> Result<bool> {
-        let mode = if attempt.is_first() || attempt.success() == 0 {
-            &self.config.mode
-        } else {
-            // if we have already opened the file successfully once
-            // we should not truncate it again or overwrite, but indeed append
-            // otherwise the reconnect logic will lead to unwanted effects
-            // e.g. if a simple write failed temporarily
-            &Mode::Append
-        };
-        debug!(
-            "[Sink::file] opening file {} with options {:?}",
-            self.config.path.to_string_lossy(),
-            mode
-        );
-        let file = file::open_with(&self.config.path, &mut mode.as_open_options()).await?;
-        self.file = Some(file);
+    async fn connect(&mut self, _ctx: &SinkContext, _attempt: &Attempt) -> Result<bool> {
+        // files with placeholders in their path are opened with the first event written to them
+        if self.path.is_static() {
+            let path = self.config.path.clone();
+            self.file(&path).await?;
+        }
         Ok(true)
     }
 
@@ -284,31 +499,86 @@ impl Sink for FileSink {
         serializer: &mut EventSerializer,
         _start: u64,
     ) -> Result<SinkReply> {
-        let file = self
-            .file
-            .as_mut()
-            .ok_or_else(|| Error::from("No file available."))?;
         let ingest_ns = event.ingest_ns;
+        let rotate_size = self.config.rotate_size;
         for value in event.value_iter() {
+            let path = self.path.render(value, ingest_ns)?;
             let data = serializer.serialize(value, ingest_ns)?;
+            let file = self.file(&path).await?;
+            let mut res = Ok(());
             for chunk in data {
-                if let Err(e) = file.write_all(&chunk).await {
-                    error!("{} Error writing to file: {}", &ctx, &e);
-                    self.file = None;
-                    ctx.notifier().connection_lost().await?;
-                    return Err(e.into());
+                res = file.file.write_all(&chunk).await;
+                if res.is_err() {
+                    break;
                 }
+                file.size += chunk.len() as u64;
+                file.dirty = true;
             }
-            if let Err(e) = file.flush().await {
-                error!("{} Error flushing file: {}", &ctx, &e);
-                self.file = None;
+            let full = rotate_size.map_or(false, |max| file.size >= max);
+            if let Err(e) = res {
+                error!("{} Error writing to file: {}", &ctx, &e);
+                self.files.remove(&path);
                 ctx.notifier().connection_lost().await?;
                 return Err(e.into());
             }
+            if full {
+                self.rotate(&path).await?;
+            }
         }
         Ok(SinkReply::NONE)
     }
 
+    async fn on_signal(
+        &mut self,
+        signal: Event,
+        ctx: &SinkContext,
+        _serializer: &mut EventSerializer,
+    ) -> Result<SinkReply> {
+        if signal.kind != Some(SignalKind::Tick) {
+            return Ok(SinkReply::default());
+        }
+        if let Some(interval_s) = self.config.rotate_interval_s {
+            // intervals too large to represent in nanoseconds never expire
+            let interval_ns = interval_s.saturating_mul(1_000_000_000);
+            let now = nanotime();
+            let expired: Vec<PathBuf> = self
+                .files
+                .iter()
+                .filter(|(_, open)| {
+                    open.size > 0 && now.saturating_sub(open.opened_at) >= interval_ns
+                })
+                .map(|(path, _)| path.clone())
+                .collect();
+            for path in expired {
+                self.rotate(&path).await?;
+            }
+        }
+        let mut idle = Vec::new();
+        let mut failed = None;
+        for (path, open) in &mut self.files {
+            if open.dirty {
+                if let Err(e) = open.file.flush().await {
+                    failed = Some((path.clone(), e));
+                    break;
+                }
+                open.dirty = false;
+            } else if !self.path.is_static() {
+                // files of templated paths not written to since the last tick are closed
+                idle.push(path.clone());
+            }
+        }
+        if let Some((path, e)) = failed {
+            error!("{} Error flushing file: {}", &ctx, &e);
+            self.files.remove(&path);
+            ctx.notifier().connection_lost().await?;
+            return Err(e.into());
+        }
+        for path in idle {
+            self.files.remove(&path);
+        }
+        Ok(SinkReply::default())
+    }
+
     fn auto_ack(&self) -> bool {
         true
     }
@@ -318,8 +588,11 @@ impl Sink for FileSink {
     }
 
     async fn on_stop(&mut self, ctx: &SinkContext) -> Result<()> {
-        if let Some(file) = self.file.take() {
-            if let Err(e) = file.sync_all().await {
+        for (_, mut open) in self.files.drain() {
+            if let Err(e) = open.file.flush().await {
+                error!("{} Error flushing file: {}", &ctx, e);
+            }
+            if let Err(e) = open.file.sync_all().await {
                 error!("{} Error flushing file: {}", &ctx, e);
             }
         }
@@ -13,11 +13,19 @@
 // limitations under the License.
 
 mod sink;
+mod table;
 
 use crate::connectors::impls::gbq::writer::sink::GbqSink;
+use crate::connectors::impls::gbq::writer::table::{AutoCreateTable, TableRef};
 use crate::connectors::prelude::*;
+use crate::connectors::sink::retry::{DeadLetter, RetryConfig, RetrySink};
 use crate::connectors::{Connector, ConnectorBuilder, ConnectorConfig, ConnectorType};
+use async_std::channel::{bounded, Receiver, Sender};
+use rand::Rng;
 use serde::Deserialize;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use std::time::Duration;
 use tremor_pipeline::ConfigImpl;
 
 #[derive(Deserialize, Clone)]
@@ -25,26 +33,243 @@ pub(crate) struct Config {
     pub table_id: String,
     pub connect_timeout: u64,
     pub request_timeout: u64,
+    #[serde(default)]
+    pub on_encode_error: EncodeErrorPolicy,
+    /// how string values of `BYTES` columns are decoded
+    #[serde(default)]
+    pub bytes_encoding: BytesEncoding,
+    /// emit the row count, byte size and duration of each append request as connector metrics
+    #[serde(default)]
+    pub request_metrics: bool,
+    /// connect and encode events against the table schema, but log them instead of appending them
+    #[serde(default)]
+    pub dry_run: bool,
+    /// interval in milliseconds in which the table schema is fetched again to pick up schema changes,
+    /// it is only fetched once on connect if not set
+    #[serde(default)]
+    pub schema_refresh_interval_ms: Option<u64>,
+    /// number of gRPC channels appends are distributed over round-robin
+    #[serde(default = "default_channel_count")]
+    pub channel_count: usize,
+    /// maximum number of concurrent requests on each channel, unlimited if not set
+    #[serde(default)]
+    pub concurrency_limit: Option<usize>,
+    /// create the table with the given schema when connecting, if it doesn't exist
+    #[serde(default)]
+    pub auto_create_table: Option<AutoCreateTable>,
+    /// maximum size of the serialized rows of a single append request in bytes,
+    /// larger batches are split over multiple requests
+    #[serde(default = "default_max_request_bytes")]
+    pub max_request_bytes: usize,
+    /// emit events whose appends fail with a permanent error, e.g. rows not matching the table schema,
+    /// on the `err` port and ack them, instead of failing them. Transient errors still fail the event.
+    #[serde(default)]
+    pub route_permanent_errors: bool,
+    /// `DATE`, `TIME` and `DATETIME` columns that also accept integers, as days since the epoch,
+    /// microseconds since midnight and microseconds since the epoch respectively.
+    /// Columns of structs are given as dot separated paths, e.g. `order.placed_on`.
+    #[serde(default)]
+    pub temporal_integer_columns: Vec<String>,
+    /// retry failed appends with a growing backoff, events failing all retries are emitted
+    /// on the `err` port and acked
+    #[serde(default)]
+    pub retry: Option<RetryConfig>,
+    /// wait with a growing, jittered backoff before notifying the runtime about a lost connection,
+    /// so many sinks losing their connection at once don't reconnect in lockstep
+    #[serde(default)]
+    pub connection_loss_backoff: Option<ConnectionLossBackoff>,
 }
 impl ConfigImpl for Config {}
 
+/// Backoff before notifying the runtime about a lost connection to BigQuery
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct ConnectionLossBackoff {
+    /// backoff upon the first connection loss in milliseconds
+    #[serde(default = "default_initial_ms")]
+    pub initial_ms: u64,
+    /// upper bound for the backoff in milliseconds
+    #[serde(default = "default_max_ms")]
+    pub max_ms: u64,
+    /// factor by which the backoff grows for each consecutive connection loss
+    #[serde(default = "default_multiplier")]
+    pub multiplier: f64,
+    /// fraction of the backoff by which it is randomly varied in both directions, between 0 and 1
+    #[serde(default = "default_jitter")]
+    pub jitter: f64,
+}
+
+fn default_initial_ms() -> u64 {
+    100
+}
+
+fn default_max_ms() -> u64 {
+    10_000
+}
+
+fn default_multiplier() -> f64 {
+    2.0
+}
+
+fn default_jitter() -> f64 {
+    0.2
+}
+
+impl Default for ConnectionLossBackoff {
+    fn default() -> Self {
+        Self {
+            initial_ms: default_initial_ms(),
+            max_ms: default_max_ms(),
+            multiplier: default_multiplier(),
+            jitter: default_jitter(),
+        }
+    }
+}
+
+impl ConnectionLossBackoff {
+    /// backoff in milliseconds without jitter for the `attempt`th consecutive connection loss, starting at 0
+    #[allow(
+        clippy::cast_precision_loss,
+        clippy::cast_sign_loss,
+        clippy::cast_possible_truncation,
+        clippy::cast_possible_wrap
+    )]
+    pub(crate) fn backoff_ms(&self, attempt: u32) -> u64 {
+        let backoff = self.initial_ms as f64 * self.multiplier.powi(attempt.min(64) as i32);
+        (backoff as u64).min(self.max_ms)
+    }
+
+    /// backoff for the `attempt`th consecutive connection loss with jitter applied
+    #[allow(
+        clippy::cast_precision_loss,
+        clippy::cast_sign_loss,
+        clippy::cast_possible_truncation
+    )]
+    pub(crate) fn jittered_backoff<R: Rng>(&self, attempt: u32, rng: &mut R) -> Duration {
+        let backoff = self.backoff_ms(attempt) as f64;
+        let jitter = backoff * self.jitter.clamp(0.0, 1.0);
+        let millis = if jitter > 0.0 {
+            rng.gen_range((backoff - jitter)..=(backoff + jitter))
+        } else {
+            backoff
+        };
+        Duration::from_millis(millis as u64)
+    }
+}
+
+/// What to do with a row containing a field that doesn't match the table schema
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum EncodeErrorPolicy {
+    /// fail the whole event
+    Fail,
+    /// omit the mismatched field, keeping the rest of the row
+    DropField,
+    /// skip the row, keeping the other rows of the event
+    DropRow,
+}
+
+impl Default for EncodeErrorPolicy {
+    fn default() -> Self {
+        Self::Fail
+    }
+}
+
+/// How string values of `BYTES` columns are decoded, byte values are always sent as they are
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum BytesEncoding {
+    /// strings are sent as their UTF-8 bytes
+    Raw,
+    /// base64 encoded strings
+    Base64,
+    /// hex encoded strings
+    Hex,
+}
+
+impl Default for BytesEncoding {
+    fn default() -> Self {
+        Self::Raw
+    }
+}
+
+fn default_channel_count() -> usize {
+    1
+}
+
+/// BigQuery limits append requests to 10MB, leave room for the schema and the request overhead
+fn default_max_request_bytes() -> usize {
+    8 * 1024 * 1024
+}
+
 #[derive(Debug, Default)]
 pub(crate) struct Builder {}
 
+impl Builder {
+    const MISSING_METRICS_INTERVAL: &'static str =
+        "`request_metrics` requires `metrics_interval_s` to be set.";
+    const NO_CHANNELS: &'static str = "`channel_count` needs to be at least 1.";
+    const NO_CONCURRENCY: &'static str = "`concurrency_limit` needs to be at least 1.";
+    const NO_REQUEST_BYTES: &'static str = "`max_request_bytes` needs to be at least 1.";
+    const EMPTY_TABLE_SCHEMA: &'static str =
+        "`auto_create_table` needs a `schema` with at least one field.";
+}
+
 struct Gbq {
     config: Config,
+    error_tx: Sender<SourceReply>,
+    error_rx: Receiver<SourceReply>,
+    source_is_connected: Arc<AtomicBool>,
 }
 
 #[async_trait::async_trait]
 impl Connector for Gbq {
+    async fn create_source(
+        &mut self,
+        source_context: SourceContext,
+        builder: SourceManagerBuilder,
+    ) -> Result<Option<SourceAddr>> {
+        if !self.config.route_permanent_errors && self.config.retry.is_none() {
+            return Ok(None);
+        }
+        let source = GbqErrorSource {
+            source_is_connected: self.source_is_connected.clone(),
+            error_rx: self.error_rx.clone(),
+        };
+        builder.spawn(source, source_context).map(Some)
+    }
+
     async fn create_sink(
         &mut self,
         sink_context: SinkContext,
         builder: SinkManagerBuilder,
     ) -> Result<Option<SinkAddr>> {
-        let sink = GbqSink::new(self.config.clone());
-
-        builder.spawn(sink, sink_context).map(Some)
+        let dead_letter = DeadLetter {
+            tx: self.error_tx.clone(),
+            origin_uri: EventOriginUri {
+                scheme: "tremor-gbq".to_string(),
+                host: "bigquerystorage.googleapis.com".to_string(),
+                port: None,
+                path: vec![self.config.table_id.clone()],
+            },
+            source_is_connected: self.source_is_connected.clone(),
+        };
+        let config = self.config.clone();
+        let permanent_errors = config.route_permanent_errors.then(|| dead_letter.clone());
+        let new_sink = move |reply_tx| {
+            let sink = GbqSink::new(config, reply_tx);
+            match permanent_errors {
+                Some(dead_letter) => sink.with_dead_letter(dead_letter),
+                None => sink,
+            }
+        };
+        if let Some(retry) = self.config.retry.clone() {
+            let sink = RetrySink::new(retry, Some(dead_letter), builder.reply_tx(), new_sink);
+            builder.spawn(sink, sink_context).map(Some)
+        } else {
+            let sink = new_sink(builder.reply_tx());
+            builder.spawn(sink, sink_context).map(Some)
+        }
     }
 
     fn codec_requirements(&self) -> CodecReq {
@@ -60,12 +285,66 @@ impl ConnectorBuilder for Builder {
 
     async fn build_cfg(
         &self,
-        _: &Alias,
-        _: &ConnectorConfig,
+        alias: &Alias,
+        connector_config: &ConnectorConfig,
         config: &Value,
         _kill_switch: &KillSwitch,
     ) -> Result<Box<dyn Connector>> {
         let config = Config::new(config)?;
-        Ok(Box::new(Gbq { config }))
+        // request metrics are only flushed together with the other connector metrics
+        if config.request_metrics && connector_config.metrics_interval_s.is_none() {
+            return Err(err_connector_def(alias, Self::MISSING_METRICS_INTERVAL));
+        }
+        if config.channel_count == 0 {
+            return Err(err_connector_def(alias, Self::NO_CHANNELS));
+        }
+        if config.concurrency_limit == Some(0) {
+            return Err(err_connector_def(alias, Self::NO_CONCURRENCY));
+        }
+        if config.max_request_bytes == 0 {
+            return Err(err_connector_def(alias, Self::NO_REQUEST_BYTES));
+        }
+        if let Some(auto_create_table) = config.auto_create_table.as_ref() {
+            if auto_create_table.schema.is_empty() {
+                return Err(err_connector_def(alias, Self::EMPTY_TABLE_SCHEMA));
+            }
+            // the table is only looked up on connect, so fail early on invalid table ids
+            TableRef::parse(&config.table_id)
+                .map_err(|e| err_connector_def(alias, &e.to_string()))?;
+        }
+        let (error_tx, error_rx) = bounded(crate::QSIZE.load(Ordering::Relaxed));
+        Ok(Box::new(Gbq {
+            config,
+            error_tx,
+            error_rx,
+            source_is_connected: Arc::new(AtomicBool::new(false)),
+        }))
+    }
+}
+
+/// Emits the events rejected by BigQuery on the `err` port
+struct GbqErrorSource {
+    source_is_connected: Arc<AtomicBool>,
+    error_rx: Receiver<SourceReply>,
+}
+
+#[async_trait::async_trait]
+impl Source for GbqErrorSource {
+    async fn pull_data(&mut self, _pull_id: &mut u64, _ctx: &SourceContext) -> Result<SourceReply> {
+        Ok(self.error_rx.recv().await?)
+    }
+
+    async fn on_cb_open(&mut self, _ctx: &SourceContext) -> Result<()> {
+        // rejected events are only sent once we know some pipeline is connected to us
+        self.source_is_connected.store(true, Ordering::Release);
+        Ok(())
+    }
+
+    fn is_transactional(&self) -> bool {
+        false
+    }
+
+    fn asynchronous(&self) -> bool {
+        true
     }
 }
@@ -13,36 +13,471 @@
 // limitations under the License.
 
 use crate::connectors::google::AuthInterceptor;
-use crate::connectors::impls::gbq::writer::Config;
+use crate::connectors::impls::gbq::writer::table::{ensure_table, RestTableClient};
+use crate::connectors::impls::gbq::writer::{
+    BytesEncoding, Config, ConnectionLossBackoff, EncodeErrorPolicy,
+};
 use crate::connectors::prelude::*;
+use crate::connectors::sink::retry::DeadLetter;
+use crate::connectors::utils::diagnostics::{ConnectStep, ConnectStepExt};
+use async_std::channel::{bounded, unbounded, Receiver, Sender};
 use async_std::prelude::{FutureExt, StreamExt};
-use futures::stream;
+use async_std::task::JoinHandle;
+use beef::Cow;
+use chrono::{Datelike, NaiveDate, NaiveDateTime};
+use futures::stream::BoxStream;
 use googapis::google::cloud::bigquery::storage::v1::append_rows_request::ProtoData;
 use googapis::google::cloud::bigquery::storage::v1::big_query_write_client::BigQueryWriteClient;
 use googapis::google::cloud::bigquery::storage::v1::table_field_schema::Type as TableType;
 use googapis::google::cloud::bigquery::storage::v1::{
-    append_rows_request, table_field_schema, write_stream, AppendRowsRequest,
-    CreateWriteStreamRequest, ProtoRows, ProtoSchema, TableFieldSchema, WriteStream,
+    append_rows_request, table_field_schema, write_stream, AppendRowsRequest, AppendRowsResponse,
+    CreateWriteStreamRequest, FinalizeWriteStreamRequest, ProtoRows, ProtoSchema, TableFieldSchema,
+    WriteStream,
 };
 use gouth::Token;
 use prost::encoding::WireType;
 use prost_types::{field_descriptor_proto, DescriptorProto, FieldDescriptorProto};
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicUsize};
+use std::sync::Arc;
 use std::time::Duration;
 use tonic::codegen::InterceptedService;
 use tonic::transport::{Certificate, Channel, ClientTlsConfig};
-use tonic::Status;
+use tonic::{Code, Status};
+use tremor_common::time::nanotime;
+
+const ENDPOINT: &str = "bigquerystorage.googleapis.com:443";
+
+type ResponseStream = BoxStream<'static, std::result::Result<AppendRowsResponse, Status>>;
+
+/// Client for the BigQuery Storage Write API
+#[async_trait::async_trait]
+pub(crate) trait WriteClient: Send {
+    /// Creates a committed write stream to the table `table_id`, including the table schema
+    async fn create_write_stream(&mut self, table_id: &str) -> Result<WriteStream>;
+
+    /// Finalizes the write stream `name`, no more rows can be appended to it afterwards
+    async fn finalize_write_stream(&mut self, name: &str) -> Result<()>;
+
+    /// Opens a bidirectional stream sending all requests received via `requests`,
+    /// the responses arrive in the order of the requests
+    async fn append_rows(
+        &mut self,
+        requests: Receiver<AppendRowsRequest>,
+    ) -> Result<ResponseStream>;
+}
+
+#[async_trait::async_trait]
+impl WriteClient for BigQueryWriteClient<InterceptedService<Channel, AuthInterceptor>> {
+    async fn create_write_stream(&mut self, table_id: &str) -> Result<WriteStream> {
+        let write_stream = BigQueryWriteClient::create_write_stream(
+            self,
+            CreateWriteStreamRequest {
+                parent: table_id.to_string(),
+                write_stream: Some(WriteStream {
+                    // The stream name here will be ignored and a generated value will be set in the response
+                    name: "".to_string(),
+                    r#type: i32::from(write_stream::Type::Committed),
+                    create_time: None,
+                    commit_time: None,
+                    table_schema: None,
+                }),
+            },
+        )
+        .await?
+        .into_inner();
+        Ok(write_stream)
+    }
+
+    async fn finalize_write_stream(&mut self, name: &str) -> Result<()> {
+        BigQueryWriteClient::finalize_write_stream(
+            self,
+            FinalizeWriteStreamRequest {
+                name: name.to_string(),
+            },
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn append_rows(
+        &mut self,
+        requests: Receiver<AppendRowsRequest>,
+    ) -> Result<ResponseStream> {
+        let responses = BigQueryWriteClient::append_rows(self, requests).await?;
+        Ok(Box::pin(responses.into_inner()))
+    }
+}
 
 pub(crate) struct GbqSink {
-    client: Option<BigQueryWriteClient<InterceptedService<Channel, AuthInterceptor>>>,
+    /// one client per channel, empty if not connected
+    clients: Vec<Box<dyn WriteClient>>,
     write_stream: Option<WriteStream>,
     mapping: Option<JsonToProtobufMapping>,
+    /// time of the last write stream creation, and with it the last schema refresh
+    schema_refreshed_at: u64,
+    /// the open append rows stream of each channel, append requests are sent over them round-robin
+    append_streams: Vec<Option<AppendStream>>,
+    /// channel the next append request is sent on
+    next_channel: usize,
     config: Config,
+    reply_tx: Sender<AsyncSinkReply>,
+    /// metrics of the append requests, drained on each metrics flush
+    metrics_tx: Sender<EventPayload>,
+    metrics_rx: Receiver<EventPayload>,
+    /// where events with permanently failing appends are sent, if enabled
+    dead_letter: Option<Arc<DeadLetter>>,
+    connection_loss: Arc<ConnectionLoss>,
+}
+
+/// How an append failing with a gRPC status is handled
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Disposition {
+    /// transient errors, the event is failed so it can be retried
+    Retry,
+    /// the append won't ever succeed, e.g. for rows not matching the table schema,
+    /// so the event is sent to the dead letter and acked
+    Reject,
+}
+
+impl From<Code> for Disposition {
+    fn from(code: Code) -> Self {
+        match code {
+            // expired tokens are refreshed, so `Unauthenticated` is transient as well
+            Code::Cancelled
+            | Code::Unknown
+            | Code::DeadlineExceeded
+            | Code::ResourceExhausted
+            | Code::Aborted
+            | Code::Internal
+            | Code::Unavailable
+            | Code::Unauthenticated => Disposition::Retry,
+            // e.g. `InvalidArgument` for rows not matching the schema, `NotFound` or `PermissionDenied`
+            _ => Disposition::Reject,
+        }
+    }
+}
+
+/// The contraflow of an event, whose rows might be split over multiple append requests
+///
+/// The event is acked once all of its requests succeeded and failed as soon as one of them failed.
+struct EventReply {
+    /// `None` for non transactional events, as they don't need to be acked
+    contraflow_data: Option<ContraflowData>,
+    /// the event, kept to send it to the dead letter if one of its requests is rejected
+    event: Option<Event>,
+    /// requests of the event without a successful response yet
+    remaining: AtomicUsize,
+    failed: AtomicBool,
+    rejected: AtomicBool,
+}
+
+impl EventReply {
+    fn new(contraflow_data: Option<ContraflowData>, event: Option<Event>, requests: usize) -> Self {
+        Self {
+            contraflow_data,
+            event,
+            remaining: AtomicUsize::new(requests),
+            failed: AtomicBool::new(false),
+            rejected: AtomicBool::new(false),
+        }
+    }
+
+    /// Records the response to one of the requests, returns the reply once the event is done
+    fn complete(&self, ack: bool, duration: u64) -> Option<AsyncSinkReply> {
+        let contraflow_data = self.contraflow_data.as_ref()?;
+        if !ack {
+            // only the first failure fails the event, it can't be acked anymore afterwards
+            let first_failure = !self.failed.swap(true, Ordering::AcqRel);
+            return first_failure.then(|| AsyncSinkReply::Fail(contraflow_data.clone()));
+        }
+        let last = self.remaining.fetch_sub(1, Ordering::AcqRel) == 1;
+        (last && !self.failed.load(Ordering::Acquire))
+            .then(|| AsyncSinkReply::Ack(contraflow_data.clone(), duration))
+    }
+
+    /// The event to send to the dead letter, only for the first rejected request of the event
+    fn reject(&self) -> Option<&Event> {
+        if self.rejected.swap(true, Ordering::AcqRel) {
+            None
+        } else {
+            self.event.as_ref()
+        }
+    }
+}
+
+/// Splits `rows` into consecutive chunks of at most `max_bytes`, a larger row is sent on its own
+fn split_rows(rows: Vec<Vec<u8>>, max_bytes: usize) -> Vec<Vec<Vec<u8>>> {
+    let mut splits = Vec::new();
+    let mut split: Vec<Vec<u8>> = Vec::new();
+    let mut split_bytes = 0;
+    for row in rows {
+        if !split.is_empty() && split_bytes + row.len() > max_bytes {
+            splits.push(std::mem::take(&mut split));
+            split_bytes = 0;
+        }
+        split_bytes += row.len();
+        split.push(row);
+    }
+    if !split.is_empty() {
+        splits.push(split);
+    }
+    splits
+}
+
+/// An append request waiting for its response
+struct PendingRequest {
+    event: Arc<EventReply>,
+    /// start of the processing of the event
+    start: u64,
+    /// time the request was sent
+    request_start: u64,
+    stats: AppendStats,
+}
+
+/// Sending side of an open append rows stream, the responses are handled by a `ResponseHandler` task
+struct AppendStream {
+    requests: Sender<AppendRowsRequest>,
+    pending: Sender<PendingRequest>,
+    /// set once the stream is closed, a new stream needs to be opened for further requests
+    closed: Arc<AtomicBool>,
+    handler: JoinHandle<()>,
+}
+
+impl AppendStream {
+    /// Closes the stream and waits up to `timeout` for the responses to all requests in flight,
+    /// so their events are acked or failed
+    async fn flush(self, ctx: &SinkContext, timeout: Duration) {
+        self.requests.close();
+        self.pending.close();
+        if self.handler.timeout(timeout).await.is_err() {
+            warn!("{ctx} Timed out waiting for the responses to the appends in flight");
+        }
+    }
+
+    fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::Acquire)
+    }
+
+    /// Sends `request` on the stream, returns the pending request back if the stream is closed
+    async fn append(
+        &self,
+        request: AppendRowsRequest,
+        pending: PendingRequest,
+    ) -> std::result::Result<(), (AppendRowsRequest, PendingRequest)> {
+        if self.is_closed() {
+            return Err((request, pending));
+        }
+        if let Err(e) = self.pending.send(pending).await {
+            return Err((request, e.into_inner()));
+        }
+        // if the request can't be sent anymore, the stream ended and
+        // the response handler fails the pending request
+        let _ = self.requests.send(request).await;
+        Ok(())
+    }
+}
+
+/// Matches the responses of an append rows stream to the pending requests,
+/// acking or failing the events they belong to
+struct ResponseHandler {
+    ctx: SinkContext,
+    reply_tx: Sender<AsyncSinkReply>,
+    metrics_tx: Option<Sender<EventPayload>>,
+    request_timeout: Duration,
+    closed: Arc<AtomicBool>,
+    dead_letter: Option<Arc<DeadLetter>>,
+    connection_loss: Arc<ConnectionLoss>,
+}
+
+impl ResponseHandler {
+    async fn run(
+        self,
+        mut responses: ResponseStream,
+        pending: Receiver<PendingRequest>,
+    ) -> Result<()> {
+        let mut connection_lost = false;
+        while let Ok(request) = pending.recv().await {
+            let (result, ack, done) = match responses.next().timeout(self.request_timeout).await {
+                Ok(Some(Ok(_))) => ("ack", true, false),
+                Ok(Some(Err(e))) => {
+                    error!("{} BigQuery error: {}", self.ctx, e);
+                    if self.reject(&request, &e).await? {
+                        ("rejected", true, false)
+                    } else {
+                        ("fail", false, false)
+                    }
+                }
+                // the stream was closed by BigQuery
+                Ok(None) => ("none", false, true),
+                Err(_) => {
+                    connection_lost = true;
+                    ("timeout", false, true)
+                }
+            };
+            self.reply(request, result, ack).await?;
+            if done {
+                break;
+            }
+        }
+        // fail everything that was sent after the stream closed
+        self.closed.store(true, Ordering::Release);
+        pending.close();
+        while let Ok(request) = pending.try_recv() {
+            self.reply(request, "none", false).await?;
+        }
+        if connection_lost {
+            self.connection_loss.notify(&self.ctx).await?;
+        }
+        Ok(())
+    }
+
+    /// Sends the event of a request failing with a permanent error to the dead letter,
+    /// returns `false` if the request is to be failed instead
+    async fn reject(&self, request: &PendingRequest, status: &Status) -> Result<bool> {
+        let dead_letter = match self.dead_letter.as_ref() {
+            Some(dead_letter) if Disposition::from(status.code()) == Disposition::Reject => {
+                dead_letter
+            }
+            _ => return Ok(false),
+        };
+        if let Some(event) = request.event.reject() {
+            let error = format!("{:?}: {}", status.code(), status.message());
+            if !dead_letter.send(event, &error, 1).await? {
+                // nobody would receive it, so fail it as before
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    async fn reply(&self, request: PendingRequest, result: &'static str, ack: bool) -> Result<()> {
+        let now = nanotime();
+        if let Some(metrics_tx) = self.metrics_tx.as_ref() {
+            let duration = now.saturating_sub(request.request_start);
+            metrics_tx
+                .send(request.stats.payload(&self.ctx, result, duration, now))
+                .await?;
+        }
+        if let Some(reply) = request
+            .event
+            .complete(ack, now.saturating_sub(request.start))
+        {
+            self.reply_tx.send(reply).await?;
+        }
+        Ok(())
+    }
+}
+
+/// Notifies the runtime about connection losses, it reconnects as configured in `reconnect`
+///
+/// With `connection_loss_backoff` the notification is sent from a task of its own once the
+/// jittered backoff passed, so neither the sink nor the response handlers wait for it.
+struct ConnectionLoss {
+    backoff: Option<ConnectionLossBackoff>,
+    /// consecutive connection losses since the last successful connect
+    losses: AtomicU32,
+    /// if a notification is already waiting for its backoff to pass
+    scheduled: AtomicBool,
+}
+
+impl ConnectionLoss {
+    fn new(backoff: Option<ConnectionLossBackoff>) -> Self {
+        Self {
+            backoff,
+            losses: AtomicU32::new(0),
+            scheduled: AtomicBool::new(false),
+        }
+    }
+
+    /// The backoff for the next notification, `None` if one is already scheduled
+    fn next_backoff(&self, backoff: &ConnectionLossBackoff) -> Option<Duration> {
+        if self.scheduled.swap(true, Ordering::AcqRel) {
+            return None;
+        }
+        let attempt = self.losses.fetch_add(1, Ordering::AcqRel);
+        Some(backoff.jittered_backoff(attempt, &mut rand::thread_rng()))
+    }
+
+    /// Called once connected again, the next connection loss starts with the initial backoff
+    fn reset(&self) {
+        self.losses.store(0, Ordering::Release);
+    }
+
+    async fn notify(self: &Arc<Self>, ctx: &SinkContext) -> Result<()> {
+        let backoff = if let Some(backoff) = self.backoff.as_ref() {
+            backoff
+        } else {
+            info!("{ctx} Connection to BigQuery lost");
+            return ctx.notifier.connection_lost().await;
+        };
+        if let Some(delay) = self.next_backoff(backoff) {
+            info!("{ctx} Connection to BigQuery lost, reconnecting in {delay:?}");
+            let this = self.clone();
+            let ctx = ctx.clone();
+            async_std::task::spawn(async move {
+                async_std::task::sleep(delay).await;
+                this.scheduled.store(false, Ordering::Release);
+                log_error!(
+                    ctx.notifier.connection_lost().await,
+                    "{ctx} Failed to notify on connection lost: {e}"
+                );
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Size of a single append request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct AppendStats {
+    rows: usize,
+    bytes: usize,
+}
+
+impl AppendStats {
+    const APPEND_REQUEST: &'static str = "gbq_append_request";
+    const ROWS: Cow<'static, str> = Cow::const_str("rows");
+    const BYTES: Cow<'static, str> = Cow::const_str("bytes");
+    const DURATION_NS: Cow<'static, str> = Cow::const_str("duration_ns");
+    const CONNECTOR: Cow<'static, str> = Cow::const_str("connector");
+    const RESULT: Cow<'static, str> = Cow::const_str("result");
+
+    fn new(serialized_rows: &[Vec<u8>]) -> Self {
+        Self {
+            rows: serialized_rows.len(),
+            bytes: serialized_rows.iter().map(Vec::len).sum(),
+        }
+    }
+
+    /// metrics payload for an append request that took `duration_ns` and finished with `result`
+    fn payload(
+        self,
+        ctx: &SinkContext,
+        result: &'static str,
+        duration_ns: u64,
+        timestamp: u64,
+    ) -> EventPayload {
+        let mut fields = halfbrown::HashMap::with_capacity(3);
+        fields.insert(Self::ROWS, Value::from(self.rows));
+        fields.insert(Self::BYTES, Value::from(self.bytes));
+        fields.insert(Self::DURATION_NS, Value::from(duration_ns));
+        let mut tags = halfbrown::HashMap::with_capacity(2);
+        tags.insert(Self::CONNECTOR, Value::from(ctx.alias().to_string()));
+        tags.insert(Self::RESULT, Value::from(result));
+        make_metrics_payload(Self::APPEND_REQUEST, fields, tags, timestamp)
+    }
 }
 
 struct Field {
     table_type: TableType,
     tag: u32,
+    // `REPEATED` mode, values are arrays with each element encoded as a separate field
+    repeated: bool,
+    // `NULLABLE` mode, null values are omitted, which BigQuery stores as NULL
+    nullable: bool,
+    // `DATE`, `TIME` or `DATETIME` column also accepting integers, see `temporal_from_integer`
+    temporal_integers: bool,
 
     // ignored if the table_type is not struct
     subfields: HashMap<String, Field>,
@@ -51,6 +486,7 @@ struct Field {
 struct JsonToProtobufMapping {
     fields: HashMap<String, Field>,
     descriptor: DescriptorProto,
+    bytes_encoding: BytesEncoding,
 }
 
 fn map_field(
@@ -67,6 +503,9 @@ fn map_field(
     for raw_field in raw_fields {
         let mut type_name = None;
         let mut subfields = HashMap::with_capacity(raw_field.fields.len());
+        let mode = table_field_schema::Mode::from_i32(raw_field.mode);
+        let repeated = mode == Some(table_field_schema::Mode::Repeated);
+        let nullable = mode == Some(table_field_schema::Mode::Nullable);
 
         let table_type =
             if let Some(table_type) = table_field_schema::Type::from_i32(raw_field.r#type) {
@@ -120,7 +559,7 @@ fn map_field(
         proto_fields.push(FieldDescriptorProto {
             name: Some(raw_field.name.to_string()),
             number: Some(i32::from(tag)),
-            label: None,
+            label: repeated.then(|| i32::from(field_descriptor_proto::Label::Repeated)),
             r#type: Some(i32::from(grpc_type)),
             type_name,
             extendee: None,
@@ -136,6 +575,9 @@ fn map_field(
             Field {
                 table_type,
                 tag: u32::from(tag),
+                repeated,
+                nullable,
+                temporal_integers: false,
                 subfields,
             },
         );
@@ -160,7 +602,94 @@ fn map_field(
     )
 }
 
-fn encode_field(val: &Value, field: &Field, result: &mut Vec<u8>) -> Result<()> {
+fn encode_field(
+    val: &Value,
+    field: &Field,
+    bytes_encoding: BytesEncoding,
+    result: &mut Vec<u8>,
+) -> Result<()> {
+    if field.nullable && val.is_null() {
+        // a missing field is NULL, null values of other columns fail to encode below
+        return Ok(());
+    }
+    if field.repeated {
+        // repeated fields are not packed, every element is encoded as its own field with the same tag
+        for element in val
+            .as_array()
+            .ok_or_else(|| ErrorKind::BigQueryTypeMismatch("array", val.value_type()))?
+        {
+            encode_value(element, field, bytes_encoding, result)?;
+        }
+        return Ok(());
+    }
+    encode_value(val, field, bytes_encoding, result)
+}
+
+/// the bytes of a `BYTES` column value, decoding strings according to `bytes_encoding`
+fn decode_bytes(val: &Value, bytes_encoding: BytesEncoding) -> Result<Vec<u8>> {
+    // `as_bytes` accepts strings as well, so they need to be decoded first
+    let decoded = match (bytes_encoding, val) {
+        (BytesEncoding::Base64, Value::String(s)) => base64::decode(s.as_bytes())
+            .map_err(|_| ErrorKind::BigQueryTypeMismatch("base64 encoded bytes", val.value_type())),
+        (BytesEncoding::Hex, Value::String(s)) => hex::decode(s.as_bytes())
+            .map_err(|_| ErrorKind::BigQueryTypeMismatch("hex encoded bytes", val.value_type())),
+        _ => val
+            .as_bytes()
+            .map(<[u8]>::to_vec)
+            .ok_or_else(|| ErrorKind::BigQueryTypeMismatch("bytes", val.value_type())),
+    };
+    Ok(decoded?)
+}
+
+/// The canonical string representation of the integer value of a `DATE`, `TIME` or `DATETIME` column,
+/// taken as days since the epoch, microseconds since midnight or microseconds since the epoch
+fn temporal_from_integer(table_type: TableType, value: i64) -> Result<String> {
+    const MICROS_PER_SECOND: i64 = 1_000_000;
+    const MICROS_PER_DAY: i64 = 86_400 * MICROS_PER_SECOND;
+    // BigQuery supports the years 1 to 9999, 0001-01-01 and 9999-12-31 as days since the epoch
+    const DAYS: std::ops::RangeInclusive<i64> = -719_162..=2_932_896;
+    let formatted = match table_type {
+        TableType::Date => NaiveDate::from_ymd_opt(1970, 1, 1)
+            .filter(|_| DAYS.contains(&value))
+            .and_then(|epoch| epoch.checked_add_signed(chrono::Duration::days(value)))
+            .map(|date| date.format("%Y-%m-%d").to_string()),
+        TableType::Time => (0..MICROS_PER_DAY).contains(&value).then(|| {
+            let seconds = value / MICROS_PER_SECOND;
+            format!(
+                "{:02}:{:02}:{:02}.{:06}",
+                seconds / 3600,
+                seconds / 60 % 60,
+                seconds % 60,
+                value % MICROS_PER_SECOND
+            )
+        }),
+        TableType::Datetime => {
+            let nanos = u32::try_from(value.rem_euclid(MICROS_PER_SECOND) * 1000).ok();
+            nanos
+                .and_then(|nanos| {
+                    NaiveDateTime::from_timestamp_opt(value.div_euclid(MICROS_PER_SECOND), nanos)
+                })
+                .filter(|datetime| (1..=9999).contains(&datetime.year()))
+                .map(|datetime| datetime.format("%Y-%m-%dT%H:%M:%S%.6f").to_string())
+        }
+        _ => None,
+    };
+    formatted.ok_or_else(|| {
+        let column_type = match table_type {
+            TableType::Date => "DATE",
+            TableType::Time => "TIME",
+            _ => "DATETIME",
+        };
+        ErrorKind::BigQueryValueOutOfRange(column_type, value).into()
+    })
+}
+
+fn encode_value(
+    val: &Value,
+    field: &Field,
+    bytes_encoding: BytesEncoding,
+    result: &mut Vec<u8>,
+) -> Result<()> {
     let tag = field.tag;
 
     // fixme check which fields are required and fail if they're missing
@@ -184,6 +713,18 @@ fn encode_field(val: &Value, field: &Field, result: &mut Vec<u8>) -> Result<()>
                 .ok_or_else(|| ErrorKind::BigQueryTypeMismatch("bool", val.value_type()))?,
             result,
         ),
+        TableType::Date | TableType::Time | TableType::Datetime
+            if field.temporal_integers && !val.is_str() =>
+        {
+            let value = val
+                .as_i64()
+                .ok_or_else(|| ErrorKind::BigQueryTypeMismatch("string or i64", val.value_type()))?;
+            prost::encoding::string::encode(
+                tag,
+                &temporal_from_integer(field.table_type, value)?,
+                result,
+            );
+        }
         TableType::String
         | TableType::Date
         | TableType::Time
@@ -210,7 +751,7 @@ fn encode_field(val: &Value, field: &Field, result: &mut Vec<u8>) -> Result<()>
                 let subfield_description = field.subfields.get(&k.to_string());
 
                 if let Some(subfield_description) = subfield_description {
-                    encode_field(v, subfield_description, &mut struct_buf)?;
+                    encode_field(v, subfield_description, bytes_encoding, &mut struct_buf)?;
                 } else {
                     warn!(
                         "Passed field {} as struct field, not present in definition",
@@ -223,15 +764,7 @@ fn encode_field(val: &Value, field: &Field, result: &mut Vec<u8>) -> Result<()>
             result.append(&mut struct_buf);
         }
         TableType::Bytes => {
-            prost::encoding::bytes::encode(
-                tag,
-                &Vec::from(
-                    val.as_bytes().ok_or_else(|| {
-                        ErrorKind::BigQueryTypeMismatch("bytes", val.value_type())
-                    })?,
-                ),
-                result,
-            );
+            prost::encoding::bytes::encode(tag, &decode_bytes(val, bytes_encoding)?, result);
         }
         TableType::Json => {
             warn!("Found a field of type JSON, this is not supported, ignoring.");
@@ -255,20 +788,82 @@ impl JsonToProtobufMapping {
         Self {
             descriptor: descriptor.0,
             fields: descriptor.1,
+            bytes_encoding: BytesEncoding::default(),
+        }
+    }
+
+    /// Decode string values of `BYTES` columns with `bytes_encoding`
+    #[must_use]
+    pub fn with_bytes_encoding(mut self, bytes_encoding: BytesEncoding) -> Self {
+        self.bytes_encoding = bytes_encoding;
+        self
+    }
+
+    /// Accept integers for the `DATE`, `TIME` and `DATETIME` columns at the dot separated `columns`
+    #[must_use]
+    pub fn with_temporal_integers(mut self, columns: &[String], ctx: &SinkContext) -> Self {
+        for column in columns {
+            let mut path = column.split('.');
+            let mut field = path.next().and_then(|name| self.fields.get_mut(name));
+            for name in path {
+                field = field.and_then(|field| field.subfields.get_mut(name));
+            }
+            match field {
+                Some(field)
+                    if matches!(
+                        field.table_type,
+                        TableType::Date | TableType::Time | TableType::Datetime
+                    ) =>
+                {
+                    field.temporal_integers = true;
+                }
+                Some(_) => warn!("{ctx} Column {column} is not a DATE, TIME or DATETIME column"),
+                None => warn!("{ctx} Column {column} is not in the table schema"),
+            }
         }
+        self
     }
 
     pub fn map(&self, value: &Value) -> Result<Vec<u8>> {
+        self.map_with_policy(value, EncodeErrorPolicy::Fail)
+            .map(Option::unwrap_or_default)
+    }
+
+    /// Encodes `value`, handling fields not matching the schema according to `policy`.
+    ///
+    /// Returns `None` if the row is to be dropped.
+    pub fn map_with_policy(
+        &self,
+        value: &Value,
+        policy: EncodeErrorPolicy,
+    ) -> Result<Option<Vec<u8>>> {
         if let Some(obj) = value.as_object() {
             let mut result = Vec::with_capacity(obj.len());
 
             for (key, val) in obj {
                 if let Some(field) = self.fields.get(&key.to_string()) {
-                    encode_field(val, field, &mut result)?;
+                    let start = result.len();
+                    if let Err(e) = encode_field(val, field, self.bytes_encoding, &mut result) {
+                        match policy {
+                            EncodeErrorPolicy::Fail => return Err(e),
+                            EncodeErrorPolicy::DropField => {
+                                warn!("Dropping field {} that could not be encoded: {}", key, e);
+                                // discard anything that was partially encoded for this field
+                                result.truncate(start);
+                            }
+                            EncodeErrorPolicy::DropRow => {
+                                warn!(
+                                    "Dropping row because field {} could not be encoded: {}",
+                                    key, e
+                                );
+                                return Ok(None);
+                            }
+                        }
+                    }
                 }
             }
 
-            return Ok(result);
+            return Ok(Some(result));
         }
 
         Err(ErrorKind::BigQueryTypeMismatch("object", value.value_type()).into())
@@ -279,21 +874,201 @@ impl JsonToProtobufMapping {
     }
 }
 impl GbqSink {
-    pub fn new(config: Config) -> Self {
+    pub fn new(config: Config, reply_tx: Sender<AsyncSinkReply>) -> Self {
+        let (metrics_tx, metrics_rx) = unbounded();
+        let connection_loss = Arc::new(ConnectionLoss::new(config.connection_loss_backoff.clone()));
         Self {
-            client: None,
+            clients: Vec::new(),
             write_stream: None,
             mapping: None,
+            schema_refreshed_at: 0,
+            append_streams: Vec::new(),
+            next_channel: 0,
             config,
+            reply_tx,
+            metrics_tx,
+            metrics_rx,
+            dead_letter: None,
+            connection_loss,
         }
     }
 
-    #[cfg(test)]
-    pub fn set_client(
+    /// Sends events with permanently failing appends to `dead_letter` instead of failing them
+    pub fn with_dead_letter(mut self, dead_letter: DeadLetter) -> Self {
+        self.dead_letter = Some(Arc::new(dead_letter));
+        self
+    }
+
+    /// Opens a new append rows stream on `channel`, with `request` as its first request
+    ///
+    /// Returns the pending request back if opening the stream timed out.
+    async fn open_append_stream(
+        &mut self,
+        ctx: &SinkContext,
+        channel: usize,
+        request: AppendRowsRequest,
+        pending: PendingRequest,
+    ) -> Result<std::result::Result<AppendStream, PendingRequest>> {
+        let client = self
+            .clients
+            .get_mut(channel)
+            .ok_or(ErrorKind::ClientNotAvailable(
+                "BigQuery",
+                "The client is not connected",
+            ))?;
+        let qsize = crate::QSIZE.load(Ordering::Relaxed);
+        let (requests_tx, requests_rx) = bounded(qsize);
+        // queue the first request, so the stream is established with it
+        requests_tx.send(request).await?;
+        let responses = match client
+            .append_rows(requests_rx)
+            .timeout(Duration::from_nanos(self.config.request_timeout))
+            .await
+        {
+            Ok(responses) => responses?,
+            Err(_) => return Ok(Err(pending)),
+        };
+        let (pending_tx, pending_rx) = bounded(qsize);
+        pending_tx.send(pending).await?;
+        let closed = Arc::new(AtomicBool::new(false));
+        let handler = ResponseHandler {
+            ctx: ctx.clone(),
+            reply_tx: self.reply_tx.clone(),
+            metrics_tx: self.config.request_metrics.then(|| self.metrics_tx.clone()),
+            request_timeout: Duration::from_nanos(self.config.request_timeout),
+            closed: closed.clone(),
+            dead_letter: self.dead_letter.clone(),
+            connection_loss: self.connection_loss.clone(),
+        };
+        let handler = spawn_task(ctx.clone(), handler.run(responses, pending_rx));
+        Ok(Ok(AppendStream {
+            requests: requests_tx,
+            pending: pending_tx,
+            closed,
+            handler,
+        }))
+    }
+
+    /// Creates a new write stream and rebuilds the mapping from its table schema
+    ///
+    /// Appends in flight finish on the previous stream with the previous schema,
+    /// new appends are sent on a new append stream. The previous stream is finalized once the
+    /// responses to the appends in flight on it arrived, waiting up to `request_timeout` for them.
+    /// If creating the stream fails, the previous stream and mapping are kept.
+    async fn create_write_stream(&mut self, ctx: &SinkContext) -> Result<()> {
+        let client = self
+            .clients
+            .first_mut()
+            .ok_or(ErrorKind::ClientNotAvailable(
+                "BigQuery",
+                "The client is not connected",
+            ))?;
+        let write_stream = client.create_write_stream(&self.config.table_id).await?;
+        let mapping = JsonToProtobufMapping::new(
+            &write_stream
+                .table_schema
+                .as_ref()
+                .ok_or(ErrorKind::GbqSinkFailed("Table schema was not provided"))?
+                .fields,
+            ctx,
+        )
+        .with_bytes_encoding(self.config.bytes_encoding)
+        .with_temporal_integers(&self.config.temporal_integer_columns, ctx);
+        self.mapping = Some(mapping);
+        let previous = self.write_stream.replace(write_stream);
+        let draining = std::mem::replace(
+            &mut self.append_streams,
+            self.clients.iter().map(|_| None).collect(),
+        );
+        self.schema_refreshed_at = nanotime();
+        // rows can't be appended to a finalized stream, so the appends in flight need to finish first
+        let timeout = Duration::from_nanos(self.config.request_timeout);
+        for append_stream in draining.into_iter().flatten() {
+            append_stream.flush(ctx, timeout).await;
+        }
+        if let Some((previous, client)) = previous.zip(self.clients.first_mut()) {
+            if let Err(e) = client.finalize_write_stream(&previous.name).await {
+                warn!(
+                    "{ctx} Failed to finalize the previous write stream {}: {e}",
+                    previous.name
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// if the schema is due for a refresh as per `schema_refresh_interval_ms`
+    fn schema_refresh_due(&self) -> bool {
+        self.config
+            .schema_refresh_interval_ms
+            .map_or(false, |interval_ms| {
+                nanotime().saturating_sub(self.schema_refreshed_at)
+                    >= interval_ms.saturating_mul(1_000_000)
+            })
+    }
+
+    /// Sets the connected clients, one per channel
+    fn set_clients(&mut self, clients: Vec<Box<dyn WriteClient>>) {
+        self.append_streams = clients.iter().map(|_| None).collect();
+        self.next_channel = 0;
+        self.clients = clients;
+    }
+
+    /// Sends `request` on the next channel, opening a new append stream if necessary
+    ///
+    /// Returns `false` if opening the stream timed out, the request wasn't sent then.
+    async fn send_request(
         &mut self,
-        client: BigQueryWriteClient<InterceptedService<Channel, AuthInterceptor>>,
-    ) {
-        self.client = Some(client);
+        ctx: &SinkContext,
+        request: AppendRowsRequest,
+        pending: PendingRequest,
+    ) -> Result<bool> {
+        let channel = self.next_channel % self.clients.len();
+        self.next_channel = (channel + 1) % self.clients.len();
+        // reuse the open stream of the channel if possible, otherwise open a new one
+        let (request, pending) = match self.append_streams.get(channel).and_then(Option::as_ref) {
+            Some(append_stream) => match append_stream.append(request, pending).await {
+                Ok(()) => return Ok(true),
+                Err(unsent) => unsent,
+            },
+            None => (request, pending),
+        };
+        if let Some(append_stream) = self.append_streams.get_mut(channel) {
+            *append_stream = None;
+        }
+        match self
+            .open_append_stream(ctx, channel, request, pending)
+            .await?
+        {
+            Ok(append_stream) => {
+                if let Some(slot) = self.append_streams.get_mut(channel) {
+                    *slot = Some(append_stream);
+                }
+                Ok(true)
+            }
+            Err(pending) => {
+                if self.config.request_metrics {
+                    let now = nanotime();
+                    let duration = now.saturating_sub(pending.request_start);
+                    self.metrics_tx
+                        .send(pending.stats.payload(ctx, "timeout", duration, now))
+                        .await?;
+                }
+                self.connection_loss.notify(ctx).await?;
+                Ok(false)
+            }
+        }
+    }
+
+    #[cfg(test)]
+    fn set_client<C: WriteClient + 'static>(&mut self, client: C) {
+        self.set_clients(vec![Box::new(client) as Box<dyn WriteClient>]);
+    }
+
+    #[cfg(test)]
+    fn set_write_stream(&mut self, write_stream: WriteStream, mapping: JsonToProtobufMapping) {
+        self.write_stream = Some(write_stream);
+        self.mapping = Some(mapping);
     }
 }
 
@@ -305,12 +1080,21 @@ impl Sink for GbqSink {
         event: Event,
         ctx: &SinkContext,
         _serializer: &mut EventSerializer,
-        _start: u64,
+        start: u64,
     ) -> Result<SinkReply> {
-        let client = self.client.as_mut().ok_or(ErrorKind::ClientNotAvailable(
-            "BigQuery",
-            "The client is not connected",
-        ))?;
+        if self.clients.is_empty() {
+            return Err(
+                ErrorKind::ClientNotAvailable("BigQuery", "The client is not connected").into(),
+            );
+        }
+        if self.write_stream.is_some() && self.schema_refresh_due() {
+            debug!("{ctx} Refreshing the table schema");
+            if let Err(e) = self.create_write_stream(ctx).await {
+                // keep writing with the current schema and try again after the next interval
+                warn!("{ctx} Failed to refresh the table schema: {e}");
+                self.schema_refreshed_at = nanotime();
+            }
+        }
         let write_stream = self
             .write_stream
             .as_ref()
@@ -326,119 +1110,153 @@ impl Sink for GbqSink {
         let mut serialized_rows = Vec::with_capacity(event.len());
 
         for data in event.value_iter() {
-            serialized_rows.push(mapping.map(data)?);
+            if let Some(row) = mapping.map_with_policy(data, self.config.on_encode_error)? {
+                serialized_rows.push(row);
+            }
+        }
+        if serialized_rows.is_empty() {
+            // all rows have been dropped, nothing to send
+            return Ok(SinkReply::ACK);
+        }
+        let stats = AppendStats::new(&serialized_rows);
+        if self.config.dry_run {
+            info!(
+                "{ctx} Dry run, not appending {} rows ({} bytes) to {}",
+                stats.rows, stats.bytes, write_stream.name
+            );
+            return Ok(SinkReply::ACK);
         }
 
-        let request = AppendRowsRequest {
-            write_stream: write_stream.name.clone(),
-            offset: None,
-            trace_id: "".to_string(),
-            rows: Some(append_rows_request::Rows::ProtoRows(ProtoData {
-                writer_schema: Some(ProtoSchema {
-                    proto_descriptor: Some(mapping.descriptor().clone()),
-                }),
-                rows: Some(ProtoRows { serialized_rows }),
-            })),
-        };
-
-        let append_response = client
-            .append_rows(stream::iter(vec![request]))
-            .timeout(Duration::from_nanos(self.config.request_timeout))
-            .await;
-
-        let append_response = if let Ok(append_response) = append_response {
-            append_response
-        } else {
-            ctx.notifier.connection_lost().await?;
-
-            return Ok(SinkReply::FAIL);
-        };
-
-        if let Ok(x) = append_response?
-            .into_inner()
-            .next()
-            .timeout(Duration::from_nanos(self.config.request_timeout))
-            .await
-        {
-            match x {
-                Some(Ok(_)) => Ok(SinkReply::ACK),
-                Some(Err(e)) => {
-                    error!("BigQuery error: {}", e);
-
-                    Ok(SinkReply::FAIL)
+        let write_stream = write_stream.name.clone();
+        let descriptor = mapping.descriptor().clone();
+        let splits = split_rows(serialized_rows, self.config.max_request_bytes);
+        if splits.len() > 1 {
+            debug!(
+                "{ctx} Splitting {} rows ({} bytes) over {} append requests",
+                stats.rows,
+                stats.bytes,
+                splits.len()
+            );
+        }
+        let contraflow_data = event.transactional.then(|| ContraflowData::from(&event));
+        // only kept if it might end up in the dead letter
+        let event = self.dead_letter.is_some().then(|| event);
+        let event_reply = Arc::new(EventReply::new(contraflow_data, event, splits.len()));
+        for (i, serialized_rows) in splits.into_iter().enumerate() {
+            let stats = AppendStats::new(&serialized_rows);
+            let request = AppendRowsRequest {
+                write_stream: write_stream.clone(),
+                offset: None,
+                trace_id: "".to_string(),
+                rows: Some(append_rows_request::Rows::ProtoRows(ProtoData {
+                    writer_schema: Some(ProtoSchema {
+                        proto_descriptor: Some(descriptor.clone()),
+                    }),
+                    rows: Some(ProtoRows { serialized_rows }),
+                })),
+            };
+            let pending = PendingRequest {
+                event: event_reply.clone(),
+                start,
+                request_start: nanotime(),
+                stats,
+            };
+            if !self.send_request(ctx, request, pending).await? {
+                if i == 0 {
+                    // nothing has been sent, so there are no responses to wait for
+                    return Ok(SinkReply::FAIL);
+                }
+                // the event is failed right away, no matter how its other requests end
+                if let Some(reply) = event_reply.complete(false, 0) {
+                    self.reply_tx.send(reply).await?;
                 }
-                None => Ok(SinkReply::NONE),
+                return Ok(SinkReply::NONE);
             }
-        } else {
-            ctx.notifier.connection_lost().await?;
+        }
+        // the event is acked or failed once the responses to its requests arrive
+        Ok(SinkReply::NONE)
+    }
 
-            Ok(SinkReply::FAIL)
+    async fn metrics(&mut self, _timestamp: u64, _ctx: &SinkContext) -> Vec<EventPayload> {
+        let mut metrics = Vec::with_capacity(self.metrics_rx.len());
+        while let Ok(metric) = self.metrics_rx.try_recv() {
+            metrics.push(metric);
         }
+        metrics
     }
 
     async fn connect(&mut self, ctx: &SinkContext, _attempt: &Attempt) -> Result<bool> {
         info!("{ctx} Connecting to BigQuery");
-        let token = Token::new()?;
-
         let tls_config = ClientTlsConfig::new()
             .ca_certificate(Certificate::from_pem(googapis::CERTIFICATES))
             .domain_name("bigquerystorage.googleapis.com");
 
-        let channel = Channel::from_static("https://bigquerystorage.googleapis.com")
+        let mut endpoint = Channel::from_static("https://bigquerystorage.googleapis.com")
             .connect_timeout(Duration::from_nanos(self.config.connect_timeout))
-            .tls_config(tls_config)?
-            .connect()
-            .await?;
+            .tls_config(tls_config)?;
+        if let Some(concurrency_limit) = self.config.concurrency_limit {
+            endpoint = endpoint.concurrency_limit(concurrency_limit);
+        }
 
-        let interceptor_ctx = ctx.clone();
-        let mut client = BigQueryWriteClient::with_interceptor(
-            channel,
-            AuthInterceptor {
-                token: Box::new(move || match token.header_value() {
-                    Ok(val) => Ok(val),
-                    Err(e) => {
-                        error!("{interceptor_ctx} Failed to get token for BigQuery: {}", e);
-
-                        Err(Status::unavailable(
-                            "Failed to retrieve authentication token.",
-                        ))
-                    }
-                }),
-            },
-        );
-
-        let write_stream = client
-            .create_write_stream(CreateWriteStreamRequest {
-                parent: self.config.table_id.clone(),
-                write_stream: Some(WriteStream {
-                    // The stream name here will be ignored and a generated value will be set in the response
-                    name: "".to_string(),
-                    r#type: i32::from(write_stream::Type::Committed),
-                    create_time: None,
-                    commit_time: None,
-                    table_schema: None,
-                }),
-            })
-            .await?
-            .into_inner();
-
-        let mapping = JsonToProtobufMapping::new(
-            &write_stream
-                .table_schema
-                .as_ref()
-                .ok_or(ErrorKind::GbqSinkFailed("Table schema was not provided"))?
-                .clone()
-                .fields,
-            ctx,
-        );
+        let mut clients: Vec<Box<dyn WriteClient>> = Vec::with_capacity(self.config.channel_count);
+        for _ in 0..self.config.channel_count {
+            let channel = endpoint
+                .connect()
+                .await
+                .connect_step(ENDPOINT, ConnectStep::Tcp)?;
+            let token = Token::new().connect_step(ENDPOINT, ConnectStep::Auth)?;
+            let interceptor_ctx = ctx.clone();
+            let client = BigQueryWriteClient::with_interceptor(
+                channel,
+                AuthInterceptor {
+                    token: Box::new(move || match token.header_value() {
+                        Ok(val) => Ok(val),
+                        Err(e) => {
+                            error!("{interceptor_ctx} Failed to get token for BigQuery: {}", e);
+
+                            Err(Status::unavailable(
+                                "Failed to retrieve authentication token.",
+                            ))
+                        }
+                    }),
+                },
+            );
+            clients.push(Box::new(client));
+        }
 
-        self.mapping = Some(mapping);
-        self.write_stream = Some(write_stream);
-        self.client = Some(client);
+        self.set_clients(clients);
+        if let Some(auto_create_table) = self.config.auto_create_table.as_ref() {
+            let mut table_client =
+                RestTableClient::new(Duration::from_nanos(self.config.request_timeout))?;
+            if ensure_table(
+                &mut table_client,
+                &self.config.table_id,
+                &auto_create_table.schema,
+            )
+            .await
+            .connect_step(&self.config.table_id, ConnectStep::Schema)?
+            {
+                info!("{ctx} Created table {}", self.config.table_id);
+            }
+        }
+        // this fetches the schema of the table
+        self.create_write_stream(ctx)
+            .await
+            .connect_step(&self.config.table_id, ConnectStep::Schema)?;
+        self.connection_loss.reset();
 
         Ok(true)
     }
 
+    async fn on_stop(&mut self, ctx: &SinkContext) -> Result<()> {
+        // don't drop the appends in flight, their events still need to be acked or failed
+        let timeout = Duration::from_nanos(self.config.request_timeout);
+        for append_stream in self.append_streams.iter_mut().filter_map(Option::take) {
+            append_stream.flush(ctx, timeout).await;
+        }
+        Ok(())
+    }
+
     fn auto_ack(&self) -> bool {
         false
     }
@@ -450,8 +1268,12 @@ mod test {
     use crate::connectors::impls::gbq;
     use crate::connectors::reconnect::ConnectionLostNotifier;
     use crate::connectors::tests::ConnectorHarness;
+    use crate::connectors::Msg;
     use googapis::google::cloud::bigquery::storage::v1::table_field_schema::Mode;
-    use std::sync::Arc;
+    use googapis::google::cloud::bigquery::storage::v1::TableSchema;
+    use rand::rngs::SmallRng;
+    use rand::SeedableRng;
+    use tremor_pipeline::EventId;
     use value_trait::StaticNode;
 
     #[test]
@@ -604,26 +1426,18 @@ mod test {
         let data = [
             (
                 Value::String("asdf".into()),
-                Field {
-                    table_type: TableType::Int64,
-                    tag: 1,
-                    subfields: Default::default(),
-                },
+                mapped_field(TableType::Int64, 1),
             ),
             (
                 Value::Static(StaticNode::F64(1.243)),
-                Field {
-                    table_type: TableType::String,
-                    tag: 2,
-                    subfields: Default::default(),
-                },
+                mapped_field(TableType::String, 2),
             ),
         ];
 
         for (value, field) in data {
             let mut result_data = vec![];
 
-            let result = encode_field(&value, &field, &mut result_data);
+            let result = encode_field(&value, &field, BytesEncoding::Raw, &mut result_data);
 
             assert!(result.is_err());
         }
@@ -649,11 +1463,8 @@ mod test {
             assert!(
                 encode_field(
                     &Value::String("I".into()),
-                    &Field {
-                        table_type: item,
-                        tag: 123,
-                        subfields: Default::default()
-                    },
+                    &mapped_field(item, 123),
+                    BytesEncoding::Raw,
                     &mut result
                 )
                 .is_ok(),
@@ -673,46 +1484,73 @@ mod test {
         let input = Value::Object(Box::new(values));
 
         let mut subfields = HashMap::new();
-        subfields.insert(
-            "a".into(),
-            Field {
-                table_type: TableType::Int64,
-                tag: 1,
-                subfields: Default::default(),
-            },
-        );
-        subfields.insert(
-            "b".into(),
-            Field {
-                table_type: TableType::Int64,
-                tag: 2,
-                subfields: Default::default(),
-            },
-        );
+        subfields.insert("a".into(), mapped_field(TableType::Int64, 1));
+        subfields.insert("b".into(), mapped_field(TableType::Int64, 2));
 
         let field = Field {
-            table_type: TableType::Struct,
-            tag: 1024,
             subfields,
+            ..mapped_field(TableType::Struct, 1024)
         };
 
         let mut result = Vec::new();
-        assert!(encode_field(&input, &field, &mut result).is_ok());
+        assert!(encode_field(&input, &field, BytesEncoding::Raw, &mut result).is_ok());
 
         assert_eq!([130u8, 64u8, 5u8, 8u8, 1u8, 16u8, 128u8, 8u8], result[..])
     }
 
     #[test]
-    pub fn can_encode_a_double() {
-        let value = Value::Static(StaticNode::F64(1.2345));
+    pub fn can_encode_a_repeated_struct() {
+        let input = literal!([{"a": 1, "b": 1024}, {"a": 2, "b": 3}]);
+
+        let mut subfields = HashMap::new();
+        subfields.insert("a".into(), mapped_field(TableType::Int64, 1));
+        subfields.insert("b".into(), mapped_field(TableType::Int64, 2));
+
         let field = Field {
-            table_type: TableType::Double,
-            tag: 2,
-            subfields: Default::default(),
+            repeated: true,
+            subfields,
+            ..mapped_field(TableType::Struct, 1024)
         };
 
         let mut result = Vec::new();
-        assert!(encode_field(&value, &field, &mut result).is_ok());
+        assert!(encode_field(&input, &field, BytesEncoding::Raw, &mut result).is_ok());
+
+        // one length delimited message per element, with the fields in object order
+        assert_eq!(
+            [130u8, 64u8, 5u8, 8u8, 1u8, 16u8, 128u8, 8u8, 130u8, 64u8, 4u8, 8u8, 2u8, 16u8, 3u8],
+            result[..]
+        );
+
+        // a single object is not a valid value for a repeated field
+        let mut result = Vec::new();
+        assert!(
+            encode_field(&literal!({"a": 1}), &field, BytesEncoding::Raw, &mut result).is_err()
+        );
+    }
+
+    #[test]
+    pub fn can_encode_repeated_scalars() {
+        let field = Field {
+            repeated: true,
+            ..mapped_field(TableType::Int64, 1)
+        };
+
+        let mut result = Vec::new();
+        assert!(encode_field(&literal!([1, 2]), &field, BytesEncoding::Raw, &mut result).is_ok());
+        assert_eq!([8u8, 1u8, 8u8, 2u8], result[..]);
+
+        let mut result = Vec::new();
+        assert!(encode_field(&literal!([]), &field, BytesEncoding::Raw, &mut result).is_ok());
+        assert!(result.is_empty());
+    }
+
+    #[test]
+    pub fn can_encode_a_double() {
+        let value = Value::Static(StaticNode::F64(1.2345));
+        let field = mapped_field(TableType::Double, 2);
+
+        let mut result = Vec::new();
+        assert!(encode_field(&value, &field, BytesEncoding::Raw, &mut result).is_ok());
 
         assert_eq!(
             [17u8, 141u8, 151u8, 110u8, 18u8, 131u8, 192u8, 243u8, 63u8],
@@ -723,14 +1561,10 @@ mod test {
     #[test]
     pub fn can_encode_boolean() {
         let value = Value::Static(StaticNode::Bool(false));
-        let field = Field {
-            table_type: TableType::Bool,
-            tag: 43,
-            subfields: Default::default(),
-        };
+        let field = mapped_field(TableType::Bool, 43);
 
         let mut result = Vec::new();
-        assert!(encode_field(&value, &field, &mut result).is_ok());
+        assert!(encode_field(&value, &field, BytesEncoding::Raw, &mut result).is_ok());
 
         assert_eq!([216u8, 2u8, 0u8], result[..]);
     }
@@ -738,29 +1572,117 @@ mod test {
     #[test]
     pub fn can_encode_bytes() {
         let value = Value::Bytes(vec![0x1u8, 0x2u8, 0x3u8].into());
-        let field = Field {
-            table_type: TableType::Bytes,
-            tag: 1,
-            subfields: Default::default(),
-        };
+        let field = mapped_field(TableType::Bytes, 1);
+
+        let mut result = Vec::new();
+        assert!(encode_field(&value, &field, BytesEncoding::Raw, &mut result).is_ok());
+
+        assert_eq!([10u8, 3u8, 1u8, 2u8, 3u8], result[..]);
+    }
+
+    #[test]
+    pub fn can_decode_encoded_bytes() -> Result<()> {
+        let field = mapped_field(TableType::Bytes, 1);
 
         let mut result = Vec::new();
-        assert!(encode_field(&value, &field, &mut result).is_ok());
+        encode_field(
+            &Value::from("AQID"),
+            &field,
+            BytesEncoding::Base64,
+            &mut result,
+        )?;
+        assert_eq!([10u8, 3u8, 1u8, 2u8, 3u8], result[..]);
 
+        let mut result = Vec::new();
+        encode_field(
+            &Value::from("010203"),
+            &field,
+            BytesEncoding::Hex,
+            &mut result,
+        )?;
         assert_eq!([10u8, 3u8, 1u8, 2u8, 3u8], result[..]);
+
+        // byte values are not decoded
+        let mut result = Vec::new();
+        encode_field(
+            &Value::Bytes(vec![0x1u8, 0x2u8, 0x3u8].into()),
+            &field,
+            BytesEncoding::Hex,
+            &mut result,
+        )?;
+        assert_eq!([10u8, 3u8, 1u8, 2u8, 3u8], result[..]);
+
+        // strings are only decoded if an encoding is configured
+        let mut result = Vec::new();
+        encode_field(
+            &Value::from("AQID"),
+            &field,
+            BytesEncoding::Raw,
+            &mut result,
+        )?;
+        assert_eq!(b"\n\x04AQID", &result[..]);
+        Ok(())
+    }
+
+    #[test]
+    pub fn fails_on_invalid_encoded_bytes() {
+        let field = mapped_field(TableType::Bytes, 1);
+
+        let mut result = Vec::new();
+        let e = encode_field(
+            &Value::from("snot"),
+            &field,
+            BytesEncoding::Hex,
+            &mut result,
+        );
+        assert!(matches!(
+            e,
+            Err(Error(
+                ErrorKind::BigQueryTypeMismatch("hex encoded bytes", ValueType::String),
+                _
+            ))
+        ));
+
+        let e = encode_field(
+            &Value::from("!!"),
+            &field,
+            BytesEncoding::Base64,
+            &mut result,
+        );
+        assert!(matches!(
+            e,
+            Err(Error(
+                ErrorKind::BigQueryTypeMismatch("base64 encoded bytes", ValueType::String),
+                _
+            ))
+        ));
+    }
+
+    #[test]
+    fn bytes_encoding_config() -> Result<()> {
+        let config = Config::new(&literal!({
+            "table_id": "doesnotmatter",
+            "connect_timeout": 1000000,
+            "request_timeout": 1000000
+        }))?;
+        assert_eq!(BytesEncoding::Raw, config.bytes_encoding);
+        let config = Config::new(&literal!({
+            "table_id": "doesnotmatter",
+            "connect_timeout": 1000000,
+            "request_timeout": 1000000,
+            "bytes_encoding": "base64"
+        }))?;
+        assert_eq!(BytesEncoding::Base64, config.bytes_encoding);
+        Ok(())
     }
 
     #[test]
     pub fn can_encode_json() {
         let value = Value::Object(Box::new(halfbrown::HashMap::new()));
-        let field = Field {
-            table_type: TableType::Json,
-            tag: 1,
-            subfields: Default::default(),
-        };
+        let field = mapped_field(TableType::Json, 1);
 
         let mut result = Vec::new();
-        assert!(encode_field(&value, &field, &mut result).is_ok());
+        assert!(encode_field(&value, &field, BytesEncoding::Raw, &mut result).is_ok());
 
         // json is currently not supported, so we expect the field to be skipped
         assert_eq!([] as [u8; 0], result[..]);
@@ -769,14 +1691,10 @@ mod test {
     #[test]
     pub fn can_encode_interval() {
         let value = Value::String("".into());
-        let field = Field {
-            table_type: TableType::Interval,
-            tag: 1,
-            subfields: Default::default(),
-        };
+        let field = mapped_field(TableType::Interval, 1);
 
         let mut result = Vec::new();
-        assert!(encode_field(&value, &field, &mut result).is_ok());
+        assert!(encode_field(&value, &field, BytesEncoding::Raw, &mut result).is_ok());
 
         // interval is currently not supported, so we expect the field to be skipped
         assert_eq!([] as [u8; 0], result[..]);
@@ -785,14 +1703,10 @@ mod test {
     #[test]
     pub fn can_skips_unspecified() {
         let value = Value::String("".into());
-        let field = Field {
-            table_type: TableType::Unspecified,
-            tag: 1,
-            subfields: Default::default(),
-        };
+        let field = mapped_field(TableType::Unspecified, 1);
 
         let mut result = Vec::new();
-        assert!(encode_field(&value, &field, &mut result).is_ok());
+        assert!(encode_field(&value, &field, BytesEncoding::Raw, &mut result).is_ok());
 
         // Fields should never have the "Unspecified" type, if that happens best we can do is to log a warning and ignore them
         assert_eq!([] as [u8; 0], result[..]);
@@ -847,6 +1761,155 @@ mod test {
         );
     }
 
+    #[test]
+    pub fn mapping_of_a_repeated_struct() -> Result<()> {
+        let sink_context = test_sink_context();
+        let field = |name: &str, table_type: TableType, mode: Mode, fields| TableFieldSchema {
+            name: name.to_string(),
+            r#type: table_type.into(),
+            mode: mode.into(),
+            fields,
+            description: "".to_string(),
+            max_length: 0,
+            precision: 0,
+            scale: 0,
+        };
+        let mapping = JsonToProtobufMapping::new(
+            &vec![field(
+                "items",
+                TableType::Struct,
+                Mode::Repeated,
+                vec![
+                    field("a", TableType::Int64, Mode::Required, vec![]),
+                    field("b", TableType::String, Mode::Nullable, vec![]),
+                ],
+            )],
+            &sink_context,
+        );
+
+        let descriptor = mapping.descriptor();
+        assert_eq!(
+            Some(field_descriptor_proto::Label::Repeated as i32),
+            descriptor.field[0].label
+        );
+        assert_eq!(None, descriptor.nested_type[0].field[0].label);
+
+        let row = literal!({"items": [{"a": 1, "b": "x"}, {"a": 2, "b": "y"}]});
+        assert_eq!(
+            vec![10_u8, 5, 8, 1, 18, 1, b'x', 10, 5, 8, 2, 18, 1, b'y'],
+            mapping.map(&row)?
+        );
+        Ok(())
+    }
+
+    #[test]
+    pub fn nulls_of_nullable_columns_are_omitted() -> Result<()> {
+        let sink_context = test_sink_context();
+        let mapping = JsonToProtobufMapping::new(
+            &vec![
+                TableFieldSchema {
+                    mode: Mode::Required.into(),
+                    ..field("a", TableType::Int64)
+                },
+                field("b", TableType::String),
+            ],
+            &sink_context,
+        );
+
+        assert_eq!(
+            mapping.map(&literal!({"a": 1}))?,
+            mapping.map(&literal!({"a": 1, "b": null}))?
+        );
+        assert_eq!(vec![8_u8, 1], mapping.map(&literal!({"a": 1, "b": null}))?);
+        assert!(mapping.map(&literal!({"a": null, "b": "x"})).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn temporal_integers() -> Result<()> {
+        assert_eq!(
+            "1970-01-01",
+            temporal_from_integer(TableType::Date, 0)?.as_str()
+        );
+        assert_eq!(
+            "2022-10-16",
+            temporal_from_integer(TableType::Date, 19_281)?.as_str()
+        );
+        assert_eq!(
+            "1969-12-31",
+            temporal_from_integer(TableType::Date, -1)?.as_str()
+        );
+        assert_eq!(
+            "00:00:00.000000",
+            temporal_from_integer(TableType::Time, 0)?.as_str()
+        );
+        assert_eq!(
+            "13:37:42.000123",
+            temporal_from_integer(TableType::Time, 49_062_000_123)?.as_str()
+        );
+        assert_eq!(
+            "23:59:59.999999",
+            temporal_from_integer(TableType::Time, 86_399_999_999)?.as_str()
+        );
+        assert_eq!(
+            "2022-10-16T13:37:42.000123",
+            temporal_from_integer(TableType::Datetime, 1_665_927_462_000_123)?.as_str()
+        );
+        assert_eq!(
+            "1969-12-31T23:59:59.999999",
+            temporal_from_integer(TableType::Datetime, -1)?.as_str()
+        );
+
+        assert!(temporal_from_integer(TableType::Time, -1).is_err());
+        assert!(temporal_from_integer(TableType::Time, 86_400_000_000).is_err());
+        assert_eq!(
+            "0001-01-01",
+            temporal_from_integer(TableType::Date, -719_162)?.as_str()
+        );
+        assert!(temporal_from_integer(TableType::Date, 2_932_897).is_err());
+        assert!(temporal_from_integer(TableType::Date, i64::MIN).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn temporal_integer_columns() -> Result<()> {
+        let sink_context = test_sink_context();
+        let field = |name: &str, table_type: TableType| TableFieldSchema {
+            name: name.to_string(),
+            r#type: table_type.into(),
+            mode: Mode::Required.into(),
+            fields: vec![],
+            description: "".to_string(),
+            max_length: 0,
+            precision: 0,
+            scale: 0,
+        };
+        let schema = vec![
+            field("day", TableType::Date),
+            field("time", TableType::Time),
+            field("other_day", TableType::Date),
+        ];
+        let mapping = JsonToProtobufMapping::new(&schema, &sink_context).with_temporal_integers(
+            &["day".to_string(), "time".to_string(), "missing".to_string()],
+            &sink_context,
+        );
+
+        assert_eq!(
+            mapping.map(&literal!({"day": "2022-10-16", "time": "13:37:42.000123"}))?,
+            mapping.map(&literal!({"day": 19_281, "time": 49_062_000_123_u64}))?
+        );
+        // strings are still accepted as they are
+        assert_eq!(
+            mapping.map(&literal!({"day": "snot"}))?,
+            JsonToProtobufMapping::new(&schema, &sink_context).map(&literal!({"day": "snot"}))?
+        );
+        assert!(mapping.map(&literal!({"time": -1})).is_err());
+        assert!(mapping.map(&literal!({"day": 1.5})).is_err());
+        // only the configured columns accept integers
+        assert!(mapping.map(&literal!({"other_day": 19_281})).is_err());
+        Ok(())
+    }
+
     #[test]
     pub fn can_map_json_to_protobuf() {
         let (rx, _tx) = async_std::channel::unbounded();
@@ -1014,6 +2077,132 @@ mod test {
         }
     }
 
+    fn encode_error_mapping() -> JsonToProtobufMapping {
+        let sink_context = test_sink_context();
+        let field = |name: &str, table_type: TableType| TableFieldSchema {
+            name: name.to_string(),
+            r#type: table_type.into(),
+            mode: Mode::Required.into(),
+            fields: vec![],
+            description: "".to_string(),
+            max_length: 0,
+            precision: 0,
+            scale: 0,
+        };
+        JsonToProtobufMapping::new(
+            &vec![field("a", TableType::Int64), field("b", TableType::Bool)],
+            &sink_context,
+        )
+    }
+
+    #[test]
+    fn encode_error_policy_fail() {
+        let mapping = encode_error_mapping();
+        let row = literal!({"a": "snot", "b": true});
+        let result = mapping.map_with_policy(&row, EncodeErrorPolicy::Fail);
+
+        assert!(matches!(
+            result,
+            Err(Error(
+                ErrorKind::BigQueryTypeMismatch("i64", ValueType::String),
+                _
+            ))
+        ));
+    }
+
+    #[test]
+    fn encode_error_policy_drop_field() -> Result<()> {
+        let mapping = encode_error_mapping();
+        let row = literal!({"a": "snot", "b": true});
+        let result = mapping.map_with_policy(&row, EncodeErrorPolicy::DropField)?;
+
+        // only field b (tag 2, varint) with value true
+        assert_eq!(Some(vec![16_u8, 1]), result);
+        Ok(())
+    }
+
+    #[test]
+    fn encode_error_policy_drop_row() -> Result<()> {
+        let mapping = encode_error_mapping();
+        let row = literal!({"a": "snot", "b": true});
+        assert_eq!(
+            None,
+            mapping.map_with_policy(&row, EncodeErrorPolicy::DropRow)?
+        );
+
+        let row = literal!({"a": 1, "b": true});
+        assert_eq!(
+            Some(vec![8_u8, 1, 16, 1]),
+            mapping.map_with_policy(&row, EncodeErrorPolicy::DropRow)?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn encode_error_policy_config() -> Result<()> {
+        let config = Config::new(&literal!({
+            "table_id": "doesnotmatter",
+            "connect_timeout": 1000000,
+            "request_timeout": 1000000
+        }))?;
+        assert_eq!(EncodeErrorPolicy::Fail, config.on_encode_error);
+        let config = Config::new(&literal!({
+            "table_id": "doesnotmatter",
+            "connect_timeout": 1000000,
+            "request_timeout": 1000000,
+            "on_encode_error": "drop_row"
+        }))?;
+        assert_eq!(EncodeErrorPolicy::DropRow, config.on_encode_error);
+        Ok(())
+    }
+
+    #[test]
+    fn append_stats() -> Result<()> {
+        let mapping = encode_error_mapping();
+        let rows: Vec<Vec<u8>> = [
+            literal!({"a": 1, "b": true}),
+            literal!({"a": 300}),
+            literal!({"b": false}),
+        ]
+        .iter()
+        .map(|row| mapping.map(row))
+        .collect::<Result<_>>()?;
+
+        let stats = AppendStats::new(&rows);
+        // 4 bytes, 3 bytes (varint 300 takes 2 bytes) and 2 bytes
+        assert_eq!(AppendStats { rows: 3, bytes: 9 }, stats);
+
+        let payload = stats.payload(&test_sink_context(), "ack", 42, 123);
+        let metric = payload.suffix().value();
+        assert_eq!(Some("gbq_append_request"), metric.get_str("measurement"));
+        let fields = metric.get("fields").expect("no fields");
+        assert_eq!(Some(3), fields.get_u64("rows"));
+        assert_eq!(Some(9), fields.get_u64("bytes"));
+        assert_eq!(Some(42), fields.get_u64("duration_ns"));
+        let tags = metric.get("tags").expect("no tags");
+        assert_eq!(Some("ack"), tags.get_str("result"));
+        assert_eq!(Some("flow::connector"), tags.get_str("connector"));
+        assert_eq!(Some(123), metric.get_u64("timestamp"));
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn request_metrics_require_metrics_interval() -> Result<()> {
+        let config = literal!({
+            "config": {
+                "table_id": "doesnotmatter",
+                "connect_timeout": 1000000,
+                "request_timeout": 1000000,
+                "request_metrics": true
+            }
+        });
+        let result =
+            ConnectorHarness::new(function_name!(), &gbq::writer::Builder::default(), &config)
+                .await;
+        assert!(result.is_err());
+        Ok(())
+    }
+
     #[test]
     fn fails_if_the_event_is_not_an_object() {
         let (rx, _tx) = async_std::channel::unbounded();
@@ -1062,6 +2251,681 @@ mod test {
         Ok(())
     }
 
+    #[async_std::test]
+    async fn dry_run_does_not_append() -> Result<()> {
+        let ctx = test_sink_context();
+        let mut serializer = test_serializer()?;
+        let config = test_config(&literal!({
+            "request_metrics": true,
+            "dry_run": true
+        }))?;
+
+        let (reply_tx, _reply_rx) = bounded(1);
+        let mut sink = GbqSink::new(config, reply_tx);
+        // appending via this client would never succeed
+        sink.set_client(BigQueryWriteClient::with_interceptor(
+            Channel::from_static("http://example.com").connect_lazy(),
+            AuthInterceptor {
+                token: Box::new(|| Ok(Arc::new(String::new()))),
+            },
+        ));
+        set_test_write_stream(&mut sink);
+
+        let event = Event {
+            data: (literal!({"a": 1, "b": true}), Value::object()).into(),
+            ..Event::default()
+        };
+        let reply = sink.on_event("", event, &ctx, &mut serializer, 0).await?;
+        assert_eq!(SinkReply::ACK, reply);
+        // no append request was made
+        assert!(sink.metrics(0, &ctx).await.is_empty());
+
+        // rows are still encoded against the schema
+        let event = Event {
+            data: (literal!({"a": "snot", "b": true}), Value::object()).into(),
+            ..Event::default()
+        };
+        let result = sink.on_event("", event, &ctx, &mut serializer, 0).await;
+        assert!(matches!(
+            result,
+            Err(Error(
+                ErrorKind::BigQueryTypeMismatch("i64", ValueType::String),
+                _
+            ))
+        ));
+        Ok(())
+    }
+
+    /// Creates write streams with the given schemas, one after another,
+    /// and answers every append request with an empty response
+    #[derive(Default)]
+    struct FakeWriteClient {
+        schemas: Vec<Vec<TableFieldSchema>>,
+        /// number of append streams opened
+        streams: Arc<AtomicUsize>,
+        /// field names of the schema of each append request
+        appended: Arc<std::sync::Mutex<Vec<Vec<String>>>>,
+        /// number of write streams created
+        created: usize,
+        /// names of the finalized write streams
+        finalized: Arc<std::sync::Mutex<Vec<String>>>,
+        /// how long each response takes
+        response_delay: Duration,
+        /// number of responses sent
+        responded: Arc<AtomicUsize>,
+    }
+
+    #[async_trait::async_trait]
+    impl WriteClient for FakeWriteClient {
+        async fn create_write_stream(&mut self, _table_id: &str) -> Result<WriteStream> {
+            if self.schemas.is_empty() {
+                return Err("no schema left".into());
+            }
+            self.created += 1;
+            Ok(WriteStream {
+                name: format!("snot{}", self.created),
+                r#type: i32::from(write_stream::Type::Committed),
+                create_time: None,
+                commit_time: None,
+                table_schema: Some(TableSchema {
+                    fields: self.schemas.remove(0),
+                }),
+            })
+        }
+
+        async fn finalize_write_stream(&mut self, name: &str) -> Result<()> {
+            self.finalized
+                .lock()
+                .map_err(|_| "poisoned lock")?
+                .push(name.to_string());
+            Ok(())
+        }
+
+        async fn append_rows(
+            &mut self,
+            requests: Receiver<AppendRowsRequest>,
+        ) -> Result<ResponseStream> {
+            self.streams.fetch_add(1, Ordering::AcqRel);
+            let appended = self.appended.clone();
+            let responded = self.responded.clone();
+            let response_delay = self.response_delay;
+            Ok(Box::pin(futures::StreamExt::then(
+                requests,
+                move |request| {
+                    let fields = match request.rows {
+                        Some(append_rows_request::Rows::ProtoRows(ProtoData {
+                            writer_schema:
+                                Some(ProtoSchema {
+                                    proto_descriptor: Some(descriptor),
+                                }),
+                            ..
+                        })) => descriptor
+                            .field
+                            .iter()
+                            .filter_map(|f| f.name.clone())
+                            .collect(),
+                        _ => vec![],
+                    };
+                    if let Ok(mut appended) = appended.lock() {
+                        appended.push(fields);
+                    }
+                    let responded = responded.clone();
+                    async move {
+                        async_std::task::sleep(response_delay).await;
+                        responded.fetch_add(1, Ordering::AcqRel);
+                        Ok(AppendRowsResponse::default())
+                    }
+                },
+            )))
+        }
+    }
+
+    fn field(name: &str, table_type: TableType) -> TableFieldSchema {
+        TableFieldSchema {
+            name: name.to_string(),
+            r#type: table_type.into(),
+            mode: Mode::Nullable.into(),
+            fields: vec![],
+            description: "".to_string(),
+            max_length: 0,
+            precision: 0,
+            scale: 0,
+        }
+    }
+
+    fn test_sink_context() -> SinkContext {
+        let (rx, _tx) = async_std::channel::unbounded();
+        SinkContext {
+            uid: Default::default(),
+            alias: Alias::new("flow", "connector"),
+            connector_type: Default::default(),
+            quiescence_beacon: Default::default(),
+            notifier: ConnectionLostNotifier::new(rx),
+        }
+    }
+
+    fn test_serializer() -> Result<EventSerializer> {
+        EventSerializer::new(
+            None,
+            CodecReq::Structured,
+            vec![],
+            &ConnectorType::from(""),
+            &Alias::new("flow", "connector"),
+        )
+    }
+
+    /// A config for `doesnotmatter` with the settings in `extra`
+    fn test_config(extra: &Value<'static>) -> Result<Config> {
+        let mut config = literal!({
+            "table_id": "doesnotmatter",
+            "connect_timeout": 1000000,
+            "request_timeout": 1000000000
+        });
+        if let (Some(config), Some(extra)) = (config.as_object_mut(), extra.as_object()) {
+            for (k, v) in extra {
+                config.insert(k.clone(), v.clone());
+            }
+        }
+        Config::new(&config)
+    }
+
+    /// Sets a write stream without schema, mapping rows with `encode_error_mapping`
+    fn set_test_write_stream(sink: &mut GbqSink) {
+        sink.set_write_stream(
+            WriteStream {
+                name: "snot".to_string(),
+                r#type: i32::from(write_stream::Type::Committed),
+                create_time: None,
+                commit_time: None,
+                table_schema: None,
+            },
+            encode_error_mapping(),
+        );
+    }
+
+    /// A `Field` that is neither repeated nor nullable, without subfields
+    fn mapped_field(table_type: TableType, tag: u32) -> Field {
+        Field {
+            table_type,
+            tag,
+            repeated: false,
+            nullable: false,
+            temporal_integers: false,
+            subfields: HashMap::new(),
+        }
+    }
+
+    #[async_std::test]
+    async fn events_share_the_append_stream() -> Result<()> {
+        let ctx = test_sink_context();
+        let mut serializer = test_serializer()?;
+        let config = test_config(&literal!({}))?;
+
+        let (reply_tx, reply_rx) = bounded(8);
+        let mut sink = GbqSink::new(config, reply_tx);
+        let streams = Arc::new(AtomicUsize::new(0));
+        sink.set_client(FakeWriteClient {
+            streams: streams.clone(),
+            ..FakeWriteClient::default()
+        });
+        set_test_write_stream(&mut sink);
+
+        for id in 0..3 {
+            let event = Event {
+                id: EventId::from_id(0, 0, id),
+                data: (literal!({"a": 1, "b": true}), Value::object()).into(),
+                transactional: true,
+                ..Event::default()
+            };
+            let reply = sink.on_event("", event, &ctx, &mut serializer, 0).await?;
+            // acks arrive asynchronously
+            assert_eq!(SinkReply::NONE, reply);
+        }
+        for id in 0..3 {
+            match reply_rx.recv().await? {
+                AsyncSinkReply::Ack(cf, _) => {
+                    assert_eq!(EventId::from_id(0, 0, id), cf.into_ack(0).id);
+                }
+                _ => return Err("expected an ack".into()),
+            }
+        }
+        assert_eq!(1, streams.load(Ordering::Acquire));
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn flushes_appends_on_stop() -> Result<()> {
+        let ctx = test_sink_context();
+        let mut serializer = test_serializer()?;
+        let config = test_config(&literal!({}))?;
+
+        let (reply_tx, reply_rx) = bounded(8);
+        let mut sink = GbqSink::new(config, reply_tx);
+        sink.set_client(FakeWriteClient::default());
+        set_test_write_stream(&mut sink);
+
+        for id in 0..3 {
+            let event = Event {
+                id: EventId::from_id(0, 0, id),
+                data: (literal!({"a": 1, "b": true}), Value::object()).into(),
+                transactional: true,
+                ..Event::default()
+            };
+            sink.on_event("", event, &ctx, &mut serializer, 0).await?;
+        }
+        sink.on_stop(&ctx).await?;
+
+        // all acks are there once the sink is stopped
+        for id in 0..3 {
+            match reply_rx.try_recv() {
+                Ok(AsyncSinkReply::Ack(cf, _)) => {
+                    assert_eq!(EventId::from_id(0, 0, id), cf.into_ack(0).id);
+                }
+                _ => return Err("expected an ack".into()),
+            }
+        }
+        assert!(sink.append_streams.iter().all(Option::is_none));
+        Ok(())
+    }
+
+    #[test]
+    fn splits_rows() {
+        let rows = |lens: &[usize]| -> Vec<Vec<u8>> { lens.iter().map(|l| vec![0; *l]).collect() };
+        let lens = |splits: Vec<Vec<Vec<u8>>>| -> Vec<Vec<usize>> {
+            splits
+                .iter()
+                .map(|split| split.iter().map(Vec::len).collect())
+                .collect()
+        };
+        assert_eq!(vec![vec![2, 3]], lens(split_rows(rows(&[2, 3]), 5)));
+        assert_eq!(
+            vec![vec![2, 3], vec![4], vec![1]],
+            lens(split_rows(rows(&[2, 3, 4, 1]), 5))
+        );
+        // rows larger than the limit are sent on their own
+        assert_eq!(
+            vec![vec![1], vec![10], vec![1]],
+            lens(split_rows(rows(&[1, 10, 1]), 5))
+        );
+        assert!(split_rows(vec![], 5).is_empty());
+    }
+
+    #[async_std::test]
+    async fn splits_oversized_batches() -> Result<()> {
+        let ctx = test_sink_context();
+        let mut serializer = test_serializer()?;
+        let row = literal!({"a": 1, "b": true});
+        let row_bytes = encode_error_mapping().map(&row)?.len();
+        // room for two rows per request
+        let config = test_config(&literal!({
+            "max_request_bytes": 2 * row_bytes + 1
+        }))?;
+
+        let (reply_tx, reply_rx) = bounded(8);
+        let mut sink = GbqSink::new(config, reply_tx);
+        let appended = Arc::new(std::sync::Mutex::new(Vec::new()));
+        sink.set_client(FakeWriteClient {
+            appended: appended.clone(),
+            ..FakeWriteClient::default()
+        });
+        set_test_write_stream(&mut sink);
+
+        let event = Event {
+            id: EventId::from_id(0, 0, 1),
+            data: (
+                Value::from(vec![row.clone(), row.clone(), row]),
+                Value::object(),
+            )
+                .into(),
+            is_batch: true,
+            transactional: true,
+            ..Event::default()
+        };
+        let reply = sink.on_event("", event, &ctx, &mut serializer, 0).await?;
+        assert_eq!(SinkReply::NONE, reply);
+        sink.on_stop(&ctx).await?;
+
+        // one ack for the event, once both requests succeeded
+        match reply_rx.try_recv() {
+            Ok(AsyncSinkReply::Ack(cf, _)) => {
+                assert_eq!(EventId::from_id(0, 0, 1), cf.into_ack(0).id);
+            }
+            _ => return Err("expected an ack".into()),
+        }
+        assert!(reply_rx.is_empty());
+        assert_eq!(2, appended.lock().map(|a| a.len()).unwrap_or_default());
+        Ok(())
+    }
+
+    #[test]
+    fn event_reply_waits_for_all_requests() {
+        let event = Event::default();
+        let reply = EventReply::new(Some(ContraflowData::from(&event)), None, 2);
+        assert!(reply.complete(true, 0).is_none());
+        assert!(matches!(
+            reply.complete(true, 0),
+            Some(AsyncSinkReply::Ack(_, _))
+        ));
+
+        // failed once, without acking afterwards
+        let reply = EventReply::new(Some(ContraflowData::from(&event)), None, 3);
+        assert!(reply.complete(true, 0).is_none());
+        assert!(matches!(
+            reply.complete(false, 0),
+            Some(AsyncSinkReply::Fail(_))
+        ));
+        assert!(reply.complete(false, 0).is_none());
+        assert!(reply.complete(true, 0).is_none());
+    }
+
+    #[test]
+    fn disposition_of_status_codes() {
+        for code in [
+            Code::Unavailable,
+            Code::ResourceExhausted,
+            Code::DeadlineExceeded,
+            Code::Aborted,
+            Code::Internal,
+        ] {
+            assert_eq!(Disposition::Retry, Disposition::from(code), "{code:?}");
+        }
+        for code in [
+            Code::InvalidArgument,
+            Code::NotFound,
+            Code::PermissionDenied,
+            Code::FailedPrecondition,
+            Code::OutOfRange,
+        ] {
+            assert_eq!(Disposition::Reject, Disposition::from(code), "{code:?}");
+        }
+    }
+
+    /// Answers every append request with an error with the given code
+    struct FailingWriteClient(Code);
+
+    #[async_trait::async_trait]
+    impl WriteClient for FailingWriteClient {
+        async fn create_write_stream(&mut self, _table_id: &str) -> Result<WriteStream> {
+            Err("not needed".into())
+        }
+
+        async fn finalize_write_stream(&mut self, _name: &str) -> Result<()> {
+            Err("not needed".into())
+        }
+
+        async fn append_rows(
+            &mut self,
+            requests: Receiver<AppendRowsRequest>,
+        ) -> Result<ResponseStream> {
+            let code = self.0;
+            Ok(Box::pin(
+                requests.map(move |_| Err(Status::new(code, "snot"))),
+            ))
+        }
+    }
+
+    /// sends a transactional event to a sink whose appends fail with `code`, with the dead letter enabled
+    async fn append_failing_with(
+        code: Code,
+    ) -> Result<(Receiver<AsyncSinkReply>, Receiver<SourceReply>)> {
+        let ctx = test_sink_context();
+        let mut serializer = test_serializer()?;
+        let config = test_config(&literal!({
+            "route_permanent_errors": true
+        }))?;
+        let (reply_tx, reply_rx) = bounded(8);
+        let (error_tx, error_rx) = bounded(8);
+        let mut sink = GbqSink::new(config, reply_tx).with_dead_letter(DeadLetter {
+            tx: error_tx,
+            origin_uri: EventOriginUri::default(),
+            source_is_connected: Arc::new(AtomicBool::new(true)),
+        });
+        sink.set_client(FailingWriteClient(code));
+        set_test_write_stream(&mut sink);
+        let event = Event {
+            id: EventId::from_id(0, 0, 1),
+            data: (literal!({"a": 1, "b": true}), Value::object()).into(),
+            transactional: true,
+            ..Event::default()
+        };
+        sink.on_event("", event, &ctx, &mut serializer, 0).await?;
+        sink.on_stop(&ctx).await?;
+        Ok((reply_rx, error_rx))
+    }
+
+    #[async_std::test]
+    async fn permanent_errors_go_to_the_dead_letter() -> Result<()> {
+        let (reply_rx, error_rx) = append_failing_with(Code::InvalidArgument).await?;
+        // acked, so it isn't retried
+        assert!(matches!(reply_rx.try_recv(), Ok(AsyncSinkReply::Ack(_, _))));
+        match error_rx.try_recv()? {
+            SourceReply::Structured { payload, port, .. } => {
+                assert_eq!(Some(ERR), port);
+                let (value, meta) = payload.parts();
+                assert_eq!(&literal!({"a": 1, "b": true}), value);
+                assert_eq!(
+                    Some("InvalidArgument: snot"),
+                    meta.get("dead_letter").get_str("error")
+                );
+            }
+            _ => return Err("expected a dead letter".into()),
+        }
+
+        // transient errors fail the event
+        let (reply_rx, error_rx) = append_failing_with(Code::Unavailable).await?;
+        assert!(matches!(reply_rx.try_recv(), Ok(AsyncSinkReply::Fail(_))));
+        assert!(error_rx.is_empty());
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn distributes_appends_over_channels() -> Result<()> {
+        let ctx = test_sink_context();
+        let mut serializer = test_serializer()?;
+        let config = test_config(&literal!({}))?;
+        // a single channel by default
+        assert_eq!(1, config.channel_count);
+        assert_eq!(None, config.concurrency_limit);
+        let config = test_config(&literal!({
+            "channel_count": 3,
+            "concurrency_limit": 10
+        }))?;
+
+        let (reply_tx, reply_rx) = bounded(8);
+        let mut sink = GbqSink::new(config, reply_tx);
+        let channels: Vec<FakeWriteClient> = (0..3)
+            .map(|_| FakeWriteClient {
+                schemas: vec![vec![field("a", TableType::Int64)]],
+                ..FakeWriteClient::default()
+            })
+            .collect();
+        let streams: Vec<_> = channels.iter().map(|c| c.streams.clone()).collect();
+        let appended: Vec<_> = channels.iter().map(|c| c.appended.clone()).collect();
+        sink.set_clients(
+            channels
+                .into_iter()
+                .map(|c| Box::new(c) as Box<dyn WriteClient>)
+                .collect(),
+        );
+        sink.create_write_stream(&ctx).await?;
+
+        for id in 0..6 {
+            let event = Event {
+                id: EventId::from_id(0, 0, id),
+                data: (literal!({"a": 1}), Value::object()).into(),
+                transactional: true,
+                ..Event::default()
+            };
+            assert_eq!(
+                SinkReply::NONE,
+                sink.on_event("", event, &ctx, &mut serializer, 0).await?
+            );
+        }
+        for _ in 0..6 {
+            assert!(matches!(reply_rx.recv().await?, AsyncSinkReply::Ack(..)));
+        }
+        for (streams, appended) in streams.iter().zip(appended.iter()) {
+            assert_eq!(1, streams.load(Ordering::Acquire));
+            assert_eq!(2, appended.lock().map_err(|_| "poisoned lock")?.len());
+        }
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn refreshes_the_schema() -> Result<()> {
+        let ctx = test_sink_context();
+        let mut serializer = test_serializer()?;
+        let config = test_config(&literal!({
+            "schema_refresh_interval_ms": 50
+        }))?;
+
+        let (reply_tx, reply_rx) = bounded(8);
+        let mut sink = GbqSink::new(config, reply_tx);
+        let streams = Arc::new(AtomicUsize::new(0));
+        let appended = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let finalized = Arc::new(std::sync::Mutex::new(Vec::new()));
+        sink.set_client(FakeWriteClient {
+            schemas: vec![
+                vec![field("a", TableType::Int64)],
+                // a column was added in the meantime
+                vec![field("a", TableType::Int64), field("b", TableType::Bool)],
+            ],
+            streams: streams.clone(),
+            appended: appended.clone(),
+            finalized: finalized.clone(),
+            ..FakeWriteClient::default()
+        });
+        sink.create_write_stream(&ctx).await?;
+
+        let event = || Event {
+            data: (literal!({"a": 1, "b": true}), Value::object()).into(),
+            transactional: true,
+            ..Event::default()
+        };
+        assert_eq!(
+            SinkReply::NONE,
+            sink.on_event("", event(), &ctx, &mut serializer, 0).await?
+        );
+        assert!(matches!(reply_rx.recv().await?, AsyncSinkReply::Ack(..)));
+
+        async_std::task::sleep(Duration::from_millis(100)).await;
+        assert_eq!(
+            SinkReply::NONE,
+            sink.on_event("", event(), &ctx, &mut serializer, 0).await?
+        );
+        assert!(matches!(reply_rx.recv().await?, AsyncSinkReply::Ack(..)));
+
+        let appended = appended.lock().map_err(|_| "poisoned lock")?.clone();
+        assert_eq!(
+            vec![
+                vec!["a".to_string()],
+                vec!["a".to_string(), "b".to_string()]
+            ],
+            appended
+        );
+        // the new schema requires a new append stream
+        assert_eq!(2, streams.load(Ordering::Acquire));
+        // and the replaced write stream is finalized
+        assert_eq!(
+            vec!["snot1".to_string()],
+            finalized.lock().map_err(|_| "poisoned lock")?.clone()
+        );
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn finalizes_the_write_stream_once_drained() -> Result<()> {
+        let ctx = test_sink_context();
+        let mut serializer = test_serializer()?;
+        let config = test_config(&literal!({}))?;
+
+        let (reply_tx, reply_rx) = bounded(8);
+        let mut sink = GbqSink::new(config, reply_tx);
+        let responded = Arc::new(AtomicUsize::new(0));
+        let finalized = Arc::new(std::sync::Mutex::new(Vec::new()));
+        sink.set_client(FakeWriteClient {
+            schemas: vec![
+                vec![field("a", TableType::Int64)],
+                vec![field("a", TableType::Int64)],
+            ],
+            finalized: finalized.clone(),
+            response_delay: Duration::from_millis(200),
+            responded: responded.clone(),
+            ..FakeWriteClient::default()
+        });
+        sink.create_write_stream(&ctx).await?;
+
+        let event = Event {
+            data: (literal!({"a": 1}), Value::object()).into(),
+            transactional: true,
+            ..Event::default()
+        };
+        sink.on_event("", event, &ctx, &mut serializer, 0).await?;
+        assert_eq!(0, responded.load(Ordering::Acquire));
+
+        // the append in flight is answered before its write stream is finalized
+        sink.create_write_stream(&ctx).await?;
+        assert_eq!(1, responded.load(Ordering::Acquire));
+        assert!(matches!(reply_rx.try_recv(), Ok(AsyncSinkReply::Ack(..))));
+        assert_eq!(
+            vec!["snot1".to_string()],
+            finalized.lock().map_err(|_| "poisoned lock")?.clone()
+        );
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn keeps_the_write_stream_if_the_schema_refresh_fails() -> Result<()> {
+        let ctx = test_sink_context();
+        let mut serializer = test_serializer()?;
+        let config = test_config(&literal!({
+            "schema_refresh_interval_ms": 50
+        }))?;
+
+        let (reply_tx, reply_rx) = bounded(8);
+        let mut sink = GbqSink::new(config, reply_tx);
+        let streams = Arc::new(AtomicUsize::new(0));
+        let appended = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let finalized = Arc::new(std::sync::Mutex::new(Vec::new()));
+        sink.set_client(FakeWriteClient {
+            // creating any further write stream fails
+            schemas: vec![vec![field("a", TableType::Int64)]],
+            streams: streams.clone(),
+            appended: appended.clone(),
+            finalized: finalized.clone(),
+            ..FakeWriteClient::default()
+        });
+        sink.create_write_stream(&ctx).await?;
+
+        let event = || Event {
+            data: (literal!({"a": 1, "b": true}), Value::object()).into(),
+            transactional: true,
+            ..Event::default()
+        };
+        async_std::task::sleep(Duration::from_millis(100)).await;
+        assert!(sink.schema_refresh_due());
+        for _ in 0..2 {
+            assert_eq!(
+                SinkReply::NONE,
+                sink.on_event("", event(), &ctx, &mut serializer, 0).await?
+            );
+            assert!(matches!(reply_rx.recv().await?, AsyncSinkReply::Ack(..)));
+        }
+        // the next refresh is only due after another interval
+        assert!(!sink.schema_refresh_due());
+
+        let appended = appended.lock().map_err(|_| "poisoned lock")?.clone();
+        assert_eq!(vec![vec!["a".to_string()]; 2], appended);
+        // the existing append stream is still used
+        assert_eq!(1, streams.load(Ordering::Acquire));
+        assert_eq!(
+            Some("snot1"),
+            sink.write_stream.as_ref().map(|s| s.name.as_str())
+        );
+        assert!(finalized.lock().map_err(|_| "poisoned lock")?.is_empty());
+        Ok(())
+    }
+
     #[async_std::test]
     async fn on_event_fails_if_client_is_not_conected() -> Result<()> {
         let (rx, _tx) = async_std::channel::unbounded();
@@ -1072,7 +2936,8 @@ mod test {
         }))
         .unwrap();
 
-        let mut sink = GbqSink::new(config);
+        let (reply_tx, _reply_rx) = bounded(1);
+        let mut sink = GbqSink::new(config, reply_tx);
 
         let result = sink
             .on_event(
@@ -1111,7 +2976,8 @@ mod test {
         }))
         .unwrap();
 
-        let mut sink = GbqSink::new(config);
+        let (reply_tx, _reply_rx) = bounded(1);
+        let mut sink = GbqSink::new(config, reply_tx);
         sink.set_client(BigQueryWriteClient::with_interceptor(
             Channel::from_static("http://example.com").connect_lazy(),
             AuthInterceptor {
@@ -1145,4 +3011,99 @@ mod test {
         assert!(result.is_err());
         Ok(())
     }
+
+    #[test]
+    fn connection_loss_backoff() {
+        let backoff = ConnectionLossBackoff {
+            initial_ms: 100,
+            max_ms: 1000,
+            multiplier: 2.0,
+            jitter: 0.0,
+        };
+        let schedule: Vec<u64> = (0..6).map(|a| backoff.backoff_ms(a)).collect();
+        assert_eq!(vec![100, 200, 400, 800, 1000, 1000], schedule);
+        assert_eq!(1000, backoff.backoff_ms(u32::MAX));
+
+        let mut rng = SmallRng::seed_from_u64(42);
+        assert_eq!(
+            Duration::from_millis(400),
+            backoff.jittered_backoff(2, &mut rng)
+        );
+    }
+
+    #[test]
+    fn connection_loss_jitter_within_bounds() {
+        let backoff = ConnectionLossBackoff {
+            initial_ms: 1000,
+            max_ms: 1000,
+            multiplier: 2.0,
+            jitter: 0.25,
+        };
+        let mut rng = SmallRng::seed_from_u64(42);
+        for attempt in 0..100 {
+            let backoff = backoff.jittered_backoff(attempt, &mut rng);
+            assert!(backoff >= Duration::from_millis(750));
+            assert!(backoff <= Duration::from_millis(1250));
+        }
+    }
+
+    #[test]
+    fn connection_loss_backoff_defaults() -> Result<()> {
+        let config = Config::new(&literal!({
+            "table_id": "doesnotmatter",
+            "connect_timeout": 1000000,
+            "request_timeout": 1000000
+        }))?;
+        assert_eq!(None, config.connection_loss_backoff);
+        let config = Config::new(&literal!({
+            "table_id": "doesnotmatter",
+            "connect_timeout": 1000000,
+            "request_timeout": 1000000,
+            "connection_loss_backoff": {}
+        }))?;
+        assert_eq!(
+            Some(ConnectionLossBackoff::default()),
+            config.connection_loss_backoff
+        );
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn connection_loss_notifies_after_backoff() -> Result<()> {
+        let (tx, rx) = async_std::channel::unbounded();
+        let ctx = SinkContext {
+            uid: Default::default(),
+            alias: Alias::new("flow", "connector"),
+            connector_type: Default::default(),
+            quiescence_beacon: Default::default(),
+            notifier: ConnectionLostNotifier::new(tx),
+        };
+        let connection_loss = Arc::new(ConnectionLoss::new(Some(ConnectionLossBackoff {
+            initial_ms: 200,
+            max_ms: 200,
+            multiplier: 1.0,
+            jitter: 0.0,
+        })));
+
+        // neither call waits for the backoff, the second loss is covered by the first notification
+        connection_loss
+            .notify(&ctx)
+            .timeout(Duration::from_millis(100))
+            .await??;
+        connection_loss
+            .notify(&ctx)
+            .timeout(Duration::from_millis(100))
+            .await??;
+        assert!(rx.is_empty());
+
+        let msg = rx.recv().timeout(Duration::from_secs(1)).await??;
+        assert!(matches!(msg, Msg::ConnectionLost));
+        async_std::task::sleep(Duration::from_millis(300)).await;
+        assert!(rx.is_empty());
+        assert_eq!(1, connection_loss.losses.load(Ordering::Acquire));
+
+        connection_loss.reset();
+        assert_eq!(0, connection_loss.losses.load(Ordering::Acquire));
+        Ok(())
+    }
 }
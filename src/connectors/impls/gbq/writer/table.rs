@@ -0,0 +1,354 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Creating the target table of the `gbq` sink via the BigQuery REST API, if it doesn't exist yet.
+//! The storage write API used for appending can't create tables.
+
+use crate::connectors::prelude::*;
+use gouth::Token;
+use http_client::h1::H1Client;
+use http_client::HttpClient;
+use http_types::{mime, Method, Request, StatusCode};
+use std::fmt;
+use std::time::Duration;
+
+const BIGQUERY_API: &str = "https://bigquery.googleapis.com/bigquery/v2";
+
+/// Table created if it doesn't exist when connecting
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct AutoCreateTable {
+    /// columns of the created table
+    pub schema: Vec<TableField>,
+}
+
+/// A column of a table, as in the BigQuery table schema
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct TableField {
+    pub name: String,
+    /// the BigQuery type of the column, e.g. `STRING`, `INT64`, `TIMESTAMP` or `RECORD`
+    #[serde(rename = "type")]
+    pub field_type: String,
+    #[serde(default)]
+    pub mode: FieldMode,
+    /// the columns of a `RECORD`
+    #[serde(default)]
+    pub fields: Vec<TableField>,
+}
+
+impl TableField {
+    fn to_value(&self) -> Value<'static> {
+        let fields: Vec<Value<'static>> = self.fields.iter().map(TableField::to_value).collect();
+        literal!({
+            "name": self.name.clone(),
+            "type": self.field_type.clone(),
+            "mode": self.mode.as_str(),
+            "fields": fields
+        })
+    }
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub(crate) enum FieldMode {
+    Nullable,
+    Required,
+    Repeated,
+}
+
+impl Default for FieldMode {
+    fn default() -> Self {
+        Self::Nullable
+    }
+}
+
+impl FieldMode {
+    fn as_str(self) -> &'static str {
+        match self {
+            FieldMode::Nullable => "NULLABLE",
+            FieldMode::Required => "REQUIRED",
+            FieldMode::Repeated => "REPEATED",
+        }
+    }
+}
+
+/// A table, parsed from a `table_id` of the form `projects/<project>/datasets/<dataset>/tables/<table>`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct TableRef {
+    project: String,
+    dataset: String,
+    table: String,
+}
+
+impl TableRef {
+    pub(crate) fn parse(table_id: &str) -> Result<Self> {
+        match table_id.split('/').collect::<Vec<_>>().as_slice() {
+            ["projects", project, "datasets", dataset, "tables", table]
+                if !project.is_empty() && !dataset.is_empty() && !table.is_empty() =>
+            {
+                Ok(Self {
+                    project: (*project).to_string(),
+                    dataset: (*dataset).to_string(),
+                    table: (*table).to_string(),
+                })
+            }
+            _ => Err(format!(
+                "Invalid table_id `{table_id}`, expected `projects/<project>/datasets/<dataset>/tables/<table>`"
+            )
+            .into()),
+        }
+    }
+
+    fn tables_url(&self) -> String {
+        format!(
+            "{BIGQUERY_API}/projects/{}/datasets/{}/tables",
+            self.project, self.dataset
+        )
+    }
+
+    fn url(&self) -> String {
+        format!("{}/{}", self.tables_url(), self.table)
+    }
+}
+
+impl fmt::Display for TableRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.project, self.dataset, self.table)
+    }
+}
+
+/// Client for the tables of the BigQuery API
+#[async_trait::async_trait]
+pub(crate) trait TableClient: Send {
+    async fn table_exists(&mut self, table: &TableRef) -> Result<bool>;
+
+    /// Creates the table, returns `false` if it exists already
+    async fn create_table(&mut self, table: &TableRef, schema: &[TableField]) -> Result<bool>;
+}
+
+/// Creates the table `table_id` with `schema` unless it exists, returns `true` if it was created
+pub(crate) async fn ensure_table<C>(
+    client: &mut C,
+    table_id: &str,
+    schema: &[TableField],
+) -> Result<bool>
+where
+    C: TableClient + ?Sized,
+{
+    let table = TableRef::parse(table_id)?;
+    if client.table_exists(&table).await? {
+        return Ok(false);
+    }
+    // another connector might create it in the meantime, which is fine
+    client.create_table(&table, schema).await
+}
+
+pub(crate) struct RestTableClient {
+    client: H1Client,
+    token: Token,
+}
+
+impl RestTableClient {
+    pub(crate) fn new(request_timeout: Duration) -> Result<Self> {
+        let mut client = H1Client::new();
+        client.set_config(http_client::Config::new().set_timeout(Some(request_timeout)))?;
+        Ok(Self {
+            client,
+            token: Token::new()?,
+        })
+    }
+
+    fn request(&self, method: Method, url: &str) -> Result<Request> {
+        let mut request = Request::new(method, url::Url::parse(url)?);
+        request.insert_header("Authorization", self.token.header_value()?.to_string());
+        Ok(request)
+    }
+}
+
+#[async_trait::async_trait]
+impl TableClient for RestTableClient {
+    async fn table_exists(&mut self, table: &TableRef) -> Result<bool> {
+        let request = self.request(Method::Get, &table.url())?;
+        let mut response = self.client.send(request).await?;
+        match response.status() {
+            StatusCode::Ok => Ok(true),
+            StatusCode::NotFound => Ok(false),
+            status => Err(format!(
+                "Error looking up table {table}: {status} {}",
+                response.body_string().await.unwrap_or_default()
+            )
+            .into()),
+        }
+    }
+
+    async fn create_table(&mut self, table: &TableRef, schema: &[TableField]) -> Result<bool> {
+        let fields: Vec<Value<'static>> = schema.iter().map(TableField::to_value).collect();
+        let body = literal!({
+            "tableReference": {
+                "projectId": table.project.clone(),
+                "datasetId": table.dataset.clone(),
+                "tableId": table.table.clone()
+            },
+            "schema": {
+                "fields": fields
+            }
+        });
+        let mut request = self.request(Method::Post, &table.tables_url())?;
+        request.set_content_type(mime::JSON);
+        request.set_body(body.encode());
+        let mut response = self.client.send(request).await?;
+        match response.status() {
+            StatusCode::Ok => Ok(true),
+            StatusCode::Conflict => Ok(false),
+            status => Err(format!(
+                "Error creating table {table}: {status} {}",
+                response.body_string().await.unwrap_or_default()
+            )
+            .into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connectors::impls::gbq::writer::Config;
+
+    /// Answers if the table exists as configured, recording the tables it is asked to create
+    struct FakeTableClient {
+        exists: bool,
+        created: Vec<(TableRef, Vec<TableField>)>,
+    }
+
+    #[async_trait::async_trait]
+    impl TableClient for FakeTableClient {
+        async fn table_exists(&mut self, _table: &TableRef) -> Result<bool> {
+            Ok(self.exists)
+        }
+
+        async fn create_table(&mut self, table: &TableRef, schema: &[TableField]) -> Result<bool> {
+            self.created.push((table.clone(), schema.to_vec()));
+            Ok(true)
+        }
+    }
+
+    const TABLE_ID: &str = "projects/snot/datasets/badger/tables/cake";
+
+    fn config() -> Result<Config> {
+        Config::new(&literal!({
+            "table_id": TABLE_ID,
+            "connect_timeout": 1_000_000,
+            "request_timeout": 1_000_000,
+            "auto_create_table": {
+                "schema": [
+                    {"name": "id", "type": "INT64", "mode": "REQUIRED"},
+                    {"name": "tags", "type": "STRING", "mode": "REPEATED"},
+                    {
+                        "name": "user",
+                        "type": "RECORD",
+                        "fields": [
+                            {"name": "name", "type": "STRING"}
+                        ]
+                    }
+                ]
+            }
+        }))
+    }
+
+    #[test]
+    fn auto_create_table_config() -> Result<()> {
+        let schema = config()?
+            .auto_create_table
+            .map(|auto_create_table| auto_create_table.schema)
+            .unwrap_or_default();
+        assert_eq!(3, schema.len());
+        assert_eq!(FieldMode::Required, schema[0].mode);
+        assert_eq!(FieldMode::Repeated, schema[1].mode);
+        assert_eq!(FieldMode::Nullable, schema[2].mode);
+        assert_eq!(
+            literal!({
+                "name": "user",
+                "type": "RECORD",
+                "mode": "NULLABLE",
+                "fields": [
+                    {"name": "name", "type": "STRING", "mode": "NULLABLE", "fields": []}
+                ]
+            }),
+            schema[2].to_value()
+        );
+
+        // not created if not configured
+        let config = Config::new(&literal!({
+            "table_id": TABLE_ID,
+            "connect_timeout": 1_000_000,
+            "request_timeout": 1_000_000
+        }))?;
+        assert_eq!(None, config.auto_create_table);
+
+        // unknown modes are rejected
+        assert!(Config::new(&literal!({
+            "table_id": TABLE_ID,
+            "connect_timeout": 1_000_000,
+            "request_timeout": 1_000_000,
+            "auto_create_table": {
+                "schema": [{"name": "id", "type": "INT64", "mode": "OPTIONAL"}]
+            }
+        }))
+        .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn table_ref() -> Result<()> {
+        let table = TableRef::parse(TABLE_ID)?;
+        assert_eq!("snot.badger.cake", table.to_string());
+        assert_eq!(
+            "https://bigquery.googleapis.com/bigquery/v2/projects/snot/datasets/badger/tables/cake",
+            table.url()
+        );
+        assert!(TableRef::parse("snot.badger.cake").is_err());
+        assert!(TableRef::parse("projects/snot/datasets//tables/cake").is_err());
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn creates_missing_table() -> Result<()> {
+        let schema = config()?
+            .auto_create_table
+            .map(|auto_create_table| auto_create_table.schema)
+            .unwrap_or_default();
+        let mut client = FakeTableClient {
+            exists: false,
+            created: vec![],
+        };
+        assert!(ensure_table(&mut client, TABLE_ID, &schema).await?);
+        assert_eq!(vec![(TableRef::parse(TABLE_ID)?, schema)], client.created);
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn skips_existing_table() -> Result<()> {
+        let mut client = FakeTableClient {
+            exists: true,
+            created: vec![],
+        };
+        assert!(!ensure_table(&mut client, TABLE_ID, &[]).await?);
+        assert!(client.created.is_empty());
+
+        // invalid table ids are not looked up
+        assert!(ensure_table(&mut client, "snot", &[]).await.is_err());
+        Ok(())
+    }
+}
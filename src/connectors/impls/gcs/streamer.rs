@@ -464,6 +464,9 @@ mod tests {
                     postprocessors: None,
                     reconnect: Default::default(),
                     metrics_interval_s: None,
+                    max_in_flight: None,
+                    in_flight_timeout_ms: None,
+                    instance_host: None,
                 },
                 &raw_config,
                 &KillSwitch::dummy(),
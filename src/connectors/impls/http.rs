@@ -14,6 +14,8 @@
 
 pub(crate) mod auth;
 pub(crate) mod client;
+pub(crate) mod http2;
 pub(crate) mod meta;
+pub(crate) mod proxy;
 pub(crate) mod server;
 pub(crate) mod utils;
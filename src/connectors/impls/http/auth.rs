@@ -12,8 +12,13 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::future::Future;
 use std::io::Write;
 
+use async_std::sync::Mutex;
+use http_client::HttpClient;
+use http_types::{headers, Body, Method, Request, Url};
+
 use crate::errors::Result;
 
 /// Authorization methods
@@ -28,6 +33,15 @@ pub(crate) enum Auth {
     ElasticsearchApiKey { id: String, api_key: String },
     #[serde(alias = "gcp")]
     Gcp,
+    /// OAuth2 client credentials grant, the obtained bearer tokens are cached and refreshed before they expire
+    #[serde(alias = "oauth2")]
+    OAuth2 {
+        token_url: String,
+        client_id: String,
+        client_secret: String,
+        #[serde(default)]
+        scopes: Vec<String>,
+    },
     #[serde(alias = "none")]
     None,
 }
@@ -57,11 +71,111 @@ impl Auth {
                 writer.into_inner(); // release the reference, so header-value is accessible again
                 Ok(Some(header_value))
             }
-            Auth::None => Ok(None),
+            // tokens need to be obtained asynchronously via `TokenCache`
+            Auth::OAuth2 { .. } | Auth::None => Ok(None),
+        }
+    }
+}
+
+/// An OAuth2 access token
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct AccessToken {
+    token: String,
+    /// expiry timestamp in nanoseconds, `None` if the token does not expire
+    expires_at: Option<u64>,
+}
+
+impl AccessToken {
+    /// tokens are refreshed this many nanoseconds before they actually expire
+    const REFRESH_MARGIN: u64 = 30_000_000_000;
+
+    pub(crate) fn new(token: String, expires_at: Option<u64>) -> Self {
+        Self { token, expires_at }
+    }
+
+    fn needs_refresh(&self, now: u64) -> bool {
+        self.expires_at
+            .map_or(false, |expires_at| now + Self::REFRESH_MARGIN >= expires_at)
+    }
+
+    fn header_value(&self) -> String {
+        format!("Bearer {}", self.token)
+    }
+}
+
+/// Caches an OAuth2 access token and refreshes it if it is about to expire
+#[derive(Debug, Default)]
+pub(crate) struct TokenCache {
+    token: Mutex<Option<AccessToken>>,
+}
+
+impl TokenCache {
+    /// Returns the authorization header value for the cached token,
+    /// calling `fetch` to obtain a new token if there is none or it is about to expire.
+    pub(crate) async fn header_value<F, Fut>(&self, now: u64, fetch: F) -> Result<String>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<AccessToken>>,
+    {
+        let mut token = self.token.lock().await;
+        if token.as_ref().map_or(true, |t| t.needs_refresh(now)) {
+            *token = Some(fetch().await?);
         }
+        Ok(token
+            .as_ref()
+            .map(AccessToken::header_value)
+            .unwrap_or_default())
+    }
+
+    /// Discard the cached token, e.g. when it got rejected, so the next request fetches a new one
+    pub(crate) async fn invalidate(&self) {
+        self.token.lock().await.take();
     }
 }
 
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    /// lifetime of the token in seconds
+    expires_in: Option<u64>,
+}
+
+/// Fetch a new access token from `token_url` using the OAuth2 client credentials grant
+pub(crate) async fn fetch_token<C: HttpClient>(
+    client: &C,
+    token_url: &str,
+    client_id: &str,
+    client_secret: &str,
+    scopes: &[String],
+    now: u64,
+) -> Result<AccessToken> {
+    let mut request = Request::new(Method::Post, Url::parse(token_url)?);
+    let credentials = base64::encode(&format!("{}:{}", client_id, client_secret));
+    request.insert_header(headers::AUTHORIZATION, format!("Basic {}", credentials));
+    let mut form = vec![("grant_type", "client_credentials".to_string())];
+    if !scopes.is_empty() {
+        form.push(("scope", scopes.join(" ")));
+    }
+    request.set_body(Body::from_form(&form)?);
+    let mut response = client.send(request).await?;
+    if !response.status().is_success() {
+        return Err(format!(
+            "Error fetching OAuth2 token from {}: {}",
+            token_url,
+            response.status()
+        )
+        .into());
+    }
+    let TokenResponse {
+        access_token,
+        expires_in,
+    } = response.body_json().await?;
+    Ok(AccessToken::new(
+        access_token,
+        expires_in.map(|secs| now + secs * 1_000_000_000),
+    ))
+}
+
 impl Default for Auth {
     fn default() -> Self {
         Self::None
@@ -71,6 +185,55 @@ impl Default for Auth {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[async_std::test]
+    async fn token_cache_caches_token() -> Result<()> {
+        let cache = TokenCache::default();
+        let fetches = &AtomicUsize::new(0);
+        let fetch = || async move {
+            fetches.fetch_add(1, Ordering::AcqRel);
+            Ok(AccessToken::new("snot".to_string(), Some(100_000_000_000)))
+        };
+        assert_eq!("Bearer snot", cache.header_value(0, fetch).await?);
+        assert_eq!("Bearer snot", cache.header_value(1, fetch).await?);
+        assert_eq!(1, fetches.load(Ordering::Acquire));
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn token_cache_refreshes_before_expiry() -> Result<()> {
+        let cache = TokenCache::default();
+        let fetches = &AtomicUsize::new(0);
+        let fetch = || async move {
+            let n = fetches.fetch_add(1, Ordering::AcqRel);
+            Ok(AccessToken::new(format!("token{n}"), Some(100_000_000_000)))
+        };
+        assert_eq!("Bearer token0", cache.header_value(0, fetch).await?);
+        // within the refresh margin
+        assert_eq!(
+            "Bearer token1",
+            cache.header_value(80_000_000_000, fetch).await?
+        );
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn token_cache_refresh_after_invalidate() -> Result<()> {
+        // this is what happens upon a 401 response
+        let cache = TokenCache::default();
+        let fetches = &AtomicUsize::new(0);
+        let fetch = || async move {
+            let n = fetches.fetch_add(1, Ordering::AcqRel);
+            Ok(AccessToken::new(format!("token{n}"), None))
+        };
+        assert_eq!("Bearer token0", cache.header_value(0, fetch).await?);
+        cache.invalidate().await;
+        assert_eq!("Bearer token1", cache.header_value(0, fetch).await?);
+        assert_eq!("Bearer token1", cache.header_value(0, fetch).await?);
+        assert_eq!(2, fetches.load(Ordering::Acquire));
+        Ok(())
+    }
 
     #[test]
     fn header_value_basic() -> Result<()> {
@@ -12,22 +12,30 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::sync::atomic::AtomicBool;
 use std::sync::Arc;
 use std::time::Duration;
 
 use async_std::channel::{bounded, Receiver, Sender};
+use async_std::io::ReadExt;
 use either::Either;
 use halfbrown::HashMap;
 use http_client::h1::H1Client;
 use http_client::HttpClient;
-use http_types::Method;
+use http_types::{headers, Method, Request, Response, StatusCode};
 use tremor_common::time::nanotime;
 
-use super::auth::Auth;
-use super::meta::{extract_request_meta, extract_response_meta, HttpRequestBuilder};
-use super::utils::{Header, RequestId};
+use super::auth::{fetch_token, Auth, TokenCache};
+use super::http2::{Http2Client, HttpVersion, HTTP1};
+use super::meta::{
+    extract_request_meta, extract_response_meta, receive_trailers, HttpRequestBuilder,
+};
+use super::proxy::ProxiedClient;
+use super::utils::{Header, RequestId, UrlTemplate};
 use crate::connectors::sink::concurrency_cap::ConcurrencyCap;
+use crate::connectors::sink::retry::{DeadLetter, RetryConfig, RetrySink};
 use crate::connectors::utils::mime::MimeCodecMap;
+use crate::connectors::utils::proxy::ProxyConfig;
 use crate::connectors::utils::tls::{tls_client_config, TLSClientConfig};
 use crate::{connectors::prelude::*, errors::err_connector_def};
 
@@ -40,6 +48,10 @@ pub(crate) struct Config {
     /// Target URL
     #[serde(default = "Default::default")]
     pub(super) url: Url,
+    /// Target URL with `{event.<path>}` and `{meta.<path>}` placeholders, resolved for each event,
+    /// takes precedence over `url`
+    #[serde(default = "Default::default")]
+    pub(super) url_template: Option<UrlTemplate>,
     /// Authorization method
     #[serde(default = "Default::default")]
     pub(super) auth: Auth,
@@ -61,6 +73,25 @@ pub(crate) struct Config {
     /// MIME mapping to/from tremor codecs
     #[serde(default)]
     custom_codecs: HashMap<String, String>,
+    /// maximum size of a request body in bytes, events exceeding it are failed
+    pub(super) max_request_bytes: Option<usize>,
+    /// maximum size of a response body in bytes, larger responses are not read and the event is failed
+    max_response_bytes: Option<usize>,
+    /// pass request and response bodies through as bytes, bypassing the codec
+    #[serde(default)]
+    pub(super) raw_body: bool,
+    /// SOCKS5 or HTTP proxy to send requests through,
+    /// the proxies from the `http_proxy` and `https_proxy` env vars are used if not set
+    #[serde(default)]
+    proxy: Option<ProxyConfig>,
+    /// retry failed requests with a growing backoff, events failing all retries are emitted
+    /// on the `err` port and acked
+    #[serde(default)]
+    retry: Option<RetryConfig>,
+    /// HTTP version to send requests with, `auto` selects HTTP/2 via ALPN for `https` urls if
+    /// the server supports it, `http2` sends `http` requests with prior knowledge
+    #[serde(default)]
+    http_version: HttpVersion,
 }
 
 const DEFAULT_CONCURRENCY: usize = 4;
@@ -108,6 +139,12 @@ impl ConnectorBuilder for Builder {
                     "missing tls config with 'https' url. Set 'tls' to 'true' or provide a full tls config.",
                 ));
         }
+        if config.http_version == HttpVersion::Http2 && config.proxy.is_some() {
+            return Err(err_connector_def(
+                id,
+                "'http_version' 'http2' can't be used with a 'proxy'.",
+            ));
+        }
         let (response_tx, response_rx) = bounded(crate::QSIZE.load(Ordering::Relaxed));
         let mime_codec_map = Arc::new(MimeCodecMap::with_overwrites(&config.custom_codecs));
 
@@ -118,6 +155,7 @@ impl ConnectorBuilder for Builder {
         Ok(Box::new(Client {
             response_tx,
             response_rx,
+            source_is_connected: Arc::new(AtomicBool::new(false)),
             config,
             tls_client_config,
             mime_codec_map,
@@ -130,6 +168,7 @@ impl ConnectorBuilder for Builder {
 pub(crate) struct Client {
     response_tx: Sender<SourceReply>,
     response_rx: Receiver<SourceReply>,
+    source_is_connected: Arc<AtomicBool>,
     config: Config,
     tls_client_config: Option<rustls::ClientConfig>,
     // this is basically an immutable map, we use arc to share it across tasks (e.g. for each request sending)
@@ -150,6 +189,7 @@ impl Connector for Client {
     ) -> Result<Option<SourceAddr>> {
         let source = HttpRequestSource {
             rx: self.response_rx.clone(),
+            source_is_connected: self.source_is_connected.clone(),
         };
         builder.spawn(source, source_context).map(Some)
     }
@@ -159,20 +199,44 @@ impl Connector for Client {
         sink_context: SinkContext,
         builder: SinkManagerBuilder,
     ) -> Result<Option<SinkAddr>> {
-        let sink = HttpRequestSink::new(
-            self.response_tx.clone(),
-            builder.reply_tx(),
-            self.config.clone(),
-            self.tls_client_config.clone(),
-            self.mime_codec_map.clone(),
-            self.configured_codec.clone(),
-        );
-        builder.spawn(sink, sink_context).map(Some)
+        let response_tx = self.response_tx.clone();
+        let config = self.config.clone();
+        let tls_client_config = self.tls_client_config.clone();
+        let mime_codec_map = self.mime_codec_map.clone();
+        let configured_codec = self.configured_codec.clone();
+        let new_sink = move |reply_tx| {
+            HttpRequestSink::new(
+                response_tx,
+                reply_tx,
+                config,
+                tls_client_config,
+                mime_codec_map,
+                configured_codec,
+            )
+        };
+        if let Some(retry) = self.config.retry.clone() {
+            let dead_letter = DeadLetter {
+                tx: self.response_tx.clone(),
+                origin_uri: EventOriginUri {
+                    scheme: String::from("http_client"),
+                    host: self.config.url.host_or_local().to_string(),
+                    port: Some(self.config.url.port_or_dflt()),
+                    path: vec![],
+                },
+                source_is_connected: self.source_is_connected.clone(),
+            };
+            let sink = RetrySink::new(retry, Some(dead_letter), builder.reply_tx(), new_sink);
+            builder.spawn(sink, sink_context).map(Some)
+        } else {
+            let sink = new_sink(builder.reply_tx());
+            builder.spawn(sink, sink_context).map(Some)
+        }
     }
 }
 
 struct HttpRequestSource {
     rx: Receiver<SourceReply>,
+    source_is_connected: Arc<AtomicBool>,
 }
 
 #[async_trait::async_trait()]
@@ -181,6 +245,12 @@ impl Source for HttpRequestSource {
         Ok(self.rx.recv().await?)
     }
 
+    async fn on_cb_open(&mut self, _ctx: &SourceContext) -> Result<()> {
+        // events failing all retries are only sent once we know some pipeline is connected to us
+        self.source_is_connected.store(true, Ordering::Release);
+        Ok(())
+    }
+
     fn is_transactional(&self) -> bool {
         false
     }
@@ -193,7 +263,7 @@ impl Source for HttpRequestSource {
 
 struct HttpRequestSink {
     request_counter: u64,
-    client: Option<Arc<H1Client>>,
+    client: Option<Arc<ProxiedClient>>,
     response_tx: Sender<SourceReply>,
     reply_tx: Sender<AsyncSinkReply>,
     config: Config,
@@ -203,6 +273,8 @@ struct HttpRequestSink {
     origin_uri: EventOriginUri,
     codec_map: Arc<MimeCodecMap>,
     configured_codec: String,
+    // only present for `oauth2` auth, shared across all request sending tasks
+    token_cache: Option<Arc<TokenCache>>,
 }
 
 impl HttpRequestSink {
@@ -215,6 +287,11 @@ impl HttpRequestSink {
         configured_codec: String,
     ) -> Self {
         let concurrency_cap = ConcurrencyCap::new(config.concurrency, reply_tx.clone());
+        let token_cache = if let Auth::OAuth2 { .. } = config.auth {
+            Some(Arc::new(TokenCache::default()))
+        } else {
+            None
+        };
         Self {
             request_counter: 1, // always start by 1, 0 is DEFAULT_STREAM_ID and this might interfere with custom codecs
             client: None,
@@ -231,7 +308,104 @@ impl HttpRequestSink {
             },
             codec_map,
             configured_codec,
+            token_cache,
+        }
+    }
+}
+
+/// Add the `Authorization` header with a (possibly refreshed) `OAuth2` bearer token
+async fn authorize(
+    client: &ProxiedClient,
+    auth: &Auth,
+    token_cache: &TokenCache,
+    request: &mut Request,
+) -> Result<()> {
+    if let Auth::OAuth2 {
+        token_url,
+        client_id,
+        client_secret,
+        scopes,
+    } = auth
+    {
+        let header_value = token_cache
+            .header_value(nanotime(), || {
+                fetch_token(
+                    client,
+                    token_url,
+                    client_id,
+                    client_secret,
+                    scopes,
+                    nanotime(),
+                )
+            })
+            .await?;
+        request.insert_header(headers::AUTHORIZATION, header_value);
+    }
+    Ok(())
+}
+
+/// Read the response body, failing if it is larger than `max_response_bytes`
+async fn read_body(response: &mut Response, max_response_bytes: Option<usize>) -> Result<Vec<u8>> {
+    if let Some(max_response_bytes) = max_response_bytes {
+        if let Some(len) = response.len().filter(|len| *len > max_response_bytes) {
+            return Err(format!(
+                "HTTP response body exceeds max_response_bytes: {len} > {max_response_bytes}"
+            )
+            .into());
+        }
+        // the content-length might be missing or lying, so never read more than allowed
+        let mut data = Vec::new();
+        response
+            .take_body()
+            .take(max_response_bytes as u64 + 1)
+            .read_to_end(&mut data)
+            .await?;
+        if data.len() > max_response_bytes {
+            return Err(format!(
+                "HTTP response body exceeds max_response_bytes: more than {max_response_bytes}"
+            )
+            .into());
         }
+        Ok(data)
+    } else {
+        Ok(response.body_bytes().await?)
+    }
+}
+
+/// Send the request, authorizing it with a cached `OAuth2` token if configured.
+///
+/// If the token is rejected with a `401`, it is invalidated and the request is retried once
+/// with a fresh token. Chunked requests cannot be retried, their body is already consumed.
+async fn send_request(
+    client: &ProxiedClient,
+    mut request: Request,
+    auth: &Auth,
+    token_cache: Option<&TokenCache>,
+    request_is_chunked: bool,
+) -> Result<Response> {
+    if let Some(token_cache) = token_cache {
+        let retry = if request_is_chunked {
+            None
+        } else {
+            // buffer the body so we can send it twice
+            let body = request.take_body().into_bytes().await?;
+            let mut retry = request.clone();
+            retry.set_body(body.clone());
+            request.set_body(body);
+            Some(retry)
+        };
+        authorize(client, auth, token_cache, &mut request).await?;
+        let response = client.send(request).await?;
+        if response.status() == StatusCode::Unauthorized {
+            token_cache.invalidate().await;
+            if let Some(mut retry) = retry {
+                authorize(client, auth, token_cache, &mut retry).await?;
+                return Ok(client.send(retry).await?);
+            }
+        }
+        Ok(response)
+    } else {
+        Ok(client.send(request).await?)
     }
 }
 
@@ -239,7 +413,12 @@ impl HttpRequestSink {
 impl Sink for HttpRequestSink {
     async fn connect(&mut self, _ctx: &SinkContext, _attempt: &Attempt) -> Result<bool> {
         let timeout = self.config.timeout.map(Duration::from_nanos);
-        let tls_config = self.tls_client_config.as_ref().cloned().map(Arc::new);
+        // requests sent over HTTP/1.1 must not offer HTTP/2
+        let h1_tls_config = self.tls_client_config.clone().map(|mut tls_config| {
+            tls_config.set_protocols(&[HTTP1.to_vec()]);
+            tls_config
+        });
+        let tls_config = h1_tls_config.clone().map(Arc::new);
         let client_config = http_client::Config::new()
             .set_http_keep_alive(true) // TODO: make configurable, maybe some people don't want that
             .set_tcp_no_delay(true)
@@ -249,7 +428,19 @@ impl Sink for HttpRequestSink {
 
         let client = H1Client::try_from(client_config)
             .map_err(|e| format!("Invalid HTTP Client config: {e}."))?;
-        self.client = Some(Arc::new(client));
+        let tls_connector = h1_tls_config.map(async_tls::TlsConnector::from);
+        let http2 = Http2Client::new(
+            self.config.http_version,
+            self.tls_client_config.as_ref(),
+            self.config.max_response_bytes,
+        );
+        self.client = Some(Arc::new(ProxiedClient::new(
+            client,
+            http2,
+            self.config.proxy.clone(),
+            tls_connector,
+            timeout,
+        )));
 
         Ok(true)
     }
@@ -279,7 +470,8 @@ impl Sink for HttpRequestSink {
             let ingest_ns = event.ingest_ns;
 
             // take the metadata from the first element of the batch
-            let event_meta = event.value_meta_iter().next().map(|t| t.1);
+            let first = event.value_meta_iter().next();
+            let event_meta = first.map(|t| t.1);
             let correlation_meta = event_meta.get("correlation").map(Value::clone_static); // :sob:
 
             // assign a unique request id to this event
@@ -290,6 +482,7 @@ impl Sink for HttpRequestSink {
             let mut builder = ctx.bail_err(
                 HttpRequestBuilder::new(
                     request_id,
+                    first,
                     http_meta,
                     &self.codec_map,
                     &self.config,
@@ -299,6 +492,10 @@ impl Sink for HttpRequestSink {
             )?;
             let configured_codec = self.configured_codec.clone();
             let codec_map = self.codec_map.clone();
+            let auth = self.config.auth.clone();
+            let max_response_bytes = self.config.max_response_bytes;
+            let raw_body = self.config.raw_body;
+            let token_cache = self.token_cache.clone();
             let mut request = builder.get_chunked_request();
             let request_is_chunked = request.is_some();
             if !request_is_chunked {
@@ -332,9 +529,34 @@ impl Sink for HttpRequestSink {
                         .path_segments()
                         .map(|iter| iter.map(ToString::to_string).collect::<Vec<_>>())
                         .unwrap_or_default();
-                    match client.send(request).await {
+                    match send_request(
+                        &client,
+                        request,
+                        &auth,
+                        token_cache.as_deref(),
+                        request_is_chunked,
+                    )
+                    .await
+                    {
                         Ok(mut response) => {
-                            let response_meta = extract_response_meta(&response);
+                            let data = match read_body(&mut response, max_response_bytes).await {
+                                Ok(data) => data,
+                                Err(e) => {
+                                    error!("{send_ctx} Error receiving response body: {e}");
+                                    if let Some(contraflow_data) = contraflow_data {
+                                        send_ctx.swallow_err(
+                                            reply_tx
+                                                .send(AsyncSinkReply::Fail(contraflow_data))
+                                                .await,
+                                            "Error sending fail contraflow",
+                                        );
+                                    }
+                                    drop(guard);
+                                    return Err(e);
+                                }
+                            };
+                            let trailers = receive_trailers(&mut response).await;
+                            let response_meta = extract_response_meta(&response, trailers.as_ref());
                             let mut meta = send_ctx.meta(literal!({
                                 "request": req_meta,
                                 "request_id": request_id.get(),
@@ -344,25 +566,31 @@ impl Sink for HttpRequestSink {
                             if let Some(corr_meta) = correlation_meta {
                                 meta.try_insert("correlation", corr_meta);
                             }
-                            let data = send_ctx.bail_err(
-                                response.body_bytes().await.map_err(Error::from),
-                                "Error receiving response body",
-                            )?;
-                            let codec_name = if let Some(mime) = response.content_type() {
-                                codec_map.get_codec_name(mime.essence())
+                            let reply = if raw_body {
+                                // the content-type is available in the response headers meta
+                                SourceReply::Structured {
+                                    origin_uri,
+                                    payload: (Value::Bytes(data.into()), meta).into(),
+                                    stream: DEFAULT_STREAM_ID,
+                                    port: None,
+                                }
                             } else {
-                                None
-                            };
-                            let codec_overwrite = codec_name
-                                .filter(|codec| *codec != &configured_codec)
-                                .cloned();
-                            let reply = SourceReply::Data {
-                                origin_uri,
-                                data,
-                                meta: Some(meta),
-                                stream: None, // a response (as well as a request) is a discrete unit and not part of a stream
-                                port: None,
-                                codec_overwrite,
+                                let codec_name = if let Some(mime) = response.content_type() {
+                                    codec_map.get_codec_name(mime.essence())
+                                } else {
+                                    None
+                                };
+                                let codec_overwrite = codec_name
+                                    .filter(|codec| *codec != &configured_codec)
+                                    .cloned();
+                                SourceReply::Data {
+                                    origin_uri,
+                                    data,
+                                    meta: Some(meta),
+                                    stream: None, // a response (as well as a request) is a discrete unit and not part of a stream
+                                    port: None,
+                                    codec_overwrite,
+                                }
                             };
                             send_ctx.swallow_err(
                                 response_tx.send(reply).await,
@@ -380,7 +608,8 @@ impl Sink for HttpRequestSink {
                                 );
                             }
                         }
-                        Err(_e) => {
+                        Err(e) => {
+                            error!("{send_ctx} Error sending HTTP request: {e}");
                             if let Some(contraflow_data) = contraflow_data {
                                 send_ctx.swallow_err(
                                     reply_tx.send(AsyncSinkReply::Fail(contraflow_data)).await,
@@ -428,3 +657,93 @@ impl Sink for HttpRequestSink {
         false
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Reconnect;
+    use std::path::Path;
+
+    #[async_std::test]
+    async fn client_certificate() -> Result<()> {
+        crate::connectors::tests::setup_for_tls();
+
+        let config = Config::new(&literal!({
+            "url": "https://localhost:65535",
+            "tls": {
+                "cert": "./tests/localhost.cert",
+                "key": "./tests/localhost.key",
+                "ca": "./tests/localhost.cert"
+            }
+        }))?;
+        let tls = match config.tls {
+            Some(Either::Left(tls)) => tls,
+            _ => return Err("expected a full tls config".into()),
+        };
+        assert_eq!(
+            Some(Path::new("./tests/localhost.cert")),
+            tls.cert.as_deref()
+        );
+        assert_eq!(Some(Path::new("./tests/localhost.key")), tls.key.as_deref());
+        assert_eq!(
+            Some(Path::new("./tests/localhost.cert")),
+            tls.cafile.as_deref()
+        );
+
+        let connector_config = ConnectorConfig {
+            connector_type: CONNECTOR_TYPE.into(),
+            codec: None,
+            config: None,
+            preprocessors: None,
+            postprocessors: None,
+            reconnect: Reconnect::None,
+            metrics_interval_s: None,
+            max_in_flight: None,
+            in_flight_timeout_ms: None,
+            instance_host: None,
+        };
+        // a certificate without a key is rejected
+        let res = Builder::default()
+            .build_cfg(
+                &Alias::new("flow", "snot"),
+                &connector_config,
+                &literal!({
+                    "url": "https://localhost:65535",
+                    "tls": {
+                        "cert": "./tests/localhost.cert"
+                    }
+                }),
+                &KillSwitch::dummy(),
+            )
+            .await;
+        assert!(res.is_err());
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn max_response_bytes() -> Result<()> {
+        let mut response = Response::new(StatusCode::Ok);
+        response.set_body("snot");
+        assert_eq!(b"snot".to_vec(), read_body(&mut response, Some(4)).await?);
+
+        let mut response = Response::new(StatusCode::Ok);
+        response.set_body("snotbadger");
+        assert!(read_body(&mut response, Some(4)).await.is_err());
+
+        // without content-length
+        let mut response = Response::new(StatusCode::Ok);
+        response.set_body(http_types::Body::from_reader(
+            async_std::io::Cursor::new(b"snotbadger".to_vec()),
+            None,
+        ));
+        assert!(read_body(&mut response, Some(4)).await.is_err());
+
+        let mut response = Response::new(StatusCode::Ok);
+        response.set_body("snotbadger");
+        assert_eq!(
+            b"snotbadger".to_vec(),
+            read_body(&mut response, None).await?
+        );
+        Ok(())
+    }
+}
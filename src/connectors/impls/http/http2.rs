@@ -0,0 +1,353 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Sending HTTP requests over HTTP/2
+//!
+//! `https` origins negotiate the protocol via ALPN, `http` origins are spoken to with prior
+//! knowledge (h2c). All requests to an origin are multiplexed on a single connection.
+//! Request trailers are only sent over HTTP/2, the HTTP/1.1 encoder drops them.
+
+use async_compat::Compat;
+use async_rustls::webpki::DNSNameRef;
+use async_rustls::TlsConnector;
+use async_std::io::{Read, Write};
+use async_std::net::TcpStream;
+use async_std::sync::Mutex;
+use bytes::Bytes;
+use either::Either;
+use h2::client::SendRequest;
+use h2::RecvStream;
+use http_client::Error;
+use http_types::trailers::Trailers;
+use http_types::{Request, Response, StatusCode, Url, Version};
+use rustls::Session;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+/// ALPN protocol id of HTTP/2
+const H2: &[u8] = b"h2";
+/// ALPN protocol id of HTTP/1.1
+pub(crate) const HTTP1: &[u8] = b"http/1.1";
+
+/// headers specific to an HTTP/1.1 connection, HTTP/2 forbids them
+const CONNECTION_HEADERS: [&str; 7] = [
+    "connection",
+    "keep-alive",
+    "proxy-connection",
+    "transfer-encoding",
+    "upgrade",
+    "host",
+    "te",
+];
+
+/// HTTP protocol version requirement
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum HttpVersion {
+    /// HTTP/2 if the server selects it via ALPN, HTTP/1.1 otherwise and for `http` urls
+    Auto,
+    /// Only HTTP/1.1
+    Http1,
+    /// Only HTTP/2, via ALPN for `https` urls and with prior knowledge for `http` urls
+    Http2,
+}
+
+impl Default for HttpVersion {
+    fn default() -> Self {
+        Self::Auto
+    }
+}
+
+impl HttpVersion {
+    /// ALPN protocols to offer during the TLS handshake, most preferred first
+    fn alpn_protocols(self) -> Vec<Vec<u8>> {
+        match self {
+            Self::Auto => vec![H2.to_vec(), HTTP1.to_vec()],
+            Self::Http1 => vec![HTTP1.to_vec()],
+            Self::Http2 => vec![H2.to_vec()],
+        }
+    }
+}
+
+/// Scheme, host and port of a url
+type Origin = (String, String, u16);
+
+fn origin(url: &Url) -> Result<Origin, Error> {
+    let host = url
+        .host_str()
+        .ok_or_else(|| Error::from_str(StatusCode::BadRequest, "Missing host in url"))?;
+    let port = url
+        .port_or_known_default()
+        .ok_or_else(|| Error::from_str(StatusCode::BadRequest, "Missing port in url"))?;
+    Ok((url.scheme().to_string(), host.to_string(), port))
+}
+
+/// HTTP/2 handshake on `stream`, the connection is driven by a task of its own
+async fn handshake<S>(stream: S) -> Result<SendRequest<Bytes>, Error>
+where
+    S: Read + Write + Send + Unpin + 'static,
+{
+    let (send_request, connection) = h2::client::handshake(Compat::new(stream)).await?;
+    async_std::task::spawn(async move {
+        if let Err(e) = connection.await {
+            debug!("HTTP/2 connection closed: {e}");
+        }
+    });
+    Ok(send_request)
+}
+
+/// The head, body and trailers of an HTTP/2 request
+type Parts = (http::Request<()>, Bytes, Option<http::HeaderMap>);
+
+/// Splits `req` into the head of the HTTP/2 request, its body and its trailers
+///
+/// The body is buffered, so chunked requests are sent once complete, followed by their trailers.
+async fn into_parts(mut req: Request) -> Result<Parts, Error> {
+    let mut url = req.url().clone();
+    url.set_fragment(None);
+    let mut head = http::Request::builder()
+        .method(req.method().as_ref())
+        .uri(url.as_str());
+    for (name, values) in req.iter() {
+        let name = name.as_str().to_ascii_lowercase();
+        if CONNECTION_HEADERS.contains(&name.as_str()) {
+            continue;
+        }
+        for value in values.iter() {
+            head = head.header(name.as_str(), value.as_str());
+        }
+    }
+    let body = req.take_body().into_bytes().await?;
+    // the trailers are only sent once the body is complete
+    let trailers = if req.has_trailers() {
+        req.recv_trailers().await
+    } else {
+        None
+    };
+    let trailers = trailers
+        .map(|trailers| {
+            let mut map = http::HeaderMap::new();
+            for (name, values) in trailers.iter() {
+                let name = http::header::HeaderName::from_bytes(name.as_str().as_bytes())?;
+                for value in values.iter() {
+                    map.append(name.clone(), http::HeaderValue::from_str(value.as_str())?);
+                }
+            }
+            Ok::<_, Error>(map)
+        })
+        .transpose()?;
+    Ok((head.body(())?, Bytes::from(body), trailers))
+}
+
+/// Reads the body and trailers of `response`, failing for bodies beyond `max_body_bytes`
+async fn from_response(
+    response: http::Response<RecvStream>,
+    max_body_bytes: Option<usize>,
+) -> Result<Response, Error> {
+    let (parts, mut recv) = response.into_parts();
+    let status = StatusCode::try_from(parts.status.as_u16())?;
+    let mut body = Vec::new();
+    while let Some(data) = recv.data().await {
+        let data = data?;
+        recv.flow_control().release_capacity(data.len())?;
+        body.extend_from_slice(&data);
+        if let Some(max_body_bytes) = max_body_bytes.filter(|max| body.len() > *max) {
+            return Err(Error::from_str(
+                StatusCode::PayloadTooLarge,
+                format!(
+                    "HTTP response body exceeds max_response_bytes: more than {max_body_bytes}"
+                ),
+            ));
+        }
+    }
+    let trailers = recv.trailers().await?;
+
+    let mut res = Response::new(status);
+    res.set_version(Some(Version::Http2_0));
+    // values that aren't visible ASCII can't be represented
+    for (name, value) in &parts.headers {
+        if let Ok(value) = value.to_str() {
+            res.append_header(name.as_str(), value);
+        }
+    }
+    res.set_body(body);
+    if let Some(trailers) = trailers {
+        let mut received = Trailers::new();
+        for (name, value) in &trailers {
+            if let Ok(value) = value.to_str() {
+                received.append(name.as_str(), value);
+            }
+        }
+        res.send_trailers().send(received).await;
+    }
+    Ok(res)
+}
+
+/// Client for the requests to be sent over HTTP/2
+pub(crate) struct Http2Client {
+    version: HttpVersion,
+    tls_connector: Option<TlsConnector>,
+    max_response_bytes: Option<usize>,
+    /// the connection of each origin
+    connections: Mutex<HashMap<Origin, SendRequest<Bytes>>>,
+    /// origins that selected HTTP/1.1 via ALPN, only with `auto`
+    http1_origins: Mutex<HashSet<Origin>>,
+}
+
+impl Http2Client {
+    /// A client for `version`, `None` for `http1`
+    pub(crate) fn new(
+        version: HttpVersion,
+        tls_config: Option<&rustls::ClientConfig>,
+        max_response_bytes: Option<usize>,
+    ) -> Option<Self> {
+        if version == HttpVersion::Http1 {
+            return None;
+        }
+        let tls_connector = tls_config.cloned().map(|mut config| {
+            config.set_protocols(&version.alpn_protocols());
+            TlsConnector::from(Arc::new(config))
+        });
+        Some(Self {
+            version,
+            tls_connector,
+            max_response_bytes,
+            connections: Mutex::new(HashMap::new()),
+            http1_origins: Mutex::new(HashSet::new()),
+        })
+    }
+
+    /// if requests must not fall back to HTTP/1.1
+    pub(crate) fn is_required(&self) -> bool {
+        self.version == HttpVersion::Http2
+    }
+
+    /// Connects to `origin`, `None` if it selected HTTP/1.1 via ALPN
+    async fn connect(&self, origin: &Origin) -> Result<Option<SendRequest<Bytes>>, Error> {
+        let (scheme, host, port) = origin;
+        let stream = TcpStream::connect((host.as_str(), *port)).await?;
+        stream.set_nodelay(true)?;
+        if scheme != "https" {
+            return handshake(stream).await.map(Some);
+        }
+        let tls_connector = self.tls_connector.as_ref().ok_or_else(|| {
+            Error::from_str(StatusCode::BadRequest, "Missing tls config for https url")
+        })?;
+        let domain = DNSNameRef::try_from_ascii_str(host).map_err(|e| {
+            Error::from_str(StatusCode::BadRequest, format!("Invalid host {host}: {e}"))
+        })?;
+        let stream = tls_connector.connect(domain, stream).await?;
+        if stream.get_ref().1.get_alpn_protocol() == Some(H2) {
+            handshake(stream).await.map(Some)
+        } else if self.is_required() {
+            Err(Error::from_str(
+                StatusCode::HttpVersionNotSupported,
+                format!("{host}:{port} did not select HTTP/2"),
+            ))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// The connection to `origin` ready for a request, `None` if it selected HTTP/1.1
+    ///
+    /// Closed connections are replaced by new ones.
+    async fn connection(&self, origin: &Origin) -> Result<Option<SendRequest<Bytes>>, Error> {
+        let cached = self.connections.lock().await.get(origin).cloned();
+        if let Some(connection) = cached {
+            if let Ok(connection) = connection.ready().await {
+                return Ok(Some(connection));
+            }
+        }
+        let connection = {
+            let mut connections = self.connections.lock().await;
+            if let Some(connection) = self.connect(origin).await? {
+                connections.insert(origin.clone(), connection.clone());
+                connection
+            } else {
+                connections.remove(origin);
+                self.http1_origins.lock().await.insert(origin.clone());
+                return Ok(None);
+            }
+        };
+        Ok(Some(connection.ready().await?))
+    }
+
+    /// Sends `req` over HTTP/2, or hands it back to be sent over HTTP/1.1
+    ///
+    /// With `auto` only `https` requests to origins selecting HTTP/2 are sent.
+    pub(crate) async fn send(&self, req: Request) -> Result<Either<Response, Request>, Error> {
+        let origin = origin(req.url())?;
+        if self.version == HttpVersion::Auto
+            && (origin.0 != "https" || self.http1_origins.lock().await.contains(&origin))
+        {
+            return Ok(Either::Right(req));
+        }
+        let mut connection = match self.connection(&origin).await? {
+            Some(connection) => connection,
+            None => return Ok(Either::Right(req)),
+        };
+        let (head, body, trailers) = into_parts(req).await?;
+        let end_of_stream = trailers.is_none();
+        let (response, mut stream) =
+            connection.send_request(head, body.is_empty() && end_of_stream)?;
+        if !body.is_empty() {
+            stream.send_data(body, end_of_stream)?;
+        }
+        if let Some(trailers) = trailers {
+            stream.send_trailers(trailers)?;
+        }
+        let response = response.await?;
+        from_response(response, self.max_response_bytes)
+            .await
+            .map(Either::Left)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http_types::Method;
+
+    #[async_std::test]
+    async fn request_parts() -> crate::errors::Result<()> {
+        let url = Url::parse("http://example.org:8080/snot?badger#fragment")
+            .map_err(|e| e.to_string())?;
+        let mut req = Request::new(Method::Put, url);
+        req.insert_header("Transfer-Encoding", "chunked");
+        req.insert_header("Connection", "keep-alive");
+        req.insert_header("x-snot", "badger");
+        req.set_body("cake");
+        let (head, body, trailers) = into_parts(req).await.map_err(|e| e.to_string())?;
+        assert_eq!(http::Method::PUT, head.method());
+        assert_eq!(
+            "http://example.org:8080/snot?badger",
+            head.uri().to_string()
+        );
+        assert_eq!(
+            Some("badger"),
+            head.headers().get("x-snot").and_then(|v| v.to_str().ok())
+        );
+        assert!(head.headers().get("transfer-encoding").is_none());
+        assert!(head.headers().get("connection").is_none());
+        assert_eq!(Bytes::from_static(b"cake"), body);
+        assert!(trailers.is_none());
+
+        assert_eq!(
+            vec![b"h2".to_vec(), b"http/1.1".to_vec()],
+            HttpVersion::default().alpn_protocols()
+        );
+        Ok(())
+    }
+}
@@ -13,16 +13,17 @@
 // limitations under the License.
 
 use super::client;
-use super::utils::{FixedBodyReader, RequestId, StreamingBodyReader};
+use super::utils::{raw_body, FixedBodyReader, RequestId, StreamingBodyReader};
 use crate::connectors::{prelude::*, utils::mime::MimeCodecMap};
 use async_std::channel::{unbounded, Sender};
 use either::Either;
 use http_types::headers::HeaderValues;
+use http_types::trailers::{self, Trailers};
 use http_types::Response;
 use http_types::{
     headers::{self, HeaderValue},
     mime::BYTE_STREAM,
-    Method, Mime, Request,
+    Method, Mime, Request, StatusCode,
 };
 use std::str::FromStr;
 use tremor_value::Value;
@@ -41,12 +42,20 @@ pub(crate) struct HttpRequestBuilder {
     request: Option<Request>,
     body_data: BodyData,
     codec_overwrite: Option<String>,
+    max_request_bytes: Option<usize>,
+    /// whether the body is passed through as bytes instead of being serialized with a codec
+    raw_body: bool,
+    /// number of body bytes appended so far
+    body_len: usize,
+    /// trailers to send after the body, only for chunked requests
+    trailers: Option<(trailers::Sender, Trailers)>,
 }
 
 // TODO: do some deduplication with SinkResponse
 impl HttpRequestBuilder {
     pub(super) fn new(
         request_id: RequestId,
+        event: Option<(&Value, &Value)>,
         meta: Option<&Value>,
         codec_map: &MimeCodecMap,
         config: &client::Config,
@@ -68,6 +77,9 @@ impl HttpRequestBuilder {
             } else {
                 return Err("Invalid HTTP URL".into());
             }
+        } else if let Some(url_template) = config.url_template.as_ref() {
+            // resolved against the first element of the batch, like the metadata
+            Url::parse(&url_template.resolve(event.map(|e| e.0), event.map(|e| e.1))?)?
         } else {
             config.url.clone()
         };
@@ -121,13 +133,14 @@ impl HttpRequestBuilder {
             .as_ref()
             .and_then(|codec| codec_map.get_mime_type(codec.as_str()))
             .or_else(|| codec_map.get_mime_type(configured_codec))
-            .and_then(|mime| Mime::from_str(mime).ok());
+            .and_then(|mime| Mime::from_str(mime).ok())
+            .filter(|_| !config.raw_body);
 
         // extract content-type and thus possible codec overwrite only from first element
         // precedence:
         //  1. from headers meta
-        //  2. from overwritten codec
-        //  3. from configured codec
+        //  2. from overwritten codec, unless using `raw_body`
+        //  3. from configured codec, unless using `raw_body`
         //  4. fall back to application/octet-stream if codec doesn't provide a mime-type
         let content_type = Some(
             header_content_type
@@ -146,6 +159,29 @@ impl HttpRequestBuilder {
             request.insert_header(headers::AUTHORIZATION, auth_header);
         }
 
+        // trailers can only follow a chunked body, they are only sent over HTTP/2
+        let trailers = match (request_meta.get("trailers").as_object(), chunked) {
+            (Some(trailer_meta), true) => {
+                let mut trailers = Trailers::new();
+                for (name, values) in trailer_meta {
+                    if let Some(trailer_values) = values.as_array() {
+                        for value in trailer_values {
+                            if let Some(trailer_value) = value.as_str() {
+                                trailers.append(name.as_ref(), trailer_value);
+                            }
+                        }
+                    } else if let Some(trailer_value) = values.as_str() {
+                        trailers.append(name.as_ref(), trailer_value);
+                    }
+                }
+                Some((request.send_trailers(), trailers))
+            }
+            (Some(_), false) => {
+                return Err("HTTP request trailers require a chunked request body".into());
+            }
+            (None, _) => None,
+        };
+
         let body_data = if chunked {
             let (chunk_tx, chunk_rx) = unbounded();
             let streaming_reader = StreamingBodyReader::new(chunk_rx);
@@ -164,6 +200,10 @@ impl HttpRequestBuilder {
             request: Some(request),
             body_data,
             codec_overwrite,
+            max_request_bytes: config.max_request_bytes,
+            raw_body: config.raw_body,
+            body_len: 0,
+            trailers,
         })
     }
 
@@ -173,16 +213,31 @@ impl HttpRequestBuilder {
         ingest_ns: u64,
         serializer: &mut EventSerializer,
     ) -> Result<()> {
-        let chunks = serializer.serialize_for_stream_with_codec(
-            value,
-            ingest_ns,
-            self.request_id.get(),
-            self.codec_overwrite.as_ref(),
-        )?;
+        let chunks = if self.raw_body {
+            vec![raw_body(value)?]
+        } else {
+            serializer.serialize_for_stream_with_codec(
+                value,
+                ingest_ns,
+                self.request_id.get(),
+                self.codec_overwrite.as_ref(),
+            )?
+        };
         self.append_data(chunks).await
     }
 
     async fn append_data(&mut self, mut chunks: Vec<Vec<u8>>) -> Result<()> {
+        // chunks are counted before they are sent, so a chunked request stops streaming at the limit
+        self.body_len += chunks.iter().map(Vec::len).sum::<usize>();
+        if let Some(max_request_bytes) = self.max_request_bytes {
+            if self.body_len > max_request_bytes {
+                return Err(format!(
+                    "HTTP request body exceeds max_request_bytes: {} > {max_request_bytes}",
+                    self.body_len
+                )
+                .into());
+            }
+        }
         match &mut self.body_data {
             BodyData::Chunked(tx) => {
                 for chunk in chunks {
@@ -203,9 +258,11 @@ impl HttpRequestBuilder {
         serializer: &mut EventSerializer,
     ) -> Result<Option<Request>> {
         // finalize the stream
-        let rest = serializer.finish_stream(self.request_id.get())?;
-        if !rest.is_empty() {
-            self.append_data(rest).await?;
+        if !self.raw_body {
+            let rest = serializer.finish_stream(self.request_id.get())?;
+            if !rest.is_empty() {
+                self.append_data(rest).await?;
+            }
         }
         let mut swap = BodyData::Data(vec![]);
         std::mem::swap(&mut swap, &mut self.body_data);
@@ -222,6 +279,9 @@ impl HttpRequestBuilder {
             BodyData::Chunked(tx) => {
                 // signal EOF to the reader
                 tx.close();
+                if let Some((sender, trailers)) = self.trailers.take() {
+                    sender.send(trailers).await;
+                }
             }
         }
         Ok(self.request.take())
@@ -237,6 +297,40 @@ impl HttpRequestBuilder {
     }
 }
 
+/// Limits on the size of the head of requests received by the HTTP server
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) struct RequestLimits {
+    /// maximum length of the path and query of the url in bytes
+    pub(super) max_url_length: usize,
+    /// maximum number of header values
+    pub(super) max_header_count: usize,
+    /// maximum size of all header names and values in bytes
+    pub(super) max_header_bytes: usize,
+}
+
+impl RequestLimits {
+    /// The status to reject `request` with if it exceeds any limit
+    pub(super) fn check(&self, request: &Request) -> Option<StatusCode> {
+        let url = request.url();
+        let url_length = url.path().len() + url.query().map_or(0, |query| query.len() + 1);
+        if url_length > self.max_url_length {
+            return Some(StatusCode::UriTooLong);
+        }
+        let mut header_count = 0;
+        let mut header_bytes = 0;
+        for (name, values) in request.iter() {
+            for value in values.iter() {
+                header_count += 1;
+                header_bytes += name.as_str().len() + value.as_str().len();
+            }
+        }
+        if header_count > self.max_header_count || header_bytes > self.max_header_bytes {
+            return Some(StatusCode::RequestHeaderFieldsTooLarge);
+        }
+        None
+    }
+}
+
 /// Extract request metadata
 pub(super) fn extract_request_meta(request: &Request) -> Value<'static> {
     // collect header values into an array for each header
@@ -287,8 +381,20 @@ pub(super) fn extract_request_meta(request: &Request) -> Value<'static> {
     })
 }
 
-/// extract response metadata
-pub(super) fn extract_response_meta(response: &Response) -> Value<'static> {
+/// Receive the trailers of a response, only available once its body has been read
+pub(super) async fn receive_trailers(response: &mut Response) -> Option<Trailers> {
+    if response.has_trailers() {
+        response.recv_trailers().await
+    } else {
+        None
+    }
+}
+
+/// extract response metadata, including the `trailers` received after the body
+pub(super) fn extract_response_meta(
+    response: &Response,
+    trailers: Option<&Trailers>,
+) -> Value<'static> {
     // collect header values into an array for each header
     let headers = response
         .header_names()
@@ -312,12 +418,27 @@ pub(super) fn extract_response_meta(response: &Response) -> Value<'static> {
         })
         .collect::<Value>();
 
-    let mut meta = Value::object_with_capacity(3);
+    let mut meta = Value::object_with_capacity(4);
     meta.try_insert("status", response.status() as u16);
     meta.try_insert("headers", headers);
     response
         .version()
         .map(|version| meta.try_insert("version", version.to_string()));
+    if let Some(trailers) = trailers {
+        let trailers = trailers
+            .iter()
+            .map(|(name, values)| {
+                (
+                    name.to_string(),
+                    values
+                        .iter()
+                        .map(|v| Value::from(v.as_str().to_string()))
+                        .collect::<Value>(),
+                )
+            })
+            .collect::<Value>();
+        meta.try_insert("trailers", trailers);
+    }
     meta
 }
 
@@ -343,12 +464,216 @@ mod test {
         let config = client::Config::new(&c)?;
         let configured_codec = "json";
 
-        let mut b =
-            HttpRequestBuilder::new(request_id, meta, &codec_map, &config, configured_codec)?;
+        let mut b = HttpRequestBuilder::new(
+            request_id,
+            None,
+            meta,
+            &codec_map,
+            &config,
+            configured_codec,
+        )?;
 
         let r = b.finalize(&mut s).await?.unwrap();
         assert_eq!(r.header("pie").unwrap().iter().count(), 1);
         assert_eq!(r.header("cake").unwrap().iter().count(), 2);
         Ok(())
     }
+
+    #[test]
+    fn request_limits() -> Result<()> {
+        let limits = RequestLimits {
+            max_url_length: 16,
+            max_header_count: 3,
+            max_header_bytes: 64,
+        };
+        let request = Request::new(Method::Get, "http://localhost/snot?badger=1");
+        assert_eq!(None, limits.check(&request));
+
+        let request = Request::new(Method::Get, "http://localhost/snot?badger=snotbadger");
+        assert_eq!(Some(StatusCode::UriTooLong), limits.check(&request));
+
+        let mut request = Request::new(Method::Get, "http://localhost/snot");
+        request.append_header("snot", "badger");
+        request.append_header("snot", "badger");
+        request.append_header("badger", "snot");
+        assert_eq!(None, limits.check(&request));
+        request.append_header("cake", "lie");
+        assert_eq!(
+            Some(StatusCode::RequestHeaderFieldsTooLarge),
+            limits.check(&request)
+        );
+
+        let mut request = Request::new(Method::Get, "http://localhost/snot");
+        request.append_header("snot", "badger".repeat(16));
+        assert_eq!(
+            Some(StatusCode::RequestHeaderFieldsTooLarge),
+            limits.check(&request)
+        );
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn max_request_bytes() -> Result<()> {
+        let codec_map = MimeCodecMap::default();
+        let mut s = EventSerializer::new(
+            None,
+            CodecReq::Optional("json"),
+            vec![],
+            &ConnectorType("http".into()),
+            &Alias::new("flow", "http"),
+        )?;
+        let config = client::Config::new(&literal!({"max_request_bytes": 10}))?;
+
+        let mut b =
+            HttpRequestBuilder::new(RequestId::new(1), None, None, &codec_map, &config, "json")?;
+        b.append(&Value::from("snot"), 0, &mut s).await?;
+        assert!(b.finalize(&mut s).await?.is_some());
+
+        let mut b =
+            HttpRequestBuilder::new(RequestId::new(2), None, None, &codec_map, &config, "json")?;
+        let res = b
+            .append(&Value::from("snotbadger is way too long"), 0, &mut s)
+            .await;
+        assert!(res
+            .err()
+            .map(|e| e.to_string())
+            .unwrap_or_default()
+            .contains("max_request_bytes"));
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn trailers() -> Result<()> {
+        let codec_map = MimeCodecMap::default();
+        let mut s = EventSerializer::new(
+            None,
+            CodecReq::Optional("json"),
+            vec![],
+            &ConnectorType("http".into()),
+            &Alias::new("flow", "http"),
+        )?;
+        let config = client::Config::new(&literal!({}))?;
+        let meta = literal!({
+            "request": {
+                "headers": {"transfer-encoding": "chunked"},
+                "trailers": {
+                    "grpc-status": "0",
+                    "x-snot": ["badger", "cake"]
+                }
+            }
+        });
+
+        let mut b = HttpRequestBuilder::new(
+            RequestId::new(1),
+            None,
+            Some(&meta),
+            &codec_map,
+            &config,
+            "json",
+        )?;
+        let mut request = b.get_chunked_request().ok_or("request is not chunked")?;
+        b.append(&Value::from("snot"), 0, &mut s).await?;
+        b.finalize(&mut s).await?;
+        // the trailers follow the body
+        assert_eq!("\"snot\"", request.take_body().into_string().await?);
+        assert!(request.has_trailers());
+        let trailers = request
+            .recv_trailers()
+            .await
+            .ok_or("no trailers were sent")?;
+        assert_eq!(
+            Some("0"),
+            trailers
+                .get("grpc-status")
+                .map(HeaderValues::last)
+                .map(HeaderValue::as_str)
+        );
+        assert_eq!(Some(2), trailers.get("x-snot").map(|v| v.iter().count()));
+
+        // without a chunked body there is nothing for them to follow
+        let meta = literal!({
+            "request": {
+                "trailers": {"grpc-status": "0"}
+            }
+        });
+        let error = HttpRequestBuilder::new(
+            RequestId::new(1),
+            None,
+            Some(&meta),
+            &codec_map,
+            &config,
+            "json",
+        )
+        .err()
+        .map(|e| e.to_string())
+        .unwrap_or_default();
+        assert!(error.contains("chunked"), "{error}");
+
+        let mut response = Response::new(http_types::StatusCode::Ok);
+        let mut sent = Trailers::new();
+        sent.insert("grpc-status", "0");
+        response.send_trailers().send(sent).await;
+        let trailers = receive_trailers(&mut response).await;
+        let meta = extract_response_meta(&response, trailers.as_ref());
+        assert_eq!(
+            Some("0"),
+            meta.get("trailers")
+                .get("grpc-status")
+                .get_idx(0)
+                .and_then(ValueAccess::as_str)
+        );
+        Ok(())
+    }
+    #[async_std::test]
+    async fn url_template() -> Result<()> {
+        let codec_map = MimeCodecMap::default();
+        let mut s = EventSerializer::new(
+            None,
+            CodecReq::Optional("json"),
+            vec![],
+            &ConnectorType("http".into()),
+            &Alias::new("flow", "http"),
+        )?;
+        let config = client::Config::new(&literal!({
+            "url_template": "http://localhost:8080/users/{event.user.id}?tenant={meta.tenant}&q={{raw}}"
+        }))?;
+        let value = literal!({"user": {"id": 42}});
+        let meta = literal!({"tenant": "snot"});
+
+        let mut b = HttpRequestBuilder::new(
+            RequestId::new(1),
+            Some((&value, &meta)),
+            None,
+            &codec_map,
+            &config,
+            "json",
+        )?;
+        let request = b.finalize(&mut s).await?.ok_or("no request")?;
+        assert_eq!(
+            "http://localhost:8080/users/42?tenant=snot&q={raw}",
+            request.url().as_str()
+        );
+
+        let value = literal!({"user": {"name": "badger"}});
+        let res = HttpRequestBuilder::new(
+            RequestId::new(2),
+            Some((&value, &meta)),
+            None,
+            &codec_map,
+            &config,
+            "json",
+        );
+        assert_eq!(
+            Some("Missing value for `{event.user.id}` in url_template".to_string()),
+            res.err().map(|e| e.to_string())
+        );
+
+        assert!(
+            client::Config::new(&literal!({"url_template": "http://localhost/{snot}"})).is_err()
+        );
+        assert!(
+            client::Config::new(&literal!({"url_template": "http://localhost/{event"})).is_err()
+        );
+        Ok(())
+    }
 }
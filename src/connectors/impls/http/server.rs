@@ -35,8 +35,8 @@ use tide::{
 use tide_rustls::TlsListener;
 use tremor_common::ids::Id;
 
-use super::meta::{extract_request_meta, BodyData};
-use super::utils::{FixedBodyReader, RequestId, StreamingBodyReader};
+use super::meta::{extract_request_meta, BodyData, RequestLimits};
+use super::utils::{raw_body, FixedBodyReader, RequestId, StreamingBodyReader};
 
 #[derive(Deserialize, Debug, Clone)]
 #[serde(deny_unknown_fields)]
@@ -50,10 +50,47 @@ pub(crate) struct Config {
     /// e.g. for handling `application/json` with the `binary` codec, if desired
     #[serde(default)]
     custom_codecs: HashMap<String, String>,
+    /// pass request and response bodies through as bytes, bypassing the codec
+    #[serde(default)]
+    raw_body: bool,
+    /// maximum length of the path and query of a request url in bytes,
+    /// longer ones are rejected with `414 URI Too Long`
+    #[serde(default = "default_max_url_length")]
+    max_url_length: usize,
+    /// maximum number of header values of a request,
+    /// requests with more are rejected with `431 Request Header Fields Too Large`
+    #[serde(default = "default_max_header_count")]
+    max_header_count: usize,
+    /// maximum size of all header names and values of a request in bytes,
+    /// larger ones are rejected with `431 Request Header Fields Too Large`
+    #[serde(default = "default_max_header_bytes")]
+    max_header_bytes: usize,
 }
 
 impl ConfigImpl for Config {}
 
+fn default_max_url_length() -> usize {
+    4096
+}
+
+fn default_max_header_count() -> usize {
+    100
+}
+
+fn default_max_header_bytes() -> usize {
+    8192
+}
+
+impl Config {
+    fn request_limits(&self) -> RequestLimits {
+        RequestLimits {
+            max_url_length: self.max_url_length,
+            max_header_count: self.max_header_count,
+            max_header_bytes: self.max_header_bytes,
+        }
+    }
+}
+
 #[derive(Debug, Default)]
 pub(crate) struct Builder {}
 
@@ -148,6 +185,8 @@ impl Connector for HttpServer {
             tls_server_config: self.tls_server_config.clone(),
             configured_codec: self.configured_codec.clone(),
             codec_map: self.codec_map.clone(),
+            raw_body: self.config.raw_body,
+            request_limits: self.config.request_limits(),
         };
         builder.spawn(source, source_context).map(Some)
     }
@@ -161,6 +200,7 @@ impl Connector for HttpServer {
             self.inflight.clone(),
             self.codec_map.clone(),
             self.configured_codec.clone(),
+            self.config.raw_body,
         );
         builder.spawn(sink, sink_context).map(Some)
     }
@@ -177,6 +217,8 @@ struct HttpServerSource {
     tls_server_config: Option<TLSServerConfig>,
     configured_codec: String,
     codec_map: MimeCodecMap,
+    raw_body: bool,
+    request_limits: RequestLimits,
 }
 
 #[async_trait::async_trait()]
@@ -211,11 +253,13 @@ impl Source for HttpServerSource {
 
         let ctx = ctx.clone();
         let tls_server_config = self.tls_server_config.clone();
+        let request_limits = self.request_limits;
 
         // Server task - this is the main receive loop for http server instances
         self.server_task = Some(spawn_task(ctx.clone(), async move {
             if let Some(tls_server_config) = tls_server_config {
-                let mut endpoint = tide::Server::with_state(HttpServerState::new(tx, ctx.clone()));
+                let mut endpoint =
+                    tide::Server::with_state(HttpServerState::new(tx, ctx.clone(), request_limits));
                 endpoint.at("/").all(handle_request);
                 endpoint.at("/*").all(handle_request);
 
@@ -233,7 +277,8 @@ impl Source for HttpServerSource {
                 }
                 listener.accept().await?;
             } else {
-                let mut endpoint = tide::Server::with_state(HttpServerState::new(tx, ctx.clone()));
+                let mut endpoint =
+                    tide::Server::with_state(HttpServerState::new(tx, ctx.clone(), request_limits));
                 endpoint.at("/").all(handle_request);
                 endpoint.at("/*").all(handle_request);
                 let mut listener = (&hostport).to_listener()?;
@@ -280,6 +325,14 @@ impl Source for HttpServerSource {
                 stream: DEFAULT_STREAM_ID, // a http request is a discrete unit and not part of any stream
                 port: None,
             }
+        } else if self.raw_body {
+            // the content-type is available in the request headers meta
+            SourceReply::Structured {
+                origin_uri: self.origin_uri.clone(),
+                payload: (Value::Bytes(data.into()), meta).into(),
+                stream: DEFAULT_STREAM_ID,
+                port: None,
+            }
         } else {
             // codec overwrite, depending on requests content-type
             // only set the overwrite if it is different than the configured codec
@@ -316,6 +369,7 @@ struct HttpServerSink {
     inflight: Arc<DashMap<RequestId, Sender<Response>>>,
     codec_map: MimeCodecMap,
     configured_codec: String,
+    raw_body: bool,
 }
 
 impl HttpServerSink {
@@ -326,11 +380,13 @@ impl HttpServerSink {
         inflight: Arc<DashMap<RequestId, Sender<Response>>>,
         codec_map: MimeCodecMap,
         configured_codec: String,
+        raw_body: bool,
     ) -> Self {
         Self {
             inflight,
             codec_map,
             configured_codec,
+            raw_body,
         }
     }
 }
@@ -373,6 +429,7 @@ impl Sink for HttpServerSink {
                                     http_meta,
                                     &self.codec_map,
                                     &self.configured_codec,
+                                    self.raw_body,
                                 )
                                 .await,
                                 Self::ERROR_MSG_EXTRACT_VALUE,
@@ -415,6 +472,7 @@ impl Sink for HttpServerSink {
                                             http_meta,
                                             &self.codec_map,
                                             &self.configured_codec,
+                                            self.raw_body,
                                         )
                                         .await,
                                         Self::ERROR_MSG_EXTRACT_VALUE,
@@ -452,6 +510,7 @@ impl Sink for HttpServerSink {
                                                 http_meta,
                                                 &self.codec_map,
                                                 &self.configured_codec,
+                                                self.raw_body,
                                             )
                                             .await,
                                             Self::ERROR_MSG_EXTRACT_VALUE,
@@ -520,6 +579,7 @@ struct SinkResponse {
     body_data: BodyData,
     tx: Sender<Response>,
     codec_overwrite: Option<String>,
+    raw_body: bool,
 }
 
 impl SinkResponse {
@@ -529,6 +589,7 @@ impl SinkResponse {
         http_meta: Option<&Value<'event>>,
         codec_map: &MimeCodecMap,
         configured_codec: &String,
+        raw_body: bool,
     ) -> Result<Self> {
         let mut res = tide::Response::new(StatusCode::Ok);
 
@@ -590,13 +651,14 @@ impl SinkResponse {
             .as_ref()
             .and_then(|codec| codec_map.get_mime_type(codec.as_str()))
             .or_else(|| codec_map.get_mime_type(configured_codec))
-            .and_then(|mime| Mime::from_str(mime).ok());
+            .and_then(|mime| Mime::from_str(mime).ok())
+            .filter(|_| !raw_body);
 
         // extract content-type and thus possible codec overwrite only from first element
         // precedence:
         //  1. from headers meta
-        //  2. from overwritten codec
-        //  3. from configured codec
+        //  2. from overwritten codec, unless using `raw_body`
+        //  3. from configured codec, unless using `raw_body`
         //  4. fall back to application/octet-stream if codec doesn't provide a mime-type
         let content_type = Some(
             header_content_type
@@ -629,6 +691,7 @@ impl SinkResponse {
             body_data,
             tx,
             codec_overwrite,
+            raw_body,
         })
     }
 
@@ -638,12 +701,16 @@ impl SinkResponse {
         ingest_ns: u64,
         serializer: &mut EventSerializer,
     ) -> Result<()> {
-        let chunks = serializer.serialize_for_stream_with_codec(
-            value,
-            ingest_ns,
-            self.request_id.get(),
-            self.codec_overwrite.as_ref(),
-        )?;
+        let chunks = if self.raw_body {
+            vec![raw_body(value)?]
+        } else {
+            serializer.serialize_for_stream_with_codec(
+                value,
+                ingest_ns,
+                self.request_id.get(),
+                self.codec_overwrite.as_ref(),
+            )?
+        };
         self.append_data(chunks).await
     }
 
@@ -663,9 +730,11 @@ impl SinkResponse {
     /// In the chunked case we have already sent it before.
     async fn finalize(mut self, serializer: &mut EventSerializer) -> Result<()> {
         // finalize the stream
-        let rest = serializer.finish_stream(self.request_id.get())?;
-        if !rest.is_empty() {
-            self.append_data(rest).await?;
+        if !self.raw_body {
+            let rest = serializer.finish_stream(self.request_id.get())?;
+            if !rest.is_empty() {
+                self.append_data(rest).await?;
+            }
         }
         // send response if necessary
         match self.body_data {
@@ -695,11 +764,16 @@ impl SinkResponse {
 struct HttpServerState {
     tx: Sender<RawRequestData>,
     ctx: SourceContext,
+    request_limits: RequestLimits,
 }
 
 impl HttpServerState {
-    fn new(tx: Sender<RawRequestData>, ctx: SourceContext) -> Self {
-        Self { tx, ctx }
+    fn new(tx: Sender<RawRequestData>, ctx: SourceContext, request_limits: RequestLimits) -> Self {
+        Self {
+            tx,
+            ctx,
+            request_limits,
+        }
     }
 }
 
@@ -726,6 +800,14 @@ async fn handle_request(mut req: tide::Request<HttpServerState>) -> tide::Result
     }
 }
 async fn _handle_request(req: &mut tide::Request<HttpServerState>) -> tide::Result<tide::Response> {
+    // reject oversized requests before reading their body
+    if let Some(status) = req.state().request_limits.check(req.as_ref()) {
+        debug!(
+            "{ctx} Rejecting HTTP request exceeding the request limits with {status}",
+            ctx = req.state().ctx
+        );
+        return Ok(tide::Response::new(status));
+    }
     let request_meta = extract_request_meta(req.as_ref());
     let content_type = req.content_type().map(|mime| mime.essence().to_string());
     let data = req.body_bytes().await?;
@@ -12,12 +12,16 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use crate::errors::{Error, Result};
 use async_std::channel::Receiver;
 use either::Either;
 use futures::{ready, Stream};
+use percent_encoding::{utf8_percent_encode, AsciiSet, CONTROLS, NON_ALPHANUMERIC};
 use std::io::{Cursor, Read};
 use std::pin::Pin;
 use std::task::Poll;
+use tremor_value::Value;
+use value_trait::{ValueAccess, Writable};
 
 // We use surf for http clients
 pub use surf::{
@@ -51,6 +55,156 @@ impl std::fmt::Display for RequestId {
     }
 }
 
+/// Characters encoded in values substituted into the path, everything with a meaning in a path segment
+const PATH_SEGMENT: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'<')
+    .add(b'>')
+    .add(b'`')
+    .add(b'?')
+    .add(b'{')
+    .add(b'}')
+    .add(b'/')
+    .add(b'%');
+
+/// Characters encoded in values substituted into the query or fragment, all but the unreserved ones
+const QUERY_COMPONENT: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+/// Part of a `UrlTemplate`
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum UrlTemplatePart {
+    Literal(String),
+    /// a value of the event, with the placeholder as written in the template
+    Event(String, Vec<String>),
+    /// a value of the event metadata, with the placeholder as written in the template
+    Meta(String, Vec<String>),
+}
+
+/// URL with `{event.<path>}` and `{meta.<path>}` placeholders, resolved against each event
+///
+/// Paths are dot separated, `{{` and `}}` are a literal `{` and `}`. Substituted values are
+/// percent-encoded for the part of the URL they end up in, so they can't add path segments or
+/// query parameters. Path values can't be `.` or `..` either, parsers remove or resolve those
+/// segments even when percent-encoded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct UrlTemplate {
+    parts: Vec<UrlTemplatePart>,
+}
+
+impl UrlTemplate {
+    pub(crate) fn parse(template: &str) -> Result<Self> {
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        let mut chars = template.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let mut placeholder = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => placeholder.push(c),
+                            None => return Err("Unterminated placeholder in url_template".into()),
+                        }
+                    }
+                    let mut path = placeholder.split('.').map(ToString::to_string);
+                    let part = match path.next().as_deref() {
+                        Some("event") => UrlTemplatePart::Event(placeholder.clone(), path.collect()),
+                        Some("meta") => UrlTemplatePart::Meta(placeholder.clone(), path.collect()),
+                        _ => {
+                            return Err(format!(
+                                "Invalid placeholder `{{{placeholder}}}` in url_template, expected `{{event.<path>}}` or `{{meta.<path>}}`"
+                            )
+                            .into())
+                        }
+                    };
+                    if !literal.is_empty() {
+                        parts.push(UrlTemplatePart::Literal(std::mem::take(&mut literal)));
+                    }
+                    parts.push(part);
+                }
+                '}' => return Err("Unmatched `}` in url_template".into()),
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            parts.push(UrlTemplatePart::Literal(literal));
+        }
+        Ok(Self { parts })
+    }
+
+    /// Resolves the placeholders against the event `value` and `meta`,
+    /// erroring for missing values and values that are arrays or records
+    pub(crate) fn resolve(&self, value: Option<&Value>, meta: Option<&Value>) -> Result<String> {
+        let mut url = String::new();
+        // values are path segments until the template starts the query or fragment
+        let mut encode_set = PATH_SEGMENT;
+        let mut in_path = true;
+        for part in &self.parts {
+            let (placeholder, root, path) = match part {
+                UrlTemplatePart::Literal(literal) => {
+                    if literal.contains(&['?', '#'][..]) {
+                        encode_set = QUERY_COMPONENT;
+                        in_path = false;
+                    }
+                    url.push_str(literal);
+                    continue;
+                }
+                UrlTemplatePart::Event(placeholder, path) => (placeholder, value, path),
+                UrlTemplatePart::Meta(placeholder, path) => (placeholder, meta, path),
+            };
+            let resolved = root
+                .and_then(|root| path.iter().try_fold(root, |v, key| v.get(key.as_str())))
+                .ok_or_else(|| {
+                    Error::from(format!(
+                        "Missing value for `{{{placeholder}}}` in url_template"
+                    ))
+                })?;
+            if let Some(s) = resolved.as_str() {
+                if in_path && (s == "." || s == "..") {
+                    return Err(format!(
+                        "Value for `{{{placeholder}}}` in url_template is a `{s}` path segment"
+                    )
+                    .into());
+                }
+                url.extend(utf8_percent_encode(s, encode_set));
+            } else if resolved.as_array().is_some() || resolved.as_object().is_some() {
+                return Err(format!(
+                    "Value for `{{{placeholder}}}` in url_template is not a string or number"
+                )
+                .into());
+            } else {
+                url.extend(utf8_percent_encode(&resolved.encode(), encode_set));
+            }
+        }
+        Ok(url)
+    }
+}
+
+impl<'de> Deserialize<'de> for UrlTemplate {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let input = String::deserialize(deserializer)?;
+        Self::parse(&input).map_err(serde::de::Error::custom)
+    }
+}
+
 /// A Thingy that implements `AsyncBufRead`, so it can be turned into a tide Body
 /// and it can be asynchronously fed with chunks from a channel, so we don't need to buffer all the chunks in memory
 pub(crate) struct StreamingBodyReader {
@@ -218,6 +372,15 @@ impl async_std::io::BufRead for FixedBodyReader {
     }
 }
 
+/// The body bytes of `value` for `raw_body`, which only accepts bytes or strings
+pub(crate) fn raw_body(value: &Value) -> Result<Vec<u8>> {
+    value
+        .as_bytes()
+        .or_else(|| value.as_str().map(str::as_bytes))
+        .map(<[u8]>::to_vec)
+        .ok_or_else(|| Error::from("Event must be bytes or a string to be sent as `raw_body`"))
+}
+
 #[cfg(test)]
 mod tests {
     use std::time::Duration;
@@ -228,6 +391,47 @@ mod tests {
     use async_std::io::prelude::BufReadExt;
     use async_std::prelude::FutureExt;
     use futures::AsyncReadExt;
+    use tremor_value::literal;
+
+    #[test]
+    fn url_template_encodes_values() -> Result<()> {
+        let template = UrlTemplate::parse(
+            "http://localhost/users/{event.id}/items?name={event.name}&tag={meta.tag}#{meta.tag}",
+        )?;
+        let value = literal!({"id": "a/b?c#d&e f", "name": "x&y=z?/#"});
+        let meta = literal!({"tag": "#1 & 2"});
+        assert_eq!(
+            "http://localhost/users/a%2Fb%3Fc%23d&e%20f/items?name=x%26y%3Dz%3F%2F%23&tag=%231%20%26%202#%231%20%26%202",
+            template.resolve(Some(&value), Some(&meta))?
+        );
+
+        // the resolved url keeps the path and query of the template
+        let url = url::Url::parse(&template.resolve(Some(&value), Some(&meta))?)?;
+        assert_eq!("/users/a%2Fb%3Fc%23d&e%20f/items", url.path());
+        let query: Vec<_> = url.query_pairs().collect();
+        assert_eq!(2, query.len());
+        assert_eq!(("name".into(), "x&y=z?/#".into()), query[0]);
+        assert_eq!(("tag".into(), "#1 & 2".into()), query[1]);
+
+        // numbers and booleans are substituted as they are
+        let value = literal!({"id": 42, "name": true});
+        assert_eq!(
+            "http://localhost/users/42/items?name=true&tag=%231%20%26%202#%231%20%26%202",
+            template.resolve(Some(&value), Some(&meta))?
+        );
+
+        // dot-segments can't navigate the path, but are fine elsewhere
+        for id in [".", ".."] {
+            let value = literal!({"id": id, "name": "x"});
+            assert!(template.resolve(Some(&value), Some(&meta)).is_err());
+        }
+        let value = literal!({"id": "a.b..", "name": ".."});
+        assert_eq!(
+            "http://localhost/users/a.b../items?name=..&tag=%231%20%26%202#%231%20%26%202",
+            template.resolve(Some(&value), Some(&meta))?
+        );
+        Ok(())
+    }
 
     #[async_std::test]
     async fn streaming_body_reader() -> Result<()> {
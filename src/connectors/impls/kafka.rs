@@ -99,6 +99,12 @@ where
     active: AtomicBool,
     // for synchronizing when the consumer should clear its assignment cache
     last_rebalance_ts: Arc<AtomicU64>,
+    // where newly assigned partitions start consuming from, only used for the consumer
+    start_position: Option<Arc<consumer::StartPosition>>,
+    // detailed statistics to emit, only used for the consumer
+    statistics: Option<consumer::StatisticsConfig>,
+    // commits the stored offsets of revoked partitions, only used for the consumer
+    revoke_committer: Option<Arc<consumer::RevokeCommitter>>,
 }
 
 impl<Ctx> TremorRDKafkaContext<Ctx>
@@ -119,6 +125,19 @@ where
     const CONSUMER_LAG: Cow<'static, str> = Cow::const_str("consumer_lag");
     const KAFKA_CONSUMER_STATS: &'static str = "kafka_consumer_stats";
 
+    const BROKER: Cow<'static, str> = Cow::const_str("broker");
+    const RTT_AVG: Cow<'static, str> = Cow::const_str("rtt_avg");
+    const RTT_P99: Cow<'static, str> = Cow::const_str("rtt_p99");
+    const OUTBUF_CNT: Cow<'static, str> = Cow::const_str("outbuf_cnt");
+    const WAITRESP_CNT: Cow<'static, str> = Cow::const_str("waitresp_cnt");
+    const KAFKA_CONSUMER_BROKER_STATS: &'static str = "kafka_consumer_broker_stats";
+    const REPLYQ: Cow<'static, str> = Cow::const_str("replyq");
+    const FETCHQ_CNT: Cow<'static, str> = Cow::const_str("fetchq_cnt");
+    const FETCHQ_SIZE: Cow<'static, str> = Cow::const_str("fetchq_size");
+    const KAFKA_CONSUMER_QUEUE_STATS: &'static str = "kafka_consumer_queue_stats";
+    const STATS: Cow<'static, str> = Cow::const_str("stats");
+    const KAFKA_CONSUMER_RAW_STATS: &'static str = "kafka_consumer_raw_stats";
+
     fn consumer(
         ctx: Ctx,
         connect_tx: Sender<KafkaError>,
@@ -131,6 +150,9 @@ where
             metrics_tx,
             active: AtomicBool::new(true),
             last_rebalance_ts,
+            start_position: None,
+            statistics: None,
+            revoke_committer: None,
         }
     }
 
@@ -145,6 +167,9 @@ where
             metrics_tx,
             active: AtomicBool::new(true),
             last_rebalance_ts: Arc::new(AtomicU64::new(0)), // not used for the producer, just a dummy here
+            start_position: None,
+            statistics: None,
+            revoke_committer: None,
         }
     }
 
@@ -210,6 +235,109 @@ where
         self.metrics_tx.try_broadcast(metrics_payload)?;
         Ok(())
     }
+
+    /// Extracts the detailed metrics from the statistics json reported by librdkafka,
+    /// timings are in microseconds
+    fn detailed_stats(&self, stats: &Value, raw: bool) -> Result<Vec<EventPayload>> {
+        if stats.get_str("type") != Some(Self::CONSUMER) {
+            return Ok(vec![]);
+        }
+        let timestamp = stats
+            .get_u64("time")
+            .ok_or_else(|| Error::from("Missing stats time"))?
+            * 1_000_000_000;
+        let connector = Value::from(self.ctx.alias().to_string());
+        let mut payloads = Vec::new();
+
+        for (name, broker) in stats
+            .get_object("brokers")
+            .into_iter()
+            .flat_map(|brokers| brokers.iter())
+        {
+            // the internal broker only serves librdkafkas internal queues
+            if broker.get_str("source") == Some("internal") {
+                continue;
+            }
+            let rtt = broker.get("rtt");
+            let mut fields = HashMap::with_capacity(4);
+            fields.insert(
+                Self::RTT_AVG,
+                Value::from(rtt.get_i64("avg").unwrap_or_default()),
+            );
+            fields.insert(
+                Self::RTT_P99,
+                Value::from(rtt.get_i64("p99").unwrap_or_default()),
+            );
+            fields.insert(
+                Self::OUTBUF_CNT,
+                Value::from(broker.get_i64("outbuf_cnt").unwrap_or_default()),
+            );
+            fields.insert(
+                Self::WAITRESP_CNT,
+                Value::from(broker.get_i64("waitresp_cnt").unwrap_or_default()),
+            );
+            let mut tags = HashMap::with_capacity(2);
+            tags.insert(Self::CONNECTOR, connector.clone());
+            tags.insert(Self::BROKER, Value::from(name.to_string()));
+            payloads.push(make_metrics_payload(
+                Self::KAFKA_CONSUMER_BROKER_STATS,
+                fields,
+                tags,
+                timestamp,
+            ));
+        }
+
+        let mut fetchq_cnt = 0_i64;
+        let mut fetchq_size = 0_i64;
+        let partitions = stats
+            .get_object("topics")
+            .into_iter()
+            .flat_map(|topics| topics.values())
+            .filter_map(|topic| topic.get_object("partitions"))
+            .flat_map(|partitions| partitions.values());
+        for partition in partitions {
+            fetchq_cnt += partition.get_i64("fetchq_cnt").unwrap_or_default();
+            fetchq_size += partition.get_i64("fetchq_size").unwrap_or_default();
+        }
+        let mut fields = HashMap::with_capacity(3);
+        fields.insert(
+            Self::REPLYQ,
+            Value::from(stats.get_i64("replyq").unwrap_or_default()),
+        );
+        fields.insert(Self::FETCHQ_CNT, Value::from(fetchq_cnt));
+        fields.insert(Self::FETCHQ_SIZE, Value::from(fetchq_size));
+        let mut tags = HashMap::with_capacity(1);
+        tags.insert(Self::CONNECTOR, connector.clone());
+        payloads.push(make_metrics_payload(
+            Self::KAFKA_CONSUMER_QUEUE_STATS,
+            fields,
+            tags,
+            timestamp,
+        ));
+
+        if raw {
+            let mut fields = HashMap::with_capacity(1);
+            fields.insert(Self::STATS, stats.clone_static());
+            let mut tags = HashMap::with_capacity(1);
+            tags.insert(Self::CONNECTOR, connector);
+            payloads.push(make_metrics_payload(
+                Self::KAFKA_CONSUMER_RAW_STATS,
+                fields,
+                tags,
+                timestamp,
+            ));
+        }
+        Ok(payloads)
+    }
+
+    fn handle_raw_stats(&self, statistics: &[u8], raw: bool) -> Result<()> {
+        let mut statistics = statistics.to_vec();
+        let stats = tremor_value::parse_to_value(&mut statistics)?;
+        for metrics_payload in self.detailed_stats(&stats, raw)? {
+            self.metrics_tx.try_broadcast(metrics_payload)?;
+        }
+        Ok(())
+    }
 }
 
 impl<Ctx> ClientContext for TremorRDKafkaContext<Ctx>
@@ -246,6 +374,19 @@ where
         }
     }
 
+    fn stats_raw(&self, statistics: &[u8]) {
+        let mut data = statistics.to_vec();
+        match simd_json::serde::from_slice(&mut data) {
+            Ok(stats) => self.stats(stats),
+            Err(e) => warn!("{} Error parsing kafka stats: {}", self.ctx, e),
+        }
+        if let Some(config) = self.statistics.as_ref() {
+            if let Err(e) = self.handle_raw_stats(statistics, config.raw) {
+                warn!("{} Error handling detailed kafka stats: {}", self.ctx, e);
+            }
+        }
+    }
+
     fn error(&self, error: KafkaError, reason: &str) {
         error!("{} Kafka Error {}: {}", &self.ctx, &error, reason);
         if !self.connect_tx.is_closed() {
@@ -13,11 +13,14 @@
 // limitations under the License.
 
 use async_std::sync::Arc;
+use std::collections::HashSet;
 use std::sync::atomic::AtomicU64;
+use std::sync::{Mutex, PoisonError, Weak};
 use std::time::Duration;
 use tremor_common::time::nanotime;
 use tremor_value::value::StaticValue;
 
+use crate::codec;
 use crate::connectors::impls::kafka::{
     SmolRuntime, TremorRDKafkaContext, KAFKA_CONNECT_TIMEOUT, NO_ERROR,
 };
@@ -29,12 +32,18 @@ use async_std::task::{self, JoinHandle};
 use halfbrown::HashMap;
 use indexmap::IndexMap;
 use log::Level::Debug;
+use rdkafka::client::NativeClient;
 use rdkafka::config::ClientConfig;
-use rdkafka::consumer::{CommitMode, Consumer, ConsumerContext, Rebalance, StreamConsumer};
+use rdkafka::consumer::{
+    BaseConsumer, CommitMode, Consumer, ConsumerContext, Rebalance, StreamConsumer,
+};
 use rdkafka::error::{KafkaError, KafkaResult};
 use rdkafka::message::{BorrowedMessage, Headers, Message};
+use rdkafka::types::RDKafkaRespErr;
 use rdkafka::{Offset, TopicPartitionList};
 use rdkafka_sys::RDKafkaErrorCode;
+use std::ffi::CStr;
+use std::ptr;
 
 const KAFKA_CONSUMER_META_KEY: &str = "kafka_consumer";
 
@@ -199,6 +208,96 @@ impl Mode {
     }
 }
 
+/// Where to start consuming from when a partition is assigned
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(deny_unknown_fields, rename_all = "lowercase")]
+pub(crate) enum StartFrom {
+    /// the committed offset of the consumer group, or as configured with `auto.offset.reset` if there is none
+    #[default]
+    Committed,
+    /// the earliest available offset
+    Beginning,
+    /// the end of the partition, only new messages are consumed
+    End,
+    /// the given offset in every partition
+    Offset(i64),
+    /// the earliest message with a timestamp at or after the given one, in nanoseconds
+    Timestamp(u64),
+}
+
+/// How the ids of events are derived from the kafka messages
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(deny_unknown_fields, rename_all = "lowercase")]
+pub(crate) enum EventIds {
+    /// topic and partition as stream id, the message offset as pull id
+    #[default]
+    Offset,
+    /// a counter incremented for each message as pull id
+    Counter,
+}
+
+impl EventIds {
+    /// the pull id of a message with the given `offset_pull_id`, `None` leaves assigning it to the source manager
+    fn pull_id(self, offset_pull_id: u64) -> Option<u64> {
+        match self {
+            EventIds::Offset => Some(offset_pull_id),
+            EventIds::Counter => None,
+        }
+    }
+}
+
+/// When the offsets stored for acked events are committed to the group coordinator
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(deny_unknown_fields, rename_all = "snake_case")]
+pub(crate) enum CommitPolicy {
+    /// as configured by the `mode`
+    #[default]
+    Auto,
+    /// every time an ack stores a new offset
+    OnAck,
+    /// every time the given number of acks stored new offsets
+    Count(usize),
+    /// periodically, every given number of milliseconds
+    Interval(u64),
+}
+
+impl CommitPolicy {
+    /// set the options the commit policy requires on the given `client_config`
+    fn apply(self, client_config: &mut ClientConfig) {
+        if self != CommitPolicy::Auto {
+            // offsets are stored by the source and committed on its own terms
+            client_config
+                .set("enable.auto.commit", "false")
+                .set("enable.auto.offset.store", "false");
+        }
+    }
+}
+
+/// How the partitions of the subscribed topics are assigned to the consumers of a group
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(deny_unknown_fields, rename_all = "lowercase")]
+pub(crate) enum AssignmentStrategy {
+    /// each consumer gets a contiguous range of the partitions of each topic
+    Range,
+    /// the partitions of all topics are assigned to the consumers one by one
+    RoundRobin,
+    /// partitions stay with their consumer across rebalances, only the partitions that need to move are revoked,
+    /// so the other consumers of the group keep consuming during a rebalance, e.g. while restarting instances one by one
+    #[serde(rename = "cooperative-sticky")]
+    CooperativeSticky,
+}
+
+impl AssignmentStrategy {
+    /// the value of the librdkafka setting `partition.assignment.strategy`
+    fn as_str(self) -> &'static str {
+        match self {
+            AssignmentStrategy::Range => "range",
+            AssignmentStrategy::RoundRobin => "roundrobin",
+            AssignmentStrategy::CooperativeSticky => "cooperative-sticky",
+        }
+    }
+}
+
 #[derive(Deserialize, Debug, Clone)]
 #[serde(deny_unknown_fields)]
 pub(crate) struct Config {
@@ -238,17 +337,164 @@ pub(crate) struct Config {
     ///   }
     ///   ```
     mode: Mode,
+
+    /// Optional mapping from topic to the name of the codec used to decode messages from this topic.
+    ///
+    /// Messages from topics not contained in this mapping are decoded with the connector codec.
+    #[serde(default)]
+    topic_codecs: HashMap<String, String>,
+
+    /// Maximum amount of data the broker should return for a fetch request.
+    ///
+    /// Maps to the librdkafka setting `fetch.max.bytes`, the librdkafka default is used if not set.
+    fetch_max_bytes: Option<u64>,
+
+    /// Maximum amount of data per partition the broker should return for a fetch request.
+    ///
+    /// Maps to the librdkafka setting `max.partition.fetch.bytes`, the librdkafka default is used if not set.
+    max_partition_fetch_bytes: Option<u64>,
+
+    /// Maximum time in milliseconds to wait for the final commit of stored offsets when stopping the consumer.
+    ///
+    /// Only applies to modes that store offsets locally, e.g. `transactional` with a non-zero `commit_interval`.
+    #[serde(default = "default_commit_timeout_ms")]
+    commit_timeout_ms: u64,
+
+    /// Maximum time in milliseconds the broker waits to fill a fetch request up to `fetch.min.bytes`.
+    ///
+    /// Maps to the librdkafka setting `fetch.wait.max.ms`, the librdkafka default is used if not set.
+    fetch_wait_max_ms: Option<u64>,
+
+    /// Maximum time in milliseconds to wait for a new message when polling the consumer.
+    ///
+    /// Bounds every poll of the consumer, so the consumer task can check if it is still needed.
+    /// If not set, polls wait indefinitely.
+    poll_timeout_ms: Option<u64>,
+
+    /// Keep the raw bytes of each message, before decoding, in the event metadata under `raw`.
+    #[serde(default)]
+    keep_raw: bool,
+
+    /// Position to start consuming from, for replaying messages instead of continuing from the committed offsets.
+    ///
+    /// Possible values: `"committed"` (the default), `"beginning"`, `"end"`, `{"offset": 42}` or `{"timestamp": nanos}`.
+    /// It only applies to partitions assigned for the first time after connecting, later assignments of the same partition
+    /// continue from the committed offset.
+    #[serde(default)]
+    start_from: StartFrom,
+
+    /// Emit statistics collected by librdkafka every `interval_ms` as metrics.
+    ///
+    /// The round trip time and request queue depths of each broker are emitted as `kafka_consumer_broker_stats`,
+    /// the depths of the fetch and reply queues as `kafka_consumer_queue_stats`.
+    /// With `raw: true` the complete statistics are emitted as well, as `kafka_consumer_raw_stats`.
+    /// If not set, statistics are collected at the `metrics_interval_s` of the connector, only for `kafka_consumer_stats`.
+    statistics: Option<StatisticsConfig>,
+
+    /// Strategy for assigning partitions to the consumers of the group: `"range"`, `"roundrobin"` or `"cooperative-sticky"`.
+    ///
+    /// Maps to the librdkafka setting `partition.assignment.strategy`, the librdkafka default is used if not set.
+    /// All consumers of a group need to use the same strategy.
+    assignment_strategy: Option<AssignmentStrategy>,
+
+    /// How the ids of the events are derived: `"offset"` (the default) or `"counter"`.
+    ///
+    /// With `offset` the topic and partition of a message are encoded in the stream id of its event and its offset is the pull id,
+    /// so acks and fails map directly to kafka offsets. With `counter` events are numbered consecutively, which is only supported
+    /// with modes that neither store nor commit offsets for handled events.
+    #[serde(default)]
+    event_ids: EventIds,
+
+    /// When the offsets stored for acked events are committed to the group coordinator.
+    ///
+    /// Possible values: `"auto"` (the default) as configured by the `mode`, `"on_ack"` with every ack,
+    /// `{"count": n}` after every `n` acks or `{"interval": ms}` every `ms` milliseconds.
+    /// Anything other than `auto` requires the `transactional` mode and replaces its `commit_interval`.
+    #[serde(default)]
+    commit_mode: CommitPolicy,
 }
 
 impl ConfigImpl for Config {}
 
+/// Statistics collected by librdkafka and emitted as metrics
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub(super) struct StatisticsConfig {
+    /// interval in milliseconds at which librdkafka collects statistics
+    interval_ms: u64,
+    /// also emit the complete statistics as reported by librdkafka
+    #[serde(default)]
+    pub(super) raw: bool,
+}
+
+impl Config {
+    /// set the configured fetch options on the given `client_config`
+    fn set_fetch_options(&self, alias: &Alias, client_config: &mut ClientConfig) -> Result<()> {
+        if let Some(fetch_max_bytes) = self.fetch_max_bytes {
+            set_client_config(
+                alias,
+                client_config,
+                "fetch.max.bytes",
+                fetch_max_bytes.to_string(),
+            )?;
+        }
+        if let Some(max_partition_fetch_bytes) = self.max_partition_fetch_bytes {
+            set_client_config(
+                alias,
+                client_config,
+                "max.partition.fetch.bytes",
+                max_partition_fetch_bytes.to_string(),
+            )?;
+        }
+        if let Some(fetch_wait_max_ms) = self.fetch_wait_max_ms {
+            set_client_config(
+                alias,
+                client_config,
+                "fetch.wait.max.ms",
+                fetch_wait_max_ms.to_string(),
+            )?;
+        }
+        Ok(())
+    }
+
+    /// set the configured partition assignment strategy on the given `client_config`
+    fn set_assignment_strategy(
+        &self,
+        alias: &Alias,
+        client_config: &mut ClientConfig,
+    ) -> Result<()> {
+        if let Some(assignment_strategy) = self.assignment_strategy {
+            set_client_config(
+                alias,
+                client_config,
+                "partition.assignment.strategy",
+                assignment_strategy.as_str(),
+            )?;
+        }
+        Ok(())
+    }
+}
+
 fn default_commit_interval() -> u64 {
     5_000_000_000 // 5 seconds, the default from librdkafka
 }
 
+fn default_commit_timeout_ms() -> u64 {
+    1000
+}
+
 #[derive(Default, Debug)]
 pub(crate) struct Builder {}
 
+impl Builder {
+    const COUNTER_EVENT_IDS: &'static str =
+        "`event_ids: \"counter\"` can't be used with a `mode` storing or committing offsets.";
+    const COMMIT_MODE_REQUIRES_TRANSACTIONAL: &'static str =
+        "A `commit_mode` other than `\"auto\"` requires the `transactional` mode.";
+    const EMPTY_COMMIT_MODE: &'static str =
+        "The `count` or `interval` of the `commit_mode` must be greater than 0.";
+}
+
 #[async_trait::async_trait()]
 impl ConnectorBuilder for Builder {
     fn connector_type(&self) -> ConnectorType {
@@ -264,8 +510,32 @@ impl ConnectorBuilder for Builder {
     ) -> Result<Box<dyn Connector>> {
         let metrics_interval_s = config.metrics_interval_s;
         let config = Config::new(raw_config)?;
+        // acks and fails are mapped to offsets via the event ids
+        if config.event_ids == EventIds::Counter && config.mode.is_transactional() {
+            return Err(err_connector_def(alias, Builder::COUNTER_EVENT_IDS));
+        }
+        if config.commit_mode != CommitPolicy::Auto
+            && !matches!(config.mode, Mode::Transactional { .. })
+        {
+            return Err(err_connector_def(
+                alias,
+                Builder::COMMIT_MODE_REQUIRES_TRANSACTIONAL,
+            ));
+        }
+        if matches!(
+            config.commit_mode,
+            CommitPolicy::Count(0) | CommitPolicy::Interval(0)
+        ) {
+            return Err(err_connector_def(alias, Builder::EMPTY_COMMIT_MODE));
+        }
         // returns the first broker if all are valid
         let (host, port) = super::verify_brokers(alias, &config.brokers)?;
+        // fail early on unknown codecs
+        for (topic, codec) in &config.topic_codecs {
+            codec::resolve(&codec.into()).map_err(|e| {
+                err_connector_def(alias, &format!("Invalid codec for topic {topic}: {e}"))
+            })?;
+        }
         let origin_uri = EventOriginUri {
             scheme: "tremor-kafka".to_string(),
             host,
@@ -280,22 +550,32 @@ impl ConnectorBuilder for Builder {
                 e.to_string(),
             ))
         })?;
+        config.commit_mode.apply(&mut client_config);
 
         // we do overwrite the rdkafka options to ensure a sane config
-        set_client_config(&mut client_config, "group.id", &config.group_id)?;
-        set_client_config(&mut client_config, "client.id", &client_id)?;
+        set_client_config(alias, &mut client_config, "group.id", &config.group_id)?;
+        set_client_config(alias, &mut client_config, "client.id", &client_id)?;
         set_client_config(
+            alias,
             &mut client_config,
             "bootstrap.servers",
             config.brokers.join(","),
         )?;
-
-        if let Some(metrics_interval_s) = metrics_interval_s {
+        config.set_fetch_options(alias, &mut client_config)?;
+        config.set_assignment_strategy(alias, &mut client_config)?;
+
+        let statistics_interval_ms = config
+            .statistics
+            .as_ref()
+            .map(|statistics| statistics.interval_ms)
+            .or_else(|| metrics_interval_s.map(|metrics_interval_s| metrics_interval_s * 1000));
+        if let Some(statistics_interval_ms) = statistics_interval_ms {
             // enable stats collection
             set_client_config(
+                alias,
                 &mut client_config,
                 "statistics.interval.ms",
-                format!("{}", metrics_interval_s * 1000),
+                format!("{statistics_interval_ms}"),
             )?;
         }
 
@@ -310,18 +590,125 @@ impl ConnectorBuilder for Builder {
 }
 
 fn set_client_config<V: Into<String>>(
+    alias: &Alias,
     client_config: &mut ClientConfig,
     key: &'static str,
     value: V,
 ) -> Result<()> {
     if client_config.get(key).is_some() {
-        return Err(format!("Provided rdkafka_option that will be overwritten: {key}").into());
+        return Err(err_connector_def(
+            alias,
+            &format!("Provided rdkafka_option that will be overwritten: {key}"),
+        ));
     }
     client_config.set(key, value);
     Ok(())
 }
 
+/// Takes the message out of an error returned by librdkafka and frees it
+fn take_error(error: *mut rdkafka_sys::rd_kafka_error_t) -> Option<String> {
+    if error.is_null() {
+        return None;
+    }
+    // SAFETY: `error` is a valid error object owned by us, its string lives until it is destroyed
+    unsafe {
+        let msg = CStr::from_ptr(rdkafka_sys::rd_kafka_error_string(error))
+            .to_string_lossy()
+            .into_owned();
+        rdkafka_sys::rd_kafka_error_destroy(error);
+        Some(msg)
+    }
+}
+
+/// The message of an error code returned by librdkafka, `None` if there is no error
+fn error_code(code: RDKafkaRespErr) -> Option<String> {
+    let code = RDKafkaErrorCode::from(code);
+    (code != RDKafkaErrorCode::NoError).then(|| code.to_string())
+}
+
+/// if the consumer group rebalances incrementally, as with the `cooperative-sticky` assignment strategy
+fn is_cooperative(native_client: &NativeClient) -> bool {
+    // SAFETY: the client is valid for the duration of the rebalance callback, the protocol name is static
+    let protocol = unsafe {
+        CStr::from_ptr(rdkafka_sys::rd_kafka_rebalance_protocol(
+            native_client.ptr(),
+        ))
+    };
+    protocol.to_bytes() == b"COOPERATIVE"
+}
+
 impl ConsumerContext for TremorRDKafkaContext<SourceContext> {
+    /// Applies the new assignment between `pre_rebalance` and `post_rebalance`
+    ///
+    /// With the `cooperative-sticky` assignment strategy rebalances are incremental:
+    /// `Assign` only contains the newly assigned partitions and `Revoke` only the revoked ones,
+    /// the other partitions stay assigned and keep being consumed. These need to be applied with
+    /// `incremental_assign` and `incremental_unassign`, replacing the whole assignment fails the consumer.
+    /// For the eager strategies the whole assignment is replaced, or removed on revoke.
+    fn rebalance(
+        &self,
+        native_client: &NativeClient,
+        err: RDKafkaRespErr,
+        tpl: &mut TopicPartitionList,
+    ) {
+        let rebalance = match err {
+            RDKafkaRespErr::RD_KAFKA_RESP_ERR__ASSIGN_PARTITIONS => Rebalance::Assign(tpl),
+            RDKafkaRespErr::RD_KAFKA_RESP_ERR__REVOKE_PARTITIONS => Rebalance::Revoke(tpl),
+            _ => {
+                let error = RDKafkaErrorCode::from(err).to_string();
+                error!("{} Error rebalancing: {error}", &self.ctx);
+                Rebalance::Error(error)
+            }
+        };
+        self.pre_rebalance(&rebalance);
+
+        let client = native_client.ptr();
+        let assign = matches!(rebalance, Rebalance::Assign(_));
+        // SAFETY: the client and the partition list are valid for the duration of the rebalance callback
+        let error = unsafe {
+            match (is_cooperative(native_client), assign) {
+                (true, true) => {
+                    take_error(rdkafka_sys::rd_kafka_incremental_assign(client, tpl.ptr()))
+                }
+                (true, false) => take_error(rdkafka_sys::rd_kafka_incremental_unassign(
+                    client,
+                    tpl.ptr(),
+                )),
+                (false, true) => error_code(rdkafka_sys::rd_kafka_assign(client, tpl.ptr())),
+                (false, false) => error_code(rdkafka_sys::rd_kafka_assign(client, ptr::null())),
+            }
+        };
+        if let Some(error) = error {
+            error!("{} Error applying the assignment: {error}", &self.ctx);
+        }
+
+        self.post_rebalance(&rebalance);
+    }
+
+    fn pre_rebalance<'a>(&self, rebalance: &rdkafka::consumer::Rebalance<'a>) {
+        match (
+            rebalance,
+            self.start_position.as_ref(),
+            self.revoke_committer.as_ref(),
+        ) {
+            // the offsets set here are the ones the partitions are assigned with
+            (Rebalance::Assign(tpl), Some(start_position), _) => {
+                if let Err(e) = start_position.apply(tpl) {
+                    warn!(
+                        "{} Error starting assigned partitions from {:?}: {e}",
+                        &self.ctx, start_position.start_from
+                    );
+                }
+            }
+            // whoever gets the partitions next continues from the committed offsets,
+            // so commit the offsets of acked events before handing them over
+            (Rebalance::Revoke(_), _, Some(revoke_committer)) => revoke_committer.commit(&self.ctx),
+            _ => {}
+        }
+    }
+
+    // Rebalances may be incremental, see `rebalance`. Neither handler assumes to see the full
+    // assignment, the assignment cache is refreshed from the consumer after every rebalance.
     fn post_rebalance<'a>(&self, rebalance: &rdkafka::consumer::Rebalance<'a>) {
         // store the last timestamp
         self.last_rebalance_ts.store(nanotime(), Ordering::Release);
@@ -408,6 +795,267 @@ impl ConsumerContext for TremorRDKafkaContext<SourceContext> {
 type TremorConsumerContext = TremorRDKafkaContext<SourceContext>;
 type TremorConsumer = StreamConsumer<TremorConsumerContext, SmolRuntime>;
 
+impl<Ctx> TremorRDKafkaContext<Ctx>
+where
+    Ctx: Context + Send + Sync + 'static,
+{
+    /// Start newly assigned partitions at `start_position`, instead of the committed offsets
+    #[must_use]
+    fn with_start_position(mut self, start_position: Option<Arc<StartPosition>>) -> Self {
+        self.start_position = start_position;
+        self
+    }
+
+    /// Emit the detailed statistics as configured in `statistics`
+    #[must_use]
+    fn with_statistics(mut self, statistics: Option<StatisticsConfig>) -> Self {
+        self.statistics = statistics;
+        self
+    }
+
+    /// Commit the stored offsets with `revoke_committer` before partitions are revoked
+    #[must_use]
+    fn with_revoke_committer(mut self, revoke_committer: Option<Arc<RevokeCommitter>>) -> Self {
+        self.revoke_committer = revoke_committer;
+        self
+    }
+}
+
+/// Looking up offsets by timestamp
+///
+/// Exists mainly to be able to test starting from a timestamp without a kafka cluster.
+trait OffsetLookup: Send + Sync {
+    /// the earliest offset of each partition in `timestamps` with a timestamp at or after the one given as its offset
+    fn offsets_for_times(
+        &self,
+        timestamps: TopicPartitionList,
+        timeout: Duration,
+    ) -> KafkaResult<TopicPartitionList>;
+}
+
+impl OffsetLookup for BaseConsumer {
+    fn offsets_for_times(
+        &self,
+        timestamps: TopicPartitionList,
+        timeout: Duration,
+    ) -> KafkaResult<TopicPartitionList> {
+        Consumer::offsets_for_times(self, timestamps, timeout)
+    }
+}
+
+/// Sets the offsets of newly assigned partitions according to `start_from`
+pub(super) struct StartPosition {
+    start_from: StartFrom,
+    /// only needed for starting from a timestamp
+    lookup: Option<Box<dyn OffsetLookup>>,
+    lookup_timeout: Duration,
+    /// partitions that have been assigned before, they continue from the committed offset
+    started: Mutex<HashSet<(String, i32)>>,
+}
+
+impl StartPosition {
+    fn new(
+        start_from: StartFrom,
+        lookup: Option<Box<dyn OffsetLookup>>,
+        lookup_timeout: Duration,
+    ) -> Self {
+        Self {
+            start_from,
+            lookup,
+            lookup_timeout,
+            started: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// Sets the offsets of all partitions in `tpl` that are assigned for the first time
+    fn apply(&self, tpl: &TopicPartitionList) -> KafkaResult<()> {
+        let mut started = self.started.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut new_partitions: Vec<_> = tpl
+            .elements()
+            .into_iter()
+            .filter(|elem| !started.contains(&(elem.topic().to_string(), elem.partition())))
+            .collect();
+        if new_partitions.is_empty() {
+            return Ok(());
+        }
+        let found = match (self.start_from, self.lookup.as_ref()) {
+            (StartFrom::Timestamp(nanos), Some(lookup)) => {
+                let millis = i64::try_from(nanos / 1_000_000).unwrap_or(i64::MAX);
+                let mut timestamps = TopicPartitionList::with_capacity(new_partitions.len());
+                for elem in &new_partitions {
+                    timestamps.add_partition_offset(
+                        elem.topic(),
+                        elem.partition(),
+                        Offset::Offset(millis),
+                    )?;
+                }
+                Some(lookup.offsets_for_times(timestamps, self.lookup_timeout)?)
+            }
+            _ => None,
+        };
+        for elem in &mut new_partitions {
+            let offset = match (self.start_from, found.as_ref()) {
+                (StartFrom::Beginning, _) => Offset::Beginning,
+                (StartFrom::End, _) => Offset::End,
+                (StartFrom::Offset(offset), _) => Offset::Offset(offset),
+                // partitions without a message after the timestamp start at the end
+                (StartFrom::Timestamp(_), Some(found)) => found
+                    .find_partition(elem.topic(), elem.partition())
+                    .map_or(Offset::End, |found| found.offset()),
+                (StartFrom::Committed | StartFrom::Timestamp(_), _) => continue,
+            };
+            elem.set_offset(offset)?;
+        }
+        started.extend(
+            new_partitions
+                .iter()
+                .map(|elem| (elem.topic().to_string(), elem.partition())),
+        );
+        Ok(())
+    }
+}
+
+/// Committing locally stored offsets
+///
+/// Exists mainly to be able to test committing without a kafka cluster.
+trait CommitStoredOffsets {
+    /// commit all locally stored offsets and block until the commit is done
+    fn commit_stored_offsets(&self) -> KafkaResult<()>;
+
+    /// commit all locally stored offsets without waiting for the commit to be done
+    fn commit_stored_offsets_async(&self) -> KafkaResult<()>;
+}
+
+impl CommitStoredOffsets for TremorConsumer {
+    fn commit_stored_offsets(&self) -> KafkaResult<()> {
+        self.commit_consumer_state(CommitMode::Sync)
+    }
+
+    fn commit_stored_offsets_async(&self) -> KafkaResult<()> {
+        self.commit_consumer_state(CommitMode::Async)
+    }
+}
+
+/// Commit all locally stored offsets in the background
+fn commit_stored<C, Ctx>(consumer: &C, ctx: &Ctx)
+where
+    C: CommitStoredOffsets,
+    Ctx: Context,
+{
+    match consumer.commit_stored_offsets_async() {
+        // no offsets stored, nothing to commit
+        Ok(()) | Err(KafkaError::ConsumerCommit(RDKafkaErrorCode::NoOffset)) => {}
+        Err(e) => warn!("{ctx} Error committing stored offsets: {e}"),
+    }
+}
+
+/// Commits the locally stored offsets when partitions are revoked during a rebalance
+///
+/// Without it, offsets stored since the last commit are lost with the revoked partitions and the
+/// events are consumed again by whoever gets the partitions next. The consumer is only known once
+/// it is created, after the context holding this.
+#[derive(Default)]
+pub(crate) struct RevokeCommitter {
+    consumer: Mutex<Option<Weak<dyn CommitStoredOffsets + Send + Sync>>>,
+}
+
+impl RevokeCommitter {
+    fn set_consumer(&self, consumer: Weak<dyn CommitStoredOffsets + Send + Sync>) {
+        *self.consumer.lock().unwrap_or_else(PoisonError::into_inner) = Some(consumer);
+    }
+
+    /// commits synchronously, the partitions are only revoked once this returns
+    fn commit<Ctx: Context>(&self, ctx: &Ctx) {
+        let consumer = self
+            .consumer
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .as_ref()
+            .and_then(Weak::upgrade);
+        if let Some(consumer) = consumer {
+            match consumer.commit_stored_offsets() {
+                // no offsets stored, nothing to commit
+                Ok(()) | Err(KafkaError::ConsumerCommit(RDKafkaErrorCode::NoOffset)) => {}
+                Err(e) => warn!("{ctx} Error committing stored offsets of revoked partitions: {e}"),
+            }
+        }
+    }
+}
+
+/// Counts the offsets stored for acks, to commit them as the `commit_mode` demands
+#[derive(Debug)]
+struct AckCommitter {
+    commit_mode: CommitPolicy,
+    stored: usize,
+}
+
+impl AckCommitter {
+    fn new(commit_mode: CommitPolicy) -> Self {
+        Self {
+            commit_mode,
+            stored: 0,
+        }
+    }
+
+    /// an ack stored a new offset, commits the stored offsets if it is time to
+    fn offset_stored<C, Ctx>(&mut self, consumer: &C, ctx: &Ctx)
+    where
+        C: CommitStoredOffsets,
+        Ctx: Context,
+    {
+        let commit = match self.commit_mode {
+            CommitPolicy::OnAck => true,
+            CommitPolicy::Count(count) => {
+                self.stored += 1;
+                self.stored >= count
+            }
+            CommitPolicy::Auto | CommitPolicy::Interval(_) => false,
+        };
+        if commit {
+            self.stored = 0;
+            commit_stored(consumer, ctx);
+        }
+    }
+}
+
+/// Commits the locally stored offsets on every tick of `ticks`, until the consumer is dropped
+async fn commit_driver<C, Ctx, T>(consumer: Weak<C>, mut ticks: T, ctx: Ctx)
+where
+    C: CommitStoredOffsets + Send + Sync + 'static,
+    Ctx: Context,
+    T: futures::Stream + Unpin,
+{
+    while ticks.next().await.is_some() {
+        if let Some(consumer) = consumer.upgrade() {
+            commit_stored(consumer.as_ref(), &ctx);
+        } else {
+            break;
+        }
+    }
+}
+
+/// Commit all locally stored offsets, waiting for at most `commit_timeout`
+async fn final_commit<C, Ctx>(consumer: Arc<C>, commit_timeout: Duration, ctx: &Ctx)
+where
+    C: CommitStoredOffsets + Send + Sync + 'static,
+    Ctx: Context,
+{
+    debug!("{ctx} Committing stored offsets...");
+    let res = task::spawn_blocking(move || consumer.commit_stored_offsets())
+        .timeout(commit_timeout)
+        .await;
+    match res {
+        Ok(Ok(())) => debug!("{ctx} Stored offsets committed."),
+        // no offsets stored, nothing to commit
+        Ok(Err(KafkaError::ConsumerCommit(RDKafkaErrorCode::NoOffset))) => {}
+        Ok(Err(e)) => warn!("{ctx} Error committing stored offsets: {e}"),
+        Err(_) => warn!(
+            "{ctx} Timeout committing stored offsets after {}ms.",
+            commit_timeout.as_millis()
+        ),
+    }
+}
+
 struct KafkaConsumerConnector {
     config: Config,
     client_config: ClientConfig,
@@ -425,7 +1073,7 @@ impl Connector for KafkaConsumerConnector {
             self.config.clone(),
             self.client_config.clone(),
             self.origin_uri.clone(),
-        );
+        )?;
         builder.spawn(source, source_context).map(Some)
     }
 
@@ -463,15 +1111,28 @@ struct KafkaConsumerSource {
     origin_uri: EventOriginUri,
     topics: Vec<String>,
     topic_resolver: TopicResolver,
+    topic_codecs: HashMap<String, String>,
     // map from stream_id to offset
     offsets: Option<HashMap<u64, i64>>,
     stores_offsets: bool,
     retry_failed_events: bool,
+    commit_mode: CommitPolicy,
+    ack_committer: AckCommitter,
     seek_timeout: Duration,
+    commit_timeout: Duration,
+    poll_timeout: Option<Duration>,
+    keep_raw: bool,
+    /// kept across reconnects, so partitions assigned before continue from the committed offset
+    start_position: Option<Arc<StartPosition>>,
+    statistics: Option<StatisticsConfig>,
+    /// shared with the context of each consumer, to commit before partitions are revoked
+    revoke_committer: Option<Arc<RevokeCommitter>>,
+    event_ids: EventIds,
     source_tx: Sender<(SourceReply, Option<u64>)>,
     source_rx: Receiver<(SourceReply, Option<u64>)>,
     consumer: Option<Arc<TremorConsumer>>,
     consumer_task: Option<JoinHandle<()>>,
+    commit_task: Option<JoinHandle<()>>,
     metrics_rx: Option<BroadcastReceiver<EventPayload>>,
     last_rebalance_ts: Arc<AtomicU64>,
     cached_assignment: Option<(TopicPartitionList, u64)>,
@@ -479,9 +1140,27 @@ struct KafkaConsumerSource {
 
 impl KafkaConsumerSource {
     const DEFAULT_SEEK_TIMEOUT: Duration = Duration::from_millis(500);
-
-    fn new(config: Config, client_config: ClientConfig, origin_uri: EventOriginUri) -> Self {
-        let Config { topics, mode, .. } = config;
+    /// detailed statistics are reported as multiple metrics, one for each broker
+    const STATISTICS_METRICS_CAPACITY: usize = 64;
+
+    fn new(
+        config: Config,
+        client_config: ClientConfig,
+        origin_uri: EventOriginUri,
+    ) -> Result<Self> {
+        let Config {
+            topics,
+            mode,
+            topic_codecs,
+            commit_timeout_ms,
+            poll_timeout_ms,
+            keep_raw,
+            start_from,
+            statistics,
+            event_ids,
+            commit_mode,
+            ..
+        } = config;
         let topic_resolver = TopicResolver::new(topics.clone());
         let seek_timeout = client_config
             // this will put the default from kafka if not present
@@ -490,6 +1169,19 @@ impl KafkaConsumerSource {
             .map(|poll_interval| poll_interval.parse().unwrap_or(500_u64) / 2)
             .map(Duration::from_millis)
             .unwrap_or(Self::DEFAULT_SEEK_TIMEOUT);
+        let start_position = match start_from {
+            StartFrom::Committed => None,
+            StartFrom::Timestamp(_) => {
+                // the consumer can't be used during a rebalance, so offsets are looked up with a separate client
+                let lookup: BaseConsumer = client_config.create()?;
+                Some(StartPosition::new(
+                    start_from,
+                    Some(Box::new(lookup)),
+                    seek_timeout,
+                ))
+            }
+            start_from => Some(StartPosition::new(start_from, None, seek_timeout)),
+        };
 
         let (source_tx, source_rx) = bounded(crate::QSIZE.load(Ordering::Relaxed));
         let offsets = if mode.is_transactional() {
@@ -497,23 +1189,53 @@ impl KafkaConsumerSource {
         } else {
             None
         };
-        Self {
+        Ok(Self {
             client_config,
             origin_uri,
             topics,
             topic_resolver,
+            topic_codecs,
             offsets,
-            stores_offsets: mode.stores_offsets(),
+            // offsets are stored for any commit mode but `auto`, to commit them when it is time to
+            stores_offsets: mode.stores_offsets() || commit_mode != CommitPolicy::Auto,
             retry_failed_events: mode.retries_failed_events(),
+            commit_mode,
+            ack_committer: AckCommitter::new(commit_mode),
             seek_timeout,
+            commit_timeout: Duration::from_millis(commit_timeout_ms),
+            poll_timeout: poll_timeout_ms.map(Duration::from_millis),
+            keep_raw,
+            start_position: start_position.map(Arc::new),
+            statistics,
+            // `auto` commits are left to librdkafka, which commits on revoke itself
+            revoke_committer: (commit_mode != CommitPolicy::Auto)
+                .then(|| Arc::new(RevokeCommitter::default())),
+            event_ids,
             source_tx,
             source_rx,
             consumer: None,
             consumer_task: None,
+            commit_task: None,
             metrics_rx: None,
             last_rebalance_ts: Arc::new(AtomicU64::new(0)),
             cached_assignment: None,
-        }
+        })
+    }
+
+    /// The context of a new consumer, sharing the start position with all previous consumers
+    fn consumer_context<Ctx>(
+        &self,
+        ctx: Ctx,
+        connect_tx: Sender<KafkaError>,
+        metrics_tx: BroadcastSender<EventPayload>,
+    ) -> TremorRDKafkaContext<Ctx>
+    where
+        Ctx: Context + Send + Sync + 'static,
+    {
+        TremorRDKafkaContext::consumer(ctx, connect_tx, metrics_tx, self.last_rebalance_ts.clone())
+            .with_start_position(self.start_position.clone())
+            .with_statistics(self.statistics.clone())
+            .with_revoke_committer(self.revoke_committer.clone())
     }
 
     /// gets the current assignment from the cache or fetches it from the group coordinator
@@ -551,6 +1273,9 @@ impl KafkaConsumerSource {
 #[async_trait::async_trait()]
 impl Source for KafkaConsumerSource {
     async fn connect(&mut self, ctx: &SourceContext, _attempt: &Attempt) -> Result<bool> {
+        if let Some(commit_task) = self.commit_task.take() {
+            commit_task.cancel().await;
+        }
         if let Some(consumer_task) = self.consumer_task.take() {
             self.cached_assignment.take(); // clear out references to the consumer
                                            // drop the consumer
@@ -570,15 +1295,16 @@ impl Source for KafkaConsumerSource {
 
         // we only ever want to report on the latest metrics and discard old ones
         // if no messages arrive, no metrics will be reported, so be it.
-        let (mut metrics_tx, metrics_rx) = broadcast(1);
+        let metrics_capacity = if self.statistics.is_some() {
+            Self::STATISTICS_METRICS_CAPACITY
+        } else {
+            1
+        };
+        let (mut metrics_tx, metrics_rx) = broadcast(metrics_capacity);
         metrics_tx.set_overflow(true);
         self.metrics_rx = Some(metrics_rx);
-        let consumer_context = TremorConsumerContext::consumer(
-            ctx.clone(),
-            connect_result_tx.clone(),
-            metrics_tx,
-            self.last_rebalance_ts.clone(),
-        );
+        let consumer_context =
+            self.consumer_context(ctx.clone(), connect_result_tx.clone(), metrics_tx);
         let consumer: TremorConsumer = self.client_config.create_with_context(consumer_context)?;
 
         let topics: Vec<&str> = self
@@ -597,12 +1323,27 @@ impl Source for KafkaConsumerSource {
         };
         let arc_consumer = Arc::new(consumer);
         let task_consumer = arc_consumer.clone();
+        if let CommitPolicy::Interval(interval_ms) = self.commit_mode {
+            self.commit_task = Some(task::spawn(commit_driver(
+                Arc::downgrade(&arc_consumer),
+                async_std::stream::interval(Duration::from_millis(interval_ms)),
+                ctx.clone(),
+            )));
+        }
+        if let Some(revoke_committer) = self.revoke_committer.as_ref() {
+            let consumer: Weak<dyn CommitStoredOffsets + Send + Sync> =
+                Arc::downgrade(&arc_consumer);
+            revoke_committer.set_consumer(consumer);
+        }
         self.consumer = Some(arc_consumer);
 
         let handle = task::spawn(consumer_task(
             task_consumer,
             self.topic_resolver.clone(),
+            self.topic_codecs.clone(),
             self.origin_uri.clone(),
+            self.poll_timeout,
+            self.event_ids,
             connect_result_tx,
             self.source_tx.clone(),
             ctx.clone(),
@@ -657,6 +1398,7 @@ impl Source for KafkaConsumerSource {
                                 // store the new maximum offset for this partition
                                 debug!("{ctx} Storing offset {topic} {partition}: {raw_offset}");
                                 consumer.store_offset(topic, partition, raw_offset)?;
+                                self.ack_committer.offset_stored(consumer.as_ref(), ctx);
                             } else {
                                 // commit directly to the group coordinator
                                 let mut tpl = TopicPartitionList::with_capacity(1);
@@ -760,8 +1502,18 @@ impl Source for KafkaConsumerSource {
         // free references, see: https://github.com/edenhill/librdkafka/blob/master/INTRODUCTION.md#high-level-kafkaconsumer
         self.cached_assignment.take();
 
+        // stop periodic commits, the final commit below takes over
+        if let Some(commit_task) = self.commit_task.take() {
+            commit_task.cancel().await;
+        }
+
         // clear out the consumer
         if let Some(consumer) = self.consumer.take() {
+            if self.stores_offsets {
+                // commit the offsets we stored for acknowledged events,
+                // so they won't be redelivered after a clean shutdown
+                final_commit(consumer.clone(), self.commit_timeout, ctx).await;
+            }
             drop(consumer);
         }
 
@@ -784,6 +1536,10 @@ impl Source for KafkaConsumerSource {
         true
     }
 
+    fn keep_raw(&self) -> bool {
+        self.keep_raw
+    }
+
     fn metrics(&mut self, _timestamp: u64, _ctx: &SourceContext) -> Vec<EventPayload> {
         if let Some(metrics_rx) = self.metrics_rx.as_mut() {
             let mut vec = Vec::with_capacity(metrics_rx.len());
@@ -801,7 +1557,10 @@ impl Source for KafkaConsumerSource {
 async fn consumer_task(
     task_consumer: Arc<StreamConsumer<TremorConsumerContext, SmolRuntime>>,
     topic_resolver: TopicResolver,
+    topic_codecs: HashMap<String, String>,
     consumer_origin_uri: EventOriginUri,
+    poll_timeout: Option<Duration>,
+    event_ids: EventIds,
     connect_result_tx: Sender<KafkaError>,
     source_tx: Sender<(SourceReply, Option<u64>)>,
     source_ctx: SourceContext,
@@ -811,7 +1570,17 @@ async fn consumer_task(
     let mut connect_result_channel = Some(connect_result_tx);
 
     loop {
-        match stream.next().await {
+        let next = if let Ok(next) = poll_next(&mut stream, poll_timeout).await {
+            next
+        } else {
+            // no message within the poll timeout, check if we are still needed
+            if source_tx.is_closed() {
+                debug!("{source_ctx} Source channel closed. Stopping consumer task.");
+                break;
+            }
+            continue;
+        };
+        match next {
             Some(Ok(kafka_msg)) => {
                 //debug!("{source_ctx} Received kafka msg: {kafka_msg:?}");
                 if let Some(tx) = connect_result_channel.take() {
@@ -837,9 +1606,9 @@ async fn consumer_task(
                     meta: Some(meta),
                     stream: Some(stream_id),
                     port: Some(OUT),
-                    codec_overwrite: None,
+                    codec_overwrite: codec_overwrite(&topic_codecs, kafka_msg.topic()),
                 };
-                if let Err(e) = source_tx.send((reply, Some(pull_id))).await {
+                if let Err(e) = source_tx.send((reply, event_ids.pull_id(pull_id))).await {
                     error!("{source_ctx} Error sending kafka message to source: {e}");
                     source_ctx.swallow_err(
                         source_ctx.notifier().connection_lost().await,
@@ -910,6 +1679,31 @@ async fn consumer_task(
     }
 }
 
+/// Poll the next item from the given `stream`, waiting for at most `poll_timeout`, if set.
+///
+/// # Errors
+///   * if no item was received within `poll_timeout`
+async fn poll_next<S>(
+    stream: &mut S,
+    poll_timeout: Option<Duration>,
+) -> std::result::Result<Option<S::Item>, async_std::future::TimeoutError>
+where
+    S: futures::Stream + Unpin,
+{
+    if let Some(poll_timeout) = poll_timeout {
+        stream.next().timeout(poll_timeout).await
+    } else {
+        Ok(stream.next().await)
+    }
+}
+
+/// Returns the codec configured for the given `topic`, if any
+///
+/// As every topic partition is its own stream, this is only considered when the stream is created.
+fn codec_overwrite(topic_codecs: &HashMap<String, String>, topic: &str) -> Option<String> {
+    topic_codecs.get(topic).cloned()
+}
+
 #[derive(Clone)]
 struct TopicResolver(IndexMap<String, u64>);
 impl TopicResolver {
@@ -975,9 +1769,30 @@ impl TopicResolver {
 #[cfg(test)]
 mod test {
 
-    use super::{Config, Offset, TopicResolver};
+    use super::{
+        codec_overwrite, commit_driver, final_commit, poll_next, AckCommitter, Builder,
+        CommitPolicy, CommitStoredOffsets, Config, EventIds, KafkaConsumerSource, Offset,
+        OffsetLookup, StartFrom, StartPosition, StatisticsConfig, TopicResolver,
+        TremorConsumerContext,
+    };
+    use crate::config::Reconnect;
+    use crate::connectors::prelude::{
+        Alias, ConfigImpl, ConnectorBuilder, ConnectorConfig, KillSwitch,
+    };
+    use crate::connectors::unit_tests::FakeContext;
     use crate::errors::Result;
+    use async_std::channel::bounded;
+    use async_std::prelude::FutureExt;
+    use async_std::sync::Arc;
     use proptest::prelude::*;
+    use rdkafka::config::ClientConfig;
+    use rdkafka::error::KafkaResult;
+    use rdkafka::{ClientContext, TopicPartitionList};
+    use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+    use std::time::{Duration, Instant};
+    use tremor_pipeline::EventId;
+    use tremor_value::literal;
+    use tremor_value::prelude::*;
 
     fn topics_and_index() -> BoxedStrategy<(Vec<String>, usize)> {
         proptest::collection::hash_set(proptest::string::string_regex(".+").unwrap(), 1..100_usize)
@@ -1041,4 +1856,709 @@ mod test {
         assert_eq!(client_config.get("string"), Some("string"));
         Ok(())
     }
+
+    #[test]
+    fn topic_codecs() -> Result<()> {
+        let mut config = r#"
+        {
+            "topics": ["json_topic", "msgpack_topic", "default_topic"],
+            "brokers": ["broker1"],
+            "group_id": "snot",
+            "mode": "performance",
+            "topic_codecs": {
+                "json_topic": "json",
+                "msgpack_topic": "msgpack"
+            }
+        }
+        "#
+        .as_bytes()
+        .to_vec();
+        let value = tremor_value::parse_to_value(config.as_mut_slice())?;
+        let config: Config = tremor_value::structurize(value)?;
+        assert_eq!(
+            Some("json".to_string()),
+            codec_overwrite(&config.topic_codecs, "json_topic")
+        );
+        assert_eq!(
+            Some("msgpack".to_string()),
+            codec_overwrite(&config.topic_codecs, "msgpack_topic")
+        );
+        assert_eq!(None, codec_overwrite(&config.topic_codecs, "default_topic"));
+        Ok(())
+    }
+
+    #[test]
+    fn fetch_options() -> Result<()> {
+        let alias = Alias::new("flow", "kafka");
+        let mut config = r#"
+        {
+            "topics": ["topic"],
+            "brokers": ["broker1"],
+            "group_id": "snot",
+            "mode": "performance",
+            "fetch_max_bytes": 1048576,
+            "max_partition_fetch_bytes": 65536,
+            "fetch_wait_max_ms": 100,
+            "poll_timeout_ms": 250
+        }
+        "#
+        .as_bytes()
+        .to_vec();
+        let value = tremor_value::parse_to_value(config.as_mut_slice())?;
+        let config: Config = tremor_value::structurize(value)?;
+        let mut client_config = config.mode.to_config()?;
+        config.set_fetch_options(&alias, &mut client_config)?;
+        assert_eq!(client_config.get("fetch.max.bytes"), Some("1048576"));
+        assert_eq!(
+            client_config.get("max.partition.fetch.bytes"),
+            Some("65536")
+        );
+        assert_eq!(client_config.get("fetch.wait.max.ms"), Some("100"));
+
+        // nothing is set if not configured
+        let mut config = r#"
+        {
+            "topics": ["topic"],
+            "brokers": ["broker1"],
+            "group_id": "snot",
+            "mode": "performance"
+        }
+        "#
+        .as_bytes()
+        .to_vec();
+        let value = tremor_value::parse_to_value(config.as_mut_slice())?;
+        let config: Config = tremor_value::structurize(value)?;
+        let mut client_config = ClientConfig::new();
+        config.set_fetch_options(&alias, &mut client_config)?;
+        assert_eq!(client_config.get("fetch.max.bytes"), None);
+        assert_eq!(client_config.get("max.partition.fetch.bytes"), None);
+        assert_eq!(client_config.get("fetch.wait.max.ms"), None);
+        Ok(())
+    }
+
+    #[test]
+    fn assignment_strategy() -> Result<()> {
+        let alias = Alias::new("flow", "kafka");
+        for (strategy, expected) in [
+            ("range", "range"),
+            ("roundrobin", "roundrobin"),
+            ("cooperative-sticky", "cooperative-sticky"),
+        ] {
+            let config = Config::new(&literal!({
+                "topics": ["topic"],
+                "brokers": ["broker1"],
+                "group_id": "snot",
+                "mode": "performance",
+                "assignment_strategy": strategy
+            }))?;
+            let mut client_config = config.mode.to_config()?;
+            config.set_assignment_strategy(&alias, &mut client_config)?;
+            assert_eq!(
+                client_config.get("partition.assignment.strategy"),
+                Some(expected)
+            );
+        }
+
+        // the librdkafka default is kept if not configured
+        let config = Config::new(&literal!({
+            "topics": ["topic"],
+            "brokers": ["broker1"],
+            "group_id": "snot",
+            "mode": "performance"
+        }))?;
+        let mut client_config = config.mode.to_config()?;
+        config.set_assignment_strategy(&alias, &mut client_config)?;
+        assert_eq!(client_config.get("partition.assignment.strategy"), None);
+
+        // unknown strategies are rejected
+        assert!(Config::new(&literal!({
+            "topics": ["topic"],
+            "brokers": ["broker1"],
+            "group_id": "snot",
+            "mode": "performance",
+            "assignment_strategy": "sticky"
+        }))
+        .is_err());
+
+        // conflicting rdkafka options are rejected
+        let config = Config::new(&literal!({
+            "topics": ["topic"],
+            "brokers": ["broker1"],
+            "group_id": "snot",
+            "mode": {
+                "custom": {
+                    "rdkafka_options": {
+                        "partition.assignment.strategy": "range"
+                    }
+                }
+            },
+            "assignment_strategy": "cooperative-sticky"
+        }))?;
+        let mut client_config = config.mode.to_config()?;
+        assert!(config
+            .set_assignment_strategy(&alias, &mut client_config)
+            .is_err());
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn poll_timeout() -> Result<()> {
+        let mut stream = futures::stream::pending::<()>();
+        let start = Instant::now();
+        assert!(poll_next(&mut stream, Some(Duration::from_millis(100)))
+            .await
+            .is_err());
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_millis(100));
+        assert!(elapsed < Duration::from_secs(5));
+
+        let mut stream = futures::stream::iter(vec![1_u8]);
+        assert_eq!(
+            Ok(Some(1)),
+            poll_next(&mut stream, Some(Duration::from_millis(100))).await
+        );
+        assert_eq!(Ok(None), poll_next(&mut stream, None).await);
+        Ok(())
+    }
+
+    #[derive(Default)]
+    struct MockConsumer {
+        commits: AtomicUsize,
+        async_commits: AtomicUsize,
+        delay: Option<Duration>,
+    }
+
+    impl CommitStoredOffsets for MockConsumer {
+        fn commit_stored_offsets(&self) -> KafkaResult<()> {
+            if let Some(delay) = self.delay {
+                std::thread::sleep(delay);
+            }
+            self.commits.fetch_add(1, Ordering::AcqRel);
+            Ok(())
+        }
+
+        fn commit_stored_offsets_async(&self) -> KafkaResult<()> {
+            self.async_commits.fetch_add(1, Ordering::AcqRel);
+            Ok(())
+        }
+    }
+
+    #[async_std::test]
+    async fn commit_on_stop() -> Result<()> {
+        let (tx, _rx) = bounded(1);
+        let ctx = FakeContext::new(tx);
+        let consumer = Arc::new(MockConsumer::default());
+        final_commit(consumer.clone(), Duration::from_secs(1), &ctx).await;
+        assert_eq!(1, consumer.commits.load(Ordering::Acquire));
+
+        // a hanging commit does not block stopping the consumer
+        let consumer = Arc::new(MockConsumer {
+            delay: Some(Duration::from_millis(500)),
+            ..MockConsumer::default()
+        });
+        let start = Instant::now();
+        final_commit(consumer.clone(), Duration::from_millis(50), &ctx).await;
+        assert!(start.elapsed() < Duration::from_millis(500));
+        Ok(())
+    }
+
+    #[test]
+    fn commit_mode_count() {
+        let (tx, _rx) = bounded(1);
+        let ctx = FakeContext::new(tx);
+        let consumer = MockConsumer::default();
+        let mut committer = AckCommitter::new(CommitPolicy::Count(3));
+        committer.offset_stored(&consumer, &ctx);
+        committer.offset_stored(&consumer, &ctx);
+        assert_eq!(0, consumer.async_commits.load(Ordering::Acquire));
+        committer.offset_stored(&consumer, &ctx);
+        assert_eq!(1, consumer.async_commits.load(Ordering::Acquire));
+        for _ in 0..3 {
+            committer.offset_stored(&consumer, &ctx);
+        }
+        assert_eq!(2, consumer.async_commits.load(Ordering::Acquire));
+
+        let consumer = MockConsumer::default();
+        let mut committer = AckCommitter::new(CommitPolicy::OnAck);
+        committer.offset_stored(&consumer, &ctx);
+        committer.offset_stored(&consumer, &ctx);
+        assert_eq!(2, consumer.async_commits.load(Ordering::Acquire));
+
+        // committed by the commit driver or by librdkafka
+        for commit_mode in [CommitPolicy::Interval(1), CommitPolicy::Auto] {
+            let consumer = MockConsumer::default();
+            let mut committer = AckCommitter::new(commit_mode);
+            committer.offset_stored(&consumer, &ctx);
+            assert_eq!(0, consumer.async_commits.load(Ordering::Acquire));
+        }
+    }
+
+    #[async_std::test]
+    async fn commit_mode_interval() -> Result<()> {
+        let (tx, _rx) = bounded(1);
+        let consumer = Arc::new(MockConsumer::default());
+        let (tick_tx, tick_rx) = bounded(1);
+        let driver = async_std::task::spawn(commit_driver(
+            Arc::downgrade(&consumer),
+            tick_rx,
+            FakeContext::new(tx.clone()),
+        ));
+        for _ in 0..3 {
+            tick_tx.send(()).await?;
+        }
+        // the driver is done once the ticks are
+        drop(tick_tx);
+        driver.timeout(Duration::from_secs(5)).await?;
+        assert_eq!(3, consumer.async_commits.load(Ordering::Acquire));
+        assert_eq!(0, consumer.commits.load(Ordering::Acquire));
+
+        // the driver stops with the consumer
+        let (tick_tx, tick_rx) = bounded(1);
+        let driver = async_std::task::spawn(commit_driver(
+            Arc::downgrade(&consumer),
+            tick_rx,
+            FakeContext::new(tx),
+        ));
+        drop(consumer);
+        tick_tx.send(()).await?;
+        driver.timeout(Duration::from_secs(5)).await?;
+        Ok(())
+    }
+
+    #[test]
+    fn commit_on_revoke() {
+        let (tx, _rx) = bounded(1);
+        let ctx = FakeContext::new(tx);
+        let committer = RevokeCommitter::default();
+        // partitions revoked before the consumer is created
+        committer.commit(&ctx);
+
+        let consumer = Arc::new(MockConsumer::default());
+        let weak: Weak<dyn CommitStoredOffsets + Send + Sync> = Arc::downgrade(&consumer);
+        committer.set_consumer(weak);
+        committer.commit(&ctx);
+        assert_eq!(1, consumer.commits.load(Ordering::Acquire));
+        assert_eq!(0, consumer.async_commits.load(Ordering::Acquire));
+
+        let weak: Weak<dyn CommitStoredOffsets + Send + Sync> = Arc::downgrade(&consumer);
+        drop(consumer);
+        committer.set_consumer(weak);
+        committer.commit(&ctx);
+    }
+
+    #[async_std::test]
+    async fn commit_mode_config() -> Result<()> {
+        let alias = Alias::new("flow", "kafka_in");
+        let config = |mode: Value<'static>, commit_mode: Value<'static>| {
+            literal!({
+                "topics": ["topic"],
+                "brokers": ["broker1:9092"],
+                "group_id": "snot",
+                "mode": mode,
+                "commit_mode": commit_mode
+            })
+        };
+        assert_eq!(
+            CommitPolicy::Interval(1000),
+            Config::new(&config(
+                Value::from("performance"),
+                literal!({"interval": 1000})
+            ))?
+            .commit_mode
+        );
+        assert_eq!(
+            CommitPolicy::OnAck,
+            Config::new(&config(Value::from("performance"), Value::from("on_ack")))?.commit_mode
+        );
+
+        let connector_config = ConnectorConfig {
+            connector_type: "kafka_consumer".into(),
+            codec: None,
+            config: None,
+            preprocessors: None,
+            postprocessors: None,
+            reconnect: Reconnect::None,
+            metrics_interval_s: None,
+            max_in_flight: None,
+            in_flight_timeout_ms: None,
+            instance_host: None,
+        };
+        let builder = Builder::default();
+        let cases = [
+            // committing is up to librdkafka in the performance mode
+            (Value::from("performance"), literal!({"count": 10}), false),
+            (
+                literal!({"transactional": {}}),
+                literal!({"count": 0}),
+                false,
+            ),
+            (
+                literal!({"transactional": {}}),
+                literal!({"count": 10}),
+                true,
+            ),
+            (literal!({"transactional": {}}), Value::from("auto"), true),
+        ];
+        for (mode, commit_mode, valid) in cases {
+            let res = builder
+                .build_cfg(
+                    &alias,
+                    &connector_config,
+                    &config(mode, commit_mode),
+                    &KillSwitch::dummy(),
+                )
+                .await;
+            assert_eq!(valid, res.is_ok());
+        }
+
+        let mut client_config = ClientConfig::new();
+        CommitPolicy::Count(10).apply(&mut client_config);
+        assert_eq!(Some("false"), client_config.get("enable.auto.commit"));
+        assert_eq!(Some("false"), client_config.get("enable.auto.offset.store"));
+        Ok(())
+    }
+
+    /// Finds the message at `timestamp * 2` in every partition
+    struct MockLookup;
+
+    impl OffsetLookup for MockLookup {
+        fn offsets_for_times(
+            &self,
+            timestamps: TopicPartitionList,
+            _timeout: Duration,
+        ) -> KafkaResult<TopicPartitionList> {
+            let mut offsets = TopicPartitionList::with_capacity(timestamps.count());
+            for elem in timestamps.elements() {
+                let offset = match elem.offset() {
+                    Offset::Offset(timestamp) => Offset::Offset(timestamp * 2),
+                    other => other,
+                };
+                offsets.add_partition_offset(elem.topic(), elem.partition(), offset)?;
+            }
+            Ok(offsets)
+        }
+    }
+
+    fn assignment(partitions: &[i32]) -> KafkaResult<TopicPartitionList> {
+        let mut tpl = TopicPartitionList::new();
+        for partition in partitions {
+            tpl.add_partition_offset("topic", *partition, Offset::Invalid)?;
+        }
+        Ok(tpl)
+    }
+
+    fn offsets(tpl: &TopicPartitionList) -> Vec<Offset> {
+        tpl.elements().iter().map(|elem| elem.offset()).collect()
+    }
+
+    #[test]
+    fn start_from_config() -> Result<()> {
+        let config = |start_from| {
+            Config::new(&literal!({
+                "topics": ["topic"],
+                "brokers": ["broker1"],
+                "group_id": "snot",
+                "mode": "performance",
+                "start_from": start_from
+            }))
+            .map(|c| c.start_from)
+        };
+        assert_eq!(StartFrom::Beginning, config(literal!("beginning"))?);
+        assert_eq!(StartFrom::End, config(literal!("end"))?);
+        assert_eq!(StartFrom::Offset(42), config(literal!({"offset": 42}))?);
+        assert_eq!(
+            StartFrom::Timestamp(1_000_000),
+            config(literal!({"timestamp": 1_000_000}))?
+        );
+        assert!(config(literal!("snot")).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn start_from_beginning_and_end() -> Result<()> {
+        let start = StartPosition::new(StartFrom::Beginning, None, Duration::from_secs(1));
+        let tpl = assignment(&[0, 1])?;
+        start.apply(&tpl)?;
+        assert_eq!(vec![Offset::Beginning, Offset::Beginning], offsets(&tpl));
+        // partitions assigned again continue from the committed offset
+        let tpl = assignment(&[0, 1, 2])?;
+        start.apply(&tpl)?;
+        assert_eq!(
+            vec![Offset::Invalid, Offset::Invalid, Offset::Beginning],
+            offsets(&tpl)
+        );
+
+        let start = StartPosition::new(StartFrom::End, None, Duration::from_secs(1));
+        let tpl = assignment(&[0])?;
+        start.apply(&tpl)?;
+        assert_eq!(vec![Offset::End], offsets(&tpl));
+        Ok(())
+    }
+
+    #[test]
+    fn start_from_offset() -> Result<()> {
+        let start = StartPosition::new(StartFrom::Offset(42), None, Duration::from_secs(1));
+        let tpl = assignment(&[0, 1])?;
+        start.apply(&tpl)?;
+        assert_eq!(vec![Offset::Offset(42), Offset::Offset(42)], offsets(&tpl));
+
+        let start = StartPosition::new(StartFrom::Committed, None, Duration::from_secs(1));
+        let tpl = assignment(&[0])?;
+        start.apply(&tpl)?;
+        assert_eq!(vec![Offset::Invalid], offsets(&tpl));
+        Ok(())
+    }
+
+    #[test]
+    fn start_from_timestamp() -> Result<()> {
+        let start = StartPosition::new(
+            StartFrom::Timestamp(21_000_000),
+            Some(Box::new(MockLookup)),
+            Duration::from_secs(1),
+        );
+        let tpl = assignment(&[0, 1])?;
+        start.apply(&tpl)?;
+        // the timestamp is looked up in milliseconds
+        assert_eq!(vec![Offset::Offset(42), Offset::Offset(42)], offsets(&tpl));
+        Ok(())
+    }
+
+    #[test]
+    fn start_position_is_kept_across_reconnects() -> Result<()> {
+        let config = Config::new(&literal!({
+            "topics": ["topic"],
+            "brokers": ["broker1"],
+            "group_id": "snot",
+            "mode": "performance",
+            "start_from": "beginning"
+        }))?;
+        let source = KafkaConsumerSource::new(config, ClientConfig::new(), Default::default())?;
+        let connect = || {
+            let (ctx_tx, _ctx_rx) = bounded(1);
+            let (connect_tx, _connect_rx) = bounded(1);
+            let (metrics_tx, _metrics_rx) = async_broadcast::broadcast(1);
+            source.consumer_context(FakeContext::new(ctx_tx), connect_tx, metrics_tx)
+        };
+
+        let first = connect();
+        let tpl = assignment(&[0])?;
+        first
+            .start_position
+            .as_ref()
+            .ok_or("no start position")?
+            .apply(&tpl)?;
+        assert_eq!(vec![Offset::Beginning], offsets(&tpl));
+
+        // after a reconnect the partition continues from the committed offset
+        let second = connect();
+        let tpl = assignment(&[0, 1])?;
+        second
+            .start_position
+            .as_ref()
+            .ok_or("no start position")?
+            .apply(&tpl)?;
+        assert_eq!(vec![Offset::Invalid, Offset::Beginning], offsets(&tpl));
+        Ok(())
+    }
+
+    #[test]
+    fn event_ids_encode_partition_and_offset() {
+        let resolver = TopicResolver::new(vec!["snot".to_string(), "badger".to_string()]);
+        let (stream_id, offset_pull_id) = resolver.resolve_stream_and_pull_ids_inner("snot", 3, 42);
+        let pull_id = EventIds::Offset.pull_id(offset_pull_id).unwrap_or_default();
+        let event_id = EventId::from_id(1, stream_id, pull_id);
+        assert_eq!(Some(42), event_id.get_max_by_stream(1, stream_id));
+        assert_eq!(
+            Some(("snot", 3, Offset::Offset(42))),
+            resolver.resolve_topic(stream_id, pull_id)
+        );
+        // the source manager counts the events
+        assert_eq!(None, EventIds::Counter.pull_id(offset_pull_id));
+    }
+
+    #[async_std::test]
+    async fn counter_event_ids_require_untracked_offsets() -> Result<()> {
+        let alias = Alias::new("flow", "kafka_in");
+        let config = |mode: Value<'static>| {
+            literal!({
+                "topics": ["topic"],
+                "brokers": ["broker1:9092"],
+                "group_id": "snot",
+                "mode": mode,
+                "event_ids": "counter"
+            })
+        };
+        let connector_config = ConnectorConfig {
+            connector_type: "kafka_consumer".into(),
+            codec: None,
+            config: None,
+            preprocessors: None,
+            postprocessors: None,
+            reconnect: Reconnect::None,
+            metrics_interval_s: None,
+            max_in_flight: None,
+            in_flight_timeout_ms: None,
+            instance_host: None,
+        };
+        let builder = Builder::default();
+        let res = builder
+            .build_cfg(
+                &alias,
+                &connector_config,
+                &config(literal!({"transactional": {}})),
+                &KillSwitch::dummy(),
+            )
+            .await;
+        assert!(res.is_err());
+        assert!(builder
+            .build_cfg(
+                &alias,
+                &connector_config,
+                &config(Value::from("performance")),
+                &KillSwitch::dummy(),
+            )
+            .await
+            .is_ok());
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn overwritten_rdkafka_option_error_contains_alias() -> Result<()> {
+        let alias = Alias::new("flow", "kafka_in");
+        let raw_config = literal!({
+            "topics": ["topic"],
+            "brokers": ["broker1:9092"],
+            "group_id": "snot",
+            "mode": {
+                "custom": {
+                    "rdkafka_options": {
+                        "group.id": "badger"
+                    }
+                }
+            }
+        });
+        let connector_config = ConnectorConfig {
+            connector_type: "kafka_consumer".into(),
+            codec: None,
+            config: None,
+            preprocessors: None,
+            postprocessors: None,
+            reconnect: Reconnect::None,
+            metrics_interval_s: None,
+            max_in_flight: None,
+            in_flight_timeout_ms: None,
+            instance_host: None,
+        };
+        let res = Builder::default()
+            .build_cfg(&alias, &connector_config, &raw_config, &KillSwitch::dummy())
+            .await;
+        let e = res.err().map(|e| e.to_string()).unwrap_or_default();
+        assert!(e.contains("flow::kafka_in"), "Missing alias in: {e}");
+        assert!(e.contains("group.id"), "Missing option in: {e}");
+        Ok(())
+    }
+
+    #[test]
+    fn detailed_statistics() -> Result<()> {
+        let (ctx_tx, _ctx_rx) = bounded(1);
+        let (connect_tx, _connect_rx) = bounded(1);
+        let (metrics_tx, mut metrics_rx) = async_broadcast::broadcast(8);
+        let ctx = TremorConsumerContext::consumer(
+            FakeContext::new(ctx_tx),
+            connect_tx,
+            metrics_tx,
+            Arc::new(AtomicU64::new(0)),
+        )
+        .with_statistics(Some(StatisticsConfig {
+            interval_ms: 1000,
+            raw: true,
+        }));
+
+        let stats = br#"{
+            "name": "rdkafka#consumer-1",
+            "client_id": "snot",
+            "type": "consumer",
+            "ts": 5016483227792,
+            "time": 1527060869,
+            "replyq": 3,
+            "brokers": {
+                "localhost:9092/0": {
+                    "name": "localhost:9092/0",
+                    "nodeid": 0,
+                    "source": "learned",
+                    "outbuf_cnt": 1,
+                    "waitresp_cnt": 2,
+                    "rtt": {"min": 100, "max": 900, "avg": 300, "p99": 850}
+                },
+                "GroupCoordinator": {
+                    "name": "GroupCoordinator",
+                    "nodeid": -1,
+                    "source": "internal",
+                    "outbuf_cnt": 0,
+                    "waitresp_cnt": 0
+                }
+            },
+            "topics": {
+                "snot": {
+                    "topic": "snot",
+                    "partitions": {
+                        "0": {"partition": 0, "fetchq_cnt": 10, "fetchq_size": 1000},
+                        "1": {"partition": 1, "fetchq_cnt": 5, "fetchq_size": 500}
+                    }
+                }
+            }
+        }"#;
+        ctx.stats_raw(stats);
+
+        let mut metrics = Vec::new();
+        while let Ok(payload) = metrics_rx.try_recv() {
+            let value = payload.suffix().value();
+            // the abbreviated stats above are not enough for `kafka_consumer_stats`
+            if value.get_str("measurement") != Some("kafka_consumer_stats") {
+                metrics.push(value.clone_static());
+            }
+        }
+        assert_eq!(3, metrics.len());
+        assert_eq!(
+            literal!({
+                "measurement": "kafka_consumer_broker_stats",
+                "tags": {
+                    "connector": "fake::fake",
+                    "broker": "localhost:9092/0"
+                },
+                "fields": {
+                    "rtt_avg": 300,
+                    "rtt_p99": 850,
+                    "outbuf_cnt": 1,
+                    "waitresp_cnt": 2
+                },
+                "timestamp": 1_527_060_869_000_000_000_u64
+            }),
+            metrics[0]
+        );
+        assert_eq!(
+            literal!({
+                "measurement": "kafka_consumer_queue_stats",
+                "tags": {
+                    "connector": "fake::fake"
+                },
+                "fields": {
+                    "replyq": 3,
+                    "fetchq_cnt": 15,
+                    "fetchq_size": 1500
+                },
+                "timestamp": 1_527_060_869_000_000_000_u64
+            }),
+            metrics[1]
+        );
+        assert_eq!(
+            Some("kafka_consumer_raw_stats"),
+            metrics[2].get_str("measurement")
+        );
+        assert_eq!(
+            Some("rdkafka#consumer-1"),
+            metrics[2].get("fields").get("stats").get_str("name")
+        );
+        Ok(())
+    }
 }
@@ -182,7 +182,7 @@ impl ConnectorBuilder for Builder {
     async fn build_cfg(
         &self,
         id: &Alias,
-        _: &ConnectorConfig,
+        connector_config: &ConnectorConfig,
         config: &Value,
         _kill_switch: &KillSwitch,
     ) -> Result<Box<dyn Connector>> {
@@ -192,7 +192,12 @@ impl ConnectorBuilder for Builder {
         }
 
         let (tx, rx) = bounded(crate::QSIZE.load(Ordering::Relaxed));
-        Ok(Box::new(Kv { config, rx, tx }))
+        Ok(Box::new(Kv {
+            config,
+            instance_host: connector_config.instance_host(),
+            rx,
+            tx,
+        }))
     }
 }
 
@@ -201,6 +206,8 @@ impl ConnectorBuilder for Builder {
 /// Receiving commands via its sink and emitting responses to those commands via its source.
 pub(crate) struct Kv {
     config: Config,
+    /// host of the origin URI of responses
+    instance_host: String,
     rx: Receiver<SourceReply>,
     tx: Sender<SourceReply>,
 }
@@ -225,7 +232,7 @@ impl Connector for Kv {
         let codec = Json::default();
         let origin_uri = EventOriginUri {
             scheme: "tremor-kv".to_string(),
-            host: hostname(),
+            host: self.instance_host.clone(),
             port: None,
             path: self
                 .config
@@ -16,7 +16,6 @@ use crate::connectors::prelude::*;
 use async_broadcast::{Receiver, Sender, TrySendError};
 use beef::Cow;
 use tremor_pipeline::{MetricsMsg, METRICS_CHANNEL};
-use tremor_script::utils::hostname;
 
 const MEASUREMENT: Cow<'static, str> = Cow::const_str("measurement");
 const TAGS: Cow<'static, str> = Cow::const_str("tags");
@@ -35,13 +34,16 @@ const TIMESTAMP: Cow<'static, str> = Cow::const_str("timestamp");
 pub(crate) struct MetricsConnector {
     tx: Sender<MetricsMsg>,
     rx: Receiver<MetricsMsg>,
+    /// host of the origin URI of the metrics events
+    instance_host: String,
 }
 
 impl MetricsConnector {
-    pub(crate) fn new() -> Self {
+    pub(crate) fn new(instance_host: String) -> Self {
         Self {
             tx: METRICS_CHANNEL.tx(),
             rx: METRICS_CHANNEL.rx(),
+            instance_host,
         }
     }
 }
@@ -58,10 +60,10 @@ impl ConnectorBuilder for Builder {
     async fn build(
         &self,
         _id: &Alias,
-        _config: &ConnectorConfig,
+        config: &ConnectorConfig,
         _kill_switch: &KillSwitch,
     ) -> Result<Box<dyn Connector>> {
-        Ok(Box::new(MetricsConnector::new()))
+        Ok(Box::new(MetricsConnector::new(config.instance_host())))
     }
 }
 
@@ -76,7 +78,7 @@ impl Connector for MetricsConnector {
         source_context: SourceContext,
         builder: SourceManagerBuilder,
     ) -> Result<Option<SourceAddr>> {
-        let source = MetricsSource::new(self.rx.clone());
+        let source = MetricsSource::new(self.rx.clone(), self.instance_host.clone());
         let addr = builder.spawn(source, source_context)?;
         Ok(Some(addr))
     }
@@ -101,12 +103,12 @@ pub(crate) struct MetricsSource {
 }
 
 impl MetricsSource {
-    pub(crate) fn new(rx: Receiver<MetricsMsg>) -> Self {
+    pub(crate) fn new(rx: Receiver<MetricsMsg>, host: String) -> Self {
         Self {
             rx,
             origin_uri: EventOriginUri {
                 scheme: "tremor-metrics".to_string(),
-                host: hostname(),
+                host,
                 port: None,
                 path: vec![],
             },
@@ -0,0 +1,378 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Aggregates the events it receives via its sink by a configurable key
+//! and periodically emits a summary (count, min, max, mean) per key via its source.
+
+use crate::connectors::prelude::*;
+use async_std::channel::{bounded, Receiver, Sender};
+use async_std::sync::Mutex;
+use async_std::task::{self, JoinHandle};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Config {
+    /// Dot separated path to the field to group events by, e.g. `request.host`.
+    /// If not set, all events are aggregated into a single group.
+    #[serde(default)]
+    pub group_by: Option<String>,
+    /// Dot separated path to the numeric field used for `min`, `max` and `mean`.
+    /// If not set, only events are counted.
+    #[serde(default)]
+    pub value_field: Option<String>,
+    /// Interval in milliseconds after which the aggregated summaries are emitted
+    pub interval_ms: u64,
+}
+
+impl ConfigImpl for Config {}
+
+#[derive(Debug, Default)]
+pub(crate) struct Builder {}
+
+impl Builder {
+    const INVALID_INTERVAL: &'static str = "`interval_ms` must be greater than 0.";
+}
+
+#[async_trait::async_trait]
+impl ConnectorBuilder for Builder {
+    fn connector_type(&self) -> ConnectorType {
+        "metrics_aggregator".into()
+    }
+
+    async fn build_cfg(
+        &self,
+        alias: &Alias,
+        connector_config: &ConnectorConfig,
+        raw: &Value,
+        _kill_switch: &KillSwitch,
+    ) -> Result<Box<dyn Connector>> {
+        let config = Config::new(raw)?;
+        if config.interval_ms == 0 {
+            return Err(err_connector_def(alias, Self::INVALID_INTERVAL));
+        }
+        let origin_uri = EventOriginUri {
+            scheme: "tremor-metrics-aggregator".to_string(),
+            host: connector_config.instance_host(),
+            port: None,
+            path: vec![config.interval_ms.to_string()],
+        };
+        let (tx, rx) = bounded(crate::QSIZE.load(Ordering::Relaxed));
+        Ok(Box::new(MetricsAggregator {
+            config,
+            origin_uri,
+            tx,
+            rx,
+        }))
+    }
+}
+
+/// Receives events via its sink and emits aggregated summaries via its source.
+pub(crate) struct MetricsAggregator {
+    config: Config,
+    origin_uri: EventOriginUri,
+    tx: Sender<SourceReply>,
+    rx: Receiver<SourceReply>,
+}
+
+#[async_trait::async_trait]
+impl Connector for MetricsAggregator {
+    async fn create_source(
+        &mut self,
+        source_context: SourceContext,
+        builder: SourceManagerBuilder,
+    ) -> Result<Option<SourceAddr>> {
+        let source = ChannelSource::from_channel(self.tx.clone(), self.rx.clone());
+        builder.spawn(source, source_context).map(Some)
+    }
+
+    async fn create_sink(
+        &mut self,
+        sink_context: SinkContext,
+        builder: SinkManagerBuilder,
+    ) -> Result<Option<SinkAddr>> {
+        let sink = MetricsAggregatorSink {
+            aggregator: Arc::new(Mutex::new(Aggregator::new(&self.config))),
+            interval: Duration::from_millis(self.config.interval_ms),
+            origin_uri: self.origin_uri.clone(),
+            tx: self.tx.clone(),
+            flush_task: None,
+        };
+        builder.spawn(sink, sink_context).map(Some)
+    }
+
+    fn codec_requirements(&self) -> CodecReq {
+        CodecReq::Structured
+    }
+}
+
+/// Statistics of a single group
+#[derive(Debug, Default, Clone, PartialEq)]
+struct Stats {
+    count: u64,
+    // number of events that carried a numeric value
+    values: u64,
+    sum: f64,
+    min: Option<f64>,
+    max: Option<f64>,
+}
+
+impl Stats {
+    fn record(&mut self, value: Option<f64>) {
+        self.count += 1;
+        if let Some(value) = value {
+            self.values += 1;
+            self.sum += value;
+            self.min = Some(self.min.map_or(value, |min| min.min(value)));
+            self.max = Some(self.max.map_or(value, |max| max.max(value)));
+        }
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    fn mean(&self) -> Option<f64> {
+        (self.values > 0).then(|| self.sum / self.values as f64)
+    }
+}
+
+fn split_path(path: &str) -> Vec<String> {
+    path.split('.').map(ToString::to_string).collect()
+}
+
+fn get_path<'value, 'event>(
+    value: &'value Value<'event>,
+    path: &[String],
+) -> Option<&'value Value<'event>> {
+    path.iter().try_fold(value, |v, key| v.get(key.as_str()))
+}
+
+/// Keeps the statistics of all groups seen since the last flush
+#[derive(Debug)]
+struct Aggregator {
+    group_by: Option<Vec<String>>,
+    value_field: Option<Vec<String>>,
+    groups: BTreeMap<String, Stats>,
+}
+
+impl Aggregator {
+    fn new(config: &Config) -> Self {
+        Self {
+            group_by: config.group_by.as_deref().map(split_path),
+            value_field: config.value_field.as_deref().map(split_path),
+            groups: BTreeMap::new(),
+        }
+    }
+
+    fn group_of(&self, value: &Value) -> String {
+        self.group_by.as_ref().map_or_else(String::new, |path| {
+            get_path(value, path).map_or_else(
+                || "null".to_string(),
+                |v| v.as_str().map_or_else(|| v.encode(), ToString::to_string),
+            )
+        })
+    }
+
+    fn record(&mut self, value: &Value) {
+        let group = self.group_of(value);
+        let number = self
+            .value_field
+            .as_ref()
+            .and_then(|path| get_path(value, path))
+            .and_then(|v| v.cast_f64());
+        self.groups.entry(group).or_default().record(number);
+    }
+
+    /// Takes the summaries of all groups, resetting the aggregator
+    fn flush(&mut self) -> Vec<Value<'static>> {
+        std::mem::take(&mut self.groups)
+            .into_iter()
+            .map(|(group, stats)| {
+                literal!({
+                    "group": group,
+                    "count": stats.count,
+                    "min": stats.min,
+                    "max": stats.max,
+                    "mean": stats.mean(),
+                })
+            })
+            .collect()
+    }
+}
+
+struct MetricsAggregatorSink {
+    aggregator: Arc<Mutex<Aggregator>>,
+    interval: Duration,
+    origin_uri: EventOriginUri,
+    tx: Sender<SourceReply>,
+    flush_task: Option<JoinHandle<()>>,
+}
+
+#[async_trait::async_trait]
+impl Sink for MetricsAggregatorSink {
+    async fn on_event(
+        &mut self,
+        _input: &str,
+        event: Event,
+        _ctx: &SinkContext,
+        _serializer: &mut EventSerializer,
+        _start: u64,
+    ) -> Result<SinkReply> {
+        let mut aggregator = self.aggregator.lock().await;
+        for value in event.value_iter() {
+            aggregator.record(value);
+        }
+        Ok(SinkReply::NONE)
+    }
+
+    async fn on_start(&mut self, ctx: &SinkContext) -> Result<()> {
+        if let Some(previous_task) = self.flush_task.take() {
+            previous_task.cancel().await;
+        }
+        let aggregator = self.aggregator.clone();
+        let interval = self.interval;
+        let origin_uri = self.origin_uri.clone();
+        let tx = self.tx.clone();
+        let ctx = ctx.clone();
+        self.flush_task = Some(task::spawn(async move {
+            loop {
+                task::sleep(interval).await;
+                let summaries = aggregator.lock().await.flush();
+                for summary in summaries {
+                    let reply = SourceReply::Structured {
+                        origin_uri: origin_uri.clone(),
+                        payload: (summary, Value::object()).into(),
+                        stream: DEFAULT_STREAM_ID,
+                        port: None,
+                    };
+                    if let Err(e) = tx.send(reply).await {
+                        error!("{ctx} Error sending aggregated metrics: {e}");
+                        return;
+                    }
+                }
+            }
+        }));
+        Ok(())
+    }
+
+    async fn on_stop(&mut self, _ctx: &SinkContext) -> Result<()> {
+        if let Some(flush_task) = self.flush_task.take() {
+            flush_task.cancel().await;
+        }
+        Ok(())
+    }
+
+    fn auto_ack(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn aggregator(group_by: Option<&str>, value_field: Option<&str>) -> Aggregator {
+        Aggregator::new(&Config {
+            group_by: group_by.map(ToString::to_string),
+            value_field: value_field.map(ToString::to_string),
+            interval_ms: 1000,
+        })
+    }
+
+    #[test]
+    fn counting() {
+        let mut aggregator = aggregator(None, None);
+        aggregator.record(&literal!({"snot": "badger"}));
+        aggregator.record(&literal!(42));
+        aggregator.record(&literal!(null));
+
+        let summaries = aggregator.flush();
+        assert_eq!(
+            vec![literal!({
+                "group": "",
+                "count": 3,
+                "min": null,
+                "max": null,
+                "mean": null,
+            })],
+            summaries
+        );
+        // flushing resets the aggregator
+        assert!(aggregator.flush().is_empty());
+    }
+
+    #[test]
+    fn grouping() {
+        let mut aggregator = aggregator(Some("req.host"), Some("req.duration"));
+        aggregator.record(&literal!({"req": {"host": "snot", "duration": 1}}));
+        aggregator.record(&literal!({"req": {"host": "snot", "duration": 3.5}}));
+        aggregator.record(&literal!({"req": {"host": "badger", "duration": 2}}));
+        aggregator.record(&literal!({"req": {"host": "badger"}}));
+        aggregator.record(&literal!({"req": {"duration": 7}}));
+
+        let summaries = aggregator.flush();
+        assert_eq!(
+            vec![
+                literal!({
+                    "group": "badger",
+                    "count": 2,
+                    "min": 2.0,
+                    "max": 2.0,
+                    "mean": 2.0,
+                }),
+                literal!({
+                    "group": "null",
+                    "count": 1,
+                    "min": 7.0,
+                    "max": 7.0,
+                    "mean": 7.0,
+                }),
+                literal!({
+                    "group": "snot",
+                    "count": 2,
+                    "min": 1.0,
+                    "max": 3.5,
+                    "mean": 2.25,
+                }),
+            ],
+            summaries
+        );
+    }
+
+    #[test]
+    fn non_string_groups() {
+        let mut aggregator = aggregator(Some("code"), None);
+        aggregator.record(&literal!({"code": 200}));
+        aggregator.record(&literal!({"code": 200}));
+        aggregator.record(&literal!({"code": 404}));
+
+        let groups: Vec<_> = aggregator
+            .flush()
+            .iter()
+            .map(|s| {
+                (
+                    s.get_str("group").map(ToString::to_string),
+                    s.get_u64("count"),
+                )
+            })
+            .collect();
+        assert_eq!(
+            vec![
+                (Some("200".to_string()), Some(2)),
+                (Some("404".to_string()), Some(1))
+            ],
+            groups
+        );
+    }
+}
@@ -22,6 +22,9 @@ use tremor_common::time::nanotime;
 pub(crate) struct Config {
     /// Interval in nanoseconds
     pub interval: u64,
+    /// The value of the emitted events, instead of the connector, ingest time and id
+    #[serde(default)]
+    pub payload: Option<simd_json::OwnedValue>,
 }
 
 impl ConfigImpl for Config {}
@@ -38,14 +41,14 @@ impl ConnectorBuilder for Builder {
     async fn build_cfg(
         &self,
         _: &Alias,
-        _: &ConnectorConfig,
+        connector_config: &ConnectorConfig,
         raw: &Value,
         _kill_switch: &KillSwitch,
     ) -> Result<Box<dyn Connector>> {
         let config = Config::new(raw)?;
         let origin_uri = EventOriginUri {
             scheme: "tremor-metronome".to_string(),
-            host: hostname(),
+            host: connector_config.instance_host(),
             port: None,
             path: vec![config.interval.to_string()],
         };
@@ -53,6 +56,9 @@ impl ConnectorBuilder for Builder {
         Ok(Box::new(Metronome {
             interval: config.interval,
             origin_uri,
+            payload: config
+                .payload
+                .map(|payload| Value::from(payload).into_static()),
         }))
     }
 }
@@ -61,6 +67,7 @@ impl ConnectorBuilder for Builder {
 pub(crate) struct Metronome {
     interval: u64,
     origin_uri: EventOriginUri,
+    payload: Option<Value<'static>>,
 }
 
 #[async_trait::async_trait()]
@@ -74,7 +81,8 @@ impl Connector for Metronome {
         source_context: SourceContext,
         builder: SourceManagerBuilder,
     ) -> Result<Option<SourceAddr>> {
-        let source = MetronomeSource::new(self.interval, self.origin_uri.clone());
+        let source =
+            MetronomeSource::new(self.interval, self.origin_uri.clone(), self.payload.clone());
         builder.spawn(source, source_context).map(Some)
     }
 }
@@ -83,15 +91,17 @@ struct MetronomeSource {
     interval_ns: u64,
     next: u64,
     origin_uri: EventOriginUri,
+    payload: Option<Value<'static>>,
     id: u64,
 }
 
 impl MetronomeSource {
-    fn new(interval_ns: u64, origin_uri: EventOriginUri) -> Self {
+    fn new(interval_ns: u64, origin_uri: EventOriginUri, payload: Option<Value<'static>>) -> Self {
         Self {
             interval_ns,
             next: nanotime() + interval_ns, // dummy placeholer
             origin_uri,
+            payload,
             id: 0,
         }
     }
@@ -103,7 +113,7 @@ impl Source for MetronomeSource {
         self.next = nanotime() + self.interval_ns;
         Ok(true)
     }
-    async fn pull_data(&mut self, pull_id: &mut u64, _ctx: &SourceContext) -> Result<SourceReply> {
+    async fn pull_data(&mut self, pull_id: &mut u64, ctx: &SourceContext) -> Result<SourceReply> {
         let now = nanotime();
         // we need to wait here before we continue to fulfill the interval conditions
         if now < self.next {
@@ -112,14 +122,21 @@ impl Source for MetronomeSource {
         self.next += self.interval_ns;
         *pull_id = self.id;
         self.id += 1;
-        let data = literal!({
-            "connector": "metronome",
-            "ingest_ns": now,
-            "id": *pull_id
+        let data = self.payload.clone().unwrap_or_else(|| {
+            literal!({
+                "connector": "metronome",
+                "ingest_ns": now,
+                "id": *pull_id
+            })
         });
+        // the tick and the time it was emitted at are available with every payload
+        let meta = ctx.meta(literal!({
+            "tick": *pull_id,
+            "ts": nanotime()
+        }));
         Ok(SourceReply::Structured {
             origin_uri: self.origin_uri.clone(),
-            payload: data.into(),
+            payload: (data, meta).into(),
             stream: DEFAULT_STREAM_ID,
             port: None,
         })
@@ -151,6 +168,9 @@ mod tests {
             postprocessors: None,
             reconnect: Reconnect::None,
             metrics_interval_s: Some(5),
+            max_in_flight: None,
+            in_flight_timeout_ms: None,
+            instance_host: None,
         };
         let kill_switch = KillSwitch::dummy();
         assert!(matches!(
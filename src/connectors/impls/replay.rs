@@ -0,0 +1,235 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Replays recorded events with their original spacing
+//!
+//! Reads a file with one `{"ts": <nanoseconds>, "event": <event>}` record per line
+//! and emits the events spaced according to the differences between their `ts`,
+//! divided by `speed`. The runtime is stopped once the file has been replayed, like for the `cb` source.
+
+use std::{path::PathBuf, time::Duration};
+
+use crate::connectors::prelude::*;
+use crate::system::{KillSwitch, ShutdownMode};
+use async_std::io::prelude::BufReadExt;
+use async_std::stream::StreamExt;
+use async_std::{fs::File, io};
+use tremor_common::asy::file::open;
+use tremor_common::time::nanotime;
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Config {
+    /// path to the file with the recorded events
+    path: PathBuf,
+    /// factor by which the replay is faster than the recording, e.g. `2.0` for replaying at double speed
+    #[serde(default = "default_speed")]
+    speed: f64,
+}
+
+fn default_speed() -> f64 {
+    1.0
+}
+
+impl ConfigImpl for Config {}
+
+#[derive(Debug, Default)]
+pub(crate) struct Builder {}
+
+impl Builder {
+    const INVALID_SPEED: &'static str = "`speed` must be greater than 0.";
+}
+
+#[async_trait::async_trait()]
+impl ConnectorBuilder for Builder {
+    fn connector_type(&self) -> ConnectorType {
+        "replay".into()
+    }
+
+    async fn build_cfg(
+        &self,
+        alias: &Alias,
+        connector_config: &ConnectorConfig,
+        raw: &Value,
+        kill_switch: &KillSwitch,
+    ) -> Result<Box<dyn Connector>> {
+        let config = Config::new(raw)?;
+        if !(config.speed.is_finite() && config.speed > 0.0) {
+            return Err(err_connector_def(alias, Self::INVALID_SPEED));
+        }
+        Ok(Box::new(Replay {
+            config,
+            instance_host: connector_config.instance_host(),
+            kill_switch: kill_switch.clone(),
+        }))
+    }
+}
+
+pub(crate) struct Replay {
+    config: Config,
+    /// host of the origin URI of the replayed events
+    instance_host: String,
+    kill_switch: KillSwitch,
+}
+
+#[async_trait::async_trait()]
+impl Connector for Replay {
+    fn codec_requirements(&self) -> CodecReq {
+        CodecReq::Structured
+    }
+
+    async fn create_source(
+        &mut self,
+        source_context: SourceContext,
+        builder: SourceManagerBuilder,
+    ) -> Result<Option<SourceAddr>> {
+        let source = ReplaySource::new(
+            &self.config,
+            self.instance_host.clone(),
+            self.kill_switch.clone(),
+        )
+        .await?;
+        builder.spawn(source, source_context).map(Some)
+    }
+}
+
+/// Maps recorded timestamps to the wall clock
+#[derive(Debug)]
+struct Schedule {
+    speed: f64,
+    /// the first recorded timestamp and when it was replayed
+    start: Option<(u64, u64)>,
+}
+
+impl Schedule {
+    fn new(speed: f64) -> Self {
+        Self { speed, start: None }
+    }
+
+    /// nanoseconds from `now` until the event recorded at `ts` is due
+    #[allow(
+        clippy::cast_precision_loss,
+        clippy::cast_sign_loss,
+        clippy::cast_possible_truncation
+    )]
+    fn delay(&mut self, ts: u64, now: u64) -> u64 {
+        let (first_ts, started) = *self.start.get_or_insert((ts, now));
+        let offset = (ts.saturating_sub(first_ts) as f64 / self.speed) as u64;
+        started.saturating_add(offset).saturating_sub(now)
+    }
+}
+
+struct ReplaySource {
+    file: io::Lines<io::BufReader<File>>,
+    schedule: Schedule,
+    /// the next event to replay, with its recorded timestamp
+    next: Option<(u64, Value<'static>)>,
+    finished: bool,
+    origin_uri: EventOriginUri,
+    kill_switch: KillSwitch,
+}
+
+impl ReplaySource {
+    async fn new(config: &Config, host: String, kill_switch: KillSwitch) -> Result<Self> {
+        let file = open(&config.path).await?;
+        Ok(Self {
+            file: io::BufReader::new(file).lines(),
+            schedule: Schedule::new(config.speed),
+            next: None,
+            finished: false,
+            origin_uri: EventOriginUri {
+                scheme: String::from("tremor-replay"),
+                host,
+                path: vec![config.path.display().to_string()],
+                ..EventOriginUri::default()
+            },
+            kill_switch,
+        })
+    }
+
+    /// extracts the recorded timestamp and the event from a line
+    fn parse(line: String) -> Result<(u64, Value<'static>)> {
+        let mut line = line.into_bytes();
+        let mut record = tremor_value::parse_to_value(&mut line)?.into_static();
+        let ts = record
+            .get_u64("ts")
+            .ok_or_else(|| Error::from("Missing or invalid `ts`"))?;
+        let event = record
+            .remove("event")?
+            .ok_or_else(|| Error::from("Missing `event`"))?;
+        Ok((ts, event))
+    }
+
+    /// reads the next valid record, skipping invalid lines
+    async fn read_next(&mut self, ctx: &SourceContext) -> Result<Option<(u64, Value<'static>)>> {
+        while let Some(line) = self.file.next().await {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            match Self::parse(line) {
+                Ok(record) => return Ok(Some(record)),
+                Err(e) => warn!("{ctx} Skipping invalid record: {e}"),
+            }
+        }
+        Ok(None)
+    }
+}
+
+#[async_trait::async_trait()]
+impl Source for ReplaySource {
+    async fn pull_data(&mut self, _pull_id: &mut u64, ctx: &SourceContext) -> Result<SourceReply> {
+        if self.next.is_none() && !self.finished {
+            self.next = self.read_next(ctx).await?;
+        }
+        // the event is only taken after waiting, as this future is dropped
+        // when the source manager receives control plane messages
+        if let Some((ts, _)) = self.next.as_ref() {
+            let delay = self.schedule.delay(*ts, nanotime());
+            if delay > 0 {
+                async_std::task::sleep(Duration::from_nanos(delay)).await;
+            }
+        }
+        if let Some((ts, event)) = self.next.take() {
+            Ok(SourceReply::Structured {
+                origin_uri: self.origin_uri.clone(),
+                payload: (event, ctx.meta(literal!({ "ts": ts }))).into(),
+                stream: DEFAULT_STREAM_ID,
+                port: None,
+            })
+        } else if self.finished {
+            let kill_switch = self.kill_switch.clone();
+            async_std::task::spawn::<_, Result<()>>(async move {
+                kill_switch.stop(ShutdownMode::Graceful).await?;
+                Ok(())
+            });
+            Ok(SourceReply::Finished)
+        } else {
+            self.finished = true;
+            Ok(SourceReply::EndStream {
+                stream: DEFAULT_STREAM_ID,
+                origin_uri: self.origin_uri.clone(),
+                meta: None,
+            })
+        }
+    }
+
+    fn is_transactional(&self) -> bool {
+        false
+    }
+
+    fn asynchronous(&self) -> bool {
+        false
+    }
+}
@@ -85,7 +85,7 @@ impl ConnectorBuilder for Builder {
     async fn build_cfg(
         &self,
         _: &Alias,
-        _: &ConnectorConfig,
+        connector_config: &ConnectorConfig,
         config: &Value,
         _kill_switch: &KillSwitch,
     ) -> Result<Box<dyn Connector>> {
@@ -95,6 +95,7 @@ impl ConnectorBuilder for Builder {
         Ok(Box::new(S3SourceConnector {
             handles: Vec::with_capacity(config.max_connections),
             config,
+            instance_host: connector_config.instance_host(),
             tx: None,
         }))
     }
@@ -102,6 +103,8 @@ impl ConnectorBuilder for Builder {
 
 struct S3SourceConnector {
     config: S3SourceConfig,
+    /// host of the origin URI of the events read
+    instance_host: String,
     tx: Option<Sender<SourceReply>>,
     handles: Vec<JoinHandle<Result<()>>>,
 }
@@ -162,7 +165,7 @@ impl Connector for S3SourceConnector {
                 .ok_or_else(|| ErrorKind::S3Error("source sender not initialized".to_string()))?;
             let origin_uri = EventOriginUri {
                 scheme: URL_SCHEME.to_string(),
-                host: hostname(),
+                host: self.instance_host.clone(),
                 port: None,
                 path: vec![bucket.clone()],
             };
@@ -12,7 +12,6 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 use crate::connectors::prelude::*;
-use crate::utils::hostname;
 use async_broadcast::{broadcast, Receiver};
 use async_std::io::{stderr, stdin, stdout, ReadExt, Stderr, Stdout};
 use beef::Cow;
@@ -49,7 +48,10 @@ lazy_static! {
 }
 
 /// connector handling 1 std stream (stdout, stderr or stdin)
-pub(crate) struct StdStreamConnector {}
+pub(crate) struct StdStreamConnector {
+    /// host of the origin URI of events read from stdin
+    instance_host: String,
+}
 
 #[derive(Debug, Default)]
 pub(crate) struct Builder {}
@@ -62,10 +64,12 @@ impl ConnectorBuilder for Builder {
     async fn build(
         &self,
         _id: &Alias,
-        _raw_config: &ConnectorConfig,
+        raw_config: &ConnectorConfig,
         _kill_switch: &KillSwitch,
     ) -> Result<Box<dyn Connector>> {
-        Ok(Box::new(StdStreamConnector {}))
+        Ok(Box::new(StdStreamConnector {
+            instance_host: raw_config.instance_host(),
+        }))
     }
 }
 
@@ -77,12 +81,12 @@ pub(crate) struct StdStreamSource {
 }
 
 impl StdStreamSource {
-    fn new() -> Self {
+    fn new(host: String) -> Self {
         Self {
             stdin: None,
             origin_uri: EventOriginUri {
                 scheme: "tremor-stdin".to_string(),
-                host: hostname(),
+                host,
                 port: None,
                 path: vec![],
             },
@@ -204,7 +208,7 @@ impl Connector for StdStreamConnector {
         source_context: SourceContext,
         builder: SourceManagerBuilder,
     ) -> Result<Option<SourceAddr>> {
-        let source = StdStreamSource::new();
+        let source = StdStreamSource::new(self.instance_host.clone());
         builder.spawn(source, source_context).map(Some)
     }
 
@@ -236,7 +240,9 @@ mod test {
     }
     #[test]
     fn connector_consts() {
-        let connector = StdStreamConnector {};
+        let connector = StdStreamConnector {
+            instance_host: "snot".to_string(),
+        };
         assert_eq!(connector.codec_requirements(), CodecReq::Required);
         assert_eq!(connector.input_ports(), ["in", "stdout", "stderr"]);
     }
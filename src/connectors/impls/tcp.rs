@@ -189,7 +189,13 @@ where
         Ok(())
     }
     async fn on_done(&mut self, _stream: u64) -> Result<StreamDone> {
-        self.underlying_stream.shutdown(std::net::Shutdown::Write)?;
+        // flush what the wrapped stream buffered, for TLS this also sends the `close_notify`
+        self.wrapped_stream.close().await?;
+        match self.underlying_stream.shutdown(std::net::Shutdown::Write) {
+            // closing the wrapped stream might have shut down the socket already
+            Err(e) if e.kind() == std::io::ErrorKind::NotConnected => {}
+            res => res?,
+        }
         Ok(StreamDone::StreamClosed)
     }
 }
@@ -19,17 +19,29 @@
 #![allow(clippy::module_name_repetitions)]
 
 use super::TcpReader;
+use crate::connectors::utils::diagnostics::{ConnectDiagnostic, ConnectStep, ConnectStepExt};
+use crate::connectors::utils::dns::DnsCache;
+use crate::connectors::utils::frame_limit::{frame_separator, FrameLimitReader, FrameOverflow};
+use crate::connectors::utils::proxy::ProxyConfig;
 use crate::connectors::utils::tls::{tls_client_connector, TLSClientConfig};
 use crate::{connectors::prelude::*, errors::err_connector_def};
 use async_std::channel::{bounded, Receiver, Sender};
 use async_std::net::TcpStream;
 use async_std::prelude::*;
 use async_tls::TlsConnector;
+use beef::Cow;
 use either::Either;
 use futures::io::AsyncReadExt;
+use std::num::NonZeroUsize;
+use std::time::Duration;
 
 const URL_SCHEME: &str = "tremor-tcp-client";
 
+/// Port reports of failed connection attempts are sent to if `connect_diagnostics` is enabled
+const STATUS: Cow<'static, str> = Cow::const_str("status");
+const OUT_PORTS: [Cow<'static, str>; 3] = [OUT, ERR, STATUS];
+const OUT_PORTS_REF: &[Cow<'static, str>; 3] = &OUT_PORTS;
+
 #[derive(Deserialize, Debug, Clone)]
 #[serde(deny_unknown_fields)]
 pub(crate) struct Config {
@@ -42,6 +54,28 @@ pub(crate) struct Config {
     buf_size: usize,
     #[serde(with = "either::serde_untagged_optional", default = "Default::default")]
     tls: Option<Either<TLSClientConfig, bool>>,
+    /// keep the raw bytes of each event, before decoding, in the event metadata under `raw`
+    #[serde(default = "default_false")]
+    keep_raw: bool,
+    /// maximum length in bytes of a line, lines are not limited if not set
+    #[serde(default)]
+    max_frame_len: Option<usize>,
+    /// what to do with a line exceeding `max_frame_len`, either `"disconnect"` or `"error"`
+    #[serde(default)]
+    on_frame_overflow: FrameOverflow,
+    /// capacity of the internal channels, overriding the global queue size if set
+    #[serde(default)]
+    queue_size: Option<NonZeroUsize>,
+    /// SOCKS5 or HTTP proxy to tunnel the connection through
+    #[serde(default)]
+    proxy: Option<ProxyConfig>,
+    /// emit which step of connecting failed, and why, on the `status` port
+    #[serde(default = "default_false")]
+    connect_diagnostics: bool,
+    /// milliseconds for which the resolved addresses of `url` are reused, until connecting to them fails.
+    /// The host is resolved again on every connect if not set.
+    #[serde(default)]
+    dns_cache_ttl_ms: Option<u64>,
 }
 
 impl ConfigImpl for Config {}
@@ -50,6 +84,8 @@ pub(crate) struct TcpClient {
     config: Config,
     tls_connector: Option<TlsConnector>,
     tls_domain: Option<String>,
+    /// separator of the lines limited by `max_frame_len`
+    frame_separator: u8,
     source_tx: Sender<SourceReply>,
     source_rx: Receiver<SourceReply>,
 }
@@ -69,7 +105,7 @@ impl ConnectorBuilder for Builder {
     async fn build_cfg(
         &self,
         id: &Alias,
-        _: &ConnectorConfig,
+        connector_config: &ConnectorConfig,
         config: &Value,
         _kill_switch: &KillSwitch,
     ) -> Result<Box<dyn Connector>> {
@@ -95,11 +131,12 @@ impl ConnectorBuilder for Builder {
             ),
             Some(Either::Right(false)) | None => (None, None),
         };
-        let (source_tx, source_rx) = bounded(crate::QSIZE.load(Ordering::Relaxed));
+        let (source_tx, source_rx) = bounded(queue_size(config.queue_size));
         Ok(Box::new(TcpClient {
             config,
             tls_connector,
             tls_domain,
+            frame_separator: frame_separator(connector_config),
             source_tx,
             source_rx,
         }))
@@ -108,6 +145,14 @@ impl ConnectorBuilder for Builder {
 
 #[async_trait::async_trait()]
 impl Connector for TcpClient {
+    fn output_ports(&self) -> &[Cow<'static, str>] {
+        if self.config.connect_diagnostics {
+            OUT_PORTS_REF
+        } else {
+            &OUT_PORTS_REF[..2]
+        }
+    }
+
     async fn create_sink(
         &mut self,
         sink_context: SinkContext,
@@ -118,11 +163,16 @@ impl Connector for TcpClient {
                 tls_connector.clone(),
                 self.tls_domain.clone(),
                 self.config.clone(),
+                self.frame_separator,
                 self.source_tx.clone(),
             );
             builder.spawn(sink, sink_context).map(Some)
         } else {
-            let sink = TcpClientSink::plain(self.config.clone(), self.source_tx.clone());
+            let sink = TcpClientSink::plain(
+                self.config.clone(),
+                self.frame_separator,
+                self.source_tx.clone(),
+            );
             builder.spawn(sink, sink_context).map(Some)
         }
     }
@@ -133,7 +183,8 @@ impl Connector for TcpClient {
         builder: SourceManagerBuilder,
     ) -> Result<Option<SourceAddr>> {
         // this source is wired up to the ending channel that is forwarding data received from the TCP (or TLS) connection
-        let source = ChannelSource::from_channel(self.source_tx.clone(), self.source_rx.clone());
+        let source = ChannelSource::from_channel(self.source_tx.clone(), self.source_rx.clone())
+            .with_keep_raw(self.config.keep_raw);
         builder.spawn(source, source_context).map(Some)
     }
 
@@ -147,6 +198,7 @@ struct TcpClientSink {
     tls_connector: Option<TlsConnector>,
     tls_domain: Option<String>,
     config: Config,
+    frame_separator: u8,
     wrapped_stream: Option<
         Box<
             dyn futures::io::AsyncWrite
@@ -157,37 +209,67 @@ struct TcpClientSink {
     >,
     tcp_stream: Option<TcpStream>,
     source_runtime: ChannelSourceRuntime,
+    source_tx: Sender<SourceReply>,
+    dns_cache: DnsCache,
 }
 
 impl TcpClientSink {
-    fn plain(config: Config, source_tx: Sender<SourceReply>) -> Self {
-        let source_runtime = ChannelSourceRuntime::new(source_tx);
+    fn plain(config: Config, frame_separator: u8, source_tx: Sender<SourceReply>) -> Self {
+        let source_runtime = ChannelSourceRuntime::new(source_tx.clone());
+        let dns_cache = DnsCache::new(config.dns_cache_ttl_ms.map(Duration::from_millis));
         Self {
             tls_connector: None,
             tls_domain: None,
             config,
+            frame_separator,
             wrapped_stream: None,
             tcp_stream: None,
             source_runtime,
+            source_tx,
+            dns_cache,
         }
     }
     fn tls(
         tls_connector: TlsConnector,
         tls_domain: Option<String>,
         config: Config,
+        frame_separator: u8,
         source_tx: Sender<SourceReply>,
     ) -> Self {
-        let source_runtime = ChannelSourceRuntime::new(source_tx);
+        let source_runtime = ChannelSourceRuntime::new(source_tx.clone());
+        let dns_cache = DnsCache::new(config.dns_cache_ttl_ms.map(Duration::from_millis));
         Self {
             tls_connector: Some(tls_connector),
             tls_domain,
             config,
+            frame_separator,
             wrapped_stream: None,
             tcp_stream: None,
             source_runtime,
+            source_tx,
+            dns_cache,
         }
     }
 
+    /// emit the report of a failed connection attempt on the `status` port
+    async fn report(&self, diagnostic: &ConnectDiagnostic) -> Result<()> {
+        let origin_uri = EventOriginUri {
+            scheme: URL_SCHEME.to_string(),
+            host: self.config.url.host_or_local().to_string(),
+            port: self.config.url.port(),
+            path: vec![],
+        };
+        self.source_tx
+            .send(SourceReply::Structured {
+                origin_uri,
+                payload: (diagnostic.to_value(), Value::object()).into(),
+                stream: DEFAULT_STREAM_ID,
+                port: Some(STATUS),
+            })
+            .await?;
+        Ok(())
+    }
+
     /// writing to the client socket
     async fn write(&mut self, data: Vec<Vec<u8>>) -> Result<()> {
         let stream = self
@@ -200,19 +282,20 @@ impl TcpClientSink {
         }
         Ok(())
     }
-}
 
-#[async_trait::async_trait()]
-impl Sink for TcpClientSink {
-    async fn connect(&mut self, ctx: &SinkContext, _attempt: &Attempt) -> Result<bool> {
+    /// connect the TCP stream and do the TLS handshake if configured
+    async fn establish(&mut self, ctx: &SinkContext) -> Result<bool> {
         let buf_size = self.config.buf_size;
 
         // connect TCP stream
-        let stream = TcpStream::connect((
-            self.config.url.host_or_local(),
-            self.config.url.port_or_dflt(),
-        ))
-        .await?;
+        let host = self.config.url.host_or_local();
+        let port = self.config.url.port_or_dflt();
+        let endpoint = format!("{host}:{port}");
+        let stream = if let Some(proxy) = self.config.proxy.as_ref() {
+            proxy.connect(host, port).await?
+        } else {
+            self.dns_cache.connect(host, port).await?
+        };
         let local_addr = stream.local_addr()?;
         // this is known to fail on macOS for IPv6.
         // See: https://github.com/rust-lang/rust/issues/95541
@@ -236,7 +319,8 @@ impl Sink for TcpClientSink {
                         .map_or_else(|| self.config.url.host_or_local(), String::as_str),
                     stream.clone(),
                 )
-                .await?;
+                .await
+                .connect_step(&endpoint, ConnectStep::Tls)?;
             let (read, write) = tls_stream.split();
             let meta = ctx.meta(literal!({
                 "tls": true,
@@ -249,13 +333,19 @@ impl Sink for TcpClientSink {
             self.wrapped_stream = Some(Box::new(write));
             self.tcp_stream = Some(stream.clone());
             // register reader
-            let tls_reader = TcpReader::tls_client(
-                read,
-                stream,
-                vec![0; buf_size],
-                ctx.alias.clone(),
-                origin_uri,
-                meta,
+            let tls_reader = FrameLimitReader::new(
+                TcpReader::tls_client(
+                    read,
+                    stream,
+                    vec![0; buf_size],
+                    ctx.alias.clone(),
+                    origin_uri,
+                    meta,
+                ),
+                ctx,
+                self.config.max_frame_len,
+                self.frame_separator,
+                self.config.on_frame_overflow,
             );
             self.source_runtime
                 .register_stream_reader(DEFAULT_STREAM_ID, ctx, tls_reader);
@@ -274,18 +364,40 @@ impl Sink for TcpClientSink {
             self.tcp_stream = Some(stream.clone());
 
             // register reader for receiving from the connection via the source
-            let reader = TcpReader::new(
-                stream,
-                vec![0; buf_size],
-                ctx.alias.clone(),
-                origin_uri,
-                meta,
+            let reader = FrameLimitReader::new(
+                TcpReader::new(
+                    stream,
+                    vec![0; buf_size],
+                    ctx.alias.clone(),
+                    origin_uri,
+                    meta,
+                ),
+                ctx,
+                self.config.max_frame_len,
+                self.frame_separator,
+                self.config.on_frame_overflow,
             );
             self.source_runtime
                 .register_stream_reader(DEFAULT_STREAM_ID, ctx, reader);
         }
         Ok(true)
     }
+}
+
+#[async_trait::async_trait()]
+impl Sink for TcpClientSink {
+    async fn connect(&mut self, ctx: &SinkContext, _attempt: &Attempt) -> Result<bool> {
+        let result = self.establish(ctx).await;
+        if let Some(diagnostic) = result
+            .as_ref()
+            .err()
+            .filter(|_| self.config.connect_diagnostics)
+            .and_then(ConnectDiagnostic::of)
+        {
+            self.report(diagnostic).await?;
+        }
+        result
+    }
 
     async fn on_event(
         &mut self,
@@ -328,3 +440,87 @@ impl Sink for TcpClientSink {
         false
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connectors::reconnect::ConnectionLostNotifier;
+    use crate::connectors::utils::tls::tls_client_connector;
+    use async_std::net::TcpListener;
+    use value_trait::ValueAccess;
+
+    fn sink_context() -> SinkContext {
+        let (tx, _rx) = async_std::channel::unbounded();
+        SinkContext {
+            uid: Default::default(),
+            alias: Alias::new("flow", "tcp_client"),
+            connector_type: "tcp_client".into(),
+            quiescence_beacon: Default::default(),
+            notifier: ConnectionLostNotifier::new(tx),
+        }
+    }
+
+    /// connects `sink` and returns the failed step, as reported in the error and on the `status` port
+    async fn failed_step(
+        mut sink: TcpClientSink,
+        source_rx: &Receiver<SourceReply>,
+    ) -> Result<(ConnectStep, String)> {
+        let error = sink
+            .connect(&sink_context(), &Attempt::default())
+            .await
+            .err()
+            .ok_or("expected connecting to fail")?;
+        let step = ConnectDiagnostic::of(&error)
+            .ok_or("expected a connect diagnostic")?
+            .step;
+        match source_rx.try_recv()? {
+            SourceReply::Structured { payload, port, .. } => {
+                assert_eq!(Some(STATUS), port);
+                let reported = payload.suffix().value().get_str("step").unwrap_or_default();
+                Ok((step, reported.to_string()))
+            }
+            _ => Err("expected a status event".into()),
+        }
+    }
+
+    #[async_std::test]
+    async fn connect_diagnostics() -> Result<()> {
+        let (source_tx, source_rx) = bounded(8);
+        let config = Config::new(&literal!({
+            "url": "snot.invalid:4242",
+            "connect_diagnostics": true
+        }))?;
+        let sink = TcpClientSink::plain(config, b'\n', source_tx.clone());
+        assert_eq!(
+            (ConnectStep::Dns, "dns".to_string()),
+            failed_step(sink, &source_rx).await?
+        );
+
+        // a server not speaking TLS
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let port = listener.local_addr()?.port();
+        let server = async_std::task::spawn(async move {
+            let (mut stream, _) = listener.accept().await?;
+            stream.write_all(b"snot badger\r\n\r\n").await?;
+            Result::Ok(())
+        });
+        let config = Config::new(&literal!({
+            "url": format!("127.0.0.1:{port}"),
+            "tls": true,
+            "connect_diagnostics": true
+        }))?;
+        let sink = TcpClientSink::tls(
+            tls_client_connector(&TLSClientConfig::default()).await?,
+            Some("localhost".to_string()),
+            config,
+            b'\n',
+            source_tx,
+        );
+        assert_eq!(
+            (ConnectStep::Tls, "tls".to_string()),
+            failed_step(sink, &source_rx).await?
+        );
+        server.await?;
+        Ok(())
+    }
+}
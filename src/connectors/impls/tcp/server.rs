@@ -15,9 +15,15 @@ use super::{TcpDefaults, TcpReader, TcpWriter};
 use crate::{
     connectors::{
         prelude::*,
-        sink::channel_sink::ChannelSinkMsg,
+        sink::channel_sink::{ChannelSinkMsg, WriteBuffer},
         utils::{
-            tls::{load_server_config, TLSServerConfig},
+            access_log::{AccessLog, AccessLogReader, AccessLogWriter},
+            diagnostics::tcp_bind,
+            frame_limit::{frame_separator, FrameLimitReader, FrameOverflow},
+            tls::{
+                load_server_config, peek_sni, record_sni, PeekedSni, TLSServerConfig,
+                MAX_TLS_RECORD_LEN,
+            },
             ConnectionMeta,
         },
     },
@@ -25,7 +31,7 @@ use crate::{
 };
 use async_std::{
     channel::{bounded, Receiver, Sender},
-    net::TcpListener,
+    net::TcpStream,
     prelude::*,
     task::JoinHandle,
 };
@@ -33,7 +39,9 @@ use async_tls::TlsAcceptor;
 use futures::io::AsyncReadExt;
 use rustls::ServerConfig;
 use simd_json::ValueAccess;
+use std::num::NonZeroUsize;
 use std::sync::Arc;
+use std::time::Duration;
 
 const URL_SCHEME: &str = "tremor-tcp-server";
 
@@ -42,17 +50,74 @@ const URL_SCHEME: &str = "tremor-tcp-server";
 pub(crate) struct Config {
     url: Url<TcpDefaults>,
     tls: Option<TLSServerConfig>,
+    /// `"terminate"` to terminate TLS as configured in `tls`, or `"passthrough"` to forward the encrypted bytes unchanged,
+    /// with the SNI server name of the `ClientHello` in `$tcp_server.tls_sni`
+    #[serde(default)]
+    tls_mode: TlsMode,
+    /// timeout in milliseconds for waiting for the `ClientHello` of each connection with `tls_mode` `"passthrough"`,
+    /// connections without a complete `ClientHello` by then are forwarded without `tls_sni`
+    #[serde(default = "default_client_hello_timeout_ms")]
+    client_hello_timeout_ms: u64,
     // TCP: receive buffer size
     #[serde(default = "default_buf_size")]
     buf_size: usize,
+    /// emit an event without data when a connection is opened and when it is closed,
+    /// with `$tcp_server.event` set to `"connected"` or `"disconnected"` respectively.
+    /// Connections are disconnected when closed by the peer, on read errors and when stopping.
+    #[serde(default = "default_false")]
+    lifecycle_events: bool,
+    /// send events without `peer` or `stream_id` metadata to all connected clients
+    #[serde(default = "default_false")]
+    broadcast: bool,
+    /// log peer, tls, bytes read and written and duration of each connection when it is closed
+    #[serde(default = "default_false")]
+    access_log: bool,
+    /// timeout in milliseconds for waiting for new connections,
+    /// this bounds how long it takes to notice the connector being stopped
+    #[serde(default = "default_accept_timeout_ms")]
+    accept_timeout_ms: u64,
+    /// keep the raw bytes of each event, before decoding, in the event metadata under `raw`
+    #[serde(default = "default_false")]
+    keep_raw: bool,
+    /// maximum length in bytes of a line, lines are not limited if not set
+    #[serde(default)]
+    max_frame_len: Option<usize>,
+    /// what to do with a line exceeding `max_frame_len`, either `"disconnect"` or `"error"`
+    #[serde(default)]
+    on_frame_overflow: FrameOverflow,
+    /// capacity of the internal channels, overriding the global queue size if set
+    #[serde(default)]
+    queue_size: Option<NonZeroUsize>,
+    /// coalesce the data of multiple events into a single write per connection, writing each event right away if not set
+    #[serde(default)]
+    write_buffer: Option<WriteBuffer>,
 }
 
 impl ConfigImpl for Config {}
 
+fn default_client_hello_timeout_ms() -> u64 {
+    10_000
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum TlsMode {
+    Terminate,
+    Passthrough,
+}
+
+impl Default for TlsMode {
+    fn default() -> Self {
+        Self::Terminate
+    }
+}
+
 #[allow(clippy::module_name_repetitions)]
 pub(crate) struct TcpServer {
     config: Config,
     tls_server_config: Option<ServerConfig>,
+    /// separator of the lines limited by `max_frame_len`
+    frame_separator: u8,
     sink_tx: Sender<ChannelSinkMsg<ConnectionMeta>>,
     sink_rx: Receiver<ChannelSinkMsg<ConnectionMeta>>,
 }
@@ -68,7 +133,7 @@ impl ConnectorBuilder for Builder {
     async fn build_cfg(
         &self,
         id: &Alias,
-        _: &ConnectorConfig,
+        connector_config: &ConnectorConfig,
         config: &Value,
         _kill_switch: &KillSwitch,
     ) -> crate::errors::Result<Box<dyn Connector>> {
@@ -76,21 +141,43 @@ impl ConnectorBuilder for Builder {
         if config.url.port().is_none() {
             return Err(err_connector_def(id, "Missing port for TCP server"));
         }
+        if config.tls_mode == TlsMode::Passthrough && config.tls.is_some() {
+            return Err(err_connector_def(
+                id,
+                "`tls` can't be combined with `tls_mode` \"passthrough\", TLS isn't terminated",
+            ));
+        }
         let tls_server_config = if let Some(tls_config) = config.tls.as_ref() {
             Some(load_server_config(tls_config)?)
         } else {
             None
         };
-        let (sink_tx, sink_rx) = bounded(crate::QSIZE.load(Ordering::Relaxed));
+        let (sink_tx, sink_rx) = bounded(queue_size(config.queue_size));
         Ok(Box::new(TcpServer {
             config,
             tls_server_config,
+            frame_separator: frame_separator(connector_config),
             sink_tx,
             sink_rx,
         }))
     }
 }
 
+/// Expect connection meta as:
+///
+/// ```json
+/// {
+///     "tcp_server": {
+///         "peer": {
+///             "host": "127.0.0.1",
+///             "port": 12345
+///         }
+///     }
+/// }
+/// ```
+///
+/// Alternatively connections can be addressed by the `stream_id` from the metadata of their events,
+/// e.g. `{"tcp_server": {"stream_id": 123}}`, which takes precedence over the `peer`.
 fn resolve_connection_meta(meta: &Value) -> Option<ConnectionMeta> {
     let peer = meta.get("peer");
     peer.get_u16("port")
@@ -103,6 +190,25 @@ fn resolve_connection_meta(meta: &Value) -> Option<ConnectionMeta> {
         })
 }
 
+/// Waits up to `timeout` for the `ClientHello` of `stream` and extracts its SNI server name,
+/// the peeked bytes are still read from the stream afterwards
+async fn peek_client_hello_sni(stream: &TcpStream, timeout: Duration) -> Option<String> {
+    let deadline = std::time::Instant::now() + timeout;
+    let mut buf = vec![0; MAX_TLS_RECORD_LEN];
+    loop {
+        let remaining = deadline.saturating_duration_since(std::time::Instant::now());
+        let peeked = stream.peek(&mut buf).timeout(remaining).await.ok()?.ok()?;
+        // ALLOW: peek returns at most buf.len() bytes
+        match peek_sni(&buf[..peeked]) {
+            PeekedSni::Found(sni) => return Some(sni),
+            PeekedSni::Missing => return None,
+            PeekedSni::Incomplete if peeked == 0 || peeked == buf.len() => return None,
+            // peek doesn't wait for more bytes than were already received
+            PeekedSni::Incomplete => async_std::task::sleep(Duration::from_millis(10)).await,
+        }
+    }
+}
+
 #[async_trait::async_trait()]
 impl Connector for TcpServer {
     async fn create_source(
@@ -110,10 +216,12 @@ impl Connector for TcpServer {
         ctx: SourceContext,
         builder: SourceManagerBuilder,
     ) -> Result<Option<SourceAddr>> {
-        let sink_runtime = ChannelSinkRuntime::new(self.sink_tx.clone());
+        let sink_runtime = ChannelSinkRuntime::new(self.sink_tx.clone())
+            .with_write_buffer(self.config.write_buffer);
         let source = TcpServerSource::new(
             self.config.clone(),
             self.tls_server_config.clone(),
+            self.frame_separator,
             sink_runtime,
         );
         builder.spawn(source, ctx).map(Some)
@@ -130,7 +238,12 @@ impl Connector for TcpServer {
             builder.reply_tx(),
             self.sink_tx.clone(),
             self.sink_rx.clone(),
-        );
+        )
+        .with_broadcast(self.config.broadcast)
+        .with_flush_on_tick(self.config.write_buffer.is_some())
+        .with_stream_id_routing(true)
+        // `$tcp_server.close_after_write` closes the write half of the connection once the event is written
+        .with_close_after_write(true);
         builder.spawn(sink, ctx).map(Some)
     }
 
@@ -142,6 +255,7 @@ impl Connector for TcpServer {
 struct TcpServerSource {
     config: Config,
     tls_server_config: Option<ServerConfig>,
+    frame_separator: u8,
     accept_task: Option<JoinHandle<()>>,
     connection_rx: Receiver<SourceReply>,
     runtime: ChannelSourceRuntime,
@@ -152,13 +266,15 @@ impl TcpServerSource {
     fn new(
         config: Config,
         tls_server_config: Option<ServerConfig>,
+        frame_separator: u8,
         sink_runtime: ChannelSinkRuntime<ConnectionMeta>,
     ) -> Self {
-        let (tx, rx) = bounded(crate::QSIZE.load(Ordering::Relaxed));
+        let (tx, rx) = bounded(queue_size(config.queue_size));
         let runtime = ChannelSourceRuntime::new(tx);
         Self {
             config,
             tls_server_config,
+            frame_separator,
             accept_task: None,
             connection_rx: rx,
             runtime,
@@ -173,6 +289,14 @@ impl Source for TcpServerSource {
         let path = vec![self.config.url.port_or_dflt().to_string()];
         let accept_ctx = ctx.clone();
         let buf_size = self.config.buf_size;
+        let lifecycle_events = self.config.lifecycle_events;
+        let access_log = self.config.access_log;
+        let max_frame_len = self.config.max_frame_len;
+        let frame_separator = self.frame_separator;
+        let on_frame_overflow = self.config.on_frame_overflow;
+        let tls_mode = self.config.tls_mode;
+        let accept_timeout = Duration::from_millis(self.config.accept_timeout_ms);
+        let client_hello_timeout = Duration::from_millis(self.config.client_hello_timeout_ms);
 
         // cancel last accept task if necessary, this will drop the previous listener
         if let Some(previous_handle) = self.accept_task.take() {
@@ -182,7 +306,7 @@ impl Source for TcpServerSource {
         let host = self.config.url.host_or_local();
         let port = self.config.url.port_or_dflt();
 
-        let listener = TcpListener::bind((host, port)).await?;
+        let listener = tcp_bind(host, port).await?;
 
         let ctx = ctx.clone();
         let tls_server_config = self.tls_server_config.clone();
@@ -194,7 +318,7 @@ impl Source for TcpServerSource {
             let mut stream_id_gen = StreamIdGen::default();
 
             while ctx.quiescence_beacon().continue_reading().await {
-                match listener.accept().timeout(ACCEPT_TIMEOUT).await {
+                match listener.accept().timeout(accept_timeout).await {
                     Ok(Ok((stream, peer_addr))) => {
                         debug!("{accept_ctx} new connection from {peer_addr}");
                         let stream_id: u64 = stream_id_gen.next_stream_id();
@@ -208,26 +332,44 @@ impl Source for TcpServerSource {
                             path: path.clone(), // captures server port
                         };
 
-                        let tls_acceptor: Option<TlsAcceptor> = tls_server_config
-                            .clone()
-                            .map(|sc| TlsAcceptor::from(Arc::new(sc)));
-                        if let Some(acceptor) = tls_acceptor {
+                        let tls_acceptor = tls_server_config.as_ref().map(|sc| {
+                            let (sc, sni) = record_sni(sc);
+                            (TlsAcceptor::from(Arc::new(sc)), sni)
+                        });
+                        if let Some((acceptor, sni)) = tls_acceptor {
                             let tls_stream = acceptor.accept(stream.clone()).await?;
                             let (tls_read_stream, tls_write_sink) = tls_stream.split();
-                            let meta = ctx.meta(literal!({
+                            let mut peer_meta = literal!({
                                 "tls": true,
                                 "peer": {
                                     "host": peer_addr.ip().to_string(),
                                     "port": peer_addr.port()
-                                }
-                            }));
-                            let tls_reader = TcpReader::tls_server(
-                                tls_read_stream,
-                                stream.clone(),
-                                vec![0; buf_size],
-                                ctx.alias.clone(),
-                                origin_uri.clone(),
-                                meta,
+                                },
+                                "stream_id": stream_id
+                            });
+                            // only present if the client sent a server name
+                            if let Some(sni) = sni.lock().ok().and_then(|mut sni| sni.take()) {
+                                peer_meta.try_insert("tls_sni", sni);
+                            }
+                            let meta = ctx.meta(peer_meta.clone());
+                            let access_log = access_log
+                                .then(|| AccessLog::new(&ctx, connection_meta.clone(), true));
+                            let tls_reader = FrameLimitReader::new(
+                                AccessLogReader::new(
+                                    TcpReader::tls_server(
+                                        tls_read_stream,
+                                        stream.clone(),
+                                        vec![0; buf_size],
+                                        ctx.alias.clone(),
+                                        origin_uri.clone(),
+                                        meta,
+                                    ),
+                                    access_log.clone(),
+                                ),
+                                &ctx,
+                                max_frame_len,
+                                frame_separator,
+                                on_frame_overflow,
                             );
 
                             sink_runtime
@@ -235,37 +377,81 @@ impl Source for TcpServerSource {
                                     stream_id,
                                     Some(connection_meta.clone()),
                                     &ctx,
-                                    TcpWriter::tls_server(tls_write_sink, stream),
+                                    AccessLogWriter::new(
+                                        TcpWriter::tls_server(tls_write_sink, stream),
+                                        access_log,
+                                    ),
                                 )
                                 .await;
 
-                            runtime.register_stream_reader(stream_id, &ctx, tls_reader);
+                            if lifecycle_events {
+                                let tls_reader =
+                                    LifecycleReader::new(tls_reader, &ctx, origin_uri, peer_meta);
+                                runtime.register_stream_reader(stream_id, &ctx, tls_reader);
+                            } else {
+                                runtime.register_stream_reader(stream_id, &ctx, tls_reader);
+                            }
                         } else {
-                            let meta = ctx.meta(literal!({
-                                "tls": false,
-                                "peer": {
-                                    "host": peer_addr.ip().to_string(),
-                                    "port": peer_addr.port()
+                            let ctx = ctx.clone();
+                            let runtime = runtime.clone();
+                            let sink_runtime = sink_runtime.clone();
+                            // waiting for the ClientHello must not hold up accepting other connections
+                            async_std::task::spawn(async move {
+                                let mut peer_meta = literal!({
+                                    "tls": false,
+                                    "peer": {
+                                        "host": peer_addr.ip().to_string(),
+                                        "port": peer_addr.port()
+                                    },
+                                    "stream_id": stream_id
+                                });
+                                if tls_mode == TlsMode::Passthrough {
+                                    peer_meta.try_insert("tls_passthrough", true);
+                                    // only present if the client sent a server name
+                                    if let Some(sni) =
+                                        peek_client_hello_sni(&stream, client_hello_timeout).await
+                                    {
+                                        peer_meta.try_insert("tls_sni", sni);
+                                    }
                                 }
-                            }));
-                            let tcp_reader = TcpReader::new(
-                                stream.clone(),
-                                vec![0; buf_size],
-                                ctx.alias.clone(),
-                                origin_uri.clone(),
-                                meta,
-                            );
-
-                            sink_runtime
-                                .register_stream_writer(
-                                    stream_id,
-                                    Some(connection_meta.clone()),
+                                let meta = ctx.meta(peer_meta.clone());
+                                let access_log = access_log
+                                    .then(|| AccessLog::new(&ctx, connection_meta.clone(), false));
+                                let tcp_reader = FrameLimitReader::new(
+                                    AccessLogReader::new(
+                                        TcpReader::new(
+                                            stream.clone(),
+                                            vec![0; buf_size],
+                                            ctx.alias.clone(),
+                                            origin_uri.clone(),
+                                            meta,
+                                        ),
+                                        access_log.clone(),
+                                    ),
                                     &ctx,
-                                    TcpWriter::new(stream),
-                                )
-                                .await;
+                                    max_frame_len,
+                                    frame_separator,
+                                    on_frame_overflow,
+                                );
+
+                                sink_runtime
+                                    .register_stream_writer(
+                                        stream_id,
+                                        Some(connection_meta.clone()),
+                                        &ctx,
+                                        AccessLogWriter::new(TcpWriter::new(stream), access_log),
+                                    )
+                                    .await;
 
-                            runtime.register_stream_reader(stream_id, &ctx, tcp_reader);
+                                if lifecycle_events {
+                                    let tcp_reader = LifecycleReader::new(
+                                        tcp_reader, &ctx, origin_uri, peer_meta,
+                                    );
+                                    runtime.register_stream_reader(stream_id, &ctx, tcp_reader);
+                                } else {
+                                    runtime.register_stream_reader(stream_id, &ctx, tcp_reader);
+                                }
+                            });
                         }
                     }
                     Ok(Err(e)) => return Err(e.into()),
@@ -298,4 +484,292 @@ impl Source for TcpServerSource {
     fn asynchronous(&self) -> bool {
         true
     }
+
+    fn keep_raw(&self) -> bool {
+        self.config.keep_raw
+    }
+}
+
+/// Wraps a `StreamReader` and emits an event without data
+/// when the connection is opened and when it is closed.
+///
+/// These events carry the connection metadata with an additional `event` field
+/// set to either `"connected"` or `"disconnected"`.
+struct LifecycleReader<R>
+where
+    R: StreamReader,
+{
+    inner: R,
+    origin_uri: EventOriginUri,
+    connected_meta: Option<Value<'static>>,
+    disconnected_meta: Option<Value<'static>>,
+    /// how the stream ends, held back until the disconnected event is read
+    end: Option<Result<SourceReply>>,
+}
+
+impl<R> LifecycleReader<R>
+where
+    R: StreamReader,
+{
+    const EVENT: &'static str = "event";
+    const CONNECTED: &'static str = "connected";
+    const DISCONNECTED: &'static str = "disconnected";
+
+    fn new<C: Context>(
+        inner: R,
+        ctx: &C,
+        origin_uri: EventOriginUri,
+        peer_meta: Value<'static>,
+    ) -> Self {
+        let mut connected_meta = peer_meta.clone();
+        connected_meta.try_insert(Self::EVENT, Self::CONNECTED);
+        let mut disconnected_meta = peer_meta;
+        disconnected_meta.try_insert(Self::EVENT, Self::DISCONNECTED);
+        Self {
+            inner,
+            origin_uri,
+            connected_meta: Some(ctx.meta(connected_meta)),
+            disconnected_meta: Some(ctx.meta(disconnected_meta)),
+            end: None,
+        }
+    }
+
+    fn lifecycle_event(&self, stream: u64, meta: Value<'static>) -> SourceReply {
+        SourceReply::Structured {
+            origin_uri: self.origin_uri.clone(),
+            payload: EventPayload::from(ValueAndMeta::from_parts(Value::const_null(), meta)),
+            stream,
+            port: None,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl<R> StreamReader for LifecycleReader<R>
+where
+    R: StreamReader,
+{
+    async fn quiesce(&mut self, stream: u64) -> Option<SourceReply> {
+        let reply = match self.end.take() {
+            Some(Ok(end)) => Some(end),
+            // the error is returned by the next read
+            Some(Err(e)) => {
+                self.end = Some(Err(e));
+                return None;
+            }
+            None => self.inner.quiesce(stream).await,
+        };
+        if self.disconnected_meta.is_some() {
+            // have the disconnected event read before the stream ends
+            self.end = reply.map(Ok);
+            None
+        } else {
+            reply
+        }
+    }
+
+    async fn read(&mut self, stream: u64) -> Result<SourceReply> {
+        if let Some(meta) = self.connected_meta.take() {
+            return Ok(self.lifecycle_event(stream, meta));
+        }
+        let reply = match self.end.take() {
+            Some(end) => end,
+            None => self.inner.read(stream).await,
+        };
+        match reply {
+            // the connection ends with the end of the stream or an error
+            Ok(SourceReply::EndStream { .. }) | Err(_) => {
+                if let Some(meta) = self.disconnected_meta.take() {
+                    // emit the disconnected event first and end the stream afterwards
+                    self.end = Some(reply);
+                    return Ok(self.lifecycle_event(stream, meta));
+                }
+                reply
+            }
+            reply => reply,
+        }
+    }
+
+    async fn on_done(&mut self, stream: u64) -> StreamDone {
+        self.inner.on_done(stream).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connectors::reconnect::ConnectionLostNotifier;
+    use async_std::channel::unbounded;
+    use async_std::net::TcpListener;
+    use tremor_common::ids::SourceId;
+
+    #[test]
+    fn accept_timeout() -> Result<()> {
+        let config = Config::new(&literal!({"url": "localhost:4242"}))?;
+        assert_eq!(default_accept_timeout_ms(), config.accept_timeout_ms);
+        let config = Config::new(&literal!({"url": "localhost:4242", "accept_timeout_ms": 5}))?;
+        assert_eq!(5, config.accept_timeout_ms);
+        Ok(())
+    }
+
+    /// `ClientHello` sent by a client connecting to `tremor.example`
+    const CLIENT_HELLO: [u8; 159] = [
+        0x16, 0x03, 0x01, 0x00, 0x9a, 0x01, 0x00, 0x00, 0x96, 0x03, 0x03, 0x93, 0xc1, 0x06, 0xf4,
+        0x3f, 0x41, 0x7e, 0xdb, 0x00, 0x3b, 0xb8, 0x49, 0xe7, 0x31, 0x86, 0x71, 0x71, 0x81, 0x79,
+        0xfa, 0x22, 0xc2, 0x06, 0x66, 0xf9, 0xb5, 0xd8, 0xb5, 0xb6, 0xef, 0x51, 0xc8, 0x00, 0x00,
+        0x04, 0xc0, 0x2f, 0x00, 0xff, 0x01, 0x00, 0x00, 0x69, 0x00, 0x00, 0x00, 0x13, 0x00, 0x11,
+        0x00, 0x00, 0x0e, 0x74, 0x72, 0x65, 0x6d, 0x6f, 0x72, 0x2e, 0x65, 0x78, 0x61, 0x6d, 0x70,
+        0x6c, 0x65, 0x00, 0x0b, 0x00, 0x04, 0x03, 0x00, 0x01, 0x02, 0x00, 0x0a, 0x00, 0x0c, 0x00,
+        0x0a, 0x00, 0x1d, 0x00, 0x17, 0x00, 0x1e, 0x00, 0x19, 0x00, 0x18, 0x00, 0x23, 0x00, 0x00,
+        0x00, 0x16, 0x00, 0x00, 0x00, 0x17, 0x00, 0x00, 0x00, 0x0d, 0x00, 0x2a, 0x00, 0x28, 0x04,
+        0x03, 0x05, 0x03, 0x06, 0x03, 0x08, 0x07, 0x08, 0x08, 0x08, 0x09, 0x08, 0x0a, 0x08, 0x0b,
+        0x08, 0x04, 0x08, 0x05, 0x08, 0x06, 0x04, 0x01, 0x05, 0x01, 0x06, 0x01, 0x03, 0x03, 0x03,
+        0x01, 0x03, 0x02, 0x04, 0x02, 0x05, 0x02, 0x06, 0x02,
+    ];
+
+    #[async_std::test]
+    async fn tls_passthrough_sni() -> Result<()> {
+        let config = Config::new(&literal!({"url": "localhost:4242", "tls_mode": "passthrough"}))?;
+        assert_eq!(TlsMode::Passthrough, config.tls_mode);
+        assert_eq!(
+            default_client_hello_timeout_ms(),
+            config.client_hello_timeout_ms
+        );
+        assert!(Config::new(&literal!({"url": "localhost:4242", "tls_mode": "snot"})).is_err());
+
+        let listener = TcpListener::bind(("127.0.0.1", 0)).await?;
+        let mut client = TcpStream::connect(listener.local_addr()?).await?;
+        let (stream, _) = listener.accept().await?;
+        // the ClientHello arrives in two parts
+        client.write_all(&CLIENT_HELLO[..50]).await?;
+        let write_rest = async_std::task::spawn(async move {
+            async_std::task::sleep(Duration::from_millis(50)).await;
+            client.write_all(&CLIENT_HELLO[50..]).await?;
+            Ok::<_, std::io::Error>(client)
+        });
+        assert_eq!(
+            Some("tremor.example".to_string()),
+            peek_client_hello_sni(&stream, Duration::from_secs(5)).await
+        );
+        let _client = write_rest.await?;
+
+        // the encrypted bytes are forwarded unchanged
+        let mut forwarded = vec![0; CLIENT_HELLO.len()];
+        AsyncReadExt::read_exact(&mut &stream, &mut forwarded).await?;
+        assert_eq!(CLIENT_HELLO.to_vec(), forwarded);
+
+        assert_eq!(PeekedSni::Incomplete, peek_sni(&CLIENT_HELLO[..100]));
+        assert_eq!(PeekedSni::Missing, peek_sni(b"GET / HTTP/1.1\r\n"));
+        Ok(())
+    }
+
+    #[test]
+    fn queue_size_override() -> Result<()> {
+        let (sink_tx, _sink_rx) = bounded(1);
+        let config = Config::new(&literal!({"url": "localhost:4242"}))?;
+        let source = TcpServerSource::new(
+            config,
+            None,
+            b'\n',
+            ChannelSinkRuntime::new(sink_tx.clone()),
+        );
+        assert_eq!(
+            Some(crate::QSIZE.load(Ordering::Relaxed)),
+            source.connection_rx.capacity()
+        );
+
+        let config = Config::new(&literal!({"url": "localhost:4242", "queue_size": 7}))?;
+        let source = TcpServerSource::new(config, None, b'\n', ChannelSinkRuntime::new(sink_tx));
+        assert_eq!(Some(7), source.connection_rx.capacity());
+
+        assert!(Config::new(&literal!({"url": "localhost:4242", "queue_size": 0})).is_err());
+        Ok(())
+    }
+
+    /// reads one chunk of data, then fails
+    struct FailingReader {
+        read: bool,
+    }
+
+    #[async_trait::async_trait]
+    impl StreamReader for FailingReader {
+        async fn read(&mut self, stream: u64) -> Result<SourceReply> {
+            if self.read {
+                return Err("connection reset".into());
+            }
+            self.read = true;
+            Ok(SourceReply::Data {
+                origin_uri: EventOriginUri::default(),
+                stream: Some(stream),
+                meta: None,
+                data: b"snot".to_vec(),
+                port: None,
+                codec_overwrite: None,
+            })
+        }
+
+        async fn quiesce(&mut self, stream: u64) -> Option<SourceReply> {
+            Some(SourceReply::EndStream {
+                origin_uri: EventOriginUri::default(),
+                stream,
+                meta: None,
+            })
+        }
+    }
+
+    fn lifecycle_reader() -> LifecycleReader<FailingReader> {
+        let (tx, _rx) = unbounded();
+        let ctx = SourceContext {
+            uid: SourceId::new(1),
+            alias: Alias::new("flow", "connector"),
+            connector_type: "tcp_server".into(),
+            quiescence_beacon: Default::default(),
+            notifier: ConnectionLostNotifier::new(tx),
+        };
+        LifecycleReader::new(
+            FailingReader { read: false },
+            &ctx,
+            EventOriginUri::default(),
+            literal!({"peer": {"host": "127.0.0.1", "port": 4242}}),
+        )
+    }
+
+    fn lifecycle_event(reply: &SourceReply) -> Option<&str> {
+        match reply {
+            SourceReply::Structured { payload, .. } => {
+                payload.suffix().meta().get("tcp_server").get_str("event")
+            }
+            _ => None,
+        }
+    }
+
+    #[async_std::test]
+    async fn disconnected_on_read_error() -> Result<()> {
+        let mut reader = lifecycle_reader();
+        assert_eq!(Some("connected"), lifecycle_event(&reader.read(1).await?));
+        assert!(matches!(reader.read(1).await?, SourceReply::Data { .. }));
+        assert_eq!(
+            Some("disconnected"),
+            lifecycle_event(&reader.read(1).await?)
+        );
+        assert!(reader.read(1).await.is_err());
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn disconnected_on_quiesce() -> Result<()> {
+        let mut reader = lifecycle_reader();
+        // quiescing before anything was read
+        assert!(reader.quiesce(1).await.is_none());
+        assert_eq!(Some("connected"), lifecycle_event(&reader.read(1).await?));
+        assert_eq!(
+            Some("disconnected"),
+            lifecycle_event(&reader.read(1).await?)
+        );
+        assert!(matches!(
+            reader.quiesce(1).await,
+            Some(SourceReply::EndStream { .. })
+        ));
+        Ok(())
+    }
 }
@@ -13,11 +13,13 @@
 // limitations under the License.
 
 use crate::connectors::prelude::*;
+use crate::connectors::utils::frame_limit::{frame_separator, FrameLimitReader, FrameOverflow};
 use crate::errors::{Kind as ErrorKind, Result};
 use async_std::channel::{bounded, Receiver, Sender};
 use async_std::os::unix::net::UnixStream;
 use async_std::path::PathBuf;
 use futures::AsyncWriteExt;
+use std::num::NonZeroUsize;
 
 use super::UnixSocketReader;
 
@@ -29,6 +31,18 @@ pub(crate) struct Config {
     path: String,
     #[serde(default = "default_buf_size")]
     buf_size: usize,
+    /// keep the raw bytes of each event, before decoding, in the event metadata under `raw`
+    #[serde(default = "default_false")]
+    keep_raw: bool,
+    /// maximum length in bytes of a line, lines are not limited if not set
+    #[serde(default)]
+    max_frame_len: Option<usize>,
+    /// what to do with a line exceeding `max_frame_len`, either `"disconnect"` or `"error"`
+    #[serde(default)]
+    on_frame_overflow: FrameOverflow,
+    /// capacity of the internal channels, overriding the global queue size if set
+    #[serde(default)]
+    queue_size: Option<NonZeroUsize>,
 }
 
 impl ConfigImpl for Config {}
@@ -44,14 +58,16 @@ impl ConnectorBuilder for Builder {
     async fn build_cfg(
         &self,
         _: &Alias,
-        _: &ConnectorConfig,
+        connector_config: &ConnectorConfig,
         conf: &Value,
         _kill_switch: &KillSwitch,
     ) -> Result<Box<dyn Connector>> {
         let config = Config::new(conf)?;
-        let (source_tx, source_rx) = bounded(crate::QSIZE.load(Ordering::Relaxed));
+        let (source_tx, source_rx) = bounded(queue_size(config.queue_size));
         Ok(Box::new(Client {
             config,
+            frame_separator: frame_separator(connector_config),
+            instance_host: connector_config.instance_host(),
             source_tx,
             source_rx,
         }))
@@ -60,6 +76,10 @@ impl ConnectorBuilder for Builder {
 
 pub(crate) struct Client {
     config: Config,
+    /// separator of the lines limited by `max_frame_len`
+    frame_separator: u8,
+    /// host of the origin URI of the events received
+    instance_host: String,
     source_tx: Sender<SourceReply>,
     source_rx: Receiver<SourceReply>,
 }
@@ -76,7 +96,8 @@ impl Connector for Client {
         builder: SourceManagerBuilder,
     ) -> Result<Option<SourceAddr>> {
         // this source is wired up to the ending channel that is forwarding data received from the TCP (or TLS) connection
-        let source = ChannelSource::from_channel(self.source_tx.clone(), self.source_rx.clone());
+        let source = ChannelSource::from_channel(self.source_tx.clone(), self.source_rx.clone())
+            .with_keep_raw(self.config.keep_raw);
         builder.spawn(source, source_context).map(Some)
     }
 
@@ -85,22 +106,36 @@ impl Connector for Client {
         sink_context: SinkContext,
         builder: SinkManagerBuilder,
     ) -> Result<Option<SinkAddr>> {
-        let sink = UnixSocketSink::new(self.config.clone(), self.source_tx.clone());
+        let sink = UnixSocketSink::new(
+            self.config.clone(),
+            self.frame_separator,
+            self.instance_host.clone(),
+            self.source_tx.clone(),
+        );
         builder.spawn(sink, sink_context).map(Some)
     }
 }
 
 struct UnixSocketSink {
     config: Config,
+    frame_separator: u8,
+    instance_host: String,
     source_runtime: ChannelSourceRuntime,
     stream: Option<UnixStream>,
 }
 
 impl UnixSocketSink {
-    fn new(config: Config, source_tx: Sender<SourceReply>) -> Self {
+    fn new(
+        config: Config,
+        frame_separator: u8,
+        instance_host: String,
+        source_tx: Sender<SourceReply>,
+    ) -> Self {
         let source_runtime = ChannelSourceRuntime::new(source_tx);
         Self {
             config,
+            frame_separator,
+            instance_host,
             source_runtime,
             stream: None,
         }
@@ -142,7 +177,7 @@ impl Sink for UnixSocketSink {
         let stream = UnixStream::connect(&path).await?;
         let origin_uri = EventOriginUri {
             scheme: URL_SCHEME.to_string(),
-            host: hostname(),
+            host: self.instance_host.clone(),
             port: None,
             path: vec![path.display().to_string()],
         };
@@ -150,12 +185,18 @@ impl Sink for UnixSocketSink {
             "peer": path.display().to_string()
         }));
         self.stream = Some(stream.clone());
-        let reader = UnixSocketReader::new(
-            stream,
-            vec![0; self.config.buf_size],
-            ctx.alias().to_string(),
-            origin_uri,
-            meta,
+        let reader = FrameLimitReader::new(
+            UnixSocketReader::new(
+                stream,
+                vec![0; self.config.buf_size],
+                ctx.alias().to_string(),
+                origin_uri,
+                meta,
+            ),
+            ctx,
+            self.config.max_frame_len,
+            self.frame_separator,
+            self.config.on_frame_overflow,
         );
         self.source_runtime
             .register_stream_reader(DEFAULT_STREAM_ID, ctx, reader);
@@ -28,8 +28,12 @@
 //! ```
 //!
 //! We try to route the event to the connection with `stream_id` `123`.
+//! With `"close_after_write": true` next to `peer`, the connection is closed for writing
+//! once the event is written.
 use crate::connectors::prelude::*;
-use crate::connectors::sink::channel_sink::ChannelSinkMsg;
+use crate::connectors::sink::channel_sink::{ChannelSinkMsg, WriteBuffer};
+use crate::connectors::utils::diagnostics::{ConnectStep, ConnectStepExt};
+use crate::connectors::utils::frame_limit::{frame_separator, FrameLimitReader, FrameOverflow};
 use async_std::os::unix::net::UnixListener;
 use async_std::path::PathBuf;
 use async_std::task::JoinHandle;
@@ -38,6 +42,10 @@ use async_std::{
     prelude::FutureExt,
 };
 
+use std::num::NonZeroUsize;
+use std::os::unix::fs::PermissionsExt;
+use std::time::Duration;
+
 use super::{UnixSocketReader, UnixSocketWriter};
 
 const URL_SCHEME: &str = "tremor-unix-socket-server";
@@ -46,10 +54,34 @@ const URL_SCHEME: &str = "tremor-unix-socket-server";
 #[serde(deny_unknown_fields)]
 pub(crate) struct Config {
     pub path: String,
+    /// permissions of the socket file, either as octal numeric mode (e.g. `"0660"`)
+    /// or as symbolic mode (e.g. `"=rw"`)
     pub permissions: Option<String>,
     /// receive buffer size
     #[serde(default = "default_buf_size")]
     buf_size: usize,
+    /// send events without `peer` metadata to all connected clients
+    #[serde(default = "default_false")]
+    broadcast: bool,
+    /// timeout in milliseconds for waiting for new connections,
+    /// this bounds how long it takes to notice the connector being stopped
+    #[serde(default = "default_accept_timeout_ms")]
+    accept_timeout_ms: u64,
+    /// keep the raw bytes of each event, before decoding, in the event metadata under `raw`
+    #[serde(default = "default_false")]
+    keep_raw: bool,
+    /// maximum length in bytes of a line, lines are not limited if not set
+    #[serde(default)]
+    max_frame_len: Option<usize>,
+    /// what to do with a line exceeding `max_frame_len`, either `"disconnect"` or `"error"`
+    #[serde(default)]
+    on_frame_overflow: FrameOverflow,
+    /// capacity of the internal channels, overriding the global queue size if set
+    #[serde(default)]
+    queue_size: Option<NonZeroUsize>,
+    /// coalesce the data of multiple events into a single write per connection, writing each event right away if not set
+    #[serde(default)]
+    write_buffer: Option<WriteBuffer>,
 }
 
 impl ConfigImpl for Config {}
@@ -66,21 +98,77 @@ impl ConnectorBuilder for Builder {
 
     async fn build_cfg(
         &self,
-        _: &Alias,
-        _: &ConnectorConfig,
+        alias: &Alias,
+        connector_config: &ConnectorConfig,
         config: &Value,
         _kill_switch: &KillSwitch,
     ) -> Result<Box<dyn Connector>> {
         let config = Config::new(config)?;
-        let (sink_tx, sink_rx) = bounded(crate::QSIZE.load(Ordering::Relaxed));
+        let permissions = config
+            .permissions
+            .as_deref()
+            .map(Permissions::parse)
+            .transpose()
+            .map_err(|e| err_connector_def(alias, &e))?;
+        let (sink_tx, sink_rx) = bounded(queue_size(config.queue_size));
         Ok(Box::new(UnixSocketServer {
             config,
+            permissions,
+            frame_separator: frame_separator(connector_config),
+            instance_host: connector_config.instance_host(),
             sink_tx,
             sink_rx,
         }))
     }
 }
 
+/// Permissions of the socket file
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Permissions {
+    /// octal numeric mode, e.g. `0o660`
+    Numeric(u32),
+    /// symbolic mode, e.g. `=rw`, applied with a umask of `0`
+    Symbolic(String),
+}
+
+impl Permissions {
+    /// the maximum numeric mode, including setuid, setgid and sticky bits
+    const MAX_MODE: u32 = 0o7777;
+
+    /// parse permissions from either an octal numeric string (e.g. `"0660"`, `"0o660"`)
+    /// or a symbolic string (e.g. `"=rw"`)
+    fn parse(permissions: &str) -> Result<Self> {
+        let numeric = permissions.strip_prefix("0o").unwrap_or(permissions);
+        if !numeric.is_empty() && numeric.chars().all(|c| c.is_ascii_digit()) {
+            match u32::from_str_radix(numeric, 8) {
+                Ok(mode) if mode <= Self::MAX_MODE => Ok(Self::Numeric(mode)),
+                _ => Err(format!("Invalid numeric permissions: {permissions}").into()),
+            }
+        } else {
+            let mut mode = file_mode::Mode::empty();
+            mode.set_str_umask(permissions, 0)
+                .map_err(|e| format!("Invalid permissions {permissions}: {e}"))?;
+            Ok(Self::Symbolic(permissions.to_string()))
+        }
+    }
+
+    /// apply these permissions to the file at `path`
+    async fn apply(&self, path: &PathBuf) -> Result<()> {
+        match self {
+            Self::Numeric(mode) => {
+                async_std::fs::set_permissions(path, std::fs::Permissions::from_mode(*mode))
+                    .await?;
+            }
+            Self::Symbolic(mode_description) => {
+                let mut mode = file_mode::Mode::empty();
+                mode.set_str_umask(mode_description, 0)?;
+                mode.set_mode_path(path)?;
+            }
+        }
+        Ok(())
+    }
+}
+
 /// just a `stream_id`
 #[derive(PartialEq, Eq, Hash, Debug, Clone, Copy)]
 struct ConnectionMeta(u64);
@@ -94,12 +182,22 @@ struct ConnectionMeta(u64);
 ///         "peer": 123
 ///     }
 /// }
+/// ```
+///
+/// The `peer` can also be given as a numeric string, e.g. `"123"`.
 fn resolve_connection_meta(meta: &Value) -> Option<ConnectionMeta> {
-    meta.get_u64("peer").map(ConnectionMeta)
+    meta.get_u64("peer")
+        .or_else(|| meta.get_str("peer").and_then(|peer| peer.parse().ok()))
+        .map(ConnectionMeta)
 }
 
 struct UnixSocketServer {
     config: Config,
+    permissions: Option<Permissions>,
+    /// separator of the lines limited by `max_frame_len`
+    frame_separator: u8,
+    /// host of the origin URI of the events received
+    instance_host: String,
     sink_tx: Sender<ChannelSinkMsg<ConnectionMeta>>,
     sink_rx: Receiver<ChannelSinkMsg<ConnectionMeta>>,
 }
@@ -115,8 +213,15 @@ impl Connector for UnixSocketServer {
         source_context: SourceContext,
         builder: SourceManagerBuilder,
     ) -> Result<Option<SourceAddr>> {
-        let sink_runtime = ChannelSinkRuntime::new(self.sink_tx.clone());
-        let source = UnixSocketSource::new(self.config.clone(), sink_runtime);
+        let sink_runtime = ChannelSinkRuntime::new(self.sink_tx.clone())
+            .with_write_buffer(self.config.write_buffer);
+        let source = UnixSocketSource::new(
+            self.config.clone(),
+            self.permissions.clone(),
+            self.frame_separator,
+            self.instance_host.clone(),
+            sink_runtime,
+        );
         builder.spawn(source, source_context).map(Some)
     }
 
@@ -130,13 +235,19 @@ impl Connector for UnixSocketServer {
             builder.reply_tx(),
             self.sink_tx.clone(),
             self.sink_rx.clone(),
-        );
+        )
+        .with_broadcast(self.config.broadcast)
+        .with_flush_on_tick(self.config.write_buffer.is_some())
+        .with_close_after_write(true);
         builder.spawn(sink, ctx).map(Some)
     }
 }
 
 struct UnixSocketSource {
     config: Config,
+    permissions: Option<Permissions>,
+    frame_separator: u8,
+    instance_host: String,
     listener_task: Option<JoinHandle<()>>,
     connection_rx: Receiver<SourceReply>,
     runtime: ChannelSourceRuntime,
@@ -144,11 +255,20 @@ struct UnixSocketSource {
 }
 
 impl UnixSocketSource {
-    fn new(config: Config, sink_runtime: ChannelSinkRuntime<ConnectionMeta>) -> Self {
-        let (tx, rx) = bounded(crate::QSIZE.load(Ordering::Relaxed));
+    fn new(
+        config: Config,
+        permissions: Option<Permissions>,
+        frame_separator: u8,
+        instance_host: String,
+        sink_runtime: ChannelSinkRuntime<ConnectionMeta>,
+    ) -> Self {
+        let (tx, rx) = bounded(queue_size(config.queue_size));
         let runtime = ChannelSourceRuntime::new(tx);
         Self {
             config,
+            permissions,
+            frame_separator,
+            instance_host,
             listener_task: None,
             connection_rx: rx,
             runtime,
@@ -167,13 +287,18 @@ impl Source for UnixSocketSource {
         if path.exists().await {
             async_std::fs::remove_file(&path).await?;
         }
-        let listener = UnixListener::bind(&path).await?;
-        if let Some(mode_description) = self.config.permissions.as_ref() {
-            let mut mode = file_mode::Mode::empty();
-            mode.set_str_umask(mode_description, 0)?;
-            mode.set_mode_path(&path)?;
+        let listener = UnixListener::bind(&path)
+            .await
+            .connect_step(&self.config.path, ConnectStep::Tcp)?;
+        if let Some(permissions) = self.permissions.as_ref() {
+            permissions.apply(&path).await?;
         }
         let buf_size = self.config.buf_size;
+        let max_frame_len = self.config.max_frame_len;
+        let frame_separator = self.frame_separator;
+        let host = self.instance_host.clone();
+        let on_frame_overflow = self.config.on_frame_overflow;
+        let accept_timeout = Duration::from_millis(self.config.accept_timeout_ms);
         let ctx = ctx.clone();
         let runtime = self.runtime.clone();
         let sink_runtime = self.sink_runtime.clone();
@@ -181,12 +306,12 @@ impl Source for UnixSocketSource {
             let mut stream_id_gen = StreamIdGen::default();
             let origin_uri = EventOriginUri {
                 scheme: URL_SCHEME.to_string(),
-                host: hostname(),
+                host,
                 port: None,
                 path: vec![path.display().to_string()],
             };
             while ctx.quiescence_beacon().continue_reading().await {
-                match listener.accept().timeout(ACCEPT_TIMEOUT).await {
+                match listener.accept().timeout(accept_timeout).await {
                     Ok(Ok((stream, _peer_addr))) => {
                         let stream_id: u64 = stream_id_gen.next_stream_id();
                         let connection_meta = ConnectionMeta(stream_id);
@@ -201,12 +326,18 @@ impl Source for UnixSocketSource {
                             let $unix_socket_server = { "peer": 123 };
                         */
                         let meta = ctx.meta(literal!({ "peer": stream_id }));
-                        let reader = UnixSocketReader::new(
-                            stream.clone(),
-                            vec![0; buf_size],
-                            ctx.alias().to_string(),
-                            origin_uri.clone(),
-                            meta,
+                        let reader = FrameLimitReader::new(
+                            UnixSocketReader::new(
+                                stream.clone(),
+                                vec![0; buf_size],
+                                ctx.alias().to_string(),
+                                origin_uri.clone(),
+                                meta,
+                            ),
+                            &ctx,
+                            max_frame_len,
+                            frame_separator,
+                            on_frame_overflow,
                         );
                         sink_runtime
                             .register_stream_writer(
@@ -245,4 +376,77 @@ impl Source for UnixSocketSource {
     fn asynchronous(&self) -> bool {
         true
     }
+
+    fn keep_raw(&self) -> bool {
+        self.config.keep_raw
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{resolve_connection_meta, Config, ConnectionMeta, Permissions};
+    use crate::connectors::prelude::{default_accept_timeout_ms, ConfigImpl};
+    use crate::errors::Result;
+    use tremor_value::literal;
+
+    #[test]
+    fn accept_timeout() -> Result<()> {
+        let config = Config::new(&literal!({"path": "/tmp/snot.sock"}))?;
+        assert_eq!(default_accept_timeout_ms(), config.accept_timeout_ms);
+        let config = Config::new(&literal!({"path": "/tmp/snot.sock", "accept_timeout_ms": 5}))?;
+        assert_eq!(5, config.accept_timeout_ms);
+        Ok(())
+    }
+
+    #[test]
+    fn resolve_numeric_peer() {
+        assert_eq!(
+            Some(ConnectionMeta(123)),
+            resolve_connection_meta(&literal!({"peer": 123}))
+        );
+    }
+
+    #[test]
+    fn resolve_numeric_string_peer() {
+        assert_eq!(
+            Some(ConnectionMeta(123)),
+            resolve_connection_meta(&literal!({"peer": "123"}))
+        );
+    }
+
+    #[test]
+    fn resolve_invalid_peer() {
+        assert_eq!(None, resolve_connection_meta(&literal!({"peer": "snot"})));
+        assert_eq!(None, resolve_connection_meta(&literal!({"peer": -1})));
+        assert_eq!(None, resolve_connection_meta(&literal!({"peer": [1]})));
+        assert_eq!(None, resolve_connection_meta(&literal!({})));
+    }
+
+    #[test]
+    fn numeric_permissions() -> Result<()> {
+        assert_eq!(Permissions::Numeric(0o660), Permissions::parse("0660")?);
+        assert_eq!(Permissions::Numeric(0o660), Permissions::parse("660")?);
+        assert_eq!(Permissions::Numeric(0o755), Permissions::parse("0o755")?);
+        Ok(())
+    }
+
+    #[test]
+    fn symbolic_permissions() -> Result<()> {
+        assert_eq!(
+            Permissions::Symbolic("=777".to_string()),
+            Permissions::parse("=777")?
+        );
+        assert_eq!(
+            Permissions::Symbolic("u=rw,g=r".to_string()),
+            Permissions::parse("u=rw,g=r")?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_permissions() {
+        assert!(Permissions::parse("0999").is_err());
+        assert!(Permissions::parse("17777").is_err());
+        assert!(Permissions::parse("snot").is_err());
+    }
 }
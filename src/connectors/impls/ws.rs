@@ -16,12 +16,31 @@ pub(crate) mod client;
 pub(crate) mod server;
 
 use crate::connectors::prelude::*;
+use async_tungstenite::tungstenite::protocol::CloseFrame;
 use async_tungstenite::tungstenite::Message;
 use async_tungstenite::WebSocketStream;
+use beef::Cow;
 use futures::prelude::*;
 use futures::stream::{SplitSink, SplitStream};
 use simd_json::StaticNode;
 
+/// Port ping and pong frames are sent to if `control_frames` is enabled
+const CONTROL: Cow<'static, str> = Cow::const_str("control");
+const OUT_PORTS: [Cow<'static, str>; 3] = [OUT, ERR, CONTROL];
+const OUT_PORTS_REF: &[Cow<'static, str>; 3] = &OUT_PORTS;
+
+/// Frame type events are written with
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Frame {
+    /// always text frames, data that is not valid UTF-8 fails the event
+    Text,
+    /// always binary frames
+    Binary,
+    /// text frames for valid UTF-8 data, binary frames otherwise
+    Auto,
+}
+
 pub(crate) struct WsDefaults;
 impl Defaults for WsDefaults {
     const SCHEME: &'static str = "ws";
@@ -41,6 +60,10 @@ where
     origin_uri: EventOriginUri,
     meta: Value<'static>,
     ctx: Ctx,
+    done: StreamDone,
+    control_frames: bool,
+    close_events: bool,
+    end: Option<SourceReply>,
 }
 
 impl<Stream, Ctx, Runtime> WsReader<Stream, Ctx, Runtime>
@@ -62,7 +85,98 @@ where
             origin_uri,
             meta,
             ctx,
+            done: StreamDone::StreamClosed,
+            control_frames: false,
+            close_events: false,
+            end: None,
+        }
+    }
+
+    /// Emit ping and pong frames as events to the `control` port instead of ignoring them
+    #[must_use]
+    fn with_control_frames(mut self, control_frames: bool) -> Self {
+        self.control_frames = control_frames;
+        self
+    }
+
+    /// Emit an event without data when the connection is closed, carrying the close code and reason
+    #[must_use]
+    fn with_close_events(mut self, close_events: bool) -> Self {
+        self.close_events = close_events;
+        self
+    }
+
+    /// the reader metadata, tagged with the opcode of the frame it belongs to
+    fn frame_meta(&self, opcode: &'static str) -> Value<'static> {
+        let mut meta = self.meta.clone();
+        if let Some(inner) = meta.get_mut(self.ctx.connector_type().to_string().as_str()) {
+            inner.try_insert("opcode", opcode);
         }
+        meta
+    }
+
+    /// an event for a ping or pong frame, carrying the payload as bytes
+    fn control_frame(&self, stream: u64, data: Vec<u8>, opcode: &'static str) -> SourceReply {
+        SourceReply::Structured {
+            origin_uri: self.origin_uri.clone(),
+            payload: (Value::Bytes(data.into()), self.frame_meta(opcode)).into(),
+            stream,
+            port: Some(CONTROL),
+        }
+    }
+
+    /// Ends the stream, preceded by an event describing why the connection was closed
+    /// if `close_events` is enabled.
+    ///
+    /// `frame` is the close frame sent by the peer, `error` the error the stream failed with.
+    fn close(
+        &mut self,
+        stream: u64,
+        frame: Option<CloseFrame>,
+        error: Option<String>,
+    ) -> SourceReply {
+        let (code, reason) = frame.map_or((Value::const_null(), Value::const_null()), |f| {
+            (
+                Value::from(u16::from(f.code)),
+                Value::from(f.reason.into_owned()),
+            )
+        });
+        let end = SourceReply::EndStream {
+            origin_uri: self.origin_uri.clone(),
+            stream,
+            meta: Some(self.meta.clone()),
+        };
+        if !self.close_events {
+            debug!(
+                "{} Connection closed with code {code} and reason {reason}",
+                self.ctx
+            );
+            return end;
+        }
+        let mut meta = self.frame_meta("close");
+        if let Some(inner) = meta.get_mut(self.ctx.connector_type().to_string().as_str()) {
+            inner.try_insert("event", "disconnected");
+            inner.try_insert("code", code);
+            inner.try_insert("reason", reason);
+            if let Some(error) = error {
+                inner.try_insert("error", error);
+            }
+        }
+        // emit the close event first and end the stream afterwards
+        self.end = Some(end);
+        SourceReply::Structured {
+            origin_uri: self.origin_uri.clone(),
+            payload: (Value::const_null(), meta).into(),
+            stream,
+            port: None,
+        }
+    }
+
+    /// Treat the end of the stream as a lost connection, so the connector reconnects
+    #[must_use]
+    fn with_reconnect(mut self) -> Self {
+        self.done = StreamDone::ConnectorClosed;
+        self
     }
 }
 
@@ -81,35 +195,36 @@ where
         })
     }
     async fn read(&mut self, stream: u64) -> Result<SourceReply> {
-        let mut is_binary = false;
+        if let Some(end) = self.end.take() {
+            return Ok(end);
+        }
         match self.stream.next().await {
             Some(Ok(message)) => {
-                let data = match message {
-                    Message::Text(text) => text.into_bytes(),
-                    Message::Binary(binary) => {
-                        is_binary = true;
-                        binary
+                let (data, opcode) = match message {
+                    Message::Text(text) => (text.into_bytes(), "text"),
+                    Message::Binary(binary) => (binary, "binary"),
+                    Message::Ping(data) if self.control_frames => {
+                        return Ok(self.control_frame(stream, data, "ping"));
+                    }
+                    Message::Pong(data) if self.control_frames => {
+                        return Ok(self.control_frame(stream, data, "pong"));
                     }
-                    Message::Close(_) => {
+                    Message::Close(frame) => {
                         // read from the stream once again to drive the closing handshake
                         let after_close = self.stream.next().await;
                         debug_assert!(
                             after_close.is_none(),
                             "WS reader not behaving as expected after receiving a close message"
                         );
-                        return Ok(SourceReply::EndStream {
-                            origin_uri: self.origin_uri.clone(),
-                            stream,
-                            meta: Some(self.meta.clone()),
-                        });
+                        return Ok(self.close(stream, frame, None));
                     }
                     Message::Ping(_) | Message::Pong(_) | Message::Frame(_) => {
                         // ignore those, but don't let the source wait
                         return self.read(stream).await;
                     }
                 };
-                let mut meta = self.meta.clone();
-                if is_binary {
+                let mut meta = self.frame_meta(opcode);
+                if opcode == "binary" {
                     meta.insert("binary", Value::Static(StaticNode::Bool(true)))?;
                 };
                 Ok(SourceReply::Data {
@@ -121,11 +236,8 @@ where
                     codec_overwrite: None,
                 })
             }
-            Some(Err(_)) | None => Ok(SourceReply::EndStream {
-                origin_uri: self.origin_uri.clone(),
-                stream,
-                meta: Some(self.meta.clone()),
-            }),
+            Some(Err(e)) => Ok(self.close(stream, None, Some(e.to_string()))),
+            None => Ok(self.close(stream, None, None)),
         }
     }
 
@@ -135,7 +247,7 @@ where
             self.sink_runtime.unregister_stream_writer(stream).await,
             "Error unregistering stream",
         );
-        StreamDone::StreamClosed
+        self.done
     }
 }
 
@@ -144,11 +256,12 @@ where
     S: async_std::io::Read + async_std::io::Write + std::marker::Unpin + std::marker::Sync,
 {
     sink: SplitSink<WebSocketStream<S>, Message>,
+    frame: Option<Frame>,
 }
 
 impl WsWriter<async_std::net::TcpStream> {
     fn new(sink: SplitSink<WebSocketStream<async_std::net::TcpStream>, Message>) -> Self {
-        Self { sink }
+        Self { sink, frame: None }
     }
 }
 
@@ -159,7 +272,7 @@ impl WsWriter<async_tls::server::TlsStream<async_std::net::TcpStream>> {
             Message,
         >,
     ) -> Self {
-        Self { sink }
+        Self { sink, frame: None }
     }
 }
 
@@ -167,7 +280,7 @@ impl WsWriter<async_std::net::TcpStream> {
     fn new_tungstenite_client(
         sink: SplitSink<WebSocketStream<async_std::net::TcpStream>, Message>,
     ) -> Self {
-        Self { sink }
+        Self { sink, frame: None }
     }
 }
 
@@ -178,7 +291,38 @@ impl WsWriter<async_tls::client::TlsStream<async_std::net::TcpStream>> {
             Message,
         >,
     ) -> Self {
-        Self { sink }
+        Self { sink, frame: None }
+    }
+}
+
+impl<S> WsWriter<S>
+where
+    S: async_std::io::Read + async_std::io::Write + std::marker::Unpin + std::marker::Sync,
+{
+    /// Write all events with the given frame type, instead of deciding by the `binary` metadata flag
+    #[must_use]
+    fn with_frame(mut self, frame: Option<Frame>) -> Self {
+        self.frame = frame;
+        self
+    }
+
+    /// the message to send `chunk` with
+    fn message(&self, chunk: Vec<u8>, meta: Option<&SinkMeta>) -> Result<Message> {
+        let message = match self.frame {
+            Some(Frame::Text) => Message::Text(String::from_utf8(chunk)?),
+            Some(Frame::Binary) => Message::Binary(chunk),
+            Some(Frame::Auto) => match String::from_utf8(chunk) {
+                Ok(text) => Message::Text(text),
+                Err(e) => Message::Binary(e.into_bytes()),
+            },
+            // If metadata is set, check for a binary framing flag
+            None if meta.and_then(|meta| meta.get_bool("binary")) == Some(true) => {
+                Message::Binary(chunk)
+            }
+            // default to text ws framing
+            None => Message::Text(String::from_utf8(chunk)?),
+        };
+        Ok(message)
     }
 }
 
@@ -193,22 +337,8 @@ where
 {
     async fn write(&mut self, data: Vec<Vec<u8>>, meta: Option<SinkMeta>) -> Result<()> {
         for chunk in data {
-            if let Some(meta) = &meta {
-                // If metadata is set, check for a binary framing flag
-                if let Some(true) = meta.get_bool("binary") {
-                    let message = Message::Binary(chunk);
-                    self.sink.send(message).await?;
-                } else {
-                    let message = std::str::from_utf8(&chunk)?;
-                    let message = Message::Text(message.to_string());
-                    self.sink.send(message).await?;
-                }
-            } else {
-                // No metadata, default to text ws framing
-                let message = std::str::from_utf8(&chunk)?;
-                let message = Message::Text(message.to_string());
-                self.sink.send(message).await?;
-            };
+            let message = self.message(chunk, meta.as_ref())?;
+            self.sink.send(message).await?;
         }
         Ok(())
     }
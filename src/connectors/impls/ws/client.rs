@@ -16,14 +16,22 @@
 #![allow(clippy::module_name_repetitions)]
 
 use super::{WsReader, WsWriter};
+use crate::connectors::utils::dns::DnsCache;
 use crate::connectors::utils::tls::{tls_client_connector, TLSClientConfig};
 use crate::{connectors::prelude::*, errors::err_connector_def};
 use async_std::net::TcpStream;
 use async_tls::TlsConnector;
 use async_tungstenite::client_async;
+use async_tungstenite::tungstenite::client::IntoClientRequest;
+use async_tungstenite::tungstenite::handshake::client::Request;
+use async_tungstenite::tungstenite::http::header::{
+    HeaderName, HeaderValue, SEC_WEBSOCKET_PROTOCOL,
+};
 use either::Either;
 use futures::StreamExt;
+use std::collections::HashMap;
 use std::net::SocketAddr;
+use std::time::Duration;
 
 const URL_SCHEME: &str = "tremor-ws-client";
 
@@ -35,6 +43,19 @@ pub(crate) struct Config {
     no_delay: bool,
     #[serde(with = "either::serde_untagged_optional", default = "Default::default")]
     tls: Option<Either<TLSClientConfig, bool>>,
+    /// HTTP headers to send along with the handshake request
+    #[serde(default)]
+    headers: HashMap<String, String>,
+    /// WebSocket subprotocol to request during the handshake
+    #[serde(default)]
+    subprotocol: Option<String>,
+    /// keep the raw bytes of each event, before decoding, in the event metadata under `raw`
+    #[serde(default = "default_false")]
+    keep_raw: bool,
+    /// milliseconds for which the resolved addresses of `url` are reused, until connecting to them fails.
+    /// The host is resolved again on every connect if not set.
+    #[serde(default)]
+    dns_cache_ttl_ms: Option<u64>,
 }
 
 impl ConfigImpl for Config {}
@@ -92,8 +113,25 @@ impl ConnectorBuilder for Builder {
             Some(Either::Right(false)) | None => (None, host),
         };
 
+        let mut headers = Vec::with_capacity(config.headers.len() + 1);
+        for (name, value) in &config.headers {
+            let name = HeaderName::from_bytes(name.as_bytes())
+                .map_err(|e| err_connector_def(id, &format!("Invalid header `{name}`: {e}")))?;
+            let value = HeaderValue::from_str(value)
+                .map_err(|e| err_connector_def(id, &format!("Invalid value for `{name}`: {e}")))?;
+            headers.push((name, value));
+        }
+        if let Some(subprotocol) = config.subprotocol.as_ref() {
+            let value = HeaderValue::from_str(subprotocol)
+                .map_err(|e| err_connector_def(id, &format!("Invalid `subprotocol`: {e}")))?;
+            headers.push((SEC_WEBSOCKET_PROTOCOL, value));
+        }
+
+        let dns_cache = DnsCache::new(config.dns_cache_ttl_ms.map(Duration::from_millis));
         Ok(Box::new(WsClient {
             config,
+            dns_cache,
+            headers,
             tls_connector,
             tls_domain,
             source_runtime: None,
@@ -104,6 +142,8 @@ impl ConnectorBuilder for Builder {
 
 pub(crate) struct WsClient {
     config: Config,
+    dns_cache: DnsCache,
+    headers: Vec<(HeaderName, HeaderValue)>,
     tls_connector: Option<TlsConnector>,
     tls_domain: String,
     source_runtime: Option<ChannelSourceRuntime>,
@@ -123,6 +163,13 @@ impl WsClient {
             }
         })
     }
+
+    /// the handshake request, including the configured headers and subprotocol
+    fn request(&self) -> Result<Request> {
+        let mut request = self.config.url.as_str().into_client_request()?;
+        request.headers_mut().extend(self.headers.iter().cloned());
+        Ok(request)
+    }
 }
 
 #[async_trait::async_trait]
@@ -132,7 +179,7 @@ impl Connector for WsClient {
         source_context: SourceContext,
         builder: SourceManagerBuilder,
     ) -> Result<Option<SourceAddr>> {
-        let source = ChannelSource::new(builder.qsize());
+        let source = ChannelSource::new(builder.qsize()).with_keep_raw(self.config.keep_raw);
         self.source_runtime = Some(source.runtime());
         let addr = builder.spawn(source, source_context)?;
         Ok(Some(addr))
@@ -159,19 +206,20 @@ impl Connector for WsClient {
             .as_ref()
             .ok_or("Sink runtime not initialized")?;
 
-        let tcp_stream = async_std::net::TcpStream::connect((
-            self.config.url.host_or_local(),
-            self.config.url.port_or_dflt(),
-        ))
-        .await?;
+        let tcp_stream = self
+            .dns_cache
+            .connect(
+                self.config.url.host_or_local(),
+                self.config.url.port_or_dflt(),
+            )
+            .await?;
         let (local_addr, peer_addr) = condition_tcp_stream(&self.config, &tcp_stream)?;
 
         if let Some(tls_connector) = self.tls_connector.as_ref() {
             // TLS
             // wrap it into arcmutex, because we need to clone it in order to close it properly
             let tls_stream = tls_connector.connect(&self.tls_domain, tcp_stream).await?;
-            let (ws_stream, _http_response) =
-                client_async(self.config.url.as_str(), tls_stream).await?;
+            let (ws_stream, _http_response) = client_async(self.request()?, tls_stream).await?;
             let origin_uri = EventOriginUri {
                 scheme: URL_SCHEME.to_string(),
                 host: local_addr.ip().to_string(),
@@ -185,12 +233,12 @@ impl Connector for WsClient {
             sink_runtime.register_stream_writer(DEFAULT_STREAM_ID, ctx, ws_writer);
 
             let ws_reader =
-                WsReader::new(reader, sink_runtime.clone(), origin_uri, meta, ctx.clone());
+                WsReader::new(reader, sink_runtime.clone(), origin_uri, meta, ctx.clone())
+                    .with_reconnect();
             source_runtime.register_stream_reader(DEFAULT_STREAM_ID, ctx, ws_reader);
         } else {
             // No TLS
-            let (ws_stream, _http_response) =
-                client_async(self.config.url.as_str(), tcp_stream).await?;
+            let (ws_stream, _http_response) = client_async(self.request()?, tcp_stream).await?;
             let origin_uri = EventOriginUri {
                 scheme: URL_SCHEME.to_string(),
                 host: local_addr.ip().to_string(),
@@ -204,7 +252,8 @@ impl Connector for WsClient {
             sink_runtime.register_stream_writer(DEFAULT_STREAM_ID, ctx, ws_writer);
 
             let ws_reader =
-                WsReader::new(reader, sink_runtime.clone(), origin_uri, meta, ctx.clone());
+                WsReader::new(reader, sink_runtime.clone(), origin_uri, meta, ctx.clone())
+                    .with_reconnect();
             source_runtime.register_stream_reader(DEFAULT_STREAM_ID, ctx, ws_reader);
         }
 
@@ -12,18 +12,21 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use super::{WsReader, WsWriter};
+use super::{Frame, WsReader, WsWriter, OUT_PORTS_REF};
+use crate::connectors::utils::access_log::{AccessLog, AccessLogReader, AccessLogWriter};
 use crate::connectors::utils::tls::{load_server_config, TLSServerConfig};
 use crate::connectors::{prelude::*, utils::ConnectionMeta};
 use async_std::task::JoinHandle;
 use async_std::{net::TcpListener, prelude::FutureExt};
 use async_tls::TlsAcceptor;
 use async_tungstenite::accept_async;
+use beef::Cow;
 use futures::StreamExt;
 use rustls::ServerConfig;
 use simd_json::ValueAccess;
 use std::net::SocketAddr;
 use std::sync::Arc;
+use std::time::Duration;
 
 const URL_SCHEME: &str = "tremor-ws-server";
 
@@ -32,11 +35,58 @@ const URL_SCHEME: &str = "tremor-ws-server";
 pub(crate) struct Config {
     // kept as a str, so it is re-resolved upon each connect
     url: Url<super::WsDefaults>,
+    /// interface to bind to, if different from `url`, which is still used for the event origin
+    bind: Option<Url<super::WsDefaults>>,
     tls: Option<TLSServerConfig>,
+    /// log peer, tls, bytes read and written and duration of each connection when it is closed
+    #[serde(default = "default_false")]
+    access_log: bool,
+    /// timeout in milliseconds for waiting for new connections,
+    /// this bounds how long it takes to notice the connector being stopped
+    #[serde(default = "default_accept_timeout_ms")]
+    accept_timeout_ms: u64,
+    /// timeout in milliseconds for the TLS and websocket handshake of a new connection,
+    /// connections not completing it in time are dropped
+    #[serde(default = "default_handshake_timeout_ms")]
+    handshake_timeout_ms: u64,
+    /// keep the raw bytes of each event, before decoding, in the event metadata under `raw`
+    #[serde(default = "default_false")]
+    keep_raw: bool,
+    /// emit ping and pong frames as events to the `control` port
+    #[serde(default = "default_false")]
+    control_frames: bool,
+    /// emit an event without data when a client closes the connection,
+    /// with `$ws_server.event` set to `"disconnected"` and the `code` and `reason` of the close frame
+    #[serde(default = "default_false")]
+    close_events: bool,
+    /// frame type events are sent with, `text`, `binary` or `auto` for text if the encoded data is valid UTF-8,
+    /// if not set the `binary` metadata flag decides
+    frame: Option<Frame>,
+    /// stop reading from a connection once this many of its messages were neither acked nor failed,
+    /// resuming as acks or fails arrive, so one connection can't flood the source
+    max_in_flight: Option<usize>,
+    /// messages in flight for this long no longer count towards `max_in_flight`,
+    /// e.g. if their events were dropped
+    in_flight_timeout_ms: Option<u64>,
 }
 
 impl ConfigImpl for Config {}
 
+fn default_handshake_timeout_ms() -> u64 {
+    10_000
+}
+
+impl Config {
+    /// host and port to bind to, taken from `bind` if present, otherwise from `url`
+    fn bind_addr(&self) -> (&str, u16) {
+        let url = self.bind.as_ref().unwrap_or(&self.url);
+        let port = url
+            .port()
+            .unwrap_or(if url.scheme() == "wss" { 443 } else { 80 });
+        (url.host_or_local(), port)
+    }
+}
+
 #[allow(clippy::module_name_repetitions)]
 pub(crate) struct WsServer {
     config: Config,
@@ -49,6 +99,11 @@ pub(crate) struct WsServer {
 #[derive(Debug, Default)]
 pub(crate) struct Builder {}
 
+impl Builder {
+    const INVALID_MAX_IN_FLIGHT: &'static str = "`max_in_flight` must be greater than 0";
+    const INVALID_IN_FLIGHT_TIMEOUT: &'static str = "`in_flight_timeout_ms` must be greater than 0";
+}
+
 #[async_trait::async_trait]
 impl ConnectorBuilder for Builder {
     fn connector_type(&self) -> ConnectorType {
@@ -56,12 +111,18 @@ impl ConnectorBuilder for Builder {
     }
     async fn build_cfg(
         &self,
-        _id: &Alias,
+        id: &Alias,
         _: &ConnectorConfig,
         raw_config: &Value,
         _kill_switch: &KillSwitch,
     ) -> crate::errors::Result<Box<dyn Connector>> {
         let config = Config::new(raw_config)?;
+        if config.max_in_flight == Some(0) {
+            return Err(err_connector_def(id, Self::INVALID_MAX_IN_FLIGHT));
+        }
+        if config.in_flight_timeout_ms == Some(0) {
+            return Err(err_connector_def(id, Self::INVALID_IN_FLIGHT_TIMEOUT));
+        }
 
         let tls_server_config = if let Some(tls_config) = config.tls.as_ref() {
             Some(load_server_config(tls_config)?)
@@ -108,6 +169,14 @@ impl WsServer {
 
 #[async_trait::async_trait()]
 impl Connector for WsServer {
+    fn output_ports(&self) -> &[Cow<'static, str>] {
+        if self.config.control_frames {
+            OUT_PORTS_REF
+        } else {
+            &OUT_PORTS_REF[..2]
+        }
+    }
+
     async fn on_stop(&mut self, _ctx: &ConnectorContext) -> Result<()> {
         if let Some(accept_task) = self.accept_task.take() {
             // stop acceptin' new connections
@@ -121,7 +190,12 @@ impl Connector for WsServer {
         ctx: SourceContext,
         builder: SourceManagerBuilder,
     ) -> Result<Option<SourceAddr>> {
-        let source = ChannelSource::new(builder.qsize());
+        let source = ChannelSource::new(builder.qsize())
+            .with_keep_raw(self.config.keep_raw)
+            .with_max_in_flight(
+                self.config.max_in_flight,
+                self.config.in_flight_timeout_ms.map(Duration::from_millis),
+            );
         self.source_runtime = Some(source.runtime());
         let addr = builder.spawn(source, ctx)?;
 
@@ -164,26 +238,22 @@ impl Connector for WsServer {
         }
 
         // TODO: allow for other sockets
-        let host = self.config.url.host_or_local();
-        let port = self
-            .config
-            .url
-            .port()
-            .unwrap_or(if self.config.url.scheme() == "wss" {
-                443
-            } else {
-                80
-            });
-        let listener = TcpListener::bind((host, port)).await?;
+        let listener = TcpListener::bind(self.config.bind_addr()).await?;
 
         let ctx = ctx.clone();
         let tls_server_config = self.tls_server_config.clone();
+        let access_log = self.config.access_log;
+        let control_frames = self.config.control_frames;
+        let close_events = self.config.close_events;
+        let frame = self.config.frame;
+        let accept_timeout = Duration::from_millis(self.config.accept_timeout_ms);
+        let handshake_timeout = Duration::from_millis(self.config.handshake_timeout_ms);
 
         // accept task
         self.accept_task = Some(spawn_task(ctx.clone(), async move {
             let mut stream_id_gen = StreamIdGen::default();
             while ctx.quiescence_beacon.continue_reading().await {
-                match listener.accept().timeout(ACCEPT_TIMEOUT).await {
+                match listener.accept().timeout(accept_timeout).await {
                     Ok(Ok((tcp_stream, peer_addr))) => {
                         let stream_id: u64 = stream_id_gen.next_stream_id();
                         let connection_meta: ConnectionMeta = peer_addr.into();
@@ -203,13 +273,27 @@ impl Connector for WsServer {
                         if let Some(acceptor) = tls_acceptor {
                             let meta = ctx.meta(WsServer::meta(peer_addr, true));
                             // TODO: this should live in its own task, as it requires rome roundtrips :()
-                            let tls_stream = acceptor.accept(tcp_stream).await?;
-                            let ws_stream = accept_async(tls_stream).await?;
+                            let handshake = async {
+                                let tls_stream = acceptor.accept(tcp_stream).await?;
+                                Ok::<_, Error>(accept_async(tls_stream).await?)
+                            };
+                            let ws_stream = match handshake.timeout(handshake_timeout).await {
+                                Ok(ws_stream) => ws_stream?,
+                                Err(_) => {
+                                    warn!("{ctx} Handshake with {peer_addr} timed out after {handshake_timeout:?}, dropping the connection.");
+                                    continue;
+                                }
+                            };
                             debug!("{ctx} new connection from {peer_addr}");
 
                             let (ws_write, ws_read) = ws_stream.split();
 
-                            let ws_writer = WsWriter::new_tls_server(ws_write);
+                            let access_log = access_log
+                                .then(|| AccessLog::new(&ctx, connection_meta.clone(), true));
+                            let ws_writer = AccessLogWriter::new(
+                                WsWriter::new_tls_server(ws_write).with_frame(frame),
+                                access_log.clone(),
+                            );
                             sink_runtime
                                 .register_stream_writer(
                                     stream_id,
@@ -219,21 +303,33 @@ impl Connector for WsServer {
                                 )
                                 .await;
 
-                            let ws_reader = WsReader::new(
-                                ws_read,
-                                sink_runtime.clone(),
-                                origin_uri.clone(),
-                                meta,
-                                ctx.clone(),
+                            let ws_reader = AccessLogReader::new(
+                                WsReader::new(
+                                    ws_read,
+                                    sink_runtime.clone(),
+                                    origin_uri.clone(),
+                                    meta,
+                                    ctx.clone(),
+                                )
+                                .with_control_frames(control_frames)
+                                .with_close_events(close_events),
+                                access_log,
                             );
                             source_runtime.register_stream_reader(stream_id, &ctx, ws_reader);
                         } else {
-                            let ws_stream = match accept_async(tcp_stream).await {
-                                Ok(s) => s,
-                                Err(e) => {
+                            let ws_stream = match accept_async(tcp_stream)
+                                .timeout(handshake_timeout)
+                                .await
+                            {
+                                Ok(Ok(s)) => s,
+                                Ok(Err(e)) => {
                                     error!("{ctx} Websocket connection error: {e}");
                                     continue;
                                 }
+                                Err(_) => {
+                                    warn!("{ctx} Handshake with {peer_addr} timed out after {handshake_timeout:?}, dropping the connection.");
+                                    continue;
+                                }
                             };
                             debug!("{ctx} new connection from {peer_addr}",);
 
@@ -241,7 +337,12 @@ impl Connector for WsServer {
 
                             let meta = ctx.meta(WsServer::meta(peer_addr, false));
 
-                            let ws_writer = WsWriter::new(ws_write);
+                            let access_log = access_log
+                                .then(|| AccessLog::new(&ctx, connection_meta.clone(), false));
+                            let ws_writer = AccessLogWriter::new(
+                                WsWriter::new(ws_write).with_frame(frame),
+                                access_log.clone(),
+                            );
 
                             sink_runtime
                                 .register_stream_writer(
@@ -252,12 +353,17 @@ impl Connector for WsServer {
                                 )
                                 .await;
 
-                            let ws_reader = WsReader::new(
-                                ws_read,
-                                sink_runtime.clone(),
-                                origin_uri.clone(),
-                                meta,
-                                ctx.clone(),
+                            let ws_reader = AccessLogReader::new(
+                                WsReader::new(
+                                    ws_read,
+                                    sink_runtime.clone(),
+                                    origin_uri.clone(),
+                                    meta,
+                                    ctx.clone(),
+                                )
+                                .with_control_frames(control_frames)
+                                .with_close_events(close_events),
+                                access_log,
                             );
                             source_runtime.register_stream_reader(stream_id, &ctx, ws_reader);
                         }
@@ -276,3 +382,37 @@ impl Connector for WsServer {
         CodecReq::Required
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bind_defaults_to_url() -> Result<()> {
+        let config = Config::new(&literal!({"url": "ws://snot:4242"}))?;
+        assert_eq!(("snot", 4242), config.bind_addr());
+        let config = Config::new(&literal!({"url": "wss://snot"}))?;
+        assert_eq!(("snot", 443), config.bind_addr());
+        Ok(())
+    }
+
+    #[test]
+    fn bind_separate_from_url() -> Result<()> {
+        let config = Config::new(&literal!({
+            "url": "ws://snot.badger:80",
+            "bind": "0.0.0.0:4242"
+        }))?;
+        assert_eq!(("0.0.0.0", 4242), config.bind_addr());
+        assert_eq!(Some("snot.badger"), config.url.host_str());
+        Ok(())
+    }
+
+    #[test]
+    fn accept_timeout() -> Result<()> {
+        let config = Config::new(&literal!({"url": "ws://snot:4242"}))?;
+        assert_eq!(default_accept_timeout_ms(), config.accept_timeout_ms);
+        let config = Config::new(&literal!({"url": "ws://snot:4242", "accept_timeout_ms": 5}))?;
+        assert_eq!(5, config.accept_timeout_ms);
+        Ok(())
+    }
+}
@@ -23,12 +23,13 @@ pub(crate) use crate::connectors::source::{
     SourceReply, StreamReader,
 };
 pub(crate) use crate::connectors::utils::{
+    queue_size,
     reconnect::Attempt,
     url::{Defaults, HttpsDefaults, Url},
 };
 pub(crate) use crate::connectors::{
-    metrics::make_metrics_payload, spawn_task, Alias, CodecReq, Connector, ConnectorBuilder,
-    ConnectorContext, ConnectorType, Context, StreamDone, StreamIdGen, ACCEPT_TIMEOUT,
+    default_accept_timeout_ms, metrics::make_metrics_payload, spawn_task, Alias, CodecReq,
+    Connector, ConnectorBuilder, ConnectorContext, ConnectorType, Context, StreamDone, StreamIdGen,
 };
 pub(crate) use crate::errors::{err_connector_def, Error, Kind as ErrorKind, Result};
 pub(crate) use crate::system::KillSwitch;
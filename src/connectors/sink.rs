@@ -18,6 +18,8 @@
 pub(crate) mod channel_sink;
 /// Utility for limiting concurrency (by sending `CB::Close` messages when a maximum concurrency value is reached)
 pub(crate) mod concurrency_cap;
+/// Retrying events a sink failed, with a dead letter for events failing all retries
+pub(crate) mod retry;
 /// Providing a `Sink` implementation for connectors handling only a single Stream
 pub(crate) mod single_stream_sink;
 
@@ -616,6 +618,8 @@ where
     #[allow(clippy::too_many_lines)]
     async fn run(mut self) -> Result<()> {
         use SinkState::{Drained, Draining, Initialized, Paused, Running, Stopped};
+        // replies sent while stopping are handled after the control plane is exited
+        let late_replies = self.reply_rx.clone();
         let from_sink = self.reply_rx.map(SinkMsgWrapper::FromSink);
         let to_sink = self.rx.map(SinkMsgWrapper::ToSink);
         let mut from_and_to_sink_channel = PriorityMerge::new(from_sink, to_sink);
@@ -668,10 +672,13 @@ where
                         SinkMsg::Stop(sender) => {
                             info!("{} Stopping...", &self.ctx);
                             self.state = Stopped;
-                            self.ctx.swallow_err(
-                                sender.send(self.sink.on_stop(&self.ctx).await).await,
-                                "Error sending Stop reply",
-                            );
+                            let res = self.sink.on_stop(&self.ctx).await;
+                            // the sink might have flushed events on stop
+                            while let Ok(reply) = late_replies.try_recv() {
+                                handle_async_reply(reply, &self.pipelines, &self.ctx).await;
+                            }
+                            self.ctx
+                                .swallow_err(sender.send(res).await, "Error sending Stop reply");
                             // exit control plane
                             break;
                         }
@@ -847,22 +854,7 @@ where
                     }
                 }
                 SinkMsgWrapper::FromSink(reply) => {
-                    // handle asynchronous sink replies
-                    let cf = match reply {
-                        AsyncSinkReply::Ack(data, duration) => Event::cb_ack_with_timing(
-                            data.ingest_ns,
-                            data.event_id,
-                            data.op_meta,
-                            duration,
-                        ),
-                        AsyncSinkReply::Fail(data) => {
-                            Event::cb_fail(data.ingest_ns, data.event_id, data.op_meta)
-                        }
-                        AsyncSinkReply::CB(data, cb) => {
-                            Event::insight(cb, data.event_id, data.ingest_ns, data.op_meta)
-                        }
-                    };
-                    send_contraflow(&self.pipelines, &self.ctx, cf).await;
+                    handle_async_reply(reply, &self.pipelines, &self.ctx).await;
                 }
             }
         }
@@ -938,6 +930,24 @@ async fn send_contraflow(
     }
 }
 
+/// turn an asynchronous sink reply into contraflow
+async fn handle_async_reply(
+    reply: AsyncSinkReply,
+    pipelines: &[(DeployEndpoint, pipeline::Addr)],
+    connector_ctx: &impl Context,
+) {
+    let cf = match reply {
+        AsyncSinkReply::Ack(data, duration) => {
+            Event::cb_ack_with_timing(data.ingest_ns, data.event_id, data.op_meta, duration)
+        }
+        AsyncSinkReply::Fail(data) => Event::cb_fail(data.ingest_ns, data.event_id, data.op_meta),
+        AsyncSinkReply::CB(data, cb) => {
+            Event::insight(cb, data.event_id, data.ingest_ns, data.op_meta)
+        }
+    };
+    send_contraflow(pipelines, connector_ctx, cf).await;
+}
+
 async fn handle_replies(
     reply: SinkReply,
     duration: u64,
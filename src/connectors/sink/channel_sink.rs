@@ -84,6 +84,89 @@ pub(crate) struct SinkData {
     pub(crate) meta: Option<SinkMeta>,
     /// timestamp of processing start
     pub(crate) start: u64,
+    /// close the stream once this data is written
+    pub(crate) close_after_write: bool,
+}
+
+impl SinkData {
+    /// marker flushing the write buffer of a stream, if it has one
+    fn flush() -> Self {
+        Self {
+            data: vec![],
+            contraflow: None,
+            meta: None,
+            start: 0,
+            close_after_write: false,
+        }
+    }
+
+    fn is_flush(&self) -> bool {
+        self.data.is_empty() && self.contraflow.is_none() && !self.close_after_write
+    }
+}
+
+fn default_flush_delay_ms() -> u64 {
+    5
+}
+
+fn default_max_buffered_bytes() -> usize {
+    64 * 1024
+}
+
+/// Coalesces the data of multiple events into a single write to a stream
+///
+/// The buffer is written once `max_buffered_bytes` are reached, after `flush_delay_ms` or on the next tick,
+/// whichever happens first. Only the metadata of the first buffered event is passed on to the `StreamWriter`.
+/// Data closing the stream after it is written is never buffered together with later data.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct WriteBuffer {
+    /// how long to wait in milliseconds for more data after the first buffered event
+    #[serde(default = "default_flush_delay_ms")]
+    pub(crate) flush_delay_ms: u64,
+    /// size of the buffer in bytes
+    #[serde(default = "default_max_buffered_bytes")]
+    pub(crate) max_buffered_bytes: usize,
+}
+
+/// the contraflow of the events of a single write, with their processing start
+type PendingReplies = Vec<((ContraflowData, Sender<AsyncSinkReply>), u64)>;
+
+impl WriteBuffer {
+    /// Buffers `first` and whatever else arrives on `rx` until the buffer is to be written,
+    /// returning whether the stream is to be closed after writing it
+    async fn coalesce(
+        &self,
+        first: SinkData,
+        rx: &Receiver<SinkData>,
+    ) -> (Vec<Vec<u8>>, Option<SinkMeta>, PendingReplies, bool) {
+        let deadline = std::time::Instant::now() + Duration::from_millis(self.flush_delay_ms);
+        let SinkData {
+            data,
+            meta,
+            contraflow,
+            start,
+            mut close_after_write,
+        } = first;
+        let mut buffer = data.concat();
+        let mut replies: PendingReplies = contraflow.map(|cf| (cf, start)).into_iter().collect();
+        while !close_after_write && buffer.len() < self.max_buffered_bytes {
+            let remaining = deadline.saturating_duration_since(std::time::Instant::now());
+            match rx.recv().timeout(remaining).await {
+                Ok(Ok(next)) if next.is_flush() => break,
+                Ok(Ok(next)) => {
+                    for chunk in next.data {
+                        buffer.extend_from_slice(&chunk);
+                    }
+                    replies.extend(next.contraflow.map(|cf| (cf, next.start)));
+                    close_after_write = next.close_after_write;
+                }
+                // flush delay passed, or the stream is gone, which the next receive notices
+                Ok(Err(_)) | Err(_) => break,
+            }
+        }
+        (vec![buffer], meta, replies, close_after_write)
+    }
 }
 
 /// tracking 1 channel per stream
@@ -100,6 +183,10 @@ where
     tx: Sender<ChannelSinkMsg<M>>,
     rx: Receiver<ChannelSinkMsg<M>>,
     reply_tx: Sender<AsyncSinkReply>,
+    broadcast: bool,
+    stream_id_routing: bool,
+    flush_on_tick: bool,
+    close_after_write: bool,
 }
 
 impl<T, F> ChannelSink<T, F, NoMeta>
@@ -158,15 +245,57 @@ where
             tx,
             rx,
             reply_tx,
+            broadcast: false,
+            stream_id_routing: false,
+            flush_on_tick: false,
+            close_after_write: false,
             _b: PhantomData::default(),
         }
     }
 
+    /// send events without stream metadata to all registered streams,
+    /// instead of only to the streams tracked in the event id
+    #[must_use]
+    pub(crate) fn with_broadcast(mut self, broadcast: bool) -> Self {
+        self.broadcast = broadcast;
+        self
+    }
+
+    /// route events with a `stream_id` in their sink metadata to the stream with that id,
+    /// before resolving the stream via the connection metadata
+    #[must_use]
+    pub(crate) fn with_stream_id_routing(mut self, stream_id_routing: bool) -> Self {
+        self.stream_id_routing = stream_id_routing;
+        self
+    }
+
+    /// flush the write buffers of all streams on each tick,
+    /// for streams registered with a `WriteBuffer`
+    #[must_use]
+    pub(crate) fn with_flush_on_tick(mut self, flush_on_tick: bool) -> Self {
+        self.flush_on_tick = flush_on_tick;
+        self
+    }
+
+    /// close the stream an event is written to if `close_after_write` is set to `true`
+    /// in its sink metadata, once the event is written
+    #[must_use]
+    pub(crate) fn with_close_after_write(mut self, close_after_write: bool) -> Self {
+        self.close_after_write = close_after_write;
+        self
+    }
+
+    /// if the stream is to be closed after writing the event with the metadata `meta`
+    fn closes_after_write(&self, meta: &Value, ctx: &SinkContext) -> bool {
+        self.close_after_write
+            && get_sink_meta(meta, ctx)
+                .and_then(|sink_meta| sink_meta.get_bool("close_after_write"))
+                .unwrap_or_default()
+    }
+
     /// hand out a `ChannelSinkRuntime` instance in order to register stream writers
     pub(crate) fn runtime(&self) -> ChannelSinkRuntime<T> {
-        ChannelSinkRuntime {
-            tx: self.tx.clone(),
-        }
+        ChannelSinkRuntime::new(self.tx.clone())
     }
 
     /// returns true, if there are no more channels to send stuff to
@@ -215,15 +344,16 @@ where
         meta: &'lt Value<'value>,
         ctx: &SinkContext,
     ) -> Option<(&u64, &Sender<SinkData>)> {
-        let sink_meta = get_sink_meta(meta, ctx);
-        sink_meta
-            .and_then(|sink_meta| (self.resolver)(sink_meta))
-            .and_then(|stream_meta| self.streams_meta.get_by_left(&stream_meta))
-            .and_then(|stream_id| {
-                self.streams
-                    .get(stream_id)
-                    .map(|sender| (stream_id, sender))
-            })
+        let sink_meta = get_sink_meta(meta, ctx)?;
+        let stream_id = self
+            .stream_id_routing
+            .then(|| sink_meta.get_u64("stream_id"))
+            .flatten()
+            .or_else(|| {
+                (self.resolver)(sink_meta)
+                    .and_then(|stream_meta| self.streams_meta.get_by_left(&stream_meta).copied())
+            })?;
+        self.streams.get_key_value(&stream_id)
     }
 }
 
@@ -234,6 +364,7 @@ where
     T: Hash + Eq + Send + 'static,
 {
     tx: Sender<ChannelSinkMsg<T>>,
+    write_buffer: Option<WriteBuffer>,
 }
 
 #[async_trait::async_trait()]
@@ -256,7 +387,17 @@ where
     const RECV_TIMEOUT: Duration = Duration::from_millis(1000);
 
     pub(crate) fn new(tx: Sender<ChannelSinkMsg<T>>) -> Self {
-        Self { tx }
+        Self {
+            tx,
+            write_buffer: None,
+        }
+    }
+
+    /// coalesce the writes of the registered stream writers with `write_buffer`
+    #[must_use]
+    pub(crate) fn with_write_buffer(mut self, write_buffer: Option<WriteBuffer>) -> Self {
+        self.write_buffer = write_buffer;
+        self
     }
 
     pub(crate) async fn register_stream_writer<W, C>(
@@ -274,6 +415,7 @@ where
         let stream_sink_tx = self.tx.clone();
         let ctx = ctx.clone();
         let tx = self.tx.clone();
+        let write_buffer = self.write_buffer;
         ctx.swallow_err(
             tx.send(ChannelSinkMsg::NewStream {
                 stream_id: stream,
@@ -295,16 +437,39 @@ where
                         // timeout, just continue
                         continue;
                     }
-                    Ok(Ok(SinkData {
-                        data,
-                        meta,
-                        contraflow,
-                        start,
-                    })) => {
-                        let failed = writer.write(data, meta).await.is_err();
+                    Ok(Ok(sink_data)) if sink_data.is_flush() => {
+                        // nothing buffered
+                        continue;
+                    }
+                    Ok(Ok(sink_data)) => {
+                        let (data, meta, replies, close_after_write) =
+                            if let Some(write_buffer) = &write_buffer {
+                                write_buffer.coalesce(sink_data, &stream_rx).await
+                            } else {
+                                let SinkData {
+                                    data,
+                                    meta,
+                                    contraflow,
+                                    start,
+                                    close_after_write,
+                                } = sink_data;
+                                (
+                                    data,
+                                    meta,
+                                    contraflow.map(|cf| (cf, start)).into_iter().collect(),
+                                    close_after_write,
+                                )
+                            };
+                        let failed = match writer.write(data, meta).await {
+                            Ok(()) => false,
+                            Err(e) => {
+                                warn!("{ctx} Error writing to stream {stream}: {e}");
+                                true
+                            }
+                        };
 
                         // send async contraflow insights if requested (only if event.transactional)
-                        if let Some((cf_data, sender)) = contraflow {
+                        for ((cf_data, sender), start) in replies {
                             let reply = if failed {
                                 AsyncSinkReply::Fail(cf_data)
                             } else {
@@ -317,6 +482,10 @@ where
                         if failed {
                             break;
                         }
+                        if close_after_write {
+                            debug!("{ctx} Closing stream {stream} after writing to it");
+                            break;
+                        }
                     }
                     Ok(Err(e)) => {
                         warn!("{ctx} Error receiving data from ChannelSink: {e}");
@@ -324,6 +493,15 @@ where
                     }
                 }
             }
+            // events queued behind the last one written never make it to the stream
+            stream_rx.close();
+            while let Ok(sink_data) = stream_rx.try_recv() {
+                if let Some((cf_data, sender)) = sink_data.contraflow {
+                    if let Err(e) = sender.send(AsyncSinkReply::Fail(cf_data)).await {
+                        error!("{ctx} Error sending async sink reply: {e}");
+                    }
+                }
+            }
             let error = match writer.on_done(stream).await {
                 Err(e) => Some(e),
                 Ok(StreamDone::ConnectorClosed) => ctx.notifier().connection_lost().await.err(),
@@ -391,7 +569,49 @@ where
 
         let mut remove_streams = vec![];
         let mut reply = SinkReply::default();
+        let mut broadcasted = false;
+        let mut async_contraflow = false;
         for (value, meta) in event.value_meta_iter() {
+            let close_after_write = self.closes_after_write(meta, ctx);
+            // events with a stream from their metadata or their origin, e.g. replies to a request,
+            // are only ever written to that stream
+            let targeted = self.resolve_stream_from_meta(meta, ctx).is_some()
+                || stream_ids.iter().any(|sid| self.streams.contains_key(sid));
+            if self.broadcast && !targeted {
+                // no specific stream given, send to all streams
+                // we ack/fail the event here, not per stream
+                let mut sent = 0_usize;
+                for (stream_id, sender) in &self.streams {
+                    let data = serializer.serialize_for_stream(value, ingest_ns, *stream_id)?;
+                    let meta = if B::NEEDS_META {
+                        Some(meta.clone_static())
+                    } else {
+                        None
+                    };
+                    let sink_data = SinkData {
+                        meta,
+                        data,
+                        contraflow: None,
+                        start,
+                        close_after_write,
+                    };
+                    if sender.send(sink_data).await.is_ok() {
+                        sent += 1;
+                    } else {
+                        warn!("{ctx} Error broadcasting to closed stream {stream_id}.");
+                        remove_streams.push(*stream_id);
+                    }
+                }
+                if sent == 0 {
+                    debug!(
+                        "{ctx} Broadcasting event {} failed for all streams",
+                        &event.id
+                    );
+                    reply = SinkReply::FAIL;
+                }
+                broadcasted = true;
+                continue;
+            }
             let mut errored = false;
             let mut found = false;
             // route based on stream id present in event metadata or in event id (trackign the event origin)
@@ -421,8 +641,10 @@ where
                     data,
                     contraflow: contraflow_utils.clone(),
                     start,
+                    close_after_write,
                 };
                 found = true;
+                async_contraflow = true;
                 if sender.send(sink_data).await.is_err() {
                     error!("{ctx} Error sending to closed stream {stream_id}.",);
                     remove_streams.push(*stream_id);
@@ -440,6 +662,10 @@ where
             serializer.drop_stream(stream_id);
             // TODO: stream based CB
         }
+        if broadcasted && !async_contraflow && reply.ack == SinkAck::None {
+            // broadcasted events are not acked by the streams
+            reply = SinkReply::ack_or_none(event.transactional);
+        }
         Ok(reply) // empty vec in case of success
     }
 
@@ -451,6 +677,12 @@ where
     ) -> Result<SinkReply> {
         if let Some(SignalKind::Tick) = signal.kind {
             self.handle_channels(ctx, serializer, true);
+            if self.flush_on_tick {
+                for sender in self.streams.values() {
+                    // a full channel is written soon anyways
+                    let _ = sender.try_send(SinkData::flush());
+                }
+            }
         }
         Ok(SinkReply::default())
     }
@@ -465,3 +697,272 @@ where
         false
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connectors::reconnect::ConnectionLostNotifier;
+    use async_std::channel::unbounded;
+    use tremor_pipeline::EventId;
+
+    /// Forwards the data of each write
+    struct RecordingWriter(Sender<Vec<Vec<u8>>>);
+
+    #[async_trait::async_trait]
+    impl StreamWriter for RecordingWriter {
+        async fn write(&mut self, data: Vec<Vec<u8>>, _meta: Option<SinkMeta>) -> Result<()> {
+            self.0.send(data).await?;
+            Ok(())
+        }
+    }
+
+    fn sink_data(data: &str, reply_tx: &Sender<AsyncSinkReply>) -> SinkData {
+        SinkData {
+            data: vec![data.as_bytes().to_vec()],
+            contraflow: Some((ContraflowData::from(&Event::default()), reply_tx.clone())),
+            meta: None,
+            start: 0,
+            close_after_write: false,
+        }
+    }
+
+    #[async_std::test]
+    async fn coalesces_writes() -> Result<()> {
+        let (rx, _tx) = unbounded();
+        let ctx = SinkContext {
+            uid: Default::default(),
+            alias: Alias::new("flow", "connector"),
+            connector_type: "snot".into(),
+            quiescence_beacon: Default::default(),
+            notifier: ConnectionLostNotifier::new(rx),
+        };
+        let (tx, rx) = bounded(8);
+        let runtime = ChannelSinkRuntime::<u64>::new(tx).with_write_buffer(Some(WriteBuffer {
+            // long enough to only ever flush on size or on the flush marker
+            flush_delay_ms: 60_000,
+            max_buffered_bytes: 9,
+        }));
+        let (write_tx, write_rx) = unbounded();
+        let _handle = runtime
+            .register_stream_writer(1, None, &ctx, RecordingWriter(write_tx))
+            .await;
+        let stream_tx = match rx.recv().await? {
+            ChannelSinkMsg::NewStream { sender, .. } => sender,
+            ChannelSinkMsg::RemoveStream(_) => return Err("expected a new stream".into()),
+        };
+        let (reply_tx, reply_rx) = unbounded();
+
+        // written once the buffer is full
+        for data in ["abc", "def", "ghi"] {
+            stream_tx.send(sink_data(data, &reply_tx)).await?;
+        }
+        let written = write_rx.recv().timeout(Duration::from_secs(5)).await??;
+        assert_eq!(vec![b"abcdefghi".to_vec()], written);
+        for _ in 0..3 {
+            assert!(matches!(reply_rx.recv().await?, AsyncSinkReply::Ack(_, _)));
+        }
+
+        // written on flush, e.g. on tick
+        stream_tx.send(sink_data("snot", &reply_tx)).await?;
+        stream_tx.send(sink_data("!", &reply_tx)).await?;
+        stream_tx.send(SinkData::flush()).await?;
+        let written = write_rx.recv().timeout(Duration::from_secs(5)).await??;
+        assert_eq!(vec![b"snot!".to_vec()], written);
+        assert!(write_rx.is_empty());
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn broadcast_only_without_target_stream() -> Result<()> {
+        let (rx, _tx) = unbounded();
+        let alias = Alias::new("flow", "connector");
+        let ctx = SinkContext {
+            uid: Default::default(),
+            alias: alias.clone(),
+            connector_type: "snot".into(),
+            quiescence_beacon: Default::default(),
+            notifier: ConnectionLostNotifier::new(rx),
+        };
+        let mut serializer = EventSerializer::new(
+            Some(crate::config::Codec::from("json")),
+            CodecReq::Required,
+            vec![],
+            &"snot".into(),
+            &alias,
+        )?;
+        let (reply_tx, _reply_rx) = unbounded();
+        let (tx, rx) = bounded(8);
+        let mut sink: ChannelSink<u64, _, NoMeta> =
+            ChannelSink::new(|_: &Value<'_>| -> Option<u64> { None }, reply_tx, tx, rx)
+                .with_broadcast(true)
+                .with_stream_id_routing(true);
+        let runtime = sink.runtime();
+        let (write_tx1, write_rx1) = unbounded();
+        let (write_tx2, write_rx2) = unbounded();
+        let _handle1 = runtime
+            .register_stream_writer(1, None, &ctx, RecordingWriter(write_tx1))
+            .await;
+        let _handle2 = runtime
+            .register_stream_writer(2, None, &ctx, RecordingWriter(write_tx2))
+            .await;
+
+        // a reply to a request received on stream 1 of this connector
+        let reply = Event {
+            id: EventId::new(ctx.uid.id(), 1, 0, 0),
+            data: (literal!("reply"), literal!({})).into(),
+            ..Event::default()
+        };
+        sink.on_event("in", reply, &ctx, &mut serializer, 0).await?;
+        let written = write_rx1.recv().timeout(Duration::from_secs(5)).await??;
+        assert_eq!(vec![b"\"reply\"".to_vec()], written);
+
+        // an event originating elsewhere goes to every stream
+        let announcement = Event {
+            id: EventId::new(ctx.uid.id() + 1, 1, 0, 0),
+            data: (literal!("all"), literal!({})).into(),
+            ..Event::default()
+        };
+        sink.on_event("in", announcement, &ctx, &mut serializer, 0)
+            .await?;
+        for write_rx in [&write_rx1, &write_rx2] {
+            let written = write_rx.recv().timeout(Duration::from_secs(5)).await??;
+            assert_eq!(vec![b"\"all\"".to_vec()], written);
+        }
+        // the reply never reached stream 2
+        assert!(write_rx2.is_empty());
+        Ok(())
+    }
+
+    /// Forwards the data of each write and reports the stream being closed
+    struct ClosingWriter {
+        writes: Sender<Vec<Vec<u8>>>,
+        closed: Sender<u64>,
+    }
+
+    #[async_trait::async_trait]
+    impl StreamWriter for ClosingWriter {
+        async fn write(&mut self, data: Vec<Vec<u8>>, _meta: Option<SinkMeta>) -> Result<()> {
+            self.writes.send(data).await?;
+            Ok(())
+        }
+        async fn on_done(&mut self, stream: u64) -> Result<StreamDone> {
+            self.closed.send(stream).await?;
+            Ok(StreamDone::StreamClosed)
+        }
+    }
+
+    #[async_std::test]
+    async fn close_after_write() -> Result<()> {
+        let (rx, _tx) = unbounded();
+        let alias = Alias::new("flow", "connector");
+        let ctx = SinkContext {
+            uid: Default::default(),
+            alias: alias.clone(),
+            connector_type: "snot".into(),
+            quiescence_beacon: Default::default(),
+            notifier: ConnectionLostNotifier::new(rx),
+        };
+        let mut serializer = EventSerializer::new(
+            Some(crate::config::Codec::from("json")),
+            CodecReq::Required,
+            vec![],
+            &"snot".into(),
+            &alias,
+        )?;
+        let (reply_tx, _reply_rx) = unbounded();
+        let (tx, rx) = bounded(8);
+        let mut sink: ChannelSink<u64, _, NoMeta> =
+            ChannelSink::new(|_: &Value<'_>| -> Option<u64> { None }, reply_tx, tx, rx)
+                .with_stream_id_routing(true)
+                .with_close_after_write(true);
+        let (write_tx, write_rx) = unbounded();
+        let (closed_tx, closed_rx) = unbounded();
+        let _handle = sink
+            .runtime()
+            .register_stream_writer(
+                1,
+                None,
+                &ctx,
+                ClosingWriter {
+                    writes: write_tx,
+                    closed: closed_tx,
+                },
+            )
+            .await;
+        let event = |close_after_write: bool| Event {
+            data: (
+                literal!("badger"),
+                literal!({"snot": {"stream_id": 1, "close_after_write": close_after_write}}),
+            )
+                .into(),
+            ..Event::default()
+        };
+
+        // the stream stays open after unflagged events
+        sink.on_event("in", event(false), &ctx, &mut serializer, 0)
+            .await?;
+        let written = write_rx.recv().timeout(Duration::from_secs(5)).await??;
+        assert_eq!(vec![b"\"badger\"".to_vec()], written);
+        assert!(closed_rx.is_empty());
+
+        // and is closed once a flagged event is written
+        sink.on_event("in", event(true), &ctx, &mut serializer, 0)
+            .await?;
+        let written = write_rx.recv().timeout(Duration::from_secs(5)).await??;
+        assert_eq!(vec![b"\"badger\"".to_vec()], written);
+        assert_eq!(1, closed_rx.recv().timeout(Duration::from_secs(5)).await??);
+        Ok(())
+    }
+
+    /// Waits for the gate to open before each write
+    struct GatedWriter {
+        gate: Receiver<()>,
+    }
+
+    #[async_trait::async_trait]
+    impl StreamWriter for GatedWriter {
+        async fn write(&mut self, _data: Vec<Vec<u8>>, _meta: Option<SinkMeta>) -> Result<()> {
+            self.gate.recv().await?;
+            Ok(())
+        }
+    }
+
+    #[async_std::test]
+    async fn close_after_write_fails_queued_events() -> Result<()> {
+        let (rx, _tx) = unbounded();
+        let ctx = SinkContext {
+            uid: Default::default(),
+            alias: Alias::new("flow", "connector"),
+            connector_type: "snot".into(),
+            quiescence_beacon: Default::default(),
+            notifier: ConnectionLostNotifier::new(rx),
+        };
+        let (tx, rx) = bounded(8);
+        let runtime = ChannelSinkRuntime::<u64>::new(tx);
+        let (gate_tx, gate_rx) = unbounded();
+        let handle = runtime
+            .register_stream_writer(1, None, &ctx, GatedWriter { gate: gate_rx })
+            .await;
+        let stream_tx = match rx.recv().await? {
+            ChannelSinkMsg::NewStream { sender, .. } => sender,
+            ChannelSinkMsg::RemoveStream(_) => return Err("expected a new stream".into()),
+        };
+        let (reply_tx, reply_rx) = unbounded();
+
+        // queue two events behind the flagged one while it is being written
+        let mut flagged = sink_data("snot", &reply_tx);
+        flagged.close_after_write = true;
+        stream_tx.send(flagged).await?;
+        stream_tx.send(sink_data("badger", &reply_tx)).await?;
+        stream_tx.send(sink_data("cake", &reply_tx)).await?;
+        gate_tx.send(()).await?;
+
+        handle.timeout(Duration::from_secs(5)).await??;
+        assert!(matches!(reply_rx.recv().await?, AsyncSinkReply::Ack(_, _)));
+        for _ in 0..2 {
+            assert!(matches!(reply_rx.recv().await?, AsyncSinkReply::Fail(_)));
+        }
+        assert!(stream_tx.is_closed());
+        Ok(())
+    }
+}
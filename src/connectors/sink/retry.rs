@@ -0,0 +1,772 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::connectors::prelude::*;
+use async_std::channel::{unbounded, Receiver, Sender};
+use async_std::task;
+use std::collections::HashMap;
+use std::sync::atomic::AtomicBool;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
+use tremor_common::time::nanotime;
+use tremor_pipeline::EventId;
+
+/// Retries for events a sink failed
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct RetryConfig {
+    /// maximum number of retries after the first failed attempt
+    pub(crate) max_retries: u64,
+    /// time to wait before the first retry in milliseconds
+    #[serde(default = "default_interval_ms")]
+    pub(crate) interval_ms: u64,
+    /// growth rate of the time to wait for consecutive retries
+    #[serde(default = "default_growth_rate")]
+    pub(crate) growth_rate: f64,
+}
+
+fn default_interval_ms() -> u64 {
+    100
+}
+
+fn default_growth_rate() -> f64 {
+    1.5
+}
+
+impl RetryConfig {
+    /// time to wait before the retry number `retry`, starting at 0
+    #[allow(
+        clippy::cast_precision_loss,
+        clippy::cast_sign_loss,
+        clippy::cast_possible_truncation,
+        clippy::cast_possible_wrap
+    )]
+    fn backoff(&self, retry: u64) -> Duration {
+        let factor = self.growth_rate.max(1.0).powi(retry.min(64) as i32);
+        Duration::from_millis((self.interval_ms as f64 * factor).min(u64::MAX as f64) as u64)
+    }
+}
+
+/// Where events end up once their retries are exhausted, the `err` port of the connector
+#[derive(Clone)]
+pub(crate) struct DeadLetter {
+    pub(crate) tx: Sender<SourceReply>,
+    pub(crate) origin_uri: EventOriginUri,
+    /// dead letters are only sent once some pipeline is connected to the source
+    pub(crate) source_is_connected: Arc<AtomicBool>,
+}
+
+impl DeadLetter {
+    /// sends every element of `event` to the `err` port, returns `false` if nobody would receive them
+    pub(crate) async fn send(&self, event: &Event, error: &str, attempts: u64) -> Result<bool> {
+        if !self.source_is_connected.load(Ordering::Acquire) {
+            return Ok(false);
+        }
+        for (value, meta) in event.value_meta_iter() {
+            let mut meta = meta.clone_static();
+            if meta.as_object().is_none() {
+                meta = Value::object();
+            }
+            meta.try_insert(
+                "dead_letter",
+                literal!({
+                    "error": error.to_string(),
+                    "attempts": attempts
+                }),
+            );
+            self.tx
+                .send(SourceReply::Structured {
+                    origin_uri: self.origin_uri.clone(),
+                    payload: (value.clone_static(), meta).into(),
+                    stream: DEFAULT_STREAM_ID,
+                    port: Some(ERR),
+                })
+                .await?;
+        }
+        Ok(true)
+    }
+}
+
+/// (source id, stream id, event id, pull id) of an event, to find it again from its contraflow
+type EventKey = (u64, u64, u64, u64);
+
+fn event_key(id: &EventId) -> EventKey {
+    (id.source_id(), id.stream_id(), id.event_id(), id.pull_id())
+}
+
+/// An event with the number of times it was sent to the wrapped sink
+struct Attempted {
+    input: String,
+    event: Event,
+    attempts: u64,
+}
+
+/// Events the wrapped sink acks or fails via the reply channel
+#[derive(Default)]
+struct Outstanding {
+    /// sent to the wrapped sink, neither acked nor failed yet
+    in_flight: HashMap<EventKey, Attempted>,
+    /// failed via the reply channel, waiting to be retried
+    failed: Vec<Attempted>,
+}
+
+/// A retry, due at `due` in nanoseconds
+struct Retry {
+    due: u64,
+    attempted: Attempted,
+}
+
+/// Passes on the replies of the wrapped sink, except the fails of events that are retried
+async fn forward_replies(
+    rx: Receiver<AsyncSinkReply>,
+    tx: Sender<AsyncSinkReply>,
+    outstanding: Arc<Mutex<Outstanding>>,
+) {
+    while let Ok(reply) = rx.recv().await {
+        let reply = {
+            let mut outstanding = outstanding.lock().unwrap_or_else(PoisonError::into_inner);
+            match reply {
+                AsyncSinkReply::Fail(data) => {
+                    if let Some(attempted) =
+                        outstanding.in_flight.remove(&event_key(&data.event_id))
+                    {
+                        outstanding.failed.push(attempted);
+                        continue;
+                    }
+                    AsyncSinkReply::Fail(data)
+                }
+                AsyncSinkReply::Ack(data, duration) => {
+                    outstanding.in_flight.remove(&event_key(&data.event_id));
+                    AsyncSinkReply::Ack(data, duration)
+                }
+                reply @ AsyncSinkReply::CB(..) => reply,
+            }
+        };
+        if tx.send(reply).await.is_err() {
+            break;
+        }
+    }
+}
+
+/// Wraps a sink, retrying the events it fails with a growing backoff
+///
+/// Failures reported from `on_event` and via the reply channel are retried. Retries are
+/// scheduled and sent with the next event or tick once they are due, without holding up
+/// the events in between. Events failing while the connection is lost are not retried,
+/// but failed right away so they can be replayed after reconnecting.
+///
+/// Events still failing after `max_retries` retries are sent to the dead letter and acked,
+/// without a dead letter they are failed as the wrapped sink did.
+pub(crate) struct RetrySink<S>
+where
+    S: Sink,
+{
+    sink: S,
+    config: RetryConfig,
+    dead_letter: Option<DeadLetter>,
+    reply_tx: Sender<AsyncSinkReply>,
+    /// the reply channel of the wrapped sink, closed on stop
+    sink_reply_tx: Sender<AsyncSinkReply>,
+    /// passes on the replies of the wrapped sink
+    forwarder: Option<task::JoinHandle<()>>,
+    outstanding: Arc<Mutex<Outstanding>>,
+    scheduled: Vec<Retry>,
+    connected: bool,
+}
+
+impl<S> RetrySink<S>
+where
+    S: Sink,
+{
+    /// Wraps the sink `sink` builds from the reply channel it is supposed to use
+    pub(crate) fn new<F>(
+        config: RetryConfig,
+        dead_letter: Option<DeadLetter>,
+        reply_tx: Sender<AsyncSinkReply>,
+        sink: F,
+    ) -> Self
+    where
+        F: FnOnce(Sender<AsyncSinkReply>) -> S,
+    {
+        let outstanding = Arc::new(Mutex::new(Outstanding::default()));
+        let (sink_reply_tx, sink_reply_rx) = unbounded();
+        let forwarder = task::spawn(forward_replies(
+            sink_reply_rx,
+            reply_tx.clone(),
+            outstanding.clone(),
+        ));
+        Self {
+            sink: sink(sink_reply_tx.clone()),
+            config,
+            dead_letter,
+            reply_tx,
+            sink_reply_tx,
+            forwarder: Some(forwarder),
+            outstanding,
+            scheduled: Vec::new(),
+            connected: true,
+        }
+    }
+
+    fn outstanding(&self) -> MutexGuard<Outstanding> {
+        self.outstanding
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Sends `attempted` to the wrapped sink, with `SinkAck::None` in the reply if the outcome isn't known yet
+    async fn attempt(
+        &mut self,
+        attempted: Attempted,
+        ctx: &SinkContext,
+        serializer: &mut EventSerializer,
+        start: u64,
+    ) -> Result<SinkReply> {
+        let Attempted {
+            input,
+            event,
+            attempts,
+        } = attempted;
+        let attempts = attempts + 1;
+        let key = event_key(&event.id);
+        let replies_async = event.transactional && !self.sink.auto_ack();
+        if replies_async {
+            // the reply might arrive before `on_event` returns
+            self.outstanding().in_flight.insert(
+                key,
+                Attempted {
+                    input: input.clone(),
+                    event: event.clone(),
+                    attempts,
+                },
+            );
+        }
+        // the event is passed on unchanged, so its id is the same for all attempts
+        let res = self
+            .sink
+            .on_event(&input, event.clone(), ctx, serializer, start)
+            .await;
+        let error = match &res {
+            Ok(reply) if reply.ack != SinkAck::Fail => {
+                let mut reply = *reply;
+                if reply.ack == SinkAck::Ack {
+                    self.outstanding().in_flight.remove(&key);
+                } else if event.transactional && self.sink.auto_ack() {
+                    reply.ack = SinkAck::Ack;
+                }
+                return Ok(reply);
+            }
+            Ok(_) => "Event failed by the sink".to_string(),
+            Err(e) => e.to_string(),
+        };
+        if replies_async {
+            self.outstanding().in_flight.remove(&key);
+        }
+        let attempted = Attempted {
+            input,
+            event,
+            attempts,
+        };
+        self.on_failure(attempted, &error, res, ctx).await
+    }
+
+    /// Schedules a retry of `attempted` if there are retries left,
+    /// otherwise sends it to the dead letter or returns the failed `res`
+    async fn on_failure(
+        &mut self,
+        attempted: Attempted,
+        error: &str,
+        res: Result<SinkReply>,
+        ctx: &SinkContext,
+    ) -> Result<SinkReply> {
+        if !self.connected {
+            debug!(
+                "{ctx} Not retrying event {} while the connection is lost: {error}",
+                attempted.event.id
+            );
+            return res;
+        }
+        if attempted.attempts <= self.config.max_retries {
+            let backoff = self.config.backoff(attempted.attempts - 1);
+            debug!(
+                "{ctx} Retrying event {} in {backoff:?}: {error}",
+                attempted.event.id
+            );
+            let backoff = u64::try_from(backoff.as_nanos()).unwrap_or(u64::MAX);
+            self.scheduled.push(Retry {
+                due: nanotime().saturating_add(backoff),
+                attempted,
+            });
+            let cb = res.map_or(CbAction::None, |reply| reply.cb);
+            return Ok(SinkReply {
+                ack: SinkAck::None,
+                cb,
+            });
+        }
+        let Attempted {
+            event, attempts, ..
+        } = attempted;
+        if let Some(dead_letter) = self.dead_letter.as_ref() {
+            if dead_letter.send(&event, error, attempts).await? {
+                warn!(
+                    "{ctx} Sent event {} to the dead letter after {attempts} attempts: {error}",
+                    event.id
+                );
+                return Ok(SinkReply::ack_or_none(event.transactional));
+            }
+        }
+        res
+    }
+
+    /// Reports the outcome `res` of an event that isn't handled by `on_event` anymore
+    async fn reply(
+        &self,
+        data: ContraflowData,
+        transactional: bool,
+        res: Result<SinkReply>,
+        start: u64,
+    ) {
+        let reply = res.unwrap_or_else(|_| SinkReply::fail_or_none(transactional));
+        let mut replies = Vec::with_capacity(2);
+        if reply.cb != CbAction::None {
+            replies.push(AsyncSinkReply::CB(data.clone(), reply.cb));
+        }
+        match reply.ack {
+            SinkAck::Ack => replies.push(AsyncSinkReply::Ack(data, nanotime() - start)),
+            SinkAck::Fail => replies.push(AsyncSinkReply::Fail(data)),
+            SinkAck::None => (),
+        }
+        for reply in replies {
+            // the sink manager is gone if this fails
+            self.reply_tx.send(reply).await.ok();
+        }
+    }
+
+    /// Schedules the events failed via the reply channel and sends the retries that are due
+    async fn retry_due(
+        &mut self,
+        ctx: &SinkContext,
+        serializer: &mut EventSerializer,
+    ) -> Result<()> {
+        let failed = std::mem::take(&mut self.outstanding().failed);
+        for attempted in failed {
+            let data = ContraflowData::from(&attempted.event);
+            let transactional = attempted.event.transactional;
+            let start = nanotime();
+            let res = self
+                .on_failure(
+                    attempted,
+                    "Event failed by the sink",
+                    Ok(SinkReply::FAIL),
+                    ctx,
+                )
+                .await;
+            self.reply(data, transactional, res, start).await;
+        }
+        let now = nanotime();
+        let (due, scheduled) = std::mem::take(&mut self.scheduled)
+            .into_iter()
+            .partition::<Vec<_>, _>(|retry| retry.due <= now);
+        self.scheduled = scheduled;
+        for Retry { attempted, .. } in due {
+            let data = ContraflowData::from(&attempted.event);
+            let transactional = attempted.event.transactional;
+            let start = nanotime();
+            let res = self.attempt(attempted, ctx, serializer, start).await;
+            self.reply(data, transactional, res, start).await;
+        }
+        Ok(())
+    }
+
+    /// Fails all events waiting to be retried
+    async fn fail_pending(&mut self) {
+        let failed = std::mem::take(&mut self.outstanding().failed);
+        let scheduled = std::mem::take(&mut self.scheduled);
+        let pending = failed
+            .into_iter()
+            .chain(scheduled.into_iter().map(|retry| retry.attempted));
+        for Attempted { event, .. } in pending {
+            let transactional = event.transactional;
+            let res = Ok(SinkReply::fail_or_none(transactional));
+            self.reply(ContraflowData::from(event), transactional, res, nanotime())
+                .await;
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl<S> Sink for RetrySink<S>
+where
+    S: Sink,
+{
+    async fn on_event(
+        &mut self,
+        input: &str,
+        event: Event,
+        ctx: &SinkContext,
+        serializer: &mut EventSerializer,
+        start: u64,
+    ) -> Result<SinkReply> {
+        self.retry_due(ctx, serializer).await?;
+        let attempted = Attempted {
+            input: input.to_string(),
+            event,
+            attempts: 0,
+        };
+        self.attempt(attempted, ctx, serializer, start).await
+    }
+
+    async fn on_signal(
+        &mut self,
+        signal: Event,
+        ctx: &SinkContext,
+        serializer: &mut EventSerializer,
+    ) -> Result<SinkReply> {
+        self.retry_due(ctx, serializer).await?;
+        self.sink.on_signal(signal, ctx, serializer).await
+    }
+
+    async fn metrics(&mut self, timestamp: u64, ctx: &SinkContext) -> Vec<EventPayload> {
+        self.sink.metrics(timestamp, ctx).await
+    }
+
+    async fn on_start(&mut self, ctx: &SinkContext) -> Result<()> {
+        self.sink.on_start(ctx).await
+    }
+
+    async fn connect(&mut self, ctx: &SinkContext, attempt: &Attempt) -> Result<bool> {
+        let connected = self.sink.connect(ctx, attempt).await;
+        self.connected = matches!(connected, Ok(true));
+        connected
+    }
+
+    async fn on_pause(&mut self, ctx: &SinkContext) -> Result<()> {
+        self.sink.on_pause(ctx).await
+    }
+
+    async fn on_resume(&mut self, ctx: &SinkContext) -> Result<()> {
+        self.sink.on_resume(ctx).await
+    }
+
+    async fn on_stop(&mut self, ctx: &SinkContext) -> Result<()> {
+        let res = self.sink.on_stop(ctx).await;
+        // pass on the replies the wrapped sink sent while stopping, before failing what is left
+        self.sink_reply_tx.close();
+        if let Some(forwarder) = self.forwarder.take() {
+            forwarder.await;
+        }
+        self.fail_pending().await;
+        res
+    }
+
+    async fn on_connection_lost(&mut self, ctx: &SinkContext) -> Result<()> {
+        self.connected = false;
+        // retrying in place is pointless, they can be replayed after reconnecting
+        self.fail_pending().await;
+        self.sink.on_connection_lost(ctx).await
+    }
+
+    async fn on_connection_established(&mut self, ctx: &SinkContext) -> Result<()> {
+        self.connected = true;
+        self.sink.on_connection_established(ctx).await
+    }
+
+    /// events are acked or failed once their retries are done
+    fn auto_ack(&self) -> bool {
+        false
+    }
+
+    /// retries are sent after `on_event` returned
+    fn asynchronous(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connectors::reconnect::ConnectionLostNotifier;
+    use async_std::prelude::FutureExt;
+
+    /// Fails the first `failures` events it receives, recording their ids
+    ///
+    /// Fails are returned from `on_event`, or sent via the reply channel if it has one.
+    /// With `held` set, replies are held back until the sink is stopped.
+    struct FlakySink {
+        failures: usize,
+        seen: Vec<EventId>,
+        reply_tx: Option<Sender<AsyncSinkReply>>,
+        held: Option<Vec<AsyncSinkReply>>,
+    }
+
+    #[async_trait::async_trait]
+    impl Sink for FlakySink {
+        async fn on_event(
+            &mut self,
+            _input: &str,
+            event: Event,
+            _ctx: &SinkContext,
+            _serializer: &mut EventSerializer,
+            _start: u64,
+        ) -> Result<SinkReply> {
+            self.seen.push(event.id.clone());
+            let failed = self.seen.len() <= self.failures;
+            match self.reply_tx.as_ref() {
+                Some(reply_tx) => {
+                    let data = ContraflowData::from(&event);
+                    let reply = if failed {
+                        AsyncSinkReply::Fail(data)
+                    } else {
+                        AsyncSinkReply::Ack(data, 0)
+                    };
+                    if let Some(held) = self.held.as_mut() {
+                        held.push(reply);
+                    } else {
+                        reply_tx.send(reply).await?;
+                    }
+                    Ok(SinkReply::NONE)
+                }
+                None if failed => Err("snot".into()),
+                None => Ok(SinkReply::NONE),
+            }
+        }
+
+        async fn on_stop(&mut self, _ctx: &SinkContext) -> Result<()> {
+            if let Some(reply_tx) = self.reply_tx.as_ref() {
+                for reply in self.held.take().unwrap_or_default() {
+                    reply_tx.send(reply).await?;
+                }
+            }
+            Ok(())
+        }
+
+        fn auto_ack(&self) -> bool {
+            self.reply_tx.is_none()
+        }
+    }
+
+    struct Harness {
+        sink: RetrySink<FlakySink>,
+        ctx: SinkContext,
+        serializer: EventSerializer,
+        replies: Receiver<AsyncSinkReply>,
+        dead_letters: Receiver<SourceReply>,
+    }
+
+    impl Harness {
+        fn new(
+            failures: usize,
+            config: RetryConfig,
+            source_is_connected: bool,
+            replies_async: bool,
+        ) -> Result<Self> {
+            let (dead_letter_tx, dead_letters) = unbounded();
+            let (reply_tx, replies) = unbounded();
+            let sink = RetrySink::new(
+                config,
+                Some(DeadLetter {
+                    tx: dead_letter_tx,
+                    origin_uri: EventOriginUri::default(),
+                    source_is_connected: Arc::new(AtomicBool::new(source_is_connected)),
+                }),
+                reply_tx,
+                |reply_tx| FlakySink {
+                    failures,
+                    seen: Vec::new(),
+                    reply_tx: replies_async.then(|| reply_tx),
+                    held: None,
+                },
+            );
+            let (rx, _tx) = unbounded();
+            let alias = Alias::new("flow", "connector");
+            let ctx = SinkContext {
+                uid: Default::default(),
+                alias: alias.clone(),
+                connector_type: "snot".into(),
+                quiescence_beacon: Default::default(),
+                notifier: ConnectionLostNotifier::new(rx),
+            };
+            let serializer =
+                EventSerializer::new(None, CodecReq::Structured, vec![], &"snot".into(), &alias)?;
+            Ok(Self {
+                sink,
+                ctx,
+                serializer,
+                replies,
+                dead_letters,
+            })
+        }
+
+        async fn send(&mut self) -> Result<SinkReply> {
+            let event = Event {
+                id: EventId::from_id(1, 2, 3),
+                data: (literal!({"snot": "badger"}), literal!({"cake": true})).into(),
+                transactional: true,
+                ..Event::default()
+            };
+            self.sink
+                .on_event("in", event, &self.ctx, &mut self.serializer, 0)
+                .await
+        }
+
+        /// ticks until the event is acked or failed
+        async fn outcome(&mut self) -> Result<AsyncSinkReply> {
+            for _ in 0..100 {
+                self.sink
+                    .on_signal(Event::signal_tick(), &self.ctx, &mut self.serializer)
+                    .await?;
+                if let Ok(reply) = self.replies.recv().timeout(Duration::from_millis(10)).await {
+                    return Ok(reply?);
+                }
+            }
+            Err("the event was neither acked nor failed".into())
+        }
+    }
+
+    fn config(max_retries: u64) -> RetryConfig {
+        RetryConfig {
+            max_retries,
+            interval_ms: 1,
+            growth_rate: 2.0,
+        }
+    }
+
+    #[test]
+    fn backoff() {
+        let config = RetryConfig {
+            max_retries: 3,
+            interval_ms: 100,
+            growth_rate: 2.0,
+        };
+        assert_eq!(Duration::from_millis(100), config.backoff(0));
+        assert_eq!(Duration::from_millis(400), config.backoff(2));
+    }
+
+    #[async_std::test]
+    async fn retry_then_success() -> Result<()> {
+        let mut harness = Harness::new(2, config(3), true, false)?;
+        assert_eq!(SinkReply::NONE, harness.send().await?);
+        assert!(matches!(harness.outcome().await?, AsyncSinkReply::Ack(..)));
+        assert_eq!(3, harness.sink.sink.seen.len());
+        assert!(harness.dead_letters.is_empty());
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn retries_dont_block() -> Result<()> {
+        let config = RetryConfig {
+            max_retries: 3,
+            interval_ms: 60_000,
+            growth_rate: 2.0,
+        };
+        let mut harness = Harness::new(10, config, true, false)?;
+        let reply = harness.send().timeout(Duration::from_secs(1)).await??;
+        assert_eq!(SinkReply::NONE, reply);
+        // the retry isn't due yet
+        harness
+            .sink
+            .on_signal(Event::signal_tick(), &harness.ctx, &mut harness.serializer)
+            .await?;
+        assert_eq!(1, harness.sink.sink.seen.len());
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn retries_async_fails() -> Result<()> {
+        let mut harness = Harness::new(2, config(3), true, true)?;
+        assert_eq!(SinkReply::NONE, harness.send().await?);
+        // only the final ack is passed on
+        assert!(matches!(harness.outcome().await?, AsyncSinkReply::Ack(..)));
+        assert_eq!(3, harness.sink.sink.seen.len());
+        assert!(harness.replies.is_empty());
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn retries_exhausted_to_dead_letter() -> Result<()> {
+        let mut harness = Harness::new(10, config(2), true, false)?;
+        assert_eq!(SinkReply::NONE, harness.send().await?);
+        assert!(matches!(harness.outcome().await?, AsyncSinkReply::Ack(..)));
+        assert_eq!(3, harness.sink.sink.seen.len());
+        match harness.dead_letters.try_recv()? {
+            SourceReply::Structured { payload, port, .. } => {
+                assert_eq!(Some(ERR), port);
+                let (value, meta) = payload.parts();
+                assert_eq!(&literal!({"snot": "badger"}), value);
+                assert_eq!(Some(&Value::from(true)), meta.get("cake"));
+                assert_eq!(Some(3), meta.get("dead_letter").get_u64("attempts"));
+                assert_eq!(Some("snot"), meta.get("dead_letter").get_str("error"));
+            }
+            _ => return Err("expected a dead letter".into()),
+        }
+
+        // without a pipeline connected to the dead letter, the event fails as before
+        let mut harness = Harness::new(10, config(1), false, true)?;
+        assert_eq!(SinkReply::NONE, harness.send().await?);
+        assert!(matches!(harness.outcome().await?, AsyncSinkReply::Fail(..)));
+        assert!(harness.dead_letters.is_empty());
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn no_retries_while_the_connection_is_lost() -> Result<()> {
+        let mut harness = Harness::new(10, config(3), true, false)?;
+        assert_eq!(SinkReply::NONE, harness.send().await?);
+        // the scheduled retry is failed right away
+        harness.sink.on_connection_lost(&harness.ctx).await?;
+        assert!(matches!(
+            harness.replies.try_recv()?,
+            AsyncSinkReply::Fail(..)
+        ));
+        // and so are new events
+        assert!(harness.send().await.is_err());
+        assert_eq!(2, harness.sink.sink.seen.len());
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn preserves_event_ids() -> Result<()> {
+        let mut harness = Harness::new(3, config(3), true, false)?;
+        harness.send().await?;
+        harness.outcome().await?;
+        assert_eq!(vec![EventId::from_id(1, 2, 3); 4], harness.sink.sink.seen);
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn forwards_replies_sent_on_stop() -> Result<()> {
+        let mut harness = Harness::new(0, config(3), true, true)?;
+        harness.sink.sink.held = Some(Vec::new());
+        assert_eq!(SinkReply::NONE, harness.send().await?);
+        assert!(harness.replies.is_empty());
+        harness.sink.on_stop(&harness.ctx).await?;
+        assert!(matches!(
+            harness.replies.try_recv()?,
+            AsyncSinkReply::Ack(..)
+        ));
+
+        // a late fail can't be retried anymore
+        let mut harness = Harness::new(10, config(3), true, true)?;
+        harness.sink.sink.held = Some(Vec::new());
+        assert_eq!(SinkReply::NONE, harness.send().await?);
+        harness.sink.on_stop(&harness.ctx).await?;
+        assert!(matches!(
+            harness.replies.try_recv()?,
+            AsyncSinkReply::Fail(..)
+        ));
+        assert!(harness.replies.is_empty());
+        assert_eq!(1, harness.sink.sink.seen.len());
+        Ok(())
+    }
+}
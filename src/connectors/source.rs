@@ -26,6 +26,7 @@ use simd_json::Mutable;
 use std::collections::btree_map::Entry;
 use std::collections::BTreeMap;
 use std::fmt::Display;
+use std::time::Duration;
 use tremor_common::{
     ids::{Id, SinkId, SourceId},
     time::nanotime,
@@ -109,7 +110,9 @@ pub(crate) enum SourceReply {
         /// Port to send to, defaults to `out`
         port: Option<Cow<'static, str>>,
         /// Overwrite the codec being used for deserializing this data.
-        /// Should only be used when setting `stream` to `None`
+        ///
+        /// For data with a `stream` this is only considered when the stream is created,
+        /// that is, for the first data of the stream.
         codec_overwrite: Option<String>,
     },
     /// an already structured event payload
@@ -255,6 +258,12 @@ pub(crate) trait Source: Send {
     /// This distinction is important for the runtime to handle pausing/resuming
     /// and quiescence correctly.
     fn asynchronous(&self) -> bool;
+
+    /// if true, the raw bytes of each event, as they were before decoding,
+    /// are kept in the event metadata under `raw`
+    fn keep_raw(&self) -> bool {
+        false
+    }
 }
 
 ///
@@ -340,6 +349,8 @@ pub(crate) struct SourceManagerBuilder {
     qsize: usize,
     streams: Streams,
     source_metrics_reporter: SourceReporter,
+    max_in_flight: Option<usize>,
+    in_flight_timeout_ms: Option<u64>,
 }
 
 impl SourceManagerBuilder {
@@ -430,6 +441,8 @@ pub(crate) fn builder(
         qsize,
         streams,
         source_metrics_reporter,
+        max_in_flight: config.max_in_flight,
+        in_flight_timeout_ms: config.in_flight_timeout_ms,
     })
 }
 
@@ -469,9 +482,12 @@ impl Streams {
     }
 
     /// get or create a stream
+    ///
+    /// The `codec_overwrite` is only considered when creating a new stream, not for existing streams.
     fn get_or_create_stream<C: Context>(
         &mut self,
         stream_id: u64,
+        codec_overwrite: Option<String>,
         ctx: &C,
     ) -> Result<&mut StreamState> {
         Ok(match self.states.entry(stream_id) {
@@ -482,7 +498,7 @@ impl Streams {
                     self.uid,
                     stream_id,
                     &self.codec_config,
-                    None,
+                    codec_overwrite,
                     &self.preprocessor_configs,
                 )?;
                 e.insert(state)
@@ -532,6 +548,101 @@ struct StreamState {
     preprocessors: Preprocessors,
 }
 
+/// Events of a transactional source that were neither acked nor failed yet
+#[derive(Debug, Default)]
+struct InFlight {
+    max: Option<usize>,
+    /// events in flight for longer than this many nanoseconds are no longer waited for
+    timeout: Option<u64>,
+    /// the time each event was sent at, by stream id, pull id and event id
+    events: BTreeMap<(u64, u64, u64), u64>,
+}
+
+impl InFlight {
+    fn new(max: Option<usize>, timeout_ms: Option<u64>) -> Self {
+        Self {
+            max,
+            timeout: timeout_ms.map(|timeout_ms| timeout_ms.saturating_mul(1_000_000)),
+            ..Self::default()
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    /// track a transactional event sent to a pipeline at `now`, only if `max_in_flight` is configured
+    fn track(&mut self, id: &EventId, now: u64) {
+        if self.max.is_some() {
+            self.events
+                .insert((id.stream_id(), id.pull_id(), id.event_id()), now);
+        }
+    }
+
+    /// forget about the events acked or failed with `id`
+    ///
+    /// Events sent to multiple pipelines are complete with the first ack or fail.
+    /// Batches only carry the range of pull ids of the events they contain,
+    /// all events of those pulls are complete with them.
+    fn complete(&mut self, source_id: u64, id: &EventId) {
+        let streams = id.get_streams(source_id);
+        let (stream_id, pull_id) = (id.stream_id(), id.pull_id());
+        let is_single = id.source_id() == source_id
+            && streams.len() == 1
+            && id.get_min_by_stream(source_id, stream_id) == Some(pull_id)
+            && id.get_max_by_stream(source_id, stream_id) == Some(pull_id);
+        if is_single {
+            self.events.remove(&(stream_id, pull_id, id.event_id()));
+            return;
+        }
+        for stream_id in streams {
+            if let Some((min, max)) = id
+                .get_min_by_stream(source_id, stream_id)
+                .zip(id.get_max_by_stream(source_id, stream_id))
+            {
+                let completed: Vec<_> = self
+                    .events
+                    .range((stream_id, min, 0)..=(stream_id, max, u64::MAX))
+                    .map(|(key, _)| *key)
+                    .collect();
+                for key in completed {
+                    self.events.remove(&key);
+                }
+            }
+        }
+    }
+
+    /// forget about the events sent `timeout` or longer before `now`, returns how many
+    fn expire(&mut self, now: u64) -> usize {
+        if let Some(timeout) = self.timeout {
+            let before = self.events.len();
+            self.events
+                .retain(|_, sent_at| now.saturating_sub(*sent_at) < timeout);
+            before - self.events.len()
+        } else {
+            0
+        }
+    }
+
+    /// time from `now` until the oldest event expires, only while full and with a timeout
+    fn next_expiry(&self, now: u64) -> Option<Duration> {
+        let timeout = self.timeout.filter(|_| self.is_full())?;
+        let oldest = self.events.values().min()?;
+        Some(Duration::from_nanos(
+            oldest.saturating_add(timeout).saturating_sub(now),
+        ))
+    }
+
+    /// forget about all events, they won't be acked or failed anymore
+    fn clear(&mut self) {
+        self.events.clear();
+    }
+
+    fn is_full(&self) -> bool {
+        self.max.map_or(false, |max| self.len() >= max)
+    }
+}
+
 /// possible states of a source implementation
 #[derive(Debug, PartialEq, Copy, Clone)]
 enum SourceState {
@@ -569,6 +680,7 @@ where
     connectivity: Connectivity,
     is_transactional: bool,
     is_asynchronous: bool,
+    keep_raw: bool,
     connector_channel: Option<Sender<Msg>>,
     /// Gather all the sinks that reported being started.
     /// This will give us some knowledge on the topology and most importantly
@@ -579,6 +691,8 @@ where
     /// an event is originating from. We can only ack or fail pulls.
     pull_counter: u64,
     cb_restore_received: u64,
+    /// events sent but not yet acked or failed, only tracked with `max_in_flight`
+    in_flight: InFlight,
 }
 
 /// control flow enum
@@ -597,10 +711,13 @@ where
         let SourceManagerBuilder {
             streams,
             source_metrics_reporter,
+            max_in_flight,
+            in_flight_timeout_ms,
             ..
         } = builder;
         let is_transactional = source.is_transactional();
         let is_asynchronous = source.asynchronous();
+        let keep_raw = source.keep_raw();
 
         Self {
             source,
@@ -614,11 +731,13 @@ where
             connectivity: Connectivity::Disconnected, // we always start as disconnected until `.connect()` connects us
             is_transactional,
             is_asynchronous,
+            keep_raw,
             connector_channel: None,
             started_sinks: HashSet::new(),
             num_started_sinks: 0,
             pull_counter: 0,
             cb_restore_received: 0,
+            in_flight: InFlight::new(max_in_flight, in_flight_timeout_ms),
         }
     }
 
@@ -640,6 +759,8 @@ where
 
             SourceMsg::Connect(sender, attempt) => {
                 info!("{} Connecting...", self.ctx);
+                // events of a previous connection won't be acked or failed anymore
+                self.in_flight.clear();
                 let connect_result = self.source.connect(&self.ctx, &attempt).await;
                 self.connectivity = if matches!(connect_result, Ok(true)) {
                     info!("{} Connected.", self.ctx);
@@ -670,6 +791,7 @@ where
             SourceMsg::Stop(sender) => {
                 info!("{} Stopping...", self.ctx);
                 self.state = Stopped;
+                self.in_flight.clear();
                 let res = sender.send(self.source.on_stop(&self.ctx).await).await;
                 self.ctx.swallow_err(res, "Error sending Stop reply");
                 Control::Terminate
@@ -677,6 +799,7 @@ where
             SourceMsg::Drain(drained_sender) => self.handle_drain(drained_sender).await,
             SourceMsg::ConnectionLost => {
                 self.connectivity = Connectivity::Disconnected;
+                self.in_flight.clear();
                 let res = self.source.on_connection_lost(&self.ctx).await;
                 self.ctx.swallow_err(res, "on_connection_lost failed");
                 Control::Continue
@@ -765,12 +888,14 @@ where
         let ctx = &self.ctx;
         match cb {
             CbAction::Fail => {
+                self.in_flight.complete(self.ctx.uid.id(), &id);
                 if let Some((stream_id, id)) = id.get_min_by_source(self.ctx.uid.id()) {
                     ctx.swallow_err(self.source.fail(stream_id, id, ctx).await, "fail failed");
                 }
                 Control::Continue
             }
             CbAction::Ack => {
+                self.in_flight.complete(self.ctx.uid.id(), &id);
                 if let Some((stream_id, id)) = id.get_max_by_source(self.ctx.uid.id()) {
                     ctx.swallow_err(self.source.ack(stream_id, id, ctx).await, "ack failed");
                 }
@@ -890,6 +1015,9 @@ where
             }
 
             if let Some((last, pipelines)) = pipelines.split_last_mut() {
+                if event.transactional {
+                    self.in_flight.track(&event.id, nanotime());
+                }
                 for (pipe_url, addr) in pipelines {
                     let input = pipe_url.port().to_string().into();
                     let msg = Box::new(pipeline::Msg::Event {
@@ -918,6 +1046,15 @@ where
 
     /// should this manager pull data from its source?
     fn should_pull_data(&mut self) -> bool {
+        if self.in_flight.is_full() {
+            let expired = self.in_flight.expire(nanotime());
+            if expired > 0 {
+                warn!(
+                    "{} {expired} events in flight expired without being acked or failed",
+                    self.ctx
+                );
+            }
+        }
         // asynchronous sources need to be drained from their asynchronous task which consumes from
         // the external resource, we pull data from it until we receive a `SourceReply::Empty`.
         // synchronous sources (polling the external resource directly in `Source::pull_data`) should not be called anymore
@@ -931,6 +1068,7 @@ where
             && self.connectivity == Connectivity::Connected // we are connected to our thingy
             && self.cb_restore_received >= self.num_started_sinks // we did receive a `CbAction::Restore` from all connected sinks
                                                                   // so we know the downstream side is ready to receive something
+            && !self.in_flight.is_full() // we are waiting for acks or fails of the events in flight
     }
 
     /// handle data from the source
@@ -1016,6 +1154,7 @@ where
                 None,
                 &meta.unwrap_or_else(Value::object),
                 self.is_transactional,
+                self.keep_raw,
             );
             if results.is_empty() {
                 let res = self
@@ -1044,7 +1183,7 @@ where
         origin_uri: EventOriginUri,
     ) -> Result<()> {
         let ingest_ns = nanotime();
-        let stream_state = self.streams.get_or_create_stream(stream, &self.ctx)?;
+        let stream_state = self.streams.get_or_create_stream(stream, None, &self.ctx)?;
         let event = build_event(
             stream_state,
             pull_id,
@@ -1076,7 +1215,9 @@ where
     ) -> Result<()> {
         let mut ingest_ns = nanotime();
         if let Some(stream) = stream {
-            let stream_state = self.streams.get_or_create_stream(stream, &self.ctx)?;
+            let stream_state =
+                self.streams
+                    .get_or_create_stream(stream, codec_overwrite, &self.ctx)?;
             let results = build_events(
                 &self.ctx.alias,
                 stream_state,
@@ -1087,6 +1228,7 @@ where
                 data,
                 &meta.unwrap_or_else(Value::object),
                 self.is_transactional,
+                self.keep_raw,
             );
             if results.is_empty() {
                 let expr = self.source.on_no_events(pull_id, stream, &self.ctx).await;
@@ -1114,6 +1256,7 @@ where
                 data,
                 &meta,
                 self.is_transactional,
+                self.keep_raw,
             );
             // finish up the stream immediately
             let mut last_events = build_last_events(
@@ -1125,6 +1268,7 @@ where
                 None,
                 &meta,
                 self.is_transactional,
+                self.keep_raw,
             );
             results.append(&mut last_events);
 
@@ -1219,7 +1363,20 @@ where
         loop {
             use futures::future::Either;
             while !self.should_pull_data() {
-                match f1.take().unwrap_or_else(|| rx.recv()).await {
+                let recv = f1.take().unwrap_or_else(|| rx.recv());
+                // with all events in flight, wait no longer than until the oldest of them expires
+                let msg = if let Some(expiry) = self.in_flight.next_expiry(nanotime()) {
+                    match futures::future::select(recv, Box::pin(task::sleep(expiry))).await {
+                        Either::Left((msg, _)) => msg,
+                        Either::Right(((), recv)) => {
+                            f1 = Some(recv);
+                            continue;
+                        }
+                    }
+                } else {
+                    recv.await
+                };
+                match msg {
                     Ok(msg) => {
                         if self.handle_control_plane_msg(msg).await == Control::Terminate {
                             debug!("{} Terminating source task...", self.ctx);
@@ -1283,6 +1440,7 @@ fn build_events(
     data: Vec<u8>,
     meta: &Value<'static>,
     is_transactional: bool,
+    keep_raw: bool,
 ) -> Vec<(Cow<'static, str>, Event)> {
     match preprocess(
         stream_state.preprocessors.as_mut_slice(),
@@ -1293,12 +1451,14 @@ fn build_events(
         Ok(processed) => {
             let mut res = Vec::with_capacity(processed.len());
             for chunk in processed {
+                // decoding might modify the data in place, so we need to copy it beforehand
+                let raw = keep_raw.then(|| chunk.clone());
                 let line_value = EventPayload::try_new::<Option<Error>, _>(chunk, |mut_data| {
                     match stream_state.codec.decode(mut_data, *ingest_ns) {
                         Ok(None) => Err(None),
                         Err(e) => Err(Some(e)),
                         Ok(Some(decoded)) => {
-                            Ok(ValueAndMeta::from_parts(decoded, meta.clone()))
+                            Ok(ValueAndMeta::from_parts(decoded, event_meta(meta, raw)))
                             // TODO: avoid clone on last iterator element
                         }
                     }
@@ -1351,17 +1511,20 @@ fn build_last_events(
     port: Option<&Cow<'static, str>>,
     meta: &Value<'static>,
     is_transactional: bool,
+    keep_raw: bool,
 ) -> Vec<(Cow<'static, str>, Event)> {
     match finish(stream_state.preprocessors.as_mut_slice(), alias) {
         Ok(processed) => {
             let mut res = Vec::with_capacity(processed.len());
             for chunk in processed {
+                // decoding might modify the data in place, so we need to copy it beforehand
+                let raw = keep_raw.then(|| chunk.clone());
                 let line_value = EventPayload::try_new::<Option<Error>, _>(chunk, |mut_data| {
                     match stream_state.codec.decode(mut_data, *ingest_ns) {
                         Ok(None) => Err(None),
                         Err(e) => Err(Some(e)),
                         Ok(Some(decoded)) => {
-                            Ok(ValueAndMeta::from_parts(decoded, meta.clone()))
+                            Ok(ValueAndMeta::from_parts(decoded, event_meta(meta, raw)))
                             // TODO: avoid clone on last iterator element
                         }
                     }
@@ -1402,6 +1565,15 @@ fn build_last_events(
     }
 }
 
+/// the metadata of a decoded event, with the `raw` bytes it was decoded from if we keep them
+fn event_meta(meta: &Value<'static>, raw: Option<Vec<u8>>) -> Value<'static> {
+    let mut meta = meta.clone();
+    if let Some(raw) = raw {
+        meta.try_insert("raw", Value::Bytes(raw.into()));
+    }
+    meta
+}
+
 /// create an error payload
 fn make_error(
     connector_alias: &Alias,
@@ -1439,3 +1611,135 @@ fn build_event(
         ..Event::default()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use value_trait::ValueAccess;
+
+    #[test]
+    fn in_flight() {
+        let mut in_flight = InFlight::new(Some(3), None);
+        // two events from the same pull
+        in_flight.track(&EventId::new(1, 0, 0, 1), 0);
+        in_flight.track(&EventId::new(1, 0, 1, 1), 0);
+        in_flight.track(&EventId::new(1, 0, 2, 2), 0);
+        assert!(in_flight.is_full());
+
+        // only the acked event of the pull is complete
+        in_flight.complete(1, &EventId::new(1, 0, 0, 1));
+        assert_eq!(2, in_flight.len());
+        assert!(!in_flight.is_full());
+        // acks from other pipelines don't complete other events
+        in_flight.complete(1, &EventId::new(1, 0, 0, 1));
+        assert_eq!(2, in_flight.len());
+        in_flight.complete(1, &EventId::new(1, 0, 1, 1));
+        assert_eq!(1, in_flight.len());
+
+        in_flight.track(&EventId::new(1, 1, 0, 3), 0);
+        in_flight.track(&EventId::new(1, 1, 1, 4), 0);
+        assert!(in_flight.is_full());
+
+        // a batch acked at once, also tracking an event of another source
+        let mut batch = EventId::new(1, 0, 2, 2);
+        batch.track(&EventId::new(1, 1, 0, 3));
+        batch.track(&EventId::new(1, 1, 1, 4));
+        batch.track(&EventId::new(2, 0, 0, 5));
+        in_flight.complete(1, &batch);
+        assert_eq!(0, in_flight.len());
+
+        // nothing is tracked without a maximum
+        let mut in_flight = InFlight::new(None, None);
+        in_flight.track(&EventId::new(1, 0, 0, 1), 0);
+        assert_eq!(0, in_flight.len());
+        assert!(!in_flight.is_full());
+    }
+
+    #[test]
+    fn in_flight_expiry() {
+        let mut in_flight = InFlight::new(Some(2), Some(10));
+        in_flight.track(&EventId::new(1, 0, 0, 1), 0);
+        in_flight.track(&EventId::new(1, 0, 1, 2), 5_000_000);
+        assert!(in_flight.is_full());
+        assert_eq!(
+            Some(Duration::from_millis(6)),
+            in_flight.next_expiry(4_000_000)
+        );
+        assert_eq!(Some(Duration::ZERO), in_flight.next_expiry(20_000_000));
+        assert_eq!(0, in_flight.expire(9_999_999));
+        assert_eq!(1, in_flight.expire(10_000_000));
+        assert_eq!(1, in_flight.len());
+        assert!(!in_flight.is_full());
+        assert_eq!(None, in_flight.next_expiry(10_000_000));
+
+        in_flight.clear();
+        assert_eq!(0, in_flight.len());
+
+        // without a timeout events are waited for forever
+        let mut in_flight = InFlight::new(Some(1), None);
+        in_flight.track(&EventId::new(1, 0, 0, 1), 0);
+        assert_eq!(0, in_flight.expire(u64::MAX));
+        assert!(in_flight.is_full());
+        assert_eq!(None, in_flight.next_expiry(0));
+    }
+
+    #[test]
+    fn keep_raw() -> Result<()> {
+        let alias = Alias::new("flow", "source");
+        let mut stream_state = Streams::build_stream(
+            SourceId::new(1),
+            DEFAULT_STREAM_ID,
+            &"json".into(),
+            None,
+            &[],
+        )?;
+        let data = br#"{"snot": "badger", "values": [1, 2.5, null], "nested": {"a": "\u00e4"}}"#;
+        let origin_uri = EventOriginUri {
+            scheme: "tremor-test".to_string(),
+            host: "localhost".to_string(),
+            port: None,
+            path: vec![],
+        };
+        let mut ingest_ns = 0;
+
+        let events = build_events(
+            &alias,
+            &mut stream_state,
+            &mut ingest_ns,
+            0,
+            &origin_uri,
+            None,
+            data.to_vec(),
+            &literal!({"snot": "badger"}),
+            false,
+            true,
+        );
+        assert_eq!(1, events.len());
+        let (port, event) = &events[0];
+        assert_eq!(&OUT, port);
+        let (value, meta) = event.data.parts();
+        assert_eq!(
+            &literal!({"snot": "badger", "values": [1, 2.5, null], "nested": {"a": "ä"}}),
+            value
+        );
+        // the raw bytes are untouched by decoding, including the escape sequence
+        assert_eq!(Some(&data[..]), meta.get_bytes("raw"));
+        assert_eq!(Some("badger"), meta.get_str("snot"));
+
+        let events = build_events(
+            &alias,
+            &mut stream_state,
+            &mut ingest_ns,
+            0,
+            &origin_uri,
+            None,
+            data.to_vec(),
+            &Value::object(),
+            false,
+            false,
+        );
+        let (_, event) = &events[0];
+        assert!(event.data.suffix().meta().get("raw").is_none());
+        Ok(())
+    }
+}
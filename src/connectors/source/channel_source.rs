@@ -19,10 +19,144 @@ use crate::connectors::Context;
 use crate::errors::Result;
 use async_std::channel::{bounded, Receiver, Sender};
 use async_std::prelude::*;
+use async_std::sync::Mutex;
 use async_std::task;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
 use std::time::Duration;
+use tremor_common::time::nanotime;
+
+/// Pull ids of the data of a stream that was neither acked nor failed yet, with the time they
+/// were pulled at, the reader of the stream took a permit for each of them
+struct StreamInFlight {
+    pulls: BTreeMap<u64, u64>,
+    permits: Receiver<()>,
+}
+
+impl StreamInFlight {
+    /// hands back the permits of `pulls`
+    fn release(&self, pulls: usize) {
+        for _ in 0..pulls {
+            // the reader only sends the reply after taking its permit, so there is one
+            self.permits.try_recv().ok();
+        }
+    }
+}
+
+/// Limits the number of replies per stream that were neither acked nor failed yet
+#[derive(Clone)]
+struct InFlightLimit {
+    max: usize,
+    /// replies in flight for this many nanoseconds hand back their permits
+    timeout: Option<u64>,
+    streams: Arc<Mutex<HashMap<u64, StreamInFlight>>>,
+}
+
+impl InFlightLimit {
+    fn new(max: usize, timeout: Option<Duration>) -> Self {
+        Self {
+            max,
+            timeout: timeout.map(|timeout| u64::try_from(timeout.as_nanos()).unwrap_or(u64::MAX)),
+            streams: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// the permits a reader of `stream` needs to take for every reply it sends
+    async fn register(&self, stream: u64) -> Sender<()> {
+        let (tx, permits) = bounded(self.max);
+        self.streams.lock().await.insert(
+            stream,
+            StreamInFlight {
+                pulls: BTreeMap::new(),
+                permits,
+            },
+        );
+        tx
+    }
+
+    async fn unregister(&self, stream: u64) {
+        self.streams.lock().await.remove(&stream);
+    }
+
+    async fn track(&self, stream: u64, pull_id: u64) {
+        if let Some(in_flight) = self.streams.lock().await.get_mut(&stream) {
+            in_flight.pulls.insert(pull_id, nanotime());
+        }
+    }
+
+    /// hands back the permits of `pull_id` and all earlier pulls of `stream`,
+    /// as the acks and fails of batched events only carry the latest pull id
+    async fn complete(&self, stream: u64, pull_id: u64) {
+        if let Some(in_flight) = self.streams.lock().await.get_mut(&stream) {
+            let pending = in_flight.pulls.split_off(&(pull_id + 1));
+            let completed = std::mem::replace(&mut in_flight.pulls, pending);
+            in_flight.release(completed.len());
+        }
+    }
+
+    /// hands back the permit of `pull_id` only, as it didn't turn into any event
+    async fn release(&self, stream: u64, pull_id: u64) {
+        if let Some(in_flight) = self.streams.lock().await.get_mut(&stream) {
+            if in_flight.pulls.remove(&pull_id).is_some() {
+                in_flight.release(1);
+            }
+        }
+    }
+
+    /// hands back the permits of the pulls of `stream` in flight for longer than the timeout,
+    /// their events might have been dropped, returns how many
+    async fn expire(&self, stream: u64) -> usize {
+        let now = nanotime();
+        match (self.timeout, self.streams.lock().await.get_mut(&stream)) {
+            (Some(timeout), Some(in_flight)) => {
+                let before = in_flight.pulls.len();
+                in_flight
+                    .pulls
+                    .retain(|_, pulled_at| now.saturating_sub(*pulled_at) < timeout);
+                let expired = before - in_flight.pulls.len();
+                in_flight.release(expired);
+                expired
+            }
+            _ => 0,
+        }
+    }
+
+    /// takes a permit for the next reply of `stream`, waiting for acks, fails or the expiry of
+    /// replies in flight if there are too many, returns `false` if the stream is gone
+    async fn take_permit<C: Context>(&self, permits: &Sender<()>, stream: u64, ctx: &C) -> bool {
+        loop {
+            match permits.send(()).timeout(self.expiry_interval()).await {
+                Ok(res) => return res.is_ok(),
+                Err(_) => {
+                    let expired = self.expire(stream).await;
+                    if expired > 0 {
+                        warn!("{ctx} {expired} replies of stream {stream} in flight expired without being acked or failed");
+                    }
+                }
+            }
+        }
+    }
+
+    /// how often to check for expired replies while waiting for a permit
+    fn expiry_interval(&self) -> Duration {
+        self.timeout
+            .map_or(ChannelSourceRuntime::READ_TIMEOUT_MS, |timeout| {
+                Duration::from_nanos(timeout).min(ChannelSourceRuntime::READ_TIMEOUT_MS)
+            })
+    }
+}
+
+/// The stream of a reply that is turned into events
+fn event_stream(reply: &SourceReply) -> Option<u64> {
+    match reply {
+        SourceReply::Data { stream, .. } => *stream,
+        SourceReply::Structured { stream, .. } => Some(*stream),
+        _ => None,
+    }
+}
+
 /// A source that receives `SourceReply` messages via a channel.
-/// It does not handle acks/fails.
+/// It does not handle acks/fails, other than for limiting the replies in flight per stream.
 ///
 /// Connector implementations handling their stuff in a separate task can use the
 /// channel obtained by `ChannelSource::sender()` to send `SourceReply`s to the
@@ -30,6 +164,8 @@ use std::time::Duration;
 pub(crate) struct ChannelSource {
     rx: Receiver<SourceReply>,
     tx: SourceReplySender,
+    keep_raw: bool,
+    in_flight: Option<InFlightLimit>,
 }
 
 impl ChannelSource {
@@ -43,7 +179,34 @@ impl ChannelSource {
     /// construct a channel source from a given channel
     #[must_use]
     pub fn from_channel(tx: Sender<SourceReply>, rx: Receiver<SourceReply>) -> Self {
-        Self { rx, tx }
+        Self {
+            rx,
+            tx,
+            keep_raw: false,
+            in_flight: None,
+        }
+    }
+
+    /// keep the raw bytes of each event in its metadata under `raw`
+    #[must_use]
+    pub fn with_keep_raw(mut self, keep_raw: bool) -> Self {
+        self.keep_raw = keep_raw;
+        self
+    }
+
+    /// stop reading from a stream once `max_in_flight` of its replies were neither acked nor failed,
+    /// this makes the source transactional if set
+    ///
+    /// Replies still in flight after `timeout` no longer count, without it only replies that
+    /// turned into no events at all don't count.
+    #[must_use]
+    pub fn with_max_in_flight(
+        mut self,
+        max_in_flight: Option<usize>,
+        timeout: Option<Duration>,
+    ) -> Self {
+        self.in_flight = max_in_flight.map(|max| InFlightLimit::new(max, timeout));
+        self
     }
 
     /// get the runtime for the source
@@ -52,6 +215,7 @@ impl ChannelSource {
     pub fn runtime(&self) -> ChannelSourceRuntime {
         ChannelSourceRuntime {
             sender: self.tx.clone(),
+            in_flight: self.in_flight.clone(),
         }
     }
 }
@@ -60,11 +224,15 @@ impl ChannelSource {
 #[derive(Clone)]
 pub(crate) struct ChannelSourceRuntime {
     sender: Sender<SourceReply>,
+    in_flight: Option<InFlightLimit>,
 }
 
 impl ChannelSourceRuntime {
     pub(crate) fn new(source_tx: Sender<SourceReply>) -> Self {
-        Self { sender: source_tx }
+        Self {
+            sender: source_tx,
+            in_flight: None,
+        }
     }
 }
 
@@ -77,7 +245,12 @@ impl ChannelSourceRuntime {
     {
         let ctx = ctx.clone();
         let tx = self.sender.clone();
+        let in_flight = self.in_flight.clone();
         task::spawn(async move {
+            let permits = match in_flight.as_ref() {
+                Some(in_flight) => Some(in_flight.register(stream).await),
+                None => None,
+            };
             loop {
                 if !ctx.quiescence_beacon().continue_reading().await {
                     debug!("{ctx} quiescing stream {stream}");
@@ -110,11 +283,26 @@ impl ChannelSourceRuntime {
                         | SourceReply::StreamFail(_)
                 );
 
+                if let Some((permits, in_flight)) = permits
+                    .as_ref()
+                    .zip(in_flight.as_ref())
+                    .filter(|_| event_stream(&sc_data).is_some())
+                {
+                    // waits for acks or fails once the stream has too many replies in flight
+                    if !in_flight.take_permit(permits, stream, &ctx).await {
+                        break;
+                    }
+                }
+
                 if tx.send(sc_data).await.is_err() || last {
                     break;
                 };
             }
 
+            if let Some(in_flight) = in_flight {
+                in_flight.unregister(stream).await;
+            }
+
             if reader.on_done(stream).await == StreamDone::ConnectorClosed {
                 ctx.swallow_err(
                     ctx.notifier().connection_lost().await,
@@ -127,16 +315,160 @@ impl ChannelSourceRuntime {
 
 #[async_trait::async_trait()]
 impl Source for ChannelSource {
-    async fn pull_data(&mut self, _pull_id: &mut u64, _ctx: &SourceContext) -> Result<SourceReply> {
-        Ok(self.rx.recv().await?)
+    async fn pull_data(&mut self, pull_id: &mut u64, _ctx: &SourceContext) -> Result<SourceReply> {
+        let reply = self.rx.recv().await?;
+        if let Some((in_flight, stream)) = self.in_flight.as_ref().zip(event_stream(&reply)) {
+            in_flight.track(stream, *pull_id).await;
+        }
+        Ok(reply)
+    }
+
+    async fn ack(&mut self, stream_id: u64, pull_id: u64, _ctx: &SourceContext) -> Result<()> {
+        if let Some(in_flight) = self.in_flight.as_ref() {
+            in_flight.complete(stream_id, pull_id).await;
+        }
+        Ok(())
     }
 
-    /// this source is not handling acks/fails
+    async fn fail(&mut self, stream_id: u64, pull_id: u64, _ctx: &SourceContext) -> Result<()> {
+        if let Some(in_flight) = self.in_flight.as_ref() {
+            in_flight.complete(stream_id, pull_id).await;
+        }
+        Ok(())
+    }
+
+    /// replies that turned into no events, e.g. partial lines, are never acked nor failed
+    async fn on_no_events(
+        &mut self,
+        pull_id: u64,
+        stream: u64,
+        _ctx: &SourceContext,
+    ) -> Result<()> {
+        if let Some(in_flight) = self.in_flight.as_ref() {
+            in_flight.release(stream, pull_id).await;
+        }
+        Ok(())
+    }
+
+    /// this source is only handling acks/fails to limit the replies in flight
     fn is_transactional(&self) -> bool {
-        false
+        self.in_flight.is_some()
     }
 
     fn asynchronous(&self) -> bool {
         true
     }
+
+    fn keep_raw(&self) -> bool {
+        self.keep_raw
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connectors::reconnect::ConnectionLostNotifier;
+    use crate::connectors::Alias;
+    use async_std::channel::unbounded;
+    use tremor_common::ids::SourceId;
+    use tremor_pipeline::EventOriginUri;
+
+    /// reads the same data over and over
+    struct ChattyReader;
+
+    #[async_trait::async_trait]
+    impl StreamReader for ChattyReader {
+        async fn read(&mut self, stream: u64) -> Result<SourceReply> {
+            Ok(SourceReply::Data {
+                origin_uri: EventOriginUri::default(),
+                stream: Some(stream),
+                meta: None,
+                data: b"snot".to_vec(),
+                port: None,
+                codec_overwrite: None,
+            })
+        }
+
+        async fn quiesce(&mut self, stream: u64) -> Option<SourceReply> {
+            Some(SourceReply::EndStream {
+                origin_uri: EventOriginUri::default(),
+                stream,
+                meta: None,
+            })
+        }
+    }
+
+    #[async_std::test]
+    async fn stalls_at_max_in_flight() -> Result<()> {
+        let (tx, _rx) = unbounded();
+        let ctx = SourceContext {
+            uid: SourceId::new(1),
+            alias: Alias::new("flow", "connector"),
+            connector_type: "snot".into(),
+            quiescence_beacon: Default::default(),
+            notifier: ConnectionLostNotifier::new(tx),
+        };
+        let mut source = ChannelSource::new(8).with_max_in_flight(Some(2), None);
+        assert!(source.is_transactional());
+        source
+            .runtime()
+            .register_stream_reader(1, &ctx, ChattyReader);
+
+        let mut pull_id = 0;
+        source.pull_data(&mut pull_id, &ctx).await?;
+        pull_id = 1;
+        source.pull_data(&mut pull_id, &ctx).await?;
+        pull_id = 2;
+        // neither of the two replies is acked yet
+        assert!(source
+            .pull_data(&mut pull_id, &ctx)
+            .timeout(Duration::from_millis(200))
+            .await
+            .is_err());
+
+        source.ack(1, 0, &ctx).await?;
+        assert!(source
+            .pull_data(&mut pull_id, &ctx)
+            .timeout(Duration::from_secs(5))
+            .await
+            .is_ok());
+
+        // a reply without events hands back its permit right away
+        source.on_no_events(2, 1, &ctx).await?;
+        pull_id = 3;
+        assert!(source
+            .pull_data(&mut pull_id, &ctx)
+            .timeout(Duration::from_secs(5))
+            .await
+            .is_ok());
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn expires_replies_in_flight() -> Result<()> {
+        let (tx, _rx) = unbounded();
+        let ctx = SourceContext {
+            uid: SourceId::new(1),
+            alias: Alias::new("flow", "connector"),
+            connector_type: "snot".into(),
+            quiescence_beacon: Default::default(),
+            notifier: ConnectionLostNotifier::new(tx),
+        };
+        let mut source =
+            ChannelSource::new(8).with_max_in_flight(Some(1), Some(Duration::from_millis(100)));
+        source
+            .runtime()
+            .register_stream_reader(1, &ctx, ChattyReader);
+
+        let mut pull_id = 0;
+        source.pull_data(&mut pull_id, &ctx).await?;
+        // the first reply is neither acked nor failed, e.g. its error event was dropped
+        pull_id = 1;
+        assert!(source
+            .pull_data(&mut pull_id, &ctx)
+            .timeout(Duration::from_secs(5))
+            .await
+            .is_ok());
+        Ok(())
+    }
 }
@@ -15,7 +15,9 @@
 use super::ConnectorHarness;
 use crate::{connectors::impls::file, errors::Result};
 use async_std::path::Path;
-use tremor_value::literal;
+use tremor_common::ports::IN;
+use tremor_pipeline::{Event, EventId};
+use tremor_value::{literal, Value};
 use value_trait::ValueAccess;
 
 #[async_std::test]
@@ -81,3 +83,117 @@ async fn file_connector() -> Result<()> {
 
     Ok(())
 }
+
+fn sink_event(id: u64, data: Value<'static>, ingest_ns: u64) -> Event {
+    Event {
+        id: EventId::from_id(0, 0, id),
+        data: (data, Value::object()).into(),
+        ingest_ns,
+        ..Event::default()
+    }
+}
+
+#[async_std::test]
+async fn file_sink_path_template() -> Result<()> {
+    let _ = env_logger::try_init();
+
+    let dir = tempfile::Builder::new().tempdir()?;
+    let defn = literal!({
+        "codec": "json",
+        "postprocessors": ["separate"],
+        "config": {
+            "path": format!("{}/{{tenant.name}}/{{ingest:%Y-%m-%d}}.log", dir.path().display()),
+            "mode": "append"
+        }
+    });
+
+    let harness = ConnectorHarness::new(function_name!(), &file::Builder::default(), &defn).await?;
+    harness.start().await?;
+    harness.wait_for_connected().await?;
+
+    // 2022-05-06 and 2022-05-07
+    let day1 = 1_651_795_200_000_000_000;
+    let day2 = day1 + 86_400_000_000_000;
+    harness
+        .send_to_sink(
+            sink_event(1, literal!({"tenant": {"name": "snot"}}), day1),
+            IN,
+        )
+        .await?;
+    harness
+        .send_to_sink(
+            sink_event(2, literal!({"tenant": {"name": "badger"}}), day1),
+            IN,
+        )
+        .await?;
+    harness
+        .send_to_sink(
+            sink_event(3, literal!({"tenant": {"name": "snot"}}), day2),
+            IN,
+        )
+        .await?;
+    // field values can't escape into other directories
+    harness
+        .send_to_sink(
+            sink_event(4, literal!({"tenant": {"name": "../x"}}), day1),
+            IN,
+        )
+        .await?;
+    let (_out, _err) = harness.stop().await?;
+
+    let read = |path: &str| std::fs::read_to_string(dir.path().join(path));
+    assert_eq!(
+        "{\"tenant\":{\"name\":\"snot\"}}\n",
+        read("snot/2022-05-06.log")?
+    );
+    assert_eq!(
+        "{\"tenant\":{\"name\":\"badger\"}}\n",
+        read("badger/2022-05-06.log")?
+    );
+    assert_eq!(
+        "{\"tenant\":{\"name\":\"snot\"}}\n",
+        read("snot/2022-05-07.log")?
+    );
+    assert!(read(".._x/2022-05-06.log").is_ok());
+    Ok(())
+}
+
+#[async_std::test]
+async fn file_sink_size_rotation() -> Result<()> {
+    let _ = env_logger::try_init();
+
+    let dir = tempfile::Builder::new().tempdir()?;
+    let path = dir.path().join("out.log");
+    let defn = literal!({
+        "codec": "string",
+        "postprocessors": ["separate"],
+        "config": {
+            "path": path.display().to_string(),
+            "mode": "truncate",
+            "rotate_size": 10
+        }
+    });
+
+    let harness = ConnectorHarness::new(function_name!(), &file::Builder::default(), &defn).await?;
+    harness.start().await?;
+    harness.wait_for_connected().await?;
+
+    for (id, data) in ["snot", "badger", "snot", "badger"].into_iter().enumerate() {
+        harness
+            .send_to_sink(sink_event(id as u64, Value::from(data), 0), IN)
+            .await?;
+    }
+    let (_out, _err) = harness.stop().await?;
+
+    // a file is rotated as soon as it reaches 10 bytes
+    assert_eq!(
+        "snot\nbadger\n",
+        std::fs::read_to_string(dir.path().join("out.log.1"))?
+    );
+    assert_eq!(
+        "snot\nbadger\n",
+        std::fs::read_to_string(dir.path().join("out.log.2"))?
+    );
+    assert!(!path.exists());
+    Ok(())
+}
@@ -17,23 +17,31 @@ use crate::{
         impls::http,
         prelude::Url,
         tests::{free_port::find_free_tcp_port, setup_for_tls, ConnectorHarness},
-        utils::url::HttpDefaults,
+        utils::{
+            tls::{load_certs, load_keys},
+            url::HttpDefaults,
+        },
     },
     errors::Result,
 };
+use async_compat::Compat;
 use async_std::{
-    io::Cursor,
+    io::{Cursor, Read, ReadExt, Write, WriteExt},
+    net::TcpListener,
     task::{spawn, JoinHandle},
 };
+use bytes::Bytes;
+use either::Either;
 use http_types::{
     headers::{HeaderValues, CONTENT_TYPE, TRANSFER_ENCODING},
     Body,
 };
-use rustls::NoClientAuth;
+use rustls::{AllowAnyAuthenticatedClient, NoClientAuth, RootCertStore};
+use std::{io::BufReader, path::Path, sync::Arc};
 use tide;
 use tide_rustls::TlsListener;
 use tremor_common::ports::IN;
-use tremor_pipeline::Event;
+use tremor_pipeline::{CbAction, Event};
 use tremor_script::{literal, Value, ValueAndMeta};
 use value_trait::{Mutable, ValueAccess};
 
@@ -72,12 +80,54 @@ async fn fake_server_dispatch(mut req: tide::Request<()>) -> tide::Result<tide::
     Ok(res)
 }
 
+/// TLS config for a server that only accepts clients presenting a certificate signed by `cert_file`
+fn client_auth_config(cert_file: &str, key_file: &str) -> Result<rustls::ServerConfig> {
+    let mut roots = RootCertStore::empty();
+    let mut pem = BufReader::new(tremor_common::file::open(cert_file)?);
+    roots
+        .add_pem_file(&mut pem)
+        .map_err(|_e| format!("Invalid certificate in {cert_file}"))?;
+    let mut config = rustls::ServerConfig::new(AllowAnyAuthenticatedClient::new(roots));
+    config.set_single_cert(
+        load_certs(Path::new(cert_file))?,
+        load_keys(Path::new(key_file))?,
+    )?;
+    Ok(config)
+}
+
 impl TestHttpServer {
     async fn new(raw_url: String) -> Result<Self> {
+        Self::start(raw_url, false).await
+    }
+
+    /// https server requiring a client certificate
+    async fn with_client_auth(raw_url: String) -> Result<Self> {
+        Self::start(raw_url, true).await
+    }
+
+    async fn start(raw_url: String, client_auth: bool) -> Result<Self> {
         let mut instance = TestHttpServer { acceptor: None };
         instance.acceptor = Some(spawn(async move {
             let url: Url<HttpDefaults> = Url::parse(&raw_url)?;
-            if "https" == url.scheme() {
+            if client_auth {
+                let cert_file = "./tests/localhost.cert";
+                let key_file = "./tests/localhost.key";
+                setup_for_tls();
+
+                let mut endpoint = tide::Server::new();
+                endpoint.at("/").all(fake_server_dispatch);
+                endpoint.at("/*").all(fake_server_dispatch);
+                if let Err(e) = endpoint
+                    .listen(
+                        TlsListener::build()
+                            .config(client_auth_config(cert_file, key_file)?)
+                            .addrs(url.url().socket_addrs(|| None)?[0]),
+                    )
+                    .await
+                {
+                    error!("Error listening on {url}: {e}");
+                }
+            } else if "https" == url.scheme() {
                 let cert_file = "./tests/localhost.cert";
                 let key_file = "./tests/localhost.key";
                 setup_for_tls(); // Setups up TLS certs for localhost testing as a side-effect
@@ -647,6 +697,50 @@ async fn missing_tls_config_https() -> Result<()> {
     Ok(())
 }
 
+#[async_std::test]
+async fn client_certificate() -> Result<()> {
+    let _ = env_logger::try_init();
+    let target = find_free_tcp_endpoint_str().await;
+    let url = format!("https://{target}");
+    let defn = literal!({
+      "config": {
+        "url": url.clone(),
+        "method": "get",
+        "tls": {
+          "cert": "./tests/localhost.cert",
+          "key": "./tests/localhost.key",
+          "ca": "./tests/localhost.cert"
+        }
+      },
+      "codec": "string",
+    });
+
+    let mut fake = TestHttpServer::with_client_auth(url.clone()).await?;
+
+    let harness =
+        ConnectorHarness::new(function_name!(), &http::client::Builder::default(), &defn).await?;
+    let out_pipeline = harness
+        .out()
+        .expect("No pipeline connected to 'out' port of connector");
+
+    harness.start().await?;
+    harness.wait_for_connected().await?;
+    harness.consume_initial_sink_contraflow().await?;
+
+    let event = Event {
+        data: (Value::from("snot"), literal!({})).into(),
+        ..Default::default()
+    };
+    harness.send_to_sink(event, IN).await?;
+
+    let event = out_pipeline.get_event().await?;
+    fake.stop().await?;
+    let (_out, err) = harness.stop().await?;
+    assert!(err.is_empty());
+    assert_eq!(&Value::from("snot"), event.data.suffix().value());
+    Ok(())
+}
+
 #[async_std::test]
 async fn missing_config() -> Result<()> {
     let defn = literal!({
@@ -662,3 +756,299 @@ async fn missing_config() -> Result<()> {
 
     Ok(())
 }
+
+/// Answers every request on `stream` with `badger` over HTTP/2
+async fn serve_h2<S>(stream: S) -> Result<()>
+where
+    S: Read + Write + Unpin,
+{
+    let mut connection = h2::server::handshake(Compat::new(stream))
+        .await
+        .map_err(|e| e.to_string())?;
+    while let Some(request) = connection.accept().await {
+        let (_request, mut respond) = request.map_err(|e| e.to_string())?;
+        let response = ::http::Response::builder()
+            .status(200)
+            .header("content-type", "text/plain")
+            .body(())
+            .map_err(|e| e.to_string())?;
+        let mut body = respond
+            .send_response(response, false)
+            .map_err(|e| e.to_string())?;
+        body.send_data(Bytes::from_static(b"badger"), true)
+            .map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+/// Answers every request on `stream` over HTTP/2 with its body followed by its `x-snot` trailer
+async fn serve_h2_trailers<S>(stream: S) -> Result<()>
+where
+    S: Read + Write + Unpin,
+{
+    let mut connection = h2::server::handshake(Compat::new(stream))
+        .await
+        .map_err(|e| e.to_string())?;
+    while let Some(request) = connection.accept().await {
+        let (request, mut respond) = request.map_err(|e| e.to_string())?;
+        let mut recv = request.into_body();
+        let mut body = Vec::new();
+        while let Some(data) = recv.data().await {
+            let data = data.map_err(|e| e.to_string())?;
+            recv.flow_control()
+                .release_capacity(data.len())
+                .map_err(|e| e.to_string())?;
+            body.extend_from_slice(&data);
+        }
+        let trailers = recv.trailers().await.map_err(|e| e.to_string())?;
+        let trailer = trailers
+            .as_ref()
+            .and_then(|trailers| trailers.get("x-snot"))
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default();
+        body.push(b' ');
+        body.extend_from_slice(trailer.as_bytes());
+        let response = ::http::Response::builder()
+            .status(200)
+            .header("content-type", "text/plain")
+            .body(())
+            .map_err(|e| e.to_string())?;
+        let mut stream = respond
+            .send_response(response, false)
+            .map_err(|e| e.to_string())?;
+        stream
+            .send_data(Bytes::from(body), true)
+            .map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+/// Answers the first request on `stream` with `badger` over HTTP/1.1
+async fn serve_h1<S>(mut stream: S) -> Result<()>
+where
+    S: Read + Write + Unpin,
+{
+    let mut head = Vec::new();
+    let mut byte = [0_u8; 1];
+    while !head.ends_with(b"\r\n\r\n") {
+        stream.read_exact(&mut byte).await?;
+        head.push(byte[0]);
+    }
+    stream
+        .write_all(
+            b"HTTP/1.1 200 OK\r\ncontent-type: text/plain\r\ncontent-length: 6\r\n\r\nbadger",
+        )
+        .await?;
+    Ok(())
+}
+
+/// Sends a single transactional event, returning the response event or the contraflow of a failed request
+async fn send_one(id: &str, defn: &Value<'static>) -> Result<Either<Event, CbAction>> {
+    send_one_with_meta(id, defn, literal!({})).await
+}
+
+/// Sends a single transactional event with `meta`, returning the response event or the contraflow of a failed request
+async fn send_one_with_meta(
+    id: &str,
+    defn: &Value<'static>,
+    meta: Value<'static>,
+) -> Result<Either<Event, CbAction>> {
+    let harness = ConnectorHarness::new(id, &http::client::Builder::default(), defn).await?;
+    let out_pipeline = harness
+        .out()
+        .expect("No pipeline connected to 'out' port of connector");
+    let in_pipeline = harness
+        .get_pipe(IN)
+        .expect("No pipeline connected to 'in' port of connector");
+    harness.start().await?;
+    harness.wait_for_connected().await?;
+    harness.consume_initial_sink_contraflow().await?;
+
+    let event = Event {
+        data: (Value::from("snot"), meta).into(),
+        transactional: true,
+        ..Default::default()
+    };
+    harness.send_to_sink(event, IN).await?;
+    let cf = in_pipeline.get_contraflow().await?;
+    let res = if cf.cb == CbAction::Ack {
+        Either::Left(out_pipeline.get_event().await?)
+    } else {
+        Either::Right(cf.cb)
+    };
+    harness.stop().await?;
+    Ok(res)
+}
+
+fn response_version(event: &Event) -> Option<&str> {
+    event
+        .data
+        .suffix()
+        .meta()
+        .get("http_client")
+        .get("response")
+        .get_str("version")
+}
+
+#[async_std::test]
+async fn http2_prior_knowledge() -> Result<()> {
+    let _ = env_logger::try_init();
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let port = listener.local_addr()?.port();
+    let server = spawn(async move {
+        let (stream, _) = listener.accept().await?;
+        serve_h2(stream).await
+    });
+    let defn = literal!({
+      "config": {
+        "url": format!("http://127.0.0.1:{port}/snot"),
+        "method": "get",
+        "http_version": "http2"
+      },
+      "codec": "string",
+    });
+    let event = send_one(function_name!(), &defn)
+        .await?
+        .left()
+        .ok_or("Expected a response")?;
+    assert_eq!(Some("HTTP/2"), response_version(&event));
+    assert_eq!(&Value::from("badger"), event.data.suffix().value());
+    server.await?;
+    Ok(())
+}
+
+#[async_std::test]
+async fn http2_via_alpn() -> Result<()> {
+    let _ = env_logger::try_init();
+    setup_for_tls();
+    let mut config = rustls::ServerConfig::new(NoClientAuth::new());
+    config.set_single_cert(
+        load_certs(Path::new("./tests/localhost.cert"))?,
+        load_keys(Path::new("./tests/localhost.key"))?,
+    )?;
+    config.set_protocols(&[b"h2".to_vec()]);
+    let acceptor = async_rustls::TlsAcceptor::from(Arc::new(config));
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let port = listener.local_addr()?.port();
+    let server = spawn(async move {
+        let (stream, _) = listener.accept().await?;
+        serve_h2(acceptor.accept(stream).await?).await
+    });
+    // `auto` is the default
+    let defn = literal!({
+      "config": {
+        "url": format!("https://localhost:{port}/snot"),
+        "method": "get",
+        "tls": {
+          "ca": "./tests/localhost.cert"
+        }
+      },
+      "codec": "string",
+    });
+    let event = send_one(function_name!(), &defn)
+        .await?
+        .left()
+        .ok_or("Expected a response")?;
+    assert_eq!(Some("HTTP/2"), response_version(&event));
+    assert_eq!(&Value::from("badger"), event.data.suffix().value());
+    server.await?;
+    Ok(())
+}
+
+#[async_std::test]
+async fn http2_required() -> Result<()> {
+    let _ = env_logger::try_init();
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let port = listener.local_addr()?.port();
+    let server = spawn(async move {
+        let (stream, _) = listener.accept().await?;
+        serve_h1(stream).await
+    });
+    // `auto` sends `http` requests over HTTP/1.1
+    let defn = literal!({
+      "config": {
+        "url": format!("http://127.0.0.1:{port}/snot"),
+        "method": "get"
+      },
+      "codec": "string",
+    });
+    let event = send_one(function_name!(), &defn)
+        .await?
+        .left()
+        .ok_or("Expected a response")?;
+    assert_eq!(Some("HTTP/1.1"), response_version(&event));
+    server.await?;
+
+    // a server without HTTP/2 support fails the request
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let port = listener.local_addr()?.port();
+    let server = spawn(async move {
+        let (stream, _) = listener.accept().await?;
+        serve_h1(stream).await
+    });
+    let defn = literal!({
+      "config": {
+        "url": format!("http://127.0.0.1:{port}/snot"),
+        "method": "get",
+        "http_version": "http2"
+      },
+      "codec": "string",
+    });
+    assert_eq!(
+        Some(CbAction::Fail),
+        send_one(function_name!(), &defn).await?.right()
+    );
+    server.cancel().await;
+    Ok(())
+}
+
+#[async_std::test]
+async fn http2_request_trailers() -> Result<()> {
+    let _ = env_logger::try_init();
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let port = listener.local_addr()?.port();
+    let server = spawn(async move {
+        let (stream, _) = listener.accept().await?;
+        serve_h2_trailers(stream).await
+    });
+    let meta = literal!({
+        "http_client": {
+            "request": {
+                "headers": {"transfer-encoding": "chunked"},
+                "trailers": {"x-snot": "badger"}
+            }
+        }
+    });
+    let defn = literal!({
+      "config": {
+        "url": format!("http://127.0.0.1:{port}/snot"),
+        "method": "post",
+        "http_version": "http2"
+      },
+      "codec": "string",
+    });
+    let event = send_one_with_meta(function_name!(), &defn, meta.clone())
+        .await?
+        .left()
+        .ok_or("Expected a response")?;
+    // the trailer arrived after the body
+    assert_eq!(&Value::from("snot badger"), event.data.suffix().value());
+    server.await?;
+
+    // over HTTP/1.1 the trailers would be dropped, so the request fails instead
+    let defn = literal!({
+      "config": {
+        "url": format!("http://127.0.0.1:{port}/snot"),
+        "method": "post",
+        "http_version": "http1"
+      },
+      "codec": "string",
+    });
+    assert_eq!(
+        Some(CbAction::Fail),
+        send_one_with_meta(function_name!(), &defn, meta)
+            .await?
+            .right()
+    );
+    Ok(())
+}
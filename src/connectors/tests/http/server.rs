@@ -442,3 +442,138 @@ value: null
 
     Ok(())
 }
+
+#[async_std::test]
+async fn http_server_raw_body() -> Result<()> {
+    let _ = env_logger::try_init();
+    let port = free_port::find_free_tcp_port().await?;
+    let url = format!("http://localhost:{port}/");
+    let defn = literal!({
+        "codec": "json",
+        "config": {
+            "url": url.clone(),
+            "raw_body": true
+        }
+    });
+    let connector =
+        ConnectorHarness::new(function_name!(), &server::Builder::default(), &defn).await?;
+    connector.start().await?;
+    connector.wait_for_connected().await?;
+
+    // neither valid json nor valid utf-8
+    let data: Vec<u8> = (0..=255).rev().collect();
+    let req = surf::Request::builder(Method::Put, Url::parse(url.as_str())?)
+        .header("content-type", "image/png")
+        .body_bytes(data.as_slice())
+        .build();
+    // echo the request body, with the content-type of the request
+    let echo = |req_data: &ValueAndMeta<'_>| -> ValueAndMeta<'static> {
+        let value = match req_data.value() {
+            Value::Bytes(bytes) => Value::Bytes(bytes.to_vec().into()),
+            _ => Value::from("not bytes"),
+        };
+        let content_type = req_data
+            .meta()
+            .get("http_server")
+            .get("request")
+            .get("headers")
+            .get("content-type")
+            .map(Value::clone_static);
+        let meta = literal!({
+            "http_server": {
+                "response": {
+                    "status": 200,
+                    "headers": {
+                        "content-type": content_type
+                    }
+                }
+            }
+        });
+        (value, meta).into()
+    };
+    let start = Instant::now();
+    let timeout = Duration::from_secs(30);
+    let mut res = handle_req(req.clone(), echo, &connector, false).await;
+    while let Err(e) = res {
+        if start.elapsed() > timeout {
+            return Err(format!("HTTP Server not listening after {timeout:?}: {e}").into());
+        }
+        res = handle_req(req.clone(), echo, &connector, false).await;
+    }
+    let mut res = res?;
+    assert_eq!(StatusCode::Ok, res.status());
+    assert_eq!(
+        &HeaderValue::from_str("image/png")?,
+        res.header("content-type").unwrap().last()
+    );
+    assert_eq!(data, res.body_bytes().await?);
+
+    let (_out, err) = connector.stop().await?;
+    assert!(err.is_empty());
+    Ok(())
+}
+
+#[async_std::test]
+async fn http_server_request_limits() -> Result<()> {
+    let _ = env_logger::try_init();
+    let port = free_port::find_free_tcp_port().await?;
+    let url = format!("http://localhost:{port}/");
+    let defn = literal!({
+        "codec": "json",
+        "config": {
+            "url": url.clone(),
+            "max_url_length": 64,
+            "max_header_count": 10,
+            "max_header_bytes": 1024
+        }
+    });
+    let connector =
+        ConnectorHarness::new(function_name!(), &server::Builder::default(), &defn).await?;
+    connector.start().await?;
+    connector.wait_for_connected().await?;
+
+    let ok = |_: &ValueAndMeta<'_>| -> ValueAndMeta<'static> {
+        let meta = literal!({
+            "http_server": {
+                "response": {
+                    "status": 200
+                }
+            }
+        });
+        (Value::from("ok"), meta).into()
+    };
+    // requests within the limits make it through, once the server is up
+    let req = surf::Request::builder(Method::Get, Url::parse(url.as_str())?).build();
+    let start = Instant::now();
+    let timeout = Duration::from_secs(30);
+    let mut res = handle_req(req.clone(), ok, &connector, false).await;
+    while let Err(e) = res {
+        if start.elapsed() > timeout {
+            return Err(format!("HTTP Server not listening after {timeout:?}: {e}").into());
+        }
+        res = handle_req(req.clone(), ok, &connector, false).await;
+    }
+    assert_eq!(StatusCode::Ok, res?.status());
+
+    let long_url = format!("{url}{}", "snot".repeat(20));
+    let req = surf::Request::builder(Method::Get, Url::parse(long_url.as_str())?).build();
+    let res = handle_req(req, ok, &connector, false).await?;
+    assert_eq!(StatusCode::UriTooLong, res.status());
+
+    let mut req = surf::Request::builder(Method::Get, Url::parse(url.as_str())?).build();
+    for i in 0..20 {
+        req.insert_header(format!("x-snot-{i}").as_str(), "badger");
+    }
+    let res = handle_req(req, ok, &connector, false).await?;
+    assert_eq!(StatusCode::RequestHeaderFieldsTooLarge, res.status());
+
+    let req = surf::Request::builder(Method::Get, Url::parse(url.as_str())?)
+        .header("x-snot", "badger".repeat(200))
+        .build();
+    let res = handle_req(req, ok, &connector, false).await?;
+    assert_eq!(StatusCode::RequestHeaderFieldsTooLarge, res.status());
+
+    let (_out, err) = connector.stop().await?;
+    assert!(err.is_empty());
+    Ok(())
+}
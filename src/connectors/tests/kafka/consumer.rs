@@ -36,7 +36,7 @@ use std::time::Duration;
 use testcontainers::clients::Cli as DockerCli;
 use tremor_pipeline::CbAction;
 use tremor_value::{literal, Value};
-use value_trait::Builder;
+use value_trait::{Builder, ValueAccess};
 
 #[async_std::test]
 #[serial(kafka)]
@@ -1380,3 +1380,133 @@ async fn transactional_commit_offset_handling() -> Result<()> {
     drop(container);
     Ok(())
 }
+
+#[async_std::test]
+#[serial(kafka)]
+async fn connector_kafka_consumer_cooperative_sticky() -> Result<()> {
+    serial_test::set_max_wait(Duration::from_secs(600));
+
+    let _ = env_logger::try_init();
+
+    let docker = DockerCli::default();
+    let container = redpanda_container(&docker).await?;
+
+    let port = container.get_host_port_ipv4(9092);
+    let mut admin_config = ClientConfig::new();
+
+    let broker = format!("127.0.0.1:{}", port);
+    let topic = "tremor_test_cooperative_sticky";
+    let group_id = "group_cooperative_sticky";
+
+    admin_config
+        .set("client.id", "test-admin")
+        .set("bootstrap.servers", &broker);
+    let admin_client = AdminClient::from_config(&admin_config)?;
+    let options = AdminOptions::default();
+    let res = admin_client
+        .create_topics(
+            vec![&NewTopic::new(topic, 2, TopicReplication::Fixed(1))],
+            &options,
+        )
+        .await?;
+    for r in res {
+        match r {
+            Err((topic, err)) => {
+                error!("Error creating topic {}: {}", &topic, err);
+            }
+            Ok(topic) => {
+                info!("Created topic {}", topic);
+            }
+        }
+    }
+
+    let producer: BaseProducer = ClientConfig::new()
+        .set("bootstrap.servers", &broker)
+        .create()
+        .expect("Producer creation error");
+    let send = |value: u64, partition: i32| -> Result<()> {
+        let payload = value.to_string();
+        let record = BaseRecord::<(), String>::to(topic)
+            .payload(&payload)
+            .partition(partition);
+        if producer.send(record).is_err() {
+            return Err("Unable to send record to Kafka".into());
+        }
+        producer.flush(Duration::from_secs(1));
+        Ok(())
+    };
+    let connector_config = literal!({
+        "codec": "json",
+        "config": {
+            "brokers": [
+                broker
+            ],
+            "group_id": group_id,
+            "topics": [
+                topic
+            ],
+            "assignment_strategy": "cooperative-sticky",
+            "mode": {
+                "custom": {
+                    "rdkafka_options": {},
+                    "retry_failed_events": false
+                }
+            }
+        }
+    });
+    let harness1 = ConnectorHarness::new(
+        "cooperative_sticky_1",
+        &kafka::consumer::Builder::default(),
+        &connector_config,
+    )
+    .await?;
+    let out1 = harness1.out().expect("No pipe connected to port OUT");
+    harness1.start().await?;
+    harness1.wait_for_connected().await?;
+    task::sleep(Duration::from_secs(5)).await;
+
+    // the only consumer gets both partitions
+    send(1, 0)?;
+    send(2, 1)?;
+    let mut values = vec![
+        out1.get_event().await?.data.suffix().value().as_u64(),
+        out1.get_event().await?.data.suffix().value().as_u64(),
+    ];
+    values.sort_unstable();
+    assert_eq!(vec![Some(1), Some(2)], values);
+
+    // the second consumer joining only takes over one of the partitions,
+    // the first one keeps consuming the other one after applying the incremental revoke
+    let harness2 = ConnectorHarness::new(
+        "cooperative_sticky_2",
+        &kafka::consumer::Builder::default(),
+        &connector_config,
+    )
+    .await?;
+    let out2 = harness2.out().expect("No pipe connected to port OUT");
+    harness2.start().await?;
+    harness2.wait_for_connected().await?;
+    task::sleep(Duration::from_secs(10)).await;
+
+    send(3, 0)?;
+    send(4, 1)?;
+    let first = out1.get_event().timeout(Duration::from_secs(10)).await??;
+    let second = out2.get_event().timeout(Duration::from_secs(10)).await??;
+    let mut values = vec![
+        first.data.suffix().value().as_u64(),
+        second.data.suffix().value().as_u64(),
+    ];
+    values.sort_unstable();
+    assert_eq!(vec![Some(3), Some(4)], values);
+
+    let (out_events, err_events) = harness2.stop().await?;
+    assert!(out_events.is_empty());
+    assert!(err_events.is_empty());
+    let (out_events, err_events) = harness1.stop().await?;
+    assert!(out_events.is_empty());
+    assert!(err_events.is_empty());
+
+    // cleanup
+    drop(container);
+    Ok(())
+}
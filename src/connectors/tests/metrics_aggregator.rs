@@ -0,0 +1,75 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::ConnectorHarness;
+use crate::{connectors::impls::metrics_aggregator, errors::Result};
+use tremor_common::ports::IN;
+use tremor_pipeline::{Event, EventId};
+use tremor_value::{literal, prelude::*};
+
+#[async_std::test]
+async fn interval_flush() -> Result<()> {
+    let _ = env_logger::try_init();
+
+    let defn = literal!({
+        "config": {
+            "group_by": "host",
+            "value_field": "duration",
+            "interval_ms": 500
+        }
+    });
+    let harness = ConnectorHarness::new(
+        function_name!(),
+        &metrics_aggregator::Builder::default(),
+        &defn,
+    )
+    .await?;
+    harness.start().await?;
+    harness.wait_for_connected().await?;
+    harness.consume_initial_sink_contraflow().await?;
+
+    let out = harness
+        .out()
+        .expect("No pipeline connected to 'out' port of metrics_aggregator connector");
+
+    for (host, duration) in [("snot", 1_u64), ("snot", 3), ("badger", 5)] {
+        let event = Event {
+            id: EventId::default(),
+            data: (
+                literal!({"host": host, "duration": duration}),
+                Value::object(),
+            )
+                .into(),
+            transactional: false,
+            ..Event::default()
+        };
+        harness.send_to_sink(event, IN).await?;
+    }
+
+    // summaries are emitted ordered by group once the interval elapsed
+    let event = out.get_event().await?;
+    assert_eq!(
+        &literal!({"group": "badger", "count": 1, "min": 5.0, "max": 5.0, "mean": 5.0}),
+        event.data.suffix().value()
+    );
+    let event = out.get_event().await?;
+    assert_eq!(
+        &literal!({"group": "snot", "count": 2, "min": 1.0, "max": 3.0, "mean": 2.0}),
+        event.data.suffix().value()
+    );
+
+    let (_out, err) = harness.stop().await?;
+    assert!(err.is_empty());
+    Ok(())
+}
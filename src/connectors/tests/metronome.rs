@@ -53,3 +53,80 @@ async fn connector_metronome_routing() -> Result<()> {
     assert!(err.is_empty());
     Ok(())
 }
+
+#[async_std::test]
+async fn connector_metronome_payload() -> Result<()> {
+    let _ = env_logger::try_init();
+
+    let defn = literal!({
+      "config": {
+        "interval": Duration::from_millis(50).as_nanos() as u64,
+        "payload": {"alive": true}
+      }
+    });
+    let harness =
+        ConnectorHarness::new(function_name!(), &metronome::Builder::default(), &defn).await?;
+    let out_pipeline = harness
+        .out()
+        .expect("No pipeline connected to 'out' port of metronome connector");
+    let start = tremor_common::time::nanotime();
+    harness.start().await?;
+    harness.wait_for_connected().await?;
+
+    let mut last_ts = start;
+    for tick in 0..2_u64 {
+        let event = out_pipeline.get_event().await?;
+        assert_eq!(&literal!({"alive": true}), event.data.suffix().value());
+        let meta = event.data.suffix().meta().get("metronome");
+        assert_eq!(Some(tick), meta.get_u64("tick"));
+        let ts = meta.get_u64("ts").expect("No ts in metronome metadata");
+        assert!(ts >= last_ts);
+        last_ts = ts;
+    }
+
+    // one event per interval, missed ticks are caught up with
+    async_std::task::sleep(Duration::from_millis(500)).await;
+    let (out, err) = harness.stop().await?;
+    assert!(err.is_empty());
+    let elapsed = Duration::from_nanos(tremor_common::time::nanotime() - start);
+    let max = u64::try_from(elapsed.as_millis() / 50)?;
+    let count = out.len() as u64 + 2;
+    assert!(
+        (max / 2..=max).contains(&count),
+        "{count} events in {elapsed:?}"
+    );
+    let ticks: Vec<_> = out
+        .iter()
+        .map(|event| event.data.suffix().meta().get("metronome").get_u64("tick"))
+        .collect();
+    let expected: Vec<_> = (2..count).map(Some).collect();
+    assert_eq!(expected, ticks);
+    Ok(())
+}
+
+#[async_std::test]
+async fn connector_metronome_instance_host() -> Result<()> {
+    let _ = env_logger::try_init();
+
+    let defn = literal!({
+      "instance_host": "snot.badger",
+      "config": {
+        "interval": Duration::from_millis(100).as_nanos() as u64
+      }
+    });
+    let harness =
+        ConnectorHarness::new(function_name!(), &metronome::Builder::default(), &defn).await?;
+    let out_pipeline = harness
+        .out()
+        .expect("No pipeline connected to 'out' port of metronome connector");
+    harness.start().await?;
+    harness.wait_for_connected().await?;
+
+    let event = out_pipeline.get_event().await?;
+    let origin_uri = event.origin_uri.expect("No origin uri");
+    assert_eq!("snot.badger", origin_uri.host);
+
+    let (_out, err) = harness.stop().await?;
+    assert!(err.is_empty());
+    Ok(())
+}
@@ -35,9 +35,11 @@ mod gpubsub;
 mod http;
 #[cfg(feature = "kafka-integration")]
 mod kafka;
+mod metrics_aggregator;
 #[cfg(feature = "metronome-integration")]
 mod metronome;
 mod pause_resume;
+mod replay;
 #[cfg(feature = "s3-integration")]
 mod s3;
 #[cfg(feature = "net-integration")]
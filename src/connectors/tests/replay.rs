@@ -0,0 +1,60 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::ConnectorHarness;
+use crate::{connectors::impls::replay, errors::Result};
+use std::io::Write;
+use tempfile::NamedTempFile;
+use tremor_value::{literal, prelude::*};
+
+#[async_std::test]
+async fn proportional_spacing() -> Result<()> {
+    let _ = env_logger::try_init();
+
+    // recorded 400ms apart
+    let mut file = NamedTempFile::new()?;
+    file.write_all(b"{\"ts\": 1000000000, \"event\": {\"snot\": 1}}\n")?;
+    file.write_all(b"not a record\n")?;
+    file.write_all(b"{\"ts\": 1400000000, \"event\": {\"snot\": 2}}\n")?;
+    file.flush()?;
+    let path = file.into_temp_path();
+
+    let defn = literal!({
+      "config": {
+        "path": path.display().to_string(),
+        "speed": 2.0
+      }
+    });
+    let harness =
+        ConnectorHarness::new(function_name!(), &replay::Builder::default(), &defn).await?;
+    let out = harness.out().expect("No out pipeline connected");
+    harness.start().await?;
+    harness.wait_for_connected().await?;
+
+    let first = out.get_event().await?;
+    assert_eq!(&literal!({"snot": 1}), first.data.suffix().value());
+    assert_eq!(
+        Some(1_000_000_000),
+        first.data.suffix().meta().get("replay").get_u64("ts")
+    );
+    let second = out.get_event().await?;
+    assert_eq!(&literal!({"snot": 2}), second.data.suffix().value());
+
+    // replayed at double speed, so about 200ms apart
+    let spacing_ms = (second.ingest_ns - first.ingest_ns) / 1_000_000;
+    assert!((190..400).contains(&spacing_ms), "{spacing_ms}ms apart");
+
+    let (_out, _err) = harness.stop().await?;
+    Ok(())
+}
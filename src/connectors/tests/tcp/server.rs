@@ -15,9 +15,12 @@
 use std::time::Duration;
 
 use crate::connectors::impls::tcp;
-use crate::connectors::tests::{free_port, ConnectorHarness};
+use crate::connectors::tests::{free_port, setup_for_tls, ConnectorHarness};
 use crate::errors::Result;
 use async_std::{io::WriteExt, net::TcpStream, prelude::*};
+use async_tls::TlsConnector;
+use rustls::ClientConfig;
+use std::sync::Arc;
 use tremor_common::ports::IN;
 use tremor_pipeline::{Event, EventId};
 use tremor_value::{literal, prelude::*, Value};
@@ -112,3 +115,463 @@ async fn server_event_routing() -> Result<()> {
     assert!(err.is_empty());
     Ok(())
 }
+
+#[async_std::test]
+async fn server_lifecycle_events() -> Result<()> {
+    let _ = env_logger::try_init();
+
+    let free_port = free_port::find_free_tcp_port().await?;
+
+    let server_addr = format!("127.0.0.1:{}", free_port);
+
+    let defn = literal!({
+      "codec": "string",
+      "preprocessors": ["separate"],
+      "config": {
+        "url": format!("tcp://127.0.0.1:{free_port}"),
+        "buf_size": 4096,
+        "lifecycle_events": true
+      }
+    });
+    let harness =
+        ConnectorHarness::new(function_name!(), &tcp::server::Builder::default(), &defn).await?;
+    let out_pipeline = harness
+        .out()
+        .expect("No pipeline connected to 'out' port of tcp_server connector");
+    harness.start().await?;
+    harness.wait_for_connected().await?;
+
+    let mut socket = TcpStream::connect(&server_addr).await?;
+    socket.write_all("snot\n".as_bytes()).await?;
+
+    // connected event comes first
+    let event = out_pipeline.get_event().await?;
+    let (data, meta) = event.data.parts();
+    assert_eq!(&Value::const_null(), data);
+    let tcp_server_meta = meta.get("tcp_server");
+    assert_eq!(Some("connected"), tcp_server_meta.get_str("event"));
+    assert!(tcp_server_meta.get_object("peer").is_some());
+
+    // then the data
+    let event = out_pipeline.get_event().await?;
+    let (data, meta) = event.data.parts();
+    assert_eq!(Some("snot"), data.as_str());
+    assert_eq!(None, meta.get("tcp_server").get_str("event"));
+
+    // closing the connection emits a disconnected event
+    socket.shutdown(std::net::Shutdown::Both)?;
+    drop(socket);
+    let event = out_pipeline.get_event().await?;
+    let (data, meta) = event.data.parts();
+    assert_eq!(&Value::const_null(), data);
+    let tcp_server_meta = meta.get("tcp_server");
+    assert_eq!(Some("disconnected"), tcp_server_meta.get_str("event"));
+    assert!(tcp_server_meta.get_object("peer").is_some());
+
+    //cleanup
+    let (_out, err) = harness.stop().await?;
+    assert!(err.is_empty());
+    Ok(())
+}
+
+#[async_std::test]
+async fn server_stream_id_routing() -> Result<()> {
+    let _ = env_logger::try_init();
+
+    let free_port = free_port::find_free_tcp_port().await?;
+
+    let server_addr = format!("127.0.0.1:{}", free_port);
+
+    let defn = literal!({
+      "codec": "string",
+      "preprocessors": ["separate"],
+      "config": {
+        "url": format!("tcp://127.0.0.1:{free_port}"),
+        "buf_size": 4096
+      }
+    });
+    let harness =
+        ConnectorHarness::new(function_name!(), &tcp::server::Builder::default(), &defn).await?;
+    let out_pipeline = harness
+        .out()
+        .expect("No pipeline connected to 'out' port of tcp_server connector");
+    harness.start().await?;
+    harness.wait_for_connected().await?;
+
+    let mut socket1 = TcpStream::connect(&server_addr).await?;
+    let mut socket2 = TcpStream::connect(&server_addr).await?;
+    socket1.write_all("snot\n".as_bytes()).await?;
+    let event = out_pipeline.get_event().await?;
+    let peer1 = event
+        .data
+        .suffix()
+        .meta()
+        .get("tcp_server")
+        .get("peer")
+        .map(Value::clone_static)
+        .unwrap_or_default();
+    socket2.write_all("badger\n".as_bytes()).await?;
+    let event = out_pipeline.get_event().await?;
+    let stream_id2 = event
+        .data
+        .suffix()
+        .meta()
+        .get("tcp_server")
+        .get_u64("stream_id")
+        .ok_or("Missing stream_id")?;
+
+    // route by peer
+    let event = Event {
+        id: EventId::default(),
+        data: (
+            Value::from("by_peer"),
+            literal!({ "tcp_server": { "peer": peer1 } }),
+        )
+            .into(),
+        ..Event::default()
+    };
+    harness.send_to_sink(event, IN).await?;
+    let mut buf = vec![0_u8; 8192];
+    let bytes_read = socket1
+        .read(&mut buf)
+        .timeout(Duration::from_secs(2))
+        .await??;
+    assert_eq!("by_peer", &String::from_utf8_lossy(&buf[0..bytes_read]));
+
+    // route by stream id, it takes precedence over the peer of another connection
+    let event = Event {
+        id: EventId::default(),
+        data: (
+            Value::from("by_stream_id"),
+            literal!({
+                "tcp_server": {
+                    "peer": {"host": "10.0.0.1", "port": 1},
+                    "stream_id": stream_id2
+                }
+            }),
+        )
+            .into(),
+        ..Event::default()
+    };
+    harness.send_to_sink(event, IN).await?;
+    let bytes_read = socket2
+        .read(&mut buf)
+        .timeout(Duration::from_secs(2))
+        .await??;
+    assert_eq!(
+        "by_stream_id",
+        &String::from_utf8_lossy(&buf[0..bytes_read])
+    );
+
+    //cleanup
+    let (_out, err) = harness.stop().await?;
+    assert!(err.is_empty());
+    Ok(())
+}
+
+#[async_std::test]
+async fn server_broadcast() -> Result<()> {
+    let _ = env_logger::try_init();
+
+    let free_port = free_port::find_free_tcp_port().await?;
+
+    let server_addr = format!("127.0.0.1:{}", free_port);
+
+    let defn = literal!({
+      "codec": "string",
+      "preprocessors": ["separate"],
+      "config": {
+        "url": format!("tcp://127.0.0.1:{free_port}"),
+        "buf_size": 4096,
+        "broadcast": true
+      }
+    });
+    let harness =
+        ConnectorHarness::new(function_name!(), &tcp::server::Builder::default(), &defn).await?;
+    let out_pipeline = harness
+        .out()
+        .expect("No pipeline connected to 'out' port of tcp_server connector");
+    harness.start().await?;
+    harness.wait_for_connected().await?;
+
+    // connect 2 client sockets and make sure they are both accepted
+    let mut socket1 = TcpStream::connect(&server_addr).await?;
+    let mut socket2 = TcpStream::connect(&server_addr).await?;
+    socket1.write_all("snot\n".as_bytes()).await?;
+    out_pipeline.get_event().await?;
+    socket2.write_all("badger\n".as_bytes()).await?;
+    out_pipeline.get_event().await?;
+
+    // an event without peer metadata and without tracked streams goes to all clients
+    let event = Event {
+        id: EventId::default(),
+        data: (Value::String("broadcast".into()), Value::object()).into(),
+        ..Event::default()
+    };
+    harness.send_to_sink(event, IN).await?;
+
+    let mut buf = vec![0_u8; 8192];
+    let bytes_read = socket1
+        .read(&mut buf)
+        .timeout(Duration::from_secs(2))
+        .await??;
+    assert_eq!("broadcast", &String::from_utf8_lossy(&buf[0..bytes_read]));
+    let bytes_read = socket2
+        .read(&mut buf)
+        .timeout(Duration::from_secs(2))
+        .await??;
+    assert_eq!("broadcast", &String::from_utf8_lossy(&buf[0..bytes_read]));
+
+    //cleanup
+    let (_out, err) = harness.stop().await?;
+    assert!(err.is_empty());
+    Ok(())
+}
+
+#[async_std::test]
+async fn server_tls_sni() -> Result<()> {
+    let _ = env_logger::try_init();
+    setup_for_tls();
+
+    let free_port = free_port::find_free_tcp_port().await?;
+
+    let defn = literal!({
+      "codec": "string",
+      "preprocessors": ["separate"],
+      "config": {
+        "url": format!("tcp://127.0.0.1:{free_port}"),
+        "buf_size": 4096,
+        "tls": {
+            "cert": "./tests/localhost.cert",
+            "key": "./tests/localhost.key"
+        }
+      }
+    });
+    let harness =
+        ConnectorHarness::new(function_name!(), &tcp::server::Builder::default(), &defn).await?;
+    let out_pipeline = harness
+        .out()
+        .expect("No pipeline connected to 'out' port of tcp_server connector");
+    harness.start().await?;
+    harness.wait_for_connected().await?;
+
+    let mut config = ClientConfig::new();
+    let mut pem = std::io::BufReader::new(std::fs::File::open("./tests/localhost.cert")?);
+    config
+        .root_store
+        .add_pem_file(&mut pem)
+        .map_err(|_e| "Error adding pem file to root store")?;
+    let tcp_stream = TcpStream::connect(("127.0.0.1", free_port)).await?;
+    // the client sends the name it connects to as SNI
+    let mut tls_stream = TlsConnector::from(Arc::new(config))
+        .connect("localhost", tcp_stream)
+        .await?;
+    tls_stream.write_all("snot\n".as_bytes()).await?;
+    tls_stream.flush().await?;
+
+    let event = out_pipeline.get_event().await?;
+    let meta = event.data.suffix().meta().get("tcp_server");
+    assert_eq!(Some(true), meta.get_bool("tls"));
+    assert_eq!(Some("localhost"), meta.get_str("tls_sni"));
+
+    //cleanup
+    let (_out, err) = harness.stop().await?;
+    assert!(err.is_empty());
+    Ok(())
+}
+
+/// Sends a reply flagged with `close_after_write` to the client,
+/// which receives it before the connection is closed
+async fn close_after_write(tls: bool) -> Result<()> {
+    let _ = env_logger::try_init();
+    if tls {
+        setup_for_tls();
+    }
+
+    let free_port = free_port::find_free_tcp_port().await?;
+
+    let mut config = literal!({
+        "url": format!("tcp://127.0.0.1:{free_port}"),
+        "buf_size": 4096
+    });
+    if tls {
+        config.try_insert(
+            "tls",
+            literal!({
+                "cert": "./tests/localhost.cert",
+                "key": "./tests/localhost.key"
+            }),
+        );
+    }
+    let defn = literal!({
+      "codec": "string",
+      "preprocessors": ["separate"],
+      "config": config
+    });
+    let harness =
+        ConnectorHarness::new(function_name!(), &tcp::server::Builder::default(), &defn).await?;
+    let out_pipeline = harness
+        .out()
+        .expect("No pipeline connected to 'out' port of tcp_server connector");
+    harness.start().await?;
+    harness.wait_for_connected().await?;
+
+    let tcp_stream = TcpStream::connect(("127.0.0.1", free_port)).await?;
+    let mut socket: Box<dyn ReadWrite> = if tls {
+        let mut config = ClientConfig::new();
+        let mut pem = std::io::BufReader::new(std::fs::File::open("./tests/localhost.cert")?);
+        config
+            .root_store
+            .add_pem_file(&mut pem)
+            .map_err(|_e| "Error adding pem file to root store")?;
+        Box::new(
+            TlsConnector::from(Arc::new(config))
+                .connect("localhost", tcp_stream)
+                .await?,
+        )
+    } else {
+        Box::new(tcp_stream)
+    };
+    socket.write_all("snot\n".as_bytes()).await?;
+    socket.flush().await?;
+    let event = out_pipeline.get_event().await?;
+    let stream_id = event
+        .data
+        .suffix()
+        .meta()
+        .get("tcp_server")
+        .get_u64("stream_id")
+        .ok_or("Missing stream_id")?;
+
+    let event = Event {
+        id: EventId::default(),
+        data: (
+            Value::from("badger"),
+            literal!({
+                "tcp_server": {
+                    "stream_id": stream_id,
+                    "close_after_write": true
+                }
+            }),
+        )
+            .into(),
+        ..Event::default()
+    };
+    harness.send_to_sink(event, IN).await?;
+    // everything written arrives before the end of the stream
+    let mut received = Vec::new();
+    socket
+        .read_to_end(&mut received)
+        .timeout(Duration::from_secs(5))
+        .await??;
+    assert_eq!("badger", &String::from_utf8_lossy(&received));
+
+    //cleanup
+    let (_out, err) = harness.stop().await?;
+    assert!(err.is_empty());
+    Ok(())
+}
+
+trait ReadWrite: futures::AsyncRead + futures::AsyncWrite + Unpin + Send {}
+impl<T> ReadWrite for T where T: futures::AsyncRead + futures::AsyncWrite + Unpin + Send {}
+
+#[async_std::test]
+async fn server_close_after_write() -> Result<()> {
+    close_after_write(false).await
+}
+
+#[async_std::test]
+async fn server_tls_close_after_write() -> Result<()> {
+    close_after_write(true).await
+}
+
+#[async_std::test]
+async fn server_tls_passthrough_slow_client_hello() -> Result<()> {
+    let _ = env_logger::try_init();
+
+    let free_port = free_port::find_free_tcp_port().await?;
+
+    let defn = literal!({
+      "codec": "string",
+      "preprocessors": ["separate"],
+      "config": {
+        "url": format!("tcp://127.0.0.1:{free_port}"),
+        "buf_size": 4096,
+        "tls_mode": "passthrough",
+        "client_hello_timeout_ms": 60_000
+      }
+    });
+    let harness =
+        ConnectorHarness::new(function_name!(), &tcp::server::Builder::default(), &defn).await?;
+    let out_pipeline = harness
+        .out()
+        .expect("No pipeline connected to 'out' port of tcp_server connector");
+    harness.start().await?;
+    harness.wait_for_connected().await?;
+
+    // a client that never sends its ClientHello doesn't hold up other connections
+    let _stalled = TcpStream::connect(("127.0.0.1", free_port)).await?;
+    let mut socket = TcpStream::connect(("127.0.0.1", free_port)).await?;
+    socket.write_all("snot\n".as_bytes()).await?;
+
+    let event = out_pipeline.get_event().await?;
+    let (data, meta) = event.data.parts();
+    assert_eq!(Some("snot"), data.as_str());
+    let meta = meta.get("tcp_server");
+    assert_eq!(Some(true), meta.get_bool("tls_passthrough"));
+    assert_eq!(None, meta.get_str("tls_sni"));
+
+    //cleanup
+    let (_out, err) = harness.stop().await?;
+    assert!(err.is_empty());
+    Ok(())
+}
+
+#[async_std::test]
+async fn server_max_frame_len() -> Result<()> {
+    let _ = env_logger::try_init();
+
+    let free_port = free_port::find_free_tcp_port().await?;
+
+    let server_addr = format!("127.0.0.1:{}", free_port);
+
+    let defn = literal!({
+      "codec": "string",
+      "preprocessors": ["separate"],
+      "config": {
+        "url": format!("tcp://127.0.0.1:{free_port}"),
+        "buf_size": 4096,
+        "max_frame_len": 5,
+        "on_frame_overflow": "error"
+      }
+    });
+    let harness =
+        ConnectorHarness::new(function_name!(), &tcp::server::Builder::default(), &defn).await?;
+    let out_pipeline = harness
+        .out()
+        .expect("No pipeline connected to 'out' port of tcp_server connector");
+    let err_pipeline = harness
+        .err()
+        .expect("No pipeline connected to 'err' port of tcp_server connector");
+    harness.start().await?;
+    harness.wait_for_connected().await?;
+
+    let mut socket = TcpStream::connect(&server_addr).await?;
+    socket.write_all("snot\nbadg".as_bytes()).await?;
+    let event = out_pipeline.get_event().await?;
+    assert_eq!(&Value::from("snot"), event.data.suffix().value());
+
+    // the line only turns out to be too long with its second part
+    socket.write_all("er\nsnot\n".as_bytes()).await?;
+    let event = err_pipeline.get_event().await?;
+    assert_eq!(
+        Some(&b"badger\n"[..]),
+        event.data.suffix().value().as_bytes()
+    );
+    let event = out_pipeline.get_event().await?;
+    assert_eq!(&Value::from("snot"), event.data.suffix().value());
+
+    //cleanup
+    let (_out, _err) = harness.stop().await?;
+    Ok(())
+}
@@ -136,3 +136,54 @@ async fn unix_socket() -> Result<()> {
     assert!(err.is_empty());
     Ok(())
 }
+
+#[async_std::test]
+async fn unix_socket_client_max_frame_len() -> Result<()> {
+    let _ = env_logger::try_init();
+
+    let temp_file = tempfile::Builder::new().tempfile()?;
+    let temp_path = temp_file.into_temp_path();
+    let socket_path = temp_path.to_path_buf();
+    temp_path.close()?;
+    let listener = async_std::os::unix::net::UnixListener::bind(&socket_path).await?;
+
+    let defn = literal!({
+      "codec": "string",
+      "preprocessors": [{"name": "separate", "config": {"separator": "|"}}],
+      "config": {
+          "path": socket_path.display().to_string(),
+          "max_frame_len": 5,
+          "on_frame_overflow": "error"
+      }
+    });
+    let harness = ConnectorHarness::new(
+        function_name!(),
+        &unix_socket::client::Builder::default(),
+        &defn,
+    )
+    .await?;
+    let out_pipeline = harness
+        .out()
+        .expect("No pipeline connected to 'out' port of unix_socket_client connector");
+    let err_pipeline = harness
+        .err()
+        .expect("No pipeline connected to 'err' port of unix_socket_client connector");
+    harness.start().await?;
+    harness.wait_for_connected().await?;
+
+    let (mut socket, _) = listener.accept().await?;
+    // lines are split at the separator of the preprocessor, not at newlines
+    socket.write_all(b"snot|bad\nger|cake|").await?;
+    let event = out_pipeline.get_event().await?;
+    assert_eq!(&Value::from("snot"), event.data.suffix().value());
+    let event = err_pipeline.get_event().await?;
+    assert_eq!(
+        Some(&b"bad\nger|"[..]),
+        event.data.suffix().value().as_bytes()
+    );
+    let event = out_pipeline.get_event().await?;
+    assert_eq!(&Value::from("cake"), event.data.suffix().value());
+
+    let (_out, _err) = harness.stop().await?;
+    Ok(())
+}
@@ -13,6 +13,7 @@
 // limitations under the License.
 use super::ConnectorHarness;
 use crate::{connectors::impls::wal, errors::Result};
+use async_std::prelude::FutureExt;
 use std::time::Duration;
 use tremor_common::{
     ids::{Id, SourceId},
@@ -115,3 +116,105 @@ async fn wal() -> Result<()> {
 
     Ok(())
 }
+
+#[async_std::test]
+async fn wal_max_in_flight() -> Result<()> {
+    let _ = env_logger::try_init();
+    let temp_dir = tempfile::Builder::new().tempdir()?;
+
+    let config = literal!({
+        "max_in_flight": 2,
+        "config": {
+            "path": temp_dir.path().display().to_string(),
+            "chunk_size": 1024,
+            "max_chunks": 100
+        }
+    });
+    let harness =
+        ConnectorHarness::new(function_name!(), &wal::Builder::default(), &config).await?;
+    harness.start().await?;
+    harness.wait_for_connected().await?;
+    harness.consume_initial_sink_contraflow().await?;
+
+    let out = harness.out().expect("No pipeline connected to WAL out.");
+
+    let mut id_gen = EventIdGenerator::new(SourceId::new(1));
+    for i in 0..4_u64 {
+        let event = Event {
+            id: id_gen.next_id(),
+            data: (Value::from(i), Value::object()).into(),
+            transactional: true,
+            ..Event::default()
+        };
+        harness.send_to_sink(event, IN).await?;
+    }
+
+    let first = out.get_event().await?;
+    assert_eq!(&Value::from(0_u64), first.data.suffix().value());
+    let second = out.get_event().await?;
+    assert_eq!(&Value::from(1_u64), second.data.suffix().value());
+    // reading stalls with 2 events in flight
+    out.expect_no_event_for(Duration::from_millis(500)).await?;
+
+    // and resumes as they are acked
+    harness.send_contraflow(CbAction::Ack, first.id).await?;
+    let third = out.get_event().await?;
+    assert_eq!(&Value::from(2_u64), third.data.suffix().value());
+    out.expect_no_event_for(Duration::from_millis(500)).await?;
+
+    harness.send_contraflow(CbAction::Ack, second.id).await?;
+    harness.send_contraflow(CbAction::Ack, third.id).await?;
+    let fourth = out.get_event().await?;
+    assert_eq!(&Value::from(3_u64), fourth.data.suffix().value());
+
+    let (_out, err) = harness.stop().await?;
+    assert!(err.is_empty());
+    Ok(())
+}
+
+#[async_std::test]
+async fn wal_in_flight_timeout() -> Result<()> {
+    let _ = env_logger::try_init();
+    let temp_dir = tempfile::Builder::new().tempdir()?;
+
+    let config = literal!({
+        "max_in_flight": 1,
+        "in_flight_timeout_ms": 1000,
+        "config": {
+            "path": temp_dir.path().display().to_string(),
+            "chunk_size": 1024,
+            "max_chunks": 100
+        }
+    });
+    let harness =
+        ConnectorHarness::new(function_name!(), &wal::Builder::default(), &config).await?;
+    harness.start().await?;
+    harness.wait_for_connected().await?;
+    harness.consume_initial_sink_contraflow().await?;
+
+    let out = harness.out().expect("No pipeline connected to WAL out.");
+
+    let mut id_gen = EventIdGenerator::new(SourceId::new(1));
+    for i in 0..2_u64 {
+        let event = Event {
+            id: id_gen.next_id(),
+            data: (Value::from(i), Value::object()).into(),
+            transactional: true,
+            ..Event::default()
+        };
+        harness.send_to_sink(event, IN).await?;
+    }
+
+    // the first event is never acked
+    let first = out.get_event().await?;
+    assert_eq!(&Value::from(0_u64), first.data.suffix().value());
+    out.expect_no_event_for(Duration::from_millis(500)).await?;
+
+    // reading resumes once it expired, without any other message waking up the source
+    let second = out.get_event().timeout(Duration::from_secs(5)).await??;
+    assert_eq!(&Value::from(1_u64), second.data.suffix().value());
+
+    let (_out, err) = harness.stop().await?;
+    assert!(err.is_empty());
+    Ok(())
+}
@@ -16,6 +16,7 @@ use super::{free_port::find_free_tcp_port, setup_for_tls, ConnectorHarness};
 use crate::connectors::impls::ws;
 use crate::connectors::{impls::ws::WsDefaults, utils::url::Url};
 use crate::errors::{Error, Result, ResultExt};
+use crate::system::KillSwitch;
 use async_std::{
     channel::{bounded, Receiver, Sender, TryRecvError},
     net::{TcpListener, TcpStream},
@@ -39,8 +40,8 @@ use std::{
         Arc,
     },
 };
-use tremor_common::ports::IN;
-use tremor_pipeline::{Event, EventId};
+use tremor_common::ports::{ERR, IN, OUT};
+use tremor_pipeline::{CbAction, Event, EventId};
 use tremor_value::{literal, prelude::*, Value};
 use tungstenite::protocol::{frame::coding::CloseCode, CloseFrame};
 
@@ -122,7 +123,6 @@ impl TestClient<WebSocket<MaybeTlsStream<std::net::TcpStream>>> {
         Ok(Self { client })
     }
 
-    #[cfg(feature = "flaky-test")]
     fn ping(&mut self) -> Result<()> {
         self.client
             .write_message(Message::Ping(vec![1, 2, 3, 4]))
@@ -145,6 +145,12 @@ impl TestClient<WebSocket<MaybeTlsStream<std::net::TcpStream>>> {
             .chain_err(|| "Failed to send to ws server")
     }
 
+    fn send_binary(&mut self, data: &[u8]) -> Result<()> {
+        self.client
+            .write_message(Message::Binary(data.to_vec()))
+            .chain_err(|| "Failed to send to ws server")
+    }
+
     fn port(&mut self) -> Result<u16> {
         match self.client.get_ref() {
             MaybeTlsStream::Plain(client) => Ok(client.local_addr()?.port()),
@@ -278,6 +284,439 @@ async fn ws_client_bad_config() -> Result<()> {
     Ok(())
 }
 
+#[async_std::test]
+async fn ws_client_invalid_header() -> Result<()> {
+    let defn = literal!({
+      "codec": "string",
+      "config": {
+          "url": "ws://127.0.0.1:8080",
+          "headers": {
+              "snot badger": "value"
+          }
+      }
+    });
+
+    let e = ConnectorHarness::new(function_name!(), &ws::client::Builder::default(), &defn)
+        .await
+        .err()
+        .map(|e| e.to_string())
+        .unwrap_or_default();
+    assert!(e.contains("Invalid header `snot badger`"));
+    Ok(())
+}
+
+#[async_std::test]
+async fn ws_server_opcodes() -> Result<()> {
+    let _ = env_logger::try_init();
+
+    let free_port = find_free_tcp_port().await?;
+    let url = format!("ws://0.0.0.0:{free_port}");
+    let defn = literal!({
+      "codec": "json",
+      "config": {
+        "url": url.clone(),
+        "control_frames": true
+      }
+    });
+
+    let harness = ConnectorHarness::new_with_ports(
+        function_name!(),
+        &ws::server::Builder::default(),
+        &defn,
+        KillSwitch::dummy(),
+        vec![IN],
+        vec![OUT, ERR, "control".into()],
+    )
+    .await?;
+    let out_pipeline = harness
+        .out()
+        .expect("No pipeline connected to 'out' port of ws_server connector");
+    let control_pipeline = harness
+        .get_pipe("control")
+        .expect("No pipeline connected to 'control' port of ws_server connector");
+
+    harness.start().await?;
+    harness.wait_for_connected().await?;
+
+    let start = Instant::now();
+    let timeout = Duration::from_secs(30);
+    let mut c1 = loop {
+        match TestClient::new(url.as_str()) {
+            Err(e) => {
+                if start.elapsed() > timeout {
+                    return Err(format!(
+                        "Timeout waiting for the ws server to start listening: {e}."
+                    )
+                    .into());
+                }
+                task::sleep(Duration::from_secs(1)).await;
+            }
+            Ok(client) => {
+                break client;
+            }
+        }
+    };
+
+    c1.send("\"snot\"")?;
+    let event = out_pipeline.get_event().await?;
+    let (data, meta) = event.data.parts();
+    assert_eq!("snot", &data.to_string());
+    assert_eq!(Some("text"), meta.get("ws_server").get_str("opcode"));
+    assert_eq!(None, meta.get_bool("binary"));
+
+    c1.send_binary(b"\"badger\"")?;
+    let event = out_pipeline.get_event().await?;
+    let (data, meta) = event.data.parts();
+    assert_eq!("badger", &data.to_string());
+    assert_eq!(Some("binary"), meta.get("ws_server").get_str("opcode"));
+    assert_eq!(Some(true), meta.get_bool("binary"));
+
+    c1.ping()?;
+    let event = control_pipeline.get_event().await?;
+    let (data, meta) = event.data.parts();
+    assert_eq!(Some(&[1_u8, 2, 3, 4][..]), data.as_bytes());
+    assert_eq!(Some("ping"), meta.get("ws_server").get_str("opcode"));
+
+    let (_out, err) = harness.stop().await?;
+    assert!(err.is_empty());
+    c1.close().await?;
+    Ok(())
+}
+
+/// Sends `data` to a client of a ws server configured with the given `frame` mode,
+/// returning the messages the client receives
+async fn ws_server_frames(
+    name: &str,
+    frame: &str,
+    data: Vec<Value<'static>>,
+) -> Result<Vec<ExpectMessage>> {
+    let free_port = find_free_tcp_port().await?;
+    let url = format!("ws://0.0.0.0:{free_port}");
+    let defn = literal!({
+      "codec": "binary",
+      "config": {
+        "url": url.clone(),
+        "frame": frame
+      }
+    });
+
+    let harness = ConnectorHarness::new(
+        &format!("{name}_{frame}"),
+        &ws::server::Builder::default(),
+        &defn,
+    )
+    .await?;
+    let out_pipeline = harness
+        .out()
+        .expect("No pipeline connected to 'out' port of ws_server connector");
+
+    harness.start().await?;
+    harness.wait_for_connected().await?;
+
+    let start = Instant::now();
+    let timeout = Duration::from_secs(30);
+    let mut c1 = loop {
+        match TestClient::new(url.as_str()) {
+            Err(e) => {
+                if start.elapsed() > timeout {
+                    return Err(format!(
+                        "Timeout waiting for the ws server to start listening: {e}."
+                    )
+                    .into());
+                }
+                task::sleep(Duration::from_secs(1)).await;
+            }
+            Ok(client) => {
+                break client;
+            }
+        }
+    };
+
+    // the peer to send the events to is taken from the first event of the client
+    c1.send("snot")?;
+    let event = out_pipeline.get_event().await?;
+    let peer_host = event
+        .data
+        .suffix()
+        .meta()
+        .get("ws_server")
+        .get("peer")
+        .get_str("host")
+        .map(ToString::to_string)
+        .unwrap_or_default();
+
+    let mut received = Vec::with_capacity(data.len());
+    for data in data {
+        let meta = literal!({
+            "ws_server": {
+                "peer": {
+                    "host": peer_host.clone(),
+                    "port": c1.port()?,
+                }
+            }
+        });
+        let event = Event {
+            id: EventId::default(),
+            data: (data, meta).into(),
+            ..Event::default()
+        };
+        harness.send_to_sink(event, IN).await?;
+        received.push(c1.expect()?);
+    }
+
+    let (_out, err) = harness.stop().await?;
+    assert!(err.is_empty());
+    c1.close().await?;
+    Ok(received)
+}
+
+#[async_std::test]
+async fn ws_server_frame_modes() -> Result<()> {
+    let _ = env_logger::try_init();
+
+    let text = Value::from("badger");
+    let bytes = Value::Bytes(vec![0xff_u8, 0x00].into());
+
+    let received = ws_server_frames(function_name!(), "text", vec![text.clone_static()]).await?;
+    assert_eq!(vec![ExpectMessage::Text("badger".into())], received);
+
+    let received = ws_server_frames(
+        function_name!(),
+        "binary",
+        vec![text.clone_static(), bytes.clone_static()],
+    )
+    .await?;
+    assert_eq!(
+        vec![
+            ExpectMessage::Binary(b"badger".to_vec()),
+            ExpectMessage::Binary(vec![0xff, 0x00])
+        ],
+        received
+    );
+
+    // auto only sends valid UTF-8 as text
+    let received = ws_server_frames(
+        function_name!(),
+        "auto",
+        vec![text.clone_static(), bytes.clone_static()],
+    )
+    .await?;
+    assert_eq!(
+        vec![
+            ExpectMessage::Text("badger".into()),
+            ExpectMessage::Binary(vec![0xff, 0x00])
+        ],
+        received
+    );
+    Ok(())
+}
+
+#[async_std::test]
+async fn ws_server_handshake_timeout() -> Result<()> {
+    use async_std::io::ReadExt;
+    use async_std::prelude::FutureExt;
+    let _ = env_logger::try_init();
+
+    let free_port = find_free_tcp_port().await?;
+    let url = format!("ws://0.0.0.0:{free_port}");
+    let defn = literal!({
+      "codec": "json",
+      "config": {
+        "url": url.clone(),
+        "handshake_timeout_ms": 200
+      }
+    });
+
+    let harness =
+        ConnectorHarness::new(function_name!(), &ws::server::Builder::default(), &defn).await?;
+    let out_pipeline = harness
+        .out()
+        .expect("No pipeline connected to 'out' port of ws_server connector");
+
+    harness.start().await?;
+    harness.wait_for_connected().await?;
+
+    let start = Instant::now();
+    let timeout = Duration::from_secs(30);
+    // connect without ever starting the handshake
+    let mut stalled = loop {
+        match TcpStream::connect(("127.0.0.1", free_port)).await {
+            Err(e) => {
+                if start.elapsed() > timeout {
+                    return Err(format!(
+                        "Timeout waiting for the ws server to start listening: {e}."
+                    )
+                    .into());
+                }
+                task::sleep(Duration::from_secs(1)).await;
+            }
+            Ok(stream) => break stream,
+        }
+    };
+
+    // the server drops the connection once the handshake timed out
+    let mut buf = [0_u8; 16];
+    let read = stalled
+        .read(&mut buf)
+        .timeout(Duration::from_secs(5))
+        .await
+        .map_err(|_| Error::from("Stalled connection was not dropped"))?;
+    assert!(matches!(read, Ok(0) | Err(_)));
+
+    // and accepts new connections afterwards
+    let mut c1 = TestClient::new(url.as_str())?;
+    c1.send("\"snot\"")?;
+    let event = out_pipeline.get_event().await?;
+    assert_eq!("snot", &event.data.suffix().value().to_string());
+
+    let (_out, err) = harness.stop().await?;
+    assert!(err.is_empty());
+    c1.close().await?;
+    Ok(())
+}
+
+#[async_std::test]
+async fn ws_server_close_events() -> Result<()> {
+    let _ = env_logger::try_init();
+
+    let free_port = find_free_tcp_port().await?;
+    let url = format!("ws://0.0.0.0:{free_port}");
+    let defn = literal!({
+      "codec": "json",
+      "config": {
+        "url": url.clone(),
+        "close_events": true
+      }
+    });
+
+    let harness =
+        ConnectorHarness::new(function_name!(), &ws::server::Builder::default(), &defn).await?;
+    let out_pipeline = harness
+        .out()
+        .expect("No pipeline connected to 'out' port of ws_server connector");
+
+    harness.start().await?;
+    harness.wait_for_connected().await?;
+
+    let start = Instant::now();
+    let timeout = Duration::from_secs(30);
+    let mut c1 = loop {
+        match TestClient::new(url.as_str()) {
+            Err(e) => {
+                if start.elapsed() > timeout {
+                    return Err(format!(
+                        "Timeout waiting for the ws server to start listening: {e}."
+                    )
+                    .into());
+                }
+                task::sleep(Duration::from_secs(1)).await;
+            }
+            Ok(client) => {
+                break client;
+            }
+        }
+    };
+    let port = c1.port()?;
+    c1.close().await?;
+
+    let event = out_pipeline.get_event().await?;
+    let (data, meta) = event.data.parts();
+    assert!(data.is_null());
+    let ws_server_meta = meta.get("ws_server");
+    assert_eq!(Some("disconnected"), ws_server_meta.get_str("event"));
+    assert_eq!(Some(1000), ws_server_meta.get_u16("code"));
+    assert_eq!(
+        Some("WS Test client closing."),
+        ws_server_meta.get_str("reason")
+    );
+    assert_eq!(Some(port), ws_server_meta.get("peer").get_u16("port"));
+
+    let (_out, err) = harness.stop().await?;
+    assert!(err.is_empty());
+    Ok(())
+}
+
+#[async_std::test]
+async fn ws_client_to_ws_server() -> Result<()> {
+    let _ = env_logger::try_init();
+
+    let free_port = find_free_tcp_port().await?;
+    let server_defn = literal!({
+      "codec": "json",
+      "config": {
+        "url": format!("ws://127.0.0.1:{free_port}")
+      }
+    });
+    let server = ConnectorHarness::new(
+        &format!("{}_server", function_name!()),
+        &ws::server::Builder::default(),
+        &server_defn,
+    )
+    .await?;
+    let server_out = server
+        .out()
+        .expect("No pipeline connected to 'out' port of ws_server connector");
+    server.start().await?;
+    server.wait_for_connected().await?;
+
+    // wait for the server to accept connections
+    let url = format!("ws://127.0.0.1:{free_port}");
+    let start = Instant::now();
+    let timeout = Duration::from_secs(30);
+    loop {
+        match TestClient::new(url.as_str()) {
+            Err(e) => {
+                if start.elapsed() > timeout {
+                    return Err(format!(
+                        "Timeout waiting for the ws server to start listening: {e}."
+                    )
+                    .into());
+                }
+                task::sleep(Duration::from_secs(1)).await;
+            }
+            Ok(mut client) => {
+                client.close().await?;
+                break;
+            }
+        }
+    }
+
+    let client_defn = literal!({
+      "codec": "json",
+      "config": {
+        "url": url.clone(),
+        "headers": {
+          "x-snot": "badger"
+        }
+      }
+    });
+    let client = ConnectorHarness::new(
+        &format!("{}_client", function_name!()),
+        &ws::client::Builder::default(),
+        &client_defn,
+    )
+    .await?;
+    client.start().await?;
+    client.wait_for_connected().await?;
+    client.consume_initial_sink_contraflow().await?;
+
+    let event = Event {
+        id: EventId::default(),
+        data: (Value::String("badger".into()), literal!({})).into(),
+        ..Event::default()
+    };
+    client.send_to_sink(event, IN).await?;
+
+    let event = server_out.get_event().await?;
+    assert_eq!(&Value::from("badger"), event.data.suffix().value());
+
+    let (_out, err) = client.stop().await?;
+    assert!(err.is_empty());
+    let (_out, err) = server.stop().await?;
+    assert!(err.is_empty());
+    Ok(())
+}
+
 #[async_std::test]
 async fn ws_server_text_routing() -> Result<()> {
     let _ = env_logger::try_init();
@@ -357,6 +796,82 @@ async fn ws_server_text_routing() -> Result<()> {
     Ok(())
 }
 
+#[async_std::test]
+async fn ws_server_max_in_flight() -> Result<()> {
+    use async_std::prelude::FutureExt;
+    let _ = env_logger::try_init();
+
+    let free_port = find_free_tcp_port().await?;
+    let url = format!("ws://0.0.0.0:{free_port}");
+    let defn = literal!({
+      "codec": "json",
+      "preprocessors": ["separate"],
+      "config": {
+        "url": url.clone(),
+        "max_in_flight": 1,
+        "in_flight_timeout_ms": 2000
+      }
+    });
+
+    let harness =
+        ConnectorHarness::new(function_name!(), &ws::server::Builder::default(), &defn).await?;
+    let out_pipeline = harness
+        .out()
+        .expect("No pipeline connected to 'out' port of ws_server connector");
+    let err_pipeline = harness
+        .err()
+        .expect("No pipeline connected to 'err' port of ws_server connector");
+
+    harness.start().await?;
+    harness.wait_for_connected().await?;
+
+    let start = Instant::now();
+    let timeout = Duration::from_secs(30);
+    let mut c1 = loop {
+        match TestClient::new(url.as_str()) {
+            Err(e) => {
+                if start.elapsed() > timeout {
+                    return Err(format!(
+                        "Timeout waiting for the ws server to start listening: {e}."
+                    )
+                    .into());
+                }
+                async_std::task::sleep(Duration::from_secs(1)).await;
+            }
+            Ok(client) => {
+                break client;
+            }
+        }
+    };
+
+    // a partial line turns into no events, it doesn't keep the next message from being read
+    c1.send("{\"snot\":")?;
+    c1.send("\"badger\"}\n")?;
+    let event = out_pipeline
+        .get_event()
+        .timeout(Duration::from_secs(1))
+        .await??;
+    assert_eq!(&literal!({"snot": "badger"}), event.data.suffix().value());
+    harness.send_contraflow(CbAction::Ack, event.id).await?;
+
+    // an undecodable message turns into an error event, which nobody acks or fails here
+    c1.send("snot\n")?;
+    let error = err_pipeline.get_event().await?;
+    assert!(error.data.suffix().value().get_str("error").is_some());
+    c1.send("\"badger\"\n")?;
+    out_pipeline
+        .expect_no_event_for(Duration::from_millis(500))
+        .await?;
+    // until it expires
+    let event = out_pipeline.get_event().await?;
+    assert_eq!(&Value::from("badger"), event.data.suffix().value());
+
+    //cleanup
+    harness.stop().await?;
+    c1.close().await?;
+    Ok(())
+}
+
 #[async_std::test]
 async fn ws_client_binary_routing() -> Result<()> {
     let _ = env_logger::try_init();
@@ -14,6 +14,14 @@
 
 use std::collections::HashMap;
 use std::net::SocketAddr;
+use std::num::NonZeroUsize;
+use std::sync::atomic::Ordering;
+
+/// Access logging facilities
+pub(crate) mod access_log;
+
+/// Line length limits for stream readers
+pub(crate) mod frame_limit;
 
 /// Metrics facilities
 pub(crate) mod metrics;
@@ -33,6 +41,21 @@ pub(crate) mod mime;
 /// Protocol Buffer utilities
 pub(crate) mod pb;
 
+/// Proxy tunneling facilities
+pub(crate) mod proxy;
+
+/// Connect diagnostics facilities
+pub(crate) mod diagnostics;
+
+/// DNS caching facilities
+pub(crate) mod dns;
+
+/// Capacity of the internal channels of a connector,
+/// the configured `queue_size` if set, the global `QSIZE` otherwise
+pub(crate) fn queue_size(queue_size: Option<NonZeroUsize>) -> usize {
+    queue_size.map_or_else(|| crate::QSIZE.load(Ordering::Relaxed), NonZeroUsize::get)
+}
+
 #[derive(Clone, Debug, Hash, PartialEq, Eq)]
 pub(crate) struct ConnectionMeta {
     pub(crate) host: String,
@@ -57,7 +80,7 @@ pub(crate) mod url {
 
     lazy_static! {
         // ALLOW: we know this regex is valid
-        static ref URL_SCHEME_REGEX: Regex = Regex::new("^[A-Za-z-]+://").expect("Invalid Regex");
+        static ref URL_SCHEME_REGEX: Regex = Regex::new("^[A-Za-z][A-Za-z0-9+.-]*://").expect("Invalid Regex");
     }
 
     pub(crate) trait Defaults {
@@ -204,6 +227,7 @@ pub(crate) mod url {
 
         #[test_case("127.0.0.1", "http://127.0.0.1/"; "ensure scheme without port")]
         #[test_case("localhost:42", "http://localhost:42/"; "ensure scheme")]
+        #[test_case("socks5://localhost:1080", "socks5://localhost:1080"; "scheme with digits")]
         #[test_case("scheme://host:42/path?query=1&query=2#fragment", "scheme://host:42/path?query=1&query=2#fragment"; "all the url features")]
         fn serialize_deserialize(input: &str, expected: &str) -> Result<()> {
             let mut input = format!("\"{input}\""); // prepare for json compat
@@ -0,0 +1,227 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::ConnectionMeta;
+use crate::connectors::prelude::*;
+use std::sync::atomic::AtomicU64;
+use std::sync::Arc;
+use tremor_common::time::nanotime;
+
+/// Byte counters and timing of a single connection.
+///
+/// It is shared between the reader and the writer of a connection
+/// and emits a single access log entry once both of them are dropped, i.e. the connection is closed.
+#[derive(Debug)]
+pub(crate) struct AccessLog {
+    ctx: String,
+    peer: ConnectionMeta,
+    tls: bool,
+    start: u64,
+    bytes_read: AtomicU64,
+    bytes_written: AtomicU64,
+}
+
+impl AccessLog {
+    pub(crate) fn new<C: Context>(ctx: &C, peer: ConnectionMeta, tls: bool) -> Arc<Self> {
+        Arc::new(Self {
+            ctx: ctx.to_string(),
+            peer,
+            tls,
+            start: nanotime(),
+            bytes_read: AtomicU64::new(0),
+            bytes_written: AtomicU64::new(0),
+        })
+    }
+
+    pub(crate) fn bytes_read(&self) -> u64 {
+        self.bytes_read.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn bytes_written(&self) -> u64 {
+        self.bytes_written.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for AccessLog {
+    fn drop(&mut self) {
+        let duration_ms = nanotime().saturating_sub(self.start) / 1_000_000;
+        info!(
+            "{} access peer={}:{} tls={} bytes_read={} bytes_written={} duration_ms={}",
+            self.ctx,
+            self.peer.host,
+            self.peer.port,
+            self.tls,
+            self.bytes_read(),
+            self.bytes_written(),
+            duration_ms
+        );
+    }
+}
+
+/// Counts the bytes of all data read by the wrapped `StreamReader`, if access logging is enabled
+pub(crate) struct AccessLogReader<R>
+where
+    R: StreamReader,
+{
+    inner: R,
+    access_log: Option<Arc<AccessLog>>,
+}
+
+impl<R> AccessLogReader<R>
+where
+    R: StreamReader,
+{
+    pub(crate) fn new(inner: R, access_log: Option<Arc<AccessLog>>) -> Self {
+        Self { inner, access_log }
+    }
+}
+
+#[async_trait::async_trait]
+impl<R> StreamReader for AccessLogReader<R>
+where
+    R: StreamReader,
+{
+    async fn read(&mut self, stream: u64) -> Result<SourceReply> {
+        let reply = self.inner.read(stream).await?;
+        if let (Some(access_log), SourceReply::Data { data, .. }) = (&self.access_log, &reply) {
+            access_log
+                .bytes_read
+                .fetch_add(data.len() as u64, Ordering::Relaxed);
+        }
+        Ok(reply)
+    }
+
+    async fn quiesce(&mut self, stream: u64) -> Option<SourceReply> {
+        self.inner.quiesce(stream).await
+    }
+
+    async fn on_done(&mut self, stream: u64) -> StreamDone {
+        self.inner.on_done(stream).await
+    }
+}
+
+/// Counts the bytes successfully written by the wrapped `StreamWriter`, if access logging is enabled
+pub(crate) struct AccessLogWriter<W>
+where
+    W: StreamWriter,
+{
+    inner: W,
+    access_log: Option<Arc<AccessLog>>,
+}
+
+impl<W> AccessLogWriter<W>
+where
+    W: StreamWriter,
+{
+    pub(crate) fn new(inner: W, access_log: Option<Arc<AccessLog>>) -> Self {
+        Self { inner, access_log }
+    }
+}
+
+#[async_trait::async_trait]
+impl<W> StreamWriter for AccessLogWriter<W>
+where
+    W: StreamWriter,
+{
+    async fn write(&mut self, data: Vec<Vec<u8>>, meta: Option<SinkMeta>) -> Result<()> {
+        let len: usize = data.iter().map(Vec::len).sum();
+        self.inner.write(data, meta).await?;
+        if let Some(access_log) = &self.access_log {
+            access_log
+                .bytes_written
+                .fetch_add(len as u64, Ordering::Relaxed);
+        }
+        Ok(())
+    }
+
+    async fn on_done(&mut self, stream: u64) -> Result<StreamDone> {
+        self.inner.on_done(stream).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connectors::unit_tests::FakeContext;
+
+    struct FakeReader {
+        chunks: Vec<Vec<u8>>,
+    }
+
+    #[async_trait::async_trait]
+    impl StreamReader for FakeReader {
+        async fn read(&mut self, stream: u64) -> Result<SourceReply> {
+            Ok(if let Some(data) = self.chunks.pop() {
+                SourceReply::Data {
+                    origin_uri: EventOriginUri::default(),
+                    data,
+                    meta: None,
+                    stream: Some(stream),
+                    port: None,
+                    codec_overwrite: None,
+                }
+            } else {
+                SourceReply::EndStream {
+                    origin_uri: EventOriginUri::default(),
+                    stream,
+                    meta: None,
+                }
+            })
+        }
+
+        async fn quiesce(&mut self, _stream: u64) -> Option<SourceReply> {
+            None
+        }
+    }
+
+    struct FakeWriter {}
+
+    #[async_trait::async_trait]
+    impl StreamWriter for FakeWriter {
+        async fn write(&mut self, _data: Vec<Vec<u8>>, _meta: Option<SinkMeta>) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    #[async_std::test]
+    async fn counts_bytes() -> Result<()> {
+        let (tx, _rx) = async_std::channel::unbounded();
+        let ctx = FakeContext::new(tx);
+        let access_log = AccessLog::new(
+            &ctx,
+            ConnectionMeta {
+                host: "127.0.0.1".to_string(),
+                port: 65535,
+            },
+            false,
+        );
+        let mut reader = AccessLogReader::new(
+            FakeReader {
+                chunks: vec![b"snot".to_vec(), b"badger".to_vec()],
+            },
+            Some(access_log.clone()),
+        );
+        let mut writer = AccessLogWriter::new(FakeWriter {}, Some(access_log.clone()));
+
+        while !matches!(reader.read(1).await?, SourceReply::EndStream { .. }) {}
+        writer
+            .write(vec![b"a".to_vec(), b"bc".to_vec()], None)
+            .await?;
+        writer.write(vec![b"def".to_vec()], None).await?;
+
+        assert_eq!(10, access_log.bytes_read());
+        assert_eq!(6, access_log.bytes_written());
+        Ok(())
+    }
+}
@@ -0,0 +1,177 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Structured reports of failed connection attempts
+
+use crate::errors::{Error, Kind as ErrorKind, Result};
+use async_std::net::{TcpListener, ToSocketAddrs};
+use std::fmt::Display;
+use std::net::SocketAddr;
+use tremor_value::{literal, Value};
+
+/// Step of establishing a connection
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ConnectStep {
+    /// resolving the host name
+    Dns,
+    /// opening (or binding) the socket
+    Tcp,
+    /// the TLS handshake
+    Tls,
+    /// authenticating with the remote end
+    Auth,
+    /// checking the remote end matches the configuration, e.g. the schema of a table
+    Schema,
+}
+
+impl ConnectStep {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Dns => "dns",
+            Self::Tcp => "tcp",
+            Self::Tls => "tls",
+            Self::Auth => "auth",
+            Self::Schema => "schema",
+        }
+    }
+}
+
+impl Display for ConnectStep {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Which step of connecting to which endpoint failed, and why
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ConnectDiagnostic {
+    pub(crate) endpoint: String,
+    pub(crate) step: ConnectStep,
+    pub(crate) error: String,
+}
+
+impl ConnectDiagnostic {
+    pub(crate) fn new<E: Display + ?Sized>(endpoint: &str, step: ConnectStep, error: &E) -> Self {
+        Self {
+            endpoint: endpoint.to_string(),
+            step,
+            error: error.to_string(),
+        }
+    }
+
+    /// The diagnostic of `error`, if it is a failed connection attempt
+    pub(crate) fn of(error: &Error) -> Option<&Self> {
+        match error.kind() {
+            ErrorKind::ConnectFailed(diagnostic) => Some(diagnostic),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn to_value(&self) -> Value<'static> {
+        literal!({
+            "endpoint": self.endpoint.clone(),
+            "step": self.step.as_str(),
+            "error": self.error.clone(),
+        })
+    }
+}
+
+impl Display for ConnectDiagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Connecting to {} failed at the {} step: {}",
+            self.endpoint, self.step, self.error
+        )
+    }
+}
+
+impl From<ConnectDiagnostic> for Error {
+    fn from(diagnostic: ConnectDiagnostic) -> Self {
+        ErrorKind::ConnectFailed(diagnostic).into()
+    }
+}
+
+/// Attaches the failed step to the error of a connection attempt
+pub(crate) trait ConnectStepExt<T> {
+    fn connect_step(self, endpoint: &str, step: ConnectStep) -> Result<T>;
+}
+
+impl<T, E: Display> ConnectStepExt<T> for std::result::Result<T, E> {
+    fn connect_step(self, endpoint: &str, step: ConnectStep) -> Result<T> {
+        self.map_err(|e| ConnectDiagnostic::new(endpoint, step, &e).into())
+    }
+}
+
+async fn resolve(host: &str, port: u16, endpoint: &str) -> Result<Vec<SocketAddr>> {
+    let addrs: Vec<SocketAddr> = (host, port)
+        .to_socket_addrs()
+        .await
+        .connect_step(endpoint, ConnectStep::Dns)?
+        .collect();
+    if addrs.is_empty() {
+        return Err(
+            ConnectDiagnostic::new(endpoint, ConnectStep::Dns, "no addresses found").into(),
+        );
+    }
+    Ok(addrs)
+}
+
+/// Binds to `host` and `port`, telling resolving the host and binding apart when failing
+pub(crate) async fn tcp_bind(host: &str, port: u16) -> Result<TcpListener> {
+    let endpoint = format!("{host}:{port}");
+    let addrs = resolve(host, port, &endpoint).await?;
+    TcpListener::bind(&addrs[..])
+        .await
+        .connect_step(&endpoint, ConnectStep::Tcp)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connectors::utils::dns::DnsCache;
+    use value_trait::ValueAccess;
+
+    async fn tcp_connect(host: &str, port: u16) -> Result<async_std::net::TcpStream> {
+        DnsCache::new(None).connect(host, port).await
+    }
+
+    #[async_std::test]
+    async fn dns_and_tcp_failures() -> Result<()> {
+        let error = tcp_connect("snot.invalid", 4242)
+            .await
+            .err()
+            .ok_or("expected a dns failure")?;
+        let diagnostic = ConnectDiagnostic::of(&error).ok_or("expected a diagnostic")?;
+        assert_eq!(ConnectStep::Dns, diagnostic.step);
+        assert_eq!("snot.invalid:4242", diagnostic.endpoint);
+
+        // a port nobody listens on anymore
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let port = listener.local_addr()?.port();
+        drop(listener);
+        let error = tcp_connect("127.0.0.1", port)
+            .await
+            .err()
+            .ok_or("expected a tcp failure")?;
+        let diagnostic = ConnectDiagnostic::of(&error).ok_or("expected a diagnostic")?;
+        assert_eq!(ConnectStep::Tcp, diagnostic.step);
+        assert_eq!(
+            Some("tcp"),
+            diagnostic.to_value().get_str("step"),
+            "{diagnostic}"
+        );
+        Ok(())
+    }
+}
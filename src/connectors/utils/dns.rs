@@ -0,0 +1,222 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Caching of the addresses host names resolve to
+
+use crate::connectors::utils::diagnostics::{ConnectDiagnostic, ConnectStep, ConnectStepExt};
+use crate::errors::Result;
+use async_std::net::{TcpStream, ToSocketAddrs};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+/// Resolves host names to addresses
+#[async_trait::async_trait]
+pub(crate) trait Resolver: Send + Sync {
+    async fn resolve(&self, host: &str, port: u16) -> std::io::Result<Vec<SocketAddr>>;
+}
+
+/// Resolves host names with the resolver of the system
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct SystemResolver;
+
+#[async_trait::async_trait]
+impl Resolver for SystemResolver {
+    async fn resolve(&self, host: &str, port: u16) -> std::io::Result<Vec<SocketAddr>> {
+        Ok((host, port).to_socket_addrs().await?.collect())
+    }
+}
+
+/// Addresses resolved for a host and port, and when
+struct Resolved {
+    addrs: Vec<SocketAddr>,
+    at: Instant,
+}
+
+/// Reuses resolved addresses for `ttl`, or until connecting to them fails
+///
+/// Without a `ttl` hosts are resolved again for every connection.
+pub(crate) struct DnsCache<R = SystemResolver> {
+    resolver: R,
+    ttl: Option<Duration>,
+    entries: HashMap<(String, u16), Resolved>,
+}
+
+impl DnsCache {
+    pub(crate) fn new(ttl: Option<Duration>) -> Self {
+        Self::with_resolver(SystemResolver, ttl)
+    }
+}
+
+impl<R: Resolver> DnsCache<R> {
+    pub(crate) fn with_resolver(resolver: R, ttl: Option<Duration>) -> Self {
+        Self {
+            resolver,
+            ttl,
+            entries: HashMap::new(),
+        }
+    }
+
+    /// The addresses of `host` and `port`, from the cache if they were resolved within the `ttl`
+    pub(crate) async fn resolve(&mut self, host: &str, port: u16) -> Result<Vec<SocketAddr>> {
+        self.resolve_at(host, port, Instant::now()).await
+    }
+
+    async fn resolve_at(&mut self, host: &str, port: u16, now: Instant) -> Result<Vec<SocketAddr>> {
+        let key = (host.to_string(), port);
+        if let Some((ttl, resolved)) = self.ttl.zip(self.entries.get(&key)) {
+            if now.saturating_duration_since(resolved.at) < ttl {
+                return Ok(resolved.addrs.clone());
+            }
+        }
+        let endpoint = format!("{host}:{port}");
+        let addrs = self
+            .resolver
+            .resolve(host, port)
+            .await
+            .connect_step(&endpoint, ConnectStep::Dns)?;
+        if addrs.is_empty() {
+            self.entries.remove(&key);
+            return Err(
+                ConnectDiagnostic::new(&endpoint, ConnectStep::Dns, "no addresses found").into(),
+            );
+        }
+        if self.ttl.is_some() {
+            self.entries.insert(
+                key,
+                Resolved {
+                    addrs: addrs.clone(),
+                    at: now,
+                },
+            );
+        }
+        Ok(addrs)
+    }
+
+    /// Forgets the addresses of `host` and `port`, so they are resolved again
+    pub(crate) fn invalidate(&mut self, host: &str, port: u16) {
+        self.entries.remove(&(host.to_string(), port));
+    }
+
+    /// Connects to `host` and `port`, resolving them again next time if connecting fails
+    pub(crate) async fn connect(&mut self, host: &str, port: u16) -> Result<TcpStream> {
+        let addrs = self.resolve(host, port).await?;
+        let stream = TcpStream::connect(&addrs[..])
+            .await
+            .connect_step(&format!("{host}:{port}"), ConnectStep::Tcp);
+        if stream.is_err() {
+            self.invalidate(host, port);
+        }
+        stream
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    /// Resolves every host to the same address, counting the lookups
+    #[derive(Default, Clone)]
+    struct StubResolver {
+        lookups: Arc<AtomicUsize>,
+    }
+
+    impl StubResolver {
+        fn lookups(&self) -> usize {
+            self.lookups.load(Ordering::Acquire)
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl Resolver for StubResolver {
+        async fn resolve(&self, _host: &str, port: u16) -> std::io::Result<Vec<SocketAddr>> {
+            self.lookups.fetch_add(1, Ordering::AcqRel);
+            Ok(vec![SocketAddr::from(([127, 0, 0, 1], port))])
+        }
+    }
+
+    #[async_std::test]
+    async fn cache_hit_within_ttl() -> Result<()> {
+        let resolver = StubResolver::default();
+        let ttl = Duration::from_secs(60);
+        let mut cache = DnsCache::with_resolver(resolver.clone(), Some(ttl));
+        let start = Instant::now();
+
+        let addrs = cache.resolve_at("snot", 4242, start).await?;
+        assert_eq!(vec![SocketAddr::from(([127, 0, 0, 1], 4242))], addrs);
+        assert_eq!(1, resolver.lookups());
+        let cached = cache
+            .resolve_at("snot", 4242, start + ttl - Duration::from_millis(1))
+            .await?;
+        assert_eq!(addrs, cached);
+        assert_eq!(1, resolver.lookups());
+
+        // other ports are resolved on their own
+        cache.resolve_at("snot", 4243, start).await?;
+        assert_eq!(2, resolver.lookups());
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn resolves_again_after_expiry() -> Result<()> {
+        let resolver = StubResolver::default();
+        let ttl = Duration::from_secs(60);
+        let mut cache = DnsCache::with_resolver(resolver.clone(), Some(ttl));
+        let start = Instant::now();
+
+        cache.resolve_at("snot", 4242, start).await?;
+        cache.resolve_at("snot", 4242, start + ttl).await?;
+        assert_eq!(2, resolver.lookups());
+        // the new addresses are cached from the time they were resolved
+        cache
+            .resolve_at("snot", 4242, start + ttl + Duration::from_secs(1))
+            .await?;
+        assert_eq!(2, resolver.lookups());
+
+        cache.invalidate("snot", 4242);
+        cache
+            .resolve_at("snot", 4242, start + ttl + Duration::from_secs(1))
+            .await?;
+        assert_eq!(3, resolver.lookups());
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn no_ttl_resolves_every_time() -> Result<()> {
+        let resolver = StubResolver::default();
+        let mut cache = DnsCache::with_resolver(resolver.clone(), None);
+        let start = Instant::now();
+        cache.resolve_at("snot", 4242, start).await?;
+        cache.resolve_at("snot", 4242, start).await?;
+        assert_eq!(2, resolver.lookups());
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn failed_connect_invalidates() -> Result<()> {
+        // a port nobody listens on anymore
+        let listener = async_std::net::TcpListener::bind("127.0.0.1:0").await?;
+        let port = listener.local_addr()?.port();
+        drop(listener);
+
+        let resolver = StubResolver::default();
+        let mut cache = DnsCache::with_resolver(resolver.clone(), Some(Duration::from_secs(60)));
+        assert!(cache.connect("snot", port).await.is_err());
+        assert!(cache.connect("snot", port).await.is_err());
+        assert_eq!(2, resolver.lookups());
+        Ok(())
+    }
+}
@@ -0,0 +1,411 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::connectors::prelude::*;
+use crate::preprocessor::separate::DEFAULT_SEPARATOR;
+use memchr::memchr;
+use std::collections::VecDeque;
+
+/// What to do with a line exceeding `max_frame_len`
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum FrameOverflow {
+    /// close the connection, dropping the oversized line
+    Disconnect,
+    /// emit the oversized line as bytes to the `err` port and continue with the next line
+    Error,
+}
+
+impl Default for FrameOverflow {
+    fn default() -> Self {
+        Self::Disconnect
+    }
+}
+
+/// The separator of the first `separate` preprocessor of the connector, lines are separated by
+/// newlines if there is none
+pub(crate) fn frame_separator(connector_config: &ConnectorConfig) -> u8 {
+    connector_config
+        .preprocessors
+        .iter()
+        .flatten()
+        .find(|preprocessor| preprocessor.name == "separate")
+        .and_then(|preprocessor| preprocessor.config.get_str("separator"))
+        .and_then(|separator| match separator.as_bytes() {
+            [separator] => Some(*separator),
+            // rejected by the preprocessor
+            _ => None,
+        })
+        .unwrap_or(DEFAULT_SEPARATOR)
+}
+
+/// Limits the length of the lines read by the wrapped `StreamReader`, if `max_frame_len` is set.
+///
+/// Lines end with `separator`, which should match the `separate` preprocessor of the connector.
+/// Only complete lines are passed on, the line in progress is held back until its separator
+/// arrives, so it can be dropped as a whole once it grows beyond the limit.
+pub(crate) struct FrameLimitReader<R>
+where
+    R: StreamReader,
+{
+    inner: R,
+    ctx: String,
+    max_frame_len: Option<usize>,
+    separator: u8,
+    on_overflow: FrameOverflow,
+    /// the line in progress
+    frame: Vec<u8>,
+    /// the rest of an oversized line is discarded up to the next separator
+    discarding: bool,
+    /// replies to return with the next reads
+    pending: VecDeque<SourceReply>,
+    /// data read after an oversized line, to be split before reading again
+    unprocessed: Option<SourceReply>,
+}
+
+impl<R> FrameLimitReader<R>
+where
+    R: StreamReader,
+{
+    pub(crate) fn new<C: Context>(
+        inner: R,
+        ctx: &C,
+        max_frame_len: Option<usize>,
+        separator: u8,
+        on_overflow: FrameOverflow,
+    ) -> Self {
+        Self {
+            inner,
+            ctx: ctx.to_string(),
+            max_frame_len,
+            separator,
+            on_overflow,
+            frame: Vec::new(),
+            discarding: false,
+            pending: VecDeque::new(),
+            unprocessed: None,
+        }
+    }
+
+    /// Splits the complete lines within the limit off `data`, stopping after the first oversized data.
+    ///
+    /// Returns the lines, the oversized data and the number of bytes of `data` consumed.
+    fn split(&mut self, max_frame_len: usize, data: &[u8]) -> (Vec<u8>, Vec<u8>, usize) {
+        let mut complete = Vec::new();
+        let mut oversized = Vec::new();
+        let mut consumed = 0;
+        while consumed < data.len() && oversized.is_empty() {
+            let rest = &data[consumed..];
+            let (line, terminated) = match memchr(self.separator, rest) {
+                Some(idx) => (&rest[..=idx], true),
+                None => (rest, false),
+            };
+            consumed += line.len();
+            if self.discarding {
+                oversized.extend_from_slice(line);
+                self.discarding = !terminated;
+            } else if self.frame.len() + line.len() - usize::from(terminated) > max_frame_len {
+                oversized.append(&mut self.frame);
+                oversized.extend_from_slice(line);
+                self.discarding = !terminated;
+            } else if terminated {
+                complete.append(&mut self.frame);
+                complete.extend_from_slice(line);
+            } else {
+                self.frame.extend_from_slice(line);
+            }
+        }
+        (complete, oversized, consumed)
+    }
+}
+
+#[async_trait::async_trait]
+impl<R> StreamReader for FrameLimitReader<R>
+where
+    R: StreamReader,
+{
+    async fn read(&mut self, stream: u64) -> Result<SourceReply> {
+        if let Some(reply) = self.pending.pop_front() {
+            return Ok(reply);
+        }
+        let max_frame_len = if let Some(max_frame_len) = self.max_frame_len {
+            max_frame_len
+        } else {
+            return self.inner.read(stream).await;
+        };
+        loop {
+            let reply = if let Some(reply) = self.unprocessed.take() {
+                reply
+            } else {
+                self.inner.read(stream).await?
+            };
+            match reply {
+                SourceReply::Data {
+                    origin_uri,
+                    stream: data_stream,
+                    meta,
+                    data,
+                    port,
+                    codec_overwrite,
+                } => {
+                    let (complete, oversized, consumed) = self.split(max_frame_len, &data);
+                    if consumed < data.len() {
+                        self.unprocessed = Some(SourceReply::Data {
+                            origin_uri: origin_uri.clone(),
+                            stream: data_stream,
+                            meta: meta.clone(),
+                            data: data[consumed..].to_vec(),
+                            port: port.clone(),
+                            codec_overwrite: codec_overwrite.clone(),
+                        });
+                    }
+                    if !oversized.is_empty() {
+                        if self.on_overflow == FrameOverflow::Disconnect {
+                            warn!(
+                                "{} Closing stream {stream} after a line exceeding {max_frame_len} bytes",
+                                self.ctx
+                            );
+                            self.unprocessed = None;
+                            self.pending.push_back(SourceReply::EndStream {
+                                origin_uri: origin_uri.clone(),
+                                stream,
+                                meta: meta.clone(),
+                            });
+                        } else {
+                            self.pending.push_back(SourceReply::Structured {
+                                origin_uri: origin_uri.clone(),
+                                payload: (
+                                    Value::Bytes(oversized.into()),
+                                    meta.clone().unwrap_or_else(Value::object),
+                                )
+                                    .into(),
+                                stream,
+                                port: Some(ERR),
+                            });
+                        }
+                    }
+                    if !complete.is_empty() {
+                        return Ok(SourceReply::Data {
+                            origin_uri,
+                            stream: data_stream,
+                            meta,
+                            data: complete,
+                            port,
+                            codec_overwrite,
+                        });
+                    }
+                    if let Some(reply) = self.pending.pop_front() {
+                        return Ok(reply);
+                    }
+                    // wait for the rest of the line
+                }
+                SourceReply::EndStream {
+                    origin_uri,
+                    stream,
+                    meta,
+                } if !self.frame.is_empty() => {
+                    // pass on the last line, even without a newline
+                    let data = std::mem::take(&mut self.frame);
+                    self.pending.push_back(SourceReply::EndStream {
+                        origin_uri: origin_uri.clone(),
+                        stream,
+                        meta: meta.clone(),
+                    });
+                    return Ok(SourceReply::Data {
+                        origin_uri,
+                        stream: Some(stream),
+                        meta,
+                        data,
+                        port: None,
+                        codec_overwrite: None,
+                    });
+                }
+                reply => return Ok(reply),
+            }
+        }
+    }
+
+    async fn quiesce(&mut self, stream: u64) -> Option<SourceReply> {
+        self.inner.quiesce(stream).await
+    }
+
+    async fn on_done(&mut self, stream: u64) -> StreamDone {
+        self.inner.on_done(stream).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{NameWithConfig, Reconnect};
+    use crate::connectors::unit_tests::FakeContext;
+
+    struct FakeReader {
+        chunks: Vec<Vec<u8>>,
+    }
+
+    #[async_trait::async_trait]
+    impl StreamReader for FakeReader {
+        async fn read(&mut self, stream: u64) -> Result<SourceReply> {
+            Ok(if self.chunks.is_empty() {
+                SourceReply::EndStream {
+                    origin_uri: EventOriginUri::default(),
+                    stream,
+                    meta: None,
+                }
+            } else {
+                SourceReply::Data {
+                    origin_uri: EventOriginUri::default(),
+                    data: self.chunks.remove(0),
+                    meta: None,
+                    stream: Some(stream),
+                    port: None,
+                    codec_overwrite: None,
+                }
+            })
+        }
+
+        async fn quiesce(&mut self, _stream: u64) -> Option<SourceReply> {
+            None
+        }
+    }
+
+    fn reader(chunks: &[&str], on_overflow: FrameOverflow) -> FrameLimitReader<FakeReader> {
+        reader_with_separator(chunks, b'\n', on_overflow)
+    }
+
+    fn reader_with_separator(
+        chunks: &[&str],
+        separator: u8,
+        on_overflow: FrameOverflow,
+    ) -> FrameLimitReader<FakeReader> {
+        let (tx, _rx) = async_std::channel::unbounded();
+        let ctx = FakeContext::new(tx);
+        FrameLimitReader::new(
+            FakeReader {
+                chunks: chunks.iter().map(|c| c.as_bytes().to_vec()).collect(),
+            },
+            &ctx,
+            Some(5),
+            separator,
+            on_overflow,
+        )
+    }
+
+    #[test]
+    fn separator_of_the_preprocessor() {
+        let mut connector_config = ConnectorConfig {
+            connector_type: "tcp_server".into(),
+            codec: None,
+            config: None,
+            preprocessors: None,
+            postprocessors: None,
+            reconnect: Reconnect::None,
+            metrics_interval_s: None,
+            max_in_flight: None,
+            in_flight_timeout_ms: None,
+            instance_host: None,
+        };
+        assert_eq!(b'\n', frame_separator(&connector_config));
+        connector_config.preprocessors = Some(vec![
+            NameWithConfig {
+                name: "base64".to_string(),
+                config: None,
+            },
+            NameWithConfig {
+                name: "separate".to_string(),
+                config: Some(literal!({"separator": "|"})),
+            },
+        ]);
+        assert_eq!(b'|', frame_separator(&connector_config));
+    }
+
+    #[async_std::test]
+    async fn custom_separator() -> Result<()> {
+        let mut reader =
+            reader_with_separator(&["snot|bad", "ger|sn\not|"], b'|', FrameOverflow::Error);
+        assert!(matches!(
+            reader.read(1).await?,
+            SourceReply::Data { data, .. } if data == b"snot|"
+        ));
+        assert!(matches!(
+            reader.read(1).await?,
+            SourceReply::Structured {
+                port: Some(ERR),
+                ..
+            }
+        ));
+        // newlines are part of the line
+        assert!(matches!(
+            reader.read(1).await?,
+            SourceReply::Data { data, .. } if data == b"sn\not|"
+        ));
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn holds_back_incomplete_lines() -> Result<()> {
+        let mut reader = reader(&["sn", "ot\nbad", "ger\nend"], FrameOverflow::Disconnect);
+        let mut lines = Vec::new();
+        loop {
+            match reader.read(1).await? {
+                SourceReply::Data { data, .. } => lines.push(String::from_utf8(data)?),
+                SourceReply::EndStream { .. } => break,
+                _ => return Err("unexpected reply".into()),
+            }
+        }
+        assert_eq!(vec!["snot\n", "badger\n", "end"], lines);
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn disconnects() -> Result<()> {
+        let mut reader = reader(&["snot\nbadg", "er\nsnot\n"], FrameOverflow::Disconnect);
+        assert!(matches!(
+            reader.read(1).await?,
+            SourceReply::Data { data, .. } if data == b"snot\n"
+        ));
+        // the line after the oversized one is dropped together with the connection
+        assert!(matches!(
+            reader.read(1).await?,
+            SourceReply::EndStream { .. }
+        ));
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn errors() -> Result<()> {
+        let mut reader = reader(&["snot\nbadg", "er\nsnot\n"], FrameOverflow::Error);
+        assert!(matches!(
+            reader.read(1).await?,
+            SourceReply::Data { data, .. } if data == b"snot\n"
+        ));
+        match reader.read(1).await? {
+            SourceReply::Structured { payload, port, .. } => {
+                assert_eq!(Some(ERR), port);
+                assert_eq!(Some(&b"badger\n"[..]), payload.suffix().value().as_bytes());
+            }
+            _ => return Err("expected the oversized line on the err port".into()),
+        }
+        match reader.read(1).await? {
+            SourceReply::Data { data, .. } => assert_eq!(b"snot\n", data.as_slice()),
+            _ => return Err("expected the next line".into()),
+        }
+        assert!(matches!(
+            reader.read(1).await?,
+            SourceReply::EndStream { .. }
+        ));
+        Ok(())
+    }
+}
@@ -189,6 +189,8 @@ pub(crate) struct ReconnectRuntime {
     notifier: ConnectionLostNotifier,
     retry_task: Option<JoinHandle<()>>,
     alias: Alias,
+    /// set once the strategy gave up on reconnecting, only an explicit restart resets it
+    failed: bool,
 }
 
 /// Notifier that connector implementations
@@ -255,9 +257,23 @@ impl ReconnectRuntime {
             notifier,
             retry_task: None,
             alias,
+            failed: false,
         }
     }
 
+    /// Returns true if we gave up reconnecting after too many failed attempts
+    pub(crate) fn is_failed(&self) -> bool {
+        self.failed
+    }
+
+    /// Forget about all failed attempts since the last successful one,
+    /// so the configured reconnect strategy starts over.
+    pub(crate) fn restart(&mut self) {
+        self.attempt.since_last_success = 0;
+        self.interval_ms = None;
+        self.failed = false;
+    }
+
     /// Use the given connector to attempt to establish a connection.
     ///
     /// Will issue a retry after the configured interval (based upon the number of the attempt)
@@ -323,8 +339,15 @@ impl ReconnectRuntime {
     }
 
     pub(crate) async fn enqueue_retry(&mut self, _ctx: &ConnectorContext) -> bool {
-        if let ShouldRetry::No(msg) = self.strategy.should_reconnect(&self.attempt) {
-            warn!("[Connector::{}] Not reconnecting: {}", &self.alias, msg);
+        if self.failed {
+            // we already reported giving up, no need to do it again for every lost connection
+            false
+        } else if let ShouldRetry::No(msg) = self.strategy.should_reconnect(&self.attempt) {
+            error!(
+                "[Connector::{}] Not reconnecting after {}: {}. The connector needs to be restarted.",
+                &self.alias, self.attempt, msg
+            );
+            self.failed = true;
             false
         } else {
             // compute next interval
@@ -374,6 +397,7 @@ impl ReconnectRuntime {
         self.attempt.on_success();
 
         self.interval_ms = None;
+        self.failed = false;
     }
 }
 
@@ -534,4 +558,66 @@ mod tests {
 
         Ok(())
     }
+
+    #[async_std::test]
+    async fn failed_after_max_retries() -> Result<()> {
+        use async_std::prelude::FutureExt;
+        let (tx, rx) = async_std::channel::unbounded();
+        let notifier = ConnectionLostNotifier::new(tx.clone());
+        let alias = Alias::new("flow", "test");
+        let addr = Addr {
+            alias: alias.clone(),
+            source: None,
+            sink: None,
+            sender: tx.clone(),
+        };
+        let config = Reconnect::Retry {
+            interval_ms: 10,
+            growth_rate: 1.0,
+            max_retries: Some(2),
+            randomized: false,
+        };
+        let mut runtime = ReconnectRuntime::inner(addr, alias.clone(), notifier, &config);
+        let mut connector = FakeConnector { answer: None };
+        let qb = QuiescenceBeacon::default();
+        let ctx = ConnectorContext {
+            alias,
+            connector_type: "fake".into(),
+            quiescence_beacon: qb,
+            notifier: runtime.notifier(),
+        };
+        // 1st failing attempt
+        assert_eq!(
+            (Connectivity::Disconnected, true),
+            runtime.attempt(&mut connector, &ctx).await?
+        );
+        assert!(!runtime.is_failed());
+        assert!(matches!(
+            rx.recv().timeout(Duration::from_secs(5)).await??,
+            Msg::Reconnect
+        ));
+
+        // the 2nd failing attempt is the last one
+        runtime.await_retry().await;
+        assert_eq!(
+            (Connectivity::Disconnected, false),
+            runtime.attempt(&mut connector, &ctx).await?
+        );
+        assert!(runtime.is_failed());
+
+        // once failed, we stay failed
+        assert!(!runtime.enqueue_retry(&ctx).await);
+        assert!(runtime.is_failed());
+        assert!(rx.recv().timeout(Duration::from_millis(100)).await.is_err());
+
+        // until we are restarted
+        runtime.restart();
+        assert!(!runtime.is_failed());
+        connector.answer = Some(true);
+        assert_eq!(
+            (Connectivity::Connected, true),
+            runtime.attempt(&mut connector, &ctx).await?
+        );
+        Ok(())
+    }
 }
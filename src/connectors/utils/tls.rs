@@ -19,9 +19,14 @@ use std::path::{Path, PathBuf};
 use crate::errors::{Error, Kind as ErrorKind, Result};
 use async_tls::TlsConnector;
 use rustls::internal::pemfile::{certs, pkcs8_private_keys, rsa_private_keys};
-use rustls::{Certificate, ClientConfig, NoClientAuth, PrivateKey, RootCertStore, ServerConfig};
+use rustls::sign::CertifiedKey;
+use rustls::{
+    Certificate, ClientConfig, ClientHello, NoClientAuth, PrivateKey, ResolvesServerCert,
+    RootCertStore, ServerConfig,
+};
 use rustls_native_certs::load_native_certs;
 use std::io::{BufReader, Cursor};
+use std::sync::{Arc, Mutex};
 
 lazy_static! {
     static ref SYSTEM_ROOT_CERTS: RootCertStore = {
@@ -47,6 +52,7 @@ pub(crate) struct TLSServerConfig {
 #[serde(deny_unknown_fields)]
 pub(crate) struct TLSClientConfig {
     /// Path to the pem-encoded certificate file of the CA to use for verifying the servers certificate
+    #[serde(alias = "ca")]
     pub(crate) cafile: Option<PathBuf>,
     /// The DNS domain used to verify the server's certificate. If not provided the domain from the connection URL will be used.
     pub(crate) domain: Option<String>,
@@ -130,6 +136,121 @@ pub(crate) fn load_server_config(config: &TLSServerConfig) -> Result<ServerConfi
     Ok(server_config)
 }
 
+/// Certificate resolver recording the SNI server name the client presented during the handshake
+struct SniRecorder {
+    inner: Arc<dyn ResolvesServerCert>,
+    sni: Arc<Mutex<Option<String>>>,
+}
+
+impl ResolvesServerCert for SniRecorder {
+    fn resolve(&self, client_hello: ClientHello) -> Option<CertifiedKey> {
+        if let Some(name) = client_hello.server_name() {
+            if let Ok(mut sni) = self.sni.lock() {
+                *sni = Some(<&str>::from(name).to_string());
+            }
+        }
+        self.inner.resolve(client_hello)
+    }
+}
+
+/// A copy of `config` for accepting a single connection, together with the slot
+/// the SNI server name sent by the client is stored in once the handshake is done
+pub(crate) fn record_sni(config: &ServerConfig) -> (ServerConfig, Arc<Mutex<Option<String>>>) {
+    let sni = Arc::new(Mutex::new(None));
+    let mut config = config.clone();
+    config.cert_resolver = Arc::new(SniRecorder {
+        inner: config.cert_resolver.clone(),
+        sni: sni.clone(),
+    });
+    (config, sni)
+}
+
+/// maximum length of a TLS record, including its header
+pub(crate) const MAX_TLS_RECORD_LEN: usize = 5 + (1 << 14) + 2048;
+
+/// SNI server name found in the first bytes of a connection, without terminating TLS
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum PeekedSni {
+    /// the server name sent by the client
+    Found(String),
+    /// no server name in the `ClientHello`, or not a TLS connection at all
+    Missing,
+    /// the `ClientHello` isn't complete yet
+    Incomplete,
+}
+
+/// Reads length prefixed fields of TLS handshake messages
+struct HandshakeReader<'data>(&'data [u8]);
+
+impl<'data> HandshakeReader<'data> {
+    fn take(&mut self, len: usize) -> Option<&'data [u8]> {
+        if self.0.len() < len {
+            return None;
+        }
+        let (head, tail) = self.0.split_at(len);
+        self.0 = tail;
+        Some(head)
+    }
+
+    fn uint(&mut self, bytes: usize) -> Option<usize> {
+        self.take(bytes)
+            .map(|b| b.iter().fold(0, |n, b| (n << 8) | usize::from(*b)))
+    }
+
+    fn vec(&mut self, len_bytes: usize) -> Option<HandshakeReader<'data>> {
+        let len = self.uint(len_bytes)?;
+        self.take(len).map(HandshakeReader)
+    }
+}
+
+/// Looks for the SNI server name in the `ClientHello` at the start of `data`,
+/// which is expected to be sent in a single TLS record
+pub(crate) fn peek_sni(data: &[u8]) -> PeekedSni {
+    const HANDSHAKE: usize = 0x16;
+    let mut record = HandshakeReader(data);
+    match record.uint(1) {
+        None => return PeekedSni::Incomplete,
+        Some(HANDSHAKE) => (),
+        Some(_) => return PeekedSni::Missing,
+    }
+    // skip the protocol version
+    if let Some(fragment) = record.take(2).and_then(|_| record.vec(2)) {
+        client_hello_sni(fragment).map_or(PeekedSni::Missing, PeekedSni::Found)
+    } else {
+        PeekedSni::Incomplete
+    }
+}
+
+fn client_hello_sni(mut handshake: HandshakeReader) -> Option<String> {
+    const CLIENT_HELLO: usize = 0x01;
+    const SERVER_NAME: usize = 0x00;
+    const HOST_NAME: usize = 0x00;
+    if handshake.uint(1)? != CLIENT_HELLO {
+        return None;
+    }
+    let mut hello = handshake.vec(3)?;
+    // version and random
+    hello.take(2 + 32)?;
+    // session id, cipher suites and compression methods
+    hello.vec(1)?;
+    hello.vec(2)?;
+    hello.vec(1)?;
+    let mut extensions = hello.vec(2)?;
+    while let Some(extension) = extensions.uint(2) {
+        let mut data = extensions.vec(2)?;
+        if extension == SERVER_NAME {
+            let mut names = data.vec(2)?;
+            while let Some(name_type) = names.uint(1) {
+                let name = names.vec(2)?;
+                if name_type == HOST_NAME {
+                    return String::from_utf8(name.0.to_vec()).ok();
+                }
+            }
+        }
+    }
+    None
+}
+
 /// if we have a cafile configured, we only load it, and no other ca certificates
 /// if there is no cafile configured, we load the default webpki-roots from Mozilla
 pub(crate) async fn tls_client_connector(config: &TLSClientConfig) -> Result<TlsConnector> {
@@ -153,10 +274,19 @@ pub(crate) async fn tls_client_config(tremor_config: &TLSClientConfig) -> Result
         tls_config.root_store = SYSTEM_ROOT_CERTS.clone();
     }
     // load client certificate stuff
-    if let (Some(cert), Some(key)) = (tremor_config.cert.as_ref(), tremor_config.key.as_ref()) {
-        let cert = load_certs(cert)?;
-        let key = load_keys(key)?;
-        tls_config.set_single_client_cert(cert, key)?;
+    match (tremor_config.cert.as_ref(), tremor_config.key.as_ref()) {
+        (Some(cert), Some(key)) => {
+            let cert = load_certs(cert)?;
+            let key = load_keys(key)?;
+            tls_config.set_single_client_cert(cert, key)?;
+        }
+        (None, None) => (),
+        _ => {
+            return Err(ErrorKind::TLSError(
+                "Both `cert` and `key` need to be provided for a client certificate".to_string(),
+            )
+            .into());
+        }
     }
     Ok(tls_config)
 }
@@ -220,4 +350,34 @@ mod tests {
         assert_eq!(true, client_config.client_auth_cert_resolver.has_certs());
         Ok(())
     }
+
+    #[async_std::test]
+    async fn client_config_incomplete_cert() -> Result<()> {
+        setup_for_tls();
+
+        let tls_config = TLSClientConfig {
+            cert: Some(Path::new("./tests/localhost.cert").to_path_buf()),
+            ..TLSClientConfig::default()
+        };
+        let e = tls_client_config(&tls_config)
+            .await
+            .err()
+            .map(|e| e.to_string());
+        assert!(e.unwrap_or_default().contains("Both `cert` and `key`"));
+
+        let tls_config = TLSClientConfig {
+            key: Some(Path::new("./tests/localhost.key").to_path_buf()),
+            ..TLSClientConfig::default()
+        };
+        assert!(tls_client_config(&tls_config).await.is_err());
+
+        // the key is not a certificate
+        let tls_config = TLSClientConfig {
+            cert: Some(Path::new("./tests/localhost.key").to_path_buf()),
+            key: Some(Path::new("./tests/localhost.key").to_path_buf()),
+            ..TLSClientConfig::default()
+        };
+        assert!(tls_client_config(&tls_config).await.is_err());
+        Ok(())
+    }
 }
@@ -222,8 +222,8 @@ error_chain! {
         }
 
         NotCSVSerializableValue(value: String) {
-            description("The value cannot be serialized to CSV. Expected an array.")
-            display("The value {} cannot be serialized to CSV. Expected an array.", value)
+            description("The value cannot be serialized to CSV. Expected an array or an object.")
+            display("The value {} cannot be serialized to CSV. Expected an array or an object.", value)
         }
 
         // TODO: Old errors, verify if needed
@@ -258,6 +258,10 @@ error_chain! {
             description("Invalid Syslog Protocol data")
                 display("Invalid Syslog Protocol data: {}", s)
         }
+        InvalidAvro(s: String) {
+            description("Invalid Avro data")
+                display("Invalid Avro data: {}", s)
+        }
         BadUtF8InString {
             description("Bad UTF8 in input string")
                 display("Bad UTF8 in input string")
@@ -348,6 +352,10 @@ error_chain! {
             description("Type in the message does not match BigQuery type")
                 display("Type in the message does not match BigQuery type. Expected: {}, actual: {:?}", expected, actual)
         }
+        BigQueryValueOutOfRange(column_type: &'static str, value: i64) {
+            description("Integer value is out of range for the BigQuery type")
+                display("Integer value {} is out of range for a BigQuery {} column", value, column_type)
+        }
 
         NoClickHouseClientAvailable {
             description("The ClickHouse adapter has no client available")
@@ -392,6 +400,10 @@ error_chain! {
             description("Google cloud storage error")
                 display("Google cloud storage error: {}", msg)
         }
+        ConnectFailed(diagnostic: crate::connectors::utils::diagnostics::ConnectDiagnostic) {
+            description("Connecting failed")
+                display("{}", diagnostic)
+        }
     }
 }
 
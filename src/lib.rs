@@ -98,18 +98,55 @@ lazy_static! {
     pub static ref QSIZE: AtomicUsize = AtomicUsize::new(128);
 }
 
-/// Loads a Troy file
+/// File name for reading a Troy file from stdin
+pub const STDIN_FILE_NAME: &str = "-";
+
+/// Loads a Troy file, `-` reads it from stdin
 ///
 /// # Errors
 /// Fails if the file can not be loaded
 pub async fn load_troy_file(world: &World, file_name: &str) -> Result<usize> {
-    use std::io::Read;
     info!("Loading troy from {}", file_name);
+    let deployable = parse_troy_file(file_name)?;
+
+    let mut count = 0;
+    for flow in deployable.iter_flows() {
+        world.start_flow(flow).await?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+/// Resolves the flows deployed by a Troy file without starting them, `-` reads it from stdin
+///
+/// Returns the effective configuration of the connectors and pipelines of each deployed flow.
+///
+/// # Errors
+/// Fails if the file can not be loaded or a flow can not be resolved
+pub fn resolve_troy_file(file_name: &str) -> Result<Vec<tremor_value::Value<'static>>> {
+    resolve_flows(&parse_troy_file(file_name)?)
+}
 
-    let mut file = tremor_common::file::open(&file_name)?;
+fn resolve_flows(deployable: &Deploy) -> Result<Vec<tremor_value::Value<'static>>> {
+    deployable
+        .iter_flows()
+        .map(system::flow::Flow::resolve)
+        .collect()
+}
+
+fn parse_troy_file(file_name: &str) -> Result<Deploy> {
+    if file_name == STDIN_FILE_NAME {
+        parse_troy("stdin", std::io::stdin().lock())
+    } else {
+        parse_troy(file_name, tremor_common::file::open(&file_name)?)
+    }
+}
+
+fn parse_troy<R: std::io::Read>(file_name: &str, mut reader: R) -> Result<Deploy> {
     let mut src = String::new();
 
-    file.read_to_string(&mut src)
+    reader
+        .read_to_string(&mut src)
         .map_err(|e| Error::from(format!("Could not open file {} => {}", file_name, e)))?;
     let aggr_reg = tremor_script::registry::aggr();
 
@@ -126,13 +163,7 @@ pub async fn load_troy_file(world: &World, file_name: &str) -> Result<usize> {
             return Err(format!("failed to load troy file: {}", file_name).into());
         }
     };
-
-    let mut count = 0;
-    for flow in deployable.iter_flows() {
-        world.start_flow(flow).await?;
-        count += 1;
-    }
-    Ok(count)
+    Ok(deployable)
 }
 
 /// Logs but ignores an error
@@ -155,6 +186,7 @@ mod tests {
     use crate::system::{ShutdownMode, WorldConfig};
     use std::io::Write;
     use tempfile;
+    use tremor_value::prelude::*;
 
     #[async_std::test]
     async fn test_load_troy_file() -> Result<()> {
@@ -181,4 +213,89 @@ mod tests {
         troy_file.close()?;
         Ok(())
     }
+
+    #[test]
+    fn test_resolve_troy_file() -> Result<()> {
+        let troy_file = tempfile::NamedTempFile::new()?;
+        troy_file.as_file().write_all(
+            r#"
+        define flow my_flow
+        flow
+            define connector foo from metronome
+            with
+                codec = "json",
+                config = { "interval": 1000 }
+            end;
+            define pipeline bar
+            pipeline
+                select event from in into out;
+            end;
+            create connector foo;
+            create pipeline bar;
+            connect /connector/foo to /pipeline/bar;
+        end;
+        deploy flow my_flow;
+        "#
+            .as_bytes(),
+        )?;
+        troy_file.as_file().flush()?;
+        let path = troy_file.path().display().to_string();
+        let resolved = resolve_troy_file(&path)?;
+        assert_eq!(1, resolved.len());
+        // the resolved configuration is valid JSON
+        let mut json = simd_json::to_string(&resolved[0])?.into_bytes();
+        let flow = tremor_value::parse_to_value(&mut json)?;
+        assert_eq!(Some("my_flow"), flow.get_str("flow"));
+        let foo = flow
+            .get("connectors")
+            .and_then(|c| c.get("foo"))
+            .ok_or("missing connector foo")?;
+        assert_eq!(Some("metronome"), foo.get_str("type"));
+        assert_eq!(
+            Some("json"),
+            foo.get("codec").and_then(|c| c.get_str("name"))
+        );
+        assert_eq!(
+            Some(1000),
+            foo.get("config").and_then(|c| c.get_u64("interval"))
+        );
+        // the connector specific config is as given, without its defaults
+        assert_eq!(
+            Some(1),
+            foo.get("config").and_then(Value::as_object).map(|c| c.len())
+        );
+        assert_eq!(Some("none"), foo.get_str("reconnect"));
+        assert_eq!(
+            Some("bar"),
+            flow.get("pipelines")
+                .and_then(|p| p.get("bar"))
+                .and_then(|p| p.get_str("definition"))
+        );
+        troy_file.close()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_resolve_troy_from_reader() -> Result<()> {
+        let src = r#"
+        define flow my_flow
+        flow
+            define connector foo from metronome
+            with
+                config = { "interval": 1000 }
+            end;
+            create connector foo;
+        end;
+        deploy flow my_flow;
+        "#;
+        let troy_file = tempfile::NamedTempFile::new()?;
+        troy_file.as_file().write_all(src.as_bytes())?;
+        troy_file.as_file().flush()?;
+        let path = troy_file.path().display().to_string();
+        // stdin is read like any other reader
+        let from_reader = resolve_flows(&parse_troy("stdin", src.as_bytes())?)?;
+        assert_eq!(resolve_troy_file(&path)?, from_reader);
+        troy_file.close()?;
+        Ok(())
+    }
 }
@@ -65,6 +65,7 @@ pub fn lookup_with_config(config: &PreprocessorConfig) -> Result<Box<dyn Preproc
             config.config.as_ref(),
         )?)),
         "remove-empty" => Ok(Box::new(FilterEmpty::default())),
+        "ndjson" => Ok(Box::new(Ndjson::default())),
         "gelf-chunking" => Ok(Box::new(gelf::Gelf::default())),
         "ingest-ns" => Ok(Box::new(ExtractIngestTs {})),
         "length-prefixed" => Ok(Box::new(LengthPrefix::default())),
@@ -181,6 +182,36 @@ impl Preprocessor for FilterEmpty {
     }
 }
 
+/// Splits newline delimited JSON into one chunk per line, dropping blank lines,
+/// to be decoded with the `json` codec
+#[derive(Default, Clone)]
+pub(crate) struct Ndjson {
+    lines: Separate,
+}
+
+impl Ndjson {
+    fn non_blank(lines: Vec<Vec<u8>>) -> Vec<Vec<u8>> {
+        lines
+            .into_iter()
+            .filter(|line| !line.iter().all(u8::is_ascii_whitespace))
+            .collect()
+    }
+}
+
+impl Preprocessor for Ndjson {
+    fn name(&self) -> &str {
+        "ndjson"
+    }
+
+    fn process(&mut self, ingest_ns: &mut u64, data: &[u8]) -> Result<Vec<Vec<u8>>> {
+        self.lines.process(ingest_ns, data).map(Self::non_blank)
+    }
+
+    fn finish(&mut self, data: Option<&[u8]>) -> Result<Vec<Vec<u8>>> {
+        self.lines.finish(data).map(Self::non_blank)
+    }
+}
+
 #[derive(Clone, Default, Debug)]
 pub(crate) struct ExtractIngestTs {}
 impl Preprocessor for ExtractIngestTs {
@@ -468,11 +499,12 @@ mod test {
         Ok(())
     }
 
-    const LOOKUP_TABLE: [&str; 8] = [
+    const LOOKUP_TABLE: [&str; 9] = [
         "separate",
         "base64",
         "decompress",
         "remove-empty",
+        "ndjson",
         "gelf-chunking",
         "ingest-ns",
         "length-prefixed",
@@ -505,6 +537,41 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn test_ndjson() -> Result<()> {
+        use crate::codec;
+        use tremor_value::literal;
+        let mut pre = lookup("ndjson")?;
+        let mut codec = codec::resolve(&"json".into())?;
+        let mut ingest_ns = 0_u64;
+
+        let mut chunks = pre.process(&mut ingest_ns, b"{\"snot\": 1}\n\n  \r\n[1,")?;
+        chunks.append(&mut pre.process(&mut ingest_ns, b"2]\r\n\"badger\"\n\n")?);
+        chunks.append(&mut pre.finish(None)?);
+        let values = chunks
+            .iter_mut()
+            .map(|chunk| {
+                codec
+                    .decode(chunk, 0)
+                    .map(|v| v.map(tremor_value::Value::into_static))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(
+            vec![
+                Some(literal!({"snot": 1})),
+                Some(literal!([1, 2])),
+                Some(literal!("badger"))
+            ],
+            values
+        );
+
+        // the last line is emitted on finish, even without a trailing newline
+        assert_eq!(Ok(vec![]), pre.process(&mut ingest_ns, b"{}"));
+        assert_eq!(Ok(vec![b"{}".to_vec()]), pre.finish(None));
+        assert_eq!(Ok(vec![]), pre.finish(Some(b"\n \n")));
+        Ok(())
+    }
+
     #[test]
     fn test_lines() -> Result<()> {
         let int = "snot\nbadger".as_bytes();
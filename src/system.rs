@@ -33,12 +33,15 @@ pub struct WorldConfig {
     pub qsize: usize,
     /// if debug connectors should be loaded
     pub debug_connectors: bool,
+    /// how long a graceful shutdown waits for flows to drain before stopping them forcefully
+    pub graceful_shutdown_timeout: Duration,
 }
 impl Default for WorldConfig {
     fn default() -> Self {
         Self {
             qsize: QSIZE.load(Ordering::Relaxed),
             debug_connectors: false,
+            graceful_shutdown_timeout: DEFAULT_GRACEFUL_SHUTDOWN_TIMEOUT,
         }
     }
 }
@@ -57,9 +60,9 @@ pub enum ShutdownMode {
     Forceful,
 }
 
-/// for draining and stopping
+/// for draining and stopping, draining is given up on after the graceful shutdown timeout
 #[derive(Debug, Clone)]
-pub struct KillSwitch(Sender<flow_supervisor::Msg>);
+pub struct KillSwitch(Sender<flow_supervisor::Msg>, Duration);
 
 impl KillSwitch {
     /// stop the runtime
@@ -70,14 +73,14 @@ impl KillSwitch {
         if mode == ShutdownMode::Graceful {
             let (tx, rx) = bounded(1);
             self.0.send(flow_supervisor::Msg::Drain(tx)).await?;
-            if let Ok(res) = rx.recv().timeout(DEFAULT_GRACEFUL_SHUTDOWN_TIMEOUT).await {
+            if let Ok(res) = rx.recv().timeout(self.1).await {
                 if let Err(e) | Ok(Err(e)) = res.map_err(Error::from) {
                     error!("Error draining all Flows: {}", e);
                 }
             } else {
                 warn!(
-                    "Timeout draining all Flows after {}s",
-                    DEFAULT_GRACEFUL_SHUTDOWN_TIMEOUT.as_secs()
+                    "Timeout draining all Flows after {}ms, stopping them forcefully",
+                    self.1.as_millis()
                 );
             }
         }
@@ -90,7 +93,7 @@ impl KillSwitch {
 
     #[cfg(test)]
     pub(crate) fn dummy() -> Self {
-        KillSwitch(bounded(1).0)
+        KillSwitch(bounded(1).0, DEFAULT_GRACEFUL_SHUTDOWN_TIMEOUT)
     }
 }
 
@@ -186,8 +189,8 @@ impl World {
     /// # Errors
     ///  * if the world manager can't be started
     pub async fn start(config: WorldConfig) -> Result<(Self, JoinHandle<Result<()>>)> {
-        let (system_h, system, kill_switch) =
-            flow_supervisor::FlowSupervisor::new(config.qsize).start();
+        let (system_h, system, kill_switch) = flow_supervisor::FlowSupervisor::new(config.qsize)
+            .start(config.graceful_shutdown_timeout);
 
         let world = Self {
             system,
@@ -206,3 +209,29 @@ impl World {
         self.kill_switch.stop(mode).await
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[async_std::test]
+    async fn graceful_stop_escalates_after_timeout() -> Result<()> {
+        let (tx, rx) = bounded(1);
+        let kill_switch = KillSwitch(tx, Duration::from_millis(100));
+        let stop =
+            async_std::task::spawn(async move { kill_switch.stop(ShutdownMode::Graceful).await });
+        // never answer the drain request
+        let _drain_tx = match rx.recv().await? {
+            flow_supervisor::Msg::Drain(drain_tx) => drain_tx,
+            _ => return Err("expected a drain request".into()),
+        };
+        let start = std::time::Instant::now();
+        assert!(matches!(
+            rx.recv().timeout(Duration::from_secs(5)).await??,
+            flow_supervisor::Msg::Stop
+        ));
+        assert!(start.elapsed() >= Duration::from_millis(50));
+        stop.await?;
+        Ok(())
+    }
+}
@@ -34,6 +34,7 @@ use tremor_script::{
     ast::{self, ConnectStmt, DeployFlow, Helper},
     errors::not_defined_err,
 };
+use tremor_value::{literal, prelude::*, Value};
 
 /// unique identifier of a flow instance within a tremor instance
 #[derive(Debug, PartialEq, PartialOrd, Eq, Hash, Clone, Serialize, Deserialize)]
@@ -180,6 +181,57 @@ impl Flow {
         self.addr.send(Msg::Resume).await.map_err(Error::from)
     }
 
+    /// Resolves the configuration of the connectors and pipelines created by `flow`, without starting them
+    ///
+    /// # Errors
+    ///  * if a connector or pipeline definition can't be resolved
+    pub(crate) fn resolve(flow: &ast::DeployFlow<'static>) -> Result<Value<'static>> {
+        let flow_alias = Alias::from(flow);
+        let mut connectors = Value::object();
+        let mut pipelines = Value::object();
+        for create in &flow.defn.creates {
+            let alias: &str = &create.instance_alias;
+            match &create.defn {
+                ast::CreateTargetDefinition::Connector(defn) => {
+                    let mut defn = defn.clone();
+                    defn.params.ingest_creational_with(&create.with)?;
+                    let connector_alias = connectors::Alias::new(flow_alias.clone(), alias);
+                    let config = crate::Connector::from_defn(&connector_alias, &defn)?;
+                    connectors.try_insert(alias.to_string(), config.to_value()?);
+                }
+                ast::CreateTargetDefinition::Pipeline(defn) => {
+                    let aggr_reg = tremor_script::aggr_registry();
+                    let reg = tremor_script::FN_REGISTRY.read()?;
+                    let mut helper = Helper::new(&reg, &aggr_reg);
+                    // only to make sure the pipeline can be created
+                    defn.to_query(&create.with, &mut helper)?;
+                    pipelines.try_insert(
+                        alias.to_string(),
+                        literal!({ "definition": defn.id.clone() }),
+                    );
+                }
+            }
+        }
+        let connections: Vec<Value<'static>> = flow
+            .defn
+            .connections
+            .iter()
+            .map(|connect| match connect {
+                ConnectStmt::ConnectorToPipeline { from, to, .. }
+                | ConnectStmt::PipelineToConnector { from, to, .. }
+                | ConnectStmt::PipelineToPipeline { from, to, .. } => {
+                    Value::from(format!("{from} -> {to}"))
+                }
+            })
+            .collect();
+        Ok(literal!({
+            "flow": flow_alias.to_string(),
+            "connectors": connectors,
+            "pipelines": pipelines,
+            "connections": connections,
+        }))
+    }
+
     pub(crate) async fn start(
         flow: ast::DeployFlow<'static>,
         operator_id_gen: &mut OperatorIdGen,
@@ -856,7 +908,7 @@ mod tests {
         deploy flow test;
         "#;
         let (tx, _rx) = bounded(1);
-        let kill_switch = KillSwitch(tx);
+        let kill_switch = KillSwitch(tx, crate::system::DEFAULT_GRACEFUL_SHUTDOWN_TIMEOUT);
         let deployable = Deploy::parse(&src, &*FN_REGISTRY.read()?, &aggr_reg)?;
         let deploy = deployable
             .deploy
@@ -24,6 +24,7 @@ use async_std::channel::{bounded, Sender};
 use async_std::prelude::*;
 use async_std::task::{self, JoinHandle};
 use hashbrown::{hash_map::Entry, HashMap};
+use std::time::Duration;
 use tremor_common::ids::{ConnectorIdGen, OperatorIdGen};
 use tremor_script::ast::DeployFlow;
 
@@ -193,9 +194,12 @@ impl FlowSupervisor {
         }
     }
 
-    pub fn start(mut self) -> (JoinHandle<Result<()>>, Channel, KillSwitch) {
+    pub fn start(
+        mut self,
+        graceful_shutdown_timeout: Duration,
+    ) -> (JoinHandle<Result<()>>, Channel, KillSwitch) {
         let (tx, rx) = bounded(self.qsize);
-        let kill_switch = KillSwitch(tx.clone());
+        let kill_switch = KillSwitch(tx.clone(), graceful_shutdown_timeout);
         let task_kill_switch = kill_switch.clone();
         let system_h = task::spawn(async move {
             while let Ok(msg) = rx.recv().await {
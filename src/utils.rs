@@ -12,6 +12,12 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 use crate::errors::{Error, Kind as ErrorKind};
+use std::sync::RwLock;
+
+lazy_static! {
+    static ref INSTANCE_HOST: RwLock<Option<String>> = RwLock::new(None);
+}
+
 /// Fetches a hostname with `tremor-host.local` being the default
 #[must_use]
 
@@ -25,3 +31,20 @@ pub fn hostname() -> String {
         })
         .unwrap_or_else(|_| "tremor_host.local".to_string())
 }
+
+/// Sets the host used in the origin URIs of events, instead of the system hostname
+pub fn set_instance_host(host: Option<String>) {
+    if let Ok(mut instance_host) = INSTANCE_HOST.write() {
+        *instance_host = host;
+    }
+}
+
+/// The host used in the origin URIs of events, the configured instance host or the system hostname
+#[must_use]
+pub fn instance_host() -> String {
+    INSTANCE_HOST
+        .read()
+        .ok()
+        .and_then(|host| host.clone())
+        .unwrap_or_else(hostname)
+}
@@ -16,7 +16,7 @@ use std::io::prelude::*;
 use tremor_common::{file, ids::OperatorIdGen};
 use tremor_pipeline::query::Query;
 use tremor_pipeline::ExecutableGraph;
-use tremor_pipeline::{Event, EventId};
+use tremor_pipeline::{CbAction, Event, EventId, OpMeta};
 use tremor_script::FN_REGISTRY;
 
 use serial_test::serial;
@@ -146,4 +146,112 @@ test_cases!(
     guard_having,
     history,
     roundrobin,
+    geoip,
+    window_tz,
+    sample,
+    redact,
+    enrich,
+    unwind,
 );
+
+#[async_std::test]
+#[serial(query)]
+async fn dedup() -> Result<()> {
+    tremor_runtime::functions::load()?;
+    let query_file = "tests/queries/dedup/query.trickle";
+    let in_file = "tests/queries/dedup/in";
+    let out_file = "tests/queries/dedup/out";
+    let mut file = file::open(query_file)?;
+    let mut contents = String::new();
+    file.read_to_string(&mut contents)?;
+    let mut pipeline = to_pipe(contents)?;
+
+    let in_json = load_event_file(in_file)?;
+    let out_json = load_event_file(out_file)?;
+
+    let mut results = Vec::new();
+    for (id, json) in in_json.into_iter().enumerate() {
+        // events arrive 30ms apart, the window is 100ms
+        let event = Event {
+            id: EventId::new(0, 0, id as u64, id as u64),
+            data: json.clone_static().into(),
+            ingest_ns: id as u64 * 30_000_000,
+            ..Event::default()
+        };
+        let mut r = vec![];
+        pipeline.enqueue("in", event, &mut r).await?;
+        results.append(&mut r);
+    }
+    assert_eq!(
+        results.len(),
+        out_json.len(),
+        "Number of events differ error"
+    );
+    for ((_, result), expected) in results.iter().zip(out_json.iter()) {
+        assert_eq!(
+            sorted_serialize(result.data.suffix().value())?,
+            sorted_serialize(expected)?
+        );
+    }
+    Ok(())
+}
+
+#[async_std::test]
+#[serial(query)]
+async fn load_shed() -> Result<()> {
+    tremor_runtime::functions::load()?;
+    let query_file = "tests/queries/load_shed/query.trickle";
+    let in_file = "tests/queries/load_shed/in";
+    let out_file = "tests/queries/load_shed/out";
+    let mut file = file::open(query_file)?;
+    let mut contents = String::new();
+    file.read_to_string(&mut contents)?;
+    let mut pipeline = to_pipe(contents)?;
+
+    let in_json = load_event_file(in_file)?;
+    let out_json = load_event_file(out_file)?;
+
+    // the circuit breaker is triggered before the 3rd event and restored before the 6th
+    let cb_actions = [(2, CbAction::Trigger), (5, CbAction::Restore)];
+
+    // like a sink, we track the op meta of all events we received
+    let mut op_meta = OpMeta::default();
+    let mut results = Vec::new();
+    for (id, json) in in_json.into_iter().enumerate() {
+        if let Some((_, cb)) = cb_actions.iter().find(|(i, _)| *i == id) {
+            let insight = Event {
+                ingest_ns: id as u64,
+                cb: *cb,
+                op_meta: op_meta.clone(),
+                ..Event::default()
+            };
+            // the operator handles the circuit breaker, so it is not propagated to the source
+            let insight = pipeline.contraflow(None, insight);
+            assert_eq!(CbAction::None, insight.cb);
+        }
+        let event = Event {
+            id: EventId::new(0, 0, id as u64, id as u64),
+            data: json.clone_static().into(),
+            ingest_ns: id as u64,
+            ..Event::default()
+        };
+        let mut r = vec![];
+        pipeline.enqueue("in", event, &mut r).await?;
+        for (_, event) in &r {
+            op_meta.merge(event.op_meta.clone());
+        }
+        results.append(&mut r);
+    }
+    assert_eq!(
+        results.len(),
+        out_json.len(),
+        "Number of events differ error"
+    );
+    for ((_, result), expected) in results.iter().zip(out_json.iter()) {
+        assert_eq!(
+            sorted_serialize(result.data.suffix().value())?,
+            sorted_serialize(expected)?
+        );
+    }
+    Ok(())
+}
@@ -239,6 +239,7 @@ mod tests {
         let config = WorldConfig {
             qsize: 16,
             debug_connectors: true,
+            ..WorldConfig::default()
         };
         let (world, world_handle) = World::start(config).await?;
 
@@ -116,6 +116,10 @@ pub(crate) async fn patch_flow_connector_status(mut req: Request) -> Result<Resp
             connector.resume().await?;
             connector.report_status().await?
         }
+        (InstanceState::Failed, InstanceState::Running) => {
+            connector.restart().await?;
+            connector.report_status().await?
+        }
         // TODO: we could stop a deployment by patching its status to `Stopped`
         (current, desired) => {
             // throw error
@@ -12,6 +12,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 use clap::{ArgEnum, Parser};
+use std::time::Duration;
 
 /// Tremor cli - Command Line Interface
 #[derive(Parser, Debug)]
@@ -243,7 +244,7 @@ pub(crate) enum ServerCommand {
 #[derive(Parser, Debug)]
 pub(crate) struct ServerRun {
     #[clap(value_parser = clap::value_parser!(String))]
-    /// Paths to files containing pipelines, onramps, offramps to provision
+    /// Paths to troy files containing the flows to deploy, `-` reads a troy file from stdin
     pub(crate) artefacts: Vec<String>,
     /// Captures process id if set and stores in a file
     #[clap(short, long, value_parser = clap::value_parser!(String))]
@@ -260,6 +261,33 @@ pub(crate) struct ServerRun {
     /// function tail-recursion stack depth limit
     #[clap(short, long, default_value = "1024", value_parser = clap::value_parser!(u32))]
     pub(crate) recursion_limit: u32,
+    /// How long to wait for flows to drain on shutdown before stopping them forcefully, e.g. `500ms`, `10s` or `1m`
+    #[clap(long, default_value = "5s", value_parser = parse_duration)]
+    pub(crate) shutdown_timeout: Duration,
+    /// Host to use in the origin URI of events instead of the system hostname, unless a connector sets its own `instance_host`
+    #[clap(long, value_parser = clap::value_parser!(String))]
+    pub(crate) instance_host: Option<String>,
+    /// Prints the resolved configuration of the flows deployed by the given files as JSON and exits without starting them,
+    /// connector specific configs are printed as given, without their defaults
+    #[clap(long, action = clap::ArgAction::SetTrue)]
+    pub(crate) dump_config: bool,
+}
+
+/// parses a duration given as a number with one of the units `ms`, `s` or `m`
+fn parse_duration(s: &str) -> std::result::Result<Duration, String> {
+    let s = s.trim();
+    let (number, unit) = s.split_at(s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len()));
+    let number: u64 = number
+        .parse()
+        .map_err(|_| format!("invalid duration `{s}`"))?;
+    match unit {
+        "ms" => Ok(Duration::from_millis(number)),
+        "s" => Ok(Duration::from_secs(number)),
+        "m" => Ok(Duration::from_secs(number.saturating_mul(60))),
+        _ => Err(format!(
+            "invalid duration `{s}`, expected a unit of `ms`, `s` or `m`"
+        )),
+    }
 }
 
 // TODO: since the API will change this isn't translated yet
@@ -483,3 +511,38 @@ pub(crate) struct Api {
                             takes_value: true
         */
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn server_run(args: &[&str]) -> std::result::Result<ServerRun, clap::Error> {
+        let cli = Cli::try_parse_from(["tremor", "server", "run"].iter().chain(args))?;
+        match cli.command {
+            Command::Server {
+                command: ServerCommand::Run(run),
+            } => Ok(run),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn shutdown_timeout() -> std::result::Result<(), clap::Error> {
+        assert_eq!(Duration::from_secs(5), server_run(&[])?.shutdown_timeout);
+        assert_eq!(
+            Duration::from_millis(250),
+            server_run(&["--shutdown-timeout", "250ms"])?.shutdown_timeout
+        );
+        assert_eq!(
+            Duration::from_secs(30),
+            server_run(&["--shutdown-timeout", "30s"])?.shutdown_timeout
+        );
+        assert_eq!(
+            Duration::from_secs(120),
+            server_run(&["--shutdown-timeout", "2m"])?.shutdown_timeout
+        );
+        assert!(server_run(&["--shutdown-timeout", "30"]).is_err());
+        assert!(server_run(&["--shutdown-timeout", "snot"]).is_err());
+        Ok(())
+    }
+}
@@ -27,7 +27,8 @@ use std::sync::atomic::Ordering;
 use tremor_api as api;
 use tremor_common::file;
 use tremor_runtime::system::{ShutdownMode, World};
-use tremor_runtime::{self, version};
+use tremor_runtime::{self, version, STDIN_FILE_NAME};
+use tremor_value::Value;
 
 macro_rules! log_and_print_error {
     ($($arg:tt)*) => {
@@ -98,10 +99,12 @@ impl ServerRun {
         }
 
         tremor_script::RECURSION_LIMIT.store(self.recursion_limit, Ordering::Relaxed);
+        tremor_runtime::utils::set_instance_host(self.instance_host.clone());
 
         // TODO: Allow configuring this for offramps and pipelines
         let config = WorldConfig {
             debug_connectors: self.debug_connectors,
+            graceful_shutdown_timeout: self.shutdown_timeout,
             ..WorldConfig::default()
         };
 
@@ -116,7 +119,7 @@ impl ServerRun {
         // We process trickle files first
         for config_file in &self.artefacts {
             let kind = get_source_kind(config_file);
-            if kind == SourceKind::Troy {
+            if kind == SourceKind::Troy || config_file == STDIN_FILE_NAME {
                 troy_files.push(config_file);
             } else {
                 return Err(
@@ -168,9 +171,32 @@ impl ServerRun {
         Ok(result)
     }
 
+    /// prints the resolved configuration of all troy files
+    fn dump_config(&self) -> Result<()> {
+        let mut flows = Vec::new();
+        for config_file in &self.artefacts {
+            let kind = get_source_kind(config_file);
+            if kind != SourceKind::Troy && config_file != STDIN_FILE_NAME {
+                return Err(
+                    ErrorKind::UnsupportedFileType(config_file.to_string(), kind, "troy").into(),
+                );
+            }
+            let resolved = tremor_runtime::resolve_troy_file(config_file)
+                .map_err(|e| ErrorKind::FileLoadError(config_file.to_string(), e))?;
+            flows.extend(resolved);
+        }
+        println!("{}", simd_json::to_string_pretty(&Value::from(flows))?);
+        Ok(())
+    }
+
     async fn run(&self) {
         version::print();
-        match self.run_dun().await {
+        let res = if self.dump_config {
+            self.dump_config().map(|_| 0)
+        } else {
+            self.run_dun().await
+        };
+        match res {
             Err(e) => {
                 match e {
                     Error(ErrorKind::AnyhowError(anyhow_e), _) => {
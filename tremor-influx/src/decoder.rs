@@ -189,6 +189,9 @@ where
             } else {
                 return Err(Error::UnexpectedEnd(total_index + offset));
             }
+        } else if input.is_empty() {
+            // the value ended with an escape, there is nothing left to escape
+            return Err(Error::UnexpectedEnd(total_index + offset));
         } else {
             res.push_str(input);
             return Ok((to_value(total_index, &res)?, None, input.len() - 1));
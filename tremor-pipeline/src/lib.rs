@@ -847,10 +847,13 @@ fn factory(node: &NodeConfig) -> Result<Box<dyn InitializableOperator>> {
     #[cfg(feature = "bert")]
     use op::bert::{SequenceClassificationFactory, SummerizationFactory};
     use op::debug::EventHistoryFactory;
-    use op::generic::{BatchFactory, CounterFactory};
+    use op::generic::{
+        BatchFactory, CounterFactory, DedupFactory, EnrichFactory, GeoIpFactory, RedactFactory,
+        SampleFactory, UnwindFactory,
+    };
     use op::grouper::BucketGrouperFactory;
     use op::identity::PassthroughFactory;
-    use op::qos::{BackpressureFactory, PercentileFactory, RoundRobinFactory};
+    use op::qos::{BackpressureFactory, LoadShedFactory, PercentileFactory, RoundRobinFactory};
     let name_parts: Vec<&str> = node.op_type.split("::").collect();
     let factory = match name_parts.as_slice() {
         ["passthrough"] => PassthroughFactory::new_boxed(),
@@ -862,9 +865,16 @@ fn factory(node: &NodeConfig) -> Result<Box<dyn InitializableOperator>> {
             BackpressureFactory::new_boxed()
         }
         ["generic", "counter"] => CounterFactory::new_boxed(),
+        ["generic", "dedup"] => DedupFactory::new_boxed(),
+        ["generic", "enrich"] => EnrichFactory::new_boxed(),
+        ["generic", "geoip"] => GeoIpFactory::new_boxed(),
+        ["generic", "redact"] => RedactFactory::new_boxed(),
+        ["generic", "sample"] => SampleFactory::new_boxed(),
+        ["generic", "unwind"] => UnwindFactory::new_boxed(),
         ["qos", "backpressure"] => BackpressureFactory::new_boxed(),
         ["qos", "roundrobin"] => RoundRobinFactory::new_boxed(),
         ["qos", "percentile"] => PercentileFactory::new_boxed(),
+        ["qos", "loadshed"] => LoadShedFactory::new_boxed(),
         #[cfg(feature = "bert")]
         ["bert", "sequence_classification"] => SequenceClassificationFactory::new_boxed(),
         #[cfg(feature = "bert")]
@@ -14,6 +14,18 @@
 
 pub mod batch;
 pub mod counter;
+pub mod dedup;
+pub mod enrich;
+pub mod geoip;
+pub mod redact;
+pub mod sample;
+pub mod unwind;
 
 pub use batch::BatchFactory;
 pub use counter::CounterFactory;
+pub use dedup::DedupFactory;
+pub use enrich::EnrichFactory;
+pub use geoip::GeoIpFactory;
+pub use redact::RedactFactory;
+pub use sample::SampleFactory;
+pub use unwind::UnwindFactory;
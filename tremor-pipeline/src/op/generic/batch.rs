@@ -24,6 +24,9 @@ pub struct Config {
     /// The amount time between messags to flush in nanoseconds
     #[serde(default = "Default::default")]
     pub timeout: Option<u64>,
+    /// Flush once the JSON encoded size of the batched events reaches this many bytes
+    #[serde(default = "Default::default")]
+    pub max_bytes: Option<usize>,
 }
 
 impl ConfigImpl for Config {}
@@ -33,6 +36,8 @@ struct Batch {
     config: Config,
     data: EventPayload,
     len: usize,
+    /// JSON encoded size of the batched events, only tracked if `max_bytes` is configured
+    bytes: usize,
     max_delay_ns: Option<u64>,
     first_ns: u64,
     /// event id for the resulting batched event
@@ -55,6 +60,7 @@ if let Some(map) = &node.config {
     Ok(Box::new(Batch {
         data: empty_payload(),
         len: 0,
+        bytes: 0,
         config,
         max_delay_ns,
         first_ns: 0,
@@ -77,6 +83,9 @@ impl Operator for Batch {
         _state: &mut Value<'static>,
         event: Event,
     ) -> Result<EventAndInsights> {
+        if self.config.max_bytes.is_some() {
+            self.bytes += event.value_iter().map(|v| v.encode().len()).sum::<usize>();
+        }
         // TODO: This is ugly
         let Event {
             id,
@@ -113,13 +122,17 @@ impl Operator for Batch {
         };
         let flush = match self.max_delay_ns {
             Some(t) if ingest_ns - self.first_ns > t => true,
-            _ => self.len == self.config.count,
+            _ => {
+                self.len == self.config.count
+                    || self.config.max_bytes.map_or(false, |max| self.bytes >= max)
+            }
         };
         if flush {
             //TODO: This is ugly
             let mut data = empty_payload();
             swap(&mut data, &mut self.data);
             self.len = 0;
+            self.bytes = 0;
 
             let mut event = Event {
                 id: self.event_id_gen.next_id(),
@@ -159,6 +172,7 @@ impl Operator for Batch {
                         swap(&mut data, &mut self.data);
 
                         self.len = 0; // reset len
+                        self.bytes = 0;
                         let mut event = Event {
                             id: self.event_id_gen.next_id(),
                             data,
@@ -195,11 +209,13 @@ mod test {
             config: Config {
                 count: 2,
                 timeout: None,
+                max_bytes: None,
             },
             first_ns: 0,
             max_delay_ns: None,
             data: empty_payload(),
             len: 0,
+            bytes: 0,
             batch_event_id: idgen.next_id(),
             is_transactional: false,
             event_id_gen: idgen,
@@ -335,11 +351,13 @@ mod test {
             config: Config {
                 count: 100,
                 timeout: Some(1),
+                max_bytes: None,
             },
             first_ns: 0,
             max_delay_ns: Some(1_000_000),
             data: empty_payload(),
             len: 0,
+            bytes: 0,
             batch_event_id: idgen.next_id(),
             is_transactional: false,
             event_id_gen: idgen,
@@ -411,11 +429,13 @@ mod test {
             config: Config {
                 count: 2,
                 timeout: Some(1),
+                max_bytes: None,
             },
             first_ns: 0,
             max_delay_ns: Some(100_000),
             data: empty_payload(),
             len: 0,
+            bytes: 0,
             batch_event_id: idgen.next_id(),
             is_transactional: false,
             event_id_gen: idgen,
@@ -464,4 +484,90 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    fn max_bytes() -> Result<()> {
+        let operator_id = OperatorId::new(0);
+        let node_config = NodeConfig::from_config(
+            &"badger",
+            Some(literal!({
+                "count": 100,
+                "max_bytes": 16,
+            })),
+        );
+        let mut op = BatchFactory::new().node_to_operator(operator_id, &node_config)?;
+        let mut state = Value::null();
+
+        // `"snot"` and `"badger"` are 6 and 8 bytes JSON encoded
+        let event1 = Event {
+            id: (1, 1, 1).into(),
+            ingest_ns: 1,
+            data: Value::from("snot").into(),
+            ..Event::default()
+        };
+        let r = op.on_event(operator_id, "in", &mut state, event1.clone())?;
+        assert_eq!(r.len(), 0);
+        let event2 = Event {
+            id: (1, 1, 2).into(),
+            ingest_ns: 2,
+            data: Value::from("badger").into(),
+            ..Event::default()
+        };
+        let r = op.on_event(operator_id, "in", &mut state, event2.clone())?;
+        assert_eq!(r.len(), 0);
+        let event3 = Event {
+            id: (1, 1, 3).into(),
+            ingest_ns: 3,
+            data: Value::from("snot").into(),
+            ..Event::default()
+        };
+        let mut r = op
+            .on_event(operator_id, "in", &mut state, event3.clone())?
+            .events;
+        assert_eq!(r.len(), 1);
+        let (_out, event) = r.pop().expect("no results");
+        let events: Vec<&Value> = event.value_iter().collect();
+        assert_eq!(
+            events,
+            vec![
+                event1.data.suffix().value(),
+                event2.data.suffix().value(),
+                event3.data.suffix().value()
+            ]
+        );
+
+        // the byte count is reset after flushing
+        let r = op.on_event(operator_id, "in", &mut state, event2)?;
+        assert_eq!(r.len(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn tracks_event_ids() -> Result<()> {
+        let operator_id = OperatorId::new(0);
+        let node_config = NodeConfig::from_config(&"badger", Some(literal!({ "count": 3 })));
+        let mut op = BatchFactory::new().node_to_operator(operator_id, &node_config)?;
+        let mut state = Value::null();
+
+        let ids: Vec<EventId> = vec![(1, 1, 1).into(), (1, 1, 2).into(), (2, 1, 7).into()];
+        let mut r = Vec::new();
+        for id in &ids {
+            let event = Event {
+                id: id.clone(),
+                ingest_ns: 1,
+                data: Value::from("snot").into(),
+                transactional: true,
+                ..Event::default()
+            };
+            r = op.on_event(operator_id, "in", &mut state, event)?.events;
+        }
+        assert_eq!(r.len(), 1);
+        let (_out, event) = r.pop().expect("no results");
+        assert!(event.is_batch);
+        assert!(event.transactional);
+        for id in &ids {
+            assert!(event.id.is_tracking(id), "{} does not track {id}", event.id);
+        }
+        Ok(())
+    }
 }
@@ -0,0 +1,229 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! # Windowed deduplication
+//!
+//! Events whose key was already seen within `window_ms` of its first occurrence are duplicates,
+//! all other events pass. Time is taken from the ingest time of the events.
+//! Keys are compared with their type, so the string `"1"` and the number `1` are different keys.
+//! Events without a key always pass.
+//!
+//! ## Configuration
+//!
+//! See [Config](struct.Config.html) for details.
+//!
+//! ## Outputs
+//!
+//! The `dup` output is used to route duplicate events.
+
+use crate::errors::{ErrorKind, Result};
+use crate::op::prelude::*;
+use beef::Cow;
+use std::collections::{HashSet, VecDeque};
+use tremor_script::prelude::*;
+
+const DUP: Cow<'static, str> = Cow::const_str("dup");
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Dot separated path to the key field of the event, e.g. `request.id`.
+    pub key: String,
+    /// Window in milliseconds after the first occurrence of a key in which events with the same key are duplicates.
+    pub window_ms: u64,
+    /// Maximum number of keys remembered, the oldest keys are forgotten first. Must be greater than 0.
+    #[serde(default = "default_max_keys")]
+    pub max_keys: usize,
+}
+
+fn default_max_keys() -> usize {
+    100_000
+}
+
+impl ConfigImpl for Config {}
+
+#[derive(Debug, Clone)]
+struct Dedup {
+    key: Vec<String>,
+    window_ns: u64,
+    max_keys: usize,
+    /// keys seen within the window
+    seen: HashSet<String>,
+    /// the keys in `seen` with the time of their first occurrence, oldest first
+    order: VecDeque<(u64, String)>,
+    /// latest ingest time seen
+    now: u64,
+}
+
+impl TryFrom<Config> for Dedup {
+    type Error = crate::errors::Error;
+
+    fn try_from(config: Config) -> Result<Self> {
+        if config.max_keys == 0 {
+            return Err(
+                ErrorKind::BadOpConfig("`max_keys` must be greater than 0".to_string()).into(),
+            );
+        }
+        Ok(Self {
+            key: config.key.split('.').map(ToString::to_string).collect(),
+            window_ns: config.window_ms.saturating_mul(1_000_000),
+            max_keys: config.max_keys,
+            seen: HashSet::new(),
+            order: VecDeque::new(),
+            now: 0,
+        })
+    }
+}
+
+impl Dedup {
+    /// the encoded key of `event`, so keys of different types don't collide
+    fn key(&self, event: &Event) -> Option<String> {
+        let value = event.data.suffix().value();
+        self.key
+            .iter()
+            .try_fold(value, |v, key| v.get(key.as_str()))
+            .map(Value::encode)
+    }
+
+    /// forgets keys whose window has passed and the oldest keys beyond `max_keys`
+    fn evict(&mut self) {
+        while let Some((first_seen, key)) = self.order.front() {
+            let expired = first_seen.saturating_add(self.window_ns) <= self.now;
+            if !expired && self.order.len() <= self.max_keys {
+                break;
+            }
+            self.seen.remove(key);
+            self.order.pop_front();
+        }
+    }
+}
+
+op!(DedupFactory(_uid, node) {
+    if let Some(map) = &node.config {
+        let config: Config = Config::new(map)?;
+        Ok(Box::new(Dedup::try_from(config)?))
+    } else {
+        Err(ErrorKind::MissingOpConfig(node.id.clone()).into())
+    }
+});
+
+impl Operator for Dedup {
+    fn on_event(
+        &mut self,
+        _uid: OperatorId,
+        _port: &str,
+        _state: &mut Value<'static>,
+        event: Event,
+    ) -> Result<EventAndInsights> {
+        self.now = self.now.max(event.ingest_ns);
+        self.evict();
+        let output = match self.key(&event) {
+            Some(key) if self.seen.contains(&key) => DUP,
+            Some(key) => {
+                self.seen.insert(key.clone());
+                self.order.push_back((self.now, key));
+                self.evict();
+                OUT
+            }
+            None => OUT,
+        };
+        Ok(vec![(output, event)].into())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use tremor_common::ids::Id;
+
+    use super::*;
+
+    fn event(ingest_ms: u64, key: Value<'static>) -> Event {
+        Event {
+            id: (1, 1, ingest_ms).into(),
+            ingest_ns: ingest_ms * 1_000_000,
+            data: (literal!({ "id": key }), Value::object()).into(),
+            ..Event::default()
+        }
+    }
+
+    #[test]
+    fn bounded_keys() -> Result<()> {
+        let uid = OperatorId::new(0);
+        let mut op = Dedup::try_from(Config {
+            key: "id".to_string(),
+            window_ms: 1_000,
+            max_keys: 2,
+        })?;
+        let mut state = Value::null();
+
+        let outputs: Vec<_> = [
+            event(1, Value::from("snot")),
+            event(2, Value::from("badger")),
+            event(3, Value::from("snot")),
+            // forgets about `snot`
+            event(4, Value::from("cake")),
+            event(5, Value::from("snot")),
+            event(6, Value::from("cake")),
+        ]
+        .into_iter()
+        .map(|e| op.on_event(uid, "in", &mut state, e))
+        .collect::<Result<Vec<_>>>()?
+        .into_iter()
+        .flat_map(|r| r.events)
+        .map(|(port, _)| port.to_string())
+        .collect();
+        assert_eq!(vec!["out", "out", "dup", "out", "out", "dup"], outputs);
+        assert_eq!(2, op.seen.len());
+        assert_eq!(2, op.order.len());
+        Ok(())
+    }
+
+    #[test]
+    fn typed_keys() -> Result<()> {
+        let uid = OperatorId::new(0);
+        let mut op = Dedup::try_from(Config {
+            key: "id".to_string(),
+            window_ms: 1_000,
+            max_keys: 10,
+        })?;
+        let mut state = Value::null();
+
+        let outputs: Vec<_> = [
+            event(1, Value::from("1")),
+            event(2, Value::from(1)),
+            event(3, Value::from(true)),
+            event(4, Value::from("true")),
+            event(5, Value::from(1)),
+        ]
+        .into_iter()
+        .map(|e| op.on_event(uid, "in", &mut state, e))
+        .collect::<Result<Vec<_>>>()?
+        .into_iter()
+        .flat_map(|r| r.events)
+        .map(|(port, _)| port.to_string())
+        .collect();
+        assert_eq!(vec!["out", "out", "out", "out", "dup"], outputs);
+        Ok(())
+    }
+
+    #[test]
+    fn bad_config() {
+        assert!(Dedup::try_from(Config {
+            key: "id".to_string(),
+            window_ms: 1_000,
+            max_keys: 0,
+        })
+        .is_err());
+    }
+}
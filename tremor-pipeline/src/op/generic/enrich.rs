@@ -0,0 +1,231 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! # Lookup table enrichment
+//!
+//! Joins events against a static lookup table, loaded once from a file when the operator is created.
+//! The row whose `table_key` column equals the `key` field of the event is attached to the event
+//! at `target`. Events without a matching row pass unchanged.
+//!
+//! Tables are either CSV files with a header row, with all values read as strings, or JSON files
+//! containing an array of records. Keys are compared by their string representation, so a CSV value
+//! `"42"` matches the number `42` in an event.
+//!
+//! ## Configuration
+//!
+//! See [Config](struct.Config.html) for details.
+
+use crate::errors::{Error, ErrorKind, Result};
+use crate::op::prelude::*;
+use std::collections::HashMap;
+use std::path::Path;
+use tremor_script::prelude::*;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Path to the table file, `.csv` files are read as CSV, all others as JSON.
+    pub path: String,
+    /// Dot separated path to the field of the event that is looked up in the table, e.g. `country.code`.
+    pub key: String,
+    /// Column of the table matched against the `key` field, defaults to the last segment of `key`.
+    pub table_key: Option<String>,
+    /// Field of the event the matching row is attached to, defaults to `enrichment`.
+    #[serde(default = "default_target")]
+    pub target: String,
+}
+
+impl ConfigImpl for Config {}
+
+fn default_target() -> String {
+    "enrichment".to_string()
+}
+
+/// the string representation of a key, strings are taken as is, other values are encoded as JSON
+fn key_string(key: &Value) -> String {
+    key.as_str()
+        .map_or_else(|| key.encode(), ToString::to_string)
+}
+
+/// The rows of a lookup table by their key
+#[derive(Debug)]
+struct Table(HashMap<String, Value<'static>>);
+
+impl Table {
+    fn load(path: &str, table_key: &str) -> Result<Self> {
+        let bytes = std::fs::read(path)
+            .map_err(|e| Error::from(format!("Unable to read lookup table {path}: {e}")))?;
+        let is_csv = Path::new(path)
+            .extension()
+            .map_or(false, |ext| ext.eq_ignore_ascii_case("csv"));
+        let rows = if is_csv {
+            Self::csv_rows(&bytes)
+        } else {
+            Self::json_rows(bytes)
+        };
+        rows.and_then(|rows| Self::from_rows(rows, table_key))
+            .map_err(|e| Error::from(format!("Invalid lookup table {path}: {e}")))
+    }
+
+    fn csv_rows(bytes: &[u8]) -> Result<Vec<Value<'static>>> {
+        let mut reader = csv::Reader::from_reader(bytes);
+        let headers = reader
+            .headers()
+            .map_err(|e| Error::from(e.to_string()))?
+            .clone();
+        reader
+            .records()
+            .map(|record| {
+                let record = record.map_err(|e| Error::from(e.to_string()))?;
+                let row: Object<'static> = headers
+                    .iter()
+                    .zip(record.iter())
+                    .map(|(column, value)| {
+                        (column.to_string().into(), Value::from(value.to_string()))
+                    })
+                    .collect();
+                Ok(Value::from(row))
+            })
+            .collect()
+    }
+
+    fn json_rows(mut bytes: Vec<u8>) -> Result<Vec<Value<'static>>> {
+        let value = tremor_value::parse_to_value(&mut bytes)
+            .map_err(|e| Error::from(e.to_string()))?
+            .into_static();
+        match value {
+            Value::Array(rows) => Ok(rows),
+            _ => Err("expected an array of records".into()),
+        }
+    }
+
+    fn from_rows(rows: Vec<Value<'static>>, table_key: &str) -> Result<Self> {
+        let mut table = HashMap::with_capacity(rows.len());
+        for (i, row) in rows.into_iter().enumerate() {
+            let key = row
+                .get(table_key)
+                .map(key_string)
+                .ok_or_else(|| Error::from(format!("row {i} has no `{table_key}`")))?;
+            table.insert(key, row);
+        }
+        Ok(Self(table))
+    }
+}
+
+#[derive(Debug)]
+struct Enrich {
+    key: Vec<String>,
+    target: String,
+    table: Table,
+}
+
+impl Enrich {
+    fn lookup(&self, value: &Value) -> Option<&Value<'static>> {
+        self.key
+            .iter()
+            .try_fold(value, |v, key| v.get(key.as_str()))
+            .and_then(|key| self.table.0.get(&key_string(key)))
+    }
+}
+
+op!(EnrichFactory(_uid, node) {
+    if let Some(map) = &node.config {
+        let config: Config = Config::new(map)?;
+        let key: Vec<String> = config.key.split('.').map(ToString::to_string).collect();
+        let table_key = config
+            .table_key
+            .as_deref()
+            .or_else(|| key.last().map(String::as_str))
+            .unwrap_or_default();
+        let table = Table::load(&config.path, table_key)?;
+        Ok(Box::new(Enrich {
+            key,
+            target: config.target,
+            table,
+        }))
+    } else {
+        Err(ErrorKind::MissingOpConfig(node.id.clone()).into())
+    }
+});
+
+impl Operator for Enrich {
+    fn on_event(
+        &mut self,
+        _uid: OperatorId,
+        _port: &str,
+        _state: &mut Value<'static>,
+        mut event: Event,
+    ) -> Result<EventAndInsights> {
+        let row = self.lookup(event.data.suffix().value()).cloned();
+        if let Some(row) = row {
+            event.data.rent_mut(|data| {
+                let (value, _) = data.parts_mut();
+                if let Some(value) = value.as_object_mut() {
+                    value.insert(self.target.clone().into(), row);
+                }
+            });
+        }
+        Ok(event.into())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn enrich(table: Table) -> Enrich {
+        Enrich {
+            key: vec!["country".to_string(), "code".to_string()],
+            target: "enrichment".to_string(),
+            table,
+        }
+    }
+
+    #[test]
+    fn csv_table() -> Result<()> {
+        let csv = b"code,name\nDE,Germany\nFR,France\n";
+        let op = enrich(Table::from_rows(Table::csv_rows(csv)?, "code")?);
+        assert_eq!(
+            Some(&literal!({"code": "FR", "name": "France"})),
+            op.lookup(&literal!({"country": {"code": "FR"}}))
+        );
+        assert_eq!(None, op.lookup(&literal!({"country": {"code": "SE"}})));
+        assert_eq!(None, op.lookup(&literal!({"country": "FR"})));
+        Ok(())
+    }
+
+    #[test]
+    fn json_table() -> Result<()> {
+        let json = br#"[{"code": 1, "name": "snot"}, {"code": "2", "name": "badger"}]"#.to_vec();
+        let op = enrich(Table::from_rows(Table::json_rows(json)?, "code")?);
+        // keys are compared by their string representation
+        assert_eq!(
+            Some(&literal!({"code": "2", "name": "badger"})),
+            op.lookup(&literal!({"country": {"code": 2}}))
+        );
+        assert_eq!(
+            Some(&literal!({"code": 1, "name": "snot"})),
+            op.lookup(&literal!({"country": {"code": "1"}}))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn bad_tables() {
+        assert!(Table::json_rows(br#"{"code": 1}"#.to_vec()).is_err());
+        let rows = vec![literal!({"name": "snot"})];
+        assert!(Table::from_rows(rows, "code").is_err());
+        assert!(Table::load("/does/not/exist.csv", "code").is_err());
+    }
+}
@@ -0,0 +1,190 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! # Geo IP enrichment
+//!
+//! Looks up the IP address at a configured field of the event in a
+//! [MaxMind DB](https://maxmind.github.io/MaxMind-DB/) (e.g. GeoIP2 or GeoLite2 City)
+//! and attaches `{country, city, lat, lon}` to the event.
+//! Events without an IP address or with an address that is not in the database pass unchanged.
+//!
+//! ## Configuration
+//!
+//! See [Config](struct.Config.html) for details.
+
+use crate::errors::{Error, ErrorKind, Result};
+use crate::op::prelude::*;
+use maxminddb::{geoip2, MaxMindDBError, Reader};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::net::IpAddr;
+use tremor_script::prelude::*;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Path to the MaxMind database file, it is loaded once when the operator is created.
+    pub database: String,
+    /// Dot separated path to the field holding the IP address. Paths starting with `$` refer
+    /// to the event metadata, e.g. `$tcp_server.peer.host`.
+    pub field: String,
+    /// Field of the event the location is attached to, defaults to `geo`.
+    #[serde(default = "default_target")]
+    pub target: String,
+}
+
+impl ConfigImpl for Config {}
+
+fn default_target() -> String {
+    "geo".to_string()
+}
+
+#[derive(Debug)]
+struct GeoIp {
+    config: Config,
+    database: Database,
+    in_meta: bool,
+    path: Vec<String>,
+}
+
+op!(GeoIpFactory(_uid, node) {
+    if let Some(map) = &node.config {
+        let config: Config = Config::new(map)?;
+        let database = Database::open(&config.database)?;
+        let (in_meta, field) = config
+            .field
+            .strip_prefix('$')
+            .map_or((false, config.field.as_str()), |field| (true, field));
+        let path = field.split('.').map(ToString::to_string).collect();
+        Ok(Box::new(GeoIp {
+            config,
+            database,
+            in_meta,
+            path,
+        }))
+    } else {
+        Err(ErrorKind::MissingOpConfig(node.id.clone()).into())
+    }
+});
+
+impl Operator for GeoIp {
+    fn on_event(
+        &mut self,
+        _uid: OperatorId,
+        _port: &str,
+        _state: &mut Value<'static>,
+        mut event: Event,
+    ) -> Result<EventAndInsights> {
+        let ip = event.data.rent(|data| {
+            let (value, meta) = data.parts();
+            let root = if self.in_meta { meta } else { value };
+            self.path
+                .iter()
+                .try_fold(root, |v, key| v.get(key.as_str()))
+                .and_then(ValueAccess::as_str)
+                .and_then(|s| s.parse::<IpAddr>().ok())
+        });
+        let location = match ip {
+            Some(ip) => self.database.lookup(ip)?,
+            None => None,
+        };
+        if let Some(location) = location {
+            event.data.rent_mut(|data| {
+                let (value, _) = data.parts_mut();
+                if let Some(value) = value.as_object_mut() {
+                    value.insert(self.config.target.clone().into(), location);
+                }
+            });
+        }
+        Ok(event.into())
+    }
+}
+
+/// The english name in the `names` of a GeoIP2 record
+fn english(names: Option<&BTreeMap<&str, &str>>) -> Option<String> {
+    names
+        .and_then(|names| names.get("en"))
+        .map(ToString::to_string)
+}
+
+fn location(city: &geoip2::City) -> Value<'static> {
+    let location = city.location.as_ref();
+    literal!({
+        "country": english(city.country.as_ref().and_then(|country| country.names.as_ref())),
+        "city": english(city.city.as_ref().and_then(|city| city.names.as_ref())),
+        "lat": location.and_then(|l| l.latitude),
+        "lon": location.and_then(|l| l.longitude),
+    })
+}
+
+/// A MaxMind database, kept in memory
+struct Database {
+    reader: Reader<Vec<u8>>,
+}
+
+impl fmt::Debug for Database {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Database")
+            .field("database_type", &self.reader.metadata.database_type)
+            .field("node_count", &self.reader.metadata.node_count)
+            .field("ip_version", &self.reader.metadata.ip_version)
+            .finish()
+    }
+}
+
+impl Database {
+    fn open(path: &str) -> Result<Self> {
+        let reader = Reader::open_readfile(path)
+            .map_err(|e| Error::from(format!("Unable to read MaxMind database {path}: {e}")))?;
+        Ok(Self { reader })
+    }
+
+    /// Looks up the location of an IP address, `None` if the address is not in the database
+    fn lookup(&self, ip: IpAddr) -> Result<Option<Value<'static>>> {
+        match self.reader.lookup::<geoip2::City>(ip) {
+            Ok(city) => Ok(Some(location(&city))),
+            Err(MaxMindDBError::AddressNotFoundError(_)) => Ok(None),
+            Err(e) => Err(format!("Invalid MaxMind database: {e}").into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn invalid_database() {
+        assert!(Reader::from_source(b"snot".to_vec()).is_err());
+        assert!(Database::open("/does/not/exist.mmdb").is_err());
+    }
+
+    #[test]
+    fn lookup() -> Result<()> {
+        let ip = |s: &str| -> IpAddr { s.parse().expect("invalid ip") };
+        let database = Database::open("../tests/queries/geoip/geoip.mmdb")?;
+        let location = database
+            .lookup(ip("81.2.69.142"))?
+            .expect("81.2.69.142 is in the database");
+        assert_eq!(
+            literal!({"country": "United Kingdom", "city": "London", "lat": 51.5142, "lon": -0.0931}),
+            location
+        );
+        // IPv4 mapped into IPv6
+        assert!(database.lookup(ip("::81.2.69.142"))?.is_some());
+        assert_eq!(None, database.lookup(ip("10.0.0.1"))?);
+        assert_eq!(None, database.lookup(ip("2001:db8::1"))?);
+        Ok(())
+    }
+}
@@ -0,0 +1,294 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! # Redaction
+//!
+//! Redacts sensitive fields of events, e.g. personally identifiable information, before they
+//! leave the pipeline. Each configured field is either dropped, replaced by a salted hash of its
+//! value or masked except for its last characters. Fields not present in an event are ignored,
+//! all other fields pass unchanged. Batched events have each of their events redacted.
+//!
+//! ## Configuration
+//!
+//! See [Config](struct.Config.html) for details.
+//!
+//! ## Outputs
+//!
+//! The redacted events are routed to `out`.
+
+use crate::errors::{ErrorKind, Result};
+use crate::op::prelude::*;
+use sha2::{Digest, Sha256};
+use tremor_script::prelude::*;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// The fields to redact.
+    pub fields: Vec<Field>,
+    /// Salt prepended to values before hashing them with the `hash` strategy, required for it.
+    #[serde(default)]
+    pub salt: Option<String>,
+}
+
+impl ConfigImpl for Config {}
+
+/// A field to redact
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Field {
+    /// Dot separated path to the field, e.g. `user.email`.
+    pub path: String,
+    /// How the field is redacted.
+    pub strategy: Strategy,
+    /// Number of trailing characters left visible with the `mask` strategy.
+    #[serde(default)]
+    pub keep_last: usize,
+}
+
+/// How a field is redacted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Strategy {
+    /// Removes the field.
+    Drop,
+    /// Replaces the value by the hex encoded SHA-256 hash of the salt followed by the value.
+    Hash,
+    /// Replaces all but the last `keep_last` characters of the value with `*`.
+    Mask,
+}
+
+#[derive(Debug, Clone)]
+struct Redact {
+    fields: Vec<(Vec<String>, Strategy, usize)>,
+    salt: String,
+}
+
+impl TryFrom<Config> for Redact {
+    type Error = crate::errors::Error;
+
+    fn try_from(config: Config) -> Result<Self> {
+        let fields = config
+            .fields
+            .into_iter()
+            .map(|field| {
+                let path: Vec<String> = field.path.split('.').map(ToString::to_string).collect();
+                if path.iter().any(String::is_empty) {
+                    Err(
+                        ErrorKind::BadOpConfig(format!("invalid field path `{}`", field.path))
+                            .into(),
+                    )
+                } else {
+                    Ok((path, field.strategy, field.keep_last))
+                }
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let salt = config.salt.filter(|salt| !salt.is_empty());
+        // unsalted hashes of guessable values like emails are easily reversed
+        if salt.is_none() && fields.iter().any(|(_, s, _)| *s == Strategy::Hash) {
+            return Err(ErrorKind::BadOpConfig(
+                "the `hash` strategy requires a non-empty `salt`".to_string(),
+            )
+            .into());
+        }
+        Ok(Self {
+            fields,
+            salt: salt.unwrap_or_default(),
+        })
+    }
+}
+
+/// the string representation of a value, strings are taken as is, other values are encoded as JSON
+fn as_string(value: &Value) -> String {
+    value
+        .as_str()
+        .map_or_else(|| value.encode(), ToString::to_string)
+}
+
+fn hash(salt: &str, value: &Value) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(salt.as_bytes());
+    hasher.update(as_string(value).as_bytes());
+    format!("{:x}", hasher.finalize())
+}
+
+fn mask(value: &Value, keep_last: usize) -> String {
+    let value = as_string(value);
+    let len = value.chars().count();
+    value
+        .chars()
+        .enumerate()
+        .map(|(i, c)| if i + keep_last < len { '*' } else { c })
+        .collect()
+}
+
+impl Redact {
+    fn redact(&self, value: &mut Value) {
+        for (path, strategy, keep_last) in &self.fields {
+            if let Some((field, parents)) = path.split_last() {
+                let parent = parents
+                    .iter()
+                    .try_fold(&mut *value, |v, key| v.get_mut(key.as_str()));
+                let parent = if let Some(parent) = parent.and_then(Value::as_object_mut) {
+                    parent
+                } else {
+                    continue;
+                };
+                match strategy {
+                    Strategy::Drop => {
+                        parent.remove(field.as_str());
+                    }
+                    Strategy::Hash | Strategy::Mask => {
+                        if let Some(v) = parent.get_mut(field.as_str()) {
+                            let redacted = if *strategy == Strategy::Hash {
+                                hash(&self.salt, v)
+                            } else {
+                                mask(v, *keep_last)
+                            };
+                            *v = Value::from(redacted);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+op!(RedactFactory(_uid, node) {
+    if let Some(map) = &node.config {
+        let config: Config = Config::new(map)?;
+        Ok(Box::new(Redact::try_from(config)?))
+    } else {
+        Err(ErrorKind::MissingOpConfig(node.id.clone()).into())
+    }
+});
+
+impl Operator for Redact {
+    fn on_event(
+        &mut self,
+        _uid: OperatorId,
+        _port: &str,
+        _state: &mut Value<'static>,
+        mut event: Event,
+    ) -> Result<EventAndInsights> {
+        let is_batch = event.is_batch;
+        event.data.rent_mut(|data| {
+            let (value, _) = data.parts_mut();
+            if is_batch {
+                if let Some(events) = value.as_array_mut() {
+                    for event in events {
+                        if let Some(value) = event.get_mut("data").and_then(|d| d.get_mut("value"))
+                        {
+                            self.redact(value);
+                        }
+                    }
+                }
+            } else {
+                self.redact(value);
+            }
+        });
+        Ok(event.into())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn redact(fields: Value<'static>) -> Result<Redact> {
+        let config = Config::new(&literal!({ "fields": fields, "salt": "snot" }))?;
+        Redact::try_from(config)
+    }
+
+    #[test]
+    fn strategies() -> Result<()> {
+        let op = redact(literal!([
+            {"path": "user.email", "strategy": "hash"},
+            {"path": "user.password", "strategy": "drop"},
+            {"path": "card", "strategy": "mask", "keep_last": 4},
+            {"path": "pin", "strategy": "mask"},
+            {"path": "missing.field", "strategy": "drop"}
+        ]))?;
+        let mut value = literal!({
+            "user": {"name": "badger", "email": "badger@example.com", "password": "cake"},
+            "card": "1234567812345678",
+            "pin": 1234
+        });
+        op.redact(&mut value);
+        assert_eq!(
+            literal!({
+                "user": {
+                    "name": "badger",
+                    "email": hash("snot", &Value::from("badger@example.com"))
+                },
+                "card": "************5678",
+                "pin": "****"
+            }),
+            value
+        );
+        assert_eq!(64, value.get("user").get_str("email").map_or(0, str::len));
+        Ok(())
+    }
+
+    #[test]
+    fn salted_hash() {
+        let value = Value::from("badger@example.com");
+        assert_ne!(hash("snot", &value), hash("badger", &value));
+        assert_eq!(hash("snot", &value), hash("snot", &value));
+    }
+
+    #[test]
+    fn bad_config() {
+        assert!(redact(literal!([{"path": "user..email", "strategy": "drop"}])).is_err());
+        assert!(redact(literal!([{"path": "user", "strategy": "shred"}])).is_err());
+
+        let fields = literal!([{"path": "user.email", "strategy": "hash"}]);
+        let config = Config::new(&literal!({ "fields": fields.clone() }));
+        assert!(config.and_then(Redact::try_from).is_err());
+        let config = Config::new(&literal!({ "fields": fields, "salt": "" }));
+        assert!(config.and_then(Redact::try_from).is_err());
+        // other strategies don't need a salt
+        let fields = literal!([{"path": "user.email", "strategy": "mask"}]);
+        assert!(Config::new(&literal!({ "fields": fields }))
+            .and_then(Redact::try_from)
+            .is_ok());
+    }
+
+    #[test]
+    fn batched_events() -> Result<()> {
+        let mut op = redact(literal!([{"path": "password", "strategy": "drop"}]))?;
+        let events = literal!([
+            {"data": {"value": {"name": "snot", "password": "cake"}, "meta": {}}},
+            {"data": {"value": {"name": "badger", "password": "cake"}, "meta": {}}}
+        ]);
+        let batch = Event {
+            data: (events, Value::object()).into(),
+            is_batch: true,
+            ..Event::default()
+        };
+        let mut state = Value::null();
+        let out = op.on_event(OperatorId::new(0), "in", &mut state, batch)?;
+        assert_eq!(1, out.events.len());
+        let values: Vec<_> = out.events[0]
+            .1
+            .value_meta_iter()
+            .map(|(value, _)| value.clone_static())
+            .collect();
+        assert_eq!(
+            vec![literal!({"name": "snot"}), literal!({"name": "badger"})],
+            values
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,160 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! # Sampling
+//!
+//! Keeps a sample of the events, either deterministically one of every `every_n` events,
+//! or each event with the probability `ratio`. Kept events pass, the others are dropped.
+//!
+//! ## Configuration
+//!
+//! See [Config](struct.Config.html) for details.
+//!
+//! ## Outputs
+//!
+//! The `dropped` output is used to route the events not kept.
+
+use crate::errors::{ErrorKind, Result};
+use crate::op::prelude::*;
+use beef::Cow;
+use rand::rngs::SmallRng;
+use rand::{Rng, SeedableRng};
+use tremor_script::prelude::*;
+
+const DROPPED: Cow<'static, str> = Cow::const_str("dropped");
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Keep one of every `every_n` events, starting with the first. Can't be combined with `ratio`.
+    pub every_n: Option<u64>,
+    /// Probability with which each event is kept, between `0.0` and `1.0`. Can't be combined with `every_n`.
+    pub ratio: Option<f64>,
+    /// Seed of the random number generator for `ratio`, for a reproducible sample.
+    pub seed: Option<u64>,
+}
+
+impl ConfigImpl for Config {}
+
+#[derive(Debug, Clone)]
+enum Sample {
+    EveryN {
+        n: u64,
+        /// events seen since the last kept one
+        seen: u64,
+    },
+    Ratio {
+        ratio: f64,
+        rng: SmallRng,
+    },
+}
+
+impl TryFrom<Config> for Sample {
+    type Error = crate::errors::Error;
+
+    fn try_from(config: Config) -> Result<Self> {
+        match (config.every_n, config.ratio) {
+            (Some(n), None) if n > 0 => Ok(Self::EveryN { n, seen: 0 }),
+            (None, Some(ratio)) if (0.0..=1.0).contains(&ratio) => Ok(Self::Ratio {
+                ratio,
+                rng: config
+                    .seed
+                    .map_or_else(SmallRng::from_entropy, SmallRng::seed_from_u64),
+            }),
+            (Some(_), None) => {
+                Err(ErrorKind::BadOpConfig("`every_n` must be greater than 0".to_string()).into())
+            }
+            (None, Some(_)) => Err(ErrorKind::BadOpConfig(
+                "`ratio` must be between 0.0 and 1.0".to_string(),
+            )
+            .into()),
+            _ => Err(ErrorKind::BadOpConfig(
+                "exactly one of `every_n` or `ratio` is required".to_string(),
+            )
+            .into()),
+        }
+    }
+}
+
+impl Sample {
+    fn keep(&mut self) -> bool {
+        match self {
+            Self::EveryN { n, seen } => {
+                let keep = *seen == 0;
+                *seen = (*seen + 1) % *n;
+                keep
+            }
+            Self::Ratio { ratio, rng } => rng.gen::<f64>() < *ratio,
+        }
+    }
+}
+
+op!(SampleFactory(_uid, node) {
+    if let Some(map) = &node.config {
+        let config: Config = Config::new(map)?;
+        Ok(Box::new(Sample::try_from(config)?))
+    } else {
+        Err(ErrorKind::MissingOpConfig(node.id.clone()).into())
+    }
+});
+
+impl Operator for Sample {
+    fn on_event(
+        &mut self,
+        _uid: OperatorId,
+        _port: &str,
+        _state: &mut Value<'static>,
+        event: Event,
+    ) -> Result<EventAndInsights> {
+        let output = if self.keep() { OUT } else { DROPPED };
+        Ok(vec![(output, event)].into())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn sample(config: Config, events: usize) -> Result<Vec<bool>> {
+        let mut op = Sample::try_from(config)?;
+        Ok((0..events).map(|_| op.keep()).collect())
+    }
+
+    #[test]
+    fn ratio_is_reproducible() -> Result<()> {
+        let config = Config {
+            every_n: None,
+            ratio: Some(0.5),
+            seed: Some(42),
+        };
+        let kept = sample(config.clone(), 1_000)?;
+        assert_eq!(kept, sample(config, 1_000)?);
+        let count = kept.iter().filter(|keep| **keep).count();
+        assert!((400..600).contains(&count), "{count} kept");
+        Ok(())
+    }
+
+    #[test]
+    fn bad_config() {
+        let config = |every_n, ratio| Config {
+            every_n,
+            ratio,
+            seed: None,
+        };
+        assert!(Sample::try_from(config(None, None)).is_err());
+        assert!(Sample::try_from(config(Some(2), Some(0.5))).is_err());
+        assert!(Sample::try_from(config(Some(0), None)).is_err());
+        assert!(Sample::try_from(config(None, Some(1.5))).is_err());
+    }
+}
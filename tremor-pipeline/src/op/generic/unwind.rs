@@ -0,0 +1,227 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! # Array unwinding
+//!
+//! Flattens an array field of the event into one event per element. Each emitted event is a copy
+//! of the original with the array at `field` replaced by one of its elements, and carries the
+//! position of that element in its metadata as `$unwind.index`, next to the length of the array
+//! as `$unwind.length`. An empty array emits no events.
+//!
+//! Events whose `field` is missing or not an array pass unchanged, unless `pass_through` is
+//! disabled.
+//!
+//! ## Configuration
+//!
+//! See [Config](struct.Config.html) for details.
+//!
+//! ## Outputs
+//!
+//! The `dropped` output is used to route the events that are not unwound when `pass_through`
+//! is disabled.
+
+use crate::errors::{ErrorKind, Result};
+use crate::op::prelude::*;
+use beef::Cow;
+use tremor_script::prelude::*;
+
+const DROPPED: Cow<'static, str> = Cow::const_str("dropped");
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Dot separated path to the array field of the event, e.g. `order.items`.
+    pub field: String,
+    /// Whether events without an array at `field` pass unchanged, or are sent to `dropped`.
+    /// Defaults to `true`.
+    #[serde(default = "default_pass_through")]
+    pub pass_through: bool,
+}
+
+impl ConfigImpl for Config {}
+
+fn default_pass_through() -> bool {
+    true
+}
+
+#[derive(Debug)]
+struct Unwind {
+    /// the parents of the array field
+    parents: Vec<String>,
+    /// the name of the array field
+    field: String,
+    pass_through: bool,
+}
+
+impl Unwind {
+    fn parent<'v, 'event>(&self, value: &'v mut Value<'event>) -> Option<&'v mut Value<'event>> {
+        self.parents
+            .iter()
+            .try_fold(value, |v, key| v.get_mut(key.as_str()))
+    }
+
+    /// Removes the array from `value`, returning its elements
+    fn take_elements(&self, value: &mut Value) -> Option<Vec<Value<'static>>> {
+        let parent = self.parent(value)?.as_object_mut()?;
+        if !parent
+            .get(self.field.as_str())
+            .map_or(false, Value::is_array)
+        {
+            return None;
+        }
+        match parent.remove(self.field.as_str()) {
+            Some(Value::Array(elements)) => {
+                Some(elements.into_iter().map(Value::into_static).collect())
+            }
+            _ => None,
+        }
+    }
+
+    /// Puts `element` where the array was, and its position into the metadata
+    fn put_element(&self, event: &mut Event, element: Value<'static>, index: usize, length: usize) {
+        event.data.rent_mut(|data| {
+            let (value, meta) = data.parts_mut();
+            if let Some(parent) = self.parent(value).and_then(Value::as_object_mut) {
+                parent.insert(self.field.clone().into(), element);
+            }
+            let position = literal!({
+                "index": index,
+                "length": length,
+            });
+            if let Some(meta) = meta.as_object_mut() {
+                meta.insert("unwind".into(), position);
+            } else {
+                *meta = literal!({ "unwind": position });
+            }
+        });
+    }
+}
+
+op!(UnwindFactory(_uid, node) {
+    if let Some(map) = &node.config {
+        let config: Config = Config::new(map)?;
+        let mut parents: Vec<String> = config.field.split('.').map(ToString::to_string).collect();
+        let field = parents.pop().unwrap_or_default();
+        Ok(Box::new(Unwind {
+            parents,
+            field,
+            pass_through: config.pass_through,
+        }))
+    } else {
+        Err(ErrorKind::MissingOpConfig(node.id.clone()).into())
+    }
+});
+
+impl Operator for Unwind {
+    fn on_event(
+        &mut self,
+        _uid: OperatorId,
+        _port: &str,
+        _state: &mut Value<'static>,
+        mut event: Event,
+    ) -> Result<EventAndInsights> {
+        let elements = event.data.rent_mut(|data| {
+            let (value, _) = data.parts_mut();
+            self.take_elements(value)
+        });
+        if let Some(elements) = elements {
+            let length = elements.len();
+            let events = elements
+                .into_iter()
+                .enumerate()
+                .map(|(index, element)| {
+                    let mut event = event.clone();
+                    self.put_element(&mut event, element, index, length);
+                    (OUT, event)
+                })
+                .collect::<Vec<_>>();
+            Ok(events.into())
+        } else if self.pass_through {
+            Ok(event.into())
+        } else {
+            Ok(vec![(DROPPED, event)].into())
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tremor_common::ids::Id;
+
+    fn unwind(field: &str, pass_through: bool) -> Unwind {
+        let mut parents: Vec<String> = field.split('.').map(ToString::to_string).collect();
+        let field = parents.pop().unwrap_or_default();
+        Unwind {
+            parents,
+            field,
+            pass_through,
+        }
+    }
+
+    fn run(op: &mut Unwind, value: Value<'static>) -> Result<EventAndInsights> {
+        let event = Event {
+            data: (value, literal!({"snot": "badger"})).into(),
+            ..Event::default()
+        };
+        let mut state = Value::null();
+        op.on_event(OperatorId::new(0), "in", &mut state, event)
+    }
+
+    #[test]
+    fn unwinds_nested_arrays() -> Result<()> {
+        let mut op = unwind("order.items", true);
+        let out = run(
+            &mut op,
+            literal!({"order": {"id": 1, "items": ["snot", "badger", "cake"]}}),
+        )?;
+        assert_eq!(3, out.events.len());
+        for (index, (port, event)) in out.events.iter().enumerate() {
+            assert_eq!(OUT, *port);
+            let (value, meta) = event.data.parts();
+            let order = value.get("order").ok_or("missing order")?;
+            let unwind = meta.get("unwind").ok_or("missing unwind meta")?;
+            assert_eq!(
+                Some(["snot", "badger", "cake"][index]),
+                order.get_str("items")
+            );
+            assert_eq!(Some(1), order.get_u64("id"));
+            assert_eq!(Some(index as u64), unwind.get_u64("index"));
+            assert_eq!(Some(3), unwind.get_u64("length"));
+            assert_eq!(Some("badger"), meta.get_str("snot"));
+        }
+
+        let out = run(&mut op, literal!({"order": {"items": []}}))?;
+        assert!(out.events.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn passes_or_drops_other_events() -> Result<()> {
+        let mut op = unwind("items", true);
+        let out = run(&mut op, literal!({"items": "snot"}))?;
+        assert_eq!(1, out.events.len());
+        assert_eq!(OUT, out.events[0].0);
+        assert_eq!(
+            Some("snot"),
+            out.events[0].1.data.suffix().value().get_str("items")
+        );
+
+        let mut op = unwind("items", false);
+        let out = run(&mut op, literal!({"badger": [1, 2]}))?;
+        assert_eq!(1, out.events.len());
+        assert_eq!(DROPPED, out.events[0].0);
+        Ok(())
+    }
+}
@@ -13,10 +13,12 @@
 // limitations under the License.
 
 pub mod backpressure;
+pub mod loadshed;
 pub mod percentile;
 pub mod rr;
 
 pub use backpressure::BackpressureFactory;
+pub use loadshed::LoadShedFactory;
 pub use percentile::PercentileFactory;
 pub use rr::RoundRobinFactory;
 
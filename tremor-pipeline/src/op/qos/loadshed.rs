@@ -0,0 +1,205 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! # Circuit breaker driven load shedding
+//!
+//! While a downstream circuit breaker is triggered, events with a priority
+//! below the configured threshold are shed instead of backpressuring the source.
+//! Once the circuit breaker is restored, all events pass again.
+//!
+//! ## Configuration
+//!
+//! See [Config](struct.Config.html) for details.
+//!
+//! ## Outputs
+//!
+//! The `shed` output is used to route events that were shed.
+
+use crate::errors::{ErrorKind, Result};
+use crate::op::prelude::*;
+use beef::Cow;
+use tremor_script::prelude::*;
+
+const SHED: Cow<'static, str> = Cow::const_str("shed");
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Dot separated path to the numeric priority field of the event, e.g. `request.priority`.
+    pub priority: String,
+    /// Events with a priority below this threshold are shed while the circuit breaker is triggered.
+    /// Events without a numeric priority are considered below the threshold.
+    pub threshold: f64,
+}
+
+impl ConfigImpl for Config {}
+
+#[derive(Debug, Clone)]
+struct LoadShed {
+    config: Config,
+    priority: Vec<String>,
+    shedding: bool,
+}
+
+impl From<Config> for LoadShed {
+    fn from(config: Config) -> Self {
+        let priority = config
+            .priority
+            .split('.')
+            .map(ToString::to_string)
+            .collect();
+        Self {
+            config,
+            priority,
+            shedding: false,
+        }
+    }
+}
+
+impl LoadShed {
+    fn is_low_priority(&self, event: &Event) -> bool {
+        let value = event.data.suffix().value();
+        self.priority
+            .iter()
+            .try_fold(value, |v, key| v.get(key.as_str()))
+            .and_then(|v| v.cast_f64())
+            .map_or(true, |priority| priority < self.config.threshold)
+    }
+}
+
+op!(LoadShedFactory(_uid, node) {
+    if let Some(map) = &node.config {
+        let config: Config = Config::new(map)?;
+        Ok(Box::new(LoadShed::from(config)))
+    } else {
+        Err(ErrorKind::MissingOpConfig(node.id.clone()).into())
+    }
+});
+
+impl Operator for LoadShed {
+    fn on_event(
+        &mut self,
+        uid: OperatorId,
+        _port: &str,
+        _state: &mut Value<'static>,
+        mut event: Event,
+    ) -> Result<EventAndInsights> {
+        // mark the event so we can tell the circuit breaker events it caused apart
+        event.op_meta.insert(uid, OwnedValue::null());
+        let output = if self.shedding && self.is_low_priority(&event) {
+            SHED
+        } else {
+            OUT
+        };
+        Ok(vec![(output, event)].into())
+    }
+
+    fn handles_contraflow(&self) -> bool {
+        true
+    }
+
+    fn on_contraflow(&mut self, uid: OperatorId, insight: &mut Event) {
+        // If the related event never touched this operator we don't take
+        // action
+        if !insight.op_meta.contains_key(uid) {
+            return;
+        }
+        match insight.cb {
+            CbAction::Trigger => self.shedding = true,
+            CbAction::Restore => self.shedding = false,
+            _ => return,
+        }
+        // we shed load instead of backpressuring the source, so the circuit breaker
+        // must not propagate further upstream
+        insight.cb = CbAction::None;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use tremor_common::ids::Id;
+
+    use super::*;
+
+    fn event(id: u64, priority: Value<'static>) -> Event {
+        Event {
+            id: (1, 1, id).into(),
+            ingest_ns: id,
+            data: (
+                literal!({ "req": { "priority": priority } }),
+                Value::object(),
+            )
+                .into(),
+            ..Event::default()
+        }
+    }
+
+    #[test]
+    fn shed_while_triggered() -> Result<()> {
+        let uid = OperatorId::new(0);
+        let mut op: LoadShed = Config {
+            priority: "req.priority".to_string(),
+            threshold: 5.0,
+        }
+        .into();
+        let mut state = Value::null();
+
+        // all events pass while the circuit breaker is not triggered
+        let mut r = op
+            .on_event(uid, "in", &mut state, event(1, 1.into()))?
+            .events;
+        assert_eq!(r.len(), 1);
+        let (out, event1) = r.pop().expect("no results");
+        assert_eq!("out", out);
+        assert!(event1.op_meta.contains_key(uid));
+
+        // a trigger for events that did not pass this operator is ignored
+        let mut insight = Event::cb_trigger(2);
+        op.on_contraflow(uid, &mut insight);
+        assert_eq!(CbAction::Trigger, insight.cb);
+        assert!(!op.shedding);
+
+        let mut insight = Event::cb_close(2, event1.op_meta.clone());
+        op.on_contraflow(uid, &mut insight);
+        assert_eq!(CbAction::None, insight.cb);
+        assert!(op.shedding);
+
+        let outputs: Vec<_> = [
+            event(3, 1.into()),
+            event(4, 5.into()),
+            event(5, 9.5.into()),
+            event(6, "high".into()),
+        ]
+        .into_iter()
+        .map(|e| op.on_event(uid, "in", &mut state, e))
+        .collect::<Result<Vec<_>>>()?
+        .into_iter()
+        .flat_map(|r| r.events)
+        .map(|(port, _)| port.to_string())
+        .collect();
+        assert_eq!(vec!["shed", "out", "out", "shed"], outputs);
+
+        let mut insight = Event::cb_open(7, event1.op_meta);
+        op.on_contraflow(uid, &mut insight);
+        assert_eq!(CbAction::None, insight.cb);
+        assert!(!op.shedding);
+
+        let mut r = op
+            .on_event(uid, "in", &mut state, event(8, 1.into()))?
+            .events;
+        let (out, _) = r.pop().expect("no results");
+        assert_eq!("out", out);
+        Ok(())
+    }
+}
@@ -912,6 +912,39 @@ fn tumbling_window_on_time_emit_empty_windows() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn tumbling_window_on_time_timezone() -> Result<()> {
+    const S: u64 = 1_000_000_000;
+    let emit = Actions {
+        include: false,
+        emit: true,
+    };
+    // daily windows, the 2022-03-27 in Europe/Berlin only has 23 hours
+    let mut window =
+        window::TumblingOnTime::from_stmt(86_400 * S, window::Impl::DEFAULT_MAX_GROUPS, None)
+            .with_timezone(chrono_tz::Europe::Berlin);
+    // 2022-03-27T00:30+01:00
+    assert_eq!(Actions::all_false(), window.on_tick(1_648_337_400 * S));
+    // 2022-03-27T23:30+02:00
+    assert_eq!(Actions::all_false(), window.on_tick(1_648_416_600 * S));
+    // 2022-03-28T00:30+02:00
+    assert_eq!(emit, window.on_tick(1_648_420_200 * S));
+
+    // hourly windows, 02:00 - 03:00 happens twice on 2022-10-30 in Europe/Berlin
+    let mut window =
+        window::TumblingOnTime::from_stmt(3_600 * S, window::Impl::DEFAULT_MAX_GROUPS, None)
+            .with_timezone(chrono_tz::Europe::Berlin);
+    // 2022-10-30T02:30+02:00
+    assert_eq!(Actions::all_false(), window.on_tick(1_667_089_800 * S));
+    // 2022-10-30T02:00+01:00
+    assert_eq!(emit, window.on_tick(1_667_091_600 * S));
+    // 2022-10-30T02:59+01:00
+    assert_eq!(Actions::all_false(), window.on_tick(1_667_095_140 * S));
+    // 2022-10-30T03:00+01:00
+    assert_eq!(emit, window.on_tick(1_667_095_200 * S));
+    Ok(())
+}
+
 #[test]
 fn no_window_emit() -> Result<()> {
     let mut window = window::No::default();
@@ -14,6 +14,8 @@
 
 use crate::{Event, EventId, EventIdGenerator, OpMeta};
 use beef::Cow;
+use chrono::{LocalResult, NaiveDateTime, Offset, TimeZone};
+use chrono_tz::Tz;
 use std::borrow::Cow as SCow;
 use tremor_common::stry;
 use tremor_script::{
@@ -482,7 +484,25 @@ pub struct TumblingOnTime {
     /// How long a window lasts (how many ns we accumulate)
     pub(crate) interval: u64,
     pub(crate) script: Option<WindowDefinition<'static>>,
+    /// Aligns windows to multiples of `interval` in this timezone instead of to the first event
+    pub(crate) timezone: Option<Tz>,
 }
+
+const NANOS_PER_SEC: i128 = 1_000_000_000;
+
+/// the date and time at `ns` nanoseconds since the epoch, truncated to seconds
+fn naive(ns: i128) -> NaiveDateTime {
+    // nanosecond timestamps fitting into an u64 are well within the range of `NaiveDateTime`
+    NaiveDateTime::from_timestamp(
+        i64::try_from(ns.div_euclid(NANOS_PER_SEC)).unwrap_or_default(),
+        0,
+    )
+}
+
+fn offset_ns<O: Offset>(offset: &O) -> i128 {
+    i128::from(offset.fix().local_minus_utc()) * NANOS_PER_SEC
+}
+
 impl TumblingOnTime {
     pub(crate) fn reset(&mut self) {
         self.next_window = None;
@@ -499,17 +519,60 @@ impl TumblingOnTime {
             max_groups,
             interval,
             script,
+            timezone: None,
         }
     }
 
+    /// Aligns the windows to multiples of `interval` in the local time of `timezone`
+    #[must_use]
+    pub fn with_timezone(mut self, timezone: Tz) -> Self {
+        self.timezone = Some(timezone);
+        self
+    }
+
+    /// The end of the window containing `time`
+    fn window_end(&self, time: u64) -> u64 {
+        self.timezone.map_or(time + self.interval, |timezone| {
+            Self::local_window_end(timezone, time, self.interval)
+        })
+    }
+
+    /// The end of the window containing `time`, for windows aligned to multiples of `interval` in the local time of `timezone`.
+    /// Windows spanning a daylight saving time change are shorter or longer accordingly.
+    fn local_window_end(timezone: Tz, time: u64, interval: u64) -> u64 {
+        let time = i128::from(time);
+        let interval = i128::from(interval.max(1));
+        let current = offset_ns(&timezone.offset_from_utc_datetime(&naive(time)));
+        // the earliest and the latest instant the clock shows `local`
+        let to_utc = |local: i128| match timezone.offset_from_local_datetime(&naive(local)) {
+            LocalResult::Single(offset) => (local - offset_ns(&offset), local - offset_ns(&offset)),
+            LocalResult::Ambiguous(a, b) => {
+                let (a, b) = (local - offset_ns(&a), local - offset_ns(&b));
+                (a.min(b), a.max(b))
+            }
+            // skipped by the clock, the window ends when it jumps
+            LocalResult::None => (local - current, local - current),
+        };
+        let start = (time + current).div_euclid(interval) * interval;
+        let (_, repeated_start) = to_utc(start);
+        let (end, _) = to_utc(start + interval);
+        // when the clock is turned back, the first pass through the repeated local time ends where it repeats
+        let end = if repeated_start > time {
+            repeated_start
+        } else {
+            end
+        };
+        u64::try_from(end.max(time + 1)).unwrap_or(u64::MAX)
+    }
+
     fn get_window_event(&mut self, time: u64) -> Actions {
         match self.next_window {
             None => {
-                self.next_window = Some(time + self.interval);
+                self.next_window = Some(self.window_end(time));
                 Actions::all_false()
             }
             Some(next_window) if next_window <= time => {
-                self.next_window = Some(time + self.interval);
+                self.next_window = Some(self.window_end(time));
                 Actions {
                     include: false, // event is beyond the current window, put it into the next
                     emit: true,     // only emit if we had any events in this interval
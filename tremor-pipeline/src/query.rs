@@ -113,9 +113,21 @@ pub(crate) fn window_defn_to_impl(d: &WindowDefinition<'static>) -> Result<windo
                 with.get(WindowDefinition::INTERVAL).and_then(Value::as_u64),
                 with.get(WindowDefinition::SIZE).and_then(Value::as_u64),
             ) {
-                (Some(interval), None) => Ok(window::Impl::from(TumblingOnTime::from_stmt(
-                    interval, max_groups, script,
-                ))),
+                (Some(interval), None) => {
+                    let mut window = TumblingOnTime::from_stmt(interval, max_groups, script);
+                    if let Some(timezone) = with.get_str(WindowDefinition::TIMEZONE) {
+                        let timezone = timezone.parse::<chrono_tz::Tz>().map_err(|e| {
+                            Error::from(format!(
+                                "Bad window configuration, invalid `timezone`: {e}"
+                            ))
+                        })?;
+                        window = window.with_timezone(timezone);
+                    }
+                    Ok(window::Impl::from(window))
+                }
+                (None, Some(_)) if with.get(WindowDefinition::TIMEZONE).is_some() => Err(
+                    Error::from("Bad window configuration, `timezone` requires `interval`."),
+                ),
                 (None, Some(size)) => Ok(window::Impl::from(TumblingOnNumber::from_stmt(
                     size, max_groups, script,
                 ))),
@@ -118,10 +118,19 @@ impl<'script> ConnectorDefinition<'script> {
     pub const METRICS_INTERVAL_S: &'static str = "metrics_interval_s";
     /// param name for reconnct configuration
     pub const RECONNECT: &'static str = "reconnect";
+    /// param name for `max_in_flight`
+    pub const MAX_IN_FLIGHT: &'static str = "max_in_flight";
+    /// param name for `in_flight_timeout_ms`
+    pub const IN_FLIGHT_TIMEOUT_MS: &'static str = "in_flight_timeout_ms";
+    /// param name for `instance_host`
+    pub const INSTANCE_HOST: &'static str = "instance_host";
 
-    const AVAILABLE_PARAMS: [&'static str; 6] = [
+    const AVAILABLE_PARAMS: [&'static str; 9] = [
         Self::CODEC,
         Self::CONFIG,
+        Self::IN_FLIGHT_TIMEOUT_MS,
+        Self::INSTANCE_HOST,
+        Self::MAX_IN_FLIGHT,
         Self::METRICS_INTERVAL_S,
         Self::POSTPROCESSORS,
         Self::PREPROCESSORS,
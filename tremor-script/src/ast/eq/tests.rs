@@ -57,6 +57,31 @@ fn test_event_path_eq() -> Result<()> {
     test_ast_eq(r#"event.foo ; event["foo"]"#, true)
 }
 
+#[test]
+fn test_const_inlined_eq() -> Result<()> {
+    // constants are evaluated when parsing, every reference is replaced with its value
+    test_ast_eq(
+        r#"
+    const single = 1;
+    event.foo + single;
+    event.foo + 1
+    "#,
+        true,
+    )
+}
+
+#[test]
+fn test_multi_use_const_inlined_eq() -> Result<()> {
+    test_ast_eq(
+        r#"
+    const multi = "snot";
+    event[multi] == multi;
+    event["snot"] == "snot"
+    "#,
+        true,
+    )
+}
+
 #[test]
 fn test_record_eq() -> Result<()> {
     test_ast_eq(
@@ -353,6 +353,8 @@ impl<'script> WindowDefinition<'script> {
     pub const INTERVAL: &'static str = "interval";
     /// `size` setting
     pub const SIZE: &'static str = "size";
+    /// `timezone` setting
+    pub const TIMEZONE: &'static str = "timezone";
 }
 
 /// A select statement
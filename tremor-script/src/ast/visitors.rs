@@ -27,9 +27,17 @@ pub mod query;
 
 pub use impls::args_rewriter::ArgsRewriter;
 pub use impls::const_folder::ConstFolder;
+pub use impls::exhaustiveness_lint::ExhaustivenessLint;
+pub use impls::formatter::Formatter;
 pub(crate) use impls::group_by_extractor::GroupByExprExtractor;
+pub use impls::group_key_collector::{GroupKey, GroupKeyCollector};
 pub(crate) use impls::is_const::IsConstFn;
+pub use impls::match_simplifier::MatchSimplifier;
+pub use impls::port_checker::PortChecker;
+pub use impls::shadow_lint::ShadowLint;
+pub use impls::string_templates::{StringTemplate, StringTemplateExtractor, TemplateSegment};
 pub(crate) use impls::target_event_ref::TargetEventRef;
+pub use impls::window_budget::WindowBudget;
 
 pub(crate) use deploy::Visitor as DeployVisitor;
 pub(crate) use expr::Visitor as ExprVisitor;
@@ -14,6 +14,14 @@
 
 pub(crate) mod args_rewriter;
 pub(crate) mod const_folder;
+pub(crate) mod exhaustiveness_lint;
+pub(crate) mod formatter;
 pub(crate) mod group_by_extractor;
+pub(crate) mod group_key_collector;
 pub(crate) mod is_const;
+pub(crate) mod match_simplifier;
+pub(crate) mod port_checker;
+pub(crate) mod shadow_lint;
+pub(crate) mod string_templates;
 pub(crate) mod target_event_ref;
+pub(crate) mod window_budget;
@@ -0,0 +1,130 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::super::prelude::*;
+use crate::ast::base_expr::Ranged;
+use crate::ast::Expression;
+use crate::pos::Span;
+
+/// Finds `match` expressions that fail at runtime if none of their cases match
+///
+/// A match is only considered exhaustive if it has a `default` case or an unguarded
+/// catch-all case like `case _`, as the patterns themselves are not analysed.
+#[derive(Default)]
+pub struct ExhaustivenessLint {
+    spans: Vec<Span>,
+}
+
+impl ExhaustivenessLint {
+    /// Returns the spans of all non exhaustive matches in `exprs`
+    ///
+    /// # Errors
+    /// if walking the expressions fails
+    pub fn lint(exprs: &mut Exprs) -> Result<Vec<Span>> {
+        let mut lint = Self::default();
+        for e in exprs {
+            ExprWalker::walk_expr(&mut lint, e)?;
+        }
+        Ok(lint.spans)
+    }
+
+    fn check<'script, Ex: Expression + 'script>(&mut self, m: &Match<'script, Ex>) {
+        if matches!(m.default, DefaultCase::None) && !m.patterns.iter().any(has_catch_all) {
+            self.spans.push(m.extent());
+        }
+    }
+}
+
+/// if the group matches anything that reaches it
+fn has_catch_all<'script, Ex: Expression + 'script>(group: &ClauseGroup<'script, Ex>) -> bool {
+    let is_catch_all =
+        |p: &PredicateClause<'script, Ex>| p.guard.is_none() && p.pattern.is_default();
+    match group {
+        _ if group.precondition().is_some() => false,
+        ClauseGroup::Single { pattern, .. } => is_catch_all(pattern),
+        ClauseGroup::Simple { patterns, .. } | ClauseGroup::SearchTree { rest: patterns, .. } => {
+            patterns.iter().any(is_catch_all)
+        }
+        ClauseGroup::Combined { groups, .. } => groups.iter().any(has_catch_all),
+    }
+}
+
+impl<'script> ImutExprWalker<'script> for ExhaustivenessLint {}
+impl<'script> ImutExprVisitor<'script> for ExhaustivenessLint {
+    fn visit_mmatch(&mut self, mmatch: &mut Match<'script, ImutExpr>) -> Result<VisitRes> {
+        self.check(mmatch);
+        Ok(VisitRes::Walk)
+    }
+}
+
+impl<'script> ExprWalker<'script> for ExhaustivenessLint {}
+impl<'script> ExprVisitor<'script> for ExhaustivenessLint {
+    fn visit_mmatch(&mut self, mmatch: &mut Match<'script, Expr<'script>>) -> Result<VisitRes> {
+        self.check(mmatch);
+        Ok(VisitRes::Walk)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::registry::registry;
+    use crate::script::Script;
+
+    fn lint(src: &str) -> Result<Vec<Span>> {
+        let reg = registry();
+        let mut script = Script::parse(src, &reg)?;
+        ExhaustivenessLint::lint(&mut script.script.exprs)
+    }
+
+    #[test]
+    fn flags_match_without_default() -> Result<()> {
+        let spans = lint(
+            r#"
+let a = match event.a of case 1 => "snot" case 2 => "badger" end;
+match event.b of case %{ present c } => let b = "badger" end
+"#,
+        )?;
+        assert_eq!(2, spans.len());
+        assert!(spans[0].start().line() < spans[1].start().line());
+        Ok(())
+    }
+
+    #[test]
+    fn ignores_match_with_default() -> Result<()> {
+        let spans = lint(
+            r#"
+let a = match event.a of case 1 => "snot" default => "badger" end;
+match event.b of case %{ present c } => let b = "snot" case _ => let b = "badger" end;
+match event.c of case x when x > 1 => "snot" case x = _ => "badger" end
+"#,
+        )?;
+        assert!(spans.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn flags_nested_match() -> Result<()> {
+        let spans = lint(
+            r#"
+match event.a of
+  case 1 => match event.b of case 2 => "snot" end
+  default => "badger"
+end
+"#,
+        )?;
+        assert_eq!(1, spans.len());
+        Ok(())
+    }
+}
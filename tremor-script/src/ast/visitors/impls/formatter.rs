@@ -0,0 +1,934 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::super::prelude::*;
+use crate::ast::base_expr::Ranged;
+use crate::ast::{AssignPattern, BooleanBinExpr, BooleanBinOpKind, Expression, UnaryOpKind};
+use crate::errors::error_generic;
+use crate::lexer::{ident_to_token, Token};
+use crate::pos::Span;
+use crate::Value;
+use simd_json::prelude::*;
+
+/// Expressions forming the body of a `case`, `default` or comprehension case
+trait Body<'script>: Expression + 'script {
+    /// separator between the expressions of a body
+    const SEP: &'static str;
+
+    fn format(&mut self, formatter: &mut Formatter) -> Result<()>;
+}
+
+impl<'script> Body<'script> for ImutExpr<'script> {
+    const SEP: &'static str = ",";
+
+    fn format(&mut self, formatter: &mut Formatter) -> Result<()> {
+        ImutExprWalker::walk_expr(formatter, self)
+    }
+}
+
+impl<'script> Body<'script> for Expr<'script> {
+    const SEP: &'static str = ";";
+
+    fn format(&mut self, formatter: &mut Formatter) -> Result<()> {
+        ExprWalker::walk_expr(formatter, self)
+    }
+}
+
+/// Renders expressions back into canonical tremor-script source
+///
+/// Records, `match`, `patch` and comprehension bodies are spread over indented lines,
+/// every other expression is kept on a single line. Operands that are not atomic are
+/// put in parentheses, so the rendered source does not depend on operator precedence.
+/// The rendered source parses into the same expressions again, but as it is rendered
+/// from the optimized AST, folded constants and grouped `case` clauses are not restored.
+#[derive(Default)]
+pub struct Formatter {
+    out: String,
+    indent: usize,
+    /// names of the locals bound by the assign patterns of the enclosing `case` clauses
+    assigns: Vec<(usize, String)>,
+}
+
+impl Formatter {
+    const INDENT: &'static str = "  ";
+
+    /// Renders an immutable expression
+    ///
+    /// # Errors
+    /// if the expression contains constructs that can not be rendered
+    pub fn format_imut(expr: &mut ImutExpr) -> Result<String> {
+        let mut formatter = Self::default();
+        ImutExprWalker::walk_expr(&mut formatter, expr)?;
+        Ok(formatter.out)
+    }
+
+    /// Renders the expressions of a script body, one per line
+    ///
+    /// # Errors
+    /// if the expressions contain constructs that can not be rendered
+    pub fn format_exprs(exprs: &mut Exprs) -> Result<String> {
+        let mut formatter = Self::default();
+        for (i, e) in exprs.iter_mut().enumerate() {
+            if i > 0 {
+                formatter.write(";");
+                formatter.newline();
+            }
+            ExprWalker::walk_expr(&mut formatter, e)?;
+        }
+        Ok(formatter.out)
+    }
+
+    fn write(&mut self, s: &str) {
+        self.out.push_str(s);
+    }
+
+    fn newline(&mut self) {
+        self.out.push('\n');
+        for _ in 0..self.indent {
+            self.out.push_str(Self::INDENT);
+        }
+    }
+
+    /// starts the `i`th item of an indented block, separated by `sep` from the previous one
+    fn item(&mut self, i: usize, sep: &str) {
+        if i > 0 {
+            self.write(sep);
+        }
+        self.newline();
+    }
+
+    fn unsupported<T, R: Ranged>(node: &R, what: &str) -> Result<T> {
+        error_generic(node, node, &format!("Formatting {what} is not supported"))
+    }
+
+    /// whether an identifier can be written as is, without backticks
+    fn is_plain_ident(id: &str) -> bool {
+        let mut chars = id.chars();
+        chars
+            .next()
+            .map_or(false, |c| c.is_ascii_alphabetic() || c == '_')
+            && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+            && id != "_"
+            && matches!(ident_to_token(id), Token::Ident(..))
+    }
+
+    fn ident(&mut self, id: &str) {
+        if Self::is_plain_ident(id) {
+            self.write(id);
+        } else {
+            self.write("`");
+            self.write(id);
+            self.write("`");
+        }
+    }
+
+    /// writes the content of a string literal, escaping interpolation
+    fn escaped(&mut self, s: &str) {
+        for c in s.chars() {
+            match c {
+                '"' => self.write("\\\""),
+                '\\' => self.write("\\\\"),
+                '#' => self.write("\\#"),
+                '\n' => self.write("\\n"),
+                '\r' => self.write("\\r"),
+                '\t' => self.write("\\t"),
+                '\u{8}' => self.write("\\b"),
+                '\u{c}' => self.write("\\f"),
+                c if c.is_control() => self.write(&format!("\\u{:04x}", u32::from(c))),
+                c => self.out.push(c),
+            }
+        }
+    }
+
+    fn quoted(&mut self, s: &str) {
+        self.write("\"");
+        self.escaped(s);
+        self.write("\"");
+    }
+
+    fn value(&mut self, value: &Value) {
+        match value {
+            Value::String(s) => self.quoted(s),
+            Value::Array(values) => {
+                self.write("[");
+                for (i, v) in values.iter().enumerate() {
+                    if i > 0 {
+                        self.write(", ");
+                    }
+                    self.value(v);
+                }
+                self.write("]");
+            }
+            Value::Object(o) if o.is_empty() => self.write("{}"),
+            Value::Object(o) => {
+                self.write("{");
+                self.indent += 1;
+                for (i, (k, v)) in o.iter().enumerate() {
+                    self.item(i, ",");
+                    self.quoted(k);
+                    self.write(": ");
+                    self.value(v);
+                }
+                self.indent -= 1;
+                self.newline();
+                self.write("}");
+            }
+            Value::Bytes(bytes) => {
+                let bytes: Vec<_> = bytes.iter().map(ToString::to_string).collect();
+                self.write("<<");
+                self.write(&bytes.join(", "));
+                self.write(">>");
+            }
+            Value::Static(_) => self.write(&value.encode()),
+        }
+    }
+
+    fn string(&mut self, s: &mut StringLit) -> Result<()> {
+        self.write("\"");
+        for element in &mut s.elements {
+            match element {
+                StrLitElement::Lit(l) => self.escaped(l),
+                StrLitElement::Expr(e) => {
+                    self.write("#{");
+                    ImutExprWalker::walk_expr(self, e)?;
+                    self.write("}");
+                }
+            }
+        }
+        self.write("\"");
+        Ok(())
+    }
+
+    /// writes an operand, in parentheses unless it is atomic
+    fn operand(&mut self, e: &mut ImutExpr) -> Result<()> {
+        let atomic = match e {
+            ImutExpr::Literal(Literal { value, .. }) => value.cast_f64().map_or(true, |v| v >= 0.0),
+            ImutExpr::Record(_)
+            | ImutExpr::List(_)
+            | ImutExpr::Path(_)
+            | ImutExpr::String(_)
+            | ImutExpr::Local { .. }
+            | ImutExpr::Invoke1(_)
+            | ImutExpr::Invoke2(_)
+            | ImutExpr::Invoke3(_)
+            | ImutExpr::Invoke(_) => true,
+            _ => false,
+        };
+        if !atomic {
+            self.write("(");
+        }
+        ImutExprWalker::walk_expr(self, e)?;
+        if !atomic {
+            self.write(")");
+        }
+        Ok(())
+    }
+
+    fn record(&mut self, record: &mut Record) -> Result<()> {
+        if record.base.is_empty() && record.fields.is_empty() {
+            self.write("{}");
+            return Ok(());
+        }
+        self.write("{");
+        self.indent += 1;
+        for (i, (k, v)) in record.base.iter().enumerate() {
+            self.item(i, ",");
+            self.quoted(k);
+            self.write(": ");
+            self.value(v);
+        }
+        let base = record.base.len();
+        for (i, field) in record.fields.iter_mut().enumerate() {
+            self.item(base + i, ",");
+            self.string(&mut field.name)?;
+            self.write(": ");
+            ImutExprWalker::walk_expr(self, &mut field.value)?;
+        }
+        self.indent -= 1;
+        self.newline();
+        self.write("}");
+        Ok(())
+    }
+
+    fn list(&mut self, exprs: &mut [ImutExpr]) -> Result<()> {
+        for (i, e) in exprs.iter_mut().enumerate() {
+            if i > 0 {
+                self.write(", ");
+            }
+            ImutExprWalker::walk_expr(self, e)?;
+        }
+        Ok(())
+    }
+
+    fn binary(&mut self, binary: &mut BinExpr) -> Result<()> {
+        self.operand(&mut binary.lhs)?;
+        self.write(&format!(" {} ", binary.kind));
+        self.operand(&mut binary.rhs)
+    }
+
+    fn binary_boolean(&mut self, binary: &mut BooleanBinExpr) -> Result<()> {
+        self.operand(&mut binary.lhs)?;
+        self.write(match binary.kind {
+            BooleanBinOpKind::Or => " or ",
+            BooleanBinOpKind::Xor => " xor ",
+            BooleanBinOpKind::And => " and ",
+        });
+        self.operand(&mut binary.rhs)
+    }
+
+    fn unary(&mut self, unary: &mut UnaryExpr) -> Result<()> {
+        if unary.kind == UnaryOpKind::Not {
+            self.write("not ");
+        } else {
+            self.write(&unary.kind.to_string());
+        }
+        self.operand(&mut unary.expr)
+    }
+
+    fn invoke(&mut self, invoke: &mut Invoke) -> Result<()> {
+        self.write(&invoke.node_id.fqn());
+        self.write("(");
+        self.list(&mut invoke.args)?;
+        self.write(")");
+        Ok(())
+    }
+
+    fn segments(&mut self, segments: &mut [Segment]) -> Result<()> {
+        for segment in segments {
+            match segment {
+                Segment::Id { key, .. } => {
+                    let key = key.key();
+                    if key.contains(|c| c == '`' || c == '\n') {
+                        self.write("[");
+                        self.quoted(key);
+                        self.write("]");
+                    } else {
+                        self.write(".");
+                        self.ident(key);
+                    }
+                }
+                Segment::Idx { idx, .. } => self.write(&format!("[{idx}]")),
+                Segment::Element { expr, .. } => {
+                    self.write("[");
+                    ImutExprWalker::walk_expr(self, expr)?;
+                    self.write("]");
+                }
+                Segment::Range { start, end, .. } => self.write(&format!("[{start}:{end}]")),
+                Segment::RangeExpr { start, end, .. } => {
+                    self.write("[");
+                    ImutExprWalker::walk_expr(self, start)?;
+                    self.write(":");
+                    ImutExprWalker::walk_expr(self, end)?;
+                    self.write("]");
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn path(&mut self, path: &mut Path) -> Result<()> {
+        match path {
+            Path::Local(local) => {
+                let name = match local.mid.name() {
+                    Some(name) => name.to_string(),
+                    None => return Self::unsupported(local, "locals without a name"),
+                };
+                self.ident(&name);
+                self.segments(&mut local.segments)
+            }
+            Path::Event(event) => {
+                self.write("event");
+                self.segments(&mut event.segments)
+            }
+            Path::State(state) => {
+                self.write("state");
+                self.segments(&mut state.segments)
+            }
+            Path::Meta(meta) => {
+                if !matches!(meta.segments.first(), None | Some(Segment::Id { .. })) {
+                    return Self::unsupported(meta, "metadata paths not starting with a key");
+                }
+                self.write("$");
+                if let Some((Segment::Id { key, .. }, rest)) = meta.segments.split_first_mut() {
+                    self.ident(key.key());
+                    self.segments(rest)?;
+                }
+                Ok(())
+            }
+            Path::Expr(expr) => {
+                self.write("(");
+                ImutExprWalker::walk_expr(self, &mut expr.expr)?;
+                self.write(")");
+                self.segments(&mut expr.segments)
+            }
+            Path::Reserved(ReservedPath::Args { segments, .. }) => {
+                self.write("args");
+                self.segments(segments)
+            }
+            Path::Reserved(ReservedPath::Window { segments, .. }) => {
+                self.write("window");
+                self.segments(segments)
+            }
+            Path::Reserved(ReservedPath::Group { segments, .. }) => {
+                self.write("group");
+                self.segments(segments)
+            }
+        }
+    }
+
+    fn patch(&mut self, patch: &mut Patch) -> Result<()> {
+        self.write("patch ");
+        ImutExprWalker::walk_expr(self, &mut patch.target)?;
+        self.write(" of");
+        self.indent += 1;
+        for (i, op) in patch.operations.iter_mut().enumerate() {
+            self.item(i, ";");
+            // the keyword, the field, the value and the target field of a copy or move
+            let (keyword, ident, expr, to) = match op {
+                PatchOperation::Insert { ident, expr, .. } => {
+                    ("insert", Some(ident), Some(expr), None)
+                }
+                PatchOperation::Upsert { ident, expr, .. } => {
+                    ("upsert", Some(ident), Some(expr), None)
+                }
+                PatchOperation::Update { ident, expr, .. } => {
+                    ("update", Some(ident), Some(expr), None)
+                }
+                PatchOperation::Erase { ident, .. } => ("erase", Some(ident), None, None),
+                PatchOperation::Copy { from, to, .. } => ("copy", Some(from), None, Some(to)),
+                PatchOperation::Move { from, to, .. } => ("move", Some(from), None, Some(to)),
+                PatchOperation::Merge { ident, expr, .. } => {
+                    ("merge", Some(ident), Some(expr), None)
+                }
+                PatchOperation::MergeRecord { expr, .. } => ("merge", None, Some(expr), None),
+                PatchOperation::Default { ident, expr, .. } => {
+                    ("default", Some(ident), Some(expr), None)
+                }
+                PatchOperation::DefaultRecord { expr, .. } => ("default", None, Some(expr), None),
+            };
+            self.write(keyword);
+            if let Some(ident) = ident {
+                self.write(" ");
+                self.string(ident)?;
+            }
+            if let Some(expr) = expr {
+                self.write(" => ");
+                ImutExprWalker::walk_expr(self, expr)?;
+            }
+            if let Some(to) = to {
+                self.write(" => ");
+                self.string(to)?;
+            }
+        }
+        self.indent -= 1;
+        self.newline();
+        self.write("end");
+        Ok(())
+    }
+
+    fn merge(&mut self, merge: &mut Merge) -> Result<()> {
+        self.write("merge ");
+        ImutExprWalker::walk_expr(self, &mut merge.target)?;
+        self.write(" of ");
+        ImutExprWalker::walk_expr(self, &mut merge.expr)?;
+        self.write(" end");
+        Ok(())
+    }
+
+    fn test(&mut self, test: &TestExpr) {
+        self.write(&test.id);
+        self.write("|");
+        self.write(&test.test.replace('\\', "\\\\").replace('|', "\\|"));
+        self.write("|");
+    }
+
+    fn record_pattern(&mut self, pattern: &mut RecordPattern) -> Result<()> {
+        self.write("%{");
+        for (i, field) in pattern.fields.iter_mut().enumerate() {
+            self.write(if i > 0 { ", " } else { " " });
+            match field {
+                PredicatePattern::TildeEq {
+                    assign, lhs, test, ..
+                } => {
+                    if assign != lhs {
+                        self.ident(assign);
+                        self.write(" = ");
+                    }
+                    self.ident(lhs);
+                    self.write(" ~= ");
+                    self.test(test);
+                }
+                PredicatePattern::Bin { lhs, rhs, kind, .. } => {
+                    self.ident(lhs);
+                    self.write(&format!(" {kind} "));
+                    ImutExprWalker::walk_expr(self, rhs)?;
+                }
+                PredicatePattern::RecordPatternEq { lhs, pattern, .. } => {
+                    self.ident(lhs);
+                    self.write(" ~= ");
+                    self.record_pattern(pattern)?;
+                }
+                PredicatePattern::ArrayPatternEq { lhs, pattern, .. } => {
+                    self.ident(lhs);
+                    self.write(" ~= ");
+                    self.array_pattern(pattern)?;
+                }
+                PredicatePattern::TuplePatternEq { lhs, pattern, .. } => {
+                    self.ident(lhs);
+                    self.write(" ~= ");
+                    self.tuple_pattern(pattern)?;
+                }
+                PredicatePattern::FieldPresent { lhs, .. } => {
+                    self.write("present ");
+                    self.ident(lhs);
+                }
+                PredicatePattern::FieldAbsent { lhs, .. } => {
+                    self.write("absent ");
+                    self.ident(lhs);
+                }
+            }
+        }
+        self.write(if pattern.fields.is_empty() { "}" } else { " }" });
+        Ok(())
+    }
+
+    fn array_predicates(&mut self, patterns: &mut [ArrayPredicatePattern]) -> Result<()> {
+        for (i, pattern) in patterns.iter_mut().enumerate() {
+            if i > 0 {
+                self.write(", ");
+            }
+            match pattern {
+                ArrayPredicatePattern::Expr(e) => ImutExprWalker::walk_expr(self, e)?,
+                ArrayPredicatePattern::Tilde(test) => {
+                    self.write("~ ");
+                    self.test(test);
+                }
+                ArrayPredicatePattern::Record(record) => self.record_pattern(record)?,
+                ArrayPredicatePattern::Ignore => self.write("_"),
+            }
+        }
+        Ok(())
+    }
+
+    fn array_pattern(&mut self, pattern: &mut ArrayPattern) -> Result<()> {
+        self.write("%[");
+        self.array_predicates(&mut pattern.exprs)?;
+        self.write("]");
+        Ok(())
+    }
+
+    fn tuple_pattern(&mut self, pattern: &mut TuplePattern) -> Result<()> {
+        self.write("%(");
+        self.array_predicates(&mut pattern.exprs)?;
+        if pattern.open {
+            self.write(if pattern.exprs.is_empty() {
+                "..."
+            } else {
+                ", ..."
+            });
+        }
+        self.write(")");
+        Ok(())
+    }
+
+    fn pattern(&mut self, pattern: &mut Pattern) -> Result<()> {
+        match pattern {
+            Pattern::Record(record) => self.record_pattern(record),
+            Pattern::Array(array) => self.array_pattern(array),
+            Pattern::Tuple(tuple) => self.tuple_pattern(tuple),
+            Pattern::Expr(e) => ImutExprWalker::walk_expr(self, e),
+            Pattern::Assign(AssignPattern { id, pattern, .. }) => {
+                self.ident(id);
+                self.write(" = ");
+                self.pattern(pattern)
+            }
+            Pattern::Extract(test) => {
+                self.write("~ ");
+                self.test(test);
+                Ok(())
+            }
+            Pattern::DoNotCare | Pattern::Default => {
+                self.write("_");
+                Ok(())
+            }
+        }
+    }
+
+    /// writes the expressions of a body, on the same line if it is a single expression
+    fn body<'script, Ex: Body<'script>>(&mut self, exprs: &mut [Ex], last: &mut Ex) -> Result<()> {
+        self.write(" =>");
+        if exprs.is_empty() {
+            self.write(" ");
+            return last.format(self);
+        }
+        self.indent += 1;
+        for e in exprs {
+            self.newline();
+            e.format(self)?;
+            self.write(Ex::SEP);
+        }
+        self.newline();
+        last.format(self)?;
+        self.indent -= 1;
+        Ok(())
+    }
+
+    fn clause<'script, Ex: Body<'script>>(
+        &mut self,
+        clause: &mut PredicateClause<'script, Ex>,
+    ) -> Result<()> {
+        self.newline();
+        if clause.pattern == Pattern::Default {
+            self.write("default");
+        } else {
+            self.write("case ");
+            self.pattern(&mut clause.pattern)?;
+        }
+        if let Some(guard) = &mut clause.guard {
+            self.write(" when ");
+            ImutExprWalker::walk_expr(self, guard)?;
+        }
+        let assign = if let Pattern::Assign(AssignPattern { id, idx, .. }) = &clause.pattern {
+            self.assigns.push((*idx, id.to_string()));
+            true
+        } else {
+            false
+        };
+        let res = self.body(&mut clause.exprs, &mut clause.last_expr);
+        if assign {
+            self.assigns.pop();
+        }
+        res
+    }
+
+    fn clause_group<'script, Ex: Body<'script>>(
+        &mut self,
+        group: &mut ClauseGroup<'script, Ex>,
+    ) -> Result<()> {
+        match group {
+            ClauseGroup::Simple { patterns, .. } => {
+                for clause in patterns {
+                    self.clause(clause)?;
+                }
+            }
+            ClauseGroup::SearchTree { tree, rest, .. } => {
+                for (value, (exprs, last)) in tree.iter_mut() {
+                    self.newline();
+                    self.write("case ");
+                    self.value(value);
+                    self.body(exprs, last)?;
+                }
+                for clause in rest {
+                    self.clause(clause)?;
+                }
+            }
+            ClauseGroup::Combined { groups, .. } => {
+                for group in groups {
+                    self.clause_group(group)?;
+                }
+            }
+            ClauseGroup::Single { pattern, .. } => self.clause(pattern)?,
+        }
+        Ok(())
+    }
+
+    fn default_case<'script, Ex: Body<'script>>(
+        &mut self,
+        default: &mut DefaultCase<Ex>,
+    ) -> Result<()> {
+        match default {
+            DefaultCase::None => Ok(()),
+            DefaultCase::Null => {
+                self.newline();
+                self.write("default => null");
+                Ok(())
+            }
+            DefaultCase::One(last) => {
+                self.newline();
+                self.write("default");
+                self.body(&mut [], last)
+            }
+            DefaultCase::Many { exprs, last_expr } => {
+                self.newline();
+                self.write("default");
+                self.body(exprs, last_expr)
+            }
+        }
+    }
+
+    fn match_<'script, Ex: Body<'script>>(
+        &mut self,
+        mmatch: &mut Match<'script, Ex>,
+    ) -> Result<()> {
+        self.write("match ");
+        ImutExprWalker::walk_expr(self, &mut mmatch.target)?;
+        self.write(" of");
+        self.indent += 1;
+        for group in &mut mmatch.patterns {
+            self.clause_group(group)?;
+        }
+        self.default_case(&mut mmatch.default)?;
+        self.indent -= 1;
+        self.newline();
+        self.write("end");
+        Ok(())
+    }
+
+    fn if_else<'script, Ex: Body<'script>>(
+        &mut self,
+        ifelse: &mut IfElse<'script, Ex>,
+    ) -> Result<()> {
+        self.write("match ");
+        ImutExprWalker::walk_expr(self, &mut ifelse.target)?;
+        self.write(" of");
+        self.indent += 1;
+        self.clause(&mut ifelse.if_clause)?;
+        self.default_case(&mut ifelse.else_clause)?;
+        self.indent -= 1;
+        self.newline();
+        self.write("end");
+        Ok(())
+    }
+
+    fn comprehension<'script, Ex: Body<'script>>(
+        &mut self,
+        comprehension: &mut Comprehension<'script, Ex>,
+    ) -> Result<()> {
+        self.write("for ");
+        ImutExprWalker::walk_expr(self, &mut comprehension.target)?;
+        self.write(" of");
+        self.indent += 1;
+        for case in &mut comprehension.cases {
+            self.newline();
+            self.write("case (");
+            self.ident(&case.key_name);
+            self.write(", ");
+            self.ident(&case.value_name);
+            self.write(")");
+            if let Some(guard) = &mut case.guard {
+                self.write(" when ");
+                ImutExprWalker::walk_expr(self, guard)?;
+            }
+            self.body(&mut case.exprs, &mut case.last_expr)?;
+        }
+        self.indent -= 1;
+        self.newline();
+        self.write("end");
+        Ok(())
+    }
+}
+
+impl<'script> ImutExprWalker<'script> for Formatter {}
+impl<'script> ExprWalker<'script> for Formatter {}
+
+impl<'script> ImutExprVisitor<'script> for Formatter {
+    fn visit_expr(&mut self, e: &mut ImutExpr<'script>) -> Result<VisitRes> {
+        match e {
+            ImutExpr::Record(record) => self.record(record)?,
+            ImutExpr::List(list) => {
+                self.write("[");
+                self.list(&mut list.exprs)?;
+                self.write("]");
+            }
+            ImutExpr::Binary(binary) => self.binary(binary)?,
+            ImutExpr::BinaryBoolean(binary) => self.binary_boolean(binary)?,
+            ImutExpr::Unary(unary) => self.unary(unary)?,
+            ImutExpr::Patch(patch) => self.patch(patch)?,
+            ImutExpr::Match(mmatch) => self.match_(mmatch.as_mut())?,
+            ImutExpr::Comprehension(comprehension) => self.comprehension(comprehension.as_mut())?,
+            ImutExpr::Merge(merge) => self.merge(merge)?,
+            ImutExpr::Path(path) => self.path(path)?,
+            ImutExpr::String(s) => self.string(s)?,
+            ImutExpr::Local { mid, .. } => match mid.name() {
+                Some(name) => self.ident(name),
+                None => {
+                    let span = Span::new(mid.start(), mid.end());
+                    return Self::unsupported(&span, "locals without a name");
+                }
+            },
+            ImutExpr::Literal(literal) => self.value(&literal.value),
+            ImutExpr::Present { path, .. } => {
+                self.write("present ");
+                self.path(path)?;
+            }
+            ImutExpr::Invoke1(invoke)
+            | ImutExpr::Invoke2(invoke)
+            | ImutExpr::Invoke3(invoke)
+            | ImutExpr::Invoke(invoke) => self.invoke(invoke)?,
+            ImutExpr::InvokeAggr(aggr) => return Self::unsupported(aggr, "aggregate functions"),
+            ImutExpr::Recur(recur) => return Self::unsupported(recur, "recur"),
+            ImutExpr::Bytes(bytes) => return Self::unsupported(bytes, "binary literals"),
+        }
+        // the expression is rendered as a whole
+        Ok(VisitRes::Stop)
+    }
+}
+
+impl<'script> ExprVisitor<'script> for Formatter {
+    fn visit_expr(&mut self, e: &mut Expr<'script>) -> Result<VisitRes> {
+        match e {
+            Expr::Match(mmatch) => self.match_(mmatch.as_mut())?,
+            Expr::IfElse(ifelse) => self.if_else(ifelse.as_mut())?,
+            Expr::Assign { path, expr, .. } => {
+                self.write("let ");
+                self.path(path)?;
+                self.write(" = ");
+                ExprWalker::walk_expr(self, expr)?;
+            }
+            Expr::AssignMoveLocal { path, idx, mid } => {
+                let name = match self.assigns.iter().rev().find(|(i, _)| *i == *idx) {
+                    Some((_, name)) => name.clone(),
+                    None => {
+                        let span = Span::new(mid.start(), mid.end());
+                        return Self::unsupported(&span, "assignments from unknown locals");
+                    }
+                };
+                self.write("let ");
+                self.path(path)?;
+                self.write(" = ");
+                self.ident(&name);
+            }
+            Expr::Comprehension(comprehension) => self.comprehension(comprehension.as_mut())?,
+            Expr::Drop { .. } => self.write("drop"),
+            Expr::Emit(emit) => {
+                self.write("emit ");
+                ImutExprWalker::walk_expr(self, &mut emit.expr)?;
+                if let Some(port) = &mut emit.port {
+                    self.write(" => ");
+                    ImutExprWalker::walk_expr(self, port)?;
+                }
+            }
+            Expr::Imut(imut) => ImutExprWalker::walk_expr(self, imut)?,
+        }
+        // the expression is rendered as a whole
+        Ok(VisitRes::Stop)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::registry::registry;
+    use crate::script::Script;
+
+    fn format(src: &str) -> Result<String> {
+        let reg = registry();
+        let mut script = Script::parse(src, &reg)?;
+        Formatter::format_exprs(&mut script.script.exprs)
+    }
+
+    /// formats `src` and checks that formatting the result again does not change it
+    fn round_trip(src: &str) -> Result<String> {
+        let formatted = format(src)?;
+        assert_eq!(formatted, format(&formatted)?, "{formatted}");
+        Ok(formatted)
+    }
+
+    #[test]
+    fn record() -> Result<()> {
+        let formatted = round_trip(r#"{"snot": event.snot, "badger": [event.badger, 1]}"#)?;
+        assert_eq!(
+            r#"{
+  "snot": event.snot,
+  "badger": [event.badger, 1]
+}"#,
+            formatted
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn operators_and_strings() -> Result<()> {
+        let formatted = round_trip(
+            r##"let a = (not event.a) and (event.b or ((-event.c) > (2 * (event.d + 1))));
+let b = "snot #{event.snot} \#{badger} \"quoted\"";
+let `match` = string::len(b) + $meta.`let`[0];
+present event.snot"##,
+        )?;
+        assert_eq!(
+            r##"let a = (not event.a) and (event.b or ((-event.c) > (2 * (event.d + 1))));
+let b = "snot #{event.snot} \#{badger} \"quoted\"";
+let `match` = string::len(b) + $meta.`let`[0];
+present event.snot"##,
+            formatted
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn match_expression() -> Result<()> {
+        round_trip(
+            r#"
+match event of
+  case %{ a == 1, present b, c ~= %[1, _] } => "ab"
+  case %[1, ~ re|^a|] => "array"
+  case x = %(1, ...) when x[0] > 0 => let y = 1; emit x => "out"
+  case "snot" => drop
+  default => null
+end
+"#,
+        )?;
+        Ok(())
+    }
+
+    #[test]
+    fn patch_and_merge() -> Result<()> {
+        let formatted = round_trip(
+            r#"
+patch event of
+  insert "a" => 1;
+  upsert "b" => event.x;
+  erase "c";
+  move "d" => "e";
+  copy "f" => "g";
+  merge "h" => {"i": event.j};
+  default => {"k": 2}
+end;
+merge event of {"a": event.a} end
+"#,
+        )?;
+        assert_eq!(
+            r#"patch event of
+  insert "a" => 1;
+  upsert "b" => event.x;
+  erase "c";
+  move "d" => "e";
+  copy "f" => "g";
+  merge "h" => {
+    "i": event.j
+  };
+  default => {
+    "k": 2
+  }
+end;
+merge event of {
+  "a": event.a
+} end"#,
+            formatted
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn comprehension() -> Result<()> {
+        round_trip(
+            r##"
+for event of
+  case (k, v) when k != "snot" => {"#{k}": v + 1}
+end
+"##,
+        )?;
+        Ok(())
+    }
+}
@@ -0,0 +1,140 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::super::prelude::*;
+use crate::ast::base_expr::Ranged;
+use crate::pos::Span;
+
+/// A grouping key of a `group by` clause
+#[derive(Clone, Debug, PartialEq)]
+pub enum GroupKey<'script> {
+    /// groups by the value of the expression
+    Expr(ImutExpr<'script>),
+    /// groups by each element of the array the expression evaluates to
+    Each(ImutExpr<'script>),
+    /// groups by the combination of the keys, `set(...)`
+    Set(Vec<GroupKey<'script>>),
+}
+
+/// Collects the grouping keys of `group by` clauses, keeping their order and nesting
+pub struct GroupKeyCollector<'script> {
+    /// keys of the `group by` and of each enclosing `set`, innermost last
+    keys: Vec<Vec<GroupKey<'script>>>,
+}
+
+impl<'script> GroupKeyCollector<'script> {
+    /// Returns the grouping key of a `group by` clause
+    #[must_use]
+    pub fn keys(group_by: &GroupBy<'script>) -> Option<GroupKey<'script>> {
+        let mut collector = Self {
+            keys: vec![Vec::new()],
+        };
+        collector.walk_group_by(group_by);
+        collector.keys.pop().and_then(|mut keys| keys.pop())
+    }
+
+    /// Returns the span and grouping key of every windowed `select` in `query` with a `group by` clause
+    #[must_use]
+    pub fn collect(query: &Query<'script>) -> Vec<(Span, GroupKey<'script>)> {
+        query
+            .stmts
+            .iter()
+            .filter_map(|stmt| match stmt {
+                Stmt::SelectStmt(SelectStmt { stmt: select, .. }) if !select.windows.is_empty() => {
+                    let key = Self::keys(select.maybe_group_by.as_ref()?)?;
+                    Some((select.extent(), key))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn push(&mut self, key: GroupKey<'script>) {
+        if let Some(keys) = self.keys.last_mut() {
+            keys.push(key);
+        }
+    }
+}
+
+impl<'script> GroupByVisitor<'script> for GroupKeyCollector<'script> {
+    fn visit_expr(&mut self, expr: &ImutExpr<'script>) {
+        self.push(GroupKey::Expr(expr.clone()));
+    }
+
+    fn walk_group_by(&mut self, group_by: &GroupBy<'script>) {
+        match group_by {
+            GroupBy::Expr { expr, .. } => self.visit_expr(expr),
+            GroupBy::Each { expr, .. } => self.push(GroupKey::Each(expr.clone())),
+            GroupBy::Set { items, .. } => {
+                self.keys.push(Vec::new());
+                for item in items {
+                    self.walk_group_by(item);
+                }
+                let set = self.keys.pop().unwrap_or_default();
+                self.push(GroupKey::Set(set));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::registry::registry;
+
+    /// the source of the expressions of a key, `each` and `set` in rust syntax
+    fn render(src: &str, key: &GroupKey) -> String {
+        let source = |e: &ImutExpr| src[e.s().absolute()..e.e().absolute()].to_string();
+        match key {
+            GroupKey::Expr(e) => source(e),
+            GroupKey::Each(e) => format!("each({})", source(e)),
+            GroupKey::Set(keys) => format!(
+                "set({})",
+                keys.iter()
+                    .map(|k| render(src, k))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        }
+    }
+
+    #[test]
+    fn composite_key() -> Result<()> {
+        let src = r#"
+define window by_2 from tumbling
+with
+  size = 2
+end;
+select event from in[by_2] group by set(event.country, each(event.regions), set(event.az)) into out;
+select event from in group by event.snot into out;
+select event from in[by_2] group by event.badger into out;
+select event from in[by_2] into out;
+"#;
+        let reg = registry();
+        let aggr_reg = crate::aggr_registry();
+        let query = crate::query::Query::parse(src, &reg, &aggr_reg)?;
+        let keys: Vec<_> = GroupKeyCollector::collect(&query.query)
+            .iter()
+            .map(|(_, key)| render(src, key))
+            .collect();
+        assert_eq!(
+            vec![
+                "set(event.country, each(event.regions), set(event.az))",
+                "event.badger"
+            ],
+            keys
+        );
+        Ok(())
+    }
+}
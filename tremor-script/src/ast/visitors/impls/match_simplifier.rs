@@ -0,0 +1,160 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::super::prelude::*;
+use crate::ast::Expression;
+
+/// Rewrites `match` expressions that only have a default case into the body of that case
+///
+/// `match x of default => y end` is simply `y` as long as evaluating `x` can neither fail
+/// nor have any other effect, so the rewrite is only applied if `x` is a literal or
+/// the root of `event`, `$` or `state`. Default cases with more then one expression
+/// are left untouched as there is no single expression to replace the match with.
+///
+/// `if` style matches always carry a case of their own, so they are never rewritten.
+#[derive(Default)]
+pub struct MatchSimplifier {}
+
+impl MatchSimplifier {
+    /// Simplifies all matches in `exprs`
+    ///
+    /// # Errors
+    /// if walking the expressions fails
+    pub fn simplify(exprs: &mut Exprs) -> Result<()> {
+        let mut simplifier = Self::default();
+        for e in exprs {
+            ExprWalker::walk_expr(&mut simplifier, e)?;
+        }
+        Ok(())
+    }
+}
+
+/// if evaluating the target can be skipped without changing the outcome of the script
+fn has_no_effects(target: &ImutExpr) -> bool {
+    match target {
+        ImutExpr::Literal(_) => true,
+        ImutExpr::Path(Path::Event(EventPath { segments, .. })) => segments.is_empty(),
+        ImutExpr::Path(Path::Meta(MetadataPath { segments, .. })) => segments.is_empty(),
+        ImutExpr::Path(Path::State(StatePath { segments, .. })) => segments.is_empty(),
+        _ => false,
+    }
+}
+
+/// the body of the default case if `m` only consists of it, otherwise `m` itself
+fn simplify<'script, Ex: Expression + 'script>(
+    m: Match<'script, Ex>,
+) -> std::result::Result<Ex, Match<'script, Ex>> {
+    if !m.patterns.is_empty() || !has_no_effects(&m.target) {
+        return Err(m);
+    }
+    let Match {
+        mid,
+        target,
+        patterns,
+        default,
+    } = m;
+    match default {
+        DefaultCase::Null => Ok(Ex::null_lit(mid)),
+        DefaultCase::One(e) => Ok(e),
+        DefaultCase::Many { exprs, last_expr } if exprs.is_empty() => Ok(*last_expr),
+        default => Err(Match {
+            mid,
+            target,
+            patterns,
+            default,
+        }),
+    }
+}
+
+impl<'script> ImutExprWalker<'script> for MatchSimplifier {}
+impl<'script> ImutExprVisitor<'script> for MatchSimplifier {
+    fn leave_expr(&mut self, e: &mut ImutExpr<'script>) -> Result<()> {
+        if matches!(e, ImutExpr::Match(_)) {
+            let mut buf = ImutExpr::null_lit(Box::new(e.meta().clone()));
+            std::mem::swap(&mut buf, e);
+            *e = match buf {
+                ImutExpr::Match(m) => simplify(*m).unwrap_or_else(|m| ImutExpr::Match(Box::new(m))),
+                other => other,
+            };
+        }
+        Ok(())
+    }
+}
+
+impl<'script> ExprWalker<'script> for MatchSimplifier {}
+impl<'script> ExprVisitor<'script> for MatchSimplifier {
+    fn leave_expr(&mut self, e: &mut Expr<'script>) -> Result<()> {
+        if matches!(e, Expr::Match(_)) {
+            let mut buf = Expr::null_lit(Box::new(e.meta().clone()));
+            std::mem::swap(&mut buf, e);
+            *e = match buf {
+                Expr::Match(m) => simplify(*m).unwrap_or_else(|m| Expr::Match(Box::new(m))),
+                other => other,
+            };
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::registry::registry;
+    use crate::script::Script;
+    use crate::Value;
+
+    fn simplified(src: &str) -> Result<Vec<Expr<'static>>> {
+        let reg = registry();
+        let mut script = Script::parse(src, &reg)?;
+        MatchSimplifier::simplify(&mut script.script.exprs)?;
+        Ok(script.script.exprs.clone())
+    }
+
+    fn literal<'e>(e: &'e Expr) -> Option<&'e Value<'e>> {
+        match e {
+            Expr::Imut(ImutExpr::Literal(Literal { value, .. })) => Some(value),
+            Expr::Assign { expr, .. } => literal(expr),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn simplifies_default_only_match() -> Result<()> {
+        let exprs = simplified(
+            r#"
+let a = match 1 of default => "snot" end;
+match event of default => let b = "badger" end;
+match $ of default => null end
+"#,
+        )?;
+        assert_eq!(Some(&Value::from("snot")), literal(&exprs[0]));
+        assert_eq!(Some(&Value::from("badger")), literal(&exprs[1]));
+        assert!(exprs[2].is_null_lit());
+        Ok(())
+    }
+
+    #[test]
+    fn leaves_other_matches_untouched() -> Result<()> {
+        let exprs = simplified(
+            r#"
+match event.x of case 1 => "one" case 2 => "two" default => "snot" end;
+match event.x of default => "snot" end;
+match event of default => let a = 1; a end
+"#,
+        )?;
+        assert_eq!(3, exprs.len());
+        assert!(exprs.iter().all(|e| matches!(e, Expr::Match(_))));
+        Ok(())
+    }
+}
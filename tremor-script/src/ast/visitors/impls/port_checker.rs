@@ -0,0 +1,218 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::super::prelude::*;
+use crate::errors::{pipeline_unknown_port_err, query_stream_not_defined_err};
+use std::collections::{HashMap, HashSet};
+
+/// Input and output ports of a pipeline definition
+#[derive(Clone, Debug, Default)]
+struct Ports {
+    from: HashSet<String>,
+    into: HashSet<String>,
+}
+
+impl From<&PipelineDefinition<'_>> for Ports {
+    fn from(defn: &PipelineDefinition<'_>) -> Self {
+        Self {
+            from: defn.from.iter().map(ToString::to_string).collect(),
+            into: defn.into.iter().map(ToString::to_string).collect(),
+        }
+    }
+}
+
+/// Verifies that every stream referenced in the `from` and `into` clause of a `select`
+/// is declared before it is used, either as a port of the query, a stream,
+/// or a created operator, script or pipeline.
+///
+/// The ports of created pipelines are checked as well, if the pipeline is defined in the query itself.
+/// Operators and scripts can emit to any port, so their ports are never checked.
+#[derive(Default)]
+pub struct PortChecker {
+    // pipeline definitions of the query by id
+    pipelines: HashMap<String, Ports>,
+    // declared streams, with their ports if we know them
+    streams: HashMap<String, Option<Ports>>,
+}
+
+impl PortChecker {
+    /// Checks all stream references of the given query
+    ///
+    /// # Errors
+    /// for the first reference to an undeclared stream or unknown pipeline port
+    pub fn check(&mut self, query: &mut Query) -> Result<()> {
+        self.walk_query(query)
+    }
+
+    fn declare(&mut self, stream: &str, ports: Option<Ports>) {
+        self.streams.insert(stream.to_string(), ports);
+    }
+
+    fn check_stream<F>(
+        &self,
+        select: &Select,
+        (stream, port): &(Ident, Ident),
+        ports: F,
+    ) -> Result<()>
+    where
+        F: Fn(&Ports) -> &HashSet<String>,
+    {
+        match self.streams.get(stream.as_str()) {
+            None => Err(query_stream_not_defined_err(
+                select,
+                stream,
+                stream.to_string(),
+                port.to_string(),
+            )),
+            Some(Some(known)) if !ports(known).contains(port.as_str()) => Err(
+                pipeline_unknown_port_err(select, port, stream.to_string(), port.to_string()),
+            ),
+            Some(_) => Ok(()),
+        }
+    }
+}
+
+impl<'script> ImutExprWalker<'script> for PortChecker {}
+impl<'script> ImutExprVisitor<'script> for PortChecker {}
+impl<'script> ExprWalker<'script> for PortChecker {}
+impl<'script> ExprVisitor<'script> for PortChecker {}
+impl<'script> QueryWalker<'script> for PortChecker {}
+impl<'script> QueryVisitor<'script> for PortChecker {
+    fn visit_query(&mut self, q: &mut Query<'script>) -> Result<VisitRes> {
+        self.pipelines = q
+            .scope
+            .content
+            .pipelines
+            .iter()
+            .map(|(id, defn)| (id.clone(), Ports::from(defn)))
+            .collect();
+        for port in q.from.iter().chain(q.into.iter()) {
+            self.declare(port.as_str(), None);
+        }
+        Ok(VisitRes::Walk)
+    }
+
+    fn visit_stream_stmt(&mut self, stmt: &mut StreamStmt) -> Result<VisitRes> {
+        self.declare(&stmt.id, None);
+        Ok(VisitRes::Walk)
+    }
+
+    fn visit_operator_create(&mut self, stmt: &mut OperatorCreate<'script>) -> Result<VisitRes> {
+        self.declare(&stmt.id, None);
+        Ok(VisitRes::Walk)
+    }
+
+    fn visit_script_create(&mut self, stmt: &mut ScriptCreate<'script>) -> Result<VisitRes> {
+        self.declare(&stmt.id, None);
+        Ok(VisitRes::Walk)
+    }
+
+    fn visit_pipeline_create(&mut self, stmt: &mut PipelineCreate) -> Result<VisitRes> {
+        // pipelines from other modules are not part of the query, so we can't check their ports
+        let ports = if stmt.target.module.is_empty() {
+            self.pipelines.get(&stmt.target.id).cloned()
+        } else {
+            None
+        };
+        self.declare(&stmt.alias, ports);
+        Ok(VisitRes::Walk)
+    }
+
+    fn visit_select(&mut self, select: &mut Select<'script>) -> Result<VisitRes> {
+        // we select from the outputs of a pipeline and into its inputs
+        self.check_stream(select, &select.from, |p| &p.into)?;
+        self.check_stream(select, &select.into, |p| &p.from)?;
+        // no need to walk the select itself
+        Ok(VisitRes::Stop)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::registry::registry;
+
+    fn check(query: &str) -> Result<()> {
+        let reg = registry();
+        let aggr_reg = crate::aggr_registry();
+        let mut query = crate::query::Query::parse(query, &reg, &aggr_reg)?;
+        PortChecker::default().check(&mut query.query)
+    }
+
+    #[test]
+    fn declared_streams() -> Result<()> {
+        check(
+            r#"
+define pipeline sub
+from a
+into b
+pipeline
+  select event from a into b;
+end;
+define operator counter from generic::counter;
+create operator counter;
+create stream s;
+create pipeline sub;
+select event from in into s;
+select event from s into counter;
+select event from counter/snot into sub/a;
+select event from sub/b into out;
+"#,
+        )
+    }
+
+    #[test]
+    fn undeclared_stream() {
+        let e = check(
+            r#"
+create stream s;
+select event from in into t;
+select event from s into out;
+"#,
+        )
+        .err()
+        .map(|e| e.to_string())
+        .unwrap_or_default();
+        assert!(e.contains("Stream used in `from` or `into` is not defined: t/in"));
+    }
+
+    #[test]
+    fn stream_declared_after_use() {
+        let e = check(
+            r#"
+select event from in into s;
+create stream s;
+"#,
+        );
+        assert!(e.is_err());
+    }
+
+    #[test]
+    fn unknown_pipeline_port() {
+        let e = check(
+            r#"
+define pipeline sub
+pipeline
+  select event from in into out;
+end;
+create pipeline sub;
+select event from in into sub/snot;
+"#,
+        )
+        .err()
+        .map(|e| e.to_string())
+        .unwrap_or_default();
+        assert!(e.contains("Query `sub` does not have port `snot`"));
+    }
+}
@@ -0,0 +1,276 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::super::prelude::*;
+use crate::ast::base_expr::Ranged;
+use crate::ast::Expression;
+use crate::pos::Span;
+use std::collections::HashSet;
+
+/// Finds `let` expressions reusing the name of a local bound in an enclosing scope
+///
+/// Every `case` and `default` arm of a `match` and every comprehension opens a scope for its body,
+/// enclosed by a scope holding the locals bound by its patterns or its key and value bindings.
+/// Binding a name again in the same scope is not considered shadowing.
+/// Cases the optimizer groups into search trees share the scope of their `match`.
+pub struct ShadowLint {
+    /// names bound in each scope, innermost last
+    scopes: Vec<HashSet<String>>,
+    warnings: Vec<(String, Span)>,
+}
+
+impl Default for ShadowLint {
+    fn default() -> Self {
+        Self {
+            scopes: vec![HashSet::new()],
+            warnings: Vec::new(),
+        }
+    }
+}
+
+impl ShadowLint {
+    /// Returns the name and span of every `let` in `exprs` shadowing a local of an enclosing scope
+    ///
+    /// # Errors
+    /// if walking the expressions fails
+    pub fn lint(exprs: &mut Exprs) -> Result<Vec<(String, Span)>> {
+        let mut lint = Self::default();
+        for e in exprs {
+            ExprWalker::walk_expr(&mut lint, e)?;
+        }
+        Ok(lint.warnings)
+    }
+
+    /// opens the scope for the bindings of an arm and the scope for its body
+    fn enter(&mut self, bindings: HashSet<String>) {
+        self.scopes.push(bindings);
+        self.scopes.push(HashSet::new());
+    }
+
+    fn enter_comprehension<'script, Ex: Expression + 'script>(
+        &mut self,
+        comp: &Comprehension<'script, Ex>,
+    ) {
+        self.enter(
+            comp.cases
+                .iter()
+                .flat_map(|case| [case.key_name.to_string(), case.value_name.to_string()])
+                .collect(),
+        );
+    }
+
+    fn leave(&mut self) {
+        self.scopes.pop();
+        self.scopes.pop();
+    }
+
+    /// binds a local of a pattern in the bindings scope of the current arm
+    fn bind_pattern(&mut self, name: &str) {
+        if let Some(bindings) = self.scopes.iter_mut().rev().nth(1) {
+            bindings.insert(name.to_string());
+        }
+    }
+
+    fn check_let(&mut self, name: &str, span: Span) {
+        let (current, enclosing) = match self.scopes.split_last() {
+            Some(scopes) => scopes,
+            None => return,
+        };
+        if !current.contains(name) && enclosing.iter().any(|scope| scope.contains(name)) {
+            self.warnings.push((name.to_string(), span));
+        }
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.to_string());
+        }
+    }
+}
+
+impl<'script> ImutExprWalker<'script> for ShadowLint {}
+impl<'script> ImutExprVisitor<'script> for ShadowLint {
+    fn visit_predicate_clause(
+        &mut self,
+        _predicate: &mut PredicateClause<'script, ImutExpr<'script>>,
+    ) -> Result<VisitRes> {
+        self.enter(HashSet::new());
+        Ok(VisitRes::Walk)
+    }
+
+    fn leave_predicate_clause(
+        &mut self,
+        _predicate: &mut PredicateClause<'script, ImutExpr<'script>>,
+    ) -> Result<()> {
+        self.leave();
+        Ok(())
+    }
+
+    fn visit_default_case(
+        &mut self,
+        _mdefault: &mut DefaultCase<ImutExpr<'script>>,
+    ) -> Result<VisitRes> {
+        self.enter(HashSet::new());
+        Ok(VisitRes::Walk)
+    }
+
+    fn leave_default_case(&mut self, _mdefault: &mut DefaultCase<ImutExpr<'script>>) -> Result<()> {
+        self.leave();
+        Ok(())
+    }
+
+    fn visit_comprehension(
+        &mut self,
+        comp: &mut Comprehension<'script, ImutExpr<'script>>,
+    ) -> Result<VisitRes> {
+        self.enter_comprehension(comp);
+        Ok(VisitRes::Walk)
+    }
+
+    fn leave_comprehension(
+        &mut self,
+        _comp: &mut Comprehension<'script, ImutExpr<'script>>,
+    ) -> Result<()> {
+        self.leave();
+        Ok(())
+    }
+
+    fn visit_match_pattern(&mut self, pattern: &mut Pattern<'script>) -> Result<VisitRes> {
+        if let Pattern::Assign(assign) = pattern {
+            self.bind_pattern(&assign.id);
+        }
+        Ok(VisitRes::Walk)
+    }
+}
+
+impl<'script> ExprWalker<'script> for ShadowLint {}
+impl<'script> ExprVisitor<'script> for ShadowLint {
+    fn visit_expr(&mut self, e: &mut Expr<'script>) -> Result<VisitRes> {
+        if let Expr::Assign {
+            path: Path::Local(local),
+            ..
+        }
+        | Expr::AssignMoveLocal {
+            path: Path::Local(local),
+            ..
+        } = e
+        {
+            // only plain `let`s bind a name, not assignments into a local
+            let name = local
+                .name()
+                .filter(|_| local.segments.is_empty())
+                .map(ToString::to_string);
+            if let Some(name) = name {
+                self.check_let(&name, e.extent());
+            }
+        }
+        Ok(VisitRes::Walk)
+    }
+
+    fn visit_predicate_clause(
+        &mut self,
+        _predicate: &mut PredicateClause<'script, Expr<'script>>,
+    ) -> Result<VisitRes> {
+        self.enter(HashSet::new());
+        Ok(VisitRes::Walk)
+    }
+
+    fn leave_predicate_clause(
+        &mut self,
+        _predicate: &mut PredicateClause<'script, Expr<'script>>,
+    ) -> Result<()> {
+        self.leave();
+        Ok(())
+    }
+
+    fn visit_default_case(
+        &mut self,
+        _mdefault: &mut DefaultCase<Expr<'script>>,
+    ) -> Result<VisitRes> {
+        self.enter(HashSet::new());
+        Ok(VisitRes::Walk)
+    }
+
+    fn leave_default_case(&mut self, _mdefault: &mut DefaultCase<Expr<'script>>) -> Result<()> {
+        self.leave();
+        Ok(())
+    }
+
+    fn visit_comprehension(
+        &mut self,
+        comp: &mut Comprehension<'script, Expr<'script>>,
+    ) -> Result<VisitRes> {
+        self.enter_comprehension(comp);
+        Ok(VisitRes::Walk)
+    }
+
+    fn leave_comprehension(
+        &mut self,
+        _comp: &mut Comprehension<'script, Expr<'script>>,
+    ) -> Result<()> {
+        self.leave();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::registry::registry;
+    use crate::script::Script;
+
+    fn lint(src: &str) -> Result<Vec<String>> {
+        let reg = registry();
+        let mut script = Script::parse(src, &reg)?;
+        Ok(ShadowLint::lint(&mut script.script.exprs)?
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect())
+    }
+
+    #[test]
+    fn ignores_rebinding_in_the_same_scope() -> Result<()> {
+        let names = lint(
+            r#"
+let a = 1;
+let a = a + 1;
+match event of
+  case %{ present b } => let c = 1; let c = c + 1
+  case _ => let c = 2
+end;
+for event of
+  case (k, v) => let d = v
+end
+"#,
+        )?;
+        assert!(names.is_empty(), "{names:?}");
+        Ok(())
+    }
+
+    #[test]
+    fn flags_shadowing() -> Result<()> {
+        let names = lint(
+            r#"
+let a = 1;
+let k = 2;
+match event of
+  case x = %{ present b } => let a = 2; let x = 3
+  case _ => let k = 3
+end;
+for event of
+  case (k, v) => let v = 4; let a = 5
+end
+"#,
+        )?;
+        assert_eq!(vec!["a", "x", "k", "v", "a"], names);
+        Ok(())
+    }
+}
@@ -0,0 +1,175 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::super::prelude::*;
+use crate::arena::Arena;
+use crate::ast::base_expr::Ranged;
+use crate::pos::Span;
+
+/// A part of an interpolated string
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TemplateSegment {
+    /// Literal text, with escape sequences already resolved
+    Lit(String),
+    /// An interpolated expression
+    Expr {
+        /// Location of the expression
+        span: Span,
+        /// Source of the expression
+        src: String,
+    },
+}
+
+/// An interpolated string found in a script
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StringTemplate {
+    /// Location of the whole string
+    pub span: Span,
+    /// Literal and expression parts of the string, in order
+    pub segments: Vec<TemplateSegment>,
+    /// If the source of the string contains escape sequences,
+    /// in which case the literal segments differ from the source
+    pub has_escapes: bool,
+}
+
+/// Extracts all interpolated strings, including strings nested in interpolated expressions
+#[derive(Default)]
+pub struct StringTemplateExtractor {
+    // strings we are currently in, innermost last
+    stack: Vec<StringTemplate>,
+    templates: Vec<StringTemplate>,
+}
+
+fn source_of(span: Span) -> Result<String> {
+    let start = span.start();
+    let src = Arena::get(start.aid)?.unwrap_or_default();
+    Ok(src
+        .get(start.absolute()..span.end().absolute())
+        .unwrap_or_default()
+        .to_string())
+}
+
+impl StringTemplateExtractor {
+    /// Extracts all interpolated strings from `exprs`, ordered by their position in the source
+    ///
+    /// # Errors
+    /// if the source of the expressions can't be fetched
+    pub fn extract(exprs: &mut Exprs) -> Result<Vec<StringTemplate>> {
+        let mut extractor = Self::default();
+        for e in exprs {
+            ExprWalker::walk_expr(&mut extractor, e)?;
+        }
+        let mut templates = extractor.templates;
+        templates.sort_by_key(|t| t.span.start().absolute());
+        Ok(templates)
+    }
+}
+
+impl<'script> ImutExprWalker<'script> for StringTemplateExtractor {}
+impl<'script> ImutExprVisitor<'script> for StringTemplateExtractor {
+    fn visit_string(&mut self, string: &mut StringLit<'script>) -> Result<VisitRes> {
+        let span = string.extent();
+        let has_escapes = source_of(span)?.contains('\\');
+        self.stack.push(StringTemplate {
+            span,
+            segments: Vec::with_capacity(string.elements.len()),
+            has_escapes,
+        });
+        Ok(VisitRes::Walk)
+    }
+
+    fn visit_string_element(&mut self, element: &mut StrLitElement<'script>) -> Result<VisitRes> {
+        let segment = match element {
+            // the lexer might leave empty literals around interpolations
+            StrLitElement::Lit(l) if l.is_empty() => None,
+            StrLitElement::Lit(l) => Some(TemplateSegment::Lit(l.to_string())),
+            StrLitElement::Expr(e) => {
+                let span = e.extent();
+                Some(TemplateSegment::Expr {
+                    span,
+                    src: source_of(span)?,
+                })
+            }
+        };
+        if let Some((segment, current)) = segment.zip(self.stack.last_mut()) {
+            current.segments.push(segment);
+        }
+        Ok(VisitRes::Walk)
+    }
+
+    fn leave_string(&mut self, _string: &mut StringLit<'script>) -> Result<()> {
+        if let Some(template) = self.stack.pop() {
+            let is_template = template
+                .segments
+                .iter()
+                .any(|s| matches!(s, TemplateSegment::Expr { .. }));
+            if is_template {
+                self.templates.push(template);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<'script> ExprWalker<'script> for StringTemplateExtractor {}
+impl<'script> ExprVisitor<'script> for StringTemplateExtractor {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::registry::registry;
+    use crate::script::Script;
+
+    fn segments(template: &StringTemplate) -> Vec<String> {
+        template
+            .segments
+            .iter()
+            .map(|s| match s {
+                TemplateSegment::Lit(l) => format!("lit:{l}"),
+                TemplateSegment::Expr { src, .. } => format!("expr:{src}"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn extract_templates() -> Result<()> {
+        let src = r#"
+let count = 3;
+let plain = "no template here";
+{
+  "msg": "{event.name} has {count} items",
+  "nested": "outer {"inner {count}"}",
+  "escaped": "tab\t{count}"
+}
+"#;
+        let reg = registry();
+        let mut script = Script::parse(src, &reg)?;
+        let templates = StringTemplateExtractor::extract(&mut script.script.exprs)?;
+
+        assert_eq!(4, templates.len());
+        assert_eq!(
+            vec!["expr:event.name", "lit: has ", "expr:count", "lit: items"],
+            segments(&templates[0])
+        );
+        assert!(!templates[0].has_escapes);
+        assert_eq!(
+            vec!["lit:outer ", r#"expr:"inner {count}""#],
+            segments(&templates[1])
+        );
+        assert_eq!(vec!["lit:inner ", "expr:count"], segments(&templates[2]));
+        assert_eq!(vec!["lit:tab\t", "expr:count"], segments(&templates[3]));
+        assert!(templates[3].has_escapes);
+        Ok(())
+    }
+}
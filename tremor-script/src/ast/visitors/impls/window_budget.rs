@@ -0,0 +1,122 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::super::prelude::*;
+use crate::errors::error_generic;
+
+/// Counts the windows used by all `select` statements of a query
+/// and fails once the number exceeds the configured budget.
+///
+/// Every window referenced by a `select` counts, so a window definition
+/// used in two selects counts twice, as each select maintains its own window state.
+#[derive(Default)]
+pub struct WindowBudget {
+    max_windows: Option<usize>,
+    windows: usize,
+}
+
+impl WindowBudget {
+    /// Creates a new budget of at most `max_windows` windows, `None` being unbounded
+    #[must_use]
+    pub fn new(max_windows: Option<usize>) -> Self {
+        Self {
+            max_windows,
+            windows: 0,
+        }
+    }
+
+    /// Number of windows counted so far
+    #[must_use]
+    pub fn windows(&self) -> usize {
+        self.windows
+    }
+
+    /// Checks the given query against the budget, returning the number of windows used
+    ///
+    /// # Errors
+    /// if the query uses more windows than the budget allows
+    pub fn check(&mut self, query: &mut Query) -> Result<usize> {
+        self.walk_query(query)?;
+        Ok(self.windows)
+    }
+}
+
+impl<'script> ImutExprWalker<'script> for WindowBudget {}
+impl<'script> ImutExprVisitor<'script> for WindowBudget {}
+impl<'script> ExprWalker<'script> for WindowBudget {}
+impl<'script> ExprVisitor<'script> for WindowBudget {}
+impl<'script> QueryWalker<'script> for WindowBudget {}
+impl<'script> QueryVisitor<'script> for WindowBudget {
+    fn visit_select(&mut self, select: &mut Select<'script>) -> Result<VisitRes> {
+        self.windows += select.windows.len();
+        if let Some(max_windows) = self.max_windows {
+            if self.windows > max_windows {
+                return error_generic(
+                    select,
+                    select,
+                    &format!(
+                        "Query uses {} windows, exceeding the maximum of {max_windows}",
+                        self.windows
+                    ),
+                );
+            }
+        }
+        // no need to walk the select itself
+        Ok(VisitRes::Stop)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::registry::registry;
+
+    const QUERY: &str = r#"
+define window w1 from tumbling
+with
+  size = 2
+end;
+define window w2 from tumbling
+with
+  interval = 100
+end;
+select aggr::stats::count() from in[w1, w2] into out;
+select aggr::stats::count() from in[w1] into out;
+"#;
+
+    fn parse() -> Result<crate::query::Query> {
+        let reg = registry();
+        let aggr_reg = crate::aggr_registry();
+        crate::query::Query::parse(QUERY, &reg, &aggr_reg)
+    }
+
+    #[test]
+    fn within_budget() -> Result<()> {
+        let mut query = parse()?;
+        assert_eq!(3, WindowBudget::default().check(&mut query.query)?);
+        assert_eq!(3, WindowBudget::new(Some(3)).check(&mut query.query)?);
+        Ok(())
+    }
+
+    #[test]
+    fn exceeding_budget() -> Result<()> {
+        let mut query = parse()?;
+        let mut budget = WindowBudget::new(Some(2));
+        let e = budget.check(&mut query.query).err().map(|e| e.to_string());
+        assert!(e
+            .unwrap_or_default()
+            .contains("Query uses 3 windows, exceeding the maximum of 2"));
+        Ok(())
+    }
+}
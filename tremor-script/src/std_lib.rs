@@ -26,6 +26,7 @@ mod chash;
 mod datetime;
 mod dummy;
 mod float;
+mod hex;
 mod integer;
 mod json;
 mod math;
@@ -44,6 +45,16 @@ mod url;
 mod win;
 
 use crate::registry::{Aggr as AggrRegistry, Registry};
+use crate::Value;
+
+/// the bytes of a `binary` or of a `string`, for functions encoding either
+fn bytes_or_string<'value>(value: &'value Value) -> Option<&'value [u8]> {
+    match value {
+        Value::Bytes(bytes) => Some(&bytes[..]),
+        Value::String(string) => Some(string.as_bytes()),
+        _ => None,
+    }
+}
 
 pub fn load(registry: &mut Registry) {
     array::load(registry);
@@ -53,6 +64,7 @@ pub fn load(registry: &mut Registry) {
     datetime::load(registry);
     dummy::load(registry);
     float::load(registry);
+    hex::load(registry);
     integer::load(registry);
     json::load(registry);
     math::load(registry);
@@ -12,16 +12,29 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use super::bytes_or_string;
 use crate::registry::Registry;
 use crate::{tremor_const_fn, tremor_fn_};
 
 pub fn load(registry: &mut Registry) {
     registry
-        .insert(tremor_const_fn! (base64|encode(_context, _input: Bytes) {
-            Ok(Value::from(base64::encode(&_input)))
+        .insert(tremor_const_fn! (base64|encode(_context, input) {
+            bytes_or_string(input)
+                .map(|input| Value::from(base64::encode(input)))
+                .ok_or_else(|| to_runtime_error("can only encode a binary or a string"))
         }))
-        .insert(tremor_const_fn! (base64|decode(_context, _input: String) {
-            base64::decode(_input.as_bytes()).map(|v| Value::Bytes(v.into())).map_err(to_runtime_error)
+        .insert(tremor_const_fn! (base64|decode(_context, input) {
+            let input = bytes_or_string(input).ok_or_else(|| to_runtime_error("can only decode a string or a binary"))?;
+            base64::decode(input).map(|v| Value::Bytes(v.into())).map_err(|e| to_runtime_error(format!("invalid base64: {e}")))
+        }))
+        .insert(tremor_const_fn! (base64|encode_url_safe(_context, input) {
+            bytes_or_string(input)
+                .map(|input| Value::from(base64::encode_config(input, base64::URL_SAFE)))
+                .ok_or_else(|| to_runtime_error("can only encode a binary or a string"))
+        }))
+        .insert(tremor_const_fn! (base64|decode_url_safe(_context, input) {
+            let input = bytes_or_string(input).ok_or_else(|| to_runtime_error("can only decode a string or a binary"))?;
+            base64::decode_config(input, base64::URL_SAFE).map(|v| Value::Bytes(v.into())).map_err(|e| to_runtime_error(format!("invalid url-safe base64: {e}")))
         }));
 }
 
@@ -35,11 +48,45 @@ mod test {
         let f = fun("base64", "decode");
         let v = Value::from("c25vdA==");
         assert_val!(f(&[&v]), Value::Bytes("snot".as_bytes().into()));
+        let v = Value::Bytes("c25vdA==".as_bytes().into());
+        assert_val!(f(&[&v]), Value::Bytes("snot".as_bytes().into()));
     }
     #[test]
     fn encode() {
         let f = fun("base64", "encode");
         let v = Value::Bytes("snot".as_bytes().into());
         assert_val!(f(&[&v]), Value::from("c25vdA=="));
+        let v = Value::from("snot");
+        assert_val!(f(&[&v]), Value::from("c25vdA=="));
+    }
+
+    #[test]
+    fn round_trip() {
+        let encode = fun("base64", "encode");
+        let decode = fun("base64", "decode");
+        let v = Value::Bytes(vec![0_u8, 251, 255, 42].into());
+        let encoded = encode(&[&v]).expect("encodes");
+        assert_val!(decode(&[&encoded]), v);
+
+        let encode = fun("base64", "encode_url_safe");
+        let decode = fun("base64", "decode_url_safe");
+        let encoded = encode(&[&v]).expect("encodes");
+        assert_eq!(Value::from("APv_Kg=="), encoded);
+        assert_val!(decode(&[&encoded]), v);
+    }
+
+    #[test]
+    fn invalid_input() {
+        let decode = fun("base64", "decode");
+        assert!(decode(&[&Value::from("c25v!dA==")]).is_err());
+        // url-safe characters are not part of the standard alphabet
+        assert!(decode(&[&Value::from("APv_Kg==")]).is_err());
+        assert!(decode(&[&Value::from(42)]).is_err());
+
+        let decode = fun("base64", "decode_url_safe");
+        assert!(decode(&[&Value::from("APv/Kg==")]).is_err());
+
+        let encode = fun("base64", "encode");
+        assert!(encode(&[&Value::from(42)]).is_err());
     }
 }
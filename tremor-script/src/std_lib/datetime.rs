@@ -23,7 +23,9 @@ use crate::datetime::{_parse, has_tz};
 use crate::prelude::*;
 use crate::registry::Registry;
 use crate::{tremor_const_fn, tremor_fn};
-use chrono::{offset::Utc, DateTime, Datelike, NaiveDateTime, SubsecRound, Timelike};
+use chrono::{
+    offset::Utc, DateTime, Datelike, NaiveDateTime, SecondsFormat, SubsecRound, Timelike,
+};
 
 macro_rules! time_fn {
     ($name:ident, $fn:ident) => {
@@ -51,6 +53,16 @@ pub fn load(registry: &mut Registry) {
 })
 }}))
 
+        .insert(tremor_const_fn!(datetime|parse_rfc3339(_context, _input: String) {
+            _parse_rfc3339(_input).map(Value::from).map_err(|error| FunctionError::RuntimeError { mfa: this_mfa(), error })
+        }))
+        .insert(tremor_const_fn!(datetime|format_rfc3339(_context, _value) {
+            if let Some(value) = _value.as_i64() {
+                _format_rfc3339(value).map(Value::from).map_err(|error| FunctionError::RuntimeError { mfa: this_mfa(), error })
+            } else {
+                Err(FunctionError::BadType{ mfa: this_mfa() })
+            }
+        }))
         .insert(time_fn!(iso8601, _iso8601))
         .insert(tremor_const_fn!(datetime|format(_context, _datetime, _fmt) {
             if let (Some(datetime), Some(fmt)) = (_datetime.as_u64(), _fmt.as_str()) {
@@ -97,6 +109,27 @@ pub fn _iso8601(datetime: u64) -> String {
     _format(datetime, "%Y-%m-%dT%H:%M:%S%.9f+00:00", false)
 }
 
+/// Parses an RFC3339 timestamp, e.g. `2019-06-17T13:15:40.752+02:00`, into nanoseconds since the epoch
+pub fn _parse_rfc3339(input: &str) -> Result<i64, String> {
+    let datetime = DateTime::parse_from_rfc3339(input)
+        .map_err(|e| format!("Cannot parse {input} as RFC3339 timestamp: {e}"))?;
+    datetime
+        .timestamp()
+        .checked_mul(1_000_000_000)
+        .and_then(|nanos| nanos.checked_add(i64::from(datetime.timestamp_subsec_nanos())))
+        .ok_or_else(|| format!("The RFC3339 timestamp {input} is out of range"))
+}
+
+/// Formats nanoseconds since the epoch as RFC3339 timestamp in UTC, with as many fractional digits as needed
+pub fn _format_rfc3339(value: i64) -> Result<String, String> {
+    NaiveDateTime::from_timestamp_opt(
+        value.div_euclid(1_000_000_000),
+        value.rem_euclid(1_000_000_000) as u32,
+    )
+    .map(|naive| DateTime::<Utc>::from_utc(naive, Utc).to_rfc3339_opts(SecondsFormat::AutoSi, true))
+    .ok_or_else(|| format!("The timestamp {value} is out of range"))
+}
+
 pub fn _format(value: u64, fmt: &str, has_timezone: bool) -> String {
     if has_timezone {
         format!(
@@ -281,6 +314,60 @@ mod tests {
         assert_eq!(output, 1_560_777_212_000_000_000);
     }
 
+    #[test]
+    pub fn parse_rfc3339() {
+        assert_eq!(
+            _parse_rfc3339("2019-06-17T13:15:40Z"),
+            Ok(1_560_777_340_000_000_000)
+        );
+        // offsets
+        assert_eq!(
+            _parse_rfc3339("2019-06-17T15:15:40+02:00"),
+            Ok(1_560_777_340_000_000_000)
+        );
+        assert_eq!(
+            _parse_rfc3339("2019-06-17T08:45:40-04:30"),
+            Ok(1_560_777_340_000_000_000)
+        );
+        // fractional seconds
+        assert_eq!(
+            _parse_rfc3339("2019-06-17T13:15:40.752Z"),
+            Ok(1_560_777_340_752_000_000)
+        );
+        assert_eq!(
+            _parse_rfc3339("2019-06-17T13:15:40.123456789+00:00"),
+            Ok(1_560_777_340_123_456_789)
+        );
+        // before the epoch
+        assert_eq!(_parse_rfc3339("1969-12-31T23:59:59.5Z"), Ok(-500_000_000));
+        // invalid
+        assert!(_parse_rfc3339("2019-06-17 13:15:40").is_err());
+        assert!(_parse_rfc3339("2019-06-17T13:15:40").is_err());
+        assert!(_parse_rfc3339("snot").is_err());
+        assert!(_parse_rfc3339("9999-12-31T23:59:59Z").is_err());
+    }
+
+    #[test]
+    pub fn format_rfc3339() {
+        assert_eq!(
+            _format_rfc3339(1_560_777_340_000_000_000),
+            Ok("2019-06-17T13:15:40Z".to_string())
+        );
+        assert_eq!(
+            _format_rfc3339(1_560_777_340_752_000_000),
+            Ok("2019-06-17T13:15:40.752Z".to_string())
+        );
+        assert_eq!(
+            _format_rfc3339(-500_000_000),
+            Ok("1969-12-31T23:59:59.500Z".to_string())
+        );
+        let nanos = 1_560_777_340_123_456_789;
+        assert_eq!(
+            _format_rfc3339(nanos).and_then(|s| _parse_rfc3339(&s)),
+            Ok(nanos)
+        );
+    }
+
     #[test]
     pub fn format_timestamp() {
         let val = 419_083_754_274_000_000;
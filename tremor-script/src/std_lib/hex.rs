@@ -0,0 +1,71 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::bytes_or_string;
+use crate::registry::Registry;
+use crate::{tremor_const_fn, tremor_fn_};
+
+pub fn load(registry: &mut Registry) {
+    registry
+        .insert(tremor_const_fn! (hex|encode(_context, input) {
+            bytes_or_string(input)
+                .map(|input| Value::from(hex::encode(input)))
+                .ok_or_else(|| to_runtime_error("can only encode a binary or a string"))
+        }))
+        .insert(tremor_const_fn! (hex|decode(_context, input) {
+            let input = bytes_or_string(input).ok_or_else(|| to_runtime_error("can only decode a string or a binary"))?;
+            hex::decode(input).map(|v| Value::Bytes(v.into())).map_err(|e| to_runtime_error(format!("invalid hex: {e}")))
+        }));
+}
+
+#[cfg(test)]
+mod test {
+    use crate::registry::fun;
+    use crate::Value;
+
+    #[test]
+    fn encode() {
+        let f = fun("hex", "encode");
+        let v = Value::Bytes(vec![0_u8, 251, 255, 42].into());
+        assert_val!(f(&[&v]), Value::from("00fbff2a"));
+        let v = Value::from("snot");
+        assert_val!(f(&[&v]), Value::from("736e6f74"));
+    }
+
+    #[test]
+    fn round_trip() {
+        let encode = fun("hex", "encode");
+        let decode = fun("hex", "decode");
+        let v = Value::Bytes("snot badger".as_bytes().into());
+        let encoded = encode(&[&v]).expect("encodes");
+        assert_val!(decode(&[&encoded]), v);
+        // upper case digits decode just as well
+        assert_val!(
+            decode(&[&Value::from("00FBFF2A")]),
+            Value::Bytes(vec![0_u8, 251, 255, 42].into())
+        );
+    }
+
+    #[test]
+    fn invalid_input() {
+        let decode = fun("hex", "decode");
+        assert!(decode(&[&Value::from("snot")]).is_err());
+        // odd number of digits
+        assert!(decode(&[&Value::from("abc")]).is_err());
+        assert!(decode(&[&Value::from(42)]).is_err());
+
+        let encode = fun("hex", "encode");
+        assert!(encode(&[&Value::from(true)]).is_err());
+    }
+}
@@ -12,10 +12,156 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use crate::prelude::*;
 use crate::registry::Registry;
 use crate::tremor_const_fn;
+use crate::Value;
 use tremor_value::parse_to_value;
 
+/// Splits a JSON Pointer (RFC 6901) into its unescaped reference tokens
+fn pointer(path: &str) -> Result<Vec<String>, String> {
+    if path.is_empty() {
+        return Ok(Vec::new());
+    }
+    let tokens = path
+        .strip_prefix('/')
+        .ok_or_else(|| format!("invalid JSON pointer `{path}`"))?;
+    Ok(tokens
+        .split('/')
+        .map(|token| token.replace("~1", "/").replace("~0", "~"))
+        .collect())
+}
+
+/// The array index `token` refers to, `len` is allowed if `append` is set,
+/// as `-` refers to the position after the last element
+fn index(token: &str, len: usize, append: bool) -> Result<usize, String> {
+    if token == "-" && append {
+        return Ok(len);
+    }
+    let idx = if token == "0" || !token.starts_with('0') {
+        token.parse::<usize>().ok()
+    } else {
+        None
+    };
+    match idx {
+        Some(idx) if idx < len || (append && idx == len) => Ok(idx),
+        _ => Err(format!("invalid array index `{token}`")),
+    }
+}
+
+fn get<'v, 'event>(doc: &'v Value<'event>, path: &[String]) -> Result<&'v Value<'event>, String> {
+    path.iter().try_fold(doc, |v, token| match v {
+        Value::Object(o) => o
+            .get(token.as_str())
+            .ok_or_else(|| format!("no member `{token}`")),
+        Value::Array(a) => Ok(&a[index(token, a.len(), false)?]),
+        _ => Err(format!("`{token}` does not refer to a record or an array")),
+    })
+}
+
+fn get_mut<'v, 'event>(
+    doc: &'v mut Value<'event>,
+    path: &[String],
+) -> Result<&'v mut Value<'event>, String> {
+    path.iter().try_fold(doc, |v, token| match v {
+        Value::Object(o) => o
+            .get_mut(token.as_str())
+            .ok_or_else(|| format!("no member `{token}`")),
+        Value::Array(a) => {
+            let idx = index(token, a.len(), false)?;
+            Ok(&mut a[idx])
+        }
+        _ => Err(format!("`{token}` does not refer to a record or an array")),
+    })
+}
+
+fn add<'event>(
+    doc: &mut Value<'event>,
+    path: &[String],
+    value: Value<'event>,
+) -> Result<(), String> {
+    if let Some((last, parent)) = path.split_last() {
+        match get_mut(doc, parent)? {
+            Value::Object(o) => {
+                o.insert(last.clone().into(), value);
+            }
+            Value::Array(a) => {
+                let idx = index(last, a.len(), true)?;
+                a.insert(idx, value);
+            }
+            _ => {
+                return Err(format!(
+                    "the parent of `{last}` is not a record or an array"
+                ))
+            }
+        }
+    } else {
+        *doc = value;
+    }
+    Ok(())
+}
+
+fn remove<'event>(doc: &mut Value<'event>, path: &[String]) -> Result<Value<'event>, String> {
+    let (last, parent) = path
+        .split_last()
+        .ok_or_else(|| "the document root can not be removed".to_string())?;
+    match get_mut(doc, parent)? {
+        Value::Object(o) => o
+            .remove(last.as_str())
+            .ok_or_else(|| format!("no member `{last}`")),
+        Value::Array(a) => {
+            let idx = index(last, a.len(), false)?;
+            Ok(a.remove(idx))
+        }
+        _ => Err(format!(
+            "the parent of `{last}` is not a record or an array"
+        )),
+    }
+}
+
+/// Applies a single JSON Patch (RFC 6902) operation to `doc`
+fn apply<'event>(doc: &mut Value<'event>, op: &Value<'event>) -> Result<(), String> {
+    let name = op.get_str("op").ok_or("missing `op`")?;
+    let path = pointer(op.get_str("path").ok_or("missing `path`")?)?;
+    let value = || op.get("value").cloned().ok_or("missing `value`");
+    let from = || {
+        op.get_str("from")
+            .ok_or_else(|| "missing `from`".to_string())
+            .and_then(pointer)
+    };
+    match name {
+        "add" => add(doc, &path, value()?),
+        "remove" => remove(doc, &path).map(|_| ()),
+        "replace" => {
+            *get_mut(doc, &path)? = value()?;
+            Ok(())
+        }
+        "move" => {
+            let from = from()?;
+            if path.len() > from.len() && path.starts_with(&from) {
+                return Err("a value can not be moved into one of its children".to_string());
+            }
+            let moved = remove(doc, &from)?;
+            add(doc, &path, moved)
+        }
+        "copy" => {
+            let copied = get(doc, &from()?)?.clone();
+            add(doc, &path, copied)
+        }
+        "test" => {
+            if *get(doc, &path)? == value()? {
+                Ok(())
+            } else {
+                Err(format!(
+                    "test failed for `{}`",
+                    op.get_str("path").unwrap_or_default()
+                ))
+            }
+        }
+        other => Err(format!("unknown op `{other}`")),
+    }
+}
+
 pub fn load(registry: &mut Registry) {
     registry
         .insert(tremor_const_fn! (json|decode(_context, _input: String) {
@@ -32,6 +178,17 @@ pub fn load(registry: &mut Registry) {
         }))
         .insert(tremor_const_fn! (json|encode_pretty(_context, _input) {
             simd_json::to_string_pretty(_input).map(Value::from).map_err(to_runtime_error)
+        }))
+        .insert(tremor_const_fn! (json|patch(_context, _target, _patch) {
+            if let Some(ops) = _patch.as_array() {
+                let mut doc = (*_target).clone();
+                for (i, op) in ops.iter().enumerate() {
+                    apply(&mut doc, op).map_err(|e| to_runtime_error(format!("operation {i}: {e}")))?;
+                }
+                Ok(doc)
+            } else {
+                Err(FunctionError::BadType{mfa: this_mfa()})
+            }
         }));
 }
 
@@ -39,6 +196,7 @@ pub fn load(registry: &mut Registry) {
 mod test {
     use crate::registry::fun;
     use crate::Value;
+    use tremor_value::literal;
 
     #[test]
     fn decode() {
@@ -68,4 +226,66 @@ mod test {
             )
         );
     }
+
+    #[test]
+    fn patch_add() {
+        let f = fun("json", "patch");
+        let v = literal!({"a": [1, 3]});
+        let p = literal!([
+            {"op": "add", "path": "/b", "value": "snot"},
+            {"op": "add", "path": "/a/1", "value": 2},
+            {"op": "add", "path": "/a/-", "value": 4}
+        ]);
+        assert_val!(f(&[&v, &p]), literal!({"a": [1, 2, 3, 4], "b": "snot"}));
+    }
+    #[test]
+    fn patch_remove() {
+        let f = fun("json", "patch");
+        let v = literal!({"a": [1, 2], "b~/c": "snot"});
+        let p = literal!([
+            {"op": "remove", "path": "/a/0"},
+            {"op": "remove", "path": "/b~0~1c"}
+        ]);
+        assert_val!(f(&[&v, &p]), literal!({"a": [2]}));
+    }
+    #[test]
+    fn patch_replace() {
+        let f = fun("json", "patch");
+        let v = literal!({"a": {"b": 1}});
+        let p = literal!([{"op": "replace", "path": "/a/b", "value": "badger"}]);
+        assert_val!(f(&[&v, &p]), literal!({"a": {"b": "badger"}}));
+        let p = literal!([{"op": "replace", "path": "/a/c", "value": "badger"}]);
+        assert!(f(&[&v, &p]).is_err());
+    }
+    #[test]
+    fn patch_move() {
+        let f = fun("json", "patch");
+        let v = literal!({"a": {"b": 1}, "c": []});
+        let p = literal!([{"op": "move", "from": "/a/b", "path": "/c/0"}]);
+        assert_val!(f(&[&v, &p]), literal!({"a": {}, "c": [1]}));
+        let p = literal!([{"op": "move", "from": "/a", "path": "/a/b"}]);
+        assert!(f(&[&v, &p]).is_err());
+    }
+    #[test]
+    fn patch_copy() {
+        let f = fun("json", "patch");
+        let v = literal!({"a": {"b": 1}});
+        let p = literal!([{"op": "copy", "from": "/a", "path": "/c"}]);
+        assert_val!(f(&[&v, &p]), literal!({"a": {"b": 1}, "c": {"b": 1}}));
+    }
+    #[test]
+    fn patch_test() {
+        let f = fun("json", "patch");
+        let v = literal!({"a": [1, 2]});
+        let p = literal!([
+            {"op": "test", "path": "/a", "value": [1, 2]},
+            {"op": "add", "path": "/b", "value": true}
+        ]);
+        assert_val!(f(&[&v, &p]), literal!({"a": [1, 2], "b": true}));
+        let p = literal!([
+            {"op": "test", "path": "/a/1", "value": 3},
+            {"op": "add", "path": "/b", "value": true}
+        ]);
+        assert!(f(&[&v, &p]).is_err());
+    }
 }